@@ -0,0 +1,365 @@
+//! Shared plumbing for the YPBank command-line tools.
+//!
+//! Every binary in the workspace reads batches from a path or stdin, writes
+//! them to a path or stdout, and reports errors the same way. Keeping that
+//! logic here gives new tools consistent behaviour:
+//!
+//! - `-` as an input path means stdin; a missing or `-` output path means stdout.
+//! - Formats can be guessed from the file extension when not given.
+//! - Errors are printed to stderr as `<tool>: error: <message>`, followed by
+//!   one `caused by:` line per underlying error.
+//! - Exit codes are [`exit::SUCCESS`], [`exit::FAILURE`] (the tool ran but
+//!   the answer is negative, e.g. files differ) and [`exit::ERROR`].
+
+use anyhow::{Context, Result};
+use parser::formats::csv::parse_csv_with_profile;
+use parser::formats::profile::BankProfile;
+use parser::validate::{KnownCurrency, ValidationReport, Validator};
+use parser::{Format, ParseOptions, ParseReport, TransactionBatch, WriteOptions};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+/// Process exit codes shared by all tools.
+pub mod exit {
+    /// The command succeeded
+    pub const SUCCESS: u8 = 0;
+    /// The command ran, but its check failed (files differ, verification failed)
+    pub const FAILURE: u8 = 1;
+    /// The command could not run: bad arguments, unreadable input, I/O errors
+    pub const ERROR: u8 = 2;
+}
+
+/// Result of a command that ran to completion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Everything checked out
+    Success,
+    /// The command's check failed; maps to [`exit::FAILURE`]
+    Failure,
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Success => ExitCode::from(exit::SUCCESS),
+            Outcome::Failure => ExitCode::from(exit::FAILURE),
+        }
+    }
+}
+
+/// Runs a command body, rendering any error and mapping the result to an exit code.
+///
+/// # Arguments
+///
+/// * `name` - The tool name used as the prefix of error messages
+/// * `body` - The command to run
+///
+/// # Examples
+///
+/// ```no_run
+/// use cli_common::Outcome;
+/// use std::process::ExitCode;
+///
+/// fn main() -> ExitCode {
+///     cli_common::run("ypbank_example", || Ok(Outcome::Success))
+/// }
+/// ```
+pub fn run<F>(name: &str, body: F) -> ExitCode
+where
+    F: FnOnce() -> Result<Outcome>,
+{
+    match body() {
+        Ok(outcome) => outcome.into(),
+        Err(error) => {
+            eprint!("{}", render_error(name, &error));
+            ExitCode::from(exit::ERROR)
+        }
+    }
+}
+
+/// Formats an error and its chain of causes, one per line.
+///
+/// Causes whose message is already part of the previous line (as with
+/// `#[error("I/O error: {0}")]` wrappers) are skipped.
+pub fn render_error(name: &str, error: &anyhow::Error) -> String {
+    let mut rendered = format!("{}: error: {}\n", name, error);
+    let mut previous = error.to_string();
+    for cause in error.chain().skip(1) {
+        let message = cause.to_string();
+        if !previous.contains(&message) {
+            rendered.push_str(&format!("  caused by: {}\n", message));
+        }
+        previous = message;
+    }
+    rendered
+}
+
+/// Parses a batch from `path`, or from stdin when `path` is `-`.
+///
+/// Any location accepted by [`parser::parse_path`] works, including URLs when
+/// the corresponding parser features are enabled.
+pub fn read_batch(path: &str, format: Format) -> Result<TransactionBatch> {
+    read_batch_with(path, format, &ParseOptions::default())
+}
+
+/// Parses a batch from `path`, or from stdin when `path` is `-`, with the
+/// given options.
+pub fn read_batch_with(
+    path: &str,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    if path == "-" {
+        let stdin = io::stdin();
+        parser::parse_with(BufReader::new(stdin.lock()), format, options)
+            .context("Failed to parse from stdin")
+    } else {
+        parser::parse_path_with(path, format, options)
+            .with_context(|| format!("Failed to parse file: {}", path))
+    }
+}
+
+/// Parses a bank's CSV export described by `profile` from `path`, or from
+/// stdin when `path` is `-`, as
+/// [`parser::formats::csv::parse_csv_with_profile`] does.
+///
+/// Only local files and stdin are supported.
+pub fn read_batch_with_profile(
+    path: &str,
+    profile: &BankProfile,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    if path == "-" {
+        let stdin = io::stdin();
+        parse_csv_with_profile(BufReader::new(stdin.lock()), profile, options)
+            .context("Failed to parse from stdin")
+    } else {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {}", path))?;
+        parse_csv_with_profile(BufReader::new(file), profile, options)
+            .with_context(|| format!("Failed to parse file: {}", path))
+    }
+}
+
+/// Parses `path`, or stdin when `path` is `-`, skipping malformed records
+/// as [`parser::parse_report`] does.
+///
+/// Only local files and stdin are supported.
+pub fn read_report(path: &str, format: Format, options: &ParseOptions) -> Result<ParseReport> {
+    if path == "-" {
+        let stdin = io::stdin();
+        parser::parse_report(BufReader::new(stdin.lock()), format, options)
+            .context("Failed to parse from stdin")
+    } else {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {}", path))?;
+        parser::parse_report(BufReader::new(file), format, options)
+            .with_context(|| format!("Failed to parse file: {}", path))
+    }
+}
+
+/// Checks `path`, or stdin when `path` is `-`, and returns every error found,
+/// as [`parser::validate_input`] does.
+///
+/// Only local files and stdin are supported; failing to open the file is
+/// an error of its own rather than part of the list.
+pub fn validate(path: &str, format: Format, options: &ParseOptions) -> Result<Vec<parser::Error>> {
+    if path == "-" {
+        let stdin = BufReader::new(io::stdin().lock());
+        Ok(parser::validate_input(stdin, format, options))
+    } else {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {}", path))?;
+        Ok(parser::validate_input(BufReader::new(file), format, options))
+    }
+}
+
+/// Like [`validate`], and also returns what the built-in
+/// [`parser::validate::Validator`] rules find in the records that parsed.
+pub fn validate_with_rules(
+    path: &str,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<(Vec<parser::Error>, ValidationReport)> {
+    let report = if path == "-" {
+        let stdin = BufReader::new(io::stdin().lock());
+        parser::parse_report(stdin, format, options)
+    } else {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {}", path))?;
+        parser::parse_report(BufReader::new(file), format, options)
+    };
+    Ok(match report {
+        Ok(mut report) => {
+            let errors = std::mem::take(&mut report.errors);
+            let validator = Validator::default().rule(KnownCurrency {
+                custom: options.custom_currencies.clone(),
+            });
+            (errors, validator.validate(&report.into_batch()))
+        }
+        Err(error) => (vec![error], ValidationReport::default()),
+    })
+}
+
+/// Prints the skipped records and warnings of a report to stderr, as
+/// `<tool>: skipped: <error>` and `<tool>: warning: <message>` lines.
+pub fn print_report(name: &str, report: &ParseReport) {
+    for error in &report.errors {
+        eprintln!("{}: skipped: {}", name, error);
+    }
+    for warning in &report.warnings {
+        eprintln!("{}: warning: {}", name, warning);
+    }
+}
+
+/// Writes a batch to `path`, or to stdout when `path` is `None` or `-`.
+pub fn write_batch(
+    batch: &TransactionBatch,
+    path: Option<&str>,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<()> {
+    match path {
+        Some(path) if path != "-" => parser::write_path_with(batch, path, format, options)
+            .with_context(|| format!("Failed to write output file: {}", path)),
+        _ => {
+            let stdout = io::stdout();
+            let mut writer = BufWriter::new(stdout.lock());
+            parser::write_with(batch, &mut writer, format, options)
+                .context("Failed to write output")?;
+            writer.flush().context("Failed to write output")
+        }
+    }
+}
+
+/// Converts the transactions of `input`, or of stdin when `input` is `-`, to
+/// `output`, or to stdout when `output` is `None` or `-`, as
+/// [`parser::convert_with`] does: one record at a time for CSV input.
+///
+/// Only local files and the standard streams are supported; use
+/// [`read_batch_with`] and [`write_batch`] for other locations.
+pub fn convert(
+    input: &str,
+    from: Format,
+    output: Option<&str>,
+    to: Format,
+    parse_options: &ParseOptions,
+    write_options: &WriteOptions,
+) -> Result<usize> {
+    let reader: Box<dyn io::Read> = if input == "-" {
+        Box::new(BufReader::new(io::stdin().lock()))
+    } else {
+        let file = File::open(input).with_context(|| format!("Failed to open file: {}", input))?;
+        Box::new(BufReader::new(file))
+    };
+    let (mut writer, name): (Box<dyn Write>, _) = match output {
+        Some(path) if path != "-" => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create output file: {}", path))?;
+            (Box::new(BufWriter::new(file)), path)
+        }
+        _ => (Box::new(BufWriter::new(io::stdout().lock())), "stdout"),
+    };
+    let count = parser::convert_with(reader, from, &mut writer, to, parse_options, write_options)
+        .with_context(|| format!("Failed to convert {} to {}", input, name))?;
+    writer.flush().context("Failed to write output")?;
+    Ok(count)
+}
+
+/// Fails if a declared closing balance of `batch` doesn't match its
+/// transactions, describing every mismatching currency.
+pub fn ensure_balanced(batch: &TransactionBatch) -> Result<()> {
+    let discrepancies = parser::balance::balance_discrepancies(batch);
+    if discrepancies.is_empty() {
+        return Ok(());
+    }
+
+    let details: Vec<String> = discrepancies
+        .iter()
+        .map(|check| {
+            format!(
+                "{}: opening {} + credits {} - debits {} = {}, but the closing balance is {} (off by {})",
+                check.currency,
+                check.opening,
+                check.credits,
+                check.debits,
+                check.expected_closing(),
+                check.closing,
+                check.discrepancy()
+            )
+        })
+        .collect();
+    anyhow::bail!("Closing balance mismatch: {}", details.join("; "))
+}
+
+/// Returns `format` if given, otherwise guesses it from the extension of `path`.
+///
+/// # Errors
+///
+/// Returns an error if no format is given and the extension is missing or unknown.
+pub fn resolve_format(path: &str, format: Option<Format>) -> Result<Format> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(Format::from_extension)
+        .with_context(|| {
+            format!(
+                "Cannot guess format of '{}', pass the format explicitly",
+                path
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_format() {
+        assert_eq!(resolve_format("a.TXT", None).unwrap(), Format::Text);
+        assert_eq!(
+            resolve_format("a.txt", Some(Format::Csv)).unwrap(),
+            Format::Csv
+        );
+        assert!(resolve_format("statement", None).is_err());
+    }
+
+    #[test]
+    fn test_render_error_includes_causes() {
+        let error = anyhow::anyhow!("disk on fire").context("Failed to parse file: a.csv");
+
+        assert_eq!(
+            render_error("ypbank_tool", &error),
+            "ypbank_tool: error: Failed to parse file: a.csv\n  caused by: disk on fire\n"
+        );
+    }
+
+    #[test]
+    fn test_ensure_balanced() {
+        let mut batch = TransactionBatch::default();
+        assert!(ensure_balanced(&batch).is_ok());
+
+        batch.balances.push(parser::Balance {
+            currency: "USD".to_string(),
+            opening: 10.into(),
+            closing: 12.into(),
+        });
+        let error = ensure_balanced(&batch).unwrap_err().to_string();
+        assert!(error.contains("USD: opening 10 + credits 0 - debits 0 = 10"));
+        assert!(error.ends_with("(off by 2)"));
+    }
+
+    #[test]
+    fn test_write_then_read_batch() {
+        let path = std::env::temp_dir().join(format!("cli-common-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap();
+        let batch = TransactionBatch::default();
+
+        write_batch(&batch, Some(path), Format::Csv, &WriteOptions::default()).unwrap();
+        let read = read_batch(path, Format::Csv).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(read.transactions.len(), 0);
+    }
+}
@@ -1,9 +1,11 @@
-use anyhow::{Context, Result};
+use anyhow::Context;
 use clap::Parser as ClapParser;
-use parser::{Format, Transaction, TransactionBatch};
-use std::fs::File;
-use std::io::BufReader;
-use std::process;
+use cli_common::Outcome;
+use parser::diff::{diff_batches, DiffOptions, DiffReport};
+use parser::reconcile::{reconcile, MatchKind, ReconcileOptions};
+use parser::{Format, TransactionBatch};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(ClapParser)]
 #[command(name = "ypbank_compare")]
@@ -20,126 +22,150 @@ struct Args {
 
     #[arg(long = "format2", help = "Second file format")]
     format2: Format,
+
+    #[arg(
+        long = "ignore-order",
+        help = "Sort both files into canonical order before comparing"
+    )]
+    ignore_order: bool,
+
+    #[arg(
+        long = "reconcile",
+        help = "Match transactions by ID, then by amount, date and description, instead of by position"
+    )]
+    reconcile: bool,
+
+    #[arg(
+        long = "date-tolerance",
+        value_name = "DAYS",
+        default_value_t = 3,
+        requires = "reconcile",
+        help = "How many days apart the dates of reconciled transactions may be"
+    )]
+    date_tolerance: u32,
+
+    #[arg(
+        long = "patch",
+        value_name = "FILE",
+        conflicts_with = "reconcile",
+        help = "Write the changes from the first file to the second as a JSON patch"
+    )]
+    patch: Option<PathBuf>,
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
-
-    let file1 = File::open(&args.file1)
-        .with_context(|| format!("Failed to open file1: {}", args.file1))?;
-    let reader1 = BufReader::new(file1);
-    let batch1 = parser::parse(reader1, args.format1)
-        .with_context(|| format!("Failed to parse file1: {}", args.file1))?;
-
-    let file2 = File::open(&args.file2)
-        .with_context(|| format!("Failed to open file2: {}", args.file2))?;
-    let reader2 = BufReader::new(file2);
-    let batch2 = parser::parse(reader2, args.format2)
-        .with_context(|| format!("Failed to parse file2: {}", args.file2))?;
-
-    compare_batches(&batch1, &batch2, &args.file1, &args.file2)?;
-
-    Ok(())
+    cli_common::run("ypbank_compare", || {
+        let batch1 = cli_common::read_batch(&args.file1, args.format1)?;
+        let batch2 = cli_common::read_batch(&args.file2, args.format2)?;
+
+        if args.reconcile {
+            let options = ReconcileOptions {
+                date_tolerance: args.date_tolerance,
+                ..ReconcileOptions::default()
+            };
+            return Ok(reconcile_batches(
+                &batch1,
+                &batch2,
+                &options,
+                &args.file1,
+                &args.file2,
+            ));
+        }
+        let options = DiffOptions {
+            ignore_order: args.ignore_order,
+            ..DiffOptions::default()
+        };
+        let report = diff_batches(&batch1, &batch2, &options);
+        if let Some(path) = &args.patch {
+            // Paired by ID, an inserted record is one addition rather than
+            // an update of every record after it
+            let options = DiffOptions {
+                by_id: true,
+                ..options
+            };
+            let patch = diff_batches(&batch1, &batch2, &options).to_patch();
+            let patch = serde_json::to_string_pretty(&patch)?;
+            std::fs::write(path, patch + "\n")
+                .with_context(|| format!("Failed to write patch file: {}", path.display()))?;
+        }
+        Ok(compare_batches(&report, &args.file1, &args.file2))
+    })
 }
 
-fn compare_batches(
-    batch1: &TransactionBatch,
-    batch2: &TransactionBatch,
-    file1_name: &str,
-    file2_name: &str,
-) -> Result<()> {
-    if batch1.transactions.len() != batch2.transactions.len() {
+fn compare_batches(report: &DiffReport, file1_name: &str, file2_name: &str) -> Outcome {
+    if report.left_count != report.right_count {
         println!(
             "The files have different number of transactions: {} vs {}",
-            batch1.transactions.len(),
-            batch2.transactions.len()
+            report.left_count, report.right_count
         );
-        process::exit(1);
+        return Outcome::Failure;
     }
 
-    let mut has_differences = false;
-
-    for (i, (tx1, tx2)) in batch1
-        .transactions
-        .iter()
-        .zip(batch2.transactions.iter())
-        .enumerate()
-    {
-        if !transactions_equal(tx1, tx2) {
-            if !has_differences {
-                println!(
-                    "The transaction records in '{}' and '{}' differ:",
-                    file1_name, file2_name
-                );
-                has_differences = true;
-            }
-            println!("\nTransaction #{} (ID: {}):", i + 1, tx1.id);
-            print_transaction_diff(tx1, tx2);
-        }
-    }
-
-    if !has_differences {
+    if !report.changed.is_empty() {
         println!(
-            "The transaction records in '{}' and '{}' are identical.",
+            "The transaction records in '{}' and '{}' differ:",
             file1_name, file2_name
         );
-    } else {
-        process::exit(1);
+        for diff in &report.changed {
+            println!("\nTransaction #{} (ID: {}):", diff.index + 1, diff.id);
+            for change in &diff.changes {
+                println!("  {}", change);
+            }
+        }
+        return Outcome::Failure;
     }
 
-    Ok(())
+    println!(
+        "The transaction records in '{}' and '{}' are identical.",
+        file1_name, file2_name
+    );
+    Outcome::Success
 }
 
-fn transactions_equal(tx1: &Transaction, tx2: &Transaction) -> bool {
-    tx1.id == tx2.id
-        && tx1.posted_at == tx2.posted_at
-        && tx1.executed_at == tx2.executed_at
-        && tx1.kind == tx2.kind
-        && tx1.amount == tx2.amount
-        && tx1.description == tx2.description
-        && tx1.account == tx2.account
-        && tx1.counterparty == tx2.counterparty
-        && tx1.category == tx2.category
-}
-
-fn print_transaction_diff(tx1: &Transaction, tx2: &Transaction) {
-    if tx1.id != tx2.id {
-        println!("  ID: '{}' vs '{}'", tx1.id, tx2.id);
-    }
-    if tx1.posted_at != tx2.posted_at {
-        println!("  Posted Date: {} vs {}", tx1.posted_at, tx2.posted_at);
-    }
-    if tx1.executed_at != tx2.executed_at {
-        println!(
-            "  Executed Date: {:?} vs {:?}",
-            tx1.executed_at, tx2.executed_at
-        );
-    }
-    if tx1.kind != tx2.kind {
-        println!("  Kind: {:?} vs {:?}", tx1.kind, tx2.kind);
-    }
-    if tx1.amount != tx2.amount {
-        println!(
-            "  Amount: {} {} vs {} {}",
-            tx1.amount.amount, tx1.amount.currency, tx2.amount.amount, tx2.amount.currency
-        );
-    }
-    if tx1.description != tx2.description {
-        println!(
-            "  Description: '{}' vs '{}'",
-            tx1.description, tx2.description
-        );
-    }
-    if tx1.account != tx2.account {
-        println!("  Account: {:?} vs {:?}", tx1.account, tx2.account);
-    }
-    if tx1.counterparty != tx2.counterparty {
-        println!(
-            "  Counterparty: {:?} vs {:?}",
-            tx1.counterparty, tx2.counterparty
-        );
+fn reconcile_batches(
+    batch1: &TransactionBatch,
+    batch2: &TransactionBatch,
+    options: &ReconcileOptions,
+    file1_name: &str,
+    file2_name: &str,
+) -> Outcome {
+    let result = reconcile(batch1, batch2, options);
+    let fuzzy = result
+        .matched
+        .iter()
+        .filter(|m| matches!(m.kind, MatchKind::Fuzzy { .. }))
+        .count();
+    println!(
+        "Matched {} transactions ({} by ID and amount, {} by amount, date and description).",
+        result.matched.len(),
+        result.matched.len() - fuzzy,
+        fuzzy
+    );
+
+    for (name, unmatched) in [
+        (file1_name, &result.unmatched_left),
+        (file2_name, &result.unmatched_right),
+    ] {
+        if unmatched.is_empty() {
+            continue;
+        }
+        println!("\nUnmatched in '{}':", name);
+        for tx in unmatched {
+            println!(
+                "  {} {} {} {} {}",
+                tx.posted_at,
+                tx.id,
+                tx.signed_amount(),
+                tx.amount.currency,
+                tx.description
+            );
+        }
     }
-    if tx1.category != tx2.category {
-        println!("  Category: {:?} vs {:?}", tx1.category, tx2.category);
+
+    if result.is_complete() {
+        Outcome::Success
+    } else {
+        Outcome::Failure
     }
 }
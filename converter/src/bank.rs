@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use clap::builder::PossibleValue;
+use clap::{Args, ValueEnum};
+use parser::formats::beancount::{self, AccountMapping};
+use parser::formats::camt::parse_camt;
+use parser::formats::fixed_width::{parse_fixed_width, write_fixed_width, Layout};
+use parser::formats::gnucash;
+use parser::formats::ledger;
+use parser::formats::mt940::parse_mt940;
+use parser::formats::open_banking::parse_open_banking;
+use parser::formats::qif::{parse_qif, write_qif};
+use parser::formats::ynab::parse_ynab;
+use parser::{Format, ParseOptions, TransactionBatch};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[derive(Args)]
+pub struct BankArgs {
+    #[arg(
+        long,
+        value_name = "CODE",
+        help = "Currency of every transaction of qif and ynab input, which carries none"
+    )]
+    pub currency: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "YAML or JSON layout of fixed-width input or output"
+    )]
+    pub layout: Option<PathBuf>,
+
+    #[arg(
+        long = "asset-account",
+        value_name = "ACCOUNT",
+        help = "Account the statement belongs to in beancount, ledger and gnucash output (default: Assets:Bank)"
+    )]
+    pub asset_account: Option<String>,
+
+    #[arg(
+        long = "category-account",
+        value_name = "CATEGORY=ACCOUNT",
+        value_parser = parse_category_account,
+        help = "Book a category to an account in beancount, ledger and gnucash output (repeatable), e.g. 'Groceries=Expenses:Food'"
+    )]
+    pub category_accounts: Vec<(String, String)>,
+}
+
+/// An `--input-format`: a [`Format`], or a bank format read by its importer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Batch(Format),
+    Qif,
+    Ynab,
+    Mt940,
+    Camt,
+    OpenBanking,
+    FixedWidth,
+}
+
+impl InputFormat {
+    /// Returns the [`Format`] of batch formats, and `None` for bank formats.
+    pub fn batch(self) -> Option<Format> {
+        match self {
+            Self::Batch(format) => Some(format),
+            _ => None,
+        }
+    }
+}
+
+impl ValueEnum for InputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        static VARIANTS: OnceLock<Vec<InputFormat>> = OnceLock::new();
+        VARIANTS.get_or_init(|| {
+            let banks = [
+                Self::Qif,
+                Self::Ynab,
+                Self::Mt940,
+                Self::Camt,
+                Self::OpenBanking,
+                Self::FixedWidth,
+            ];
+            let formats = Format::value_variants().iter().copied().map(Self::Batch);
+            formats.chain(banks).collect()
+        })
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        let (name, help) = match self {
+            Self::Batch(format) => return format.to_possible_value(),
+            Self::Qif => ("qif", "Quicken Interchange Format; needs --currency"),
+            Self::Ynab => ("ynab", "YNAB register export; needs --currency"),
+            Self::Mt940 => ("mt940", "SWIFT MT940 statement"),
+            Self::Camt => ("camt", "ISO 20022 CAMT.053 statement"),
+            Self::OpenBanking => ("open-banking", "UK Open Banking transactions response"),
+            Self::FixedWidth => ("fixed-width", "Fixed-width records; needs --layout"),
+        };
+        Some(PossibleValue::new(name).help(help))
+    }
+}
+
+/// An `--output-format`: a [`Format`], or a format written for another tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Batch(Format),
+    Qif,
+    FixedWidth,
+    Beancount,
+    Ledger,
+    Gnucash,
+}
+
+impl OutputFormat {
+    /// Returns the [`Format`] of batch formats, and `None` for the others.
+    pub fn batch(self) -> Option<Format> {
+        match self {
+            Self::Batch(format) => Some(format),
+            _ => None,
+        }
+    }
+}
+
+impl ValueEnum for OutputFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        static VARIANTS: OnceLock<Vec<OutputFormat>> = OnceLock::new();
+        VARIANTS.get_or_init(|| {
+            let others = [
+                Self::Qif,
+                Self::FixedWidth,
+                Self::Beancount,
+                Self::Ledger,
+                Self::Gnucash,
+            ];
+            let formats = Format::value_variants().iter().copied().map(Self::Batch);
+            formats.chain(others).collect()
+        })
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        let (name, help) = match self {
+            Self::Batch(format) => return format.to_possible_value(),
+            Self::Qif => ("qif", "Quicken Interchange Format"),
+            Self::FixedWidth => ("fixed-width", "Fixed-width records; needs --layout"),
+            Self::Beancount => ("beancount", "Beancount directives"),
+            Self::Ledger => ("ledger", "Ledger journal"),
+            Self::Gnucash => ("gnucash", "CSV for GnuCash's transaction import"),
+        };
+        Some(PossibleValue::new(name).help(help))
+    }
+}
+
+/// Reads a batch with one of the bank importers.
+type Importer<'a> = dyn FnOnce(Box<dyn Read>) -> parser::Result<TransactionBatch> + 'a;
+
+/// Writes a batch with one of the exporters for other tools.
+type Exporter<'a> = dyn FnOnce(&mut Box<dyn Write>) -> parser::Result<()> + 'a;
+
+/// Reads `path`, or stdin when `path` is `-`, with the importer of `format`.
+///
+/// Only local files and stdin are supported.
+pub fn read(
+    path: &str,
+    format: InputFormat,
+    args: &BankArgs,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let currency = || {
+        args.currency
+            .as_deref()
+            .context("--currency is required for qif and ynab input")
+    };
+    // Check the arguments of the importer before opening the input
+    let parse: Box<Importer> = match format {
+        InputFormat::Batch(format) => return cli_common::read_batch_with(path, format, options),
+        InputFormat::Qif => {
+            let currency = currency()?;
+            Box::new(move |reader| parse_qif(reader, currency, options))
+        }
+        InputFormat::Ynab => {
+            let currency = currency()?;
+            Box::new(move |reader| parse_ynab(reader, currency, options))
+        }
+        InputFormat::Mt940 => Box::new(|reader| parse_mt940(reader, options)),
+        InputFormat::Camt => Box::new(|reader| parse_camt(reader, options)),
+        InputFormat::OpenBanking => Box::new(|reader| parse_open_banking(reader, options)),
+        InputFormat::FixedWidth => {
+            let layout = load_layout(args)?;
+            Box::new(move |reader| parse_fixed_width(reader, &layout, options))
+        }
+    };
+    let reader: Box<dyn Read> = if path == "-" {
+        Box::new(BufReader::new(io::stdin().lock()))
+    } else {
+        let file = File::open(path).with_context(|| format!("Failed to open file: {}", path))?;
+        Box::new(BufReader::new(file))
+    };
+    parse(reader).with_context(|| format!("Failed to parse file: {}", path))
+}
+
+/// Writes `batch` in `format` to `path`, or to stdout when `path` is `None`
+/// or `-`. Batch formats go through [`cli_common::write_batch`] with
+/// `options`; the others don't take them.
+///
+/// Only local files and stdout are supported for the formats of other tools.
+pub fn write(
+    batch: &TransactionBatch,
+    path: Option<&str>,
+    format: OutputFormat,
+    args: &BankArgs,
+    options: &parser::WriteOptions,
+) -> Result<()> {
+    // Check the arguments of the exporter before creating the output
+    let export: Box<Exporter> = match format {
+        OutputFormat::Batch(format) => {
+            return cli_common::write_batch(batch, path, format, options)
+        }
+        OutputFormat::Qif => Box::new(|writer| write_qif(batch, writer)),
+        OutputFormat::FixedWidth => {
+            let layout = load_layout(args)?;
+            Box::new(move |writer| write_fixed_width(batch, writer, &layout))
+        }
+        OutputFormat::Beancount => {
+            let mapping = mapping(args);
+            Box::new(move |writer| beancount::write(batch, writer, &mapping))
+        }
+        OutputFormat::Ledger => {
+            let mapping = mapping(args);
+            Box::new(move |writer| ledger::write(batch, writer, &mapping))
+        }
+        OutputFormat::Gnucash => {
+            let mapping = mapping(args);
+            Box::new(move |writer| gnucash::write_csv(batch, writer, &mapping))
+        }
+    };
+    let (mut writer, name): (Box<dyn Write>, _) = match path {
+        Some(path) if path != "-" => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create output file: {}", path))?;
+            (Box::new(BufWriter::new(file)), path)
+        }
+        _ => (Box::new(BufWriter::new(io::stdout().lock())), "stdout"),
+    };
+    export(&mut writer).with_context(|| format!("Failed to write output file: {}", name))?;
+    writer.flush().context("Failed to write output")
+}
+
+fn load_layout(args: &BankArgs) -> Result<Layout> {
+    let path = args
+        .layout
+        .as_ref()
+        .context("--layout is required for fixed-width input and output")?;
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read layout: {}", path.display()))?;
+    Layout::from_yaml(&source).with_context(|| format!("Invalid layout: {}", path.display()))
+}
+
+fn mapping(args: &BankArgs) -> AccountMapping {
+    let mut mapping = AccountMapping::default();
+    if let Some(account) = &args.asset_account {
+        mapping.asset_account = account.clone();
+    }
+    mapping
+        .by_category
+        .extend(args.category_accounts.iter().cloned());
+    mapping
+}
+
+/// Parses a `--category-account` value: `CATEGORY=ACCOUNT`.
+fn parse_category_account(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((category, account)) if !category.is_empty() && !account.is_empty() => {
+            Ok((category.to_string(), account.to_string()))
+        }
+        _ => Err(format!("expected CATEGORY=ACCOUNT, got '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_names() {
+        let input = |name| InputFormat::from_str(name, false).unwrap();
+        assert_eq!(input("csv"), InputFormat::Batch(Format::Csv));
+        assert_eq!(input("open-banking"), InputFormat::OpenBanking);
+        assert_eq!(input("fixed-width"), InputFormat::FixedWidth);
+        let output = |name| OutputFormat::from_str(name, false).unwrap();
+        assert_eq!(output("binary"), OutputFormat::Batch(Format::Binary));
+        assert_eq!(output("beancount"), OutputFormat::Beancount);
+        assert_eq!(output("gnucash"), OutputFormat::Gnucash);
+        assert!(OutputFormat::from_str("ynab", false).is_err());
+    }
+
+    #[test]
+    fn test_category_account() {
+        assert_eq!(
+            parse_category_account("Groceries=Expenses:Food").unwrap(),
+            ("Groceries".to_string(), "Expenses:Food".to_string())
+        );
+        assert!(parse_category_account("Groceries").is_err());
+    }
+}
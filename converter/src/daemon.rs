@@ -0,0 +1,490 @@
+//! Drop-folder conversion daemon.
+//!
+//! Polls the configured input directories, converts (or just validates) every
+//! file that has stopped changing, and moves the original into a `done` or
+//! `failed` directory. Files that declare opening and closing balances that
+//! don't add up are failed too. A small HTTP endpoint reports health and
+//! Prometheus metrics.
+//!
+//! Nothing is overwritten: when a file of the same name is already in the
+//! target directory, a numeric suffix is added (`jan.csv` becomes
+//! `jan-1.csv`). A file that can't be moved is logged and left in place,
+//! and the rest of the poll goes on; later polls skip it until it is
+//! modified, so it isn't converted and counted again.
+
+use anyhow::{Context, Result};
+use parser::Format;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Top-level daemon configuration, loaded from a TOML file.
+///
+/// ```toml
+/// poll_interval_secs = 5
+/// settle_secs = 2
+/// metrics_addr = "127.0.0.1:9898"
+///
+/// [[watch]]
+/// input_dir = "/srv/ypbank/incoming"
+/// input_format = "csv"
+/// output_dir = "/srv/ypbank/converted"
+/// output_format = "binary"
+/// done_dir = "/srv/ypbank/done"
+/// failed_dir = "/srv/ypbank/failed"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64,
+
+    /// Files modified more recently than this are assumed to be still
+    /// uploading and are left for the next poll.
+    #[serde(default = "default_settle")]
+    pub settle_secs: u64,
+
+    pub metrics_addr: Option<SocketAddr>,
+
+    #[serde(rename = "watch")]
+    pub watches: Vec<Watch>,
+}
+
+/// One watched input directory.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Watch {
+    pub input_dir: PathBuf,
+    #[serde(deserialize_with = "deserialize_format")]
+    pub input_format: Format,
+
+    /// Where converted files go. Leave out (together with `output_format`)
+    /// to only validate incoming files.
+    pub output_dir: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_optional_format")]
+    pub output_format: Option<Format>,
+
+    pub done_dir: PathBuf,
+    pub failed_dir: PathBuf,
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+fn default_settle() -> u64 {
+    2
+}
+
+fn deserialize_format<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Format, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    Format::from_str(&value).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_format<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Format>, D::Error> {
+    deserialize_format(deserializer).map(Some)
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+
+        for watch in &config.watches {
+            if watch.output_dir.is_some() != watch.output_format.is_some() {
+                anyhow::bail!(
+                    "watch '{}': output_dir and output_format must be set together",
+                    watch.input_dir.display()
+                );
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Counters exposed on the metrics endpoint.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    processed: AtomicU64,
+    failed: AtomicU64,
+    transactions: AtomicU64,
+    last_poll: AtomicU64,
+}
+
+impl Metrics {
+    fn render(&self) -> String {
+        format!(
+            "# TYPE ypbank_files_processed_total counter\n\
+             ypbank_files_processed_total {}\n\
+             # TYPE ypbank_files_failed_total counter\n\
+             ypbank_files_failed_total {}\n\
+             # TYPE ypbank_transactions_total counter\n\
+             ypbank_transactions_total {}\n\
+             # TYPE ypbank_last_poll_timestamp_seconds gauge\n\
+             ypbank_last_poll_timestamp_seconds {}\n",
+            self.processed.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+            self.transactions.load(Ordering::Relaxed),
+            self.last_poll.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Runs the daemon until the process is terminated.
+pub fn run(config: &Config) -> Result<()> {
+    for watch in &config.watches {
+        for dir in [&watch.done_dir, &watch.failed_dir]
+            .into_iter()
+            .chain(watch.output_dir.as_ref())
+        {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        }
+    }
+
+    let metrics = Arc::new(Metrics::default());
+
+    if let Some(addr) = config.metrics_addr {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("Failed to bind metrics endpoint: {}", addr))?;
+        let metrics = Arc::clone(&metrics);
+        thread::spawn(move || serve_metrics(listener, &metrics));
+        eprintln!("Metrics available at http://{}/metrics", addr);
+    }
+
+    let settle = Duration::from_secs(config.settle_secs);
+    let mut stuck: Vec<Stuck> = config.watches.iter().map(|_| Stuck::default()).collect();
+    loop {
+        for (watch, stuck) in config.watches.iter().zip(&mut stuck) {
+            if let Err(e) = poll(watch, settle, &metrics, stuck) {
+                eprintln!("Error scanning {}: {:#}", watch.input_dir.display(), e);
+            }
+        }
+        metrics.last_poll.store(unix_now(), Ordering::Relaxed);
+        thread::sleep(Duration::from_secs(config.poll_interval_secs));
+    }
+}
+
+/// Files of a watch that were processed but couldn't be archived, with the
+/// modification time they had then.
+pub type Stuck = HashMap<PathBuf, SystemTime>;
+
+/// Processes every settled file currently in the watch's input directory.
+///
+/// Files in `stuck` that haven't been modified since are skipped. A file
+/// that can't be archived is added to it.
+pub fn poll(watch: &Watch, settle: Duration, metrics: &Metrics, stuck: &mut Stuck) -> Result<()> {
+    let mut entries: Vec<(PathBuf, SystemTime)> = fs::read_dir(&watch.input_dir)
+        .with_context(|| format!("Failed to read directory: {}", watch.input_dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| ready_since(&entry, settle).map(|modified| (entry.path(), modified)))
+        .collect();
+    entries.sort();
+    stuck.retain(|path, _| path.exists());
+
+    for (path, modified) in entries {
+        if stuck.get(&path) == Some(&modified) {
+            continue;
+        }
+        stuck.remove(&path);
+        let file_name = path.file_name().unwrap_or_default().to_owned();
+
+        let archived = match process_file(watch, &path) {
+            Ok(count) => {
+                metrics.processed.fetch_add(1, Ordering::Relaxed);
+                metrics.transactions.fetch_add(count, Ordering::Relaxed);
+                eprintln!("Processed {} ({} transactions)", path.display(), count);
+                move_file(&path, &unique_path(&watch.done_dir, &file_name))
+            }
+            Err(e) => {
+                metrics.failed.fetch_add(1, Ordering::Relaxed);
+                eprintln!("Failed {}: {:#}", path.display(), e);
+                let target = unique_path(&watch.failed_dir, &file_name);
+                move_file(&path, &target).and_then(|()| {
+                    let mut report = target.into_os_string();
+                    report.push(".error.txt");
+                    let report = PathBuf::from(report);
+                    fs::write(&report, format!("{:#}\n", e)).with_context(|| {
+                        format!("Failed to write error report: {}", report.display())
+                    })
+                })
+            }
+        };
+        if let Err(e) = archived {
+            eprintln!("Error archiving {}: {:#}", path.display(), e);
+            stuck.insert(path, modified);
+        }
+    }
+
+    Ok(())
+}
+
+fn process_file(watch: &Watch, path: &Path) -> Result<u64> {
+    let batch = parser::parse_path(path, watch.input_format)
+        .with_context(|| format!("Failed to parse file: {}", path.display()))?;
+    cli_common::ensure_balanced(&batch)?;
+
+    if let (Some(output_dir), Some(output_format)) = (&watch.output_dir, watch.output_format) {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = format!("{}.{}", stem, output_format.extension());
+        let target = unique_path(output_dir, name.as_ref());
+        let target_name = target.file_name().unwrap_or_default().to_string_lossy();
+        let partial = output_dir.join(format!(".{}.partial", target_name));
+
+        parser::write_path(&batch, &partial, output_format)
+            .with_context(|| format!("Failed to write output file: {}", partial.display()))?;
+        fs::rename(&partial, &target)
+            .with_context(|| format!("Failed to move output into place: {}", target.display()))?;
+    }
+
+    Ok(batch.transactions.len() as u64)
+}
+
+/// Returns the modification time of a file that has stopped changing, or
+/// `None` for hidden files, directories and files still being written.
+fn ready_since(entry: &fs::DirEntry, settle: Duration) -> Option<SystemTime> {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return None;
+    }
+
+    let metadata = entry.metadata().ok()?;
+    let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+    let age = modified.elapsed().unwrap_or_default();
+
+    (metadata.is_file() && age >= settle).then_some(modified)
+}
+
+/// Returns `dir/name`, or `dir/stem-N.ext` with the lowest `N` that is free
+/// when `dir/name` exists already.
+fn unique_path(dir: &Path, name: &OsStr) -> PathBuf {
+    let target = dir.join(name);
+    if !target.exists() {
+        return target;
+    }
+
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 1;
+    loop {
+        let target = dir.join(format!("{}-{}{}", stem, n, extension));
+        if !target.exists() {
+            return target;
+        }
+        n += 1;
+    }
+}
+
+/// Moves a file, falling back to copy-and-delete across filesystems.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)
+            .with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn serve_metrics(listener: TcpListener, metrics: &Metrics) {
+    for stream in listener.incoming().flatten() {
+        if let Err(e) = respond(stream, metrics) {
+            eprintln!("Metrics endpoint error: {}", e);
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    // The endpoint serves one client at a time, so an idle one mustn't block it
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, body) = match path {
+        "/health" => ("200 OK", "ok\n".to_string()),
+        "/metrics" => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ypbank-daemon-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn watch(root: &Path, output_format: Option<Format>) -> Watch {
+        let watch = Watch {
+            input_dir: root.join("in"),
+            input_format: Format::Csv,
+            output_dir: output_format.map(|_| root.join("out")),
+            output_format,
+            done_dir: root.join("done"),
+            failed_dir: root.join("failed"),
+        };
+        for dir in [&watch.input_dir, &watch.done_dir, &watch.failed_dir]
+            .into_iter()
+            .chain(watch.output_dir.as_ref())
+        {
+            fs::create_dir_all(dir).unwrap();
+        }
+        watch
+    }
+
+    #[test]
+    fn test_poll_converts_and_moves_files() {
+        let root = temp_dir("convert");
+        let watch = watch(&root, Some(Format::Binary));
+        fs::write(
+            watch.input_dir.join("jan.csv"),
+            "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+             TX001,2024-01-15,,Credit,10.00,USD,Refund\n",
+        )
+        .unwrap();
+        fs::write(watch.input_dir.join("broken.csv"), "garbage\n").unwrap();
+
+        let metrics = Metrics::default();
+        poll(&watch, Duration::ZERO, &metrics, &mut Stuck::new()).unwrap();
+
+        assert!(root.join("out/jan.bin").exists());
+        assert!(root.join("done/jan.csv").exists());
+        assert!(root.join("failed/broken.csv").exists());
+        assert!(root.join("failed/broken.csv.error.txt").exists());
+        assert_eq!(fs::read_dir(&watch.input_dir).unwrap().count(), 0);
+        assert!(metrics.render().contains("ypbank_files_processed_total 1"));
+        assert!(metrics.render().contains("ypbank_files_failed_total 1"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_poll_keeps_files_of_the_same_name() {
+        let root = temp_dir("same-name");
+        let watch = watch(&root, Some(Format::Binary));
+        let csv = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                   TX001,2024-01-15,,Credit,10.00,USD,Refund\n";
+        fs::write(watch.input_dir.join("jan.csv"), csv).unwrap();
+        fs::write(watch.input_dir.join("jan.txt"), csv).unwrap();
+
+        let metrics = Metrics::default();
+        poll(&watch, Duration::ZERO, &metrics, &mut Stuck::new()).unwrap();
+        fs::write(watch.input_dir.join("jan.csv"), csv).unwrap();
+        poll(&watch, Duration::ZERO, &metrics, &mut Stuck::new()).unwrap();
+
+        for name in ["out/jan.bin", "out/jan-1.bin", "out/jan-2.bin"] {
+            assert!(root.join(name).exists(), "{} is missing", name);
+        }
+        for name in ["done/jan.csv", "done/jan.txt", "done/jan-1.csv"] {
+            assert!(root.join(name).exists(), "{} is missing", name);
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_poll_continues_after_failed_move() {
+        let root = temp_dir("failed-move");
+        let watch = watch(&root, None);
+        for name in ["a.csv", "b.csv"] {
+            fs::write(
+                watch.input_dir.join(name),
+                "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                 TX001,2024-01-15,,Credit,10.00,USD,Refund\n",
+            )
+            .unwrap();
+        }
+        fs::remove_dir_all(&watch.done_dir).unwrap();
+
+        let metrics = Metrics::default();
+        let mut stuck = Stuck::new();
+        poll(&watch, Duration::ZERO, &metrics, &mut stuck).unwrap();
+
+        assert!(metrics.render().contains("ypbank_files_processed_total 2"));
+        assert_eq!(fs::read_dir(&watch.input_dir).unwrap().count(), 2);
+
+        // Files left in place aren't processed again until they change
+        poll(&watch, Duration::ZERO, &metrics, &mut stuck).unwrap();
+        assert!(metrics.render().contains("ypbank_files_processed_total 2"));
+
+        fs::File::options()
+            .write(true)
+            .open(watch.input_dir.join("a.csv"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        poll(&watch, Duration::ZERO, &metrics, &mut stuck).unwrap();
+        assert!(metrics.render().contains("ypbank_files_processed_total 3"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_config_requires_output_pair() {
+        let root = temp_dir("config");
+        fs::create_dir_all(&root).unwrap();
+        let path = root.join("daemon.toml");
+        fs::write(
+            &path,
+            "[[watch]]\n\
+             input_dir = \"in\"\n\
+             input_format = \"csv\"\n\
+             output_dir = \"out\"\n\
+             done_dir = \"done\"\n\
+             failed_dir = \"failed\"\n",
+        )
+        .unwrap();
+
+        assert!(Config::load(&path).is_err());
+
+        fs::write(
+            &path,
+            "[[watch]]\n\
+             input_dir = \"in\"\n\
+             input_format = \"txt\"\n\
+             done_dir = \"done\"\n\
+             failed_dir = \"failed\"\n",
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.poll_interval_secs, 5);
+        assert_eq!(config.watches[0].input_format, Format::Text);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
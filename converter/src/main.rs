@@ -1,43 +1,611 @@
 use anyhow::{Context, Result};
-use clap::Parser as ClapParser;
-use parser::Format;
-use std::fs::File;
-use std::io::{self, BufReader, BufWriter};
+use bank::{BankArgs, InputFormat, OutputFormat};
+use clap::{Parser as ClapParser, Subcommand, ValueEnum};
+use cli_common::Outcome;
+use parser::filter::Filter;
+#[cfg(feature = "encryption")]
+use parser::formats::binary::EncryptionKey;
+use parser::formats::profile::BankProfile;
+use parser::formats::text::TextLabels;
+use parser::patch::{apply_patch, Patch};
+#[cfg(feature = "signing")]
+use parser::signature::{SigningKey, VerifyingKey};
+use parser::tax::{apply_tax, TaxRates};
+use parser::validate::ValidationReport;
+use parser::{
+    DedupKey, Format, ParseOptions, Quoting, SortKey, SplitKey, TransactionBatch, WriteOptions,
+};
+use rust_decimal::Decimal;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod bank;
+mod daemon;
+#[cfg(feature = "sql")]
+mod sql;
 
 #[derive(ClapParser)]
 #[command(name = "ypbank_converter")]
 #[command(about = "Convert YPBank transaction files between different formats")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long, help = "Input file path (use '-' for stdin)")]
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, help = "Input file path or URI (use '-' for stdin)")]
+    #[cfg_attr(feature = "sql", arg(required_unless_present = "input_dsn"))]
+    #[cfg_attr(not(feature = "sql"), arg(required = true))]
+    input: Option<String>,
 
     #[arg(long = "input-format", help = "Input format")]
-    input_format: Format,
+    #[cfg_attr(
+        feature = "sql",
+        arg(required_unless_present_any = ["input_dsn", "profile"])
+    )]
+    #[cfg_attr(not(feature = "sql"), arg(required_unless_present = "profile"))]
+    input_format: Option<InputFormat>,
+
+    #[arg(
+        long,
+        value_name = "NAME|PATH",
+        conflicts_with_all = ["input_format", "validate", "skip_invalid"],
+        help = "Read a bank's own CSV export with a built-in profile (tinkoff, revolut, wise) or a profile TOML file"
+    )]
+    profile: Option<String>,
 
     #[arg(long = "output-format", help = "Output format")]
-    output_format: Format,
+    #[cfg_attr(feature = "sql", arg(required_unless_present = "output_dsn"))]
+    #[cfg_attr(not(feature = "sql"), arg(required = true))]
+    output_format: Option<OutputFormat>,
+
+    #[arg(
+        long,
+        value_name = "LABEL",
+        help = "Encoding of text-based input, e.g. 'windows-1251' or 'utf-16le' (detected by default)"
+    )]
+    encoding: Option<String>,
+
+    #[arg(
+        long = "allow-incomplete",
+        help = "Accept text records without a date, type or amount instead of failing"
+    )]
+    allow_incomplete: bool,
+
+    #[arg(
+        long = "skip-invalid",
+        help = "Skip malformed CSV lines and text records, listing them on stderr, instead of failing"
+    )]
+    skip_invalid: bool,
+
+    #[arg(
+        long = "iso-currencies",
+        help = "Reject currency codes that aren't in ISO 4217, such as the typo UDS"
+    )]
+    iso_currencies: bool,
+
+    #[arg(
+        long = "custom-currency",
+        value_name = "CODE",
+        requires = "iso_currencies",
+        help = "Accept this code besides ISO 4217 ones (repeatable), e.g. for loyalty points"
+    )]
+    custom_currencies: Vec<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["output", "output_format", "skip_invalid"],
+        help = "List every error in the input instead of converting it"
+    )]
+    validate: bool,
+
+    #[arg(
+        long = "check-rules",
+        requires = "validate",
+        help = "With --validate, also check the records that parse with the built-in rules, such as known currencies and unique IDs"
+    )]
+    check_rules: bool,
+
+    #[arg(
+        long = "scan-pii",
+        conflicts_with_all = ["output", "output_format", "validate"],
+        help = "List card numbers, IBANs, phone numbers and e-mail addresses in free-text fields instead of converting"
+    )]
+    scan_pii: bool,
+
+    #[arg(short, long, help = "Output file path or URI (default: stdout)")]
+    output: Option<String>,
+
+    #[arg(
+        long,
+        help = "Write canonical output: sorted records, normalized amounts, byte-identical for identical data"
+    )]
+    canonical: bool,
+
+    #[arg(
+        long,
+        value_name = "KEYS",
+        value_delimiter = ',',
+        conflicts_with = "canonical",
+        help = "Sort records by keys (date, amount, id, category), e.g. 'date,amount:desc'"
+    )]
+    sort: Vec<SortKey>,
+
+    #[arg(
+        long,
+        value_name = "EXPR",
+        help = "Only keep records matching EXPR, e.g. 'amount > 100 && category =~ \"Food\"'"
+    )]
+    filter: Option<Filter>,
+
+    #[arg(
+        long,
+        value_name = "KEY",
+        help = "Drop records that repeat an earlier one by 'id' or by 'composite' (date, amount, counterparty, description)"
+    )]
+    dedup: Option<DedupKey>,
+
+    #[arg(
+        long = "split-by",
+        value_name = "KEY",
+        requires = "output",
+        help = "Write one file per month, year, account, category or currency; --output must contain '{key}'"
+    )]
+    split_by: Option<SplitKey>,
+
+    #[arg(
+        long = "apply-patch",
+        value_name = "FILE",
+        help = "Apply a JSON patch written by 'ypbank_compare --patch' before converting"
+    )]
+    apply_patch: Option<PathBuf>,
+
+    #[arg(
+        long = "schema-version",
+        help = "Start CSV output with a '# schema-version: N' comment"
+    )]
+    schema_version: bool,
+
+    #[arg(
+        long = "date-format",
+        value_name = "FORMAT",
+        help = "chrono format of posting dates in CSV and text output, e.g. '%d/%m/%Y'"
+    )]
+    date_format: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "CHAR",
+        value_parser = parse_delimiter,
+        help = "Field delimiter of CSV output, e.g. ';' or 'tab' (CSV input is sniffed)"
+    )]
+    delimiter: Option<char>,
+
+    #[arg(
+        long = "text-template",
+        value_name = "FILE",
+        help = "Template file laying out each text record with {field} placeholders"
+    )]
+    text_template: Option<PathBuf>,
+
+    #[arg(
+        long = "text-labels",
+        value_name = "LANG",
+        value_parser = parse_text_labels,
+        help = "Language of text format labels, in input and output: 'en' or 'ru'"
+    )]
+    text_labels: Option<TextLabels>,
+
+    #[arg(long = "quote-all", help = "Quote every field of CSV output")]
+    quote_all: bool,
+
+    #[arg(
+        long,
+        help = "Write RFC 4180-strict CSV: CRLF line endings, ',' delimiter, quoted text fields"
+    )]
+    rfc4180: bool,
+
+    #[arg(
+        long,
+        help = "Start CSV and text output with a UTF-8 byte order mark, for Excel"
+    )]
+    bom: bool,
+
+    #[arg(
+        long,
+        help = "Protect binary output with per-transaction and whole-file CRC32 checksums"
+    )]
+    checksums: bool,
+
+    #[arg(
+        long,
+        help = "Append an index of transaction IDs to binary output, for random access"
+    )]
+    index: bool,
+
+    #[cfg(feature = "zstd")]
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        default_missing_value = "0",
+        help = "Compress binary output with zstd, optionally at LEVEL (1-22)"
+    )]
+    compress: Option<i32>,
+
+    #[cfg(feature = "encryption")]
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "Encrypt binary output with AES-256-GCM under this 64-digit hex key"
+    )]
+    encrypt_key: Option<EncryptionKey>,
+
+    #[cfg(feature = "encryption")]
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "Decrypt encrypted binary input with this 64-digit hex key"
+    )]
+    decrypt_key: Option<EncryptionKey>,
+
+    #[cfg(feature = "signing")]
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "Embed an Ed25519 signature in binary output, by this 64-digit hex key seed"
+    )]
+    sign_key: Option<SigningKey>,
+
+    #[cfg(feature = "signing")]
+    #[arg(
+        long,
+        value_name = "HEX",
+        help = "Require binary input to be signed by this 64-digit hex public key"
+    )]
+    verify_key: Option<VerifyingKey>,
+
+    #[arg(
+        long = "decimal-places",
+        value_name = "N",
+        help = "Round amounts and balances to N decimal places"
+    )]
+    decimal_places: Option<u32>,
+
+    #[arg(
+        long = "check-balances",
+        help = "Fail if declared closing balances don't match opening balance plus transactions"
+    )]
+    check_balances: bool,
+
+    #[arg(
+        long = "extract-tax",
+        help = "Fill tax amount and rate from descriptions such as 'в т.ч. НДС 20% - 166.67'"
+    )]
+    extract_tax: bool,
+
+    #[arg(
+        long = "tax-rate",
+        value_name = "PERCENT",
+        requires = "extract_tax",
+        help = "Tax rate assumed for transactions whose description doesn't state one"
+    )]
+    tax_rate: Option<Decimal>,
+
+    #[command(flatten)]
+    bank: BankArgs,
+
+    #[cfg(feature = "sql")]
+    #[command(flatten)]
+    sql: sql::SqlArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Watch drop folders and convert or validate files as they arrive
+    Daemon {
+        #[arg(long, help = "Path to the daemon TOML configuration")]
+        config: PathBuf,
+    },
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
+    cli_common::run("ypbank_converter", || {
+        if args.validate {
+            return validate(&args);
+        }
+        if args.scan_pii {
+            return scan_pii(&args);
+        }
+        convert(&args)?;
+        Ok(Outcome::Success)
+    })
+}
 
-    let batch = if args.input == "-" {
-        let stdin = io::stdin();
-        let reader = BufReader::new(stdin.lock());
-        parser::parse(reader, args.input_format)
-            .context("Failed to parse from stdin")?
+fn validate(args: &Args) -> Result<Outcome> {
+    let input = args.input.as_deref().context("--input is required")?;
+    let format = batch_input_format(args, "--validate")?;
+    let (errors, report) = if args.check_rules {
+        cli_common::validate_with_rules(input, format, &parse_options(args))?
     } else {
-        let file = File::open(&args.input)
-            .with_context(|| format!("Failed to open input file: {}", args.input))?;
-        let reader = BufReader::new(file);
-        parser::parse(reader, args.input_format)
-            .with_context(|| format!("Failed to parse file: {}", args.input))?
+        let errors = cli_common::validate(input, format, &parse_options(args))?;
+        (errors, ValidationReport::default())
     };
+    for error in &errors {
+        eprintln!("ypbank_converter: {}", error);
+    }
+    for issue in &report.issues {
+        eprintln!("ypbank_converter: {}", issue);
+    }
+    if errors.is_empty() && report.is_valid() {
+        Ok(Outcome::Success)
+    } else {
+        let count = errors.len() + report.errors().count();
+        eprintln!("ypbank_converter: {} errors found", count);
+        Ok(Outcome::Failure)
+    }
+}
+
+fn scan_pii(args: &Args) -> Result<Outcome> {
+    let batch = read_input(args)?;
+    let findings = batch.scan_pii();
+    for finding in &findings {
+        eprintln!(
+            "ypbank_converter: transaction {} (record {}): possible {} in {}",
+            finding.id,
+            finding.index + 1,
+            finding.kind,
+            finding.field
+        );
+    }
+    if findings.is_empty() {
+        Ok(Outcome::Success)
+    } else {
+        eprintln!(
+            "ypbank_converter: {} possible personal data values found",
+            findings.len()
+        );
+        Ok(Outcome::Failure)
+    }
+}
+
+fn convert(args: &Args) -> Result<()> {
+    if let Some(Command::Daemon { config }) = &args.command {
+        let config = daemon::Config::load(config)?;
+        return daemon::run(&config);
+    }
 
-    let stdout = io::stdout();
-    let mut writer = BufWriter::new(stdout.lock());
-    parser::write(&batch, &mut writer, args.output_format)
-        .context("Failed to write output")?;
+    if streamable(args) {
+        let input = args.input.as_deref().context("--input is required")?;
+        let from = args
+            .input_format
+            .and_then(InputFormat::batch)
+            .context("--input-format is required")?;
+        let to = args
+            .output_format
+            .and_then(OutputFormat::batch)
+            .context("--output-format is required")?;
+        let (parse_options, write_options) = (parse_options(args), write_options(args)?);
+        cli_common::convert(
+            input,
+            from,
+            args.output.as_deref(),
+            to,
+            &parse_options,
+            &write_options,
+        )?;
+        return Ok(());
+    }
 
+    #[cfg(feature = "sql")]
+    let batch = match &args.sql.input_dsn {
+        Some(dsn) => sql::read(dsn, &args.sql)?,
+        None => read_input(args)?,
+    };
+    #[cfg(not(feature = "sql"))]
+    let batch = read_input(args)?;
+
+    if args.check_balances {
+        cli_common::ensure_balanced(&batch)?;
+    }
+
+    let mut batch = batch;
+    if let Some(path) = &args.apply_patch {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read patch file: {}", path.display()))?;
+        let patch: Patch = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse patch file: {}", path.display()))?;
+        apply_patch(&mut batch, &patch)
+            .with_context(|| format!("Failed to apply patch file: {}", path.display()))?;
+    }
+    if args.extract_tax {
+        let rates = TaxRates {
+            default: args.tax_rate,
+            ..TaxRates::default()
+        };
+        apply_tax(&mut batch, &rates);
+    }
+    if let Some(filter) = &args.filter {
+        batch = batch.filter(filter);
+    }
+    if let Some(key) = args.dedup {
+        let removed = batch.dedup_by(key);
+        if !removed.is_empty() {
+            eprintln!("ypbank_converter: duplicates removed: {}", removed.len());
+        }
+    }
+    batch.sort_by_keys(&args.sort);
+
+    if let Some(key) = args.split_by {
+        return write_parts(&batch, key, args);
+    }
+
+    #[cfg(feature = "sql")]
+    if let Some(dsn) = &args.sql.output_dsn {
+        return sql::write(&batch, dsn, &args.sql);
+    }
+
+    write_output(&batch, args)
+}
+
+/// Whether the input can be converted one record at a time, without
+/// reading the whole batch: CSV from a local file or stdin to a local file or
+/// stdout, with no option that needs the batch.
+fn streamable(args: &Args) -> bool {
+    #[cfg(feature = "sql")]
+    if args.sql.input_dsn.is_some() || args.sql.output_dsn.is_some() {
+        return false;
+    }
+    #[cfg(feature = "sqlite")]
+    if args.output_format == Some(OutputFormat::Batch(Format::Sqlite)) {
+        return false;
+    }
+    let local = |path: &str| !path.contains("://");
+    args.input_format == Some(InputFormat::Batch(Format::Csv))
+        && args.output_format.and_then(OutputFormat::batch).is_some()
+        && args.input.as_deref().is_some_and(local)
+        && args.output.as_deref().is_none_or(local)
+        && !args.check_balances
+        && !args.extract_tax
+        && !args.skip_invalid
+        && args.sort.is_empty()
+        && args.filter.is_none()
+        && args.dedup.is_none()
+        && args.split_by.is_none()
+        && args.apply_patch.is_none()
+}
+
+fn read_input(args: &Args) -> Result<TransactionBatch> {
+    let input = args.input.as_deref().context("--input is required")?;
+    if let Some(profile) = &args.profile {
+        let profile = load_profile(profile)?;
+        return cli_common::read_batch_with_profile(input, &profile, &parse_options(args));
+    }
+    if args.skip_invalid {
+        let format = batch_input_format(args, "--skip-invalid")?;
+        let report = cli_common::read_report(input, format, &parse_options(args))?;
+        cli_common::print_report("ypbank_converter", &report);
+        return Ok(report.into_batch());
+    }
+    let format = args.input_format.context("--input-format is required")?;
+    bank::read(input, format, &args.bank, &parse_options(args))
+}
+
+/// Returns the `--input-format` for `what`, which only works with the
+/// formats of [`Format`], not with the bank formats read by their importers.
+fn batch_input_format(args: &Args, what: &str) -> Result<Format> {
+    let format = args.input_format.context("--input-format is required")?;
+    format.batch().with_context(|| {
+        let value = format.to_possible_value();
+        let name = value.as_ref().map_or("this", |value| value.get_name());
+        format!("{} doesn't support {} input", what, name)
+    })
+}
+
+fn parse_options(args: &Args) -> ParseOptions {
+    ParseOptions {
+        encoding: args.encoding.clone(),
+        text_labels: args.text_labels.clone(),
+        allow_incomplete: args.allow_incomplete,
+        iso_currencies: args.iso_currencies,
+        custom_currencies: args.custom_currencies.clone(),
+        #[cfg(feature = "encryption")]
+        encryption_key: args.decrypt_key.clone(),
+        #[cfg(feature = "signing")]
+        verifying_key: args.verify_key,
+        ..ParseOptions::default()
+    }
+}
+
+fn write_output(batch: &TransactionBatch, args: &Args) -> Result<()> {
+    let format = args.output_format.context("--output-format is required")?;
+    let options = write_options(args)?;
+    bank::write(batch, args.output.as_deref(), format, &args.bank, &options)
+}
+
+/// Writes each part of `batch` split by `key` to the output path with
+/// `{key}` replaced by the part's key, or `none` for the part without one.
+fn write_parts(batch: &TransactionBatch, key: SplitKey, args: &Args) -> Result<()> {
+    let format = args.output_format.context("--output-format is required")?;
+    let template = args.output.as_deref().context("--output is required")?;
+    if !template.contains("{key}") {
+        anyhow::bail!("--output must contain '{{key}}' with --split-by");
+    }
+    let options = write_options(args)?;
+    for (name, part) in batch.split_by(key) {
+        let name = match name.as_str() {
+            "" => "none".to_string(),
+            name => name.replace(['/', '\\'], "_"),
+        };
+        let path = template.replace("{key}", &name);
+        bank::write(&part, Some(&path), format, &args.bank, &options)?;
+    }
     Ok(())
 }
+
+fn write_options(args: &Args) -> Result<WriteOptions> {
+    let text_template = match &args.text_template {
+        Some(path) => {
+            let source = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read template: {}", path.display()))?;
+            Some(source.parse().context("Invalid text template")?)
+        }
+        None => None,
+    };
+    Ok(WriteOptions {
+        canonical: args.canonical,
+        schema_version: args.schema_version,
+        date_format: args.date_format.clone(),
+        delimiter: args.delimiter,
+        quoting: if args.quote_all {
+            Quoting::Always
+        } else {
+            Quoting::Necessary
+        },
+        decimal_places: args.decimal_places,
+        text_template,
+        text_labels: args.text_labels.clone(),
+        rfc4180: args.rfc4180,
+        bom: args.bom,
+        checksums: args.checksums,
+        index: args.index,
+        #[cfg(feature = "zstd")]
+        compression: args.compress,
+        #[cfg(feature = "encryption")]
+        encryption_key: args.encrypt_key.clone(),
+        #[cfg(feature = "signing")]
+        signing_key: args.sign_key.clone(),
+        ..WriteOptions::default()
+    })
+}
+
+/// Returns the built-in bank profile called `value`, or else the profile in
+/// the TOML file at `value`.
+fn load_profile(value: &str) -> Result<BankProfile> {
+    if let Some(profile) = BankProfile::builtin(value) {
+        return Ok(profile);
+    }
+    BankProfile::load(value).with_context(|| {
+        format!(
+            "'{}' is neither a built-in profile (tinkoff, revolut, wise) nor a readable profile file",
+            value
+        )
+    })
+}
+
+/// Parses a `--text-labels` language code.
+fn parse_text_labels(value: &str) -> std::result::Result<TextLabels, String> {
+    TextLabels::builtin(value).ok_or_else(|| format!("expected 'en' or 'ru', got '{}'", value))
+}
+
+/// Parses a `--delimiter` value: a single character, or `tab`.
+fn parse_delimiter(value: &str) -> std::result::Result<char, String> {
+    if value.eq_ignore_ascii_case("tab") || value == "\\t" {
+        return Ok('\t');
+    }
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch != '"' && ch != '\n' => Ok(ch),
+        _ => Err(format!("expected a single character or 'tab', got '{}'", value)),
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use parser::formats::sql::{self, SqlPool, TableSchema};
+use parser::TransactionBatch;
+
+#[derive(Args)]
+pub struct SqlArgs {
+    #[arg(
+        long = "input-dsn",
+        conflicts_with = "input",
+        requires = "input_query",
+        help = "Read transactions from a database (postgres://... or mysql://...)"
+    )]
+    pub input_dsn: Option<String>,
+
+    #[arg(long = "input-query", help = "SQL query selecting the transactions to read")]
+    pub input_query: Option<String>,
+
+    #[arg(
+        long = "output-dsn",
+        conflicts_with_all = ["output_format", "output"],
+        help = "Insert transactions into a database instead of writing to stdout"
+    )]
+    pub output_dsn: Option<String>,
+
+    #[arg(
+        long = "table",
+        default_value = "transactions",
+        help = "Database table; columns are named after transaction fields"
+    )]
+    pub table: String,
+}
+
+pub fn read(dsn: &str, args: &SqlArgs) -> Result<TransactionBatch> {
+    let query = args
+        .input_query
+        .as_deref()
+        .context("--input-query is required")?;
+
+    runtime()?.block_on(async {
+        let pool = SqlPool::connect(dsn)
+            .await
+            .context("Failed to connect to input database")?;
+        sql::query_batch(&pool, query, &TableSchema::new(&args.table))
+            .await
+            .context("Failed to read transactions from database")
+    })
+}
+
+pub fn write(batch: &TransactionBatch, dsn: &str, args: &SqlArgs) -> Result<()> {
+    let inserted = runtime()?.block_on(async {
+        let pool = SqlPool::connect(dsn)
+            .await
+            .context("Failed to connect to output database")?;
+        sql::insert_batch(&pool, batch, &TableSchema::new(&args.table))
+            .await
+            .context("Failed to insert transactions into database")
+    })?;
+
+    eprintln!("Inserted {} transactions into {}", inserted, args.table);
+    Ok(())
+}
+
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start async runtime")
+}
@@ -0,0 +1,465 @@
+//! gRPC front-end for the parser library.
+//!
+//! The service definition lives in `proto/ypbank.proto`; this crate compiles
+//! it with `tonic` and implements [`TransactionService`] on top of
+//! [`parser::parse`] and [`parser::write`]. File contents travel as raw bytes,
+//! so any format the library understands can be sent over the wire.
+//!
+//! Requests come from untrusted clients, so their data is parsed with the
+//! caps of [`Limits::untrusted`]. Parsing and writing run on tokio's blocking
+//! pool, so a large request doesn't hold up the others.
+
+use parser::async_io::parse_stream_with;
+use parser::diff::{diff_batches, DiffOptions, DiffReport, FieldChange};
+use parser::{
+    Error, Format, Limits, ParseOptions, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use std::io::Cursor;
+use std::pin::Pin;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+/// Types generated from `proto/ypbank.proto`.
+#[allow(clippy::all)]
+pub mod pb {
+    tonic::include_proto!("ypbank.v1");
+}
+
+pub use pb::transaction_service_server::TransactionServiceServer;
+
+/// Implementation of the `ypbank.v1.TransactionService` gRPC service.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TransactionService;
+
+#[tonic::async_trait]
+impl pb::transaction_service_server::TransactionService for TransactionService {
+    async fn convert(
+        &self,
+        request: Request<pb::ConvertRequest>,
+    ) -> Result<Response<pb::ConvertResponse>, Status> {
+        let req = request.into_inner();
+        let input_format = to_format(req.input_format)?;
+        let output_format = to_format(req.output_format)?;
+
+        let data = blocking(move || {
+            let batch = parse_bytes(req.data, input_format)?;
+            let mut data = Vec::new();
+            parser::write(&batch, &mut data, output_format)
+                .map_err(|e| Status::internal(e.to_string()))?;
+            Ok(data)
+        })
+        .await?;
+
+        Ok(Response::new(pb::ConvertResponse { data }))
+    }
+
+    async fn validate(
+        &self,
+        request: Request<pb::ValidateRequest>,
+    ) -> Result<Response<pb::ValidateResponse>, Status> {
+        let req = request.into_inner();
+        let format = to_format(req.format)?;
+
+        let parsed =
+            blocking(move || Ok(parser::parse_with(Cursor::new(req.data), format, &parse_options())))
+                .await?;
+        let response = match parsed {
+            Ok(batch) => pb::ValidateResponse {
+                valid: true,
+                error: String::new(),
+                transaction_count: batch.transactions.len() as u32,
+                account_id: batch.account_id,
+            },
+            Err(e) => pb::ValidateResponse {
+                valid: false,
+                error: e.to_string(),
+                transaction_count: 0,
+                account_id: None,
+            },
+        };
+
+        Ok(Response::new(response))
+    }
+
+    async fn diff(
+        &self,
+        request: Request<pb::DiffRequest>,
+    ) -> Result<Response<pb::DiffResponse>, Status> {
+        let req = request.into_inner();
+        let format1 = to_format(req.format1)?;
+        let format2 = to_format(req.format2)?;
+
+        let response = blocking(move || {
+            let batch1 = parse_bytes(req.data1, format1)?;
+            let batch2 = parse_bytes(req.data2, format2)?;
+            let report = diff_batches(&batch1, &batch2, &DiffOptions::default());
+            Ok(to_proto_diff(&report))
+        })
+        .await?;
+        Ok(Response::new(response))
+    }
+
+    type ParseTransactionsStream =
+        Pin<Box<dyn Stream<Item = Result<pb::Transaction, Status>> + Send>>;
+
+    /// Streams the transactions as they are parsed; CSV is parsed one line at
+    /// a time, so the first transactions are sent before the rest of the data
+    /// has been looked at. Other formats are parsed as a whole first.
+    async fn parse_transactions(
+        &self,
+        request: Request<pb::ParseTransactionsRequest>,
+    ) -> Result<Response<Self::ParseTransactionsStream>, Status> {
+        let req = request.into_inner();
+        let format = to_format(req.format)?;
+
+        if format != Format::Csv {
+            let batch = blocking(move || parse_bytes(req.data, format)).await?;
+            let transactions = batch.transactions.into_iter();
+            let stream = tokio_stream::iter(transactions.map(|tx| Ok(to_proto_transaction(&tx))));
+            return Ok(Response::new(Box::pin(stream)));
+        }
+
+        let stream = parse_stream_with(Cursor::new(req.data), format, &parse_options())
+            .map(|tx| tx.map(|tx| to_proto_transaction(&tx)).map_err(to_status));
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// How request data is parsed.
+fn parse_options() -> ParseOptions {
+    ParseOptions {
+        limits: Limits::untrusted(),
+        ..ParseOptions::default()
+    }
+}
+
+/// Runs parser work on the blocking pool.
+async fn blocking<T, F>(work: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Status> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+}
+
+fn parse_bytes(data: Vec<u8>, format: Format) -> Result<TransactionBatch, Status> {
+    parser::parse_with(Cursor::new(data), format, &parse_options()).map_err(to_status)
+}
+
+fn to_status(error: Error) -> Status {
+    match error {
+        Error::LimitExceeded(_) => Status::resource_exhausted(error.to_string()),
+        _ => Status::invalid_argument(error.to_string()),
+    }
+}
+
+fn to_format(value: i32) -> Result<Format, Status> {
+    match pb::Format::try_from(value) {
+        Ok(pb::Format::Csv) => Ok(Format::Csv),
+        Ok(pb::Format::Text) => Ok(Format::Text),
+        Ok(pb::Format::Binary) => Ok(Format::Binary),
+        Ok(pb::Format::Json) => Ok(Format::Json),
+        Ok(pb::Format::Xml) => Ok(Format::Xml),
+        Ok(pb::Format::Yaml) => Ok(Format::Yaml),
+        #[cfg(feature = "parquet")]
+        Ok(pb::Format::Parquet) => Ok(Format::Parquet),
+        #[cfg(feature = "proto")]
+        Ok(pb::Format::Protobuf) => Ok(Format::Protobuf),
+        #[cfg(feature = "sqlite")]
+        Ok(pb::Format::Sqlite) => Ok(Format::Sqlite),
+        #[cfg(feature = "xlsx")]
+        Ok(pb::Format::Xlsx) => Ok(Format::Xlsx),
+        #[allow(unreachable_patterns)]
+        Ok(format @ (pb::Format::Parquet
+        | pb::Format::Protobuf
+        | pb::Format::Sqlite
+        | pb::Format::Xlsx)) => Err(Status::unimplemented(format!(
+            "format {} is not enabled in this build",
+            format.as_str_name()
+        ))),
+        Ok(pb::Format::Unspecified) | Err(_) => Err(Status::invalid_argument(format!(
+            "unsupported format: {}",
+            value
+        ))),
+    }
+}
+
+fn to_proto_transaction(tx: &Transaction) -> pb::Transaction {
+    let kind = match tx.kind {
+        TransactionKind::Debit => pb::TransactionKind::Debit,
+        TransactionKind::Credit => pb::TransactionKind::Credit,
+        TransactionKind::Transfer => pb::TransactionKind::Transfer,
+        TransactionKind::Fee => pb::TransactionKind::Fee,
+        TransactionKind::Interest => pb::TransactionKind::Interest,
+        TransactionKind::Refund => pb::TransactionKind::Refund,
+        TransactionKind::Reversal => pb::TransactionKind::Reversal,
+    };
+    let status = match tx.status {
+        TransactionStatus::Booked => pb::TransactionStatus::Booked,
+        TransactionStatus::Pending => pb::TransactionStatus::Pending,
+        TransactionStatus::Cancelled => pb::TransactionStatus::Cancelled,
+    };
+
+    pb::Transaction {
+        id: tx.id.clone(),
+        posted_at: tx.posted_at.format("%Y-%m-%d").to_string(),
+        executed_at: tx
+            .executed_at
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+        kind: kind as i32,
+        amount: tx.amount.amount.to_string(),
+        currency: tx.amount.currency.clone(),
+        description: tx.description.clone(),
+        account: tx.account.clone(),
+        counterparty: tx.counterparty.clone(),
+        category: tx.category.clone(),
+        tax_amount: tx.tax_amount.map(|v| v.to_string()),
+        tax_rate: tx.tax_rate.map(|v| v.to_string()),
+        status: status as i32,
+        extra: tx
+            .extra
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+    }
+}
+
+fn to_proto_diff(report: &DiffReport) -> pb::DiffResponse {
+    let differences = report
+        .changed
+        .iter()
+        .map(|diff| pb::TransactionDifference {
+            index: (diff.index + 1) as u32,
+            id: diff.id.clone(),
+            fields: diff.changes.iter().flat_map(to_proto_fields).collect(),
+        })
+        .collect();
+
+    pb::DiffResponse {
+        identical: report.is_identical(),
+        count1: report.left_count as u32,
+        count2: report.right_count as u32,
+        differences,
+        removed: report.removed.iter().map(to_proto_transaction).collect(),
+        added: report.added.iter().map(to_proto_transaction).collect(),
+    }
+}
+
+/// Maps a changed field to the differences of the proto, which lists the
+/// amount and the currency separately.
+fn to_proto_fields(change: &FieldChange) -> Vec<pb::FieldDifference> {
+    fn field(name: &str, left: impl ToString, right: impl ToString) -> pb::FieldDifference {
+        pb::FieldDifference {
+            field: name.to_string(),
+            left: left.to_string(),
+            right: right.to_string(),
+        }
+    }
+    fn optional<T: ToString>(value: &Option<T>) -> String {
+        value.as_ref().map(T::to_string).unwrap_or_default()
+    }
+    match change {
+        FieldChange::Id(c) => vec![field("id", &c.left, &c.right)],
+        FieldChange::PostedAt(c) => vec![field(
+            "posted_at",
+            c.left.format("%Y-%m-%d"),
+            c.right.format("%Y-%m-%d"),
+        )],
+        FieldChange::ExecutedAt(c) => vec![field(
+            "executed_at",
+            c.left
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            c.right
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+        )],
+        FieldChange::Kind(c) => vec![field("kind", c.left, c.right)],
+        FieldChange::Status(c) => vec![field("status", c.left, c.right)],
+        FieldChange::Amount(c) => {
+            let mut fields = Vec::new();
+            if c.left.amount != c.right.amount {
+                fields.push(field("amount", c.left.amount, c.right.amount));
+            }
+            if c.left.currency != c.right.currency {
+                fields.push(field("currency", &c.left.currency, &c.right.currency));
+            }
+            fields
+        }
+        FieldChange::Description(c) => vec![field("description", &c.left, &c.right)],
+        FieldChange::Account(c) => vec![field("account", optional(&c.left), optional(&c.right))],
+        FieldChange::Counterparty(c) => {
+            vec![field("counterparty", optional(&c.left), optional(&c.right))]
+        }
+        FieldChange::Category(c) => {
+            vec![field("category", optional(&c.left), optional(&c.right))]
+        }
+        FieldChange::TaxAmount(c) => {
+            vec![field("tax_amount", optional(&c.left), optional(&c.right))]
+        }
+        FieldChange::TaxRate(c) => vec![field("tax_rate", optional(&c.left), optional(&c.right))],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pb::transaction_service_server::TransactionService as _;
+    use super::*;
+
+    const CSV: &str = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description,Account,Counterparty,Category\n\
+                       TX001,2024-01-15,2024-01-15 10:30:00,Credit,1000.50,USD,Salary payment,ACC123,Employer Inc,Salary\n\
+                       TX002,2024-01-16,,Debit,150.50,USD,Groceries,ACC123,Store,Food\n";
+
+    #[tokio::test]
+    async fn test_convert_csv_to_text() {
+        let response = TransactionService
+            .convert(Request::new(pb::ConvertRequest {
+                data: CSV.as_bytes().to_vec(),
+                input_format: pb::Format::Csv as i32,
+                output_format: pb::Format::Text as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let text = String::from_utf8(response.data).unwrap();
+        assert!(text.contains("ID: TX001"));
+        assert!(text.contains("Amount: 150.50 USD"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_reports_parse_error() {
+        let response = TransactionService
+            .validate(Request::new(pb::ValidateRequest {
+                data: b"not a csv".to_vec(),
+                format: pb::Format::Csv as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(!response.valid);
+        assert!(response.error.contains("invalid header"));
+    }
+
+    #[tokio::test]
+    async fn test_diff_and_stream() {
+        let changed = CSV.replace("150.50", "151.00");
+        let response = TransactionService
+            .diff(Request::new(pb::DiffRequest {
+                data1: CSV.as_bytes().to_vec(),
+                format1: pb::Format::Csv as i32,
+                data2: changed.into_bytes(),
+                format2: pb::Format::Csv as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(!response.identical);
+        assert_eq!(response.differences.len(), 1);
+        assert_eq!(response.differences[0].id, "TX002");
+        assert_eq!(response.differences[0].index, 2);
+        let field = &response.differences[0].fields[0];
+        assert_eq!(field.field, "amount");
+        assert_eq!(
+            (field.left.as_str(), field.right.as_str()),
+            ("150.50", "151.00")
+        );
+
+        // Amounts are compared as decimals, not as text
+        let response = TransactionService
+            .diff(Request::new(pb::DiffRequest {
+                data1: CSV.as_bytes().to_vec(),
+                format1: pb::Format::Csv as i32,
+                data2: CSV.replace("150.50", "150.5").into_bytes(),
+                format2: pb::Format::Csv as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.identical);
+        assert!(response.differences.is_empty());
+
+        // Records past the end of the shorter input are sent in full
+        let shorter = CSV.lines().take(2).collect::<Vec<_>>().join("\n");
+        let response = TransactionService
+            .diff(Request::new(pb::DiffRequest {
+                data1: shorter.into_bytes(),
+                format1: pb::Format::Csv as i32,
+                data2: CSV.as_bytes().to_vec(),
+                format2: pb::Format::Csv as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(!response.identical);
+        assert!(response.differences.is_empty());
+        assert!(response.removed.is_empty());
+        assert_eq!(response.added.len(), 1);
+        assert_eq!(response.added[0].id, "TX002");
+        assert_eq!(response.added[0].amount, "150.50");
+
+        let stream = TransactionService
+            .parse_transactions(Request::new(pb::ParseTransactionsRequest {
+                data: CSV.as_bytes().to_vec(),
+                format: pb::Format::Csv as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let ids: Vec<String> = stream.map(|tx| tx.unwrap().id).collect().await;
+        assert_eq!(ids, vec!["TX001", "TX002"]);
+
+        // Transactions before an invalid record are still sent
+        let stream = TransactionService
+            .parse_transactions(Request::new(pb::ParseTransactionsRequest {
+                data: CSV.replace("150.50", "abc").into_bytes(),
+                format: pb::Format::Csv as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let results: Vec<_> = stream.collect().await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap().id, "TX001");
+        let status = results[1].as_ref().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_stream_carries_extra_columns() {
+        let csv = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description,MCC\n\
+                   TX001,2024-01-15,,Debit,4.50,USD,Coffee,5814\n";
+        let converted = TransactionService
+            .convert(Request::new(pb::ConvertRequest {
+                data: csv.as_bytes().to_vec(),
+                input_format: pb::Format::Csv as i32,
+                output_format: pb::Format::Text as i32,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        for (data, format) in [
+            (csv.as_bytes().to_vec(), pb::Format::Csv),
+            (converted.data, pb::Format::Text),
+        ] {
+            let stream = TransactionService
+                .parse_transactions(Request::new(pb::ParseTransactionsRequest {
+                    data,
+                    format: format as i32,
+                }))
+                .await
+                .unwrap()
+                .into_inner();
+            let transactions: Vec<_> = stream.map(|tx| tx.unwrap()).collect().await;
+            assert_eq!(transactions.len(), 1, "{:?}", format);
+            assert_eq!(transactions[0].extra["MCC"], "5814", "{:?}", format);
+        }
+    }
+}
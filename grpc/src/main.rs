@@ -0,0 +1,26 @@
+use anyhow::{Context, Result};
+use clap::Parser as ClapParser;
+use grpc::{TransactionService, TransactionServiceServer};
+use std::net::SocketAddr;
+
+#[derive(ClapParser)]
+#[command(name = "ypbank_grpc")]
+#[command(about = "Serve YPBank conversion, validation and diffing over gRPC")]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1:50051", help = "Address to listen on")]
+    addr: SocketAddr,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    eprintln!("ypbank_grpc listening on {}", args.addr);
+    tonic::transport::Server::builder()
+        .add_service(TransactionServiceServer::new(TransactionService))
+        .serve(args.addr)
+        .await
+        .context("gRPC server failed")?;
+
+    Ok(())
+}
@@ -0,0 +1,369 @@
+//! Line-level checks for the plain text format.
+//!
+//! [`parser::formats::text::parse_text`] stops at the first error and
+//! silently skips lines it does not recognise. Editors need more than that,
+//! so this module re-checks a document line by line and reports every
+//! problem with the span of the offending text.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use parser::{Period, TransactionKind, TransactionStatus};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Field labels understood by the text format, in the order they are written.
+pub const FIELD_LABELS: [&str; 13] = [
+    "ID",
+    "Date",
+    "ExecutedDate",
+    "Type",
+    "Status",
+    "Amount",
+    "Description",
+    "Account",
+    "Counterparty",
+    "Category",
+    "TaxAmount",
+    "TaxRate",
+    "Extra",
+];
+
+/// Values of the `Type` field.
+pub const TRANSACTION_KINDS: [&str; 7] = [
+    "Credit", "Debit", "Transfer", "Fee", "Interest", "Refund", "Reversal",
+];
+
+/// Values of the `Status` field.
+pub const TRANSACTION_STATUSES: [&str; 3] = ["Booked", "Pending", "Cancelled"];
+
+/// Batch fields allowed before the first transaction.
+const HEADER_LABELS: [&str; 6] = [
+    "OpeningBalance",
+    "ClosingBalance",
+    "GeneratedAt",
+    "SourceSystem",
+    "Period",
+    "SchemaVersion",
+];
+
+/// Fields every transaction must set; the parser rejects records without them.
+const REQUIRED_FIELDS: [&str; 3] = ["Date", "Type", "Amount"];
+
+/// How serious a diagnostic is, numbered as in the LSP specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The document does not describe the intended data
+    Error = 1,
+    /// The document parses but something is likely a mistake
+    Warning = 2,
+}
+
+/// A problem found in a document.
+///
+/// Columns are counted in UTF-16 code units, as LSP clients expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Zero-based line number
+    pub line: u32,
+    /// Start column of the highlighted span
+    pub start: u32,
+    /// End column of the highlighted span
+    pub end: u32,
+    /// How serious the problem is
+    pub severity: Severity,
+    /// Human-readable description
+    pub message: String,
+}
+
+/// Transaction currently being checked.
+struct Block {
+    line: u32,
+    id_span: (u32, u32),
+    fields: HashSet<&'static str>,
+}
+
+/// Checks a text-format document and returns every problem found.
+pub fn check(text: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut ids = HashSet::new();
+    let mut block: Option<Block> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_num = index as u32;
+        let trimmed = line.trim();
+        let indent = line.len() - line.trim_start().len();
+        let span = |from: usize, to: usize| (utf16_col(line, from), utf16_col(line, to));
+        let whole = span(indent, indent + trimmed.len());
+
+        if index == 0 && trimmed.starts_with("Account: ") {
+            continue;
+        }
+
+        if trimmed.is_empty() || trimmed == "---" {
+            if let Some(done) = block.take() {
+                finish_block(done, &mut diagnostics);
+            }
+            continue;
+        }
+
+        let Some((label, value)) = trimmed.split_once(": ") else {
+            report(
+                &mut diagnostics,
+                line_num,
+                whole,
+                Severity::Warning,
+                "expected 'Label: value'; this line is ignored".to_string(),
+            );
+            continue;
+        };
+        let value_start = indent + label.len() + 2;
+        let label_span = span(indent, indent + label.len());
+        let value_span = span(value_start, value_start + value.len());
+
+        if block.is_none() && HEADER_LABELS.contains(&label) {
+            if let Err(message) = check_value(label, value) {
+                report(
+                    &mut diagnostics,
+                    line_num,
+                    value_span,
+                    Severity::Error,
+                    message,
+                );
+            }
+            continue;
+        }
+
+        let Some(label) = FIELD_LABELS.iter().copied().find(|known| *known == label) else {
+            report(
+                &mut diagnostics,
+                line_num,
+                label_span,
+                Severity::Warning,
+                format!("unknown field '{}'; this line is ignored", label),
+            );
+            continue;
+        };
+
+        if label == "ID" {
+            if let Some(done) = block.take() {
+                finish_block(done, &mut diagnostics);
+            }
+            if !ids.insert(value) {
+                report(
+                    &mut diagnostics,
+                    line_num,
+                    value_span,
+                    Severity::Warning,
+                    format!("duplicate transaction ID '{}'", value),
+                );
+            }
+            block = Some(Block {
+                line: line_num,
+                id_span: value_span,
+                fields: HashSet::new(),
+            });
+            continue;
+        }
+
+        let Some(current) = block.as_mut() else {
+            report(
+                &mut diagnostics,
+                line_num,
+                label_span,
+                Severity::Warning,
+                format!(
+                    "'{}' outside a transaction is ignored; start one with 'ID: '",
+                    label
+                ),
+            );
+            continue;
+        };
+
+        // Each `Extra` line adds an entry of its own
+        if !current.fields.insert(label) && label != "Extra" {
+            report(
+                &mut diagnostics,
+                line_num,
+                label_span,
+                Severity::Warning,
+                format!("'{}' is set more than once; the last value wins", label),
+            );
+        }
+
+        if let Err(message) = check_value(label, value) {
+            report(
+                &mut diagnostics,
+                line_num,
+                value_span,
+                Severity::Error,
+                message,
+            );
+        }
+    }
+
+    if let Some(done) = block {
+        finish_block(done, &mut diagnostics);
+    }
+
+    diagnostics
+}
+
+fn check_value(label: &str, value: &str) -> Result<(), String> {
+    match label {
+        "Date" => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|_| ())
+            .map_err(|e| format!("invalid date, expected YYYY-MM-DD: {}", e)),
+        "ExecutedDate" => NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+            .map(|_| ())
+            .map_err(|e| format!("invalid executed date, expected YYYY-MM-DD HH:MM:SS: {}", e)),
+        "Type" => TransactionKind::from_str(value).map(|_| ()).map_err(|_| {
+            format!(
+                "invalid type, expected one of {}",
+                TRANSACTION_KINDS.join(", ")
+            )
+        }),
+        "Status" => TransactionStatus::from_str(value).map(|_| ()).map_err(|_| {
+            format!(
+                "invalid status, expected one of {}",
+                TRANSACTION_STATUSES.join(", ")
+            )
+        }),
+        "Amount" => {
+            let parts: Vec<&str> = value.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err("invalid amount, expected '<amount> <currency>'".to_string());
+            }
+            Decimal::from_str(parts[0])
+                .map(|_| ())
+                .map_err(|e| format!("invalid amount: {}", e))
+        }
+        "TaxAmount" => Decimal::from_str(value)
+            .map(|_| ())
+            .map_err(|e| format!("invalid tax amount: {}", e)),
+        "TaxRate" => Decimal::from_str(value.strip_suffix('%').unwrap_or(value).trim_end())
+            .map(|_| ())
+            .map_err(|e| format!("invalid tax rate: {}", e)),
+        "Extra" if !value.contains('=') => {
+            Err("invalid extra field, expected key=value".to_string())
+        }
+        "OpeningBalance" | "ClosingBalance" => {
+            check_value("Amount", value).map_err(|message| message.replace("amount", "balance"))
+        }
+        "GeneratedAt" => NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+            .map(|_| ())
+            .map_err(|e| {
+                format!(
+                    "invalid generation time, expected YYYY-MM-DD HH:MM:SS: {}",
+                    e
+                )
+            }),
+        "Period" => Period::from_str(value)
+            .map(|_| ())
+            .map_err(|_| "invalid period, expected YYYY-MM-DD..YYYY-MM-DD".to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn report(
+    diagnostics: &mut Vec<Diagnostic>,
+    line: u32,
+    (start, end): (u32, u32),
+    severity: Severity,
+    message: String,
+) {
+    diagnostics.push(Diagnostic {
+        line,
+        start,
+        end,
+        severity,
+        message,
+    });
+}
+
+fn finish_block(block: Block, diagnostics: &mut Vec<Diagnostic>) {
+    let missing: Vec<&str> = REQUIRED_FIELDS
+        .iter()
+        .copied()
+        .filter(|field| !block.fields.contains(field))
+        .collect();
+
+    if !missing.is_empty() {
+        report(
+            diagnostics,
+            block.line,
+            block.id_span,
+            Severity::Error,
+            format!("transaction is missing {}", missing.join(", ")),
+        );
+    }
+}
+
+/// Converts a byte offset within `line` to a UTF-16 column.
+pub fn utf16_col(line: &str, byte: usize) -> u32 {
+    line[..byte].encode_utf16().count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_document_has_no_diagnostics() {
+        let text = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../examples/transactions.txt"
+        ))
+        .unwrap();
+
+        assert_eq!(check(&text), vec![]);
+    }
+
+    #[test]
+    fn test_reports_every_problem_with_span() {
+        let text = "Account: ACC1\n\
+                    \n\
+                    ID: TX1\n\
+                    Date: 2024-13-01\n\
+                    Type: Rebate\n\
+                    Amount: 10.00 USD\n\
+                    Colour: blue\n\
+                    ---\n\
+                    ID: TX1\n\
+                    Description: Café\n";
+
+        let diagnostics = check(text);
+        let summary: Vec<(u32, u32, u32, Severity)> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.start, d.end, d.severity))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                (3, 6, 16, Severity::Error),
+                (4, 6, 12, Severity::Error),
+                (6, 0, 6, Severity::Warning),
+                (8, 4, 7, Severity::Warning),
+                (8, 4, 7, Severity::Error),
+            ]
+        );
+        assert_eq!(
+            diagnostics[4].message,
+            "transaction is missing Date, Type, Amount"
+        );
+
+        let header = check(
+            "OpeningBalance: 1.00 USD\nClosingBalance: many USD\n\
+             SourceSystem: CoreBank\nPeriod: 2024-01-31..2024-01-01\n",
+        );
+        assert_eq!(header.len(), 2);
+        assert_eq!((header[0].line, header[0].severity), (1, Severity::Error));
+        assert_eq!((header[1].line, header[1].severity), (3, Severity::Error));
+
+        let status =
+            check("ID: TX1\nDate: 2024-01-02\nType: Debit\nStatus: Posted\nAmount: 1 USD\n");
+        assert_eq!(status.len(), 1);
+        assert_eq!((status[0].line, status[0].start), (3, 8));
+        assert!(status[0].message.starts_with("invalid status"));
+    }
+}
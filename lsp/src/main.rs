@@ -0,0 +1,33 @@
+//! Language server for YPBank plain text statements.
+//!
+//! Speaks JSON-RPC over stdin/stdout and gives editors live diagnostics,
+//! formatting, and field-label completion for hand-edited `.ypb` files.
+
+mod diagnostics;
+mod rpc;
+mod server;
+
+use anyhow::Result;
+use server::{Flow, Server};
+use std::io::{self, BufReader, BufWriter};
+use std::process;
+
+fn main() -> Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = BufReader::new(stdin.lock());
+    let mut writer = BufWriter::new(stdout.lock());
+    let mut server = Server::default();
+
+    while let Some(message) = rpc::read_message(&mut reader)? {
+        let (responses, flow) = server.handle(&message);
+        for response in &responses {
+            rpc::write_message(&mut writer, response)?;
+        }
+        if let Flow::Exit(code) = flow {
+            process::exit(code);
+        }
+    }
+
+    Ok(())
+}
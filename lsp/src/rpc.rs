@@ -0,0 +1,76 @@
+//! JSON-RPC message framing over stdio.
+//!
+//! Messages are framed the way the Language Server Protocol specifies: a
+//! `Content-Length` header, a blank line, then the JSON body.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+/// Reads one framed message. Returns `None` at end of input.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .context("Invalid Content-Length header")?,
+                );
+            }
+        }
+    }
+
+    let Some(length) = content_length else {
+        bail!("Missing Content-Length header");
+    };
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let message = serde_json::from_slice(&body).context("Invalid JSON-RPC message")?;
+    Ok(Some(message))
+}
+
+/// Writes one framed message and flushes the writer.
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_message_roundtrip() {
+        let mut buffer = Vec::new();
+        write_message(
+            &mut buffer,
+            &json!({"jsonrpc": "2.0", "id": 1, "method": "ping"}),
+        )
+        .unwrap();
+        write_message(&mut buffer, &json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+
+        let mut reader = Cursor::new(buffer);
+        let first = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(first["method"], "ping");
+        let second = read_message(&mut reader).unwrap().unwrap();
+        assert_eq!(second["method"], "exit");
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+}
@@ -0,0 +1,277 @@
+//! Language server request handling.
+//!
+//! Supports full-document sync, diagnostics published on open and change,
+//! whole-document formatting through the text writer, and completion of
+//! field labels, transaction types and statuses.
+
+use crate::diagnostics::{self, utf16_col, FIELD_LABELS, TRANSACTION_KINDS, TRANSACTION_STATUSES};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// JSON-RPC error code for unknown methods.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code for malformed parameters.
+const INVALID_PARAMS: i64 = -32602;
+
+/// LSP `CompletionItemKind` values.
+const KIND_FIELD: u32 = 5;
+const KIND_ENUM_MEMBER: u32 = 20;
+
+/// Open documents and protocol state.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+/// What the main loop should do after a message was handled.
+pub enum Flow {
+    /// Keep reading messages
+    Continue,
+    /// Stop with the given process exit code
+    Exit(i32),
+}
+
+impl Server {
+    /// Handles one incoming message and returns the messages to send back.
+    pub fn handle(&mut self, message: &Value) -> (Vec<Value>, Flow) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        if method == "exit" {
+            return (
+                Vec::new(),
+                Flow::Exit(if self.shutdown_requested { 0 } else { 1 }),
+            );
+        }
+
+        let Some(id) = id else {
+            return (self.notification(method, params), Flow::Continue);
+        };
+
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/formatting" => self.format(params),
+            "textDocument/completion" => self.complete(params),
+            _ => Err((METHOD_NOT_FOUND, format!("method not found: {}", method))),
+        };
+
+        let response = match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {"code": code, "message": message},
+            }),
+        };
+        (vec![response], Flow::Continue)
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                let Some(text) = changes
+                    .and_then(|c| c.last())
+                    .and_then(|c| c["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.to_string(), text.to_string());
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return vec![publish(uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        }
+
+        let diagnostics = diagnostics::check(&self.documents[uri])
+            .into_iter()
+            .map(|d| {
+                json!({
+                    "range": range(d.line, d.start, d.line, d.end),
+                    "severity": d.severity as u32,
+                    "source": "ypbank",
+                    "message": d.message,
+                })
+            })
+            .collect();
+        vec![publish(uri, diagnostics)]
+    }
+
+    fn format(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let text = self.document(params)?;
+
+        // Leave documents that don't parse alone; diagnostics explain why.
+        let Ok(batch) = parser::formats::text::parse_text(Cursor::new(text)) else {
+            return Ok(json!([]));
+        };
+        let mut formatted = Vec::new();
+        parser::formats::text::write_text(&batch, &mut formatted)
+            .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+        let formatted = String::from_utf8(formatted).expect("text writer emits UTF-8");
+
+        if formatted == text {
+            return Ok(json!([]));
+        }
+
+        let end_line = text.split('\n').count() as u32;
+        Ok(json!([{"range": range(0, 0, end_line, 0), "newText": formatted}]))
+    }
+
+    fn complete(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let text = self.document(params)?;
+        let line_num = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or_default() as u32;
+
+        let line = text.lines().nth(line_num).unwrap_or_default();
+        let cursor = line
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([line.len()])
+            .find(|&i| utf16_col(line, i) >= character)
+            .unwrap_or(line.len());
+        let prefix = line[..cursor].trim_start();
+
+        let items: Vec<Value> = if prefix.starts_with("Type: ") {
+            TRANSACTION_KINDS
+                .iter()
+                .map(|kind| json!({"label": kind, "kind": KIND_ENUM_MEMBER}))
+                .collect()
+        } else if prefix.starts_with("Status: ") {
+            TRANSACTION_STATUSES
+                .iter()
+                .map(|status| json!({"label": status, "kind": KIND_ENUM_MEMBER}))
+                .collect()
+        } else if !prefix.contains(':') {
+            FIELD_LABELS
+                .iter()
+                .filter(|label| label.starts_with(prefix))
+                .map(|label| {
+                    json!({
+                        "label": label,
+                        "kind": KIND_FIELD,
+                        "insertText": format!("{}: ", label),
+                    })
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Value::Array(items))
+    }
+
+    fn document(&self, params: &Value) -> Result<&str, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        self.documents
+            .get(uri)
+            .map(String::as_str)
+            .ok_or_else(|| (INVALID_PARAMS, format!("unknown document: {}", uri)))
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "documentFormattingProvider": true,
+            "completionProvider": {"triggerCharacters": [" "]},
+        },
+        "serverInfo": {"name": "ypbank_lsp", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    })
+}
+
+fn range(start_line: u32, start: u32, end_line: u32, end: u32) -> Value {
+    json!({
+        "start": {"line": start_line, "character": start},
+        "end": {"line": end_line, "character": end},
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(server: &mut Server, text: &str) -> Vec<Value> {
+        let (out, _) = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {"textDocument": {"uri": "file:///a.ypb", "text": text}},
+        }));
+        out
+    }
+
+    fn request(server: &mut Server, method: &str, params: Value) -> Value {
+        let (mut out, _) = server.handle(&json!({
+            "jsonrpc": "2.0", "id": 7, "method": method, "params": params,
+        }));
+        out.remove(0)
+    }
+
+    #[test]
+    fn test_open_publishes_diagnostics() {
+        let mut server = Server::default();
+        let out = open(
+            &mut server,
+            "Account: A\n\nID: TX1\nDate: nope\nType: Debit\nAmount: 1 USD\n",
+        );
+
+        assert_eq!(out[0]["method"], "textDocument/publishDiagnostics");
+        let diagnostics = out[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({"line": 3, "character": 6})
+        );
+    }
+
+    #[test]
+    fn test_formatting_and_completion() {
+        let mut server = Server::default();
+        open(
+            &mut server,
+            "Account: A\n\nID: TX1\nType: Debit\nDate: 2024-01-02\nAmount: 1 USD\nCa",
+        );
+        let doc = json!({"uri": "file:///a.ypb"});
+
+        let edits = request(
+            &mut server,
+            "textDocument/formatting",
+            json!({"textDocument": doc}),
+        );
+        let new_text = edits["result"][0]["newText"].as_str().unwrap();
+        assert!(new_text.contains("Date: 2024-01-02\nType: Debit\n"));
+
+        let completion = request(
+            &mut server,
+            "textDocument/completion",
+            json!({"textDocument": doc, "position": {"line": 6, "character": 2}}),
+        );
+        assert_eq!(completion["result"][0]["insertText"], "Category: ");
+
+        let unknown = request(&mut server, "textDocument/hover", json!({}));
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,153 @@
+//! Parse and write throughput of each format.
+//!
+//! Every format is parsed and written at 10k and 1M transactions:
+//!
+//! ```bash
+//! cargo bench -p parser              # everything
+//! cargo bench -p parser -- csv/parse # one format and direction
+//! cargo bench -p parser -- 10000     # the small batches only
+//! ```
+
+use chrono::NaiveDate;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use parser::formats::csv::parse_csv_with_profile;
+use parser::formats::profile::BankProfile;
+use parser::{
+    Format, Money, ParseOptions, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::hint::black_box;
+
+const FORMATS: [Format; 6] = [
+    Format::Csv,
+    Format::Text,
+    Format::Json,
+    Format::Xml,
+    Format::Yaml,
+    Format::Binary,
+];
+
+const SIZES: [usize; 2] = [10_000, 1_000_000];
+
+/// A batch that looks like a real statement: a few accounts, currencies and
+/// categories repeated over many transactions, and descriptions that
+/// sometimes need quoting.
+fn batch(size: usize) -> TransactionBatch {
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+    let transactions = (0..size)
+        .map(|i| Transaction {
+            id: format!("TX{:08}", i),
+            posted_at: start + chrono::Days::new((i % 365) as u64),
+            executed_at: None,
+            kind: if i % 10 == 0 {
+                TransactionKind::Credit
+            } else {
+                TransactionKind::Debit
+            },
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new((i as i64 * 7919) % 1_000_000, 2),
+                currency: ["USD", "EUR", "RUB"][i % 3].to_string(),
+            },
+            description: match i % 4 {
+                0 => "Coffee, to go".to_string(),
+                1 => "Groceries \"Fresh\" market".to_string(),
+                _ => format!("Card payment {}", i),
+            },
+            account: Some(format!("ACC{}", i % 5)),
+            counterparty: (i % 2 == 0).then(|| "Store Inc".to_string()),
+            category: Some(["Food", "Transport", "Salary"][i % 3].to_string()),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        })
+        .collect();
+    TransactionBatch {
+        account_id: Some("ACC0".to_string()),
+        transactions,
+        balances: Vec::new(),
+        metadata: Default::default(),
+    }
+}
+
+fn encode(batch: &TransactionBatch, format: Format) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    parser::write(batch, &mut bytes, format).unwrap();
+    bytes
+}
+
+fn formats(c: &mut Criterion) {
+    for size in SIZES {
+        let batch = batch(size);
+        for format in FORMATS {
+            let input = encode(&batch, format);
+
+            let mut group = c.benchmark_group(format!("{}/parse", format));
+            group.throughput(Throughput::Bytes(input.len() as u64));
+            if size >= 1_000_000 {
+                group.sample_size(10);
+            }
+            group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+                b.iter(|| parser::parse(black_box(&input[..]), format).unwrap())
+            });
+            group.finish();
+
+            let mut group = c.benchmark_group(format!("{}/write", format));
+            group.throughput(Throughput::Bytes(input.len() as u64));
+            if size >= 1_000_000 {
+                group.sample_size(10);
+            }
+            group.bench_with_input(BenchmarkId::from_parameter(size), &batch, |b, batch| {
+                let mut output = Vec::with_capacity(input.len());
+                b.iter(|| {
+                    output.clear();
+                    parser::write(black_box(batch), &mut output, format).unwrap();
+                })
+            });
+            group.finish();
+        }
+    }
+}
+
+/// A bank profile for this crate's own CSV header, so that the profile parser
+/// reads the same input as [`parser::parse`].
+fn own_profile() -> BankProfile {
+    BankProfile::from_toml(
+        r#"
+sign = "kind_column"
+
+[columns]
+id = "TransactionId"
+posted_date = "PostedDate"
+kind = "Type"
+amount = "Amount"
+currency = "Currency"
+description = "Description"
+account = "Account"
+counterparty = "Counterparty"
+category = "Category"
+"#,
+    )
+    .unwrap()
+}
+
+fn profile(c: &mut Criterion) {
+    let profile = own_profile();
+    let options = ParseOptions::default();
+    let mut group = c.benchmark_group("profile/parse");
+    for size in SIZES {
+        let input = encode(&batch(size), Format::Csv);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        if size >= 1_000_000 {
+            group.sample_size(10);
+        }
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| parse_csv_with_profile(black_box(&input[..]), &profile, &options).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, formats, profile);
+criterion_main!(benches);
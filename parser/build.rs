@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/transactions.proto");
+    #[cfg(feature = "proto")]
+    {
+        // Use the vendored protoc so the build doesn't depend on a system install.
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        prost_build::compile_protos(&["proto/transactions.proto"], &["proto"])?;
+    }
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! [`Arbitrary`] implementations for property testing and fuzzing.
+//!
+//! With the `arbitrary` feature, [`Money`], [`Transaction`] and
+//! [`TransactionBatch`] can be built from unstructured bytes, as `cargo fuzz`
+//! and [`arbitrary`]-based property tests do. The generated
+//! values are valid data rather than arbitrary field contents, so that they
+//! exercise pipelines instead of tripping their input validation:
+//!
+//! - amounts have up to four decimal places and are non-negative, except for
+//!   transfers and reversals, whose amount carries its sign; currencies are
+//!   ISO 4217 codes;
+//! - dates lie between 2000 and 2099, execution times are whole seconds on
+//!   or up to three days before the posting date, and periods don't end
+//!   before they start;
+//! - IDs and text fields are non-empty words (descriptions may be empty)
+//!   without leading, trailing or repeated spaces, but do include non-ASCII
+//!   letters and the quotes, commas and markup characters that writers have
+//!   to escape; optional text fields are `None` rather than empty;
+//! - `extra` keys are names no format uses for a field of its own, and each
+//!   declared balance is in a different currency.
+//!
+//! Such values survive a round trip through every format that carries all
+//! their fields, which makes them suitable for fuzzing the format
+//! round-trips.
+//!
+//! # Examples
+//!
+//! ```
+//! use arbitrary::{Arbitrary, Unstructured};
+//! use parser::{Format, TransactionBatch};
+//!
+//! let data: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+//! let batch = TransactionBatch::arbitrary(&mut Unstructured::new(&data))?;
+//!
+//! let mut bytes = Vec::new();
+//! parser::write(&batch, &mut bytes, Format::Binary)?;
+//! assert_eq!(parser::parse(&bytes[..], Format::Binary)?, batch);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::{
+    Balance, BatchMetadata, Money, Period, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// Currencies amounts are generated in, with zero, two and three decimal
+/// places.
+const CURRENCIES: [&str; 7] = ["USD", "EUR", "GBP", "RUB", "CHF", "JPY", "BHD"];
+
+/// Keys of [`Transaction::extra`] entries.
+const EXTRA_KEYS: [&str; 4] = ["BankRef", "MCC", "Terminal", "Channel"];
+
+/// Characters of generated words.
+const WORD_CHARS: &[char] = &[
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B', 'C', 'K', 'M', 'P', 'T', 'X', '0', '1', '2', '3',
+    '4', '5', '6', '7', '8', '9', 'é', 'ü', 'ß', 'Ж', 'я', '€', '-', '.', ',', ';', '/', '&', '"',
+    '\'', '<', '>',
+];
+
+const KINDS: [TransactionKind; 7] = [
+    TransactionKind::Debit,
+    TransactionKind::Credit,
+    TransactionKind::Transfer,
+    TransactionKind::Fee,
+    TransactionKind::Interest,
+    TransactionKind::Refund,
+    TransactionKind::Reversal,
+];
+
+const STATUSES: [TransactionStatus; 3] = [
+    TransactionStatus::Booked,
+    TransactionStatus::Pending,
+    TransactionStatus::Cancelled,
+];
+
+impl<'a> Arbitrary<'a> for Money {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Money {
+            amount: amount(u, 1_000_000_000_000)?,
+            currency: u.choose(&CURRENCIES)?.to_string(),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Transaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let posted_at = date(u)?;
+        let executed_at = if u.arbitrary()? {
+            let days_before = u.int_in_range(0..=3)?;
+            Some(date_time(u, posted_at - Duration::days(days_before))?)
+        } else {
+            None
+        };
+        let kind = *u.choose(&KINDS)?;
+        let mut amount = Money::arbitrary(u)?;
+        if kind.sign().is_none() && u.arbitrary()? {
+            amount.amount.set_sign_negative(true);
+        }
+        let (tax_amount, tax_rate) = if u.ratio(1, 4)? {
+            let rate = Decimal::new(u.int_in_range(0..=300)?, 1).normalize();
+            let tax = (amount.amount.abs() * rate / (Decimal::ONE_HUNDRED + rate)).round_dp(2);
+            (Some(tax), Some(rate))
+        } else {
+            (None, None)
+        };
+
+        let mut extra = BTreeMap::new();
+        for _ in 0..u.int_in_range(0..=2)? {
+            extra.insert(u.choose(&EXTRA_KEYS)?.to_string(), words(u, 1)?);
+        }
+
+        Ok(Transaction {
+            id: word(u)?,
+            posted_at,
+            executed_at,
+            kind,
+            status: *u.choose(&STATUSES)?,
+            amount,
+            description: words(u, 0)?,
+            account: optional_words(u)?,
+            counterparty: optional_words(u)?,
+            category: optional_words(u)?,
+            tax_amount,
+            tax_rate,
+            extra,
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        // Roughly what a transaction with short text fields takes, so that
+        // batches get as many transactions as their input can fill
+        (40, None)
+    }
+}
+
+impl<'a> Arbitrary<'a> for TransactionBatch {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut balances = Vec::new();
+        for currency in CURRENCIES {
+            if u.ratio(1, 4)? {
+                balances.push(Balance {
+                    currency: currency.to_string(),
+                    opening: amount(u, 100_000_000_000)?,
+                    closing: amount(u, 100_000_000_000)?,
+                });
+            }
+        }
+
+        let metadata = BatchMetadata {
+            generated_at: if u.arbitrary()? {
+                let day = date(u)?;
+                Some(date_time(u, day)?)
+            } else {
+                None
+            },
+            source_system: optional_words(u)?,
+            period: if u.arbitrary()? {
+                let start = date(u)?;
+                let end = start + Duration::days(u.int_in_range(0..=366)?);
+                Some(Period { start, end })
+            } else {
+                None
+            },
+            schema_version: if u.arbitrary()? {
+                Some(format!(
+                    "{}.{}",
+                    u.int_in_range(1..=9u8)?,
+                    u.int_in_range(0..=9u8)?
+                ))
+            } else {
+                None
+            },
+        };
+
+        Ok(TransactionBatch {
+            account_id: if u.arbitrary()? { Some(word(u)?) } else { None },
+            transactions: (0..u.arbitrary_len::<Transaction>()?)
+                .map(|_| Transaction::arbitrary(u))
+                .collect::<Result<_>>()?,
+            balances,
+            metadata,
+        })
+    }
+}
+
+/// A non-negative amount of up to `max` units of its last decimal place, with
+/// zero to four decimal places.
+fn amount(u: &mut Unstructured<'_>, max: i64) -> Result<Decimal> {
+    Ok(Decimal::new(
+        u.int_in_range(0..=max)?,
+        u.int_in_range(0..=4)?,
+    ))
+}
+
+fn date(u: &mut Unstructured<'_>) -> Result<NaiveDate> {
+    let first = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+    Ok(first + Duration::days(u.int_in_range(0..=36_524)?))
+}
+
+fn date_time(u: &mut Unstructured<'_>, date: NaiveDate) -> Result<NaiveDateTime> {
+    let seconds = u.int_in_range(0..=86_399)?;
+    Ok(date.and_hms_opt(0, 0, 0).expect("valid time") + Duration::seconds(seconds))
+}
+
+fn word(u: &mut Unstructured<'_>) -> Result<String> {
+    let len = u.int_in_range(1..=12)?;
+    (0..len).map(|_| u.choose(WORD_CHARS).copied()).collect()
+}
+
+/// Between `min` and four words separated by single spaces.
+fn words(u: &mut Unstructured<'_>, min: usize) -> Result<String> {
+    let count = u.int_in_range(min..=4)?;
+    let words = (0..count).map(|_| word(u)).collect::<Result<Vec<_>>>()?;
+    Ok(words.join(" "))
+}
+
+fn optional_words(u: &mut Unstructured<'_>) -> Result<Option<String>> {
+    if u.arbitrary()? {
+        Ok(Some(words(u, 1)?))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    /// Deterministic pseudo-random input, standing in for a fuzzer's.
+    fn data(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                (state >> 33) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arbitrary_values_are_valid() {
+        let mut count = 0;
+        for seed in 0..50 {
+            let data = data(seed, 2048);
+            let batch = TransactionBatch::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            count += batch.transactions.len();
+            for tx in &batch.transactions {
+                assert!(!tx.id.is_empty() && !tx.id.contains(' '));
+                assert!(tx.kind.sign().is_none() || !tx.amount.amount.is_sign_negative());
+                assert!(tx.executed_at.is_none_or(|dt| dt.date() <= tx.posted_at));
+                assert!(crate::currency::is_iso_4217(&tx.amount.currency));
+                assert_eq!(tx.description.trim(), tx.description);
+            }
+            if let Some(period) = batch.metadata.period {
+                assert!(period.start <= period.end);
+            }
+        }
+        assert!(count >= 100, "only {} transactions", count);
+    }
+
+    #[test]
+    fn test_arbitrary_batches_roundtrip() {
+        for seed in 0..50 {
+            let data = data(seed, 2048);
+            let batch = TransactionBatch::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            for format in [Format::Text, Format::Binary] {
+                let mut bytes = Vec::new();
+                crate::write(&batch, &mut bytes, format).unwrap();
+                let parsed = crate::parse(&bytes[..], format).unwrap();
+                assert_eq!(parsed, batch, "seed {} in {}", seed, format);
+            }
+        }
+    }
+}
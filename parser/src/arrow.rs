@@ -0,0 +1,513 @@
+//! Apache Arrow interop.
+//!
+//! [`TransactionBatch::to_arrow`] and [`TransactionBatch::from_arrow`]
+//! convert between a batch and an Arrow [`RecordBatch`] with one row per
+//! transaction, so transactions can be handed to DataFusion, Polars or any
+//! other Arrow-based engine without a detour through CSV.
+//!
+//! The record batch has the columns `id`, `posted_at` (`Date32`),
+//! `executed_at` (`Timestamp(Microsecond)`), `kind`, `status`, `amount`
+//! (`Decimal128(38, s)`), `currency`, `description`, `account`,
+//! `counterparty`, `category`, `tax_amount` and `tax_rate`
+//! (`Decimal128(38, s)`). Each decimal column uses the largest scale among
+//! its values, so every amount is represented exactly. The account ID and
+//! the declared balances are kept in the schema metadata under
+//! `ypbank.account_id` and `ypbank.balances`.
+
+use crate::{
+    Balance, Error, Money, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use arrow_array::builder::Decimal128Builder;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Date32Type, Decimal128Type, TimestampMicrosecondType, TimestampMillisecondType,
+    TimestampNanosecondType, TimestampSecondType,
+};
+use arrow_array::{
+    Array, ArrayRef, Date32Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Schema metadata key holding the batch account ID.
+pub const ACCOUNT_ID_KEY: &str = "ypbank.account_id";
+/// Schema metadata key holding the declared balances, one
+/// `CURRENCY OPENING CLOSING` line per currency.
+pub const BALANCES_KEY: &str = "ypbank.balances";
+/// Widest precision of an Arrow `Decimal128`.
+const DECIMAL_PRECISION: u8 = 38;
+/// Days from 0001-01-01 (chrono's day 1) to the Unix epoch.
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+impl TransactionBatch {
+    /// Converts the batch into an Arrow record batch, one row per
+    /// transaction.
+    ///
+    /// See the [module documentation](crate::arrow) for the schema.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an amount cannot be represented at its column's
+    /// scale within 38 digits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::TransactionBatch;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let batch = TransactionBatch::default();
+    /// let record_batch = batch.to_arrow()?;
+    /// assert_eq!(record_batch.num_columns(), 13);
+    /// assert_eq!(TransactionBatch::from_arrow(&record_batch)?, batch);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_arrow(&self) -> Result<RecordBatch> {
+        let txs = &self.transactions;
+        let amount_scale = max_scale(txs.iter().map(|tx| tx.amount.amount));
+        let tax_amount_scale = max_scale(txs.iter().filter_map(|tx| tx.tax_amount));
+        let tax_rate_scale = max_scale(txs.iter().filter_map(|tx| tx.tax_rate));
+
+        let mut metadata = HashMap::new();
+        if let Some(account) = &self.account_id {
+            metadata.insert(ACCOUNT_ID_KEY.to_string(), account.clone());
+        }
+        if !self.balances.is_empty() {
+            let balances: Vec<String> = self
+                .balances
+                .iter()
+                .map(|b| format!("{} {} {}", b.currency, b.opening, b.closing))
+                .collect();
+            metadata.insert(BALANCES_KEY.to_string(), balances.join("\n"));
+        }
+
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("posted_at", DataType::Date32, false),
+            Field::new(
+                "executed_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("status", DataType::Utf8, false),
+            Field::new("amount", decimal_type(amount_scale), false),
+            Field::new("currency", DataType::Utf8, false),
+            Field::new("description", DataType::Utf8, false),
+            Field::new("account", DataType::Utf8, true),
+            Field::new("counterparty", DataType::Utf8, true),
+            Field::new("category", DataType::Utf8, true),
+            Field::new("tax_amount", decimal_type(tax_amount_scale), true),
+            Field::new("tax_rate", decimal_type(tax_rate_scale), true),
+        ])
+        .with_metadata(metadata);
+
+        let columns: Vec<ArrayRef> = vec![
+            strings(txs.iter().map(|tx| Some(tx.id.as_str()))),
+            Arc::new(Date32Array::from_iter_values(
+                txs.iter().map(|tx| date_to_days(tx.posted_at)),
+            )),
+            Arc::new(TimestampMicrosecondArray::from_iter(txs.iter().map(|tx| {
+                tx.executed_at.map(|dt| dt.and_utc().timestamp_micros())
+            }))),
+            strings(txs.iter().map(|tx| Some(tx.kind.to_string()))),
+            strings(txs.iter().map(|tx| Some(tx.status.to_string()))),
+            decimals(txs.iter().map(|tx| Some(tx.amount.amount)), amount_scale)?,
+            strings(txs.iter().map(|tx| Some(tx.amount.currency.as_str()))),
+            strings(txs.iter().map(|tx| Some(tx.description.as_str()))),
+            strings(txs.iter().map(|tx| tx.account.as_deref())),
+            strings(txs.iter().map(|tx| tx.counterparty.as_deref())),
+            strings(txs.iter().map(|tx| tx.category.as_deref())),
+            decimals(txs.iter().map(|tx| tx.tax_amount), tax_amount_scale)?,
+            decimals(txs.iter().map(|tx| tx.tax_rate), tax_rate_scale)?,
+        ];
+
+        RecordBatch::try_new(Arc::new(schema), columns)
+            .map_err(|e| Error::parse("Arrow", e.to_string()))
+    }
+
+    /// Builds a batch from an Arrow record batch.
+    ///
+    /// Reads the columns produced by [`TransactionBatch::to_arrow`] by name;
+    /// their order doesn't matter and other columns are ignored. Only `id`,
+    /// `posted_at`, `kind`, `amount` and `currency` are required. String
+    /// columns may also be `LargeUtf8` or `Utf8View` and `executed_at` may
+    /// use any timestamp unit, as produced by other Arrow engines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a required column is missing, a column has an
+    /// unexpected type, or a value cannot be converted.
+    pub fn from_arrow(batch: &RecordBatch) -> Result<Self> {
+        let (account_id, balances) = read_metadata(batch.schema().metadata(), "Arrow")?;
+        let mut transactions = Vec::with_capacity(batch.num_rows());
+        read_transactions(batch, &mut transactions, "Arrow")?;
+        Ok(TransactionBatch {
+            account_id,
+            transactions,
+            balances,
+            metadata: Default::default(),
+        })
+    }
+}
+
+/// Reads the account ID and balances from schema metadata.
+pub(crate) fn read_metadata(
+    metadata: &HashMap<String, String>,
+    format: &'static str,
+) -> Result<(Option<String>, Vec<Balance>)> {
+    let balances = match metadata.get(BALANCES_KEY) {
+        Some(value) => value
+            .lines()
+            .map(|line| parse_balance(line, format))
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    Ok((metadata.get(ACCOUNT_ID_KEY).cloned(), balances))
+}
+
+fn parse_balance(line: &str, format: &'static str) -> Result<Balance> {
+    let mut parts = line.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(currency), Some(opening), Some(closing)) => Ok(Balance {
+            currency: currency.to_string(),
+            opening: parse_decimal(opening, "opening balance", format)?,
+            closing: parse_decimal(closing, "closing balance", format)?,
+        }),
+        _ => Err(Error::parse(
+            format,
+            format!("invalid {} metadata: {}", BALANCES_KEY, line),
+        )),
+    }
+}
+
+fn parse_decimal(value: &str, name: &str, format: &'static str) -> Result<Decimal> {
+    Decimal::from_str(value)
+        .map_err(|e| Error::parse(format, format!("invalid {}: {}", name, e)).with_source(e))
+}
+
+fn decimal_type(scale: u32) -> DataType {
+    DataType::Decimal128(DECIMAL_PRECISION, scale as i8)
+}
+
+fn max_scale(values: impl Iterator<Item = Decimal>) -> u32 {
+    values.map(|value| value.scale()).max().unwrap_or(0)
+}
+
+fn date_to_days(date: NaiveDate) -> i32 {
+    date.num_days_from_ce() - UNIX_EPOCH_DAYS_FROM_CE
+}
+
+fn strings<S: AsRef<str>>(values: impl Iterator<Item = Option<S>>) -> ArrayRef {
+    Arc::new(values.collect::<StringArray>())
+}
+
+fn decimals(values: impl Iterator<Item = Option<Decimal>>, scale: u32) -> Result<ArrayRef> {
+    let mut builder = Decimal128Builder::new();
+    for value in values {
+        match value {
+            Some(mut value) => {
+                value.rescale(scale);
+                if value.scale() != scale {
+                    return Err(Error::parse(
+                        "Arrow",
+                        format!("{} does not fit a decimal with scale {}", value, scale),
+                    ));
+                }
+                builder.append_value(value.mantissa());
+            }
+            None => builder.append_null(),
+        }
+    }
+    let array = builder
+        .finish()
+        .with_precision_and_scale(DECIMAL_PRECISION, scale as i8)
+        .map_err(|e| Error::parse("Arrow", e.to_string()))?;
+    Ok(Arc::new(array))
+}
+
+/// Typed access to the columns of one record batch.
+struct Columns<'a> {
+    batch: &'a RecordBatch,
+    format: &'static str,
+}
+
+impl<'a> Columns<'a> {
+    fn column(&self, name: &str, required: bool) -> Result<Option<&'a ArrayRef>> {
+        match self.batch.column_by_name(name) {
+            Some(column) => Ok(Some(column)),
+            None if required => Err(Error::parse(
+                self.format,
+                format!("missing column: {}", name),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn type_error(&self, name: &str, column: &ArrayRef) -> Error {
+        Error::parse(
+            self.format,
+            format!("column {} has unexpected type {}", name, column.data_type()),
+        )
+    }
+
+    fn strings(&self, name: &str, required: bool) -> Result<Vec<Option<String>>> {
+        let Some(column) = self.column(name, required)? else {
+            return Ok(vec![None; self.batch.num_rows()]);
+        };
+        let owned = |v: Option<&str>| v.map(str::to_string);
+        match column.data_type() {
+            DataType::Utf8 => Ok(column.as_string::<i32>().iter().map(owned).collect()),
+            DataType::LargeUtf8 => Ok(column.as_string::<i64>().iter().map(owned).collect()),
+            DataType::Utf8View => Ok(column.as_string_view().iter().map(owned).collect()),
+            _ => Err(self.type_error(name, column)),
+        }
+    }
+
+    fn decimals(&self, name: &str, required: bool) -> Result<Vec<Option<Decimal>>> {
+        let Some(column) = self.column(name, required)? else {
+            return Ok(vec![None; self.batch.num_rows()]);
+        };
+        let array = column
+            .as_primitive_opt::<Decimal128Type>()
+            .ok_or_else(|| self.type_error(name, column))?;
+        let scale = array.scale() as u32;
+        array
+            .iter()
+            .map(|value| {
+                value
+                    .map(|v| {
+                        Decimal::try_from_i128_with_scale(v, scale).map_err(|e| {
+                            Error::parse(self.format, format!("invalid {}: {}", name, e))
+                                .with_source(e)
+                        })
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+
+    fn dates(&self, name: &str) -> Result<Vec<Option<NaiveDate>>> {
+        let column = self.column(name, true)?.expect("required column");
+        let array = column
+            .as_primitive_opt::<Date32Type>()
+            .ok_or_else(|| self.type_error(name, column))?;
+        Ok(array
+            .iter()
+            .map(|days| {
+                days.and_then(|d| NaiveDate::from_num_days_from_ce_opt(d + UNIX_EPOCH_DAYS_FROM_CE))
+            })
+            .collect())
+    }
+
+    fn timestamps(&self, name: &str) -> Result<Vec<Option<NaiveDateTime>>> {
+        let Some(column) = self.column(name, false)? else {
+            return Ok(vec![None; self.batch.num_rows()]);
+        };
+        let micros: Vec<Option<i64>> = match column.data_type() {
+            DataType::Timestamp(TimeUnit::Second, _) => column
+                .as_primitive::<TimestampSecondType>()
+                .iter()
+                .map(|v| v.map(|v| v * 1_000_000))
+                .collect(),
+            DataType::Timestamp(TimeUnit::Millisecond, _) => column
+                .as_primitive::<TimestampMillisecondType>()
+                .iter()
+                .map(|v| v.map(|v| v * 1_000))
+                .collect(),
+            DataType::Timestamp(TimeUnit::Microsecond, _) => column
+                .as_primitive::<TimestampMicrosecondType>()
+                .iter()
+                .collect(),
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => column
+                .as_primitive::<TimestampNanosecondType>()
+                .iter()
+                .map(|v| v.map(|v| v / 1_000))
+                .collect(),
+            _ => return Err(self.type_error(name, column)),
+        };
+        Ok(micros
+            .into_iter()
+            .map(|v| {
+                v.and_then(DateTime::from_timestamp_micros)
+                    .map(|dt| dt.naive_utc())
+            })
+            .collect())
+    }
+}
+
+/// Appends the transactions of one record batch, reporting errors as
+/// `format`.
+pub(crate) fn read_transactions(
+    batch: &RecordBatch,
+    transactions: &mut Vec<Transaction>,
+    format: &'static str,
+) -> Result<()> {
+    let columns = Columns { batch, format };
+
+    let ids = columns.strings("id", true)?;
+    let posted = columns.dates("posted_at")?;
+    let executed = columns.timestamps("executed_at")?;
+    let kinds = columns.strings("kind", true)?;
+    let statuses = columns.strings("status", false)?;
+    let amounts = columns.decimals("amount", true)?;
+    let currencies = columns.strings("currency", true)?;
+    let descriptions = columns.strings("description", false)?;
+    let accounts = columns.strings("account", false)?;
+    let counterparties = columns.strings("counterparty", false)?;
+    let categories = columns.strings("category", false)?;
+    let tax_amounts = columns.decimals("tax_amount", false)?;
+    let tax_rates = columns.decimals("tax_rate", false)?;
+
+    for row in 0..batch.num_rows() {
+        let row_error = |message: &str| {
+            Error::parse(
+                format,
+                format!("row {}: {}", transactions.len() + 1, message),
+            )
+        };
+
+        let kind = kinds[row]
+            .as_deref()
+            .ok_or_else(|| row_error("missing kind"))
+            .and_then(|kind| {
+                TransactionKind::from_str(kind)
+                    .map_err(|e| row_error(&format!("invalid kind: {}", e)))
+            })?;
+        let status = statuses[row]
+            .as_deref()
+            .map(TransactionStatus::from_str)
+            .transpose()
+            .map_err(|e| row_error(&format!("invalid status: {}", e)))?
+            .unwrap_or_default();
+
+        transactions.push(Transaction {
+            id: ids[row].clone().ok_or_else(|| row_error("missing id"))?,
+            posted_at: posted[row].ok_or_else(|| row_error("missing or invalid posted_at"))?,
+            executed_at: executed[row],
+            kind,
+            status,
+            amount: Money {
+                amount: amounts[row].ok_or_else(|| row_error("missing amount"))?,
+                currency: currencies[row]
+                    .clone()
+                    .ok_or_else(|| row_error("missing currency"))?,
+            },
+            description: descriptions[row].clone().unwrap_or_default(),
+            account: accounts[row].clone(),
+            counterparty: counterparties[row].clone(),
+            category: categories[row].clone(),
+            tax_amount: tax_amounts[row],
+            tax_rate: tax_rates[row],
+            extra: BTreeMap::new(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int64Array, LargeStringArray, TimestampMillisecondArray};
+
+    #[test]
+    fn test_arrow_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![Transaction {
+                id: "TX001".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                    .unwrap()
+                    .and_hms_micro_opt(10, 30, 0, 250),
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(120050, 2),
+                    currency: "RUB".to_string(),
+                },
+                description: "Payment".to_string(),
+                account: None,
+                counterparty: Some("ООО Ромашка".to_string()),
+                category: None,
+                tax_amount: Some(Decimal::new(20008, 2)),
+                tax_rate: Some(Decimal::new(20, 0)),
+                extra: BTreeMap::new(),
+            }],
+            balances: vec![Balance {
+                currency: "RUB".to_string(),
+                opening: Decimal::new(500000, 2),
+                closing: Decimal::new(379950, 2),
+            }],
+            metadata: Default::default(),
+        };
+
+        let record_batch = batch.to_arrow().unwrap();
+        assert_eq!(record_batch.num_rows(), 1);
+        assert_eq!(
+            record_batch
+                .schema()
+                .field_with_name("amount")
+                .unwrap()
+                .data_type(),
+            &DataType::Decimal128(38, 2)
+        );
+        assert_eq!(TransactionBatch::from_arrow(&record_batch).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_from_foreign_record_batch() {
+        let schema = Schema::new(vec![
+            Field::new("rowid", DataType::Int64, false),
+            Field::new("currency", DataType::LargeUtf8, false),
+            Field::new("amount", DataType::Decimal128(10, 3), false),
+            Field::new("kind", DataType::LargeUtf8, false),
+            Field::new("posted_at", DataType::Date32, false),
+            Field::new(
+                "executed_at",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                true,
+            ),
+            Field::new("id", DataType::LargeUtf8, false),
+        ]);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(vec![1])),
+            Arc::new(LargeStringArray::from(vec!["USD"])),
+            Arc::new(
+                arrow_array::Decimal128Array::from(vec![12_500])
+                    .with_precision_and_scale(10, 3)
+                    .unwrap(),
+            ),
+            Arc::new(LargeStringArray::from(vec!["Credit"])),
+            Arc::new(Date32Array::from(vec![19_737])),
+            Arc::new(TimestampMillisecondArray::from(vec![Some(
+                1_705_314_600_000,
+            )])),
+            Arc::new(LargeStringArray::from(vec!["TX1"])),
+        ];
+        let record_batch = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+
+        let batch = TransactionBatch::from_arrow(&record_batch).unwrap();
+        let tx = &batch.transactions[0];
+        assert_eq!(tx.amount.amount, Decimal::new(125, 1));
+        assert_eq!(tx.posted_at, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+        assert_eq!(
+            tx.executed_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15)
+                .unwrap()
+                .and_hms_opt(10, 30, 0)
+        );
+        assert_eq!(tx.description, "");
+
+        let err =
+            TransactionBatch::from_arrow(&record_batch.project(&[0, 1]).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("missing column"));
+    }
+}
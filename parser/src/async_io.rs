@@ -0,0 +1,397 @@
+//! Parsing and writing over tokio's asynchronous I/O.
+//!
+//! Services that receive transaction feeds over the network can use these
+//! functions from async code instead of calling the synchronous API through
+//! `spawn_blocking`. [`parse_async`] and [`write_async`] read and write a
+//! whole batch. [`parse_stream`] yields the transactions as they arrive, one
+//! CSV line at a time, so a feed of any length is parsed in constant memory.
+//!
+//! # Examples
+//!
+//! ```
+//! use futures::StreamExt;
+//! use parser::async_io::parse_stream;
+//! use parser::Format;
+//!
+//! # fn main() -> Result<(), parser::Error> {
+//! # futures::executor::block_on(async {
+//! let feed: &[u8] = b"TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,\
+//!     Description,Account,Counterparty,Category\n\
+//!     TX1,2024-01-15,,Debit,150.50,USD,Groceries,ACC123,,Food\n";
+//! let mut transactions = std::pin::pin!(parse_stream(feed, Format::Csv));
+//! while let Some(tx) = transactions.next().await {
+//!     println!("{}", tx?.id);
+//! }
+//! # Ok(())
+//! # })
+//! # }
+//! ```
+
+use crate::formats::csv::CsvDecoder;
+use crate::formats::{self, ENCODING_SAMPLE};
+use crate::{
+    Error, Format, Limits, ParseOptions, Result, Transaction, TransactionBatch, WriteOptions,
+};
+use futures::stream::{self, Stream};
+use std::io::Cursor;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, Chain, Take,
+};
+
+/// Parses a batch of transactions from an asynchronous reader.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `format` - The format of the input data
+///
+/// # Returns
+///
+/// Returns the parsed batch, or an [`Error`] in the same cases as
+/// [`crate::parse`].
+pub async fn parse_async<R>(reader: R, format: Format) -> Result<TransactionBatch>
+where
+    R: AsyncRead + Unpin,
+{
+    parse_async_with(reader, format, &ParseOptions::default()).await
+}
+
+/// Parses a batch of transactions from an asynchronous reader with the given
+/// options.
+///
+/// The input is read to the end, then parsed as [`crate::parse_with`] does.
+/// No more than [`Limits::max_input_bytes`] is buffered.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `format` - The format of the input data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns the parsed batch, or an [`Error`] in the same cases as
+/// [`crate::parse_with`].
+pub async fn parse_async_with<R>(
+    reader: R,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<TransactionBatch>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut bytes = Vec::new();
+    capped(reader, &options.limits)
+        .read_to_end(&mut bytes)
+        .await?;
+    crate::parse_with(&bytes[..], format, options)
+}
+
+/// Writes a batch of transactions to an asynchronous writer.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the transaction data to
+/// * `format` - The desired output format
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+pub async fn write_async<W>(batch: &TransactionBatch, writer: &mut W, format: Format) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_async_with(batch, writer, format, &WriteOptions::default()).await
+}
+
+/// Writes a batch of transactions to an asynchronous writer with the given
+/// options.
+///
+/// The output is encoded as [`crate::write_with`] encodes it, then written
+/// and flushed.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the transaction data to
+/// * `format` - The desired output format
+/// * `options` - How to write the batch
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+pub async fn write_async_with<W>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut bytes = Vec::new();
+    crate::write_with(batch, &mut bytes, format, options)?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Parses transactions from an asynchronous reader as they arrive.
+///
+/// Equivalent to [`parse_stream_with`] with default options.
+pub fn parse_stream<R>(reader: R, format: Format) -> impl Stream<Item = Result<Transaction>>
+where
+    R: AsyncRead + Unpin,
+{
+    parse_stream_with(reader, format, &ParseOptions::default())
+}
+
+/// Parses transactions from an asynchronous reader as they arrive, with the
+/// given options.
+///
+/// UTF-8 CSV input is parsed one line at a time, and each transaction is
+/// yielded as soon as its line has been read. Input in other formats and
+/// encodings is read to the end and parsed as [`crate::parse_with`] does
+/// before the first transaction is yielded. Either way, the stream fails with
+/// [`Error::LimitExceeded`] once the input goes past
+/// [`Limits::max_input_bytes`], without buffering more than that.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `format` - The format of the input data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns a stream of the parsed transactions. The stream ends after the
+/// first [`Error`], in the same cases as [`crate::parse_with`].
+pub fn parse_stream_with<R>(
+    reader: R,
+    format: Format,
+    options: &ParseOptions,
+) -> impl Stream<Item = Result<Transaction>>
+where
+    R: AsyncRead + Unpin,
+{
+    let state = State::Start(reader, format, Box::new(options.clone()));
+    stream::unfold(state, State::next)
+}
+
+/// What [`parse_stream_with`] reads next.
+enum State<R> {
+    /// Nothing has been read yet
+    Start(R, Format, Box<ParseOptions>),
+    /// CSV input, parsed line by line
+    Lines(Box<CsvLines<Take<R>>>),
+    /// The transactions of input parsed as a whole
+    Parsed(std::vec::IntoIter<Transaction>),
+    Done,
+}
+
+struct CsvLines<R> {
+    reader: BufReader<Chain<Cursor<Vec<u8>>, R>>,
+    /// Index of the next line
+    index: usize,
+    /// Bytes read so far
+    read: u64,
+    /// [`Limits::max_input_bytes`]
+    limit: Option<u64>,
+    decoder: CsvDecoder,
+}
+
+impl<R: AsyncRead + Unpin> CsvLines<R> {
+    /// Reads the next line without its line ending, or `None` at the end of
+    /// the input.
+    async fn next_line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line).await?;
+        self.read += read as u64;
+        if let Some(limit) = self.limit.filter(|&limit| self.read > limit) {
+            return Err(Error::LimitExceeded(format!(
+                "input longer than {} bytes",
+                limit
+            )));
+        }
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+}
+
+impl<R: AsyncRead + Unpin> State<R> {
+    /// Reads the next transaction, returning it with the state after it.
+    async fn next(self) -> Option<(Result<Transaction>, Self)> {
+        let mut state = self;
+        loop {
+            state = match state {
+                State::Start(reader, format, options) => {
+                    match start(reader, format, *options).await {
+                        Ok(state) => state,
+                        Err(e) => return Some((Err(e), State::Done)),
+                    }
+                }
+                State::Lines(mut csv) => {
+                    if csv.decoder.is_full() {
+                        return None;
+                    }
+                    let line = match csv.next_line().await {
+                        Ok(Some(line)) => line,
+                        Ok(None) if csv.decoder.has_header() => return None,
+                        Ok(None) => {
+                            let e = Error::parse("CSV", "empty file");
+                            return Some((Err(e), State::Done));
+                        }
+                        Err(e) => return Some((Err(e), State::Done)),
+                    };
+                    let tx = csv.decoder.decode(csv.index, &line);
+                    csv.index += 1;
+                    match tx {
+                        Ok(Some(tx)) => return Some((Ok(tx), State::Lines(csv))),
+                        Ok(None) => State::Lines(csv),
+                        Err(e) => return Some((Err(e), State::Done)),
+                    }
+                }
+                State::Parsed(mut transactions) => {
+                    let tx = transactions.next()?;
+                    return Some((Ok(tx), State::Parsed(transactions)));
+                }
+                State::Done => return None,
+            }
+        }
+    }
+}
+
+/// Reads the start of the input to tell whether it can be parsed line by
+/// line, and parses it as a whole if not.
+async fn start<R: AsyncRead + Unpin>(
+    reader: R,
+    format: Format,
+    options: ParseOptions,
+) -> Result<State<R>> {
+    let mut reader = capped(reader, &options.limits);
+    let mut bytes = Vec::new();
+    if format == Format::Csv {
+        (&mut reader)
+            .take(ENCODING_SAMPLE as u64)
+            .read_to_end(&mut bytes)
+            .await?;
+        let encoding = formats::resolve_encoding(options.encoding.as_deref(), &bytes)?;
+        if encoding == encoding_rs::UTF_8 {
+            return Ok(State::Lines(Box::new(CsvLines {
+                reader: BufReader::new(Cursor::new(bytes).chain(reader)),
+                index: 0,
+                read: 0,
+                limit: options.limits.max_input_bytes,
+                decoder: CsvDecoder::new(&options),
+            })));
+        }
+    }
+    reader.read_to_end(&mut bytes).await?;
+    let batch = crate::parse_with(&bytes[..], format, &options)?;
+    Ok(State::Parsed(batch.transactions.into_iter()))
+}
+
+/// Reads one byte past [`Limits::max_input_bytes`], so that the parser can
+/// tell input that ends right at the cap from input that goes on.
+fn capped<R: AsyncRead>(reader: R, limits: &Limits) -> Take<R> {
+    let limit = limits
+        .max_input_bytes
+        .map_or(u64::MAX, |max| max.saturating_add(1));
+    reader.take(limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, StreamExt, TryStreamExt};
+
+    const CSV: &str = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,\
+                       Description,Account,Counterparty,Category\n\
+                       TX1,2024-01-15,,Credit,5000.00,USD,Salary,ACC1,Employer,\n\
+                       \n\
+                       TX2,2024-01-16,,Debit,150.50,USD,Groceries,ACC1,,Food\n";
+
+    #[test]
+    fn test_parse_and_write() {
+        let batch = crate::parse(CSV.as_bytes(), Format::Csv).unwrap();
+        for format in [Format::Csv, Format::Json, Format::Binary] {
+            let mut output = Vec::new();
+            block_on(write_async(&batch, &mut output, format)).unwrap();
+            let mut expected = Vec::new();
+            crate::write(&batch, &mut expected, format).unwrap();
+            assert_eq!(output, expected);
+
+            let parsed = block_on(parse_async(&output[..], format)).unwrap();
+            assert_eq!(parsed, crate::parse(&expected[..], format).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let batch = crate::parse(CSV.as_bytes(), Format::Csv).unwrap();
+        let stream = parse_stream(CSV.as_bytes(), Format::Csv);
+        let transactions: Vec<_> = block_on(stream.try_collect()).unwrap();
+        assert_eq!(transactions, batch.transactions);
+
+        let mut json = Vec::new();
+        crate::write(&batch, &mut json, Format::Json).unwrap();
+        let stream = parse_stream(&json[..], Format::Json);
+        let transactions: Vec<_> = block_on(stream.try_collect()).unwrap();
+        assert_eq!(transactions, batch.transactions);
+
+        // The stream ends with the first error
+        let input = CSV.replace("150.50", "abc");
+        let results: Vec<_> = block_on(parse_stream(input.as_bytes(), Format::Csv).collect());
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok() && results[1].is_err());
+        let results: Vec<_> = block_on(parse_stream(&b""[..], Format::Csv).collect());
+        assert!(matches!(results[..], [Err(Error::Parse { .. })]));
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = ParseOptions {
+            limits: Limits {
+                max_input_bytes: Some(CSV.len() as u64 - 1),
+                ..Limits::default()
+            },
+            ..ParseOptions::default()
+        };
+        let parsed = block_on(parse_async_with(CSV.as_bytes(), Format::Csv, &options));
+        assert!(matches!(parsed, Err(Error::LimitExceeded(_))));
+
+        // The transactions before the cap are yielded
+        let stream = parse_stream_with(CSV.as_bytes(), Format::Csv, &options);
+        let results: Vec<_> = block_on(stream.collect());
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::LimitExceeded(_))));
+
+        let batch = crate::parse(CSV.as_bytes(), Format::Csv).unwrap();
+        let mut json = Vec::new();
+        crate::write(&batch, &mut json, Format::Json).unwrap();
+        let stream = parse_stream_with(&json[..], Format::Json, &options);
+        let results: Vec<_> = block_on(stream.collect());
+        assert!(matches!(results[..], [Err(Error::LimitExceeded(_))]));
+
+        let options = ParseOptions {
+            limits: Limits {
+                max_input_bytes: Some(CSV.len() as u64),
+                ..Limits::default()
+            },
+            ..ParseOptions::default()
+        };
+        let stream = parse_stream_with(CSV.as_bytes(), Format::Csv, &options);
+        let transactions: Vec<_> = block_on(stream.try_collect()).unwrap();
+        assert_eq!(transactions, batch.transactions);
+    }
+}
@@ -0,0 +1,211 @@
+//! Closing-balance reconciliation.
+//!
+//! A statement that declares its opening and closing balances can be checked
+//! for completeness: for every currency, the opening balance plus credits
+//! minus debits must equal the closing balance. A missing, duplicated or
+//! mistyped transaction shows up as a discrepancy, which makes this the most
+//! useful single sanity check for a statement file. Only booked transactions
+//! count: pending and cancelled entries don't move a statement's balance.
+
+use crate::{Error, Result, TransactionBatch};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use rust_decimal::Decimal;
+
+/// The result of checking one declared [`crate::Balance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceCheck {
+    /// The currency code
+    pub currency: String,
+    /// Declared opening balance
+    pub opening: Decimal,
+    /// Sum of credits in the currency
+    pub credits: Decimal,
+    /// Sum of debits in the currency
+    pub debits: Decimal,
+    /// Declared closing balance
+    pub closing: Decimal,
+}
+
+impl BalanceCheck {
+    /// The closing balance implied by the transactions.
+    pub fn expected_closing(&self) -> Decimal {
+        self.opening + self.credits - self.debits
+    }
+
+    /// Declared minus expected closing balance; zero when they agree.
+    pub fn discrepancy(&self) -> Decimal {
+        self.closing - self.expected_closing()
+    }
+
+    /// Returns `true` if the declared closing balance matches.
+    pub fn is_balanced(&self) -> bool {
+        self.discrepancy().is_zero()
+    }
+}
+
+/// Checks every declared balance of a batch against its transactions.
+///
+/// Returns one [`BalanceCheck`] per entry of [`TransactionBatch::balances`],
+/// in the same order. Currencies without a declared balance are not checked,
+/// and transactions that aren't [booked](crate::TransactionStatus::Booked)
+/// are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use parser::balance::check_balances;
+/// use parser::{Balance, TransactionBatch};
+/// use rust_decimal::Decimal;
+///
+/// let batch = TransactionBatch {
+///     account_id: None,
+///     transactions: vec![],
+///     balances: vec![Balance {
+///         currency: "USD".to_string(),
+///         opening: Decimal::new(100, 0),
+///         closing: Decimal::new(90, 0),
+///     }],
+///     metadata: Default::default(),
+/// };
+/// let checks = check_balances(&batch);
+/// assert_eq!(checks[0].discrepancy(), Decimal::new(-10, 0));
+/// ```
+pub fn check_balances(batch: &TransactionBatch) -> Vec<BalanceCheck> {
+    batch
+        .balances
+        .iter()
+        .map(|balance| {
+            let mut check = BalanceCheck {
+                currency: balance.currency.clone(),
+                opening: balance.opening,
+                credits: Decimal::ZERO,
+                debits: Decimal::ZERO,
+                closing: balance.closing,
+            };
+            for tx in batch
+                .transactions
+                .iter()
+                .filter(|tx| tx.status.is_booked() && tx.amount.currency == balance.currency)
+            {
+                if tx.is_credit() {
+                    check.credits += tx.unsigned_amount();
+                } else {
+                    check.debits += tx.unsigned_amount();
+                }
+            }
+            check
+        })
+        .collect()
+}
+
+/// Returns only the checks that don't balance.
+pub fn balance_discrepancies(batch: &TransactionBatch) -> Vec<BalanceCheck> {
+    check_balances(batch)
+        .into_iter()
+        .filter(|check| !check.is_balanced())
+        .collect()
+}
+
+/// Fails with a parse error of `format` if a declared closing balance of
+/// `batch` doesn't match its transactions, for the statement formats whose
+/// balances come with the data.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn ensure_balanced(batch: &TransactionBatch, format: &'static str) -> Result<()> {
+    match balance_discrepancies(batch).first() {
+        None => Ok(()),
+        Some(check) => Err(Error::parse(
+            format,
+            format!(
+                "{}: opening balance {} + credits {} - debits {} = {}, but the closing balance is {}",
+                check.currency,
+                check.opening,
+                check.credits,
+                check.debits,
+                check.expected_closing(),
+                check.closing
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, Money, Transaction, TransactionKind, TransactionStatus};
+    use alloc::collections::BTreeMap;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use chrono::NaiveDate;
+
+    fn tx(kind: TransactionKind, amount: i64, currency: &str) -> Transaction {
+        Transaction {
+            id: "TX".to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(amount, 2),
+                currency: currency.to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn balance(currency: &str, opening: i64, closing: i64) -> Balance {
+        Balance {
+            currency: currency.to_string(),
+            opening: Decimal::new(opening, 2),
+            closing: Decimal::new(closing, 2),
+        }
+    }
+
+    #[test]
+    fn test_check_balances() {
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx(TransactionKind::Credit, 5000, "USD"),
+                tx(TransactionKind::Debit, 1250, "USD"),
+                tx(TransactionKind::Debit, 700, "EUR"),
+            ],
+            balances: vec![balance("USD", 10000, 13750), balance("EUR", 1000, 400)],
+            metadata: Default::default(),
+        };
+
+        let checks = check_balances(&batch);
+        assert!(checks[0].is_balanced());
+        assert_eq!(checks[1].expected_closing(), Decimal::new(300, 2));
+        assert_eq!(checks[1].discrepancy(), Decimal::new(100, 2));
+
+        let discrepancies = balance_discrepancies(&batch);
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].currency, "EUR");
+    }
+
+    #[test]
+    fn test_check_balances_ignores_unbooked() {
+        let mut pending = tx(TransactionKind::Debit, 2500, "USD");
+        pending.status = TransactionStatus::Pending;
+        let mut cancelled = tx(TransactionKind::Credit, 900, "USD");
+        cancelled.status = TransactionStatus::Cancelled;
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![tx(TransactionKind::Credit, 5000, "USD"), pending, cancelled],
+            balances: vec![balance("USD", 10000, 15000)],
+            metadata: Default::default(),
+        };
+
+        let checks = check_balances(&batch);
+        assert!(checks[0].is_balanced());
+        assert_eq!(checks[0].debits, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,202 @@
+//! Canonical form of transaction batches.
+//!
+//! Two batches that describe the same transactions can still serialize
+//! differently: records may come in another order, `10.5` may be written as
+//! `10.50`, and an empty optional field may be stored as `Some("")` by one
+//! source and as `None` by another. [`canonicalize`] removes those
+//! differences, so that writing a canonical batch produces byte-identical
+//! output in every format. This is what signatures, content-addressed caches
+//! and reproducible archives rely on.
+
+use crate::TransactionBatch;
+use alloc::string::String;
+use alloc::vec::Vec;
+use chrono::Timelike;
+use rust_decimal::Decimal;
+
+/// Rewrites a batch into its canonical form.
+///
+/// The canonical form:
+/// - normalizes every amount, including tax amounts and rates, to its
+///   smallest scale (`10.50` becomes `10.5`);
+/// - replaces empty optional strings, including the batch account and
+///   metadata, with `None`;
+/// - truncates execution and generation timestamps to whole seconds, the
+///   precision every format stores;
+/// - sorts transactions into their canonical order (see
+///   [`TransactionBatch::sort_canonical`]), which is total and independent of
+///   the input order;
+/// - sorts declared balances by currency.
+///
+/// # Examples
+///
+/// ```
+/// use parser::canonical::canonicalize;
+/// use parser::TransactionBatch;
+///
+/// let mut batch = TransactionBatch {
+///     account_id: Some(String::new()),
+///     transactions: vec![],
+///     balances: vec![],
+///     metadata: Default::default(),
+/// };
+/// canonicalize(&mut batch);
+/// assert_eq!(batch.account_id, None);
+/// ```
+pub fn canonicalize(batch: &mut TransactionBatch) {
+    normalize_optional(&mut batch.account_id);
+    normalize_optional(&mut batch.metadata.source_system);
+    normalize_optional(&mut batch.metadata.schema_version);
+    let generated_at = batch.metadata.generated_at;
+    batch.metadata.generated_at = generated_at.map(|dt| dt.with_nanosecond(0).unwrap_or(dt));
+
+    for tx in &mut batch.transactions {
+        tx.amount.amount = tx.amount.amount.normalize();
+        tx.tax_amount = tx.tax_amount.map(|v| v.normalize());
+        tx.tax_rate = tx.tax_rate.map(|v| v.normalize());
+        tx.executed_at = tx.executed_at.map(|dt| dt.with_nanosecond(0).unwrap_or(dt));
+        normalize_optional(&mut tx.account);
+        normalize_optional(&mut tx.counterparty);
+        normalize_optional(&mut tx.category);
+    }
+
+    batch.sort_canonical();
+
+    for balance in &mut batch.balances {
+        balance.opening = balance.opening.normalize();
+        balance.closing = balance.closing.normalize();
+    }
+    batch.balances.sort_by(|a, b| a.currency.cmp(&b.currency));
+}
+
+/// Returns `true` if the batch is already in canonical form.
+pub fn is_canonical(batch: &TransactionBatch) -> bool {
+    let mut canonical = batch.clone();
+    canonicalize(&mut canonical);
+    // `Decimal` equality ignores scale and the sign of zero (`10.50 == 10.5`,
+    // `-0 == 0`), so those are compared separately.
+    canonical == *batch && decimal_forms(&canonical) == decimal_forms(batch)
+}
+
+/// The scale and sign of every decimal in the batch, in field order.
+fn decimal_forms(batch: &TransactionBatch) -> Vec<(u32, bool)> {
+    let form = |v: Decimal| (v.scale(), v.is_sign_negative());
+    let mut forms = Vec::new();
+    for tx in &batch.transactions {
+        forms.push(form(tx.amount.amount));
+        forms.extend(tx.tax_amount.map(form));
+        forms.extend(tx.tax_rate.map(form));
+    }
+    for balance in &batch.balances {
+        forms.push(form(balance.opening));
+        forms.push(form(balance.closing));
+    }
+    forms
+}
+
+fn normalize_optional(value: &mut Option<String>) {
+    if value.as_deref() == Some("") {
+        *value = None;
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{Format, Money, Transaction, TransactionKind, TransactionStatus, WriteOptions};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use std::str::FromStr;
+
+    fn tx(id: &str, day: u32, amount: &str, category: Option<&str>) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str(amount).unwrap(),
+                currency: "USD".to_string(),
+            },
+            description: "Payment".to_string(),
+            account: None,
+            counterparty: None,
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC1".to_string()),
+            transactions: vec![tx("B", 2, "10.50", Some("")), tx("A", 2, "1.00", None)],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        canonicalize(&mut batch);
+
+        assert_eq!(batch.transactions[0].id, "A");
+        assert_eq!(batch.transactions[0].amount.amount.to_string(), "1");
+        assert_eq!(batch.transactions[1].amount.amount.to_string(), "10.5");
+        assert_eq!(batch.transactions[1].category, None);
+        assert!(is_canonical(&batch));
+    }
+
+    #[test]
+    fn test_is_canonical_checks_scale() {
+        let mut batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![tx("A", 1, "10.50", None)],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        assert!(!is_canonical(&batch));
+
+        batch.transactions[0].amount.amount = Decimal::from_str("10.5").unwrap();
+        batch.transactions[0].tax_rate = Some(Decimal::from_str("20.0").unwrap());
+        assert!(!is_canonical(&batch));
+
+        batch.transactions[0].tax_rate = None;
+        batch.transactions[0].amount.amount = -Decimal::ZERO;
+        assert!(!is_canonical(&batch));
+
+        canonicalize(&mut batch);
+        assert!(is_canonical(&batch));
+    }
+
+    #[test]
+    fn test_canonical_output_is_byte_identical() {
+        let first = TransactionBatch {
+            account_id: None,
+            transactions: vec![tx("TX2", 3, "20.00", Some("Food")), tx("TX1", 1, "5", None)],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let second = TransactionBatch {
+            account_id: Some(String::new()),
+            transactions: vec![
+                tx("TX1", 1, "5.000", Some("")),
+                tx("TX2", 3, "20", Some("Food")),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let options = WriteOptions {
+            canonical: true,
+            ..WriteOptions::default()
+        };
+
+        for format in [Format::Csv, Format::Text, Format::Binary] {
+            let mut a = Vec::new();
+            crate::write_with(&first, &mut a, format, &options).unwrap();
+            let mut b = Vec::new();
+            crate::write_with(&second, &mut b, format, &options).unwrap();
+            assert_eq!(a, b, "{}", format);
+        }
+    }
+}
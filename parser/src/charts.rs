@@ -0,0 +1,304 @@
+//! Chart rendering for batch statistics.
+//!
+//! Renders the series computed by [`crate::stats`] with `plotters`. The
+//! output format follows the file extension: `.svg` produces an SVG
+//! document, anything else a PNG bitmap. Text is drawn with the system
+//! `sans-serif` font, so a font must be installed where charts are rendered.
+
+use crate::stats::{balance_over_time, category_totals, monthly_totals};
+use crate::{Error, Result, TransactionBatch};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use rust_decimal::prelude::ToPrimitive;
+use std::path::Path;
+
+const SIZE: (u32, u32) = (1024, 640);
+
+/// The kinds of chart that can be rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChartKind {
+    /// Bar chart of debits per calendar month
+    MonthlySpend,
+    /// Bar chart of debits per category
+    CategoryBar,
+    /// Pie chart of debits per category
+    CategoryPie,
+    /// Line chart of the running balance by posting date
+    Balance,
+}
+
+/// Renders a chart of `kind` for the transactions in `currency` to `path`.
+///
+/// # Errors
+///
+/// Returns [`Error::Chart`] if the chart cannot be drawn or saved, for
+/// example when no font is available or the file cannot be created.
+///
+/// # Examples
+///
+/// ```no_run
+/// use parser::charts::{render_chart, ChartKind};
+/// use parser::{parse_path, Format};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let batch = parse_path("transactions.csv", Format::Csv)?;
+/// render_chart(&batch, ChartKind::MonthlySpend, "USD", "spend.svg")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn render_chart<P: AsRef<Path>>(
+    batch: &TransactionBatch,
+    kind: ChartKind,
+    currency: &str,
+    path: P,
+) -> Result<()> {
+    let path = path.as_ref();
+    let is_svg = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+
+    if is_svg {
+        draw(
+            SVGBackend::new(path, SIZE).into_drawing_area(),
+            batch,
+            kind,
+            currency,
+        )
+    } else {
+        draw(
+            BitMapBackend::new(path, SIZE).into_drawing_area(),
+            batch,
+            kind,
+            currency,
+        )
+    }
+}
+
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    batch: &TransactionBatch,
+    kind: ChartKind,
+    currency: &str,
+) -> Result<()> {
+    root.fill(&WHITE).map_err(chart_error)?;
+
+    match kind {
+        ChartKind::MonthlySpend => {
+            let (labels, values): (Vec<String>, Vec<f64>) = monthly_totals(batch)
+                .into_iter()
+                .filter(|t| t.currency == currency)
+                .map(|t| (format!("{}-{:02}", t.year, t.month), to_f64(t.debits)))
+                .unzip();
+            draw_bars(
+                &root,
+                &format!("Monthly spend ({})", currency),
+                &labels,
+                &values,
+            )?;
+        }
+        ChartKind::CategoryBar | ChartKind::CategoryPie => {
+            let (labels, values): (Vec<String>, Vec<f64>) = category_totals(batch)
+                .into_iter()
+                .filter(|t| t.currency == currency && !t.debits.is_zero())
+                .map(|t| (t.category, to_f64(t.debits)))
+                .unzip();
+            let title = format!("Spend by category ({})", currency);
+            if kind == ChartKind::CategoryBar {
+                draw_bars(&root, &title, &labels, &values)?;
+            } else {
+                draw_pie(&root, &title, &labels, &values)?;
+            }
+        }
+        ChartKind::Balance => draw_balance(&root, batch, currency)?,
+    }
+
+    root.present().map_err(chart_error)
+}
+
+fn draw_bars<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    labels: &[String],
+    values: &[f64],
+) -> Result<()> {
+    let max = values.iter().cloned().fold(0.0, f64::max).max(1.0) * 1.1;
+
+    let mut chart = ChartBuilder::on(root)
+        .caption(title, ("sans-serif", 28))
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d((0..labels.len() as i32).into_segmented(), 0.0..max)
+        .map_err(chart_error)?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(labels.len().max(1))
+        .x_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(i) => labels.get(*i as usize).cloned().unwrap_or_default(),
+            _ => String::new(),
+        })
+        .draw()
+        .map_err(chart_error)?;
+
+    chart
+        .draw_series(
+            Histogram::vertical(&chart)
+                .style(BLUE.mix(0.7).filled())
+                .margin(10)
+                .data(values.iter().enumerate().map(|(i, v)| (i as i32, *v))),
+        )
+        .map_err(chart_error)?;
+
+    Ok(())
+}
+
+fn draw_pie<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    title: &str,
+    labels: &[String],
+    values: &[f64],
+) -> Result<()> {
+    let root = root
+        .titled(title, ("sans-serif", 28))
+        .map_err(chart_error)?;
+
+    if values.is_empty() {
+        return Ok(());
+    }
+
+    let (width, height) = root.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = f64::from(width.min(height)) * 0.35;
+    let colors: Vec<RGBColor> = (0..values.len())
+        .map(|i| {
+            let color = Palette99::pick(i).to_rgba();
+            RGBColor(color.0, color.1, color.2)
+        })
+        .collect();
+
+    let mut pie = Pie::new(&center, &radius, values, &colors, labels);
+    pie.start_angle(-90.0);
+    pie.label_style(("sans-serif", 16).into_font());
+    pie.percentages(("sans-serif", 14).into_font().color(&WHITE));
+    root.draw(&pie).map_err(chart_error)?;
+
+    Ok(())
+}
+
+fn draw_balance<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    batch: &TransactionBatch,
+    currency: &str,
+) -> Result<()> {
+    let points: Vec<_> = balance_over_time(batch)
+        .into_iter()
+        .filter(|p| p.currency == currency)
+        .collect();
+
+    let first = points.first().map(|p| p.date).unwrap_or_default();
+    let days: Vec<(i64, f64)> = points
+        .iter()
+        .map(|p| ((p.date - first).num_days(), to_f64(p.balance)))
+        .collect();
+
+    let last_day = days.last().map(|d| d.0).unwrap_or_default().max(1);
+    let (min, max) = days
+        .iter()
+        .fold((0.0_f64, 0.0_f64), |(lo, hi), d| (lo.min(d.1), hi.max(d.1)));
+    let padding = ((max - min) * 0.1).max(1.0);
+
+    let mut chart = ChartBuilder::on(root)
+        .caption(format!("Balance ({})", currency), ("sans-serif", 28))
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d(0..last_day, (min - padding)..(max + padding))
+        .map_err(chart_error)?;
+
+    chart
+        .configure_mesh()
+        .x_label_formatter(&|day| (first + chrono::Duration::days(*day)).to_string())
+        .draw()
+        .map_err(chart_error)?;
+
+    chart
+        .draw_series(LineSeries::new(days.iter().cloned(), &BLUE))
+        .map_err(chart_error)?;
+
+    Ok(())
+}
+
+fn to_f64(value: rust_decimal::Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+fn chart_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::Chart(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, Transaction, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn sample_batch() -> TransactionBatch {
+        let transactions = (0..6)
+            .map(|i| Transaction {
+                id: format!("TX{}", i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1 + i % 3, 10).unwrap(),
+                executed_at: None,
+                kind: if i == 0 {
+                    TransactionKind::Credit
+                } else {
+                    TransactionKind::Debit
+                },
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(10_000 + i as i64 * 1_000, 2),
+                    currency: "USD".to_string(),
+                },
+                description: String::new(),
+                account: None,
+                counterparty: None,
+                category: Some(["Food", "Rent", "Travel"][i as usize % 3].to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            })
+            .collect();
+
+        TransactionBatch {
+            account_id: None,
+            transactions,
+            balances: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_render_all_kinds_to_svg() {
+        let dir = std::env::temp_dir();
+        let batch = sample_batch();
+
+        for kind in [
+            ChartKind::MonthlySpend,
+            ChartKind::CategoryBar,
+            ChartKind::CategoryPie,
+            ChartKind::Balance,
+        ] {
+            let path = dir.join(format!("ypbank-chart-{}-{}.svg", kind, std::process::id()));
+            render_chart(&batch, kind, "USD", &path).unwrap();
+
+            let svg = std::fs::read_to_string(&path).unwrap();
+            assert!(svg.starts_with("<svg"), "{}", kind);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
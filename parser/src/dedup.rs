@@ -0,0 +1,178 @@
+//! Removing duplicate transactions from a batch.
+//!
+//! Overlapping exports and re-sent files repeat records. Most banks give a
+//! repeated record the same ID, but some reuse or regenerate IDs, so a
+//! duplicate can also be recognized by its content. [`DedupKey`] chooses
+//! between the two, and [`TransactionBatch::dedup_by`] returns what it
+//! removed so that it can be audited.
+
+use crate::{Transaction, TransactionBatch};
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+/// What makes two transactions duplicates of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum DedupKey {
+    /// The same [`Transaction::id`]
+    Id,
+    /// The same posting date, signed amount and currency, counterparty and
+    /// description, whatever their IDs; amounts are compared by value, so
+    /// `10.50` and `10.5` match, and an empty counterparty matches a
+    /// missing one
+    Composite,
+}
+
+/// The fields a [`DedupKey`] compares, in a form that orders and compares
+/// as the key does.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Identity<'a> {
+    Id(&'a str),
+    Composite {
+        posted_at: NaiveDate,
+        amount: Decimal,
+        currency: &'a str,
+        counterparty: Option<&'a str>,
+        description: &'a str,
+    },
+}
+
+impl DedupKey {
+    fn identity<'a>(&self, tx: &'a Transaction) -> Identity<'a> {
+        match self {
+            DedupKey::Id => Identity::Id(&tx.id),
+            DedupKey::Composite => Identity::Composite {
+                posted_at: tx.posted_at,
+                amount: tx.signed_amount().normalize(),
+                currency: &tx.amount.currency,
+                counterparty: tx.counterparty.as_deref().filter(|c| !c.is_empty()),
+                description: &tx.description,
+            },
+        }
+    }
+}
+
+impl TransactionBatch {
+    /// Removes transactions that duplicate an earlier one by `key`.
+    ///
+    /// The first transaction of each group of duplicates is kept, and the
+    /// remaining transactions keep their order.
+    ///
+    /// # Returns
+    ///
+    /// The removed transactions, in the order they had in the batch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{DedupKey, Format};
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-03\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-01-03\nType: Debit\nAmount: 5.00 USD\n---\n\
+    ///             ID: TX1\nDate: 2024-01-04\nType: Credit\nAmount: 9 USD\n";
+    /// let mut batch = parser::parse(text.as_bytes(), Format::Text)?;
+    ///
+    /// let removed = batch.clone().dedup_by(DedupKey::Id);
+    /// assert_eq!(removed[0].posted_at.to_string(), "2024-01-04");
+    ///
+    /// let removed = batch.dedup_by(DedupKey::Composite);
+    /// assert_eq!(removed[0].id, "TX2");
+    /// assert_eq!(batch.transactions.len(), 2);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn dedup_by(&mut self, key: DedupKey) -> Vec<Transaction> {
+        let duplicates: Vec<bool> = {
+            let mut seen = BTreeSet::new();
+            self.transactions
+                .iter()
+                .map(|tx| !seen.insert(key.identity(tx)))
+                .collect()
+        };
+
+        let mut removed = Vec::new();
+        let transactions = core::mem::take(&mut self.transactions);
+        for (tx, duplicate) in transactions.into_iter().zip(duplicates) {
+            if duplicate {
+                removed.push(tx);
+            } else {
+                self.transactions.push(tx);
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::collections::BTreeMap;
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+
+    fn tx(
+        id: &str,
+        kind: TransactionKind,
+        amount: Decimal,
+        counterparty: Option<&str>,
+    ) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount,
+                currency: "EUR".to_string(),
+            },
+            description: "Coffee".to_string(),
+            account: None,
+            counterparty: counterparty.map(str::to_string),
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_dedup_by() {
+        let batch = TransactionBatch {
+            transactions: vec![
+                tx("A", TransactionKind::Debit, Decimal::new(350, 2), None),
+                tx("B", TransactionKind::Debit, Decimal::new(35, 1), Some("")),
+                tx("A", TransactionKind::Credit, Decimal::new(35, 1), None),
+                tx(
+                    "C",
+                    TransactionKind::Debit,
+                    Decimal::new(35, 1),
+                    Some("Cafe"),
+                ),
+                tx("B", TransactionKind::Debit, Decimal::new(35, 1), None),
+            ],
+            ..TransactionBatch::default()
+        };
+        let ids = |transactions: &[Transaction]| {
+            transactions
+                .iter()
+                .map(|tx| tx.id.as_str())
+                .collect::<String>()
+        };
+
+        let mut by_id = batch.clone();
+        let removed = by_id.dedup_by(DedupKey::Id);
+        assert_eq!(ids(&by_id.transactions), "ABC");
+        assert_eq!(ids(&removed), "AB");
+        assert_eq!(removed[0].kind, TransactionKind::Credit);
+
+        let mut by_content = batch.clone();
+        let removed = by_content.dedup_by(DedupKey::Composite);
+        assert_eq!(ids(&by_content.transactions), "AAC");
+        assert_eq!(ids(&removed), "BB");
+
+        assert!(by_content.dedup_by(DedupKey::Composite).is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+//! Deltas between two versions of a batch.
+//!
+//! A [`Delta`] lists the records removed from, changed in, and added to a
+//! base batch, identified by transaction ID. It carries the fingerprints (see
+//! [`crate::manifest::ordered_fingerprint`]) of both the base and the target batch, so
+//! [`apply_delta`] refuses to patch the wrong base and verifies its result.
+//! Daily syncs can then ship only the changed records instead of the whole
+//! statement.
+//!
+//! Records that keep their ID but move relative to the other kept records are
+//! encoded as a removal plus an insertion, so applying a delta reproduces the
+//! target batch exactly, including its order.
+//!
+//! # Binary layout
+//!
+//! Deltas are serialized with the primitives of the binary format:
+//!
+//! ```text
+//! magic "YPBD" (u32) | version (u8)
+//! base fingerprint (string) | target fingerprint (string)
+//! account id (optional string)
+//! removed: count (u32), then IDs (string)
+//! changed: count (u32), then transactions
+//! added: count (u32), then position (u32) + transaction
+//! ```
+
+use crate::formats::binary::{
+    read_optional_string, read_string, read_transaction, read_u32, read_u8, write_optional_string,
+    write_string, write_transaction, write_u32, write_u8, IoSink, IoSource,
+};
+use crate::manifest::ordered_fingerprint;
+use crate::{Error, Result, Transaction, TransactionBatch};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+const MAGIC_NUMBER: u32 = 0x59504244;
+/// Version 2 stores transactions with the binary format's extension block.
+/// Delta versions match the transaction encoding they use.
+const VERSION: u8 = 2;
+
+/// A record inserted into the target batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insertion {
+    /// Index of the record in the target batch
+    pub position: usize,
+    /// The inserted record
+    pub transaction: Transaction,
+}
+
+/// Changes that turn a base batch into a target batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delta {
+    /// Fingerprint of the batch the delta applies to
+    pub base_fingerprint: String,
+    /// Fingerprint of the batch the delta produces
+    pub target_fingerprint: String,
+    /// Account identifier of the target batch
+    pub account_id: Option<String>,
+    /// IDs of base records that are dropped
+    pub removed: Vec<String>,
+    /// Replacement records for base records that stay in place, matched by ID
+    pub changed: Vec<Transaction>,
+    /// Records inserted into the target, ordered by position
+    pub added: Vec<Insertion>,
+}
+
+impl Delta {
+    /// Returns `true` if applying the delta leaves the records unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.changed.is_empty() && self.added.is_empty()
+    }
+
+    /// Writes the delta in its binary representation.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let writer = &mut IoSink(writer);
+
+        write_u32(writer, MAGIC_NUMBER)?;
+        write_u8(writer, VERSION)?;
+        write_string(writer, &self.base_fingerprint)?;
+        write_string(writer, &self.target_fingerprint)?;
+        write_optional_string(writer, self.account_id.as_deref())?;
+
+        write_u32(writer, self.removed.len() as u32)?;
+        for id in &self.removed {
+            write_string(writer, id)?;
+        }
+
+        write_u32(writer, self.changed.len() as u32)?;
+        for tx in &self.changed {
+            write_transaction(writer, tx, VERSION)?;
+        }
+
+        write_u32(writer, self.added.len() as u32)?;
+        for insertion in &self.added {
+            write_u32(writer, insertion.position as u32)?;
+            write_transaction(writer, &insertion.transaction, VERSION)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a delta from its binary representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the magic number or version is wrong, or the data
+    /// is truncated or corrupted.
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let reader = &mut IoSource(reader);
+
+        if read_u32(reader)? != MAGIC_NUMBER {
+            return Err(Error::parse("Delta", "invalid magic number"));
+        }
+        let version = read_u8(reader)?;
+        if version == 0 || version > VERSION {
+            return Err(Error::parse(
+                "Delta",
+                format!("unsupported version: {}", version),
+            ));
+        }
+
+        let base_fingerprint = read_string(reader)?;
+        let target_fingerprint = read_string(reader)?;
+        let account_id = read_optional_string(reader)?;
+
+        let removed = (0..read_u32(reader)?)
+            .map(|_| read_string(reader))
+            .collect::<Result<_>>()?;
+        let changed = (0..read_u32(reader)?)
+            .map(|_| read_transaction(reader, version))
+            .collect::<Result<_>>()?;
+        let added = (0..read_u32(reader)?)
+            .map(|_| {
+                Ok(Insertion {
+                    position: read_u32(reader)? as usize,
+                    transaction: read_transaction(reader, version)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            base_fingerprint,
+            target_fingerprint,
+            account_id,
+            removed,
+            changed,
+            added,
+        })
+    }
+}
+
+/// Computes the delta that turns `base` into `target`.
+///
+/// # Errors
+///
+/// Returns an error if either batch contains duplicate transaction IDs,
+/// since records are matched by ID.
+///
+/// # Examples
+///
+/// ```
+/// use parser::delta::{apply_delta, diff_to_delta};
+/// use parser::TransactionBatch;
+///
+/// # fn main() -> Result<(), parser::Error> {
+/// let base = TransactionBatch::default();
+/// let target = TransactionBatch::default();
+/// let delta = diff_to_delta(&base, &target)?;
+/// assert!(delta.is_empty());
+/// assert_eq!(apply_delta(&base, &delta)?, target);
+/// # Ok(())
+/// # }
+/// ```
+pub fn diff_to_delta(base: &TransactionBatch, target: &TransactionBatch) -> Result<Delta> {
+    let base_index = index_by_id(base, "base")?;
+    let target_index = index_by_id(target, "target")?;
+
+    // Base positions of target records that also exist in the base, in
+    // target order. The longest increasing run stays in place; every other
+    // common record has moved and is re-inserted.
+    let common: Vec<(usize, usize)> = target
+        .transactions
+        .iter()
+        .enumerate()
+        .filter_map(|(t, tx)| base_index.get(tx.id.as_str()).map(|&b| (t, b)))
+        .collect();
+    let kept = longest_increasing(&common.iter().map(|&(_, b)| b).collect::<Vec<_>>());
+    let kept: HashSet<usize> = kept.into_iter().map(|i| common[i].0).collect();
+
+    let removed = base
+        .transactions
+        .iter()
+        .filter(|tx| match target_index.get(tx.id.as_str()) {
+            Some(t) => !kept.contains(t),
+            None => true,
+        })
+        .map(|tx| tx.id.clone())
+        .collect();
+
+    let mut changed = Vec::new();
+    let mut added = Vec::new();
+    for (position, tx) in target.transactions.iter().enumerate() {
+        if kept.contains(&position) {
+            if base.transactions[base_index[tx.id.as_str()]] != *tx {
+                changed.push(tx.clone());
+            }
+        } else {
+            added.push(Insertion {
+                position,
+                transaction: tx.clone(),
+            });
+        }
+    }
+
+    Ok(Delta {
+        base_fingerprint: ordered_fingerprint(base),
+        target_fingerprint: ordered_fingerprint(target),
+        account_id: target.account_id.clone(),
+        removed,
+        changed,
+        added,
+    })
+}
+
+/// Applies a delta to the batch it was computed against.
+///
+/// # Errors
+///
+/// Returns an error if `base` is not the batch the delta was made for, if
+/// the delta references records that don't exist, or if the result does not
+/// match the delta's target fingerprint.
+pub fn apply_delta(base: &TransactionBatch, delta: &Delta) -> Result<TransactionBatch> {
+    if ordered_fingerprint(base) != delta.base_fingerprint {
+        return Err(Error::parse(
+            "Delta",
+            "base batch does not match the delta's base fingerprint",
+        ));
+    }
+
+    let removed: HashSet<&str> = delta.removed.iter().map(String::as_str).collect();
+    let mut changed: HashMap<&str, &Transaction> = delta
+        .changed
+        .iter()
+        .map(|tx| (tx.id.as_str(), tx))
+        .collect();
+
+    let mut kept = Vec::with_capacity(base.transactions.len());
+    for tx in &base.transactions {
+        if removed.contains(tx.id.as_str()) {
+            continue;
+        }
+        kept.push(match changed.remove(tx.id.as_str()) {
+            Some(replacement) => replacement.clone(),
+            None => tx.clone(),
+        });
+    }
+    if let Some(id) = changed.keys().next() {
+        return Err(Error::parse(
+            "Delta",
+            format!("changed record {} is not in the base batch", id),
+        ));
+    }
+
+    let total = kept.len() + delta.added.len();
+    let mut kept = kept.into_iter();
+    let mut added = delta.added.iter().peekable();
+    let mut transactions = Vec::with_capacity(total);
+    for position in 0..total {
+        let next = match added.next_if(|insertion| insertion.position == position) {
+            Some(insertion) => insertion.transaction.clone(),
+            None => kept
+                .next()
+                .ok_or_else(|| Error::parse("Delta", "insertion position out of range"))?,
+        };
+        transactions.push(next);
+    }
+    if added.next().is_some() {
+        return Err(Error::parse(
+            "Delta",
+            "insertion positions are not in order",
+        ));
+    }
+
+    let result = TransactionBatch {
+        account_id: delta.account_id.clone(),
+        transactions,
+        balances: Vec::new(),
+        metadata: Default::default(),
+    };
+    if ordered_fingerprint(&result) != delta.target_fingerprint {
+        return Err(Error::parse(
+            "Delta",
+            "result does not match the delta's target fingerprint",
+        ));
+    }
+
+    Ok(result)
+}
+
+fn index_by_id<'a>(batch: &'a TransactionBatch, name: &str) -> Result<HashMap<&'a str, usize>> {
+    let mut index = HashMap::with_capacity(batch.transactions.len());
+    for (i, tx) in batch.transactions.iter().enumerate() {
+        if index.insert(tx.id.as_str(), i).is_some() {
+            return Err(Error::parse(
+                "Delta",
+                format!("duplicate transaction ID {} in {} batch", tx.id, name),
+            ));
+        }
+    }
+    Ok(index)
+}
+
+/// Returns the indices of a longest strictly increasing subsequence.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // tails[k] is the index of the smallest tail of an increasing run of length k + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+
+    for (i, &value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&t| values[t] < value);
+        previous[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut run = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        run.push(i);
+        current = previous[i];
+    }
+    run.reverse();
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::io::Cursor;
+
+    fn tx(id: &str, amount: i64) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(amount, 2),
+                currency: "EUR".to_string(),
+            },
+            description: format!("Payment {}", id),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn batch(transactions: Vec<Transaction>) -> TransactionBatch {
+        TransactionBatch {
+            account_id: Some("ACC1".to_string()),
+            transactions,
+            balances: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let base = batch(vec![tx("A", 100), tx("B", 200), tx("C", 300), tx("D", 400)]);
+        let target = batch(vec![tx("C", 300), tx("A", 150), tx("D", 400), tx("E", 500)]);
+
+        let delta = diff_to_delta(&base, &target).unwrap();
+
+        assert_eq!(delta.removed, vec!["B", "C"]);
+        assert_eq!(delta.changed, vec![tx("A", 150)]);
+        let positions: Vec<usize> = delta.added.iter().map(|a| a.position).collect();
+        assert_eq!(positions, vec![0, 3]);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), target);
+
+        assert!(apply_delta(&target, &delta).is_err());
+        assert!(diff_to_delta(&base, &base).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delta_binary_roundtrip() {
+        let base = batch(vec![tx("A", 100), tx("B", 200)]);
+        let target = batch(vec![tx("A", 100), tx("B", 250), tx("C", 300)]);
+        let delta = diff_to_delta(&base, &target).unwrap();
+
+        let mut buffer = Vec::new();
+        delta.write(&mut buffer).unwrap();
+        let read = Delta::read(Cursor::new(&buffer)).unwrap();
+
+        assert_eq!(read, delta);
+        assert!(Delta::read(Cursor::new(&buffer[..buffer.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_ids_are_rejected() {
+        let base = batch(vec![tx("A", 100), tx("A", 200)]);
+
+        assert!(diff_to_delta(&base, &batch(vec![])).is_err());
+    }
+}
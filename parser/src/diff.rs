@@ -0,0 +1,419 @@
+//! Comparing two batches field by field.
+//!
+//! [`diff_batches`] compares the transactions of two batches by position,
+//! in canonical order with [`DiffOptions::ignore_order`] or by ID with
+//! [`DiffOptions::by_id`], and reports every field that differs as a typed
+//! [`FieldChange`]. It is what
+//! `ypbank_compare` prints, and what a service checking that two systems
+//! export the same records can inspect instead.
+//!
+//! Source columns kept in [`Transaction::extra`] aren't compared, since most
+//! formats can't carry them and would otherwise never match.
+
+use crate::{Money, Transaction, TransactionBatch, TransactionKind, TransactionStatus};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::vec::Vec;
+use chrono::{NaiveDate, NaiveDateTime};
+use core::fmt;
+use rust_decimal::Decimal;
+
+/// How [`diff_batches`] lines up the transactions of two batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiffOptions {
+    /// Compare both batches in canonical order (see
+    /// [`TransactionBatch::sort_canonical`]) instead of as given, so the same
+    /// transactions in a different order are identical
+    pub ignore_order: bool,
+    /// Pair transactions by ID instead of by position, so an inserted or
+    /// removed transaction is reported as added or removed instead of
+    /// shifting every later one. Transactions with a repeated ID are paired
+    /// in the order they occur
+    pub by_id: bool,
+}
+
+/// A value on the left and on the right side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change<T> {
+    /// The value in the first batch
+    pub left: T,
+    /// The value in the second batch
+    pub right: T,
+}
+
+/// A field that differs between two transactions, with both values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "field", rename_all = "snake_case"))]
+pub enum FieldChange {
+    /// [`Transaction::id`]
+    Id(Change<String>),
+    /// [`Transaction::posted_at`]
+    PostedAt(Change<NaiveDate>),
+    /// [`Transaction::executed_at`]
+    ExecutedAt(Change<Option<NaiveDateTime>>),
+    /// [`Transaction::kind`]
+    Kind(Change<TransactionKind>),
+    /// [`Transaction::status`]
+    Status(Change<TransactionStatus>),
+    /// [`Transaction::amount`], amount and currency together
+    Amount(Change<Money>),
+    /// [`Transaction::description`]
+    Description(Change<String>),
+    /// [`Transaction::account`]
+    Account(Change<Option<String>>),
+    /// [`Transaction::counterparty`]
+    Counterparty(Change<Option<String>>),
+    /// [`Transaction::category`]
+    Category(Change<Option<String>>),
+    /// [`Transaction::tax_amount`]
+    TaxAmount(Change<Option<Decimal>>),
+    /// [`Transaction::tax_rate`]
+    TaxRate(Change<Option<Decimal>>),
+}
+
+impl fmt::Display for FieldChange {
+    /// Writes the change as `ypbank_compare` prints it, such as
+    /// `Amount: 10.00 EUR vs 12.00 EUR`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldChange::Id(c) => write!(f, "ID: '{}' vs '{}'", c.left, c.right),
+            FieldChange::PostedAt(c) => write!(f, "Posted Date: {} vs {}", c.left, c.right),
+            FieldChange::ExecutedAt(c) => {
+                write!(f, "Executed Date: {:?} vs {:?}", c.left, c.right)
+            }
+            FieldChange::Kind(c) => write!(f, "Kind: {:?} vs {:?}", c.left, c.right),
+            FieldChange::Status(c) => write!(f, "Status: {:?} vs {:?}", c.left, c.right),
+            FieldChange::Amount(c) => write!(f, "Amount: {} vs {}", c.left, c.right),
+            FieldChange::Description(c) => {
+                write!(f, "Description: '{}' vs '{}'", c.left, c.right)
+            }
+            FieldChange::Account(c) => write!(f, "Account: {:?} vs {:?}", c.left, c.right),
+            FieldChange::Counterparty(c) => {
+                write!(f, "Counterparty: {:?} vs {:?}", c.left, c.right)
+            }
+            FieldChange::Category(c) => write!(f, "Category: {:?} vs {:?}", c.left, c.right),
+            FieldChange::TaxAmount(c) => write!(f, "Tax Amount: {:?} vs {:?}", c.left, c.right),
+            FieldChange::TaxRate(c) => write!(f, "Tax Rate: {:?} vs {:?}", c.left, c.right),
+        }
+    }
+}
+
+/// Two paired transactions that differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransactionDiff {
+    /// The position of the transaction in the first batch, from 0, in the
+    /// compared order
+    pub index: usize,
+    /// The ID of the transaction in the first batch
+    pub id: String,
+    /// The fields that differ, in the order of [`Transaction`]'s fields
+    pub changes: Vec<FieldChange>,
+}
+
+/// The result of [`diff_batches`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffReport {
+    /// How many transactions the first batch has
+    pub left_count: usize,
+    /// How many transactions the second batch has
+    pub right_count: usize,
+    /// The paired transactions that differ, in the order of the first batch
+    pub changed: Vec<TransactionDiff>,
+    /// The transactions of the first batch without a counterpart in the
+    /// second
+    pub removed: Vec<Transaction>,
+    /// The transactions of the second batch without a counterpart in the
+    /// first
+    pub added: Vec<Transaction>,
+}
+
+impl DiffReport {
+    /// Whether both batches have the same transactions.
+    pub fn is_identical(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// Compares the transactions of `a` and `b`.
+///
+/// Transactions are compared position by position, up to the shorter
+/// batch; the rest of the longer batch is reported as removed or added, so
+/// batches of different lengths are never identical. With
+/// [`DiffOptions::by_id`] the transactions with the same ID are compared
+/// instead, and those whose ID the other batch lacks are removed or added.
+/// Balances and metadata aren't compared.
+///
+/// # Examples
+///
+/// ```
+/// use parser::diff::{diff_batches, DiffOptions, FieldChange};
+/// use parser::Format;
+///
+/// let a = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10 EUR\n---\n\
+///          ID: TX2\nDate: 2024-01-16\nType: Debit\nAmount: 3 EUR\nCategory: Food\n";
+/// let b = "ID: TX2\nDate: 2024-01-16\nType: Debit\nAmount: 3 EUR\n---\n\
+///          ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10.00 EUR\n";
+/// let a = parser::parse(a.as_bytes(), Format::Text)?;
+/// let b = parser::parse(b.as_bytes(), Format::Text)?;
+///
+/// let options = DiffOptions {
+///     ignore_order: true,
+///     ..DiffOptions::default()
+/// };
+/// let report = diff_batches(&a, &b, &options);
+/// assert!(!report.is_identical());
+/// assert_eq!(report.changed[0].id, "TX2");
+/// assert!(matches!(&report.changed[0].changes[..], [FieldChange::Category(_)]));
+/// assert_eq!(
+///     report.changed[0].changes[0].to_string(),
+///     "Category: Some(\"Food\") vs None"
+/// );
+/// # Ok::<(), parser::Error>(())
+/// ```
+pub fn diff_batches(
+    a: &TransactionBatch,
+    b: &TransactionBatch,
+    options: &DiffOptions,
+) -> DiffReport {
+    let left = ordered(a, options);
+    let right = ordered(b, options);
+    let pairing = if options.by_id {
+        pair_by_id(&left, &right)
+    } else {
+        pair_by_position(&left, &right)
+    };
+
+    let changed = pairing
+        .pairs
+        .iter()
+        .filter_map(|&(l, r)| {
+            let changes = diff_transactions(left[l], right[r]);
+            (!changes.is_empty()).then(|| TransactionDiff {
+                index: l,
+                id: left[l].id.clone(),
+                changes,
+            })
+        })
+        .collect();
+    DiffReport {
+        left_count: left.len(),
+        right_count: right.len(),
+        changed,
+        removed: pairing.removed.iter().map(|&l| left[l].clone()).collect(),
+        added: pairing.added.iter().map(|&r| right[r].clone()).collect(),
+    }
+}
+
+/// Which transactions of two batches are compared with each other, by
+/// their positions.
+struct Pairing {
+    /// Positions in the first and the second batch compared with each other
+    pairs: Vec<(usize, usize)>,
+    /// Positions in the first batch without a counterpart
+    removed: Vec<usize>,
+    /// Positions in the second batch without a counterpart
+    added: Vec<usize>,
+}
+
+fn pair_by_position(left: &[&Transaction], right: &[&Transaction]) -> Pairing {
+    let common = left.len().min(right.len());
+    Pairing {
+        pairs: (0..common).map(|index| (index, index)).collect(),
+        removed: (common..left.len()).collect(),
+        added: (common..right.len()).collect(),
+    }
+}
+
+/// Pairs the n-th transaction with an ID in `left` with the n-th with that
+/// ID in `right`.
+fn pair_by_id(left: &[&Transaction], right: &[&Transaction]) -> Pairing {
+    let mut unpaired: BTreeMap<&str, VecDeque<usize>> = BTreeMap::new();
+    for (index, tx) in right.iter().enumerate() {
+        unpaired.entry(tx.id.as_str()).or_default().push_back(index);
+    }
+
+    let mut pairs = Vec::new();
+    let mut removed = Vec::new();
+    for (index, tx) in left.iter().enumerate() {
+        match unpaired
+            .get_mut(tx.id.as_str())
+            .and_then(VecDeque::pop_front)
+        {
+            Some(other) => pairs.push((index, other)),
+            None => removed.push(index),
+        }
+    }
+    let mut added: Vec<usize> = unpaired.into_values().flatten().collect();
+    added.sort_unstable();
+    Pairing {
+        pairs,
+        removed,
+        added,
+    }
+}
+
+/// The transactions of `batch` in the order they are compared in.
+fn ordered<'a>(batch: &'a TransactionBatch, options: &DiffOptions) -> Vec<&'a Transaction> {
+    let mut transactions: Vec<&Transaction> = batch.transactions.iter().collect();
+    if options.ignore_order {
+        transactions.sort();
+    }
+    transactions
+}
+
+/// Returns the fields in which `left` and `right` differ, ignoring
+/// [`Transaction::extra`].
+pub fn diff_transactions(left: &Transaction, right: &Transaction) -> Vec<FieldChange> {
+    fn change<T: PartialEq + Clone>(left: &T, right: &T) -> Option<Change<T>> {
+        (left != right).then(|| Change {
+            left: left.clone(),
+            right: right.clone(),
+        })
+    }
+
+    [
+        change(&left.id, &right.id).map(FieldChange::Id),
+        change(&left.posted_at, &right.posted_at).map(FieldChange::PostedAt),
+        change(&left.executed_at, &right.executed_at).map(FieldChange::ExecutedAt),
+        change(&left.kind, &right.kind).map(FieldChange::Kind),
+        change(&left.status, &right.status).map(FieldChange::Status),
+        change(&left.amount, &right.amount).map(FieldChange::Amount),
+        change(&left.description, &right.description).map(FieldChange::Description),
+        change(&left.account, &right.account).map(FieldChange::Account),
+        change(&left.counterparty, &right.counterparty).map(FieldChange::Counterparty),
+        change(&left.category, &right.category).map(FieldChange::Category),
+        change(&left.tax_amount, &right.tax_amount).map(FieldChange::TaxAmount),
+        change(&left.tax_rate, &right.tax_rate).map(FieldChange::TaxRate),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn tx(id: &str, amount: i64) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from(amount),
+                currency: "EUR".to_string(),
+            },
+            description: "Rent".to_string(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn batch(transactions: Vec<Transaction>) -> TransactionBatch {
+        TransactionBatch {
+            transactions,
+            ..TransactionBatch::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_batches() {
+        let mut changed = tx("B", 20);
+        changed.amount.currency = "USD".to_string();
+        changed.status = TransactionStatus::Pending;
+        changed.extra.insert("mcc".to_string(), "5411".to_string());
+        let a = batch(vec![tx("A", 10), tx("B", 20)]);
+        let b = batch(vec![tx("A", 10), changed]);
+
+        let report = diff_batches(&a, &b, &DiffOptions::default());
+        assert_eq!(report.changed.len(), 1);
+        let diff = &report.changed[0];
+        assert_eq!((diff.index, diff.id.as_str()), (1, "B"));
+        assert_eq!(diff.changes.len(), 2);
+        assert!(matches!(diff.changes[0], FieldChange::Status(_)));
+        assert_eq!(diff.changes[1].to_string(), "Amount: 20 EUR vs 20 USD");
+
+        // Extra columns alone don't make transactions differ
+        let mut extra = tx("A", 10);
+        extra.extra.insert("mcc".to_string(), "5411".to_string());
+        assert!(diff_transactions(&tx("A", 10), &extra).is_empty());
+    }
+
+    #[test]
+    fn test_diff_batches_order_and_length() {
+        let a = batch(vec![tx("A", 10), tx("B", 20)]);
+        let b = batch(vec![tx("B", 20), tx("A", 10)]);
+        assert_eq!(
+            diff_batches(&a, &b, &DiffOptions::default()).changed.len(),
+            2
+        );
+        let options = DiffOptions {
+            ignore_order: true,
+            ..DiffOptions::default()
+        };
+        assert!(diff_batches(&a, &b, &options).is_identical());
+
+        let report = diff_batches(&a, &batch(vec![tx("A", 10)]), &options);
+        assert!(report.changed.is_empty());
+        assert_eq!((report.left_count, report.right_count), (2, 1));
+        assert_eq!(report.removed, vec![tx("B", 20)]);
+        assert!(report.added.is_empty());
+        assert!(!report.is_identical());
+    }
+
+    #[test]
+    fn test_diff_batches_by_id() {
+        let a = batch(vec![tx("A", 10), tx("B", 20), tx("B", 21), tx("C", 30)]);
+        let b = batch(vec![tx("X", 5), tx("A", 10), tx("B", 20), tx("B", 22)]);
+        let options = DiffOptions {
+            by_id: true,
+            ..DiffOptions::default()
+        };
+
+        let report = diff_batches(&a, &b, &options);
+        // The repeated ID B is paired in order: the second B changed
+        assert_eq!(report.changed.len(), 1);
+        assert_eq!(
+            (report.changed[0].index, report.changed[0].id.as_str()),
+            (2, "B")
+        );
+        assert_eq!(report.removed, vec![tx("C", 30)]);
+        assert_eq!(report.added, vec![tx("X", 5)]);
+        assert_eq!(
+            diff_batches(&a, &b, &DiffOptions::default()).changed.len(),
+            4
+        );
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn test_serialize_report() {
+        let report = diff_batches(
+            &batch(vec![tx("A", 10)]),
+            &batch(vec![tx("A", 12)]),
+            &DiffOptions::default(),
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json["changed"][0]["changes"][0],
+            serde_json::json!({
+                "field": "amount",
+                "left": {"amount": "10", "currency": "EUR"},
+                "right": {"amount": "12", "currency": "EUR"},
+            })
+        );
+    }
+}
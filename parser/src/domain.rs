@@ -4,8 +4,17 @@
 //! financial transactions, including money amounts, transaction types,
 //! and batches of transactions.
 
+use crate::{Error, Result};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use chrono::{NaiveDate, NaiveDateTime};
-use rust_decimal::Decimal;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Add, Neg, Sub};
+use core::str::FromStr;
+use rust_decimal::{Decimal, RoundingStrategy};
 
 /// Represents a monetary amount with a specific currency.
 ///
@@ -20,7 +29,13 @@ use rust_decimal::Decimal;
 ///     currency: "USD".to_string(),
 /// };
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "schemars",
+    schemars(description = "A monetary amount with its currency code.")
+)]
 pub struct Money {
     /// The amount as a decimal value for precise financial calculations
     pub amount: Decimal,
@@ -28,21 +43,348 @@ pub struct Money {
     pub currency: String,
 }
 
-/// The type of transaction: incoming (credit) or outgoing (debit).
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl Money {
+    /// Adds two amounts in the same currency.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CurrencyMismatch`] when the currencies differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{Error, Money};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let usd = |cents| Money { amount: Decimal::new(cents, 2), currency: "USD".to_string() };
+    /// assert_eq!(usd(150).try_add(&usd(250))?, usd(400));
+    ///
+    /// let eur = Money { amount: Decimal::ONE, currency: "EUR".to_string() };
+    /// assert!(matches!(usd(150).try_add(&eur), Err(Error::CurrencyMismatch { .. })));
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn try_add(&self, other: &Money) -> Result<Money> {
+        self.same_currency(other)?;
+        Ok(Money {
+            amount: self.amount + other.amount,
+            currency: self.currency.clone(),
+        })
+    }
+
+    /// Subtracts an amount in the same currency.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CurrencyMismatch`] when the currencies differ.
+    pub fn try_sub(&self, other: &Money) -> Result<Money> {
+        self.same_currency(other)?;
+        Ok(Money {
+            amount: self.amount - other.amount,
+            currency: self.currency.clone(),
+        })
+    }
+
+    /// Rounds the amount to the decimal places of its currency, as listed by
+    /// [`crate::currency::minor_units`]: two for USD, none for JPY, three
+    /// for BHD. Midpoints are rounded away from zero. Amounts in currencies
+    /// without a known minor unit are returned unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Money;
+    ///
+    /// let money: Money = "1234.5 JPY".parse()?;
+    /// assert_eq!(money.round_to_minor_units().to_string(), "1 235 JPY");
+    /// let money: Money = "10.0005 BHD".parse()?;
+    /// assert_eq!(money.round_to_minor_units().to_string(), "10.001 BHD");
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn round_to_minor_units(&self) -> Money {
+        let amount = match crate::currency::minor_units(&self.currency) {
+            Some(places) => self
+                .amount
+                .round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero),
+            None => self.amount,
+        };
+        Money {
+            amount,
+            currency: self.currency.clone(),
+        }
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<()> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(Error::CurrencyMismatch {
+                left: self.currency.clone(),
+                right: other.currency.clone(),
+            })
+        }
+    }
+}
+
+/// Formats as `<amount> <currency>` with the whole part grouped by thousands,
+/// such as `1 000.50 USD`, keeping the amount's decimal places. The
+/// alternate form (`{:#}`) leaves out the grouping: `1000.50 USD`.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let amount = self.amount.to_string();
+        let (sign, digits) = match amount.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", &amount[..]),
+        };
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (digits, None),
+        };
+        let mut formatted = String::with_capacity(amount.len() + self.currency.len() + 8);
+        formatted.push_str(sign);
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 && !f.alternate() {
+                formatted.push(' ');
+            }
+            formatted.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            formatted.push('.');
+            formatted.push_str(fraction);
+        }
+        formatted.push(' ');
+        formatted.push_str(&self.currency);
+        f.pad(&formatted)
+    }
+}
+
+/// Parses `<amount> <currency>`, such as `1000.50 USD` or the grouped
+/// `1 000.50 USD` that [`Display`](fmt::Display) writes.
+///
+/// # Examples
+///
+/// ```
+/// use parser::Money;
+/// use rust_decimal::Decimal;
+///
+/// let money: Money = "-1 000.50 USD".parse()?;
+/// assert_eq!(money.amount, Decimal::new(-100050, 2));
+/// assert_eq!(money.currency, "USD");
+/// assert_eq!(money.to_string(), "-1 000.50 USD");
+/// # Ok::<(), parser::Error>(())
+/// ```
+impl FromStr for Money {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Money> {
+        let expected = || {
+            let message = alloc::format!("expected '<amount> <currency>': {}", value);
+            Error::parse("Money", message)
+        };
+        let (amount, currency) = value
+            .trim()
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(expected)?;
+        let amount: String = amount.chars().filter(|c| !c.is_whitespace()).collect();
+        let amount = Decimal::from_str(&amount)
+            .map_err(|e| Error::parse("Money", alloc::format!("invalid amount: {}", e)))?;
+        Ok(Money {
+            amount,
+            currency: currency.into(),
+        })
+    }
+}
+
+/// Adds two amounts in the same currency, as [`Money::try_add`] does.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{Error, Money};
+///
+/// let sum = ("150 USD".parse::<Money>()? + "250 USD".parse()?)?;
+/// assert_eq!(sum, "400 USD".parse()?);
+/// let mixed = "150 USD".parse::<Money>()? + "1 EUR".parse()?;
+/// assert!(matches!(mixed, Err(Error::CurrencyMismatch { .. })));
+/// # Ok::<(), parser::Error>(())
+/// ```
+impl Add for Money {
+    type Output = Result<Money>;
+
+    fn add(self, other: Money) -> Result<Money> {
+        self.try_add(&other)
+    }
+}
+
+/// Subtracts an amount in the same currency, as [`Money::try_sub`] does.
+impl Sub for Money {
+    type Output = Result<Money>;
+
+    fn sub(self, other: Money) -> Result<Money> {
+        self.try_sub(&other)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money {
+            amount: -self.amount,
+            currency: self.currency,
+        }
+    }
+}
+
+/// Sums amounts per currency, never mixing currencies.
+///
+/// Returns one [`Money`] per currency that occurs, ordered by currency code.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{sum_by_currency, Money};
+/// use rust_decimal::Decimal;
+///
+/// let money = |amount, currency: &str| Money { amount: Decimal::from(amount), currency: currency.to_string() };
+/// let amounts = [money(5, "USD"), money(2, "EUR"), money(-1, "USD")];
+/// assert_eq!(sum_by_currency(&amounts), [money(2, "EUR"), money(4, "USD")]);
+/// ```
+pub fn sum_by_currency<'a, I>(amounts: I) -> Vec<Money>
+where
+    I: IntoIterator<Item = &'a Money>,
+{
+    let mut totals: BTreeMap<&str, Decimal> = BTreeMap::new();
+    for money in amounts {
+        *totals.entry(&money.currency).or_default() += money.amount;
+    }
+    totals
+        .into_iter()
+        .map(|(currency, amount)| Money {
+            amount,
+            currency: currency.into(),
+        })
+        .collect()
+}
+
+/// The type of transaction.
+///
+/// Most kinds have a fixed direction, given by [`TransactionKind::sign`],
+/// and a non-negative amount. Transfers and reversals can go either way, so
+/// their amount is signed instead: negative when money leaves the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[derive(strum::EnumString, strum::Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum TransactionKind {
     /// Outgoing transaction (withdrawal, payment)
     Debit,
     /// Incoming transaction (deposit, receipt)
     Credit,
+    /// Money moved between accounts; the sign of the amount gives the direction
+    Transfer,
+    /// Outgoing bank or card fee
+    Fee,
+    /// Incoming interest
+    Interest,
+    /// Incoming refund of an earlier payment
+    Refund,
+    /// Reversal of an earlier transaction; the sign of the amount gives the
+    /// direction
+    Reversal,
+}
+
+impl TransactionKind {
+    /// Returns `1` for kinds that add money to the account (credits,
+    /// interest, refunds), `-1` for those that take it out (debits, fees),
+    /// and `None` for transfers and reversals, whose amount carries its own
+    /// sign.
+    pub fn sign(self) -> Option<Decimal> {
+        match self {
+            TransactionKind::Credit | TransactionKind::Interest | TransactionKind::Refund => {
+                Some(Decimal::ONE)
+            }
+            TransactionKind::Debit | TransactionKind::Fee => Some(Decimal::NEGATIVE_ONE),
+            TransactionKind::Transfer | TransactionKind::Reversal => None,
+        }
+    }
+
+    /// Picks [`TransactionKind::Debit`] for a negative amount and
+    /// [`TransactionKind::Credit`] otherwise, returning the amount without
+    /// its sign.
+    ///
+    /// Readers fall back to this for kind codes they don't know, such as
+    /// those written by a newer version, so that such files still parse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::TransactionKind;
+    /// use rust_decimal::Decimal;
+    ///
+    /// let (kind, amount) = TransactionKind::from_sign(Decimal::new(-250, 2));
+    /// assert_eq!(kind, TransactionKind::Debit);
+    /// assert_eq!(amount, Decimal::new(250, 2));
+    /// ```
+    pub fn from_sign(amount: Decimal) -> (TransactionKind, Decimal) {
+        if amount.is_sign_negative() {
+            (TransactionKind::Debit, -amount)
+        } else {
+            (TransactionKind::Credit, amount)
+        }
+    }
+}
+
+/// Whether a transaction has settled.
+///
+/// Banks report card payments and incoming transfers as pending before they
+/// are booked, and may cancel them instead. Only booked transactions move
+/// the booked balance, so [`crate::balance::check_balances`] leaves the
+/// others out.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    strum::EnumString,
+    strum::Display,
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum TransactionStatus {
+    /// Posted to the account
+    #[default]
+    Booked,
+    /// Authorized or announced, but not posted yet
+    Pending,
+    /// Withdrawn before it was posted
+    Cancelled,
+}
+
+impl TransactionStatus {
+    /// Returns `true` for [`TransactionStatus::Booked`].
+    pub fn is_booked(&self) -> bool {
+        *self == TransactionStatus::Booked
+    }
 }
 
 /// Represents a single financial transaction.
 ///
 /// A transaction includes all relevant information about a financial operation,
 /// including dates, amount, parties involved, and categorization.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Transactions are ordered canonically: by posting date, then execution
+/// time (transactions without one first), then ID. Ties are broken by the
+/// remaining fields in declaration order, so two transactions compare equal
+/// only if they are equal. Amounts compare by value, so `10.5` and `10.50`
+/// are the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Transaction {
     /// Unique identifier for the transaction
     pub id: String,
@@ -52,6 +394,12 @@ pub struct Transaction {
     pub executed_at: Option<NaiveDateTime>,
     /// Whether this is a debit or credit transaction
     pub kind: TransactionKind,
+    /// Whether the transaction is booked, pending or cancelled
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "TransactionStatus::is_booked")
+    )]
+    pub status: TransactionStatus,
     /// The monetary amount and currency of the transaction
     pub amount: Money,
     /// Human-readable description of the transaction
@@ -62,6 +410,181 @@ pub struct Transaction {
     pub counterparty: Option<String>,
     /// Optional category for transaction classification
     pub category: Option<String>,
+    /// Optional tax (e.g. VAT) included in the amount, in the same currency
+    pub tax_amount: Option<Decimal>,
+    /// Optional tax rate in percent (`20` for 20% VAT)
+    pub tax_rate: Option<Decimal>,
+    /// Source columns without a field of their own, such as a bank reference
+    /// or MCC code, keyed by column name
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl Transaction {
+    /// Returns the amount as credits minus debits: positive when money comes
+    /// in, negative when it goes out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{Format, TransactionKind};
+    /// use rust_decimal::Decimal;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-15\nType: Fee\nAmount: 2.50 USD\n";
+    /// let mut tx = parser::parse(text.as_bytes(), Format::Text)?.transactions.remove(0);
+    /// assert_eq!(tx.signed_amount(), Decimal::new(-250, 2));
+    ///
+    /// tx.kind = TransactionKind::Transfer;
+    /// assert_eq!(tx.signed_amount(), Decimal::new(250, 2));
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn signed_amount(&self) -> Decimal {
+        match self.kind.sign() {
+            Some(sign) => sign * self.amount.amount,
+            None => self.amount.amount,
+        }
+    }
+
+    /// Returns whether the transaction adds money to the account, from its
+    /// kind or, for transfers and reversals, from the sign of its amount.
+    pub fn is_credit(&self) -> bool {
+        match self.kind.sign() {
+            Some(sign) => sign.is_sign_positive(),
+            None => self.amount.amount.is_sign_positive(),
+        }
+    }
+
+    /// Returns the amount to go with a debit or credit marker such as
+    /// [`Transaction::is_credit`]: the amount itself for kinds with a fixed
+    /// direction, and the amount without its sign for transfers and
+    /// reversals.
+    pub fn unsigned_amount(&self) -> Decimal {
+        match self.kind.sign() {
+            Some(_) => self.amount.amount,
+            None => self.amount.amount.abs(),
+        }
+    }
+}
+
+impl Ord for Transaction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.posted_at
+            .cmp(&other.posted_at)
+            .then_with(|| self.executed_at.cmp(&other.executed_at))
+            .then_with(|| self.id.cmp(&other.id))
+            .then_with(|| self.kind.cmp(&other.kind))
+            .then_with(|| self.status.cmp(&other.status))
+            .then_with(|| self.amount.currency.cmp(&other.amount.currency))
+            .then_with(|| self.amount.amount.cmp(&other.amount.amount))
+            .then_with(|| self.description.cmp(&other.description))
+            .then_with(|| self.account.cmp(&other.account))
+            .then_with(|| self.counterparty.cmp(&other.counterparty))
+            .then_with(|| self.category.cmp(&other.category))
+            .then_with(|| self.tax_amount.cmp(&other.tax_amount))
+            .then_with(|| self.tax_rate.cmp(&other.tax_rate))
+            .then_with(|| self.extra.cmp(&other.extra))
+    }
+}
+
+impl PartialOrd for Transaction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A [`Transaction`] whose text fields may borrow from the input it was
+/// parsed from, so that parsing doesn't allocate a string per field.
+///
+/// `formats::csv::parse_csv_borrowed` parses CSV into it.
+/// [`TransactionRef::into_owned`] turns it into a [`Transaction`], and a
+/// `TransactionRef` borrowing every field can be made from a [`Transaction`]
+/// with [`From`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionRef<'a> {
+    /// Unique identifier for the transaction
+    pub id: Cow<'a, str>,
+    /// Date when the transaction was posted to the account
+    pub posted_at: NaiveDate,
+    /// Optional timestamp when the transaction was actually executed
+    pub executed_at: Option<NaiveDateTime>,
+    /// Whether this is a debit or credit transaction
+    pub kind: TransactionKind,
+    /// Whether the transaction is booked, pending or cancelled
+    pub status: TransactionStatus,
+    /// The amount of the transaction
+    pub amount: Decimal,
+    /// The currency code of the amount
+    pub currency: Cow<'a, str>,
+    /// Human-readable description of the transaction
+    pub description: Cow<'a, str>,
+    /// Optional account identifier
+    pub account: Option<Cow<'a, str>>,
+    /// Optional counterparty (the other party in the transaction)
+    pub counterparty: Option<Cow<'a, str>>,
+    /// Optional category for transaction classification
+    pub category: Option<Cow<'a, str>>,
+    /// Optional tax (e.g. VAT) included in the amount, in the same currency
+    pub tax_amount: Option<Decimal>,
+    /// Optional tax rate in percent (`20` for 20% VAT)
+    pub tax_rate: Option<Decimal>,
+    /// Source columns without a field of their own, keyed by column name
+    pub extra: BTreeMap<Cow<'a, str>, Cow<'a, str>>,
+}
+
+impl TransactionRef<'_> {
+    /// Converts the transaction to a [`Transaction`], copying the borrowed
+    /// fields.
+    pub fn into_owned(self) -> Transaction {
+        let owned = |field: Option<Cow<'_, str>>| field.map(Cow::into_owned);
+        Transaction {
+            id: self.id.into_owned(),
+            posted_at: self.posted_at,
+            executed_at: self.executed_at,
+            kind: self.kind,
+            status: self.status,
+            amount: Money {
+                amount: self.amount,
+                currency: self.currency.into_owned(),
+            },
+            description: self.description.into_owned(),
+            account: owned(self.account),
+            counterparty: owned(self.counterparty),
+            category: owned(self.category),
+            tax_amount: self.tax_amount,
+            tax_rate: self.tax_rate,
+            extra: self
+                .extra
+                .into_iter()
+                .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a Transaction> for TransactionRef<'a> {
+    fn from(tx: &'a Transaction) -> Self {
+        let borrowed = |field: &'a Option<String>| field.as_deref().map(Cow::Borrowed);
+        TransactionRef {
+            id: Cow::Borrowed(&tx.id),
+            posted_at: tx.posted_at,
+            executed_at: tx.executed_at,
+            kind: tx.kind,
+            status: tx.status,
+            amount: tx.amount.amount,
+            currency: Cow::Borrowed(&tx.amount.currency),
+            description: Cow::Borrowed(&tx.description),
+            account: borrowed(&tx.account),
+            counterparty: borrowed(&tx.counterparty),
+            category: borrowed(&tx.category),
+            tax_amount: tx.tax_amount,
+            tax_rate: tx.tax_rate,
+            extra: tx
+                .extra
+                .iter()
+                .map(|(name, value)| (Cow::Borrowed(&name[..]), Cow::Borrowed(&value[..])))
+                .collect(),
+        }
+    }
 }
 
 /// A batch of transactions, optionally associated with an account.
@@ -70,9 +593,436 @@ pub struct Transaction {
 /// typically representing all transactions for a specific account
 /// or time period.
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct TransactionBatch {
     /// Optional identifier for the account these transactions belong to
     pub account_id: Option<String>,
     /// The list of transactions in this batch
     pub transactions: Vec<Transaction>,
+    /// Opening and closing balances declared by the source, one per currency
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub balances: Vec<Balance>,
+    /// Information about the batch as a whole, such as the period it covers
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: BatchMetadata,
+}
+
+impl TransactionBatch {
+    /// Returns the period the batch covers: the declared
+    /// [`BatchMetadata::period`] if there is one, otherwise the first to the
+    /// last posting date of its transactions. `None` for an empty batch
+    /// without a declared period.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{Format, Period};
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-01-03\nType: Credit\nAmount: 9 USD\n";
+    /// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+    /// assert_eq!(batch.period(), Some("2024-01-03..2024-01-31".parse::<Period>()?));
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn period(&self) -> Option<Period> {
+        if let Some(period) = self.metadata.period {
+            return Some(period);
+        }
+        let dates = self.transactions.iter().map(|tx| tx.posted_at);
+        Some(Period {
+            start: dates.clone().min()?,
+            end: dates.max()?,
+        })
+    }
+
+    /// Sorts the transactions into their canonical order: by posting date,
+    /// execution time and ID (see [`Transaction`]'s [`Ord`]).
+    ///
+    /// Two sources that emit the same transactions in a different order give
+    /// the same batch after sorting, so the batches can be compared record by
+    /// record.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    ///
+    /// let text = "ID: TX2\nDate: 2024-01-03\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX1\nDate: 2024-01-03\nType: Credit\nAmount: 9 USD\n";
+    /// let mut batch = parser::parse(text.as_bytes(), Format::Text)?;
+    /// batch.sort_canonical();
+    /// assert_eq!(batch.transactions[0].id, "TX1");
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn sort_canonical(&mut self) {
+        self.transactions.sort();
+    }
+}
+
+impl IntoIterator for TransactionBatch {
+    type Item = Transaction;
+    type IntoIter = alloc::vec::IntoIter<Transaction>;
+
+    /// Iterates over the transactions, dropping the rest of the batch.
+    fn into_iter(self) -> Self::IntoIter {
+        self.transactions.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TransactionBatch {
+    type Item = &'a Transaction;
+    type IntoIter = core::slice::Iter<'a, Transaction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.transactions.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut TransactionBatch {
+    type Item = &'a mut Transaction;
+    type IntoIter = core::slice::IterMut<'a, Transaction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.transactions.iter_mut()
+    }
+}
+
+/// Collects transactions into a batch without an account ID, balances or
+/// metadata.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{Format, TransactionBatch};
+///
+/// let text = "ID: TX1\nDate: 2024-01-03\nType: Debit\nAmount: 5 USD\n---\n\
+///             ID: TX2\nDate: 2024-01-04\nType: Credit\nAmount: 9 USD\n";
+/// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+///
+/// let credits: TransactionBatch = batch.into_iter().filter(|tx| tx.is_credit()).collect();
+/// assert_eq!(credits.transactions.len(), 1);
+/// # Ok::<(), parser::Error>(())
+/// ```
+impl FromIterator<Transaction> for TransactionBatch {
+    fn from_iter<I: IntoIterator<Item = Transaction>>(iter: I) -> Self {
+        TransactionBatch {
+            transactions: iter.into_iter().collect(),
+            ..TransactionBatch::default()
+        }
+    }
+}
+
+/// Appends transactions, leaving the account ID, balances and metadata as
+/// they are.
+impl Extend<Transaction> for TransactionBatch {
+    fn extend<I: IntoIterator<Item = Transaction>>(&mut self, iter: I) {
+        self.transactions.extend(iter);
+    }
+}
+
+/// Information about a batch as a whole, written in the header section of
+/// the formats that have one.
+///
+/// Every field is optional; sources that don't provide one leave it out.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct BatchMetadata {
+    /// When the source system produced the batch
+    pub generated_at: Option<NaiveDateTime>,
+    /// Name of the system that produced the batch, such as a core banking
+    /// platform
+    pub source_system: Option<String>,
+    /// The days the statement covers, which may include days without
+    /// transactions
+    pub period: Option<Period>,
+    /// Version of the source's schema, as declared by the source
+    pub schema_version: Option<String>,
+}
+
+impl BatchMetadata {
+    /// Whether no field is set, so that there is nothing to write.
+    pub fn is_empty(&self) -> bool {
+        *self == BatchMetadata::default()
+    }
+}
+
+/// A range of days, both ends included.
+///
+/// Written as `2024-01-01..2024-01-31` by [`fmt::Display`] and read back
+/// by [`FromStr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Period {
+    /// First day of the period
+    pub start: NaiveDate,
+    /// Last day of the period
+    pub end: NaiveDate,
+}
+
+impl Period {
+    /// Whether `date` falls within the period.
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FromStr for Period {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::parse("Period", alloc::format!("invalid period: {}", s));
+        let (start, end) = s.trim().split_once("..").ok_or_else(invalid)?;
+        let date = |value: &str| {
+            NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").map_err(|_| invalid())
+        };
+        let period = Period {
+            start: date(start)?,
+            end: date(end)?,
+        };
+        if period.start > period.end {
+            return Err(invalid());
+        }
+        Ok(period)
+    }
+}
+
+/// Opening and closing balance of an account in one currency, as declared by
+/// a statement.
+///
+/// See [`crate::balance::check_balances`] for verifying them against the
+/// transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Balance {
+    /// The currency code
+    pub currency: String,
+    /// Balance before the first transaction of the batch
+    pub opening: Decimal,
+    /// Balance after the last transaction of the batch
+    pub closing: Decimal,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: i64, currency: &str) -> Money {
+        Money {
+            amount: Decimal::from(amount),
+            currency: currency.into(),
+        }
+    }
+
+    #[test]
+    fn test_money_arithmetic() {
+        assert_eq!((money(5, "USD") + money(3, "USD")).unwrap(), money(8, "USD"));
+        assert_eq!((money(5, "USD") - money(8, "USD")).unwrap(), money(-3, "USD"));
+        assert_eq!(-money(5, "USD"), money(-5, "USD"));
+
+        let error = money(5, "USD").try_sub(&money(1, "EUR")).unwrap_err();
+        assert_eq!(error.to_string(), "currency mismatch: USD and EUR");
+        assert!(sum_by_currency(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_transaction_kind_direction() {
+        let mut tx = Transaction {
+            id: "TX1".into(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Refund,
+            status: TransactionStatus::Booked,
+            amount: money(40, "USD"),
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+        assert!(tx.is_credit());
+        assert_eq!(tx.signed_amount(), Decimal::from(40));
+
+        tx.kind = TransactionKind::Reversal;
+        tx.amount = money(-40, "USD");
+        assert!(!tx.is_credit());
+        assert_eq!(tx.signed_amount(), Decimal::from(-40));
+        assert_eq!(tx.unsigned_amount(), Decimal::from(40));
+
+        assert_eq!("Interest".parse(), Ok(TransactionKind::Interest));
+        assert_eq!(TransactionKind::Fee.sign(), Some(Decimal::NEGATIVE_ONE));
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        let tx = |id: &str, day: u32, hour: Option<u32>, amount: i64| Transaction {
+            id: id.into(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            executed_at: hour.and_then(|h| {
+                NaiveDate::from_ymd_opt(2024, 1, day)
+                    .unwrap()
+                    .and_hms_opt(h, 0, 0)
+            }),
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: money(amount, "USD"),
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+
+        let mut batch = TransactionBatch {
+            transactions: alloc::vec![
+                tx("A", 2, Some(9), 1),
+                tx("B", 2, Some(8), 1),
+                tx("C", 2, None, 1),
+                tx("A", 1, Some(9), 1),
+                tx("A", 2, Some(9), 0),
+            ],
+            ..TransactionBatch::default()
+        };
+        batch.sort_canonical();
+
+        let order: Vec<_> = batch
+            .transactions
+            .iter()
+            .map(|tx| (tx.posted_at.to_string(), tx.id.as_str(), tx.amount.amount))
+            .collect();
+        assert_eq!(
+            order,
+            alloc::vec![
+                ("2024-01-01".to_string(), "A", Decimal::from(1)),
+                ("2024-01-02".to_string(), "C", Decimal::from(1)),
+                ("2024-01-02".to_string(), "B", Decimal::from(1)),
+                ("2024-01-02".to_string(), "A", Decimal::from(0)),
+                ("2024-01-02".to_string(), "A", Decimal::from(1)),
+            ]
+        );
+
+        let mut scaled = tx("A", 1, None, 0);
+        scaled.amount.amount = Decimal::new(0, 2);
+        assert_eq!(scaled.cmp(&tx("A", 1, None, 0)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_batch_period() {
+        let period: Period = "2024-01-01..2024-01-31".parse().unwrap();
+        assert_eq!(period.to_string(), "2024-01-01..2024-01-31");
+        assert!(period.contains(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()));
+        assert!("2024-01-31..2024-01-01".parse::<Period>().is_err());
+        assert!("2024-01-01".parse::<Period>().is_err());
+
+        let mut batch = TransactionBatch::default();
+        assert_eq!(batch.period(), None);
+        batch.metadata.period = Some(period);
+        assert_eq!(batch.period(), Some(period));
+    }
+
+    #[test]
+    fn test_batch_iterators() {
+        let tx = |id: &str, amount: i64| Transaction {
+            id: id.into(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Credit,
+            status: TransactionStatus::Booked,
+            amount: money(amount, "USD"),
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+
+        let mut batch: TransactionBatch = [tx("A", 1), tx("B", 2)].into_iter().collect();
+        batch.account_id = Some("ACC1".into());
+        batch.extend([tx("C", 3)]);
+        for tx in &mut batch {
+            tx.amount.amount *= Decimal::TEN;
+        }
+        let total: Decimal = (&batch).into_iter().map(|tx| tx.amount.amount).sum();
+        assert_eq!(total, Decimal::from(60));
+        assert_eq!(batch.account_id.as_deref(), Some("ACC1"));
+
+        let ids: Vec<String> = batch.into_iter().map(|tx| tx.id).collect();
+        assert_eq!(ids, ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_money_display() {
+        let display = |amount: &str| Money::from_str(amount).unwrap().to_string();
+        assert_eq!(display("1000.50 USD"), "1 000.50 USD");
+        assert_eq!(display("-1234567 JPY"), "-1 234 567 JPY");
+        assert_eq!(display("999.999 BHD"), "999.999 BHD");
+        assert_eq!(display("0 EUR"), "0 EUR");
+
+        let money = money(1_000_000, "RUB");
+        assert_eq!(alloc::format!("{:#}", money), "1000000 RUB");
+        assert_eq!(alloc::format!("{:>16}", money), "   1 000 000 RUB");
+        assert!(Money::from_str("1000.50").is_err());
+        assert!(Money::from_str("abc USD").is_err());
+    }
+
+    #[test]
+    fn test_add_currency_mismatch() {
+        let error = (money(5, "USD") + money(1, "EUR")).unwrap_err();
+        assert_eq!(error.to_string(), "currency mismatch: USD and EUR");
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn test_serde_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC1".into()),
+            transactions: alloc::vec![Transaction {
+                id: "TX1".into(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(1050, 2),
+                    currency: "EUR".into(),
+                },
+                description: "Coffee".into(),
+                account: None,
+                counterparty: None,
+                category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            }],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let json = serde_json::to_value(&batch).unwrap();
+        let tx = &json["transactions"][0];
+        assert_eq!(tx["amount"]["amount"], "10.50");
+        assert_eq!(tx["posted_at"], "2024-01-15");
+        assert_eq!(tx["kind"], "Debit");
+        let parsed: TransactionBatch = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, batch);
+
+        // Fields with defaults may be left out
+        let json = r#"{"account_id": null, "transactions": []}"#;
+        assert!(serde_json::from_str::<TransactionBatch>(json).is_ok());
+    }
 }
@@ -3,17 +3,20 @@
 //! This module defines all error types that can occur during parsing
 //! and writing of financial transaction data.
 
+use alloc::boxed::Box;
+use alloc::string::String;
 use thiserror::Error as ThisError;
 
 /// A specialized Result type for parser operations.
 ///
 /// This type is used throughout the library to simplify error handling.
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
 
 /// Errors that can occur during parsing or writing of transaction data.
 #[derive(Debug, ThisError)]
 pub enum Error {
     /// An I/O error occurred while reading or writing data
+    #[cfg(feature = "std")]
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -22,6 +25,7 @@ pub enum Error {
     UnsupportedFormat(String),
 
     /// Failed to parse format from string
+    #[cfg(feature = "std")]
     #[error("invalid format: {0}")]
     InvalidFormat(#[from] strum::ParseError),
 
@@ -32,7 +36,89 @@ pub enum Error {
         format: &'static str,
         /// A detailed message describing the parsing error
         message: String,
+        /// The error of the value that failed to parse, such as a
+        /// [`chrono::ParseError`] or [`rust_decimal::Error`], when there is one
+        source: Option<Box<dyn core::error::Error + Send + Sync>>,
     },
+
+    /// The input is larger than a cap of
+    /// [`ParseOptions::limits`](crate::ParseOptions::limits) allows
+    #[error("limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// Amounts in different currencies were added or subtracted
+    #[error("currency mismatch: {left} and {right}")]
+    CurrencyMismatch {
+        /// The currency of the left operand
+        left: String,
+        /// The currency of the right operand
+        right: String,
+    },
+
+    /// A currency code isn't in ISO 4217 or
+    /// [`ParseOptions::custom_currencies`](crate::ParseOptions::custom_currencies),
+    /// with [`ParseOptions::iso_currencies`](crate::ParseOptions::iso_currencies) set
+    #[error("unknown currency {code} in {location}")]
+    UnknownCurrency {
+        /// The code as it was found
+        code: String,
+        /// Where it was found, such as `transaction TX3`
+        location: String,
+    },
+
+    /// Batches of different accounts were merged
+    #[error("account mismatch: {left} and {right}")]
+    AccountMismatch {
+        /// The account of the batch merged into
+        left: String,
+        /// The account of the merged batch
+        right: String,
+    },
+
+    /// Two different transactions with the same ID were merged with
+    /// [`MergePolicy::Error`](crate::MergePolicy::Error)
+    #[error("duplicate transaction ID {0}")]
+    DuplicateId(String),
+
+    /// A patch doesn't apply: a transaction it updates or removes is
+    /// missing, or has other values than the patch expects
+    #[error("patch conflict: {0}")]
+    PatchConflict(String),
+
+    /// A database operation failed
+    #[cfg(feature = "sql")]
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    /// An object storage operation failed
+    #[cfg(feature = "s3")]
+    #[error("object storage error: {0}")]
+    Storage(#[from] object_store::Error),
+
+    /// An HTTP request failed or returned a non-success status
+    #[cfg(feature = "http")]
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// Rendering a PDF document failed
+    #[cfg(feature = "pdf")]
+    #[error("PDF error: {0}")]
+    Pdf(#[from] printpdf::Error),
+
+    /// Reading or writing a Parquet file failed
+    #[cfg(feature = "parquet")]
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    /// Rendering or saving a chart failed
+    #[cfg(feature = "charts")]
+    #[error("chart error: {0}")]
+    Chart(String),
+
+    /// A signature doesn't match the signed data or the signer's key
+    #[cfg(feature = "signing")]
+    #[error("invalid signature")]
+    InvalidSignature,
 }
 
 impl Error {
@@ -54,6 +140,110 @@ impl Error {
         Self::Parse {
             format,
             message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Attaches the error that caused a parse error, so that it is kept as
+    /// the [`source`](core::error::Error::source) of the error instead of
+    /// only as text in its message. Other errors are returned unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use core::error::Error as _;
+    /// use parser::Error;
+    /// use rust_decimal::Decimal;
+    /// use std::str::FromStr;
+    ///
+    /// let cause = Decimal::from_str("abc").unwrap_err();
+    /// let error = Error::parse("CSV", format!("invalid amount: {}", cause)).with_source(cause);
+    /// assert!(error.source().unwrap().is::<rust_decimal::Error>());
+    /// ```
+    pub fn with_source(self, cause: impl core::error::Error + Send + Sync + 'static) -> Self {
+        match self {
+            Self::Parse {
+                format, message, ..
+            } => Self::Parse {
+                format,
+                message,
+                source: Some(Box::new(cause)),
+            },
+            other => other,
+        }
+    }
+
+    /// Puts `prefix` in front of the message of a parse error and reports it
+    /// as `format`, keeping its source, as when a nested value's error is
+    /// given the position of the record it belongs to. Other errors are
+    /// returned unchanged.
+    #[cfg(feature = "std")]
+    pub(crate) fn prefixed(self, format: &'static str, prefix: &str) -> Self {
+        match self {
+            Self::Parse {
+                message, source, ..
+            } => Self::Parse {
+                format,
+                message: alloc::format!("{}{}", prefix, message),
+                source,
+            },
+            other => other,
+        }
+    }
+
+    /// A short, stable name for the kind of error, such as `"parse"` or
+    /// `"limit_exceeded"`, for services that map errors to codes.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(_) => "io",
+            Self::UnsupportedFormat(_) => "unsupported_format",
+            #[cfg(feature = "std")]
+            Self::InvalidFormat(_) => "invalid_format",
+            Self::Parse { .. } => "parse",
+            Self::LimitExceeded(_) => "limit_exceeded",
+            Self::CurrencyMismatch { .. } => "currency_mismatch",
+            Self::UnknownCurrency { .. } => "unknown_currency",
+            Self::AccountMismatch { .. } => "account_mismatch",
+            Self::DuplicateId(_) => "duplicate_id",
+            Self::PatchConflict(_) => "patch_conflict",
+            #[cfg(feature = "sql")]
+            Self::Database(_) => "database",
+            #[cfg(feature = "s3")]
+            Self::Storage(_) => "storage",
+            #[cfg(feature = "http")]
+            Self::Http(_) => "http",
+            #[cfg(feature = "pdf")]
+            Self::Pdf(_) => "pdf",
+            #[cfg(feature = "parquet")]
+            Self::Parquet(_) => "parquet",
+            #[cfg(feature = "charts")]
+            Self::Chart(_) => "chart",
+            #[cfg(feature = "signing")]
+            Self::InvalidSignature => "invalid_signature",
+        }
+    }
+}
+
+/// Serializes as `{ "kind": ..., "message": ... }`, plus the `format` of a
+/// parse error, so that services can return errors as JSON. The message is
+/// the same text as [`Display`](core::fmt::Display).
+#[cfg(feature = "serde")]
+impl serde::Serialize for Error {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        use alloc::string::ToString;
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Error", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        match self {
+            Self::Parse { format, .. } => state.serialize_field("format", format)?,
+            _ => state.skip_field("format")?,
         }
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
@@ -0,0 +1,415 @@
+//! Fee and interest detection.
+//!
+//! Statements mix bank charges in with ordinary payments: a commission for a
+//! transfer, monthly service fees, interest earned on a savings balance or
+//! charged on an overdraft. [`ChargeRules`] tells those apart by category,
+//! merchant category code (MCC) or description keywords; the helpers here
+//! total them per currency, compute the effective interest rate over a
+//! period, and attach each fee to the payment it was charged for so that
+//! gross and net amounts can be reported side by side.
+
+use crate::stats::signed_amount;
+use crate::{Transaction, TransactionBatch};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// What kind of bank charge a transaction is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChargeKind {
+    /// A fee or commission
+    Fee,
+    /// Interest, earned (credit) or paid (debit)
+    Interest,
+}
+
+/// Rules that identify fee and interest transactions.
+///
+/// A transaction matches when its category equals one of the categories
+/// (ignoring case), its MCC is listed, or its description contains one of
+/// the keywords (ignoring case). Fee rules are checked first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChargeRules {
+    /// Categories of fee transactions
+    pub fee_categories: Vec<String>,
+    /// Merchant category codes of fee transactions
+    pub fee_mccs: Vec<u16>,
+    /// Lowercase description keywords of fee transactions
+    pub fee_keywords: Vec<String>,
+    /// Categories of interest transactions
+    pub interest_categories: Vec<String>,
+    /// Merchant category codes of interest transactions
+    pub interest_mccs: Vec<u16>,
+    /// Lowercase description keywords of interest transactions
+    pub interest_keywords: Vec<String>,
+}
+
+impl Default for ChargeRules {
+    /// Matches the `Fees` / `Interest` categories and common English and
+    /// Russian wording; no MCCs.
+    fn default() -> Self {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+        Self {
+            fee_categories: strings(&["Fees", "Fee", "Commission"]),
+            fee_mccs: Vec::new(),
+            fee_keywords: strings(&["комиссия", "commission", "fee"]),
+            interest_categories: strings(&["Interest"]),
+            interest_mccs: Vec::new(),
+            interest_keywords: strings(&["проценты", "процентов", "interest"]),
+        }
+    }
+}
+
+impl ChargeRules {
+    /// Classifies a transaction, returning `None` for ordinary payments.
+    pub fn classify(&self, tx: &Transaction) -> Option<ChargeKind> {
+        let description = tx.description.to_lowercase();
+        let category = tx.category.as_deref().map(str::to_lowercase);
+        let mcc = mcc(&tx.description);
+
+        let matches = |categories: &[String], mccs: &[u16], keywords: &[String]| {
+            category
+                .as_ref()
+                .is_some_and(|category| categories.iter().any(|c| c.to_lowercase() == *category))
+                || mcc.is_some_and(|mcc| mccs.contains(&mcc))
+                || keywords.iter().any(|k| description.contains(k.as_str()))
+        };
+
+        if matches(&self.fee_categories, &self.fee_mccs, &self.fee_keywords) {
+            Some(ChargeKind::Fee)
+        } else if matches(
+            &self.interest_categories,
+            &self.interest_mccs,
+            &self.interest_keywords,
+        ) {
+            Some(ChargeKind::Interest)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extracts a merchant category code written as `MCC 6012`, `MCC:6012` or
+/// `MCC6012` from a description.
+pub fn mcc(description: &str) -> Option<u16> {
+    let upper = description.to_uppercase();
+    let start = upper.find("MCC")? + 3;
+    let digits: String = upper[start..]
+        .trim_start_matches([' ', ':'])
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    if digits.len() == 4 {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Fees and interest in one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChargeTotal {
+    /// The currency code
+    pub currency: String,
+    /// Fees paid minus fees refunded
+    pub fees: Decimal,
+    /// Interest earned minus interest paid
+    pub interest: Decimal,
+}
+
+/// Totals fees and interest per currency, ordered by currency code.
+///
+/// Currencies without any charges are omitted.
+pub fn charge_totals(batch: &TransactionBatch, rules: &ChargeRules) -> Vec<ChargeTotal> {
+    let mut totals: BTreeMap<&str, (Decimal, Decimal)> = BTreeMap::new();
+
+    for tx in &batch.transactions {
+        let Some(kind) = rules.classify(tx) else {
+            continue;
+        };
+        let entry = totals.entry(tx.amount.currency.as_str()).or_default();
+        match kind {
+            ChargeKind::Fee => entry.0 -= signed_amount(tx),
+            ChargeKind::Interest => entry.1 += signed_amount(tx),
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(currency, (fees, interest))| ChargeTotal {
+            currency: currency.to_string(),
+            fees,
+            interest,
+        })
+        .collect()
+}
+
+/// Interest over a period, relative to the balance it was earned on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterestSummary {
+    /// Interest earned minus interest paid in the period
+    pub interest: Decimal,
+    /// Average end-of-day balance over the period
+    pub average_balance: Decimal,
+    /// Annualized interest as a percentage of the average balance, rounded to
+    /// four decimal places; `None` when the average balance is zero
+    pub effective_rate: Option<Decimal>,
+}
+
+/// Computes the effective annual interest rate in `currency` between `from`
+/// and `to`, inclusive.
+///
+/// The balance starts at zero before the first transaction, so the batch
+/// should cover the account from its opening (or include an opening-balance
+/// transaction). The rate is `interest / average balance * 365 / days`.
+pub fn effective_interest(
+    batch: &TransactionBatch,
+    rules: &ChargeRules,
+    currency: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> InterestSummary {
+    let mut daily: BTreeMap<NaiveDate, Decimal> = BTreeMap::new();
+    let mut opening = Decimal::ZERO;
+    let mut interest = Decimal::ZERO;
+
+    for tx in batch
+        .transactions
+        .iter()
+        .filter(|tx| tx.amount.currency == currency && tx.posted_at <= to)
+    {
+        if tx.posted_at < from {
+            opening += signed_amount(tx);
+            continue;
+        }
+        *daily.entry(tx.posted_at).or_default() += signed_amount(tx);
+        if rules.classify(tx) == Some(ChargeKind::Interest) {
+            interest += signed_amount(tx);
+        }
+    }
+
+    let days = (to - from).num_days() + 1;
+    let mut balance = opening;
+    let mut balance_days = Decimal::ZERO;
+    for day in from.iter_days().take(days.max(0) as usize) {
+        balance += daily.get(&day).copied().unwrap_or_default();
+        balance_days += balance;
+    }
+
+    let average_balance = if days > 0 {
+        balance_days / Decimal::from(days)
+    } else {
+        Decimal::ZERO
+    };
+    let effective_rate = (!average_balance.is_zero()).then(|| {
+        (interest / average_balance * Decimal::from(365) / Decimal::from(days)
+            * Decimal::ONE_HUNDRED)
+            .round_dp(4)
+    });
+
+    InterestSummary {
+        interest,
+        average_balance: average_balance.round_dp(2),
+        effective_rate,
+    }
+}
+
+/// A payment together with the fees charged for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Charged<'a> {
+    /// The payment
+    pub transaction: &'a Transaction,
+    /// Fees attributed to the payment
+    pub fees: Vec<&'a Transaction>,
+}
+
+impl Charged<'_> {
+    /// The payment's own signed amount (credits positive).
+    pub fn gross(&self) -> Decimal {
+        signed_amount(self.transaction)
+    }
+
+    /// The signed amount including its fees.
+    pub fn net(&self) -> Decimal {
+        self.gross()
+            + self
+                .fees
+                .iter()
+                .map(|fee| signed_amount(fee))
+                .sum::<Decimal>()
+    }
+}
+
+/// Payments with their fees attached, and the fees that matched no payment.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeeAttachment<'a> {
+    /// Every transaction that isn't a fee, in batch order
+    pub charged: Vec<Charged<'a>>,
+    /// Fees no payment could be found for
+    pub unattached: Vec<&'a Transaction>,
+}
+
+/// Attaches each fee to the payment it was charged for.
+///
+/// A fee whose description mentions an earlier payment's ID is attached to
+/// that payment (the longest matching ID wins). Otherwise it goes to the nearest preceding payment posted on the
+/// same day, in the same currency and account.
+pub fn attach_fees<'a>(batch: &'a TransactionBatch, rules: &ChargeRules) -> FeeAttachment<'a> {
+    let mut attachment = FeeAttachment::default();
+    let mut index: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for tx in &batch.transactions {
+        if rules.classify(tx) != Some(ChargeKind::Fee) {
+            index.insert(tx.id.as_str(), attachment.charged.len());
+            attachment.charged.push(Charged {
+                transaction: tx,
+                fees: Vec::new(),
+            });
+            continue;
+        }
+
+        let referenced = index
+            .iter()
+            .filter(|(id, _)| !id.is_empty() && tx.description.contains(*id))
+            .max_by_key(|(id, _)| id.len())
+            .map(|(_, &i)| i);
+        let parent = referenced.or_else(|| {
+            attachment.charged.iter().rposition(|charged| {
+                let parent = charged.transaction;
+                parent.posted_at == tx.posted_at
+                    && parent.amount.currency == tx.amount.currency
+                    && parent.account == tx.account
+            })
+        });
+
+        match parent {
+            Some(i) => attachment.charged[i].fees.push(tx),
+            None => attachment.unattached.push(tx),
+        }
+    }
+
+    attachment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use std::str::FromStr;
+
+    fn tx(
+        id: &str,
+        day: u32,
+        kind: TransactionKind,
+        amount: &str,
+        description: &str,
+    ) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str(amount).unwrap(),
+                currency: "RUB".to_string(),
+            },
+            description: description.to_string(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn batch() -> TransactionBatch {
+        use TransactionKind::{Credit, Debit};
+        TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx("T1", 1, Credit, "10000", "Opening deposit"),
+                tx("T2", 5, Debit, "1000", "Transfer to Ivan"),
+                tx("T3", 5, Debit, "15", "Комиссия за перевод"),
+                tx("T4", 6, Debit, "2000", "Card payment MCC 5411"),
+                tx("T5", 9, Debit, "30", "Fee for T4"),
+                tx("T6", 10, Debit, "99", "Service fee"),
+                tx("T7", 31, Credit, "50", "Interest on balance"),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_classify_and_totals() {
+        let rules = ChargeRules {
+            fee_mccs: vec![6012],
+            ..ChargeRules::default()
+        };
+        let batch = batch();
+
+        assert_eq!(mcc("Card payment MCC:5411 GROCERY"), Some(5411));
+        assert_eq!(rules.classify(&batch.transactions[1]), None);
+        assert_eq!(
+            rules.classify(&batch.transactions[2]),
+            Some(ChargeKind::Fee)
+        );
+        assert_eq!(
+            rules.classify(&tx("X", 1, TransactionKind::Debit, "1", "Cash MCC 6012")),
+            Some(ChargeKind::Fee)
+        );
+
+        let totals = charge_totals(&batch, &rules);
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].fees, Decimal::from(144));
+        assert_eq!(totals[0].interest, Decimal::from(50));
+    }
+
+    #[test]
+    fn test_effective_interest() {
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx("T1", 1, TransactionKind::Credit, "10000", "Deposit"),
+                tx("T2", 31, TransactionKind::Credit, "100", "Interest"),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let from = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 1, 30).unwrap();
+        let summary = effective_interest(&batch, &ChargeRules::default(), "RUB", from, to);
+        assert_eq!(summary.interest, Decimal::ZERO);
+        assert_eq!(summary.average_balance, Decimal::from(10000));
+
+        let to = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        let from = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let summary = effective_interest(&batch, &ChargeRules::default(), "RUB", from, to);
+        assert_eq!(summary.interest, Decimal::from(100));
+        assert!(summary.effective_rate.unwrap() > Decimal::from(11));
+    }
+
+    #[test]
+    fn test_attach_fees() {
+        let batch = batch();
+        let attachment = attach_fees(&batch, &ChargeRules::default());
+
+        let fees: Vec<(&str, Vec<&str>)> = attachment
+            .charged
+            .iter()
+            .map(|c| {
+                (
+                    c.transaction.id.as_str(),
+                    c.fees.iter().map(|f| f.id.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(fees[1], ("T2", vec!["T3"]));
+        assert_eq!(fees[2], ("T4", vec!["T5"]));
+        assert_eq!(attachment.charged[1].net(), Decimal::from(-1015));
+        assert_eq!(attachment.unattached.len(), 1);
+        assert_eq!(attachment.unattached[0].id, "T6");
+    }
+}
@@ -0,0 +1,589 @@
+//! Filter expressions over transactions.
+//!
+//! A [`Filter`] is parsed from a small expression language and tested
+//! against transactions with [`Filter::matches`], so that ad-hoc queries
+//! don't need a flag of their own:
+//!
+//! ```text
+//! amount > 100 && currency == "USD" && category =~ "Food|Groceries"
+//! !(kind == "fee" || date < "2024-01-01")
+//! ```
+//!
+//! A comparison is a field, an operator and a literal. The fields are:
+//!
+//! | Field | Type | Value |
+//! |-------|------|-------|
+//! | `id`, `currency`, `description`, `account`, `counterparty`, `category` | text | the field |
+//! | `kind` (or `type`), `status` | text | the name, such as `Debit`, compared ignoring case |
+//! | `amount`, `signed_amount`, `tax_amount`, `tax_rate` | number | the field, or [`Transaction::signed_amount`] |
+//! | `date`, `executed_at` | date | the posting date, or the day of the execution time |
+//!
+//! Text is compared with `==` and `!=` to a string literal in double quotes
+//! (with `\"` and `\\` escapes), or matched with `=~` against a regular
+//! expression, which matches anywhere in the text unless anchored with `^`
+//! and `$`. Numbers and dates are compared with `==`, `!=`, `<`, `<=`, `>`
+//! and `>=`, numbers to a number such as `-12.5` and dates to a string such
+//! as `"2024-01-31"`. Comparisons combine with `&&`, `||`, `!` and
+//! parentheses, `&&` binding more tightly than `||`.
+//!
+//! A comparison on an optional field that a transaction doesn't have, such
+//! as the category of an uncategorized one, is false, except for `!=`,
+//! which is true.
+
+use crate::{Error, Result, Transaction, TransactionBatch};
+use chrono::NaiveDate;
+use regex::Regex;
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed filter expression.
+///
+/// See the [module documentation](crate::filter) for the syntax.
+///
+/// # Examples
+///
+/// ```
+/// use parser::filter::Filter;
+/// use parser::Format;
+///
+/// let text = "ID: TX1\nDate: 2024-01-03\nType: Debit\nAmount: 150 USD\nCategory: Food\n---\n\
+///             ID: TX2\nDate: 2024-01-04\nType: Debit\nAmount: 150 EUR\nCategory: Food\n---\n\
+///             ID: TX3\nDate: 2024-01-05\nType: Debit\nAmount: 50 USD\nCategory: Fast food\n";
+/// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+///
+/// let filter: Filter = r#"amount > 100 && currency == "USD" && category =~ "Food""#.parse()?;
+/// let matching: Vec<_> = batch.transactions.iter().filter(|tx| filter.matches(tx)).collect();
+/// assert_eq!(matching.len(), 1);
+/// assert_eq!(matching[0].id, "TX1");
+/// # Ok::<(), parser::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    /// Whether `tx` satisfies the expression.
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        self.expr.matches(tx)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+            end: s.chars().count() + 1,
+        };
+        let expr = parser.or()?;
+        if let Some((token, column)) = parser.tokens.get(parser.position) {
+            return Err(error(*column, format!("unexpected {}", token)));
+        }
+        Ok(Filter {
+            source: s.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TransactionBatch {
+    /// Returns a batch with the transactions that match `filter`.
+    ///
+    /// The batch keeps the account ID and metadata but no balances, since
+    /// the declared balances don't describe a selection of the transactions.
+    pub fn filter(&self, filter: &Filter) -> TransactionBatch {
+        TransactionBatch {
+            account_id: self.account_id.clone(),
+            transactions: self
+                .transactions
+                .iter()
+                .filter(|tx| filter.matches(tx))
+                .cloned()
+                .collect(),
+            balances: Vec::new(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Text(TextField, bool, String),
+    Regex(TextField, Regex),
+    Number(NumberField, Op, Decimal),
+    Date(DateField, Op, NaiveDate),
+}
+
+impl Expr {
+    fn matches(&self, tx: &Transaction) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(tx) && right.matches(tx),
+            Expr::Or(left, right) => left.matches(tx) || right.matches(tx),
+            Expr::Not(inner) => !inner.matches(tx),
+            Expr::Text(field, equal, value) => match field.value(tx) {
+                Some(actual) => field.equals(&actual, value) == *equal,
+                None => !equal,
+            },
+            Expr::Regex(field, regex) => field.value(tx).is_some_and(|v| regex.is_match(&v)),
+            Expr::Number(field, op, value) => op.holds(field.value(tx).map(|v| v.cmp(value))),
+            Expr::Date(field, op, value) => op.holds(field.value(tx).map(|v| v.cmp(value))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextField {
+    Id,
+    Currency,
+    Description,
+    Account,
+    Counterparty,
+    Category,
+    Kind,
+    Status,
+}
+
+impl TextField {
+    fn value<'a>(&self, tx: &'a Transaction) -> Option<Cow<'a, str>> {
+        match self {
+            TextField::Id => Some(Cow::Borrowed(&tx.id)),
+            TextField::Currency => Some(Cow::Borrowed(&tx.amount.currency)),
+            TextField::Description => Some(Cow::Borrowed(&tx.description)),
+            TextField::Account => tx.account.as_deref().map(Cow::Borrowed),
+            TextField::Counterparty => tx.counterparty.as_deref().map(Cow::Borrowed),
+            TextField::Category => tx.category.as_deref().map(Cow::Borrowed),
+            TextField::Kind => Some(Cow::Owned(tx.kind.to_string())),
+            TextField::Status => Some(Cow::Owned(tx.status.to_string())),
+        }
+    }
+
+    fn equals(&self, actual: &str, expected: &str) -> bool {
+        match self {
+            TextField::Kind | TextField::Status => actual.eq_ignore_ascii_case(expected),
+            _ => actual == expected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NumberField {
+    Amount,
+    SignedAmount,
+    TaxAmount,
+    TaxRate,
+}
+
+impl NumberField {
+    fn value(&self, tx: &Transaction) -> Option<Decimal> {
+        match self {
+            NumberField::Amount => Some(tx.amount.amount),
+            NumberField::SignedAmount => Some(tx.signed_amount()),
+            NumberField::TaxAmount => tx.tax_amount,
+            NumberField::TaxRate => tx.tax_rate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateField {
+    Date,
+    ExecutedAt,
+}
+
+impl DateField {
+    fn value(&self, tx: &Transaction) -> Option<NaiveDate> {
+        match self {
+            DateField::Date => Some(tx.posted_at),
+            DateField::ExecutedAt => tx.executed_at.map(|dt| dt.date()),
+        }
+    }
+}
+
+enum Field {
+    Text(TextField),
+    Number(NumberField),
+    Date(DateField),
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Field> {
+        Some(match name {
+            "id" => Field::Text(TextField::Id),
+            "currency" => Field::Text(TextField::Currency),
+            "description" => Field::Text(TextField::Description),
+            "account" => Field::Text(TextField::Account),
+            "counterparty" => Field::Text(TextField::Counterparty),
+            "category" => Field::Text(TextField::Category),
+            "kind" | "type" => Field::Text(TextField::Kind),
+            "status" => Field::Text(TextField::Status),
+            "amount" => Field::Number(NumberField::Amount),
+            "signed_amount" => Field::Number(NumberField::SignedAmount),
+            "tax_amount" => Field::Number(NumberField::TaxAmount),
+            "tax_rate" => Field::Number(NumberField::TaxRate),
+            "date" => Field::Date(DateField::Date),
+            "executed_at" => Field::Date(DateField::ExecutedAt),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+}
+
+impl Op {
+    /// Whether the comparison holds, given how the field's value compares
+    /// to the literal, `None` for a missing value.
+    fn holds(&self, ordering: Option<Ordering>) -> bool {
+        let Some(ordering) = ordering else {
+            return *self == Op::Ne;
+        };
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+            Op::Match => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(Decimal),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Str(value) => write!(f, "string \"{}\"", value),
+            Token::Number(value) => write!(f, "number {}", value),
+            Token::Op(op) => write!(f, "operator '{}'", op_symbol(*op)),
+            Token::And => f.write_str("'&&'"),
+            Token::Or => f.write_str("'||'"),
+            Token::Not => f.write_str("'!'"),
+            Token::Open => f.write_str("'('"),
+            Token::Close => f.write_str("')'"),
+        }
+    }
+}
+
+fn op_symbol(op: Op) -> &'static str {
+    match op {
+        Op::Eq => "==",
+        Op::Ne => "!=",
+        Op::Lt => "<",
+        Op::Le => "<=",
+        Op::Gt => ">",
+        Op::Ge => ">=",
+        Op::Match => "=~",
+    }
+}
+
+fn error(column: usize, message: impl fmt::Display) -> Error {
+    Error::parse("Filter", format!("column {}: {}", column, message))
+}
+
+/// Splits an expression into tokens, each with its 1-based column.
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (chars[i], next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('=', Some('~')) => (Token::Op(Op::Match), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('"', _) => {
+                let mut value = String::new();
+                let mut j = i + 1;
+                loop {
+                    match chars.get(j) {
+                        None => return Err(error(column, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') => match chars.get(j + 1) {
+                            Some(&c @ ('"' | '\\')) => {
+                                value.push(c);
+                                j += 1;
+                            }
+                            _ => return Err(error(j + 1, "invalid escape, expected \\\" or \\\\")),
+                        },
+                        Some(&c) => value.push(c),
+                    }
+                    j += 1;
+                }
+                (Token::Str(value), j + 1 - i)
+            }
+            (c, _) if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.')
+                    .count()
+                    + 1;
+                let text: String = chars[i..i + len].iter().collect();
+                let value = Decimal::from_str(&text).map_err(|e| {
+                    error(column, format!("invalid number '{}'", text)).with_source(e)
+                })?;
+                (Token::Number(value), len)
+            }
+            (c, _) if c.is_ascii_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                (Token::Ident(chars[i..i + len].iter().collect()), len)
+            }
+            (c, _) => return Err(error(column, format!("unexpected character '{}'", c))),
+        };
+        tokens.push((token, column));
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens: `||` of `&&` of unary expressions.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Column just past the end of the expression, for errors at the end
+    end: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self
+            .tokens
+            .get(self.position)
+            .is_some_and(|(t, _)| t == token);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some((Token::Not, _)) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some((Token::Open, column)) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some((Token::Close, _)) => Ok(expr),
+                    _ => Err(error(column, "unclosed '('")),
+                }
+            }
+            Some((Token::Ident(name), column)) => self.comparison(&name, column),
+            Some((token, column)) => {
+                Err(error(column, format!("expected a field, found {}", token)))
+            }
+            None => Err(error(self.end, "expected a field, found the end")),
+        }
+    }
+
+    fn comparison(&mut self, name: &str, column: usize) -> Result<Expr> {
+        let field =
+            Field::parse(name).ok_or_else(|| error(column, format!("unknown field '{}'", name)))?;
+        let (op, op_column) = match self.next() {
+            Some((Token::Op(op), column)) => (op, column),
+            Some((token, column)) => {
+                return Err(error(
+                    column,
+                    format!("expected an operator, found {}", token),
+                ))
+            }
+            None => return Err(error(self.end, "expected an operator, found the end")),
+        };
+        let (value, value_column) = self
+            .next()
+            .ok_or_else(|| error(self.end, "expected a value, found the end"))?;
+        let invalid = |expected: &str| {
+            error(
+                value_column,
+                format!(
+                    "'{}' {} expects {}, found {}",
+                    name,
+                    op_symbol(op),
+                    expected,
+                    value
+                ),
+            )
+        };
+
+        match (field, op, &value) {
+            (Field::Text(field), Op::Match, Token::Str(pattern)) => {
+                let regex = Regex::new(pattern).map_err(|e| {
+                    error(value_column, format!("invalid regular expression: {}", e)).with_source(e)
+                })?;
+                Ok(Expr::Regex(field, regex))
+            }
+            (Field::Text(field), Op::Eq | Op::Ne, Token::Str(text)) => {
+                Ok(Expr::Text(field, op == Op::Eq, text.clone()))
+            }
+            (Field::Text(_), Op::Eq | Op::Ne | Op::Match, _) => Err(invalid("a string")),
+            (Field::Text(_), _, _) => Err(error(
+                op_column,
+                format!(
+                    "'{}' is text and can't be compared with '{}'",
+                    name,
+                    op_symbol(op)
+                ),
+            )),
+            (_, Op::Match, _) => Err(error(
+                op_column,
+                format!("'=~' only applies to text, not to '{}'", name),
+            )),
+            (Field::Number(field), _, Token::Number(number)) => {
+                Ok(Expr::Number(field, op, *number))
+            }
+            (Field::Number(_), _, _) => Err(invalid("a number")),
+            (Field::Date(field), _, Token::Str(text)) => {
+                let date = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                    .map_err(|_| invalid("a date such as \"2024-01-31\""))?;
+                Ok(Expr::Date(field, op, date))
+            }
+            (Field::Date(_), _, _) => Err(invalid("a date such as \"2024-01-31\"")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+
+    fn tx(id: &str, kind: TransactionKind, amount: &str, category: Option<&str>) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 10).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str(amount).unwrap(),
+                currency: "USD".to_string(),
+            },
+            description: "Card payment \"Cafe\"".to_string(),
+            account: None,
+            counterparty: None,
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn matching(filter: &str) -> String {
+        let filter: Filter = filter.parse().unwrap();
+        [
+            tx("A", TransactionKind::Debit, "150", Some("Food")),
+            tx("B", TransactionKind::Credit, "150", Some("Salary")),
+            tx("C", TransactionKind::Fee, "2.50", None),
+        ]
+        .iter()
+        .filter(|tx| filter.matches(tx))
+        .map(|tx| tx.id.as_str())
+        .collect()
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        assert_eq!(matching("amount > 100"), "AB");
+        assert_eq!(matching("signed_amount < 0"), "AC");
+        assert_eq!(matching("amount >= 2.5 && amount <= 2.50"), "C");
+        assert_eq!(matching(r#"kind == "FEE" || category =~ "^Sal""#), "BC");
+        assert_eq!(matching(r#"!(currency == "USD") || id != "B""#), "AC");
+        assert_eq!(matching(r#"category != "Food""#), "BC");
+        assert_eq!(matching(r#"category =~ ".""#), "AB");
+        assert_eq!(matching(r#"description == "Card payment \"Cafe\"""#), "ABC");
+        assert_eq!(matching(r#"date == "2024-01-10""#), "ABC");
+        // No transaction has an execution time
+        assert_eq!(matching(r#"executed_at < "2030-01-01""#), "");
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        let message = |filter: &str| filter.parse::<Filter>().unwrap_err().to_string();
+
+        assert!(message("payee == \"x\"").contains("column 1: unknown field 'payee'"));
+        assert!(message("amount > \"x\"").contains("column 10: 'amount' > expects a number"));
+        assert!(message("category > \"x\"").contains("can't be compared with '>'"));
+        assert!(message("amount =~ \"1\"").contains("'=~' only applies to text"));
+        assert!(message("date < \"Jan\"").contains("expects a date"));
+        assert!(message("(amount > 1").contains("column 1: unclosed '('"));
+        assert!(message("amount > 1 amount").contains("column 12: unexpected 'amount'"));
+        assert!(message("id == \"x").contains("unterminated string"));
+        assert!(message("category =~ \"(\"").contains("invalid regular expression"));
+        assert!(message("").contains("expected a field, found the end"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Content fingerprints of transactions and batches.
+//!
+//! A fingerprint is a SHA-256 digest over a canonical encoding of the data,
+//! formatted as `sha256:<hex digest>`. Differences that don't change the
+//! data are left out of the encoding, in the same way as
+//! [`crate::canonical::canonicalize`] removes them: `10.50` and `10.5` hash
+//! the same, as do an empty optional field and a missing one, and execution
+//! times count to the second. Two statements with the same transactions
+//! therefore have the same fingerprint whichever format they were read from,
+//! without having to diff them.
+//!
+//! These fingerprints don't depend on the order of a batch's transactions.
+//! [`crate::manifest::ordered_fingerprint`], which binds manifests and
+//! deltas to a file, does.
+
+use crate::hashing::{digest_string, hash_field};
+use crate::{Transaction, TransactionBatch};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chrono::Timelike;
+use sha2::{Digest, Sha256};
+
+/// Prefix of the encoding of a transaction.
+const TRANSACTION_TAG: &[u8] = b"ypbank-transaction-v1";
+/// Prefix of the encoding of a batch, so that a batch never has the
+/// fingerprint of a transaction.
+const BATCH_TAG: &[u8] = b"ypbank-batch-v1";
+
+impl Transaction {
+    /// Returns the content fingerprint of the transaction.
+    ///
+    /// Every field is hashed, including [`Transaction::extra`]. See the
+    /// [module documentation](crate::fingerprint) for the differences that
+    /// don't change the fingerprint.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    /// use rust_decimal::Decimal;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10.50 USD\n";
+    /// let mut tx = parser::parse(text.as_bytes(), Format::Text)?.transactions.remove(0);
+    /// let fingerprint = tx.fingerprint();
+    /// assert!(fingerprint.starts_with("sha256:"));
+    ///
+    /// tx.amount.amount = Decimal::new(105, 1);
+    /// assert_eq!(tx.fingerprint(), fingerprint);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn fingerprint(&self) -> String {
+        digest_string(&transaction_digest(self))
+    }
+}
+
+impl TransactionBatch {
+    /// Returns the content fingerprint of the batch's transactions.
+    ///
+    /// The fingerprint doesn't depend on the order of the transactions, and
+    /// leaves out the account ID, balances and metadata, since not every
+    /// format carries them. A transaction that appears twice counts twice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10.50 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-01-16\nType: Credit\nAmount: 99 USD\n";
+    /// let mut batch = parser::parse(text.as_bytes(), Format::Text)?;
+    /// let fingerprint = batch.fingerprint();
+    ///
+    /// batch.transactions.reverse();
+    /// assert_eq!(batch.fingerprint(), fingerprint);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn fingerprint(&self) -> String {
+        let mut digests: Vec<[u8; 32]> = self.transactions.iter().map(transaction_digest).collect();
+        digests.sort_unstable();
+
+        let mut hasher = Sha256::new();
+        hasher.update(BATCH_TAG);
+        hasher.update((digests.len() as u64).to_le_bytes());
+        for digest in &digests {
+            hasher.update(digest);
+        }
+        digest_string(&hasher.finalize())
+    }
+}
+
+fn transaction_digest(tx: &Transaction) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(TRANSACTION_TAG);
+
+    let executed_at = tx
+        .executed_at
+        .map(|dt| dt.with_nanosecond(0).unwrap_or(dt))
+        .map(|dt| format!("{}", dt.format("%Y-%m-%d %H:%M:%S")));
+    let decimal = |value: Option<rust_decimal::Decimal>| value.map(|v| v.normalize().to_string());
+
+    hash_field(&mut hasher, Some(&tx.id));
+    hash_field(
+        &mut hasher,
+        Some(&format!("{}", tx.posted_at.format("%Y-%m-%d"))),
+    );
+    hash_field(&mut hasher, executed_at.as_deref());
+    hash_field(&mut hasher, Some(&tx.kind.to_string()));
+    hash_field(&mut hasher, Some(&tx.status.to_string()));
+    hash_field(&mut hasher, decimal(Some(tx.amount.amount)).as_deref());
+    hash_field(&mut hasher, Some(&tx.amount.currency));
+    hash_field(&mut hasher, Some(&tx.description));
+    for value in [&tx.account, &tx.counterparty, &tx.category] {
+        hash_field(&mut hasher, value.as_deref().filter(|v| !v.is_empty()));
+    }
+    hash_field(&mut hasher, decimal(tx.tax_amount).as_deref());
+    hash_field(&mut hasher, decimal(tx.tax_rate).as_deref());
+
+    hasher.update((tx.extra.len() as u64).to_le_bytes());
+    for (key, value) in &tx.extra {
+        hash_field(&mut hasher, Some(key));
+        hash_field(&mut hasher, Some(value));
+    }
+
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::collections::BTreeMap;
+    use alloc::vec;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn tx(id: &str, amount: Decimal) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            executed_at: NaiveDate::from_ymd_opt(2024, 3, 1)
+                .unwrap()
+                .and_hms_milli_opt(9, 30, 0, 250),
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount,
+                currency: "EUR".to_string(),
+            },
+            description: "Groceries".to_string(),
+            account: None,
+            counterparty: Some(String::new()),
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_transaction_fingerprint() {
+        let original = tx("TX1", Decimal::new(1250, 2));
+
+        let mut same = tx("TX1", Decimal::new(125, 1));
+        same.counterparty = None;
+        same.executed_at = same.executed_at.and_then(|dt| dt.with_nanosecond(0));
+        assert_eq!(same.fingerprint(), original.fingerprint());
+
+        let mut pending = original.clone();
+        pending.status = TransactionStatus::Pending;
+        let mut extra = original.clone();
+        extra.extra.insert("MCC".to_string(), "5411".to_string());
+        for changed in [tx("TX2", Decimal::new(1250, 2)), pending, extra] {
+            assert_ne!(changed.fingerprint(), original.fingerprint());
+        }
+    }
+
+    #[test]
+    fn test_batch_fingerprint() {
+        let batch = |transactions| TransactionBatch {
+            transactions,
+            ..TransactionBatch::default()
+        };
+        let a = tx("TX1", Decimal::new(5, 0));
+        let b = tx("TX2", Decimal::new(7, 0));
+
+        let forward = batch(vec![a.clone(), b.clone()]);
+        let mut reversed = batch(vec![b.clone(), a.clone()]);
+        reversed.account_id = Some("ACC1".to_string());
+        assert_eq!(forward.fingerprint(), reversed.fingerprint());
+
+        assert_ne!(forward.fingerprint(), batch(vec![a.clone()]).fingerprint());
+        assert_ne!(
+            batch(vec![a.clone(), a.clone()]).fingerprint(),
+            batch(vec![a.clone()]).fingerprint()
+        );
+        assert_ne!(batch(vec![a.clone()]).fingerprint(), a.fingerprint());
+    }
+}
@@ -0,0 +1,160 @@
+use super::posting::{journal, Entry};
+use crate::{Result, TransactionBatch};
+use chrono::Duration;
+use std::io::Write;
+
+pub use super::posting::AccountMapping;
+
+/// Writes transactions as Beancount directives.
+///
+/// The output opens every account used on the first posting date, books
+/// each non-zero opening balance of [`TransactionBatch::balances`] against
+/// [`AccountMapping::opening_balances`], then lists one `*` transaction per
+/// record with the counterparty as payee, the description as narration and
+/// the transaction ID as `id` metadata. Closing balances become `balance`
+/// assertions on the day after the last posting, so `bean-check` verifies
+/// the statement.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the directives to
+/// * `mapping` - Which accounts to book the transactions to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`](crate::Error) if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    mapping: &AccountMapping,
+) -> Result<()> {
+    let Some(journal) = journal(batch, mapping) else {
+        return Ok(());
+    };
+
+    if let Some(account_id) = &batch.account_id {
+        writeln!(writer, "; Account {}", account_id)?;
+        writeln!(writer)?;
+    }
+
+    for account in &journal.accounts {
+        writeln!(writer, "{} open {}", journal.first, account)?;
+    }
+
+    for entry in &journal.entries {
+        writeln!(writer)?;
+        write_entry(writer, entry)?;
+    }
+
+    if !batch.balances.is_empty() {
+        writeln!(writer)?;
+    }
+    let check_date = journal.last + Duration::days(1);
+    for balance in &batch.balances {
+        writeln!(
+            writer,
+            "{} balance {} {} {}",
+            check_date, mapping.asset_account, balance.closing, balance.currency
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &Entry) -> Result<()> {
+    match entry.payee {
+        Some(payee) => writeln!(
+            writer,
+            "{} * {} {}",
+            entry.date,
+            quote(payee),
+            quote(entry.narration)
+        )?,
+        None => writeln!(writer, "{} * {}", entry.date, quote(entry.narration))?,
+    }
+    if let Some(id) = entry.id {
+        writeln!(writer, "  id: {}", quote(id))?;
+    }
+    for posting in &entry.postings {
+        writeln!(
+            writer,
+            "  {:<40} {:>14} {}",
+            posting.account, posting.amount, entry.currency
+        )?;
+    }
+    Ok(())
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, Money, Transaction, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_beancount() {
+        let tx = |id: &str, day, kind, amount, category: Option<&str>| Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(amount, 2),
+                currency: "USD".to_string(),
+            },
+            description: "Weekly \"big\" shop".to_string(),
+            account: None,
+            counterparty: Some("Grocer".to_string()),
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![
+                tx("TX2", 20, TransactionKind::Debit, 4550, Some("Groceries")),
+                tx("TX1", 15, TransactionKind::Credit, 100000, None),
+            ],
+            balances: vec![Balance {
+                currency: "USD".to_string(),
+                opening: Decimal::new(1000, 2),
+                closing: Decimal::new(96450, 2),
+            }],
+            metadata: Default::default(),
+        };
+        let mut mapping = AccountMapping {
+            asset_account: "Assets:Bank:Checking".to_string(),
+            ..AccountMapping::default()
+        };
+        mapping
+            .by_category
+            .insert("Groceries".to_string(), "Expenses:Food".to_string());
+
+        let mut output = Vec::new();
+        write(&batch, &mut output, &mapping).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("2024-01-15 open Assets:Bank:Checking\n"));
+        assert!(output.contains("2024-01-15 open Equity:Opening-Balances\n"));
+        assert!(output.contains("2024-01-15 open Expenses:Food\n"));
+        assert!(output.contains("2024-01-15 open Income:Uncategorized\n"));
+        assert!(output.contains("2024-01-20 * \"Grocer\" \"Weekly \\\"big\\\" shop\"\n"));
+        assert!(output.contains("  id: \"TX2\"\n  Assets:Bank:Checking"));
+        assert!(output.contains("  Expenses:Food                                     45.50 USD\n"));
+        assert!(output.contains("2024-01-21 balance Assets:Bank:Checking 964.50 USD\n"));
+        assert!(output.find("\"TX1\"").unwrap() < output.find("\"TX2\"").unwrap());
+    }
+}
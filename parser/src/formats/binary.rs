@@ -1,11 +1,86 @@
-use crate::{Error, Money, Result, Transaction, TransactionBatch, TransactionKind};
+use super::Limits;
+#[cfg(feature = "std")]
+use super::{ParseOptions, WriteOptions};
+use crate::{
+    Balance, BatchMetadata, Error, Money, Period, Result, Transaction, TransactionBatch,
+    TransactionKind, TransactionStatus,
+};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
 use chrono::{Datelike, NaiveDate};
+#[cfg(feature = "std")]
+use core::ops::{Bound, RangeBounds};
+use core::str::FromStr;
 use rust_decimal::Decimal;
-use std::io::{Read, Write};
-use std::str::FromStr;
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
 
 const MAGIC_NUMBER: u32 = 0x59504246;
-const VERSION: u8 = 1;
+/// Current version of the binary format. Version 2 added the batch and
+/// per-transaction extension blocks, and version 3 replaced fixed-width
+/// integers and decimal strings with a compact encoding; version 1 and 2
+/// files are still read.
+pub(crate) const VERSION: u8 = 3;
+
+/// Days from 0001-01-01 (chrono's day 1) to 1970-01-01.
+const EPOCH_DAYS: i32 = 719_163;
+
+/// Header flag set when everything after the flags is compressed with zstd.
+const FLAG_ZSTD: u8 = 1;
+/// Header flag set when each transaction is followed by the CRC32 of its
+/// bytes, and the batch by the CRC32 of all bytes before it.
+const FLAG_CHECKSUMS: u8 = 2;
+/// Header flag set when transactions' currencies, accounts, counterparties
+/// and categories are written once and then referenced (see [`StringTable`]).
+const FLAG_DICTIONARY: u8 = 4;
+/// Header flag set when the batch is followed by an index of its transactions
+/// (see [`IndexedBinary`]).
+const FLAG_INDEX: u8 = 8;
+/// Header flag set when transactions come in chunks instead of after their
+/// total count, so that more can be added later (see [`append`]).
+const FLAG_CHUNKED: u8 = 16;
+/// Header flag set when everything after the flags is sealed with AES-256-GCM
+/// (see [`EncryptionKey`]).
+const FLAG_ENCRYPTED: u8 = 32;
+/// Header flag set when an Ed25519 signature of the batch precedes the
+/// (possibly compressed) payload, inside the encryption if any.
+const FLAG_SIGNED: u8 = 64;
+/// Length of an embedded signature.
+const SIGNATURE_LEN: usize = 64;
+/// Flags of batches written without options.
+const DEFAULT_FLAGS: u8 = FLAG_DICTIONARY | FLAG_CHUNKED;
+
+/// Batch extension tag for one [`Balance`].
+const EXT_BALANCE: u8 = 1;
+/// Batch extension tag for [`BatchMetadata::generated_at`], in seconds since
+/// the Unix epoch (version 3 only).
+const EXT_GENERATED_AT: u8 = 2;
+/// Batch extension tag for [`BatchMetadata::source_system`] (version 3 only).
+const EXT_SOURCE_SYSTEM: u8 = 3;
+/// Batch extension tag for [`BatchMetadata::period`], its first and last day
+/// in days since the epoch (version 3 only).
+const EXT_PERIOD: u8 = 4;
+/// Batch extension tag for [`BatchMetadata::schema_version`] (version 3
+/// only).
+const EXT_SCHEMA_VERSION: u8 = 5;
+
+/// Extension tag for [`Transaction::tax_amount`].
+const EXT_TAX_AMOUNT: u8 = 1;
+/// Extension tag for [`Transaction::tax_rate`].
+const EXT_TAX_RATE: u8 = 2;
+/// Extension tag for one entry of [`Transaction::extra`], its key and value
+/// as two strings (version 3 only).
+const EXT_EXTRA: u8 = 3;
+/// Extension tag for the [`Transaction::status`] of transactions that aren't
+/// booked: 1 for pending, 2 for cancelled (version 3 only).
+const EXT_STATUS: u8 = 4;
 
 /// Parses transaction data from a binary format.
 ///
@@ -27,70 +102,2063 @@ const VERSION: u8 = 1;
 /// This function will return an error if:
 /// - The magic number is invalid
 /// - The version is not supported
+/// - The data is compressed and the `zstd` feature is disabled
+/// - The data is encrypted (see [`parse_binary_with`])
 /// - The binary data is corrupted or incomplete
+#[cfg(feature = "std")]
 pub fn parse_binary<R: Read>(mut reader: R) -> Result<TransactionBatch> {
-    let magic = read_u32(&mut reader)?;
-    if magic != MAGIC_NUMBER {
+    read_batch(&mut IoSource(&mut reader), None)
+}
+
+/// Parses transaction data from a binary format with the given options.
+///
+/// Behaves like [`parse_binary`], except that with the `encryption` feature
+/// encrypted data is decrypted with `ParseOptions::encryption_key`, and with
+/// the `signing` feature the embedded signature is checked against
+/// `ParseOptions::verifying_key`. Other options don't apply to binary input.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing binary transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the data is invalid or corrupted, can't be decrypted with the key, or
+/// isn't signed by the owner of the verifying key.
+#[cfg(feature = "std")]
+pub fn parse_binary_with<R: Read>(
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    #[cfg(feature = "encryption")]
+    let key = options.encryption_key.as_ref();
+    #[cfg(not(feature = "encryption"))]
+    let key = None;
+    let source = &mut Limited(&mut IoSource(&mut reader), options.limits);
+    let (batch, signature) = read_signed_batch(source, key)?;
+
+    #[cfg(feature = "signing")]
+    if let Some(key) = &options.verifying_key {
+        let signature =
+            signature.ok_or_else(|| Error::parse("Binary", "the data is not signed"))?;
+        crate::signature::verify_batch(
+            &batch,
+            &crate::signature::Signature::from_bytes(signature),
+            key,
+        )?;
+    }
+    #[cfg(not(feature = "signing"))]
+    let _ = (options, signature);
+    Ok(batch)
+}
+
+/// Decodes transaction data in binary format from a byte slice.
+///
+/// This is the `no_std` counterpart of [`parse_binary`]: it only needs
+/// `alloc`, so it can run on devices without an operating system. Bytes
+/// after the end of the batch are ignored.
+///
+/// # Arguments
+///
+/// * `bytes` - The encoded batch
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all decoded transactions, or an [`Error`]
+/// if the data is invalid, corrupted or truncated.
+///
+/// # Examples
+///
+/// ```
+/// use parser::formats::binary::{decode_binary, encode_binary};
+/// use parser::TransactionBatch;
+///
+/// let mut bytes = Vec::new();
+/// encode_binary(&TransactionBatch::default(), &mut bytes).unwrap();
+/// assert_eq!(decode_binary(&bytes).unwrap(), TransactionBatch::default());
+/// ```
+pub fn decode_binary(mut bytes: &[u8]) -> Result<TransactionBatch> {
+    read_batch(&mut bytes, None)
+}
+
+/// Writes transaction data in binary format.
+///
+/// This function writes a compact binary representation of the transaction batch,
+/// including a magic number and version header for validation.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the binary data to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+#[cfg(feature = "std")]
+pub fn write_binary<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    write_batch(&mut IoSink(writer), batch, DEFAULT_FLAGS)
+}
+
+/// Writes transaction data in a given version of the binary format.
+///
+/// [`parse_binary`] reads every version, but consumers built against an
+/// older release only read the version they know; this produces files for
+/// them. Version 3 is what [`write_binary`] writes. Version 2 has no string
+/// table and drops the `extra` fields, statuses and batch metadata, and
+/// version 1 drops balances and tax fields as well.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the binary data to
+/// * `version` - The format version, from 1 to 3
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails, or if the
+/// version is unknown.
+#[cfg(feature = "std")]
+pub fn write_binary_versioned<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    version: u8,
+) -> Result<()> {
+    match version {
+        1 | 2 => write_batch_v2(&mut IoSink(writer), batch, version),
+        VERSION => write_binary(batch, writer),
+        _ => Err(Error::UnsupportedFormat(format!(
+            "binary format version {}",
+            version
+        ))),
+    }
+}
+
+/// Writes transaction data in binary format with the given options.
+///
+/// Behaves like [`write_binary`], except that [`WriteOptions::checksums`]
+/// protects each transaction and the whole batch with a CRC32, so that
+/// [`parse_binary`] reports exactly which record of a damaged file is
+/// corrupted, and [`WriteOptions::index`] appends an index for
+/// [`open_indexed`]. With the `zstd` feature `WriteOptions::compression`
+/// compresses the batch (see `write_binary_compressed`), and with the
+/// `encryption` feature `WriteOptions::encryption_key` encrypts it, after
+/// compression. With the `signing` feature `WriteOptions::signing_key`
+/// embeds a signature of the batch (see `parser::signature`). Other options
+/// don't apply to binary output.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the binary data to
+/// * `options` - How to write the batch
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails, or if
+/// an index is requested together with compression, encryption or a
+/// signature.
+#[cfg(feature = "std")]
+pub fn write_binary_with<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<()> {
+    let mut flags = DEFAULT_FLAGS;
+    if options.checksums {
+        flags |= FLAG_CHECKSUMS;
+    }
+    #[cfg(feature = "zstd")]
+    if options.compression.is_some() {
+        flags |= FLAG_ZSTD;
+    }
+    #[cfg(feature = "encryption")]
+    if options.encryption_key.is_some() {
+        flags |= FLAG_ENCRYPTED;
+    }
+    #[cfg(feature = "signing")]
+    if options.signing_key.is_some() {
+        flags |= FLAG_SIGNED;
+    }
+    if options.index {
+        if flags & (FLAG_ZSTD | FLAG_ENCRYPTED | FLAG_SIGNED) != 0 {
+            return Err(Error::UnsupportedFormat(
+                "indexed binary files can't be compressed, encrypted or signed".to_string(),
+            ));
+        }
+        // The index refers to one string table for the whole batch
+        flags = flags & !FLAG_CHUNKED | FLAG_INDEX;
+    }
+
+    writer.write_all(&header(flags))?;
+    #[cfg(feature = "encryption")]
+    if let Some(key) = &options.encryption_key {
+        let mut payload = Vec::new();
+        write_body(&mut payload, batch, flags, options)?;
+        writer.write_all(&key.seal(&header(flags), payload)?)?;
+        return Ok(());
+    }
+    write_body(writer, batch, flags, options)
+}
+
+/// Writes what follows the header: the signature and the payload,
+/// compressed if `flags` say so.
+#[cfg(feature = "std")]
+#[cfg_attr(not(feature = "zstd"), allow(unused_variables))]
+fn write_body<W: Write>(
+    writer: &mut W,
+    batch: &TransactionBatch,
+    flags: u8,
+    options: &WriteOptions,
+) -> Result<()> {
+    #[cfg(feature = "signing")]
+    if let Some(key) = &options.signing_key {
+        let signature = crate::signature::sign_batch(batch, key);
+        writer.write_all(signature.as_bytes())?;
+    }
+    #[cfg(feature = "zstd")]
+    if let Some(level) = options.compression {
+        let mut encoder = zstd::Encoder::new(writer, level)?;
+        write_payload(&mut IoSink(&mut encoder), batch, flags)?;
+        encoder.finish()?;
+        return Ok(());
+    }
+    write_payload(&mut IoSink(writer), batch, flags)
+}
+
+/// A 256-bit AES-GCM key for encrypted binary files.
+///
+/// With a key in [`WriteOptions`], binary output is sealed with
+/// AES-256-GCM: only the magic number, version and flags stay readable, and
+/// they are authenticated too. Parsing the output needs the same key in
+/// [`ParseOptions`]; a wrong key or any modification makes it fail.
+///
+/// # Examples
+///
+/// ```
+/// use parser::formats::binary::{parse_binary_with, write_binary_with, EncryptionKey};
+/// use parser::{ParseOptions, TransactionBatch, WriteOptions};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let key: EncryptionKey = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff".parse()?;
+/// let mut bytes = Vec::new();
+/// let options = WriteOptions { encryption_key: Some(key.clone()), ..WriteOptions::default() };
+/// write_binary_with(&TransactionBatch::default(), &mut bytes, &options)?;
+///
+/// let options = ParseOptions { encryption_key: Some(key), ..ParseOptions::default() };
+/// assert_eq!(parse_binary_with(bytes.as_slice(), &options)?, TransactionBatch::default());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "encryption")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+#[cfg(feature = "encryption")]
+impl EncryptionKey {
+    /// Creates a key from its bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a random key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system's random number generator fails.
+    pub fn generate() -> Result<Self> {
+        let mut bytes = [0u8; 32];
+        ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut bytes)
+            .map_err(|_| Error::parse("Binary", "cannot generate a key"))?;
+        Ok(Self(bytes))
+    }
+
+    /// The key's bytes.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    fn aead_key(&self) -> ring::aead::LessSafeKey {
+        let key = ring::aead::UnboundKey::new(&ring::aead::AES_256_GCM, &self.0)
+            .expect("AES-256 keys are 32 bytes");
+        ring::aead::LessSafeKey::new(key)
+    }
+
+    /// Encrypts `payload`, authenticating `header` along with it, and
+    /// returns the random nonce followed by the ciphertext and tag.
+    fn seal(&self, header: &[u8], mut payload: Vec<u8>) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; ring::aead::NONCE_LEN];
+        ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut nonce)
+            .map_err(|_| Error::parse("Binary", "cannot generate a nonce"))?;
+        self.aead_key()
+            .seal_in_place_append_tag(
+                ring::aead::Nonce::assume_unique_for_key(nonce),
+                ring::aead::Aad::from(header),
+                &mut payload,
+            )
+            .map_err(|_| Error::parse("Binary", "encryption failed"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&payload);
+        Ok(sealed)
+    }
+
+    /// Reverses [`EncryptionKey::seal`].
+    fn open(&self, header: &[u8], mut sealed: Vec<u8>) -> Result<Vec<u8>> {
+        if sealed.len() < ring::aead::NONCE_LEN {
+            return Err(Error::parse("Binary", "unexpected end of data"));
+        }
+        let mut payload = sealed.split_off(ring::aead::NONCE_LEN);
+        let nonce = ring::aead::Nonce::try_assume_unique_for_key(&sealed)
+            .map_err(|_| Error::parse("Binary", "invalid nonce"))?;
+        let len = self
+            .aead_key()
+            .open_in_place(nonce, ring::aead::Aad::from(header), &mut payload)
+            .map_err(|_| Error::parse("Binary", "decryption failed: wrong key or corrupted data"))?
+            .len();
+        payload.truncate(len);
+        Ok(payload)
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl core::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// Parses a key from 64 hexadecimal digits.
+#[cfg(feature = "encryption")]
+impl FromStr for EncryptionKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::parse("Binary", "invalid key: expected 64 hexadecimal digits");
+        let s = s.trim();
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(invalid());
+        }
+        let mut bytes = [0u8; 32];
+        for (byte, pair) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let pair = core::str::from_utf8(pair).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// The key [`read_batch`] decrypts with, which can't exist without the
+/// `encryption` feature.
+#[cfg(feature = "encryption")]
+type Key = EncryptionKey;
+#[cfg(not(feature = "encryption"))]
+type Key = core::convert::Infallible;
+
+/// Writes transaction data in binary format, compressing the payload with
+/// zstd.
+///
+/// The magic number, version and flags stay uncompressed, so the output is
+/// still recognisably binary transaction data, and [`parse_binary`]
+/// decompresses it transparently.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the binary data to
+/// * `level` - The zstd compression level, from 1 to 22; 0 selects zstd's
+///   default
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+#[cfg(feature = "zstd")]
+pub fn write_binary_compressed<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    level: i32,
+) -> Result<()> {
+    let options = WriteOptions {
+        compression: Some(level),
+        ..WriteOptions::default()
+    };
+    write_binary_with(batch, writer, &options)
+}
+
+/// Encodes a batch in binary format, appending the bytes to `out`.
+///
+/// This is the `no_std` counterpart of [`write_binary`]. The output is
+/// byte-for-byte identical.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to encode
+/// * `out` - The buffer to append to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if the batch cannot be encoded.
+pub fn encode_binary(batch: &TransactionBatch, out: &mut Vec<u8>) -> Result<()> {
+    write_batch(out, batch, DEFAULT_FLAGS)
+}
+
+/// Opens a binary file written with [`WriteOptions::index`] for random
+/// access by transaction ID.
+///
+/// Only the header and the index are read; transactions are decoded as they
+/// are looked up.
+///
+/// # Arguments
+///
+/// * `path` - The file to open
+///
+/// # Returns
+///
+/// Returns an [`IndexedBinary`] over the file, or an [`Error`] if it can't
+/// be read or has no index.
+///
+/// # Examples
+///
+/// ```no_run
+/// use parser::formats::binary::open_indexed;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut archive = open_indexed("archive.bin")?;
+/// if let Some(tx) = archive.get("TX-2024-000042")? {
+///     println!("{}: {}", tx.id, tx.amount.amount);
+/// }
+/// for tx in archive.range("TX-2024-01".."TX-2024-02")? {
+///     println!("{}", tx.id);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn open_indexed<P: AsRef<Path>>(path: P) -> Result<IndexedBinary<BufReader<File>>> {
+    IndexedBinary::new(BufReader::new(File::open(path)?))
+}
+
+/// A binary batch written with [`WriteOptions::index`], whose transactions
+/// are read individually, by ID.
+///
+/// When several transactions share an ID, the first one is found.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IndexedBinary<R> {
+    reader: R,
+    flags: u8,
+    account_id: Option<String>,
+    balances: Vec<Balance>,
+    metadata: BatchMetadata,
+    strings: Vec<String>,
+    /// Offset of each transaction and the posted date (in days since the
+    /// epoch) that its own is relative to
+    index: BTreeMap<String, (u64, i128)>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read + Seek> IndexedBinary<R> {
+    /// Reads the header and index of an indexed binary batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The batch, from its start
+    ///
+    /// # Returns
+    ///
+    /// Returns the opened batch, or an [`Error`] if the data is invalid or
+    /// has no index.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let source = &mut IoSource(&mut reader);
+        if read_u32(source)? != MAGIC_NUMBER {
+            return Err(Error::parse("Binary", "invalid magic number"));
+        }
+        let version = read_u8(source)?;
+        let flags = if version == VERSION {
+            read_u8(source)?
+        } else {
+            0
+        };
+        if flags & FLAG_INDEX == 0 {
+            return Err(Error::parse("Binary", "the file has no index"));
+        }
+        if flags & !(FLAG_CHECKSUMS | FLAG_DICTIONARY | FLAG_INDEX) != 0 {
+            return Err(Error::parse(
+                "Binary",
+                format!("unsupported flags: {:#04x}", flags),
+            ));
+        }
+        let (account_id, balances, metadata) = read_batch_fields_v3(source)?;
+
+        reader.seek(SeekFrom::End(-8))?;
+        let mut offset = [0u8; 8];
+        reader.read_exact(&mut offset)?;
+        reader.seek(SeekFrom::Start(u64::from_le_bytes(offset)))?;
+
+        let source = &mut IoSource(&mut reader);
+        let count = read_len(source)?;
+        let mut strings = Vec::with_capacity(count.min(4096));
+        for _ in 0..count {
+            strings.push(read_str(source)?);
+        }
+        let mut index = BTreeMap::new();
+        for _ in 0..read_len(source)? {
+            let id = read_str(source)?;
+            let position = u64::try_from(read_varint(source)?)
+                .map_err(|_| Error::parse("Binary", "invalid index offset"))?;
+            let days = read_signed(source)?;
+            index.entry(id).or_insert((position, days));
+        }
+
+        Ok(Self {
+            reader,
+            flags,
+            account_id,
+            balances,
+            metadata,
+            strings,
+            index,
+        })
+    }
+
+    /// The batch's account ID.
+    pub fn account_id(&self) -> Option<&str> {
+        self.account_id.as_deref()
+    }
+
+    /// The batch's declared balances.
+    pub fn balances(&self) -> &[Balance] {
+        &self.balances
+    }
+
+    /// The batch's metadata.
+    pub fn metadata(&self) -> &BatchMetadata {
+        &self.metadata
+    }
+
+    /// The number of distinct transaction IDs.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Whether the batch has no transactions.
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The transaction IDs, in ascending order.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.index.keys().map(String::as_str)
+    }
+
+    /// Reads the transaction with the given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The transaction ID
+    ///
+    /// # Returns
+    ///
+    /// Returns the transaction, `None` if the batch has none with this ID, or
+    /// an [`Error`] if it can't be read.
+    pub fn get(&mut self, id: &str) -> Result<Option<Transaction>> {
+        match self.index.get(id) {
+            Some(&(position, days)) => self.read_at(position, days).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the transactions whose IDs fall within `range`, in ID order.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The IDs to read, such as `"TX-0100".."TX-0200"`
+    ///
+    /// # Returns
+    ///
+    /// Returns the transactions, or an [`Error`] if one can't be read.
+    pub fn range<'a, B: RangeBounds<&'a str>>(&mut self, range: B) -> Result<Vec<Transaction>> {
+        let bounds: (Bound<&str>, Bound<&str>) =
+            (range.start_bound().cloned(), range.end_bound().cloned());
+        let positions: Vec<_> = self
+            .index
+            .range::<str, _>(bounds)
+            .map(|(_, &entry)| entry)
+            .collect();
+        positions
+            .into_iter()
+            .map(|(position, days)| self.read_at(position, days))
+            .collect()
+    }
+
+    fn read_at(&mut self, position: u64, days: i128) -> Result<Transaction> {
+        self.reader.seek(SeekFrom::Start(position))?;
+        let source = &mut IoSource(&mut self.reader);
+        let reader = &mut Crc::new(source, self.flags);
+        let mut strings = StringTable::complete(self.flags, core::mem::take(&mut self.strings));
+        let mut days = days;
+        let tx = read_dated_transaction_v3(reader, &mut days, &mut strings);
+        self.strings = strings.strings;
+
+        let tx = tx?;
+        if self.flags & FLAG_CHECKSUMS != 0 {
+            let crc = reader.record();
+            if read_u32(reader)? != crc {
+                return Err(Error::parse(
+                    "Binary",
+                    format!("transaction {} corrupted: checksum mismatch", tx.id),
+                ));
+            }
+        }
+        Ok(tx)
+    }
+}
+
+/// Appends transactions to a binary file in place.
+///
+/// Only the header and the end of the file are read, so this takes the same
+/// time for a large archive as for an empty one. The transactions are added
+/// as a new chunk, with checksums if the file has them. Files written by
+/// [`write_binary`] can be appended to, unless they are compressed,
+/// encrypted, signed or indexed.
+///
+/// # Arguments
+///
+/// * `path` - The binary file to extend
+/// * `transactions` - The transactions to add after the existing ones
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if the file can't be
+/// appended to or writing fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use parser::formats::binary::append;
+/// use parser::{parse_path, Format};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let today = parse_path("today.csv", Format::Csv)?;
+/// append("archive.bin", &today.transactions)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn append<P: AsRef<Path>>(path: P, transactions: &[Transaction]) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let source = &mut IoSource(&mut file);
+    if read_u32(source)? != MAGIC_NUMBER {
         return Err(Error::parse("Binary", "invalid magic number"));
     }
+    let version = read_u8(source)?;
+    let flags = if version == VERSION {
+        read_u8(source)?
+    } else {
+        0
+    };
+    let excluded = FLAG_ZSTD | FLAG_ENCRYPTED | FLAG_SIGNED | FLAG_INDEX;
+    if flags & FLAG_CHUNKED == 0 || flags & excluded != 0 {
+        return Err(Error::parse(
+            "Binary",
+            "only plain, unsigned, unindexed files in chunks can be appended to",
+        ));
+    }
+
+    // The file ends with the 0 chunk count and, with checksums, the CRC32
+    // of everything before it
+    let checksums = flags & FLAG_CHECKSUMS != 0;
+    let end = file.seek(SeekFrom::End(if checksums { -5 } else { -1 }))?;
+    let source = &mut IoSource(&mut file);
+    if read_u8(source)? != 0 {
+        return Err(Error::parse("Binary", "missing end of transactions"));
+    }
+    let crc = if checksums { read_u32(source)? } else { 0 };
+    if transactions.is_empty() {
+        return Ok(());
+    }
+
+    file.seek(SeekFrom::Start(end))?;
+    let mut sink = IoSink(BufWriter::new(&mut file));
+    let writer = &mut Crc::resume(&mut sink, flags, crc, end);
+    write_chunk(writer, transactions, flags)?;
+    end_chunks(writer, flags)?;
+    sink.0.flush()?;
+    Ok(())
+}
+
+/// Transactions per chunk of a [`BinaryWriter`].
+#[cfg(feature = "std")]
+const CHUNK_LEN: usize = 4096;
+
+/// Writes a binary batch one transaction at a time.
+///
+/// Unlike [`write_binary`], this doesn't need the whole batch in memory:
+/// transactions are encoded as they come and written in chunks, so only the
+/// current chunk is held. Call [`BinaryWriter::finish`] once all are
+/// written; without it the output is incomplete.
+///
+/// # Examples
+///
+/// ```
+/// use parser::formats::binary::{decode_binary, BinaryWriter};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut writer = BinaryWriter::create(Vec::new(), Some("ACC123"), &[])?;
+/// // for tx in source { writer.write_tx(&tx)?; }
+/// let bytes = writer.finish()?;
+/// assert_eq!(decode_binary(&bytes)?.account_id.as_deref(), Some("ACC123"));
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub struct BinaryWriter<W: Write> {
+    output: Output<W>,
+    flags: u8,
+    /// CRC32 of the batch so far
+    crc: u32,
+    /// Bytes of the batch so far, header included
+    position: u64,
+    /// The current chunk's encoded transactions
+    chunk: Vec<u8>,
+    chunk_len: usize,
+    strings: StringTable,
+    previous_days: i32,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> BinaryWriter<W> {
+    /// Starts a batch, writing its header.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A writer to output the binary data to
+    /// * `account_id` - The batch's account ID
+    /// * `balances` - The batch's declared balances
+    ///
+    /// # Returns
+    ///
+    /// Returns the writer, or an [`Error`] if writing fails.
+    pub fn create(writer: W, account_id: Option<&str>, balances: &[Balance]) -> Result<Self> {
+        Self::create_with(writer, account_id, balances, &WriteOptions::default())
+    }
+
+    /// Starts a batch with the given options, writing its header.
+    ///
+    /// [`WriteOptions::checksums`] and, with the `zstd` feature,
+    /// `WriteOptions::compression` apply as in [`write_binary_with`]; other
+    /// options don't apply to binary output.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A writer to output the binary data to
+    /// * `account_id` - The batch's account ID
+    /// * `balances` - The batch's declared balances
+    /// * `options` - How to write the batch
+    ///
+    /// # Returns
+    ///
+    /// Returns the writer, or an [`Error`] if writing fails or an index,
+    /// encryption or a signature is requested, which need the whole batch.
+    pub fn create_with(
+        writer: W,
+        account_id: Option<&str>,
+        balances: &[Balance],
+        options: &WriteOptions,
+    ) -> Result<Self> {
+        let header = TransactionBatch {
+            account_id: account_id.map(str::to_string),
+            transactions: Vec::new(),
+            balances: balances.to_vec(),
+            metadata: BatchMetadata::default(),
+        };
+        Self::create_from(writer, &header, options)
+    }
+
+    /// Starts a batch with the account, balances and metadata of `batch`
+    /// and the given options, writing its header. The transactions of
+    /// `batch` are not written.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A writer to output the binary data to
+    /// * `batch` - The batch whose header to write
+    /// * `options` - How to write the batch
+    ///
+    /// # Returns
+    ///
+    /// Returns the writer, or an [`Error`] in the same cases as
+    /// [`BinaryWriter::create_with`].
+    pub fn create_from(
+        mut writer: W,
+        batch: &TransactionBatch,
+        options: &WriteOptions,
+    ) -> Result<Self> {
+        if options.index {
+            return Err(Error::UnsupportedFormat(
+                "streamed binary files can't be indexed".to_string(),
+            ));
+        }
+        #[cfg(feature = "encryption")]
+        if options.encryption_key.is_some() {
+            return Err(Error::UnsupportedFormat(
+                "streamed binary files can't be encrypted".to_string(),
+            ));
+        }
+        #[cfg(feature = "signing")]
+        if options.signing_key.is_some() {
+            return Err(Error::UnsupportedFormat(
+                "streamed binary files can't be signed".to_string(),
+            ));
+        }
+        let mut flags = DEFAULT_FLAGS;
+        if options.checksums {
+            flags |= FLAG_CHECKSUMS;
+        }
+        #[cfg(feature = "zstd")]
+        if options.compression.is_some() {
+            flags |= FLAG_ZSTD;
+        }
+
+        writer.write_all(&header(flags))?;
+        let mut output = match options {
+            #[cfg(feature = "zstd")]
+            WriteOptions {
+                compression: Some(level),
+                ..
+            } => Output::Zstd(zstd::Encoder::new(writer, *level)?),
+            _ => Output::Plain(writer),
+        };
+        let sink = &mut IoSink(&mut output);
+        let writer = &mut Crc::new(sink, flags);
+        let account_id = batch.account_id.as_deref();
+        write_batch_fields_v3(writer, account_id, &batch.balances, &batch.metadata)?;
+        let (crc, position) = (writer.batch(), writer.position);
+
+        Ok(Self {
+            output,
+            flags,
+            crc,
+            position,
+            chunk: Vec::new(),
+            chunk_len: 0,
+            strings: StringTable::new(flags),
+            previous_days: 0,
+        })
+    }
+
+    /// Writes one transaction, after the previous ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - The transaction to write
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+    pub fn write_tx(&mut self, tx: &Transaction) -> Result<()> {
+        let chunk = &mut Crc::new(&mut self.chunk, self.flags);
+        write_record(
+            chunk,
+            tx,
+            self.flags,
+            &mut self.strings,
+            &mut self.previous_days,
+        )?;
+        self.chunk_len += 1;
+        if self.chunk_len == CHUNK_LEN {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining transactions and ends the batch.
+    ///
+    /// # Returns
+    ///
+    /// Returns the underlying writer, or an [`Error`] if writing fails.
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_chunk()?;
+        let sink = &mut IoSink(&mut self.output);
+        let writer = &mut Crc::resume(sink, self.flags, self.crc, self.position);
+        end_chunks(writer, self.flags)?;
+        match self.output {
+            Output::Plain(writer) => Ok(writer),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(encoder) => Ok(encoder.finish()?),
+        }
+    }
+
+    fn flush_chunk(&mut self) -> Result<()> {
+        if self.chunk_len == 0 {
+            return Ok(());
+        }
+        let sink = &mut IoSink(&mut self.output);
+        let writer = &mut Crc::resume(sink, self.flags, self.crc, self.position);
+        write_varint(writer, self.chunk_len as u128)?;
+        writer.put(&self.chunk)?;
+        (self.crc, self.position) = (writer.batch(), writer.position);
+
+        self.chunk.clear();
+        self.chunk_len = 0;
+        self.strings = StringTable::new(self.flags);
+        self.previous_days = 0;
+        Ok(())
+    }
+}
+
+/// Where a [`BinaryWriter`] writes what follows the header.
+#[cfg(feature = "std")]
+enum Output<W: Write> {
+    Plain(W),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(writer) => writer.flush(),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// A source of bytes the decoder reads from.
+pub(crate) trait Source {
+    /// Fills `buf` completely or fails.
+    fn take(&mut self, buf: &mut [u8]) -> Result<()>;
+
+    /// The remaining bytes as a [`Read`], to decompress them.
+    #[cfg(feature = "zstd")]
+    fn as_read(&mut self) -> &mut dyn Read;
+
+    /// Reads all remaining bytes.
+    #[cfg(feature = "encryption")]
+    fn take_rest(&mut self) -> Result<Vec<u8>>;
+
+    /// The caps the decoded batch must stay within.
+    fn limits(&self) -> Limits {
+        Limits::default()
+    }
+}
+
+impl Source for &[u8] {
+    fn take(&mut self, buf: &mut [u8]) -> Result<()> {
+        if self.len() < buf.len() {
+            return Err(Error::parse("Binary", "unexpected end of data"));
+        }
+        let (head, rest) = self.split_at(buf.len());
+        buf.copy_from_slice(head);
+        *self = rest;
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    fn as_read(&mut self) -> &mut dyn Read {
+        self
+    }
+
+    #[cfg(feature = "encryption")]
+    fn take_rest(&mut self) -> Result<Vec<u8>> {
+        Ok(core::mem::take(self).to_vec())
+    }
+}
+
+/// Adapts a [`Read`] to [`Source`].
+#[cfg(feature = "std")]
+pub(crate) struct IoSource<R>(pub(crate) R);
+
+#[cfg(feature = "std")]
+impl<R: Read> Source for IoSource<R> {
+    fn take(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.0.read_exact(buf)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    fn as_read(&mut self) -> &mut dyn Read {
+        &mut self.0
+    }
+
+    #[cfg(feature = "encryption")]
+    fn take_rest(&mut self) -> Result<Vec<u8>> {
+        let mut rest = Vec::new();
+        self.0.read_to_end(&mut rest)?;
+        Ok(rest)
+    }
+}
+
+/// A [`Source`] whose batch must stay within [`Limits`].
+#[cfg(feature = "std")]
+struct Limited<'a, S>(&'a mut S, Limits);
+
+#[cfg(feature = "std")]
+impl<S: Source> Source for Limited<'_, S> {
+    fn take(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.0.take(buf)
+    }
+
+    #[cfg(feature = "zstd")]
+    fn as_read(&mut self) -> &mut dyn Read {
+        self.0.as_read()
+    }
+
+    #[cfg(feature = "encryption")]
+    fn take_rest(&mut self) -> Result<Vec<u8>> {
+        self.0.take_rest()
+    }
+
+    fn limits(&self) -> Limits {
+        self.1
+    }
+}
+
+/// A sink of bytes the encoder writes to.
+pub(crate) trait Sink {
+    /// Writes all of `bytes`.
+    fn put(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+impl Sink for Vec<u8> {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Adapts a [`Write`] to [`Sink`].
+#[cfg(feature = "std")]
+pub(crate) struct IoSink<W>(pub(crate) W);
+
+#[cfg(feature = "std")]
+impl<W: Write> Sink for IoSink<W> {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        self.0.write_all(bytes)?;
+        Ok(())
+    }
+}
+
+fn read_batch<R: Source>(reader: &mut R, key: Option<&Key>) -> Result<TransactionBatch> {
+    read_signed_batch(reader, key).map(|(batch, _)| batch)
+}
+
+/// A batch with its embedded signature, if any.
+type Signed = (TransactionBatch, Option<[u8; SIGNATURE_LEN]>);
+
+fn read_signed_batch<R: Source>(reader: &mut R, key: Option<&Key>) -> Result<Signed> {
+    let magic = read_u32(reader)?;
+    if magic != MAGIC_NUMBER {
+        return Err(Error::parse("Binary", "invalid magic number"));
+    }
+
+    // Each version has its own decoder, so that files of every version stay
+    // readable from the same entry point
+    match read_u8(reader)? {
+        version @ (1 | 2) => Ok((read_batch_v2(reader, version)?, None)),
+        3 => read_batch_v3(reader, key),
+        version => Err(Error::parse(
+            "Binary",
+            format!("unsupported version: {}", version),
+        )),
+    }
+}
+
+/// Reads what follows the version of a version 1 or 2 batch. Version 1 has
+/// neither balances nor tax fields.
+fn read_batch_v2<R: Source>(reader: &mut R, version: u8) -> Result<TransactionBatch> {
+    let account_id = read_optional_string(reader)?;
+
+    let mut balances = Vec::new();
+    if version >= 2 {
+        for (tag, value) in read_extensions(reader)? {
+            if tag == EXT_BALANCE {
+                let value = &mut value.as_slice();
+                balances.push(Balance {
+                    currency: read_string(value)?,
+                    opening: decode_decimal(read_string(value)?.into_bytes(), "opening balance")?,
+                    closing: decode_decimal(read_string(value)?.into_bytes(), "closing balance")?,
+                });
+            }
+        }
+    }
+
+    let tx_count = read_u32(reader)? as usize;
+    reader.limits().check_transactions(tx_count)?;
+    // The count comes from the file, so don't trust it for the allocation
+    let mut transactions = Vec::with_capacity(tx_count.min(4096));
+
+    for _ in 0..tx_count {
+        let transaction = read_transaction(reader, version)?;
+        transactions.push(transaction);
+    }
+
+    Ok(TransactionBatch {
+        account_id,
+        transactions,
+        balances,
+        metadata: Default::default(),
+    })
+}
+
+/// Writes a batch in version 1 or 2 of the format, dropping what the
+/// version can't store.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+fn write_batch_v2<W: Sink>(writer: &mut W, batch: &TransactionBatch, version: u8) -> Result<()> {
+    write_u32(writer, MAGIC_NUMBER)?;
+    write_u8(writer, version)?;
+
+    write_optional_string(writer, batch.account_id.as_deref())?;
+
+    if version >= 2 {
+        let mut extensions = Vec::new();
+        for balance in &batch.balances {
+            let mut value = Vec::new();
+            write_string(&mut value, &balance.currency)?;
+            write_string(&mut value, &balance.opening.to_string())?;
+            write_string(&mut value, &balance.closing.to_string())?;
+            write_extension(&mut extensions, EXT_BALANCE, &value)?;
+        }
+        write_u32(writer, extensions.len() as u32)?;
+        writer.put(&extensions)?;
+    }
+
+    write_u32(writer, batch.transactions.len() as u32)?;
+
+    for tx in &batch.transactions {
+        write_transaction(writer, tx, version)?;
+    }
+
+    Ok(())
+}
+
+// Version 3 layout. Varints are unsigned LEB128, and signed values are
+// zigzag-encoded first. Strings are a varint byte length followed by UTF-8;
+// optional strings store 0 for `None` and the length plus one otherwise.
+// Decimals are their scale (u8) and the varint of their mantissa, i.e. the
+// amount in minor units.
+//
+//   magic (u32) | version (u8) | flags (u8, see FLAG_*)
+//   with FLAG_ZSTD, the rest is a zstd frame of:
+//   account ID (optional string)
+//   batch extensions: varint length, then tag (u8), varint length, value
+//   transaction count (varint), then for each transaction (with
+//   FLAG_CHUNKED, chunks of a varint count and as many transactions, ended
+//   by a count of 0; each chunk has its own date deltas and string table):
+//     posted date: signed varint of the days since the previous
+//       transaction's posted date (the first one's since 1970-01-01)
+//     ID (string)
+//     kind (u8): bit 0 set for money coming in, bit 1 when an execution
+//       time follows, bits 2-7 the kind code (see KIND_CODES; 0 for plain
+//       debits and credits)
+//     execution time: signed varint of the seconds since the posted date
+//     amount (decimal), currency and description (strings)
+//     account, counterparty and category (optional strings)
+//     (with FLAG_DICTIONARY, currency, account, counterparty and category
+//     are string table references instead)
+//     extensions, as for the batch
+//     with FLAG_CHECKSUMS, the CRC32 of the transaction from its date (u32)
+//   with FLAG_CHECKSUMS, the CRC32 of the uncompressed batch up to the end
+//     of its last transaction (u32)
+//   with FLAG_INDEX (never compressed or chunked):
+//     the string table: varint count, then strings in the order they appeared
+//     varint count of transactions, then for each: ID (string), offset of
+//       its posted date from the start of the file (varint), and the posted
+//       date its date is relative to (signed varint days since 1970-01-01)
+//     offset of the string table (u64)
+
+/// Bit of a version 3 transaction's kind byte set for credits and other
+/// transactions that bring money in.
+const KIND_CREDIT: u8 = 1;
+/// Bit of a version 3 transaction's kind byte set when an execution time
+/// follows.
+const KIND_EXECUTED: u8 = 2;
+/// Shift of the kind code in a version 3 transaction's kind byte.
+const KIND_CODE_SHIFT: u8 = 2;
+/// Kinds other than debits and credits with their codes, the kind bytes of
+/// versions 1 and 2 and the kind codes of version 3. Unknown codes, as
+/// written by newer versions, are read as debits or credits by direction.
+const KIND_CODES: [(TransactionKind, u8); 5] = [
+    (TransactionKind::Transfer, 2),
+    (TransactionKind::Fee, 3),
+    (TransactionKind::Interest, 4),
+    (TransactionKind::Refund, 5),
+    (TransactionKind::Reversal, 6),
+];
+
+/// Returns the code of a kind, 0 for debits and 1 for credits.
+fn kind_code(kind: TransactionKind) -> u8 {
+    match kind {
+        TransactionKind::Debit => 0,
+        TransactionKind::Credit => 1,
+        _ => KIND_CODES
+            .iter()
+            .find(|(known, _)| *known == kind)
+            .map_or(0, |(_, code)| *code),
+    }
+}
+
+/// Returns the kind with the given code, if it's one of [`KIND_CODES`].
+fn code_kind(code: u8) -> Option<TransactionKind> {
+    KIND_CODES
+        .iter()
+        .find(|(_, known)| *known == code)
+        .map(|(kind, _)| *kind)
+}
+
+fn write_batch<W: Sink>(writer: &mut W, batch: &TransactionBatch, flags: u8) -> Result<()> {
+    writer.put(&header(flags))?;
+    write_payload(writer, batch, flags)
+}
+
+fn header(flags: u8) -> [u8; 6] {
+    let mut header = [0u8; 6];
+    header[..4].copy_from_slice(&MAGIC_NUMBER.to_le_bytes());
+    header[4] = VERSION;
+    header[5] = flags;
+    header
+}
+
+/// Passes bytes through to or from `inner`, computing the CRC32 of the whole
+/// batch and of the current transaction, and counting them.
+struct Crc<'a, T> {
+    inner: &'a mut T,
+    batch: crc32fast::Hasher,
+    record: crc32fast::Hasher,
+    /// Whether the batch has checksums; without them nothing is hashed
+    checksums: bool,
+    /// Offset from the start of the batch, header included
+    position: u64,
+}
+
+impl<'a, T> Crc<'a, T> {
+    fn new(inner: &'a mut T, flags: u8) -> Self {
+        let header = header(flags);
+        let mut batch = crc32fast::Hasher::new();
+        batch.update(&header);
+        Self {
+            inner,
+            batch,
+            record: crc32fast::Hasher::new(),
+            checksums: flags & FLAG_CHECKSUMS != 0,
+            position: header.len() as u64,
+        }
+    }
+
+    /// Continues a batch whose first `position` bytes have the CRC32 `crc`.
+    #[cfg(feature = "std")]
+    fn resume(inner: &'a mut T, flags: u8, crc: u32, position: u64) -> Self {
+        Self {
+            inner,
+            batch: crc32fast::Hasher::new_with_initial(crc),
+            record: crc32fast::Hasher::new(),
+            checksums: flags & FLAG_CHECKSUMS != 0,
+            position,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        // Hashing every field costs more than decoding it, so it's skipped
+        // when there are no checksums to compare with
+        if self.checksums {
+            self.batch.update(bytes);
+            self.record.update(bytes);
+        }
+        self.position += bytes.len() as u64;
+    }
+
+    /// Starts a new transaction.
+    fn start_record(&mut self) {
+        self.record.reset();
+    }
+
+    /// Returns the CRC32 of the bytes since the transaction started.
+    fn record(&self) -> u32 {
+        self.record.clone().finalize()
+    }
+
+    fn batch(&self) -> u32 {
+        self.batch.clone().finalize()
+    }
+}
+
+impl<T: Source> Source for Crc<'_, T> {
+    fn take(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.inner.take(buf)?;
+        self.update(buf);
+        Ok(())
+    }
+
+    // Payloads are decrypted and decompressed before they are checked, so
+    // these are unused
+    #[cfg(feature = "zstd")]
+    fn as_read(&mut self) -> &mut dyn Read {
+        self.inner.as_read()
+    }
+
+    #[cfg(feature = "encryption")]
+    fn take_rest(&mut self) -> Result<Vec<u8>> {
+        self.inner.take_rest()
+    }
+
+    fn limits(&self) -> Limits {
+        self.inner.limits()
+    }
+}
+
+impl<T: Sink> Sink for Crc<'_, T> {
+    fn put(&mut self, bytes: &[u8]) -> Result<()> {
+        self.inner.put(bytes)?;
+        self.update(bytes);
+        Ok(())
+    }
+}
+
+/// Writes what follows the header of a version 3 batch.
+fn write_payload<W: Sink>(writer: &mut W, batch: &TransactionBatch, flags: u8) -> Result<()> {
+    let writer = &mut Crc::new(writer, flags);
+    let account_id = batch.account_id.as_deref();
+    write_batch_fields_v3(writer, account_id, &batch.balances, &batch.metadata)?;
+
+    if flags & FLAG_CHUNKED != 0 {
+        write_chunk(writer, &batch.transactions, flags)?;
+        return end_chunks(writer, flags);
+    }
+
+    write_varint(writer, batch.transactions.len() as u128)?;
+    let mut strings = StringTable::new(flags);
+    let mut index = Vec::new();
+    write_transactions(writer, &batch.transactions, flags, &mut strings, &mut index)?;
+
+    if flags & FLAG_CHECKSUMS != 0 {
+        let crc = writer.batch();
+        write_u32(writer, crc)?;
+    }
+
+    if flags & FLAG_INDEX != 0 {
+        let offset = writer.position;
+        let table = strings.into_strings();
+        write_varint(writer, table.len() as u128)?;
+        for s in &table {
+            write_str(writer, s)?;
+        }
+        write_varint(writer, index.len() as u128)?;
+        for (id, position, days) in index {
+            write_str(writer, id)?;
+            write_varint(writer, u128::from(position))?;
+            write_signed(writer, i128::from(days))?;
+        }
+        writer.put(&offset.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes the account ID, balances and metadata of a version 3 batch.
+fn write_batch_fields_v3<W: Sink>(
+    writer: &mut W,
+    account_id: Option<&str>,
+    balances: &[Balance],
+    metadata: &BatchMetadata,
+) -> Result<()> {
+    write_optional_str(writer, account_id)?;
+
+    let mut extensions = Vec::new();
+    for balance in balances {
+        let mut value = Vec::new();
+        write_str(&mut value, &balance.currency)?;
+        write_decimal(&mut value, balance.opening)?;
+        write_decimal(&mut value, balance.closing)?;
+        write_extension_v3(&mut extensions, EXT_BALANCE, &value)?;
+    }
+    if let Some(generated_at) = metadata.generated_at {
+        let mut value = Vec::new();
+        write_signed(&mut value, i128::from(generated_at.and_utc().timestamp()))?;
+        write_extension_v3(&mut extensions, EXT_GENERATED_AT, &value)?;
+    }
+    if let Some(source_system) = &metadata.source_system {
+        let mut value = Vec::new();
+        write_str(&mut value, source_system)?;
+        write_extension_v3(&mut extensions, EXT_SOURCE_SYSTEM, &value)?;
+    }
+    if let Some(period) = metadata.period {
+        let mut value = Vec::new();
+        for day in [period.start, period.end] {
+            write_signed(&mut value, i128::from(day.num_days_from_ce() - EPOCH_DAYS))?;
+        }
+        write_extension_v3(&mut extensions, EXT_PERIOD, &value)?;
+    }
+    if let Some(schema_version) = &metadata.schema_version {
+        let mut value = Vec::new();
+        write_str(&mut value, schema_version)?;
+        write_extension_v3(&mut extensions, EXT_SCHEMA_VERSION, &value)?;
+    }
+    write_varint(writer, extensions.len() as u128)?;
+    writer.put(&extensions)
+}
+
+/// Writes a chunk of a [`FLAG_CHUNKED`] batch; nothing if `transactions` is
+/// empty, which would end the batch.
+fn write_chunk<W: Sink>(
+    writer: &mut Crc<'_, W>,
+    transactions: &[Transaction],
+    flags: u8,
+) -> Result<()> {
+    if transactions.is_empty() {
+        return Ok(());
+    }
+    write_varint(writer, transactions.len() as u128)?;
+    let mut strings = StringTable::new(flags);
+    write_transactions(writer, transactions, flags, &mut strings, &mut Vec::new())
+}
+
+/// Ends the chunks of a [`FLAG_CHUNKED`] batch.
+fn end_chunks<W: Sink>(writer: &mut Crc<'_, W>, flags: u8) -> Result<()> {
+    let crc = writer.batch();
+    write_u8(writer, 0)?;
+    if flags & FLAG_CHECKSUMS != 0 {
+        write_u32(writer, crc)?;
+    }
+    Ok(())
+}
+
+/// Writes transactions with dates relative to each other, recording their
+/// offsets in `index` when the batch has [`FLAG_INDEX`].
+fn write_transactions<'a, W: Sink>(
+    writer: &mut Crc<'_, W>,
+    transactions: &'a [Transaction],
+    flags: u8,
+    strings: &mut StringTable,
+    index: &mut Vec<(&'a str, u64, i32)>,
+) -> Result<()> {
+    let mut previous_days = 0;
+    for tx in transactions {
+        if flags & FLAG_INDEX != 0 {
+            index.push((tx.id.as_str(), writer.position, previous_days));
+        }
+        write_record(writer, tx, flags, strings, &mut previous_days)?;
+    }
+    Ok(())
+}
+
+/// Writes a transaction with its date relative to `previous_days`, which
+/// becomes its own, and its checksum if the batch has them.
+fn write_record<W: Sink>(
+    writer: &mut Crc<'_, W>,
+    tx: &Transaction,
+    flags: u8,
+    strings: &mut StringTable,
+    previous_days: &mut i32,
+) -> Result<()> {
+    writer.start_record();
+    let days = tx.posted_at.num_days_from_ce() - EPOCH_DAYS;
+    write_signed(writer, i128::from(days) - i128::from(*previous_days))?;
+    *previous_days = days;
+    write_transaction_v3(writer, tx, strings)?;
+    if flags & FLAG_CHECKSUMS != 0 {
+        let crc = writer.record();
+        write_u32(writer, crc)?;
+    }
+    Ok(())
+}
+
+fn read_batch_v3<R: Source>(reader: &mut R, key: Option<&Key>) -> Result<Signed> {
+    let flags = read_u8(reader)?;
+    let known = FLAG_ZSTD | FLAG_CHECKSUMS | FLAG_DICTIONARY | FLAG_INDEX | FLAG_CHUNKED;
+    if flags & !(known | FLAG_ENCRYPTED | FLAG_SIGNED) != 0 {
+        return Err(Error::parse(
+            "Binary",
+            format!("unsupported flags: {:#04x}", flags),
+        ));
+    }
+    if flags & FLAG_ENCRYPTED != 0 {
+        #[cfg(feature = "encryption")]
+        {
+            let key = key.ok_or_else(|| {
+                Error::parse("Binary", "the data is encrypted and no key was given")
+            })?;
+            let payload = key.open(&header(flags), reader.take_rest()?)?;
+            let limits = reader.limits();
+            return read_body(&mut Limited(&mut payload.as_slice(), limits), flags);
+        }
+        #[cfg(not(feature = "encryption"))]
+        return Err(Error::parse(
+            "Binary",
+            "encrypted data requires the `encryption` feature",
+        ));
+    }
+    #[cfg(not(feature = "encryption"))]
+    let _ = key;
+    read_body(reader, flags)
+}
+
+/// Reads what follows the header of a version 3 batch: the signature, if
+/// any, and the payload, decompressing it if `flags` say so. Signatures are
+/// only verified by [`parse_binary_with`].
+fn read_body<R: Source>(reader: &mut R, flags: u8) -> Result<Signed> {
+    let mut signature = None;
+    if flags & FLAG_SIGNED != 0 {
+        let mut bytes = [0u8; SIGNATURE_LEN];
+        reader.take(&mut bytes)?;
+        signature = Some(bytes);
+    }
+    if flags & FLAG_ZSTD != 0 {
+        #[cfg(feature = "zstd")]
+        {
+            let limits = reader.limits();
+            let decoder = &mut IoSource(zstd::Decoder::new(reader.as_read())?);
+            let batch = read_payload(&mut Limited(decoder, limits), flags)?;
+            return Ok((batch, signature));
+        }
+        #[cfg(not(feature = "zstd"))]
+        return Err(Error::parse(
+            "Binary",
+            "compressed data requires the `zstd` feature",
+        ));
+    }
+    Ok((read_payload(reader, flags)?, signature))
+}
+
+/// Reads what follows the header of a version 3 batch.
+fn read_payload<R: Source>(reader: &mut R, flags: u8) -> Result<TransactionBatch> {
+    let reader = &mut Crc::new(reader, flags);
+    let (account_id, balances, metadata) = read_batch_fields_v3(reader)?;
+
+    let mut transactions = Vec::new();
+    if flags & FLAG_CHUNKED != 0 {
+        loop {
+            let crc = reader.batch();
+            let count = read_len(reader)?;
+            if count == 0 {
+                check_batch_crc(reader, flags, crc)?;
+                break;
+            }
+            reader
+                .limits()
+                .check_transactions(transactions.len().saturating_add(count))?;
+            read_transactions(reader, count, flags, &mut transactions)?;
+        }
+    } else {
+        let count = read_len(reader)?;
+        reader.limits().check_transactions(count)?;
+        read_transactions(reader, count, flags, &mut transactions)?;
+        let crc = reader.batch();
+        check_batch_crc(reader, flags, crc)?;
+    }
+
+    Ok(TransactionBatch {
+        account_id,
+        transactions,
+        balances,
+        metadata,
+    })
+}
+
+fn check_batch_crc<R: Source>(reader: &mut Crc<'_, R>, flags: u8, crc: u32) -> Result<()> {
+    if flags & FLAG_CHECKSUMS != 0 && read_u32(reader)? != crc {
+        return Err(Error::parse("Binary", "file checksum mismatch"));
+    }
+    Ok(())
+}
+
+/// Reads `count` transactions with dates relative to each other, appending
+/// them to `transactions`.
+fn read_transactions<R: Source>(
+    reader: &mut Crc<'_, R>,
+    count: usize,
+    flags: u8,
+    transactions: &mut Vec<Transaction>,
+) -> Result<()> {
+    // The count comes from the file, so don't trust it for the allocation
+    transactions.reserve(count.min(4096));
+    let mut strings = StringTable::new(flags);
+    let mut days = 0i128;
+    for _ in 0..count {
+        let number = transactions.len() + 1;
+        reader.start_record();
+        let tx = read_dated_transaction_v3(reader, &mut days, &mut strings);
+        if flags & FLAG_CHECKSUMS == 0 {
+            transactions.push(tx?);
+            continue;
+        }
+        // With checksums, any failure within a record is its corruption
+        let corrupted = |reason: &str| {
+            Error::parse("Binary", format!("record {} corrupted: {}", number, reason))
+        };
+        let tx = tx.map_err(|e| match e {
+            Error::Parse { message, .. } => corrupted(&message),
+            e => corrupted(&e.to_string()),
+        })?;
+        let crc = reader.record();
+        if read_u32(reader)? != crc {
+            return Err(corrupted("checksum mismatch"));
+        }
+        transactions.push(tx);
+    }
+    Ok(())
+}
+
+/// Reads the account ID, balances and metadata of a version 3 batch.
+fn read_batch_fields_v3<R: Source>(
+    reader: &mut R,
+) -> Result<(Option<String>, Vec<Balance>, BatchMetadata)> {
+    let account_id = read_optional_str(reader)?;
+
+    let mut balances = Vec::new();
+    let mut metadata = BatchMetadata::default();
+    // Unknown tags come from newer writers and are skipped.
+    for (tag, value) in read_extensions_v3(reader)? {
+        let value = &mut value.as_slice();
+        match tag {
+            EXT_BALANCE => balances.push(Balance {
+                currency: read_str(value)?,
+                opening: read_decimal(value, "opening balance")?,
+                closing: read_decimal(value, "closing balance")?,
+            }),
+            EXT_GENERATED_AT => {
+                let generated_at = i64::try_from(read_signed(value)?)
+                    .ok()
+                    .and_then(|seconds| chrono::DateTime::from_timestamp(seconds, 0))
+                    .ok_or_else(|| Error::parse("Binary", "invalid generation time"))?;
+                metadata.generated_at = Some(generated_at.naive_utc());
+            }
+            EXT_SOURCE_SYSTEM => metadata.source_system = Some(read_str(value)?),
+            EXT_PERIOD => {
+                let mut day = || {
+                    i32::try_from(read_signed(value)?)
+                        .ok()
+                        .and_then(|days| days.checked_add(EPOCH_DAYS))
+                        .and_then(NaiveDate::from_num_days_from_ce_opt)
+                        .ok_or_else(|| Error::parse("Binary", "invalid period"))
+                };
+                let (start, end) = (day()?, day()?);
+                if start > end {
+                    return Err(Error::parse("Binary", "invalid period"));
+                }
+                metadata.period = Some(Period { start, end });
+            }
+            EXT_SCHEMA_VERSION => metadata.schema_version = Some(read_str(value)?),
+            _ => {}
+        }
+    }
+    Ok((account_id, balances, metadata))
+}
+
+/// Reads a version 3 transaction, `days` being the previous one's posted date
+/// in days since the epoch.
+fn read_dated_transaction_v3<R: Source>(
+    reader: &mut R,
+    days: &mut i128,
+    strings: &mut StringTable,
+) -> Result<Transaction> {
+    *days = days
+        .checked_add(read_signed(reader)?)
+        .ok_or_else(|| Error::parse("Binary", "invalid posted date"))?;
+    let posted_at = i32::try_from(*days)
+        .ok()
+        .and_then(|days| days.checked_add(EPOCH_DAYS))
+        .and_then(NaiveDate::from_num_days_from_ce_opt)
+        .ok_or_else(|| Error::parse("Binary", "invalid posted date"))?;
+    read_transaction_v3(reader, posted_at, strings)
+}
+
+/// Writes the fields of a version 3 transaction that follow its posted date.
+fn write_transaction_v3<W: Sink>(
+    writer: &mut W,
+    tx: &Transaction,
+    strings: &mut StringTable,
+) -> Result<()> {
+    write_str(writer, &tx.id)?;
+
+    let mut kind = match tx.kind {
+        TransactionKind::Debit | TransactionKind::Credit => 0,
+        other => kind_code(other) << KIND_CODE_SHIFT,
+    };
+    if tx.is_credit() {
+        kind |= KIND_CREDIT;
+    }
+    if tx.executed_at.is_some() {
+        kind |= KIND_EXECUTED;
+    }
+    write_u8(writer, kind)?;
+    if let Some(executed) = tx.executed_at {
+        let midnight = tx.posted_at.and_time(chrono::NaiveTime::MIN);
+        write_signed(writer, i128::from((executed - midnight).num_seconds()))?;
+    }
+
+    write_decimal(writer, tx.amount.amount)?;
+    strings.write(writer, &tx.amount.currency)?;
+    write_str(writer, &tx.description)?;
+
+    strings.write_optional(writer, tx.account.as_deref())?;
+    strings.write_optional(writer, tx.counterparty.as_deref())?;
+    strings.write_optional(writer, tx.category.as_deref())?;
+
+    let mut extensions = Vec::new();
+    if let Some(tax) = tx.tax_amount {
+        let mut value = Vec::new();
+        write_decimal(&mut value, tax)?;
+        write_extension_v3(&mut extensions, EXT_TAX_AMOUNT, &value)?;
+    }
+    if let Some(rate) = tx.tax_rate {
+        let mut value = Vec::new();
+        write_decimal(&mut value, rate)?;
+        write_extension_v3(&mut extensions, EXT_TAX_RATE, &value)?;
+    }
+    for (key, entry) in &tx.extra {
+        let mut value = Vec::new();
+        write_str(&mut value, key)?;
+        write_str(&mut value, entry)?;
+        write_extension_v3(&mut extensions, EXT_EXTRA, &value)?;
+    }
+    let status = match tx.status {
+        TransactionStatus::Booked => None,
+        TransactionStatus::Pending => Some(1),
+        TransactionStatus::Cancelled => Some(2),
+    };
+    if let Some(status) = status {
+        write_extension_v3(&mut extensions, EXT_STATUS, &[status])?;
+    }
+    write_varint(writer, extensions.len() as u128)?;
+    writer.put(&extensions)
+}
+
+/// Reads the fields of a version 3 transaction that follow its posted date.
+fn read_transaction_v3<R: Source>(
+    reader: &mut R,
+    posted_at: NaiveDate,
+    strings: &mut StringTable,
+) -> Result<Transaction> {
+    let id = read_str(reader)?;
+
+    let kind_byte = read_u8(reader)?;
+    let executed_at = if kind_byte & KIND_EXECUTED != 0 {
+        let seconds = i64::try_from(read_signed(reader)?)
+            .map_err(|_| Error::parse("Binary", "invalid executed timestamp"))?;
+        let midnight = posted_at.and_time(chrono::NaiveTime::MIN);
+        Some(
+            chrono::TimeDelta::try_seconds(seconds)
+                .and_then(|offset| midnight.checked_add_signed(offset))
+                .ok_or_else(|| Error::parse("Binary", "invalid executed timestamp"))?,
+        )
+    } else {
+        None
+    };
+
+    let mut amount = Money {
+        amount: read_decimal(reader, "amount")?,
+        currency: strings.read(reader)?,
+    };
+    let credit = kind_byte & KIND_CREDIT != 0;
+    let kind = match kind_byte >> KIND_CODE_SHIFT {
+        0 if credit => TransactionKind::Credit,
+        0 => TransactionKind::Debit,
+        code => code_kind(code).unwrap_or_else(|| {
+            let magnitude = amount.amount.abs();
+            let (kind, unsigned) =
+                TransactionKind::from_sign(if credit { magnitude } else { -magnitude });
+            amount.amount = unsigned;
+            kind
+        }),
+    };
+    let description = read_str(reader)?;
+    let account = strings.read_optional(reader)?;
+    let counterparty = strings.read_optional(reader)?;
+    let category = strings.read_optional(reader)?;
+
+    let mut tax_amount = None;
+    let mut tax_rate = None;
+    let mut extra = BTreeMap::new();
+    let mut status = TransactionStatus::Booked;
+    // Unknown tags come from newer writers and are skipped.
+    for (tag, value) in read_extensions_v3(reader)? {
+        match tag {
+            EXT_TAX_AMOUNT => tax_amount = Some(read_decimal(&mut value.as_slice(), "tax amount")?),
+            EXT_TAX_RATE => tax_rate = Some(read_decimal(&mut value.as_slice(), "tax rate")?),
+            EXT_EXTRA => {
+                let mut value = value.as_slice();
+                let key = read_str(&mut value)?;
+                extra.insert(key, read_str(&mut value)?);
+            }
+            EXT_STATUS => {
+                status = match value.as_slice() {
+                    [1] => TransactionStatus::Pending,
+                    [2] => TransactionStatus::Cancelled,
+                    _ => return Err(Error::parse("Binary", "invalid status")),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Transaction {
+        id,
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount,
+        description,
+        account,
+        counterparty,
+        category,
+        tax_amount,
+        tax_rate,
+        extra,
+    })
+}
+
+/// The strings of a batch written so far, with [`FLAG_DICTIONARY`].
+///
+/// A string's first occurrence is written in full, preceded by a varint 0,
+/// and adds it to the table; later ones are the varint of its index in the
+/// table plus one. Optional strings shift these by one, 0 meaning `None`.
+/// Without the flag, strings are written as is.
+struct StringTable {
+    enabled: bool,
+    indexes: BTreeMap<String, usize>,
+    strings: Vec<String>,
+    /// Whether `strings` is the whole batch's table, read from its index
+    complete: bool,
+}
+
+impl StringTable {
+    fn new(flags: u8) -> Self {
+        Self {
+            enabled: flags & FLAG_DICTIONARY != 0,
+            indexes: BTreeMap::new(),
+            strings: Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// The table of a whole batch, to read its transactions in any order.
+    #[cfg(feature = "std")]
+    fn complete(flags: u8, strings: Vec<String>) -> Self {
+        Self {
+            strings,
+            complete: true,
+            ..Self::new(flags)
+        }
+    }
+
+    /// Returns the written strings in the order of their indexes.
+    fn into_strings(self) -> Vec<String> {
+        let mut strings: Vec<_> = self.indexes.into_iter().collect();
+        strings.sort_unstable_by_key(|&(_, index)| index);
+        strings.into_iter().map(|(s, _)| s).collect()
+    }
+
+    fn write<W: Sink>(&mut self, writer: &mut W, s: &str) -> Result<()> {
+        if !self.enabled {
+            return write_str(writer, s);
+        }
+        self.write_reference(writer, s, 0)
+    }
+
+    fn write_optional<W: Sink>(&mut self, writer: &mut W, s: Option<&str>) -> Result<()> {
+        match s {
+            _ if !self.enabled => write_optional_str(writer, s),
+            Some(s) => self.write_reference(writer, s, 1),
+            None => write_u8(writer, 0),
+        }
+    }
+
+    fn write_reference<W: Sink>(&mut self, writer: &mut W, s: &str, base: usize) -> Result<()> {
+        if let Some(&index) = self.indexes.get(s) {
+            return write_varint(writer, (base + index + 1) as u128);
+        }
+        write_varint(writer, base as u128)?;
+        write_str(writer, s)?;
+        self.indexes.insert(s.to_string(), self.indexes.len());
+        Ok(())
+    }
+
+    fn read<R: Source>(&mut self, reader: &mut R) -> Result<String> {
+        if !self.enabled {
+            return read_str(reader);
+        }
+        let reference = read_len(reader)?;
+        self.resolve(reader, reference)
+    }
+
+    fn read_optional<R: Source>(&mut self, reader: &mut R) -> Result<Option<String>> {
+        if !self.enabled {
+            return read_optional_str(reader);
+        }
+        match read_len(reader)? {
+            0 => Ok(None),
+            reference => self.resolve(reader, reference - 1).map(Some),
+        }
+    }
+
+    fn resolve<R: Source>(&mut self, reader: &mut R, reference: usize) -> Result<String> {
+        if reference == 0 {
+            let s = read_str(reader)?;
+            if !self.complete {
+                self.strings.push(s.clone());
+            }
+            return Ok(s);
+        }
+        self.strings.get(reference - 1).cloned().ok_or_else(|| {
+            Error::parse("Binary", format!("invalid string reference: {}", reference))
+        })
+    }
+}
+
+fn read_extensions_v3<R: Source>(reader: &mut R) -> Result<Vec<(u8, Vec<u8>)>> {
+    let len = read_len(reader)?;
+    let block = read_bytes(reader, len)?;
+    let mut block = block.as_slice();
+
+    let mut entries = Vec::new();
+    while !block.is_empty() {
+        let tag = read_u8(&mut block)?;
+        let len = read_len(&mut block)?;
+        entries.push((tag, read_bytes(&mut block, len)?));
+    }
+    Ok(entries)
+}
+
+fn write_extension_v3(block: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<()> {
+    write_u8(block, tag)?;
+    write_varint(block, value.len() as u128)?;
+    block.put(value)
+}
+
+fn write_varint<W: Sink>(writer: &mut W, mut value: u128) -> Result<()> {
+    let mut buf = [0u8; 19];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    writer.put(&buf[..len])
+}
 
-    let version = read_u8(&mut reader)?;
-    if version != VERSION {
-        return Err(Error::parse(
-            "Binary",
-            format!("unsupported version: {}", version),
-        ));
+fn read_varint<R: Source>(reader: &mut R) -> Result<u128> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let byte = read_u8(reader)?;
+        let bits = u128::from(byte & 0x7f);
+        if shift == 126 && bits > 0b11 {
+            break;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
     }
+    Err(Error::parse("Binary", "varint too long"))
+}
 
-    let account_id = read_optional_string(&mut reader)?;
+fn write_signed<W: Sink>(writer: &mut W, value: i128) -> Result<()> {
+    write_varint(writer, ((value << 1) ^ (value >> 127)) as u128)
+}
 
-    let tx_count = read_u32(&mut reader)? as usize;
-    let mut transactions = Vec::with_capacity(tx_count);
+fn read_signed<R: Source>(reader: &mut R) -> Result<i128> {
+    let value = read_varint(reader)?;
+    Ok((value >> 1) as i128 ^ -((value & 1) as i128))
+}
 
-    for _ in 0..tx_count {
-        let transaction = read_transaction(&mut reader)?;
-        transactions.push(transaction);
-    }
+/// Reads a varint length or count.
+fn read_len<R: Source>(reader: &mut R) -> Result<usize> {
+    usize::try_from(read_varint(reader)?).map_err(|_| Error::parse("Binary", "length too large"))
+}
 
-    Ok(TransactionBatch {
-        account_id,
-        transactions,
-    })
+fn write_decimal<W: Sink>(writer: &mut W, value: Decimal) -> Result<()> {
+    write_u8(writer, value.scale() as u8)?;
+    write_signed(writer, value.mantissa())
 }
 
-/// Writes transaction data in binary format.
-///
-/// This function writes a compact binary representation of the transaction batch,
-/// including a magic number and version header for validation.
-///
-/// # Arguments
-///
-/// * `batch` - The transaction batch to write
-/// * `writer` - A writer to output the binary data to
-///
-/// # Returns
-///
-/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
-///
-/// # Errors
-///
-/// This function will return an error if any I/O operation fails.
-pub fn write_binary<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
-    write_u32(writer, MAGIC_NUMBER)?;
-    write_u8(writer, VERSION)?;
+fn read_decimal<R: Source>(reader: &mut R, name: &str) -> Result<Decimal> {
+    let scale = read_u8(reader)?;
+    let mantissa = read_signed(reader)?;
+    Decimal::try_from_i128_with_scale(mantissa, u32::from(scale))
+        .map_err(|e| Error::parse("Binary", format!("invalid {}: {}", name, e)))
+}
 
-    write_optional_string(writer, batch.account_id.as_deref())?;
+fn write_str<W: Sink>(writer: &mut W, s: &str) -> Result<()> {
+    write_varint(writer, s.len() as u128)?;
+    writer.put(s.as_bytes())
+}
 
-    write_u32(writer, batch.transactions.len() as u32)?;
+/// Reads `len` bytes. Lengths come from the file, so a corrupted one mustn't
+/// allocate more than the data actually holds.
+fn read_bytes<R: Source>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    const CHUNK: usize = 4096;
+    let mut buf = vec![0u8; len.min(CHUNK)];
+    reader.take(&mut buf)?;
+    while buf.len() < len {
+        let start = buf.len();
+        buf.resize(len.min(start + CHUNK), 0);
+        reader.take(&mut buf[start..])?;
+    }
+    Ok(buf)
+}
 
-    for tx in &batch.transactions {
-        write_transaction(writer, tx)?;
+fn read_str<R: Source>(reader: &mut R) -> Result<String> {
+    let len = read_len(reader)?;
+    reader.limits().check_string(len)?;
+    String::from_utf8(read_bytes(reader, len)?)
+        .map_err(|e| Error::parse("Binary", format!("invalid UTF-8: {}", e)))
+}
+
+fn write_optional_str<W: Sink>(writer: &mut W, s: Option<&str>) -> Result<()> {
+    match s {
+        Some(value) => {
+            write_varint(writer, value.len() as u128 + 1)?;
+            writer.put(value.as_bytes())
+        }
+        None => write_u8(writer, 0),
     }
+}
 
-    Ok(())
+fn read_optional_str<R: Source>(reader: &mut R) -> Result<Option<String>> {
+    let len = read_len(reader)?;
+    if len == 0 {
+        return Ok(None);
+    }
+    reader.limits().check_string(len - 1)?;
+    String::from_utf8(read_bytes(reader, len - 1)?)
+        .map(Some)
+        .map_err(|e| Error::parse("Binary", format!("invalid UTF-8: {}", e)))
 }
 
-fn read_transaction<R: Read>(reader: &mut R) -> Result<Transaction> {
+/// Reads one transaction encoded by format `version` (1 or 2).
+pub(crate) fn read_transaction<R: Source>(reader: &mut R, version: u8) -> Result<Transaction> {
     let id = read_string(reader)?;
 
     let posted_days = read_u32(reader)?;
@@ -110,21 +2178,21 @@ fn read_transaction<R: Read>(reader: &mut R) -> Result<Transaction> {
     };
 
     let kind_byte = read_u8(reader)?;
+
+    let amount_str = read_string(reader)?;
+    let mut amount_value = Decimal::from_str(&amount_str)
+        .map_err(|e| Error::parse("Binary", format!("invalid amount: {}", e)))?;
+
     let kind = match kind_byte {
         0 => TransactionKind::Debit,
         1 => TransactionKind::Credit,
-        _ => {
-            return Err(Error::parse(
-                "Binary",
-                format!("invalid kind: {}", kind_byte),
-            ))
-        }
+        code => code_kind(code).unwrap_or_else(|| {
+            let (kind, unsigned) = TransactionKind::from_sign(amount_value);
+            amount_value = unsigned;
+            kind
+        }),
     };
 
-    let amount_str = read_string(reader)?;
-    let amount_value = Decimal::from_str(&amount_str)
-        .map_err(|e| Error::parse("Binary", format!("invalid amount: {}", e)))?;
-
     let currency = read_string(reader)?;
 
     let amount = Money {
@@ -137,20 +2205,43 @@ fn read_transaction<R: Read>(reader: &mut R) -> Result<Transaction> {
     let counterparty = read_optional_string(reader)?;
     let category = read_optional_string(reader)?;
 
+    let mut tax_amount = None;
+    let mut tax_rate = None;
+    if version >= 2 {
+        // Unknown tags come from newer writers and are skipped.
+        for (tag, value) in read_extensions(reader)? {
+            match tag {
+                EXT_TAX_AMOUNT => tax_amount = Some(decode_decimal(value, "tax amount")?),
+                EXT_TAX_RATE => tax_rate = Some(decode_decimal(value, "tax rate")?),
+                _ => {}
+            }
+        }
+    }
+
     Ok(Transaction {
         id,
         posted_at,
         executed_at,
         kind,
+        status: TransactionStatus::Booked,
         amount,
         description,
         account,
         counterparty,
         category,
+        tax_amount,
+        tax_rate,
+        extra: BTreeMap::new(),
     })
 }
 
-fn write_transaction<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
+/// Writes a transaction in version 1 or 2 of the format. Version 1 has no
+/// extension block, so it drops the tax fields.
+pub(crate) fn write_transaction<W: Sink>(
+    writer: &mut W,
+    tx: &Transaction,
+    version: u8,
+) -> Result<()> {
     write_string(writer, &tx.id)?;
 
     let posted_days = tx.posted_at.num_days_from_ce() as u32;
@@ -164,11 +2255,7 @@ fn write_transaction<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
         write_u8(writer, 0)?;
     }
 
-    let kind_byte = match tx.kind {
-        TransactionKind::Debit => 0,
-        TransactionKind::Credit => 1,
-    };
-    write_u8(writer, kind_byte)?;
+    write_u8(writer, kind_code(tx.kind))?;
 
     write_string(writer, &tx.amount.amount.to_string())?;
     write_string(writer, &tx.amount.currency)?;
@@ -177,57 +2264,98 @@ fn write_transaction<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
     write_optional_string(writer, tx.account.as_deref())?;
     write_optional_string(writer, tx.counterparty.as_deref())?;
     write_optional_string(writer, tx.category.as_deref())?;
+    if version < 2 {
+        return Ok(());
+    }
+
+    let mut extensions = Vec::new();
+    if let Some(tax) = tx.tax_amount {
+        write_extension(&mut extensions, EXT_TAX_AMOUNT, tax.to_string().as_bytes())?;
+    }
+    if let Some(rate) = tx.tax_rate {
+        write_extension(&mut extensions, EXT_TAX_RATE, rate.to_string().as_bytes())?;
+    }
+    write_u32(writer, extensions.len() as u32)?;
+    writer.put(&extensions)?;
 
     Ok(())
 }
 
-fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+/// Reads an extension block: a u32 length followed by `tag, length, value`
+/// entries.
+fn read_extensions<R: Source>(reader: &mut R) -> Result<Vec<(u8, Vec<u8>)>> {
+    let len = read_u32(reader)? as usize;
+    let block = read_bytes(reader, len)?;
+    let mut block = block.as_slice();
+
+    let mut entries = Vec::new();
+    while !block.is_empty() {
+        let tag = read_u8(&mut block)?;
+        let len = read_u32(&mut block)? as usize;
+        entries.push((tag, read_bytes(&mut block, len)?));
+    }
+    Ok(entries)
+}
+
+fn write_extension(block: &mut Vec<u8>, tag: u8, value: &[u8]) -> Result<()> {
+    write_u8(block, tag)?;
+    write_u32(block, value.len() as u32)?;
+    block.put(value)
+}
+
+fn decode_decimal(bytes: Vec<u8>, name: &str) -> Result<Decimal> {
+    let text = String::from_utf8(bytes)
+        .map_err(|e| Error::parse("Binary", format!("invalid UTF-8: {}", e)))?;
+    Decimal::from_str(&text).map_err(|e| Error::parse("Binary", format!("invalid {}: {}", name, e)))
+}
+
+pub(crate) fn read_u8<R: Source>(reader: &mut R) -> Result<u8> {
     let mut buf = [0u8; 1];
-    reader.read_exact(&mut buf)?;
+    reader.take(&mut buf)?;
     Ok(buf[0])
 }
 
-fn write_u8<W: Write>(writer: &mut W, value: u8) -> Result<()> {
-    writer.write_all(&[value])?;
+pub(crate) fn write_u8<W: Sink>(writer: &mut W, value: u8) -> Result<()> {
+    writer.put(&[value])?;
     Ok(())
 }
 
-fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+pub(crate) fn read_u32<R: Source>(reader: &mut R) -> Result<u32> {
     let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
+    reader.take(&mut buf)?;
     Ok(u32::from_le_bytes(buf))
 }
 
-fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
-    writer.write_all(&value.to_le_bytes())?;
+pub(crate) fn write_u32<W: Sink>(writer: &mut W, value: u32) -> Result<()> {
+    writer.put(&value.to_le_bytes())?;
     Ok(())
 }
 
-fn read_i64<R: Read>(reader: &mut R) -> Result<i64> {
+fn read_i64<R: Source>(reader: &mut R) -> Result<i64> {
     let mut buf = [0u8; 8];
-    reader.read_exact(&mut buf)?;
+    reader.take(&mut buf)?;
     Ok(i64::from_le_bytes(buf))
 }
 
-fn write_i64<W: Write>(writer: &mut W, value: i64) -> Result<()> {
-    writer.write_all(&value.to_le_bytes())?;
+fn write_i64<W: Sink>(writer: &mut W, value: i64) -> Result<()> {
+    writer.put(&value.to_le_bytes())?;
     Ok(())
 }
 
-fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+pub(crate) fn read_string<R: Source>(reader: &mut R) -> Result<String> {
     let len = read_u32(reader)? as usize;
-    let mut buf = vec![0u8; len];
-    reader.read_exact(&mut buf)?;
-    String::from_utf8(buf).map_err(|e| Error::parse("Binary", format!("invalid UTF-8: {}", e)))
+    reader.limits().check_string(len)?;
+    String::from_utf8(read_bytes(reader, len)?)
+        .map_err(|e| Error::parse("Binary", format!("invalid UTF-8: {}", e)))
 }
 
-fn write_string<W: Write>(writer: &mut W, s: &str) -> Result<()> {
+pub(crate) fn write_string<W: Sink>(writer: &mut W, s: &str) -> Result<()> {
     write_u32(writer, s.len() as u32)?;
-    writer.write_all(s.as_bytes())?;
+    writer.put(s.as_bytes())?;
     Ok(())
 }
 
-fn read_optional_string<R: Read>(reader: &mut R) -> Result<Option<String>> {
+pub(crate) fn read_optional_string<R: Source>(reader: &mut R) -> Result<Option<String>> {
     let has_value = read_u8(reader)? != 0;
     if has_value {
         Ok(Some(read_string(reader)?))
@@ -236,7 +2364,7 @@ fn read_optional_string<R: Read>(reader: &mut R) -> Result<Option<String>> {
     }
 }
 
-fn write_optional_string<W: Write>(writer: &mut W, s: Option<&str>) -> Result<()> {
+pub(crate) fn write_optional_string<W: Sink>(writer: &mut W, s: Option<&str>) -> Result<()> {
     if let Some(value) = s {
         write_u8(writer, 1)?;
         write_string(writer, value)?;
@@ -246,7 +2374,7 @@ fn write_optional_string<W: Write>(writer: &mut W, s: Option<&str>) -> Result<()
     Ok(())
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use std::io::Cursor;
@@ -256,6 +2384,8 @@ mod tests {
         let mut batch = TransactionBatch {
             account_id: Some("ACC123".to_string()),
             transactions: vec![],
+            balances: Vec::new(),
+            metadata: Default::default(),
         };
 
         batch.transactions.push(Transaction {
@@ -263,6 +2393,7 @@ mod tests {
             posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
             executed_at: None,
             kind: TransactionKind::Credit,
+            status: TransactionStatus::Booked,
             amount: Money {
                 amount: Decimal::from_str("1000.50").unwrap(),
                 currency: "USD".to_string(),
@@ -271,6 +2402,9 @@ mod tests {
             account: Some("ACC123".to_string()),
             counterparty: None,
             category: Some("Salary".to_string()),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
         });
 
         let mut buffer = Vec::new();
@@ -285,6 +2419,25 @@ mod tests {
         assert_eq!(parsed.transactions[0].amount.amount.to_string(), "1000.50");
     }
 
+    #[test]
+    fn test_decode_encode_slice() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        let mut bytes = Vec::new();
+        encode_binary(&batch, &mut bytes).unwrap();
+        let mut via_writer = Vec::new();
+        write_binary(&batch, &mut via_writer).unwrap();
+        assert_eq!(bytes, via_writer);
+
+        assert_eq!(decode_binary(&bytes).unwrap(), batch);
+        assert!(decode_binary(&bytes[..bytes.len() - 1]).is_err());
+    }
+
     #[test]
     fn test_invalid_magic_number() {
         let data = vec![0, 0, 0, 0];
@@ -307,6 +2460,7 @@ mod tests {
                             .unwrap(),
                     ),
                     kind: TransactionKind::Credit,
+                    status: TransactionStatus::Booked,
                     amount: Money {
                         amount: Decimal::from_str("1000.50").unwrap(),
                         currency: "USD".to_string(),
@@ -315,12 +2469,16 @@ mod tests {
                     account: Some("ACC123".to_string()),
                     counterparty: Some("Employer Inc".to_string()),
                     category: Some("Salary".to_string()),
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
                 },
                 Transaction {
                     id: "TX002".to_string(),
                     posted_at: NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
                     executed_at: None,
                     kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
                     amount: Money {
                         amount: Decimal::from_str("150.50").unwrap(),
                         currency: "USD".to_string(),
@@ -329,8 +2487,17 @@ mod tests {
                     account: Some("ACC123".to_string()),
                     counterparty: Some("Store".to_string()),
                     category: Some("Food".to_string()),
+                    tax_amount: Some(Decimal::from_str("25.08").unwrap()),
+                    tax_rate: Some(Decimal::from(20)),
+                    extra: BTreeMap::new(),
                 },
             ],
+            balances: vec![Balance {
+                currency: "USD".to_string(),
+                opening: Decimal::from_str("500.00").unwrap(),
+                closing: Decimal::from_str("1350.00").unwrap(),
+            }],
+            metadata: Default::default(),
         };
 
         // Write to binary format
@@ -343,9 +2510,14 @@ mod tests {
 
         // Compare
         assert_eq!(parsed_batch.account_id, original_batch.account_id);
+        assert_eq!(parsed_batch.balances, original_batch.balances);
         assert_eq!(parsed_batch.transactions.len(), original_batch.transactions.len());
 
-        for (original, parsed) in original_batch.transactions.iter().zip(parsed_batch.transactions.iter()) {
+        for (original, parsed) in original_batch
+            .transactions
+            .iter()
+            .zip(parsed_batch.transactions.iter())
+        {
             assert_eq!(parsed.id, original.id);
             assert_eq!(parsed.posted_at, original.posted_at);
             assert_eq!(parsed.executed_at, original.executed_at);
@@ -355,6 +2527,837 @@ mod tests {
             assert_eq!(parsed.account, original.account);
             assert_eq!(parsed.counterparty, original.counterparty);
             assert_eq!(parsed.category, original.category);
+            assert_eq!(parsed.tax_amount, original.tax_amount);
+            assert_eq!(parsed.tax_rate, original.tax_rate);
+        }
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let mut batch = TransactionBatch::default();
+        batch.transactions.push(Transaction {
+            id: "TX001".to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str("10.00").unwrap(),
+                currency: "EUR".to_string(),
+            },
+            description: "Coffee".to_string(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        });
+
+        // Version 1 is version 2 without the (here empty) extension blocks,
+        // found after the account and at the end of the transaction.
+        let mut bytes = Vec::new();
+        write_batch_v2(&mut bytes, &batch, 2).unwrap();
+        assert_eq!(decode_binary(&bytes).unwrap(), batch);
+
+        bytes[4] = 1;
+        bytes.drain(6..10);
+        bytes.truncate(bytes.len() - 4);
+
+        assert_eq!(decode_binary(&bytes).unwrap(), batch);
+        let mut v1 = Vec::new();
+        write_batch_v2(&mut v1, &batch, 1).unwrap();
+        assert_eq!(v1, bytes);
+
+        bytes[4] = VERSION + 1;
+        assert!(decode_binary(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_write_versioned() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![Transaction {
+                id: "TX001".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Pending,
+                amount: Money {
+                    amount: Decimal::from_str("120.00").unwrap(),
+                    currency: "EUR".to_string(),
+                },
+                description: "Invoice".to_string(),
+                account: None,
+                counterparty: None,
+                category: None,
+                tax_amount: Some(Decimal::from_str("20.00").unwrap()),
+                tax_rate: Some(Decimal::from_str("0.2").unwrap()),
+                extra: BTreeMap::from([
+                    ("MCC".to_string(), "5411".to_string()),
+                    ("TerminalId".to_string(), "T-01".to_string()),
+                ]),
+            }],
+            balances: vec![Balance {
+                currency: "EUR".to_string(),
+                opening: Decimal::from_str("500.00").unwrap(),
+                closing: Decimal::from_str("380.00").unwrap(),
+            }],
+            metadata: BatchMetadata {
+                generated_at: NaiveDate::from_ymd_opt(2024, 2, 1)
+                    .unwrap()
+                    .and_hms_opt(6, 30, 0),
+                source_system: Some("CoreBank".to_string()),
+                period: Some("2024-01-01..2024-01-31".parse().unwrap()),
+                schema_version: Some("4.2".to_string()),
+            },
+        };
+
+        for version in 1..=VERSION {
+            let mut bytes = Vec::new();
+            write_binary_versioned(&batch, &mut bytes, version).unwrap();
+            assert_eq!(bytes[4], version);
+
+            let mut expected = batch.clone();
+            if version < 3 {
+                expected.transactions[0].extra.clear();
+                expected.transactions[0].status = TransactionStatus::Booked;
+                expected.metadata = BatchMetadata::default();
+            }
+            if version == 1 {
+                expected.balances.clear();
+                expected.transactions[0].tax_amount = None;
+                expected.transactions[0].tax_rate = None;
+            }
+            assert_eq!(parse_binary(bytes.as_slice()).unwrap(), expected);
+        }
+
+        for version in [0, VERSION + 1] {
+            let result = write_binary_versioned(&batch, &mut Vec::new(), version);
+            assert!(matches!(result, Err(Error::UnsupportedFormat(_))));
+        }
+    }
+
+    #[test]
+    fn test_transaction_kinds() {
+        let kinds = [
+            (TransactionKind::Transfer, "-25.00"),
+            (TransactionKind::Fee, "1.50"),
+            (TransactionKind::Interest, "0.42"),
+            (TransactionKind::Refund, "9.99"),
+            (TransactionKind::Reversal, "9.99"),
+        ];
+        let mut batch = TransactionBatch::default();
+        for (i, (kind, amount)) in kinds.into_iter().enumerate() {
+            batch.transactions.push(Transaction {
+                id: format!("TXK{}", i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                executed_at: None,
+                kind,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::from_str(amount).unwrap(),
+                    currency: "EUR".to_string(),
+                },
+                description: String::new(),
+                account: None,
+                counterparty: None,
+                category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        for version in 1..=VERSION {
+            let mut bytes = Vec::new();
+            write_binary_versioned(&batch, &mut bytes, version).unwrap();
+            assert_eq!(parse_binary(bytes.as_slice()).unwrap(), batch);
+        }
+
+        // A kind code from a newer version is read as a debit or credit
+        let mut bytes = Vec::new();
+        encode_binary(&batch, &mut bytes).unwrap();
+        let kind_at = bytes.windows(4).position(|id| id == b"TXK0").unwrap() + 4;
+        bytes[kind_at] = 63 << KIND_CODE_SHIFT;
+        let tx = &decode_binary(&bytes).unwrap().transactions[0];
+        assert_eq!(tx.kind, TransactionKind::Debit);
+        assert_eq!(tx.amount.amount, Decimal::from_str("25.00").unwrap());
+    }
+
+    #[test]
+    fn test_version_3_is_compact() {
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC-1".to_string()),
+            ..Default::default()
+        };
+        for (i, day) in [15, 15, 16, 20].into_iter().enumerate() {
+            let posted_at = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+            batch.transactions.push(Transaction {
+                id: format!("TX{:03}", i),
+                posted_at,
+                executed_at: (i % 2 == 0).then(|| posted_at.and_hms_opt(9, 30, i as u32).unwrap()),
+                kind: TransactionKind::Credit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::from_str("-1234.56").unwrap(),
+                    currency: "EUR".to_string(),
+                },
+                description: "Transfer".to_string(),
+                account: None,
+                counterparty: Some("ACME".to_string()),
+                category: None,
+                tax_amount: Some(Decimal::from_str("0.19").unwrap()),
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        // Dates before the epoch make the first delta negative
+        batch.transactions[3].posted_at = NaiveDate::from_ymd_opt(1960, 2, 29).unwrap();
+
+        let mut v2 = Vec::new();
+        write_batch_v2(&mut v2, &batch, 2).unwrap();
+        let mut v3 = Vec::new();
+        encode_binary(&batch, &mut v3).unwrap();
+        assert_eq!(v3[4], 3);
+        assert!(v3.len() < v2.len() * 3 / 4, "{} vs {}", v3.len(), v2.len());
+
+        assert_eq!(decode_binary(&v3).unwrap(), batch);
+        assert_eq!(decode_binary(&v2).unwrap(), batch);
+
+        v3[5] = 0x80;
+        assert!(decode_binary(&v3).is_err());
+    }
+
+    #[test]
+    fn test_compressed_payload() {
+        let mut batch = TransactionBatch::default();
+        for i in 0..200 {
+            batch.transactions.push(Transaction {
+                id: format!("TX{:05}", i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 3, 1 + i % 28).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(i64::from(i) * 100 + 99, 2),
+                    currency: "EUR".to_string(),
+                },
+                description: "Card payment, Grocery Store #12".to_string(),
+                account: Some("DE89370400440532013000".to_string()),
+                counterparty: None,
+                category: Some("groceries".to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        let mut plain = Vec::new();
+        encode_binary(&batch, &mut plain).unwrap();
+
+        #[cfg(feature = "zstd")]
+        {
+            let mut compressed = Vec::new();
+            write_binary_compressed(&batch, &mut compressed, 0).unwrap();
+            assert_eq!(compressed[..5], plain[..5]);
+            assert_eq!(compressed[5], DEFAULT_FLAGS | FLAG_ZSTD);
+            assert!(compressed.len() * 4 < plain.len());
+
+            assert_eq!(parse_binary(compressed.as_slice()).unwrap(), batch);
+            assert_eq!(decode_binary(&compressed).unwrap(), batch);
+            assert!(decode_binary(&compressed[..compressed.len() - 8]).is_err());
+        }
+
+        #[cfg(not(feature = "zstd"))]
+        {
+            plain[5] = FLAG_ZSTD;
+            let err = decode_binary(&plain).unwrap_err();
+            assert!(err.to_string().contains("zstd"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_encrypted_payload() {
+        let batch = TransactionBatch {
+            account_id: Some("DE89370400440532013000".to_string()),
+            transactions: vec![Transaction {
+                id: "TX1".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Credit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(150000, 2),
+                    currency: "EUR".to_string(),
+                },
+                description: "Salary".to_string(),
+                account: None,
+                counterparty: Some("Jane Doe".to_string()),
+                category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            }],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        #[cfg(feature = "encryption")]
+        {
+            let key = EncryptionKey::generate().unwrap();
+            let options = WriteOptions {
+                encryption_key: Some(key.clone()),
+                checksums: true,
+                ..WriteOptions::default()
+            };
+            let mut sealed = Vec::new();
+            write_binary_with(&batch, &mut sealed, &options).unwrap();
+            assert_eq!(sealed[5], DEFAULT_FLAGS | FLAG_CHECKSUMS | FLAG_ENCRYPTED);
+            let text = String::from_utf8_lossy(&sealed);
+            assert!(!text.contains("Jane Doe") && !text.contains("DE8937"));
+
+            let read = |bytes: &[u8], key: Option<EncryptionKey>| {
+                let options = ParseOptions {
+                    encryption_key: key,
+                    ..ParseOptions::default()
+                };
+                parse_binary_with(bytes, &options)
+            };
+            assert_eq!(read(&sealed, Some(key.clone())).unwrap(), batch);
+            assert!(read(&sealed, None).is_err());
+            let other = EncryptionKey::generate().unwrap();
+            let err = read(&sealed, Some(other)).unwrap_err();
+            assert!(err.to_string().contains("wrong key"), "{}", err);
+
+            // The header is authenticated along with the payload
+            let mut tampered = sealed.clone();
+            tampered[5] &= !FLAG_CHECKSUMS;
+            assert!(read(&tampered, Some(key.clone())).is_err());
+            assert!(read(&sealed[..sealed.len() - 1], Some(key)).is_err());
+
+            let hex = "00112233445566778899AABBCCDDEEFF00112233445566778899aabbccddeeff";
+            let key: EncryptionKey = hex.parse().unwrap();
+            assert_eq!(key.as_bytes()[..3], [0x00, 0x11, 0x22]);
+            assert!(hex[1..].parse::<EncryptionKey>().is_err());
+            assert_eq!(format!("{:?}", key), "EncryptionKey(..)");
+        }
+
+        #[cfg(not(feature = "encryption"))]
+        {
+            let mut bytes = Vec::new();
+            encode_binary(&batch, &mut bytes).unwrap();
+            bytes[5] |= FLAG_ENCRYPTED;
+            let err = decode_binary(&bytes).unwrap_err();
+            assert!(err.to_string().contains("encryption"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_signed_payload() {
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            ..Default::default()
+        };
+        for (id, cents) in [("TX2", 1050), ("TX1", 99)] {
+            batch.transactions.push(Transaction {
+                id: id.to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(cents, 2),
+                    currency: "EUR".to_string(),
+                },
+                description: "Card payment".to_string(),
+                account: None,
+                counterparty: None,
+                category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        let mut plain = Vec::new();
+        encode_binary(&batch, &mut plain).unwrap();
+
+        // Signatures are skipped when they aren't checked
+        let mut signed = plain[..6].to_vec();
+        signed[5] |= FLAG_SIGNED;
+        signed.extend_from_slice(&[7; SIGNATURE_LEN]);
+        signed.extend_from_slice(&plain[6..]);
+        assert_eq!(decode_binary(&signed).unwrap(), batch);
+
+        #[cfg(feature = "signing")]
+        {
+            use crate::signature::{sign_batch, SigningKey};
+
+            let key = SigningKey::generate().unwrap();
+            let options = WriteOptions {
+                signing_key: Some(key.clone()),
+                checksums: true,
+                ..WriteOptions::default()
+            };
+            let mut bytes = Vec::new();
+            write_binary_with(&batch, &mut bytes, &options).unwrap();
+            assert_eq!(bytes[5], DEFAULT_FLAGS | FLAG_CHECKSUMS | FLAG_SIGNED);
+            assert_eq!(
+                bytes[6..6 + SIGNATURE_LEN],
+                sign_batch(&batch, &key).as_bytes()[..]
+            );
+
+            let read = |bytes: &[u8], key: &SigningKey| {
+                let options = ParseOptions {
+                    verifying_key: Some(key.verifying_key()),
+                    ..ParseOptions::default()
+                };
+                parse_binary_with(bytes, &options)
+            };
+            assert_eq!(read(&bytes, &key).unwrap(), batch);
+            assert!(read(&plain, &key).is_err());
+            assert!(read(&signed, &key).is_err());
+            let other = SigningKey::generate().unwrap();
+            assert!(matches!(read(&bytes, &other), Err(Error::InvalidSignature)));
+
+            // A record changed along with its checksums still fails
+            let mut forged = batch.clone();
+            forged.transactions[1].amount.amount = Decimal::new(9900, 2);
+            let mut tampered = bytes[..6 + SIGNATURE_LEN].to_vec();
+            let mut payload = Vec::new();
+            write_payload(&mut payload, &forged, bytes[5]).unwrap();
+            tampered.extend_from_slice(&payload);
+            assert_eq!(decode_binary(&tampered).unwrap(), forged);
+            assert!(matches!(
+                read(&tampered, &key),
+                Err(Error::InvalidSignature)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_checksums() {
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            ..Default::default()
+        };
+        for description in ["First", "Second", "Third"] {
+            batch.transactions.push(Transaction {
+                id: description.to_uppercase(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::from_str("10.00").unwrap(),
+                    currency: "EUR".to_string(),
+                },
+                description: description.to_string(),
+                account: None,
+                counterparty: None,
+                category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        let options = WriteOptions {
+            checksums: true,
+            ..WriteOptions::default()
+        };
+        let mut bytes = Vec::new();
+        write_binary_with(&batch, &mut bytes, &options).unwrap();
+        assert_eq!(bytes[5], DEFAULT_FLAGS | FLAG_CHECKSUMS);
+        assert_eq!(parse_binary(bytes.as_slice()).unwrap(), batch);
+
+        let corrupt = |at: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[at] = value;
+            parse_binary(bytes.as_slice()).unwrap_err().to_string()
+        };
+        let second = bytes.windows(6).position(|w| w == b"Second").unwrap();
+        assert!(corrupt(second, b's').contains("record 2 corrupted: checksum mismatch"));
+        // A damaged length shows up as a decoding failure within the record
+        assert!(corrupt(second - 1, 0x7f).contains("record 2 corrupted"));
+        assert!(corrupt(7, b'X').contains("file checksum mismatch"));
+        assert!(corrupt(bytes.len() - 1, 0).contains("file checksum mismatch"));
+
+        #[cfg(feature = "zstd")]
+        {
+            let options = WriteOptions {
+                compression: Some(3),
+                ..options
+            };
+            let mut compressed = Vec::new();
+            write_binary_with(&batch, &mut compressed, &options).unwrap();
+            assert_eq!(compressed[5], DEFAULT_FLAGS | FLAG_CHECKSUMS | FLAG_ZSTD);
+            assert_eq!(decode_binary(&compressed).unwrap(), batch);
+        }
+    }
+
+    #[test]
+    fn test_string_dictionary() {
+        let mut batch = TransactionBatch::default();
+        for (i, category) in ["groceries", "rent", "groceries", "groceries"]
+            .into_iter()
+            .enumerate()
+        {
+            batch.transactions.push(Transaction {
+                id: format!("TX{}", i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::from_str("10.00").unwrap(),
+                    currency: "EUR".to_string(),
+                },
+                description: "Payment".to_string(),
+                account: Some("DE89370400440532013000".to_string()),
+                counterparty: (i == 1).then(|| "Landlord".to_string()),
+                category: Some(category.to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        let mut bytes = Vec::new();
+        encode_binary(&batch, &mut bytes).unwrap();
+        let count = |needle: &[u8]| bytes.windows(needle.len()).filter(|w| *w == needle).count();
+        assert_eq!(count(b"groceries"), 1);
+        assert_eq!(count(b"DE89370400440532013000"), 1);
+        assert_eq!(count(b"EUR"), 1);
+        assert_eq!(decode_binary(&bytes).unwrap(), batch);
+
+        let mut plain = Vec::new();
+        write_batch(&mut plain, &batch, 0).unwrap();
+        assert!(plain.len() > bytes.len() + 50);
+        assert_eq!(decode_binary(&plain).unwrap(), batch);
+
+        // The last transaction's category, before its extensions and the end
+        // of the chunks, refers to the table's third string (after EUR and
+        // the account), shifted by one for being optional
+        let at = bytes.len() - 3;
+        assert_eq!(bytes[at], 4);
+        bytes[at] = 9;
+        let err = decode_binary(&bytes).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid string reference: 8"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_indexed_lookup() {
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            ..Default::default()
+        };
+        for i in 0..50u32 {
+            batch.transactions.push(Transaction {
+                id: format!("TX{:03}", 49 - i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1 + i % 12, 1 + i % 28).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Credit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(i64::from(i), 1),
+                    currency: ["EUR", "USD"][i as usize % 2].to_string(),
+                },
+                description: format!("Payment {}", i),
+                account: None,
+                counterparty: None,
+                category: Some(["food", "rent", "travel"][i as usize % 3].to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        let options = WriteOptions {
+            index: true,
+            checksums: true,
+            ..WriteOptions::default()
+        };
+        let mut bytes = Vec::new();
+        write_binary_with(&batch, &mut bytes, &options).unwrap();
+        assert_eq!(parse_binary(bytes.as_slice()).unwrap(), batch);
+
+        let mut indexed = IndexedBinary::new(Cursor::new(&bytes)).unwrap();
+        assert_eq!(indexed.account_id(), Some("ACC123"));
+        assert_eq!(indexed.len(), 50);
+        assert_eq!(indexed.ids().next(), Some("TX000"));
+        assert_eq!(
+            indexed.get("TX007").unwrap().as_ref(),
+            Some(&batch.transactions[42])
+        );
+        assert_eq!(
+            indexed.get("TX049").unwrap().as_ref(),
+            Some(&batch.transactions[0])
+        );
+        assert_eq!(indexed.get("TX050").unwrap(), None);
+
+        let range = indexed.range("TX010"..="TX012").unwrap();
+        let expected: Vec<_> = batch.transactions[37..40].iter().rev().cloned().collect();
+        assert_eq!(range, expected);
+
+        let mut plain = Vec::new();
+        write_binary(&batch, &mut plain).unwrap();
+        let err = IndexedBinary::new(Cursor::new(&plain)).unwrap_err();
+        assert!(err.to_string().contains("no index"), "{}", err);
+
+        #[cfg(feature = "zstd")]
+        {
+            let options = WriteOptions {
+                compression: Some(0),
+                ..options
+            };
+            assert!(write_binary_with(&batch, &mut Vec::new(), &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_append() {
+        let transaction = |id: &str, day: u32| Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str("10.00").unwrap(),
+                currency: "EUR".to_string(),
+            },
+            description: "Coffee".to_string(),
+            account: None,
+            counterparty: None,
+            category: Some("food".to_string()),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![transaction("TX1", 30), transaction("TX2", 31)],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        for checksums in [false, true] {
+            let path = std::env::temp_dir().join(format!(
+                "binary-append-{}-{}.bin",
+                std::process::id(),
+                checksums
+            ));
+            let options = WriteOptions {
+                checksums,
+                ..WriteOptions::default()
+            };
+            let mut file = File::create(&path).unwrap();
+            write_binary_with(&batch, &mut file, &options).unwrap();
+            drop(file);
+
+            let more = [transaction("TX3", 1), transaction("TX4", 2)];
+            append(&path, &more).unwrap();
+            append(&path, &[]).unwrap();
+            append(&path, &more[..1]).unwrap();
+
+            let parsed = parse_binary(File::open(&path).unwrap()).unwrap();
+            let mut expected = batch.clone();
+            expected.transactions.extend(more.iter().cloned());
+            expected.transactions.push(more[0].clone());
+            assert_eq!(parsed, expected);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("binary-append-{}.bin", std::process::id()));
+        let options = WriteOptions {
+            index: true,
+            ..WriteOptions::default()
+        };
+        batch.transactions.truncate(1);
+        write_binary_with(&batch, &mut File::create(&path).unwrap(), &options).unwrap();
+        assert!(append(&path, &batch.transactions).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_binary_writer() {
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: Vec::new(),
+            balances: vec![Balance {
+                currency: "EUR".to_string(),
+                opening: Decimal::from_str("100.00").unwrap(),
+                closing: Decimal::from_str("90.00").unwrap(),
+            }],
+            metadata: BatchMetadata {
+                source_system: Some("CoreBank".to_string()),
+                ..BatchMetadata::default()
+            },
+        };
+        for i in 0..CHUNK_LEN as u32 * 2 + 10 {
+            batch.transactions.push(Transaction {
+                id: format!("TX{}", i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1 + i % 12, 1 + i % 28).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(i64::from(i), 2),
+                    currency: "EUR".to_string(),
+                },
+                description: "Coffee".to_string(),
+                account: None,
+                counterparty: None,
+                category: Some(["food", "travel"][i as usize % 2].to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+
+        let stream = |options: &WriteOptions| {
+            let mut writer = BinaryWriter::create_from(Vec::new(), &batch, options).unwrap();
+            for tx in &batch.transactions {
+                writer.write_tx(tx).unwrap();
+            }
+            writer.finish().unwrap()
+        };
+        let mut options = WriteOptions::default();
+        assert_eq!(decode_binary(&stream(&options)).unwrap(), batch);
+        options.checksums = true;
+        assert_eq!(decode_binary(&stream(&options)).unwrap(), batch);
+        #[cfg(feature = "zstd")]
+        {
+            options.compression = Some(0);
+            assert_eq!(decode_binary(&stream(&options)).unwrap(), batch);
+        }
+
+        let mut writer = BinaryWriter::create(Vec::new(), None, &[]).unwrap();
+        writer.write_tx(&batch.transactions[0]).unwrap();
+        let mut expected = Vec::new();
+        encode_binary(
+            &TransactionBatch {
+                account_id: None,
+                transactions: batch.transactions[..1].to_vec(),
+                balances: Vec::new(),
+                metadata: Default::default(),
+            },
+            &mut expected,
+        )
+        .unwrap();
+        assert_eq!(writer.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [
+            0,
+            1,
+            -1,
+            63,
+            -64,
+            64,
+            i128::from(i64::MAX),
+            i128::MIN,
+            i128::MAX,
+        ] {
+            let mut bytes = Vec::new();
+            write_signed(&mut bytes, value).unwrap();
+            assert_eq!(read_signed(&mut bytes.as_slice()).unwrap(), value);
+        }
+        assert!(read_varint(&mut [0xffu8; 20].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_date_delta_overflow() {
+        let file = |second_delta: i128| {
+            let mut data = MAGIC_NUMBER.to_le_bytes().to_vec();
+            data.extend_from_slice(&[3, 0]);
+            write_optional_str(&mut data, None).unwrap();
+            write_varint(&mut data, 0).unwrap();
+            write_varint(&mut data, 2).unwrap();
+            for (id, delta) in [("TX1", 1), ("TX2", second_delta)] {
+                write_signed(&mut data, delta).unwrap();
+                write_str(&mut data, id).unwrap();
+                write_u8(&mut data, 0).unwrap();
+                write_decimal(&mut data, Decimal::from(5)).unwrap();
+                write_str(&mut data, "USD").unwrap();
+                write_str(&mut data, "").unwrap();
+                for _ in 0..3 {
+                    write_optional_str(&mut data, None).unwrap();
+                }
+                write_varint(&mut data, 0).unwrap();
+            }
+            data
+        };
+
+        let batch = decode_binary(&file(0)).unwrap();
+        let posted_at = NaiveDate::from_ymd_opt(1970, 1, 2).unwrap();
+        assert_eq!(batch.transactions[1].posted_at, posted_at);
+        let err = decode_binary(&file(i128::MAX)).unwrap_err();
+        assert!(err.to_string().contains("invalid posted date"), "{}", err);
+    }
+
+    #[test]
+    fn test_untrusted_lengths() {
+        // Corrupted lengths and counts fail at the end of the data instead of
+        // allocating what they claim
+        let mut header = MAGIC_NUMBER.to_le_bytes().to_vec();
+        header.push(2);
+        let mut long_string = header.clone();
+        long_string.push(1);
+        long_string.extend_from_slice(&u32::MAX.to_le_bytes());
+        long_string.extend_from_slice(b"ACC");
+        let mut many_transactions = header;
+        many_transactions.push(0);
+        many_transactions.extend_from_slice(&0u32.to_le_bytes());
+        many_transactions.extend_from_slice(&u32::MAX.to_le_bytes());
+        for data in [long_string, many_transactions] {
+            assert!(matches!(decode_binary(&data), Err(Error::Parse { .. })));
+        }
+
+        let mut batch = TransactionBatch::default();
+        for id in ["TX1", "TX2"] {
+            batch.transactions.push(Transaction {
+                id: id.to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::from(5),
+                    currency: "USD".to_string(),
+                },
+                description: "Coffee".to_string(),
+                account: None,
+                counterparty: None,
+                category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            });
+        }
+        let mut buffer = Vec::new();
+        write_binary(&batch, &mut buffer).unwrap();
+        let parse = |limits: Limits| {
+            let options = ParseOptions {
+                limits,
+                ..ParseOptions::default()
+            };
+            parse_binary_with(&buffer[..], &options)
+        };
+        assert_eq!(parse(Limits::untrusted()).unwrap(), batch);
+        for limits in [
+            Limits {
+                max_transactions: Some(1),
+                ..Limits::default()
+            },
+            Limits {
+                max_string_len: Some(5),
+                ..Limits::default()
+            },
+        ] {
+            assert!(matches!(parse(limits), Err(Error::LimitExceeded(_))));
         }
     }
 }
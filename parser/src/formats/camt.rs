@@ -0,0 +1,529 @@
+use super::ParseOptions;
+use crate::balance;
+use crate::{
+    Balance, Error, Money, Period, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+
+/// Child elements of an `<Ntry>` or `<Bal>`, keyed by their path below it
+/// (`BookgDt/Dt`), with the currency of its `<Amt>` kept aside.
+#[derive(Default)]
+struct Record {
+    fields: HashMap<String, String>,
+    currency: Option<String>,
+}
+
+impl Record {
+    /// The first of `keys` that is present and not empty.
+    fn first(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .filter_map(|key| self.fields.get(*key))
+            .map(|value| value.trim())
+            .find(|value| !value.is_empty())
+    }
+}
+
+/// What has been read so far, besides the current record.
+#[derive(Default)]
+struct Statement {
+    batch: TransactionBatch,
+    record: Record,
+    entries: usize,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+}
+
+/// Parses an ISO 20022 CAMT.053 bank-to-customer statement.
+///
+/// Elements are matched by local name, so any version of the `camt.053`
+/// namespace is accepted, and a document may hold several `<Stmt>`s.
+///
+/// - `<Acct><Id>` (`<IBAN>` or `<Othr><Id>`) is the batch's account ID
+/// - `<CreDtTm>` is the [`BatchMetadata::generated_at`](crate::BatchMetadata::generated_at)
+///   time and `<FrToDt>` the [`BatchMetadata::period`](crate::BatchMetadata::period);
+///   without `<FrToDt>`, the period runs from the opening to the closing
+///   balance date
+/// - `<Bal>`s of type `OPBD` (or `PRCD`) are opening balances and `CLBD`
+///   closing balances; other types, such as available balances, are ignored
+/// - each booked (`BOOK`) or pending (`PDNG`) `<Ntry>` is a transaction with
+///   the matching [`TransactionStatus`]; informational and future entries
+///   are skipped. Pending entries don't count towards the closing balance.
+///   `<CdtDbtInd>` gives the kind; reversals (`<RvslInd>true`) are
+///   [`TransactionKind::Reversal`] with the sign of their effect on the
+///   balance. `<BookgDt>` is the posting date and `<ValDt>` the execution
+///   date. The ID is the bank's `<AcctSvcrRef>`, else the `<NtryRef>` or the
+///   end-to-end ID; entries with none get sequential IDs (`CAMT000001`,
+///   ...). The unstructured remittance information (or `<AddtlNtryInf>`) is
+///   the description, and the creditor of a debit or the debtor of a credit
+///   the counterparty.
+///
+/// Like [`super::mt940::parse_mt940`], the document is read whole and
+/// balanced before it is cut to [`ParseOptions::max_records`]
+/// transactions; [`ParseOptions::limits`] bound the input and the batch.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing the CAMT.053 document
+/// * `options` - Record and size caps, and currency checks
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with the booked and pending transactions
+/// and declared balances, or an [`Error`] if the document is malformed or doesn't
+/// balance.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not well-formed XML
+/// - An entry or balance lacks its amount, currency, indicator or date
+/// - A date or amount cannot be parsed
+/// - A closing balance has no opening balance in its currency
+/// - The opening balance plus the entries doesn't equal the closing balance
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_camt<R: Read>(reader: R, options: &ParseOptions) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| parse_document(reader))
+}
+
+fn parse_document<R: Read>(reader: R) -> Result<TransactionBatch> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    let mut buf = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut statement = Statement::default();
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|e| {
+            Error::parse(
+                "CAMT",
+                format!("at byte {}: {}", reader.error_position(), e),
+            )
+        })?;
+
+        match event {
+            Event::Start(element) => {
+                open_element(&element, &path, &mut statement.record)?;
+                path.push(element_name(&element)?);
+                text.clear();
+            }
+            Event::Empty(element) => {
+                open_element(&element, &path, &mut statement.record)?;
+                let name = element_name(&element)?;
+                close_element(&name, &path, String::new(), &mut statement)?;
+            }
+            Event::End(_) => {
+                if let Some(name) = path.pop() {
+                    let text = std::mem::take(&mut text);
+                    close_element(&name, &path, text, &mut statement)?;
+                }
+            }
+            Event::Text(content) => text.push_str(&content.xml_content().map_err(xml_error)?),
+            Event::CData(content) => text.push_str(&content.decode().map_err(xml_error)?),
+            Event::GeneralRef(reference) => {
+                if let Some(ch) = reference.resolve_char_ref().map_err(xml_error)? {
+                    text.push(ch);
+                } else {
+                    let name = reference.decode().map_err(xml_error)?;
+                    let value = resolve_predefined_entity(&name).ok_or_else(|| {
+                        Error::parse("CAMT", format!("unknown entity &{};", name))
+                    })?;
+                    text.push_str(value);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let Statement {
+        mut batch,
+        from,
+        to,
+        start,
+        end,
+        ..
+    } = statement;
+    let period = match (from, to) {
+        (Some(start), Some(end)) => Some((start, end)),
+        _ => start.zip(end),
+    };
+    batch.metadata.period = period
+        .filter(|(start, end)| start <= end)
+        .map(|(start, end)| Period { start, end });
+    balance::ensure_balanced(&batch, "CAMT")?;
+    Ok(batch)
+}
+
+fn element_name(element: &BytesStart) -> Result<String> {
+    Ok(std::str::from_utf8(element.local_name().as_ref())
+        .map_err(|e| Error::parse("CAMT", format!("invalid element name: {}", e)))?
+        .to_string())
+}
+
+fn xml_error(error: impl std::fmt::Display) -> Error {
+    Error::parse("CAMT", error.to_string())
+}
+
+fn open_element(element: &BytesStart, path: &[String], record: &mut Record) -> Result<()> {
+    let name = element_name(element)?;
+    match path {
+        [.., parent] if parent == "Stmt" && (name == "Ntry" || name == "Bal") => {
+            *record = Record::default();
+        }
+        [.., stmt, parent]
+            if stmt == "Stmt" && (parent == "Ntry" || parent == "Bal") && name == "Amt" =>
+        {
+            record.currency = element
+                .try_get_attribute("Ccy")
+                .map_err(xml_error)?
+                .map(|attr| attr.decode_and_unescape_value(element.decoder()))
+                .transpose()
+                .map_err(xml_error)?
+                .map(|value| value.into_owned());
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn close_element(
+    name: &str,
+    path: &[String],
+    text: String,
+    statement: &mut Statement,
+) -> Result<()> {
+    let Some(stmt) = path.iter().rposition(|parent| parent == "Stmt") else {
+        // The message's creation time, unless the statement has its own
+        if name == "CreDtTm" && path.last().is_some_and(|parent| parent == "GrpHdr") {
+            statement.batch.metadata.generated_at = Some(parse_date_time(&text)?);
+        }
+        return Ok(());
+    };
+    let below = &path[stmt + 1..];
+    let key = below
+        .iter()
+        .map(String::as_str)
+        .chain([name])
+        .collect::<Vec<_>>()
+        .join("/");
+    match below {
+        [] if name == "Ntry" => {
+            statement.entries += 1;
+            let index = statement.entries;
+            let record = std::mem::take(&mut statement.record);
+            let tx = build_transaction(&record, index)
+                .map_err(|e| e.prefixed("CAMT", &format!("entry {}: ", index)))?;
+            if let Some(tx) = tx {
+                statement.batch.transactions.push(tx);
+            }
+        }
+        [] if name == "Bal" => {
+            let record = std::mem::take(&mut statement.record);
+            apply_balance(&record, statement)?;
+        }
+        [] if name == "CreDtTm" => {
+            statement.batch.metadata.generated_at = Some(parse_date_time(&text)?);
+        }
+        [record, ..] if record == "Ntry" || record == "Bal" => {
+            let key = key.split_once('/').map_or("", |(_, key)| key);
+            statement
+                .record
+                .fields
+                .entry(key.to_string())
+                .or_insert(text);
+        }
+        _ => match key.as_str() {
+            "Acct/Id/IBAN" | "Acct/Id/Othr/Id" if statement.batch.account_id.is_none() => {
+                statement.batch.account_id = Some(text.trim().to_string());
+            }
+            "FrToDt/FrDtTm" => statement.from = Some(parse_date(&text)?),
+            "FrToDt/ToDtTm" => statement.to = Some(parse_date(&text)?),
+            _ => {}
+        },
+    }
+    Ok(())
+}
+
+/// Records an opening or closing `<Bal>`.
+fn apply_balance(record: &Record, statement: &mut Statement) -> Result<()> {
+    let opening = match record.first(&["Tp/CdOrPrtry/Cd", "Tp/CdOrPrtry/Prtry"]) {
+        Some("OPBD" | "PRCD") => true,
+        Some("CLBD") => false,
+        _ => return Ok(()),
+    };
+    let code = record
+        .currency
+        .clone()
+        .ok_or_else(|| Error::parse("CAMT", "balance: missing Ccy attribute on <Amt>"))?;
+    let amount = signed_amount(record).map_err(|e| e.prefixed("CAMT", "balance: "))?;
+    let date = record
+        .first(&["Dt/Dt", "Dt/DtTm"])
+        .ok_or_else(|| Error::parse("CAMT", "balance: missing <Dt>"))?;
+    let date = parse_date(date)?;
+
+    let batch = &mut statement.batch;
+    if opening {
+        if !batch.balances.iter().any(|b| b.currency == code) {
+            batch.balances.push(Balance {
+                currency: code,
+                opening: amount,
+                closing: amount,
+            });
+            statement.start = Some(statement.start.map_or(date, |start| start.min(date)));
+        }
+    } else {
+        let balance = batch
+            .balances
+            .iter_mut()
+            .find(|b| b.currency == code)
+            .ok_or_else(|| {
+                let message = format!("closing balance without opening balance in {}", code);
+                Error::parse("CAMT", message)
+            })?;
+        balance.closing = amount;
+        statement.end = statement.end.max(Some(date));
+    }
+    Ok(())
+}
+
+/// Builds the transaction of an `<Ntry>`, or `None` if it is neither booked
+/// nor pending.
+fn build_transaction(record: &Record, index: usize) -> Result<Option<Transaction>> {
+    let status = match record.first(&["Sts/Cd", "Sts"]) {
+        None | Some("BOOK") => TransactionStatus::Booked,
+        Some("PDNG") => TransactionStatus::Pending,
+        Some(_) => return Ok(None),
+    };
+
+    let reversal = record.first(&["RvslInd"]) == Some("true");
+    let (kind, amount) = match (record.first(&["CdtDbtInd"]), reversal) {
+        (Some(_), true) => (TransactionKind::Reversal, signed_amount(record)?),
+        (Some("CRDT"), false) => (TransactionKind::Credit, unsigned_amount(record)?),
+        (Some("DBIT"), false) => (TransactionKind::Debit, unsigned_amount(record)?),
+        (Some(other), false) => {
+            return Err(Error::parse(
+                "CAMT",
+                format!("invalid <CdtDbtInd>: {}", other),
+            ))
+        }
+        (None, _) => return Err(Error::parse("CAMT", "missing <CdtDbtInd>")),
+    };
+    let currency = record
+        .currency
+        .clone()
+        .ok_or_else(|| Error::parse("CAMT", "missing Ccy attribute on <Amt>"))?;
+
+    let booked = record.first(&["BookgDt/Dt", "BookgDt/DtTm"]);
+    let value = record.first(&["ValDt/Dt", "ValDt/DtTm"]);
+    let posted_at = booked
+        .or(value)
+        .ok_or_else(|| Error::parse("CAMT", "missing <BookgDt>"))?;
+    let posted_at = parse_date(posted_at)?;
+    let executed_at = match (booked, value) {
+        (Some(_), Some(value)) if value.len() > 10 => Some(parse_date_time(value)?),
+        (Some(_), Some(value)) => parse_date(value)?.and_hms_opt(0, 0, 0),
+        _ => None,
+    };
+
+    let id = record
+        .first(&[
+            "AcctSvcrRef",
+            "NtryRef",
+            "NtryDtls/TxDtls/Refs/AcctSvcrRef",
+            "NtryDtls/TxDtls/Refs/EndToEndId",
+        ])
+        .filter(|id| *id != "NOTPROVIDED")
+        .map_or_else(|| format!("CAMT{:06}", index), str::to_string);
+    let description = record
+        .first(&[
+            "NtryDtls/TxDtls/RmtInf/Ustrd",
+            "NtryDtls/TxDtls/AddtlTxInf",
+            "AddtlNtryInf",
+        ])
+        .unwrap_or_default()
+        .to_string();
+    let party = if record.first(&["CdtDbtInd"]) == Some("DBIT") {
+        "Cdtr"
+    } else {
+        "Dbtr"
+    };
+    let counterparty = record
+        .first(&[
+            &format!("NtryDtls/TxDtls/RltdPties/{}/Nm", party),
+            &format!("NtryDtls/TxDtls/RltdPties/{}/Pty/Nm", party),
+        ])
+        .map(str::to_string);
+
+    Ok(Some(Transaction {
+        id,
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money { amount, currency },
+        description,
+        account: None,
+        counterparty,
+        category: None,
+        tax_amount: None,
+        tax_rate: None,
+        extra: BTreeMap::new(),
+    }))
+}
+
+fn unsigned_amount(record: &Record) -> Result<Decimal> {
+    let amount = record
+        .first(&["Amt"])
+        .ok_or_else(|| Error::parse("CAMT", "missing <Amt>"))?;
+    Decimal::from_str(amount)
+        .map_err(|e| Error::parse("CAMT", format!("invalid amount: {}", amount)).with_source(e))
+}
+
+/// The amount of a record, negative when `<CdtDbtInd>` is `DBIT`.
+fn signed_amount(record: &Record) -> Result<Decimal> {
+    let amount = unsigned_amount(record)?;
+    match record.first(&["CdtDbtInd"]) {
+        Some("CRDT") => Ok(amount),
+        Some("DBIT") => Ok(-amount),
+        Some(other) => Err(Error::parse(
+            "CAMT",
+            format!("invalid <CdtDbtInd>: {}", other),
+        )),
+        None => Err(Error::parse("CAMT", "missing <CdtDbtInd>")),
+    }
+}
+
+/// Parses an ISO date, or the date of an ISO date and time.
+fn parse_date(text: &str) -> Result<NaiveDate> {
+    let text = text.trim();
+    NaiveDate::parse_from_str(text.get(..10).unwrap_or(text), "%Y-%m-%d")
+        .map_err(|e| Error::parse("CAMT", format!("invalid date: {}", text)).with_source(e))
+}
+
+/// Parses an ISO date and time, ignoring fractional seconds and the offset.
+fn parse_date_time(text: &str) -> Result<NaiveDateTime> {
+    let text = text.trim();
+    NaiveDateTime::parse_from_str(text.get(..19).unwrap_or(text), "%Y-%m-%dT%H:%M:%S").map_err(
+        |e| Error::parse("CAMT", format!("invalid date and time: {}", text)).with_source(e),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const STATEMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.08">
+  <BkToCstmrStmt>
+    <GrpHdr><MsgId>MSG1</MsgId><CreDtTm>2024-02-01T06:00:00+01:00</CreDtTm></GrpHdr>
+    <Stmt>
+      <Id>STMT240131</Id>
+      <FrToDt><FrDtTm>2024-01-01T00:00:00</FrDtTm><ToDtTm>2024-01-31T23:59:59</ToDtTm></FrToDt>
+      <Acct><Id><IBAN>NL91ABNA0417164300</IBAN></Id></Acct>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>OPBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">1000.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2024-01-01</Dt></Dt>
+      </Bal>
+      <Bal>
+        <Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp>
+        <Amt Ccy="EUR">1470.00</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+        <Dt><Dt>2024-01-31</Dt></Dt>
+      </Bal>
+      <Ntry>
+        <Amt Ccy="EUR">25.50</Amt><CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><Dt>2024-01-02</Dt></BookgDt><ValDt><Dt>2024-01-01</Dt></ValDt>
+        <AcctSvcrRef>B4A02</AcctSvcrRef>
+        <NtryDtls><TxDtls>
+          <RltdPties><Cdtr><Nm>Coffee Shop B.V.</Nm></Cdtr></RltdPties>
+          <RmtInf><Ustrd>Coffee &amp; cake</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">500</Amt><CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><Dt>2024-01-31</Dt></BookgDt>
+        <AddtlNtryInf>Salary January</AddtlNtryInf>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">4.50</Amt><CdtDbtInd>DBIT</CdtDbtInd><RvslInd>true</RvslInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><Dt>2024-01-15</Dt></BookgDt>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">99.00</Amt><CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts><Cd>PDNG</Cd></Sts>
+        <BookgDt><Dt>2024-01-31</Dt></BookgDt>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>
+"#;
+
+    #[test]
+    fn test_parse_camt() {
+        let batch = parse_camt(Cursor::new(STATEMENT), &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.account_id.as_deref(), Some("NL91ABNA0417164300"));
+        assert_eq!(
+            batch.balances,
+            vec![Balance {
+                currency: "EUR".to_string(),
+                opening: Decimal::new(1000, 0),
+                closing: Decimal::new(1470, 0),
+            }]
+        );
+        let period = batch.metadata.period.unwrap();
+        assert_eq!(period.to_string(), "2024-01-01..2024-01-31");
+        assert_eq!(
+            batch.metadata.generated_at,
+            NaiveDate::from_ymd_opt(2024, 2, 1)
+                .unwrap()
+                .and_hms_opt(6, 0, 0)
+        );
+
+        let [coffee, salary, reversal, pending] = &batch.transactions[..] else {
+            panic!("expected three booked and one pending transaction");
+        };
+        assert_eq!(coffee.id, "B4A02");
+        assert_eq!(coffee.kind, TransactionKind::Debit);
+        assert_eq!(coffee.amount.amount, Decimal::new(2550, 2));
+        assert_eq!(coffee.description, "Coffee & cake");
+        assert_eq!(coffee.counterparty.as_deref(), Some("Coffee Shop B.V."));
+        assert_eq!(
+            coffee.executed_at,
+            NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+        );
+        assert_eq!(salary.id, "CAMT000002");
+        assert_eq!(salary.description, "Salary January");
+        assert_eq!(reversal.kind, TransactionKind::Reversal);
+        assert_eq!(reversal.signed_amount(), Decimal::new(-450, 2));
+        assert_eq!(salary.status, TransactionStatus::Booked);
+        assert_eq!(pending.status, TransactionStatus::Pending);
+        assert_eq!(pending.amount.amount, Decimal::new(9900, 2));
+    }
+
+    #[test]
+    fn test_unbalanced_camt() {
+        let statement = STATEMENT.replace(">1470.00<", ">1480.00<");
+        let error = parse_camt(Cursor::new(statement), &ParseOptions::default()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("= 1470.00, but the closing balance is 1480.00"));
+    }
+}
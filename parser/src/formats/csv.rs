@@ -1,14 +1,317 @@
-use crate::{Error, Money, Result, Transaction, TransactionBatch, TransactionKind};
-use chrono::NaiveDate;
+use super::profile::BankProfile;
+use super::{Field, ParseOptions, ParseReport, Quoting, WriteOptions, BOM};
+use crate::{
+    BatchMetadata, Error, Result, Transaction, TransactionBatch, TransactionKind, TransactionRef,
+    TransactionStatus,
+};
+use chrono::NaiveDateTime;
 use rust_decimal::Decimal;
-use std::io::{BufRead, BufReader, Read, Write};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Lines, Read, Write};
+use std::iter::Enumerate;
 use std::str::FromStr;
 
+/// Version of the CSV schema written by [`write_csv`].
+///
+/// Version 1 had ten columns; version 2 added `TaxAmount` and `TaxRate`,
+/// and version 3 `Status`.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Comment that declares the schema version of a file, e.g.
+/// `# schema-version: 3`, placed before the header row.
+const SCHEMA_COMMENT: &str = "schema-version:";
+
+/// Comments that declare the fields of [`BatchMetadata`], placed before the
+/// header row. The source's schema version has its own name, since
+/// `schema-version` is the version of the CSV layout.
+const GENERATED_AT_COMMENT: &str = "generated-at:";
+const SOURCE_SYSTEM_COMMENT: &str = "source-system:";
+const PERIOD_COMMENT: &str = "period:";
+const SOURCE_SCHEMA_COMMENT: &str = "source-schema-version:";
+
+/// A field of the current model that a CSV column maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Id,
+    PostedDate,
+    ExecutedDate,
+    Type,
+    Amount,
+    Currency,
+    Description,
+    Account,
+    Counterparty,
+    Category,
+    TaxAmount,
+    TaxRate,
+    Status,
+}
+
+const COLUMN_COUNT: usize = Column::Status as usize + 1;
+
+/// The header layout of one schema version: column names in file order.
+struct Layout {
+    version: u32,
+    columns: &'static [(&'static str, Column)],
+    /// Number of leading columns every row must have
+    required: usize,
+}
+
+const COLUMNS: [(&str, Column); COLUMN_COUNT] = [
+    ("TransactionId", Column::Id),
+    ("PostedDate", Column::PostedDate),
+    ("ExecutedDate", Column::ExecutedDate),
+    ("Type", Column::Type),
+    ("Amount", Column::Amount),
+    ("Currency", Column::Currency),
+    ("Description", Column::Description),
+    ("Account", Column::Account),
+    ("Counterparty", Column::Counterparty),
+    ("Category", Column::Category),
+    ("TaxAmount", Column::TaxAmount),
+    ("TaxRate", Column::TaxRate),
+    ("Status", Column::Status),
+];
+
+/// Every known layout, oldest first. When the schema changes, add a layout
+/// here instead of editing an old one, so that archived files keep parsing;
+/// renamed columns only need their own name list.
+const LAYOUTS: [Layout; 3] = [
+    Layout {
+        version: 1,
+        columns: COLUMNS.split_at(10).0,
+        required: 7,
+    },
+    Layout {
+        version: 2,
+        columns: COLUMNS.split_at(12).0,
+        required: 7,
+    },
+    Layout {
+        version: 3,
+        columns: &COLUMNS,
+        required: 7,
+    },
+];
+
+/// Where each model field is found in the rows of one file.
+struct ColumnMap {
+    positions: [Option<usize>; COLUMN_COUNT],
+    /// Number of fields every row must have to reach the required columns
+    required: usize,
+    /// Number of columns in the header
+    width: usize,
+    /// Positions and names of the columns no field maps to
+    extra: Vec<(usize, String)>,
+    delimiter: char,
+}
+
+/// Delimiters recognized when a file doesn't say which it uses.
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+/// Guesses the delimiter of a header row: the candidate occurring most often
+/// outside quotes, `,` when there is none.
+fn sniff_delimiter(header: &str) -> char {
+    let mut counts = [0; DELIMITERS.len()];
+    let mut in_quotes = false;
+    for ch in header.chars() {
+        if ch == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes {
+            if let Some(i) = DELIMITERS.iter().position(|d| *d == ch) {
+                counts[i] += 1;
+            }
+        }
+    }
+    // The first of equal counts wins, so ties go to `,`
+    let mut best = 0;
+    for (i, count) in counts.iter().enumerate() {
+        if *count > counts[best] {
+            best = i;
+        }
+    }
+    DELIMITERS[best]
+}
+
+/// Finds the layout of a header row.
+///
+/// Columns are matched by name, case-insensitively, so they may come in any
+/// order. With a declared version only that layout is tried; otherwise the
+/// newest layout whose required columns are all present wins. Columns the
+/// layout doesn't know are kept as extra columns, or rejected in strict mode.
+fn match_layout(
+    header: &str,
+    declared: Option<u32>,
+    layouts: &[Layout],
+    options: &ParseOptions,
+) -> Result<ColumnMap> {
+    if let Some(version) = declared {
+        if !layouts.iter().any(|layout| layout.version == version) {
+            return Err(Error::parse(
+                "CSV",
+                format!(
+                    "unsupported schema version {} (newest supported is {})",
+                    version, SCHEMA_VERSION
+                ),
+            ));
+        }
+    }
+
+    let delimiter = options
+        .delimiter
+        .unwrap_or_else(|| sniff_delimiter(header));
+    let names: Vec<String> = parse_delimited_fields(header, delimiter)
+        .iter()
+        .map(|name| unescape_csv_field(name).trim().to_string())
+        .collect();
+    let position = |expected: &str| names.iter().position(|name| name.eq_ignore_ascii_case(expected));
+    let missing = |layout: &Layout| {
+        layout.columns[..layout.required]
+            .iter()
+            .find(|(expected, _)| position(expected).is_none())
+            .map(|(expected, _)| *expected)
+    };
+
+    let mut candidates = layouts
+        .iter()
+        .rev()
+        .filter(|layout| declared.is_none_or(|version| layout.version == version));
+    let newest = candidates.clone().next();
+    let layout = candidates
+        .find(|layout| missing(layout).is_none())
+        .ok_or_else(|| {
+            let missing = newest.and_then(missing).unwrap_or_default();
+            Error::parse(
+                "CSV",
+                format!("invalid header: {} (missing column: {})", header, missing),
+            )
+        })?;
+
+    let mut positions = [None; COLUMN_COUNT];
+    let mut extra = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        let Some((expected, column)) = layout
+            .columns
+            .iter()
+            .find(|(expected, _)| name.eq_ignore_ascii_case(expected))
+        else {
+            if options.strict {
+                return Err(Error::parse("CSV", format!("unknown column: {}", name)));
+            }
+            extra.push((i, name.clone()));
+            continue;
+        };
+        if positions[*column as usize].replace(i).is_some() {
+            return Err(Error::parse(
+                "CSV",
+                format!("duplicate column: {}", expected),
+            ));
+        }
+    }
+    let required = layout.columns[..layout.required]
+        .iter()
+        .filter_map(|(_, column)| positions[*column as usize])
+        .max()
+        .map_or(0, |last| last + 1);
+
+    Ok(ColumnMap {
+        positions,
+        required,
+        width: names.len(),
+        extra,
+        delimiter,
+    })
+}
+
+fn parse_schema_version(version: &str) -> Result<u32> {
+    version.trim().parse().map_err(|_| {
+        Error::parse(
+            "CSV",
+            format!("invalid schema version: {}", version.trim()),
+        )
+    })
+}
+
+/// Reads a comment before the header row into `metadata` if it declares
+/// one of its fields; other comments are ignored.
+fn parse_metadata_comment(comment: &str, metadata: &mut BatchMetadata) -> Result<()> {
+    if let Some(value) = comment.strip_prefix(GENERATED_AT_COMMENT) {
+        let generated_at = NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S")
+            .map_err(|e| {
+                Error::parse("CSV", format!("invalid generation time: {}", e)).with_source(e)
+            })?;
+        metadata.generated_at = Some(generated_at);
+    } else if let Some(value) = comment.strip_prefix(SOURCE_SYSTEM_COMMENT) {
+        metadata.source_system = Some(value.trim().to_string());
+    } else if let Some(value) = comment.strip_prefix(PERIOD_COMMENT) {
+        let period = value.parse().map_err(|e: Error| e.prefixed("CSV", ""))?;
+        metadata.period = Some(period);
+    } else if let Some(value) = comment.strip_prefix(SOURCE_SCHEMA_COMMENT) {
+        metadata.schema_version = Some(value.trim().to_string());
+    }
+    Ok(())
+}
+
+/// Writes a comment for each field of `metadata` that is set, for
+/// [`write_csv_with`] to put before the header row.
+pub(crate) fn write_metadata_comments<W: Write>(
+    writer: &mut W,
+    metadata: &BatchMetadata,
+) -> Result<()> {
+    if let Some(generated_at) = metadata.generated_at {
+        let generated_at = generated_at.format("%Y-%m-%d %H:%M:%S");
+        writeln!(writer, "# {} {}", GENERATED_AT_COMMENT, generated_at)?;
+    }
+    if let Some(source_system) = &metadata.source_system {
+        let source_system = single_line(source_system);
+        writeln!(writer, "# {} {}", SOURCE_SYSTEM_COMMENT, source_system)?;
+    }
+    if let Some(period) = metadata.period {
+        writeln!(writer, "# {} {}", PERIOD_COMMENT, period)?;
+    }
+    if let Some(schema_version) = &metadata.schema_version {
+        let schema_version = single_line(schema_version);
+        writeln!(writer, "# {} {}", SOURCE_SCHEMA_COMMENT, schema_version)?;
+    }
+    Ok(())
+}
+
+/// `value` with line breaks replaced by spaces, so that it fits in a comment.
+fn single_line(value: &str) -> Cow<'_, str> {
+    match value.contains(['\n', '\r']) {
+        true => Cow::Owned(value.replace(['\n', '\r'], " ")),
+        false => Cow::Borrowed(value),
+    }
+}
+
+/// Writes the `# schema-version: N` comment that lets future releases
+/// recognize the layout of a file.
+pub fn write_schema_comment<W: Write>(writer: &mut W) -> Result<()> {
+    writeln!(writer, "# {} {}", SCHEMA_COMMENT, SCHEMA_VERSION)?;
+    Ok(())
+}
+
 /// Parses transaction data from CSV (Comma-Separated Values) format.
 ///
 /// The CSV format expects a header row followed by transaction records.
 /// Each line represents one transaction with comma-separated fields.
 ///
+/// Columns are found by the names in the header, case-insensitively, so they
+/// may come in any order. Columns this crate doesn't know, such as a bank
+/// reference or MCC code, are kept in [`Transaction::extra`] under their
+/// header name, and [`write_csv`] writes them back.
+///
+/// Fields may also be separated by `;`, tabs or `|`, as in exports made with
+/// European regional settings; the delimiter is sniffed from the header.
+///
+/// Headers of every earlier schema version are recognized and mapped to the
+/// current model, and `#` comment lines before the header are skipped. A
+/// `# schema-version: N` comment selects the layout of version `N`
+/// explicitly. `# generated-at:`, `# source-system:`, `# period:` and
+/// `# source-schema-version:` comments fill [`TransactionBatch::metadata`].
+/// A UTF-8 byte order mark, as Excel writes, is skipped.
+///
 /// # Arguments
 ///
 /// * `reader` - A reader containing CSV transaction data
@@ -22,36 +325,329 @@ use std::str::FromStr;
 ///
 /// This function will return an error if:
 /// - The file is empty
-/// - The header is invalid
+/// - The header is invalid, or declares an unknown schema version
 /// - Any line contains invalid data
 pub fn parse_csv<R: Read>(reader: R) -> Result<TransactionBatch> {
-    let buf_reader = BufReader::new(reader);
-    let mut lines = buf_reader.lines();
+    parse_csv_with(reader, &ParseOptions::default())
+}
+
+/// Parses transaction data from CSV format with the given options.
+///
+/// Behaves like [`parse_csv`], except that the fields are separated by
+/// [`ParseOptions::delimiter`] (sniffed from the header by default, like
+/// [`parse_csv`] does), posting dates may use one of
+/// [`ParseOptions::date_formats`], amounts and tax columns may be written
+/// with [`ParseOptions::decimal_separator`] and
+/// [`ParseOptions::thousands_separator`], parsing stops after
+/// [`ParseOptions::max_records`] transactions, and in
+/// [`ParseOptions::strict`] mode unknown columns and surplus fields are
+/// errors instead of being ignored.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing CSV transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the CSV is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error in the same cases as [`parse_csv`],
+/// and in strict mode if the header or a line has more columns than known.
+pub fn parse_csv_with<R: Read>(reader: R, options: &ParseOptions) -> Result<TransactionBatch> {
+    let mut records = CsvRecords::new(reader, options)?;
+    let metadata = records.metadata().clone();
+    let transactions = records.by_ref().collect::<Result<Vec<_>>>()?;
 
-    let header = lines
-        .next()
-        .ok_or_else(|| Error::parse("CSV", "empty file"))??;
+    Ok(TransactionBatch {
+        account_id: None,
+        transactions,
+        balances: Vec::new(),
+        metadata,
+    })
+}
 
-    if !header.starts_with("TransactionId") {
-        return Err(Error::parse("CSV", format!("invalid header: {}", header)));
+/// Parses CSV data, skipping malformed lines instead of failing.
+///
+/// Behaves like [`parse_csv_with`], except that a line with an invalid value
+/// is left out and its error added to [`ParseReport::errors`], and fields
+/// beyond the header, which are ignored outside strict mode, are reported in
+/// [`ParseReport::warnings`].
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing CSV transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns the lines that parsed along with what went wrong with the
+/// others, or an [`Error`] if the input can't be read at all, such as a
+/// missing or unknown header or an I/O error.
+pub fn parse_csv_report<R: Read>(reader: R, options: &ParseOptions) -> Result<ParseReport> {
+    let mut records = CsvRecords::new(reader, options)?;
+    records.decoder.collect_warnings();
+    let mut report = ParseReport {
+        metadata: records.metadata().clone(),
+        ..ParseReport::default()
+    };
+    for result in records.by_ref() {
+        match result {
+            Ok(tx) => report.transactions.push(tx),
+            Err(e @ Error::Parse { .. }) => report.errors.push(e),
+            Err(e) => return Err(e),
+        }
     }
+    report.warnings = records.decoder.take_warnings();
+    Ok(report)
+}
 
+/// Parses CSV data held in memory without copying its text fields.
+///
+/// Behaves like [`parse_csv_with`], except that the text fields of the
+/// transactions borrow from `input`, so parsing a large file doesn't
+/// allocate a string per field. Only fields with escaped quotes (`""`) and
+/// the names of extra columns are copied. [`TransactionRef::into_owned`]
+/// turns a transaction into a [`Transaction`] when it has to outlive
+/// `input`.
+///
+/// # Arguments
+///
+/// * `input` - CSV transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns the parsed transactions, or an [`Error`] in the same cases as
+/// [`parse_csv_with`].
+pub fn parse_csv_borrowed<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> Result<Vec<TransactionRef<'a>>> {
+    let mut decoder = CsvDecoder::new(options);
     let mut transactions = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        if decoder.has_header() && decoder.is_full() {
+            break;
+        }
+        if let Some(tx) = decoder.decode_borrowed(index, line)? {
+            transactions.push(tx);
+        }
+    }
+    if !decoder.has_header() {
+        return Err(Error::parse("CSV", "empty file"));
+    }
+    Ok(transactions)
+}
+
+/// The transactions of a CSV file, read one line at a time as
+/// [`parse_csv_with`] parses them.
+pub(crate) struct CsvRecords<R> {
+    lines: Enumerate<Lines<BufReader<R>>>,
+    decoder: CsvDecoder,
+}
+
+impl<R: Read> CsvRecords<R> {
+    /// Reads the header of a CSV file.
+    pub(crate) fn new(reader: R, options: &ParseOptions) -> Result<Self> {
+        let mut lines = BufReader::new(reader).lines().enumerate();
+        let mut decoder = CsvDecoder::new(options);
+        while !decoder.has_header() {
+            let (index, line) = lines
+                .next()
+                .ok_or_else(|| Error::parse("CSV", "empty file"))?;
+            decoder.decode(index, &line?)?;
+        }
+        Ok(CsvRecords { lines, decoder })
+    }
+
+    /// Names of the columns whose values go to [`Transaction::extra`].
+    pub(crate) fn extra_columns(&self) -> impl Iterator<Item = &str> {
+        self.decoder.extra_columns()
+    }
+
+    /// The batch metadata declared by the comments before the header.
+    pub(crate) fn metadata(&self) -> &BatchMetadata {
+        self.decoder.metadata()
+    }
+}
+
+impl<R: Read> Iterator for CsvRecords<R> {
+    type Item = Result<Transaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.decoder.is_full() {
+            let (index, line) = self.lines.next()?;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if let Some(result) = self.decoder.decode(index, &line).transpose() {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+/// Parses a CSV file fed to it one line at a time, wherever the lines come
+/// from.
+pub(crate) struct CsvDecoder {
+    /// The columns of the header, once it has been read
+    layout: Option<ColumnMap>,
+    /// The version of a schema comment before the header
+    declared_version: Option<u32>,
+    /// The batch metadata of the comments before the header
+    metadata: BatchMetadata,
+    options: ParseOptions,
+    /// Number of transactions parsed so far
+    count: usize,
+    /// What was ignored, when collected for a [`ParseReport`]
+    warnings: Option<Vec<String>>,
+}
+
+impl CsvDecoder {
+    pub(crate) fn new(options: &ParseOptions) -> Self {
+        CsvDecoder {
+            layout: None,
+            declared_version: None,
+            metadata: BatchMetadata::default(),
+            options: options.clone(),
+            count: 0,
+            warnings: None,
+        }
+    }
+
+    /// Collects warnings about ignored fields from now on, for
+    /// [`CsvDecoder::take_warnings`].
+    pub(crate) fn collect_warnings(&mut self) {
+        self.warnings.get_or_insert_with(Vec::new);
+    }
+
+    /// The warnings collected since the last call.
+    pub(crate) fn take_warnings(&mut self) -> Vec<String> {
+        self.warnings
+            .as_mut()
+            .map(core::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Parses line `index` (counted from 0) of the file.
+    ///
+    /// Returns the transaction of the line, or `None` for the comments and
+    /// header at the start and for blank lines.
+    pub(crate) fn decode(&mut self, index: usize, line: &str) -> Result<Option<Transaction>> {
+        let tx = self.decode_borrowed(index, line)?;
+        Ok(tx.map(TransactionRef::into_owned))
+    }
 
-    for (line_num, line_result) in lines.enumerate() {
-        let line = line_result?;
+    /// Parses line `index` of the file like [`CsvDecoder::decode`], borrowing
+    /// the fields of the transaction from `line`.
+    pub(crate) fn decode_borrowed<'a>(
+        &mut self,
+        index: usize,
+        line: &'a str,
+    ) -> Result<Option<TransactionRef<'a>>> {
+        let Some(layout) = &self.layout else {
+            let line = match index {
+                0 => line.strip_prefix(BOM).unwrap_or(line),
+                _ => line,
+            };
+            match line.trim().strip_prefix('#') {
+                Some(comment) => {
+                    let comment = comment.trim();
+                    if let Some(version) = comment.strip_prefix(SCHEMA_COMMENT) {
+                        self.declared_version = Some(parse_schema_version(version)?);
+                    } else {
+                        parse_metadata_comment(comment, &mut self.metadata)?;
+                    }
+                }
+                None => {
+                    let version = self.declared_version;
+                    self.layout = Some(match_layout(line, version, &LAYOUTS, &self.options)?);
+                }
+            }
+            return Ok(None);
+        };
 
         if line.trim().is_empty() {
-            continue;
+            return Ok(None);
         }
+        let warnings = self.warnings.as_mut();
+        let tx = parse_csv_line(line, index + 1, layout, &self.options, warnings)?;
+        self.count += 1;
+        self.options.limits.check_transactions(self.count)?;
+        self.options.limits.check_transaction(&tx)?;
+        Ok(Some(tx))
+    }
 
-        let transaction = parse_csv_line(&line, line_num + 2)?;
-        transactions.push(transaction);
+    /// Whether the header has been read.
+    pub(crate) fn has_header(&self) -> bool {
+        self.layout.is_some()
     }
 
-    Ok(TransactionBatch {
-        account_id: None,
-        transactions,
+    /// Whether [`ParseOptions::max_records`] transactions have been parsed.
+    pub(crate) fn is_full(&self) -> bool {
+        self.options.is_full(self.count)
+    }
+
+    /// The batch metadata declared by the comments before the header.
+    pub(crate) fn metadata(&self) -> &BatchMetadata {
+        &self.metadata
+    }
+
+    /// Names of the columns whose values go to [`Transaction::extra`].
+    pub(crate) fn extra_columns(&self) -> impl Iterator<Item = &str> {
+        let extra = self.layout.iter().flat_map(|layout| &layout.extra);
+        extra.map(|(_, name)| name.as_str())
+    }
+}
+
+/// Parses a bank's own CSV export, described by a [`BankProfile`].
+///
+/// Unlike [`parse_csv`], which only accepts this crate's header, the profile
+/// says where each field is (by header name or position), which delimiter,
+/// date format and decimal separator the bank uses, and how debits are told
+/// from credits. Rows without an ID column get sequential IDs.
+///
+/// The input is decoded from [`ParseOptions::encoding`], or from the
+/// encoding detected from its first bytes, as [`crate::parse_with`] does,
+/// so Windows-1251 exports such as Tinkoff's are read as they come. The
+/// dialect settings of the options (delimiter, date formats, separators)
+/// are ignored in favour of the profile's. Rows after the
+/// [`ParseOptions::max_records`]th transaction are not read, and
+/// [`ParseOptions::limits`] cap the input and the batch.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing the exported CSV data
+/// * `profile` - The bank's CSV dialect
+/// * `options` - How to decode the input, and record and size caps
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the CSV doesn't match the profile.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The encoding is unknown or the input isn't valid in it
+/// - A column named by the profile is missing from the header
+/// - The profile lacks a column its sign convention needs
+/// - Any line contains invalid data
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_csv_with_profile<R: Read>(
+    reader: R,
+    profile: &BankProfile,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| {
+        let decoded = super::decode(reader, options.encoding.as_deref())?;
+        super::profile::parse(decoded, profile, options)
     })
 }
 
@@ -59,7 +655,10 @@ pub fn parse_csv<R: Read>(reader: R) -> Result<TransactionBatch> {
 ///
 /// This function outputs a header row followed by transaction records,
 /// with each field separated by commas. Fields containing special characters
-/// are properly escaped.
+/// are properly escaped. The [`Transaction::extra`] columns of the batch
+/// follow the known ones, sorted by name. The fields of
+/// [`TransactionBatch::metadata`] that are set precede the header row as
+/// comments.
 ///
 /// # Arguments
 ///
@@ -74,162 +673,404 @@ pub fn parse_csv<R: Read>(reader: R) -> Result<TransactionBatch> {
 ///
 /// This function will return an error if any I/O operation fails.
 pub fn write_csv<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
-    writeln!(
-        writer,
-        "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description,Account,Counterparty,Category"
-    )?;
+    write_csv_with(batch, writer, &WriteOptions::default())
+}
 
+/// Every field, in the column order of [`write_csv`].
+const ALL_FIELDS: [Field; COLUMN_COUNT] = [
+    Field::Id,
+    Field::PostedDate,
+    Field::ExecutedDate,
+    Field::Kind,
+    Field::Amount,
+    Field::Currency,
+    Field::Description,
+    Field::Account,
+    Field::Counterparty,
+    Field::Category,
+    Field::TaxAmount,
+    Field::TaxRate,
+    Field::Status,
+];
+
+/// Writes transaction data in CSV format with the given options.
+///
+/// Behaves like [`write_csv`], except that [`WriteOptions::fields`] selects
+/// and orders the columns, which are separated by
+/// [`WriteOptions::delimiter`], posting dates use
+/// [`WriteOptions::date_format`] and [`WriteOptions::quoting`] decides which
+/// fields are quoted. [`WriteOptions::rfc4180`] makes the output follow
+/// RFC 4180 to the letter. Extra columns are only written when no field
+/// selection is given. The batch is written as given; [`crate::write_with`]
+/// applies the options that change the batch itself.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the CSV data to
+/// * `options` - How to write the batch
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_csv_with<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<()> {
+    let extra: BTreeSet<&str> = match options.fields {
+        Some(_) => BTreeSet::new(),
+        None => batch
+            .transactions
+            .iter()
+            .flat_map(|transaction| transaction.extra.keys().map(String::as_str))
+            .collect(),
+    };
+
+    write_metadata_comments(writer, &batch.metadata)?;
+    write_csv_header(writer, &extra, options)?;
     for transaction in &batch.transactions {
-        let executed_date = transaction
-            .executed_at
-            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-            .unwrap_or_default();
-
-        writeln!(
-            writer,
-            "{},{},{},{},{},{},{},{},{},{}",
-            escape_csv_field(&transaction.id),
-            transaction.posted_at.format("%Y-%m-%d"),
-            executed_date,
-            transaction.kind,
-            transaction.amount.amount,
-            escape_csv_field(&transaction.amount.currency),
-            escape_csv_field(&transaction.description),
-            escape_csv_field(transaction.account.as_deref().unwrap_or("")),
-            escape_csv_field(transaction.counterparty.as_deref().unwrap_or("")),
-            escape_csv_field(transaction.category.as_deref().unwrap_or(""))
-        )?;
+        write_csv_record(writer, transaction, &extra, options)?;
     }
 
     Ok(())
 }
 
-fn parse_csv_line(line: &str, line_num: usize) -> Result<Transaction> {
-    let fields = parse_csv_fields(line);
+/// Writes the header row of [`write_csv_with`], followed by the `extra`
+/// columns.
+pub(crate) fn write_csv_header<W: Write, S: AsRef<str>>(
+    writer: &mut W,
+    extra: &BTreeSet<S>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let fields = options.fields.as_deref().unwrap_or(&ALL_FIELDS);
+    let header: Vec<String> = fields
+        .iter()
+        .map(|field| quote_field(COLUMNS[column_of(*field) as usize].0, true, options))
+        .chain(
+            extra
+                .iter()
+                .map(|name| quote_field(name.as_ref(), true, options)),
+        )
+        .collect();
+    write_row(writer, &header, options)
+}
+
+/// Writes one row of [`write_csv_with`], followed by the `extra` columns.
+pub(crate) fn write_csv_record<W: Write, S: AsRef<str>>(
+    writer: &mut W,
+    transaction: &Transaction,
+    extra: &BTreeSet<S>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let fields = options.fields.as_deref().unwrap_or(&ALL_FIELDS);
+    let row: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let value = field_value(transaction, *field, options);
+            quote_field(&value, is_text(*field), options)
+        })
+        .chain(extra.iter().map(|name| {
+            let value = transaction
+                .extra
+                .get(name.as_ref())
+                .map_or("", String::as_str);
+            quote_field(value, true, options)
+        }))
+        .collect();
+    write_row(writer, &row, options)
+}
 
-    if fields.len() < 7 {
+/// The field delimiter and line ending of CSV output.
+fn separators(options: &WriteOptions) -> (char, &'static str) {
+    match options.rfc4180 {
+        true => (',', "\r\n"),
+        false => (options.delimiter.unwrap_or(','), "\n"),
+    }
+}
+
+/// Quotes a value of CSV output as the options require. In RFC 4180 mode
+/// `text` is always quoted, and only dates and numbers are left bare when
+/// they can be.
+fn quote_field(value: &str, text: bool, options: &WriteOptions) -> String {
+    match options.quoting {
+        Quoting::Necessary if !(options.rfc4180 && text) => {
+            escape_delimited_field(value, separators(options).0)
+        }
+        _ => format!("\"{}\"", value.replace('"', "\"\"")),
+    }
+}
+
+fn write_row<W: Write>(writer: &mut W, values: &[String], options: &WriteOptions) -> Result<()> {
+    let (delimiter, line_end) = separators(options);
+    write!(writer, "{}{}", values.join(&delimiter.to_string()), line_end)?;
+    Ok(())
+}
+
+fn column_of(field: Field) -> Column {
+    match field {
+        Field::Id => Column::Id,
+        Field::PostedDate => Column::PostedDate,
+        Field::ExecutedDate => Column::ExecutedDate,
+        Field::Kind => Column::Type,
+        Field::Amount => Column::Amount,
+        Field::Currency => Column::Currency,
+        Field::Description => Column::Description,
+        Field::Account => Column::Account,
+        Field::Counterparty => Column::Counterparty,
+        Field::Category => Column::Category,
+        Field::TaxAmount => Column::TaxAmount,
+        Field::TaxRate => Column::TaxRate,
+        Field::Status => Column::Status,
+    }
+}
+
+/// Whether a field holds free text rather than a date or number.
+fn is_text(field: Field) -> bool {
+    !matches!(
+        field,
+        Field::PostedDate
+            | Field::ExecutedDate
+            | Field::Amount
+            | Field::TaxAmount
+            | Field::TaxRate
+    )
+}
+
+/// The unescaped CSV value of a field.
+pub(crate) fn field_value(transaction: &Transaction, field: Field, options: &WriteOptions) -> String {
+    match field {
+        Field::Id => transaction.id.clone(),
+        Field::PostedDate => options.format_date(transaction.posted_at),
+        Field::ExecutedDate => transaction
+            .executed_at
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default(),
+        Field::Kind => transaction.kind.to_string(),
+        Field::Amount => transaction.amount.amount.to_string(),
+        Field::Currency => transaction.amount.currency.clone(),
+        Field::Description => transaction.description.clone(),
+        Field::Account => transaction.account.clone().unwrap_or_default(),
+        Field::Counterparty => transaction.counterparty.clone().unwrap_or_default(),
+        Field::Category => transaction.category.clone().unwrap_or_default(),
+        Field::TaxAmount => optional_decimal(transaction.tax_amount),
+        Field::TaxRate => optional_decimal(transaction.tax_rate),
+        Field::Status => transaction.status.to_string(),
+    }
+}
+
+fn parse_csv_line<'a>(
+    line: &'a str,
+    line_num: usize,
+    layout: &ColumnMap,
+    options: &ParseOptions,
+    warnings: Option<&mut Vec<String>>,
+) -> Result<TransactionRef<'a>> {
+    let fields = split_borrowed_fields(line, layout.delimiter);
+
+    if fields.len() < layout.required {
         return Err(Error::parse(
             "CSV",
             format!(
-                "line {}: insufficient fields (expected at least 7)",
-                line_num
+                "line {}: insufficient fields (expected at least {})",
+                line_num, layout.required
             ),
         ));
     }
-
-    let id = unescape_csv_field(&fields[0]).trim().to_string();
-
-    let posted_at = NaiveDate::parse_from_str(fields[1].trim(), "%Y-%m-%d").map_err(|e| {
-        Error::parse(
+    if options.strict && fields.len() > layout.width {
+        return Err(Error::parse(
             "CSV",
-            format!("line {}: invalid posted date: {}", line_num, e),
-        )
-    })?;
+            format!(
+                "line {}: too many fields (expected at most {})",
+                line_num, layout.width
+            ),
+        ));
+    }
 
-    let executed_at = if fields[2].trim().is_empty() {
-        None
-    } else {
-        Some(
-            chrono::NaiveDateTime::parse_from_str(fields[2].trim(), "%Y-%m-%d %H:%M:%S").map_err(
-                |e| {
-                    Error::parse(
-                        "CSV",
-                        format!("line {}: invalid executed date: {}", line_num, e),
-                    )
-                },
-            )?,
-        )
+    let field = |column: Column| -> Cow<'a, str> {
+        layout.positions[column as usize]
+            .and_then(|i| fields.get(i))
+            .cloned()
+            .unwrap_or_default()
     };
+    let optional = |column: Column| Some(field(column)).filter(|val| !val.is_empty());
 
-    let kind = TransactionKind::from_str(fields[3].trim())
-        .map_err(|e| Error::parse("CSV", format!("line {}: invalid transaction type: {}", line_num, e)))?;
-
-    let amount_value = Decimal::from_str(fields[4].trim())
-        .map_err(|e| Error::parse("CSV", format!("line {}: invalid amount: {}", line_num, e)))?;
+    let id = field(Column::Id);
 
-    let currency = unescape_csv_field(&fields[5]).trim().to_string();
+    let posted_at = options
+        .parse_date(&field(Column::PostedDate))
+        .map_err(|e| {
+            Error::parse(
+                "CSV",
+                format!("line {}: invalid posted date: {}", line_num, e),
+            )
+            .with_source(e)
+        })?;
 
-    let amount = Money {
-        amount: amount_value,
-        currency,
+    let executed_at = match optional(Column::ExecutedDate) {
+        None => None,
+        Some(executed) => Some(
+            chrono::NaiveDateTime::parse_from_str(&executed, "%Y-%m-%d %H:%M:%S").map_err(|e| {
+                Error::parse(
+                    "CSV",
+                    format!("line {}: invalid executed date: {}", line_num, e),
+                )
+                .with_source(e)
+            })?,
+        ),
     };
 
-    let description = unescape_csv_field(&fields[6]).trim().to_string();
+    let kind = TransactionKind::from_str(&field(Column::Type))
+        .map_err(|e| Error::parse("CSV", format!("line {}: invalid transaction type: {}", line_num, e)))?;
 
-    let account = if fields.len() > 7 {
-        let val = unescape_csv_field(&fields[7]).trim().to_string();
-        if val.is_empty() { None } else { Some(val) }
-    } else {
-        None
-    };
+    let amount_value = options.parse_amount(&field(Column::Amount)).map_err(|e| {
+        Error::parse("CSV", format!("line {}: invalid amount: {}", line_num, e)).with_source(e)
+    })?;
 
-    let counterparty = if fields.len() > 8 {
-        let val = unescape_csv_field(&fields[8]).trim().to_string();
-        if val.is_empty() { None } else { Some(val) }
-    } else {
-        None
-    };
+    let status = optional(Column::Status)
+        .map(|status| TransactionStatus::from_str(&status))
+        .transpose()
+        .map_err(|e| Error::parse("CSV", format!("line {}: invalid status: {}", line_num, e)))?
+        .unwrap_or_default();
 
-    let category = if fields.len() > 9 {
-        let val = unescape_csv_field(&fields[9]).trim().to_string();
-        if val.is_empty() { None } else { Some(val) }
-    } else {
-        None
-    };
+    let tax_amount =
+        parse_optional_decimal(optional(Column::TaxAmount), "tax amount", line_num, options)?;
+    let tax_rate =
+        parse_optional_decimal(optional(Column::TaxRate), "tax rate", line_num, options)?;
+    let extra = layout
+        .extra
+        .iter()
+        .filter_map(|(i, name)| {
+            let value = fields.get(*i)?;
+            (!value.is_empty()).then(|| (Cow::Owned(name.clone()), value.clone()))
+        })
+        .collect();
+
+    if let Some(warnings) = warnings.filter(|_| fields.len() > layout.width) {
+        warnings.push(format!(
+            "line {}: ignored {} fields beyond the header",
+            line_num,
+            fields.len() - layout.width
+        ));
+    }
 
-    Ok(Transaction {
+    Ok(TransactionRef {
         id,
         posted_at,
         executed_at,
         kind,
-        amount,
-        description,
-        account,
-        counterparty,
-        category,
+        status,
+        amount: amount_value,
+        currency: field(Column::Currency),
+        description: field(Column::Description),
+        account: optional(Column::Account),
+        counterparty: optional(Column::Counterparty),
+        category: optional(Column::Category),
+        tax_amount,
+        tax_rate,
+        extra,
+    })
+}
+
+/// Parses an optional decimal column; missing and empty columns are `None`.
+fn parse_optional_decimal(
+    field: Option<Cow<'_, str>>,
+    name: &str,
+    line_num: usize,
+    options: &ParseOptions,
+) -> Result<Option<Decimal>> {
+    let Some(field) = field else {
+        return Ok(None);
+    };
+    options.parse_amount(&field).map(Some).map_err(|e| {
+        Error::parse("CSV", format!("line {}: invalid {}: {}", line_num, name, e)).with_source(e)
     })
 }
 
+fn optional_decimal(value: Option<Decimal>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
 /// Parses a CSV line into fields, properly handling quoted fields.
-fn parse_csv_fields(line: &str) -> Vec<String> {
+pub(crate) fn parse_csv_fields(line: &str) -> Vec<String> {
+    parse_delimited_fields(line, ',')
+}
+
+/// Parses a line of `delimiter`-separated fields, properly handling quoted
+/// fields.
+pub(crate) fn parse_delimited_fields(line: &str, delimiter: char) -> Vec<String> {
     let mut fields = Vec::new();
     let mut current_field = String::new();
     let mut in_quotes = false;
-    let mut chars = line.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        match ch {
-            '"' => {
-                if in_quotes {
-                    // Check if this is an escaped quote (two quotes in a row)
-                    if chars.peek() == Some(&'"') {
-                        current_field.push('"');
-                        chars.next(); // Skip the second quote
-                    } else {
-                        // End of quoted field
-                        in_quotes = false;
-                    }
-                } else {
-                    // Start of quoted field
-                    in_quotes = true;
-                }
-            }
-            ',' if !in_quotes => {
-                // Field delimiter outside quotes
-                fields.push(current_field.clone());
-                current_field.clear();
-            }
-            _ => {
-                current_field.push(ch);
+    // Start of the text not yet copied to the field; runs of ordinary
+    // characters are copied at once rather than char by char
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, ch)) = chars.next() {
+        if ch == '"' {
+            current_field.push_str(&line[start..i]);
+            start = i + 1;
+            if in_quotes && chars.peek().is_some_and(|&(_, next)| next == '"') {
+                // An escaped quote: the second one starts the next run
+                chars.next();
+            } else {
+                // Start or end of quoted field
+                in_quotes = !in_quotes;
             }
+        } else if ch == delimiter && !in_quotes {
+            // Field delimiter outside quotes
+            current_field.push_str(&line[start..i]);
+            fields.push(core::mem::take(&mut current_field));
+            start = i + ch.len_utf8();
         }
     }
 
     // Push the last field
+    current_field.push_str(&line[start..]);
     fields.push(current_field);
     fields
 }
 
+/// Splits a line like [`parse_delimited_fields`] does and unescapes the
+/// fields, borrowing them from `line` unless they contain escaped quotes.
+fn split_borrowed_fields(line: &str, delimiter: char) -> Vec<Cow<'_, str>> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, ch) in line.char_indices() {
+        if ch == '"' {
+            // An escaped quote toggles twice, staying in the quoted field
+            in_quotes = !in_quotes;
+        } else if ch == delimiter && !in_quotes {
+            fields.push(borrowed_field(&line[start..i], delimiter));
+            start = i + ch.len_utf8();
+        }
+    }
+    fields.push(borrowed_field(&line[start..], delimiter));
+    fields
+}
+
+/// Unescapes one field of [`split_borrowed_fields`].
+fn borrowed_field(field: &str, delimiter: char) -> Cow<'_, str> {
+    if !field.contains('"') {
+        return Cow::Borrowed(field.trim());
+    }
+    let trimmed = field.trim();
+    let quoted = trimmed.strip_prefix('"').and_then(|f| f.strip_suffix('"'));
+    match quoted {
+        Some(inner) if !inner.contains('"') => Cow::Borrowed(inner.trim()),
+        _ => {
+            let unquoted = parse_delimited_fields(field, delimiter).concat();
+            Cow::Owned(unescape_csv_field(&unquoted).trim().to_string())
+        }
+    }
+}
+
 /// Unescapes a CSV field by removing surrounding quotes if present.
 fn unescape_csv_field(field: &str) -> String {
     let trimmed = field.trim();
@@ -241,8 +1082,14 @@ fn unescape_csv_field(field: &str) -> String {
 }
 
 /// Escapes a CSV field by quoting it if necessary and escaping internal quotes.
-fn escape_csv_field(field: &str) -> String {
-    if field.contains(',') || field.contains('"') || field.contains('\n') {
+pub(crate) fn escape_csv_field(field: &str) -> String {
+    escape_delimited_field(field, ',')
+}
+
+/// Escapes a field of `delimiter`-separated values by quoting it if
+/// necessary and escaping internal quotes.
+pub(crate) fn escape_delimited_field(field: &str, delimiter: char) -> String {
+    if field.contains(delimiter) || field.contains(['"', '\n', '\r']) {
         format!("\"{}\"", field.replace('"', "\"\""))
     } else {
         field.to_string()
@@ -252,6 +1099,9 @@ fn escape_csv_field(field: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Format, Limits, Money};
+    use chrono::NaiveDate;
+    use std::collections::BTreeMap;
     use std::io::Cursor;
 
     #[test]
@@ -274,6 +1124,7 @@ mod tests {
             posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
             executed_at: None,
             kind: TransactionKind::Credit,
+            status: TransactionStatus::Booked,
             amount: Money {
                 amount: Decimal::from_str("1000.50").unwrap(),
                 currency: "USD".to_string(),
@@ -282,6 +1133,9 @@ mod tests {
             account: Some("ACC123".to_string()),
             counterparty: None,
             category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
         });
 
         let mut buffer = Vec::new();
@@ -292,6 +1146,50 @@ mod tests {
         assert!(output.contains("TX001"));
     }
 
+    #[test]
+    fn test_write_csv_with_options() {
+        let mut batch = TransactionBatch::default();
+        batch.transactions.push(Transaction {
+            id: "TX001".to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str("12.345").unwrap(),
+                currency: "EUR".to_string(),
+            },
+            description: "Say \"hi\"".to_string(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        });
+        let options = WriteOptions {
+            date_format: Some("%d/%m/%Y".to_string()),
+            quoting: Quoting::Always,
+            decimal_places: Some(2),
+            fields: Some(vec![
+                Field::PostedDate,
+                Field::Id,
+                Field::Amount,
+                Field::Description,
+            ]),
+            ..WriteOptions::default()
+        };
+
+        let mut buffer = Vec::new();
+        crate::write_with(&batch, &mut buffer, crate::Format::Csv, &options).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "\"PostedDate\",\"TransactionId\",\"Amount\",\"Description\"\n\
+             \"05/01/2024\",\"TX001\",\"12.35\",\"Say \"\"hi\"\"\"\n"
+        );
+    }
+
     #[test]
     fn test_csv_roundtrip() {
         // Create test data with special characters
@@ -306,6 +1204,7 @@ mod tests {
                             .unwrap(),
                     ),
                     kind: TransactionKind::Credit,
+                    status: TransactionStatus::Booked,
                     amount: Money {
                         amount: Decimal::from_str("1000.50").unwrap(),
                         currency: "USD".to_string(),
@@ -314,12 +1213,16 @@ mod tests {
                     account: Some("ACC123".to_string()),
                     counterparty: Some("Employer, Inc".to_string()), // comma in counterparty
                     category: Some("Salary".to_string()),
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
                 },
                 Transaction {
                     id: "TX002".to_string(),
                     posted_at: NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
                     executed_at: None,
                     kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
                     amount: Money {
                         amount: Decimal::from_str("150.50").unwrap(),
                         currency: "USD".to_string(),
@@ -328,8 +1231,13 @@ mod tests {
                     account: Some("ACC123".to_string()),
                     counterparty: Some("Store".to_string()),
                     category: Some("Food".to_string()),
+                    tax_amount: Some(Decimal::from_str("25.08").unwrap()),
+                    tax_rate: Some(Decimal::from(20)),
+                    extra: BTreeMap::new(),
                 },
             ],
+            balances: Vec::new(),
+            metadata: Default::default(),
         };
 
         // Write to CSV
@@ -353,6 +1261,395 @@ mod tests {
             assert_eq!(parsed.account, original.account);
             assert_eq!(parsed.counterparty, original.counterparty);
             assert_eq!(parsed.category, original.category);
+            assert_eq!(parsed.tax_amount, original.tax_amount);
+            assert_eq!(parsed.tax_rate, original.tax_rate);
         }
     }
+
+    #[test]
+    fn test_schema_versions() {
+        let data = "# exported by ypbank\n\
+                    # schema-version: 1\n\
+                    TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX001,2024-01-15,,Debit,10.00,EUR,Coffee\n";
+        let batch = parse_csv(Cursor::new(data)).unwrap();
+        assert_eq!(batch.transactions[0].description, "Coffee");
+
+        let newer = "# schema-version: 4\nTransactionId\n";
+        let error = parse_csv(Cursor::new(newer)).unwrap_err();
+        assert!(error.to_string().contains("unsupported schema version 4"));
+
+        let mut output = Vec::new();
+        write_schema_comment(&mut output).unwrap();
+        write_csv(&batch, &mut output).unwrap();
+        assert!(output.starts_with(b"# schema-version: 3\nTransactionId,"));
+        assert_eq!(parse_csv(Cursor::new(output)).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_legacy_layout_is_mapped() {
+        // A layout with renamed and reordered columns, as a future schema
+        // change would register it.
+        let legacy = [Layout {
+            version: 0,
+            columns: &[
+                ("Id", Column::Id),
+                ("Date", Column::PostedDate),
+                ("Amount", Column::Amount),
+                ("Currency", Column::Currency),
+                ("Type", Column::Type),
+                ("Memo", Column::Description),
+            ],
+            required: 6,
+        }];
+
+        let options = ParseOptions::default();
+        let layout =
+            match_layout("Id,Date,Amount,Currency,Type,Memo", None, &legacy, &options).unwrap();
+        let tx =
+            parse_csv_line("TX9,2023-12-31,5.50,USD,Credit,Refund", 2, &layout, &options, None)
+                .unwrap()
+                .into_owned();
+        assert_eq!(tx.id, "TX9");
+        assert_eq!(tx.kind, TransactionKind::Credit);
+        assert_eq!(tx.amount.amount.to_string(), "5.50");
+        assert_eq!(tx.description, "Refund");
+        assert_eq!(tx.executed_at, None);
+
+        assert!(match_layout("Id,Date", None, &legacy, &options).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_with_options() {
+        let data = "TransactionId;PostedDate;ExecutedDate;Type;Amount;Currency;Description\n\
+                    TX1;15.01.2024;;Debit;9.99;EUR;Coffee;x\n\
+                    TX2;2024-01-16;;Credit;5;EUR;Refund;y\n\
+                    TX3;not a date;;Credit;5;EUR;Unread;z\n";
+        let mut options = ParseOptions {
+            delimiter: Some(';'),
+            date_formats: vec!["%d.%m.%Y".to_string()],
+            max_records: Some(2),
+            ..ParseOptions::default()
+        };
+        let batch = parse_csv_with(Cursor::new(data), &options).unwrap();
+        assert_eq!(batch.transactions.len(), 2);
+        assert_eq!(
+            batch.transactions[0].posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+
+        options.strict = true;
+        let err = parse_csv_with(Cursor::new(data), &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("line 2: too many fields (expected at most 7)"));
+    }
+
+    #[test]
+    fn test_delimiters() {
+        assert_eq!(sniff_delimiter("TransactionId;PostedDate;\"a,b\""), ';');
+        assert_eq!(sniff_delimiter("TransactionId"), ',');
+
+        let data = "TransactionId\tPostedDate\tExecutedDate\tType\tAmount\tCurrency\tDescription\n\
+                    TX1\t2024-01-15\t\tDebit\t9.99\tEUR\tCoffee, large\n";
+        let batch = parse_csv(Cursor::new(data)).unwrap();
+        assert_eq!(batch.transactions[0].description, "Coffee, large");
+
+        let options = WriteOptions {
+            delimiter: Some(';'),
+            fields: Some(vec![Field::Id, Field::Description]),
+            ..WriteOptions::default()
+        };
+        let mut buffer = Vec::new();
+        write_csv_with(&batch, &mut buffer, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "TransactionId;Description\nTX1;Coffee, large\n"
+        );
+    }
+
+    #[test]
+    fn test_columns_are_mapped_by_name() {
+        let data = "Amount,Currency,Type,BankRef,transactionid,PostedDate,Description,ExecutedDate\n\
+                    9.99,EUR,Debit,R-1,TX1,2024-01-15,Coffee,\n";
+        let batch = parse_csv(Cursor::new(data)).unwrap();
+        let tx = &batch.transactions[0];
+        assert_eq!(tx.id, "TX1");
+        assert_eq!(tx.amount.amount, Decimal::new(999, 2));
+        assert_eq!(tx.kind, TransactionKind::Debit);
+        assert_eq!(tx.description, "Coffee");
+
+        let err = parse_csv(Cursor::new("Amount,Currency,Type\n")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("(missing column: TransactionId)"));
+        let err = parse_csv(Cursor::new(data.replace("BankRef", "Amount"))).unwrap_err();
+        assert!(err.to_string().contains("duplicate column: Amount"));
+    }
+
+    #[test]
+    fn test_extra_columns_roundtrip() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description,MCC,BankRef\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Coffee,5814,R-1\n\
+                    TX2,2024-01-16,,Credit,5,EUR,Refund,,\"R,2\"\n";
+        let batch = parse_csv(Cursor::new(data)).unwrap();
+        assert_eq!(batch.transactions[0].extra["MCC"], "5814");
+        assert_eq!(batch.transactions[1].extra.get("MCC"), None);
+        assert_eq!(batch.transactions[1].extra["BankRef"], "R,2");
+
+        let mut buffer = Vec::new();
+        write_csv(&batch, &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.starts_with("TransactionId,"));
+        assert!(output.contains(",Status,BankRef,MCC\n"));
+        assert!(output.ends_with(",,Booked,\"R,2\",\n"));
+        let reparsed = parse_csv(Cursor::new(output)).unwrap();
+        assert_eq!(reparsed.transactions[0].extra, batch.transactions[0].extra);
+        assert_eq!(reparsed.transactions[1].extra, batch.transactions[1].extra);
+    }
+
+    #[test]
+    fn test_locale_amounts() {
+        let data = "TransactionId;PostedDate;ExecutedDate;Type;Amount;Currency;Description;TaxAmount\n\
+                    TX1;2024-01-15;;Debit;1 234,56;EUR;Rent;205,76\n";
+        let err = parse_csv(Cursor::new(data)).unwrap_err();
+        assert!(err.to_string().contains("line 2: invalid amount"));
+
+        let options = ParseOptions {
+            decimal_separator: Some(','),
+            thousands_separator: Some(' '),
+            ..ParseOptions::default()
+        };
+        let batch = parse_csv_with(Cursor::new(data), &options).unwrap();
+        assert_eq!(batch.transactions[0].amount.amount, Decimal::new(123456, 2));
+        assert_eq!(batch.transactions[0].tax_amount, Some(Decimal::new(20576, 2)));
+
+        let options = ParseOptions {
+            thousands_separator: Some(','),
+            ..ParseOptions::default()
+        };
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Credit,\"1,234.56\",USD,Salary\n";
+        let batch = parse_csv_with(Cursor::new(data), &options).unwrap();
+        assert_eq!(batch.transactions[0].amount.amount, Decimal::new(123456, 2));
+    }
+
+    #[test]
+    fn test_encoding_is_detected() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,500,RUB,Оплата аренды\n";
+        let options = ParseOptions::default();
+
+        let (cp1251, _, _) = encoding_rs::WINDOWS_1251.encode(data);
+        assert!(parse_csv(&cp1251[..]).is_err());
+        let batch = crate::parse_with(&cp1251[..], crate::Format::Csv, &options).unwrap();
+        assert_eq!(batch.transactions[0].description, "Оплата аренды");
+
+        let utf16: Vec<u8> = data.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let with_bom = [&[0xFF, 0xFE][..], &utf16].concat();
+        for input in [&utf16, &with_bom] {
+            let batch = crate::parse_with(&input[..], crate::Format::Csv, &options).unwrap();
+            assert_eq!(batch.transactions[0].id, "TX1");
+            assert_eq!(batch.transactions[0].description, "Оплата аренды");
+        }
+    }
+
+    #[test]
+    fn test_bom() {
+        let data = "\u{feff}TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Café\n";
+        let batch = parse_csv(Cursor::new(data)).unwrap();
+        assert_eq!(batch.transactions[0].id, "TX1");
+
+        let options = WriteOptions {
+            bom: true,
+            schema_version: true,
+            ..WriteOptions::default()
+        };
+        let mut buffer = Vec::new();
+        crate::write_with(&batch, &mut buffer, crate::Format::Csv, &options).unwrap();
+        assert!(buffer.starts_with(b"\xEF\xBB\xBF# schema-version: 3\n"));
+        let reparsed = parse_csv(Cursor::new(buffer)).unwrap();
+        assert_eq!(reparsed.transactions[0].description, "Café");
+    }
+
+    #[test]
+    fn test_rfc4180() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description,Account\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,\"Say \"\"hi\"\"\",\n";
+        let batch = parse_csv(Cursor::new(data)).unwrap();
+        let mut options = WriteOptions {
+            rfc4180: true,
+            delimiter: Some(';'),
+            fields: Some(vec![
+                Field::Id,
+                Field::PostedDate,
+                Field::Amount,
+                Field::Description,
+                Field::Account,
+            ]),
+            ..WriteOptions::default()
+        };
+        let mut buffer = Vec::new();
+        write_csv_with(&batch, &mut buffer, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "\"TransactionId\",\"PostedDate\",\"Amount\",\"Description\",\"Account\"\r\n\
+             \"TX1\",2024-01-15,9.99,\"Say \"\"hi\"\"\",\"\"\r\n"
+        );
+
+        options.fields = None;
+        let mut buffer = Vec::new();
+        write_csv_with(&batch, &mut buffer, &options).unwrap();
+        let reparsed = parse_csv(Cursor::new(buffer)).unwrap();
+        assert_eq!(reparsed.transactions[0].description, "Say \"hi\"");
+        assert_eq!(reparsed.transactions[0].account, None);
+    }
+
+    #[test]
+    fn test_parse_csv_borrowed() {
+        let data = "\u{feff}TransactionId;PostedDate;ExecutedDate;Type;Amount;Currency;\
+                    Description;MCC\n\
+                    TX1; 2024-01-15 ;;Debit;9.99;EUR; \"Coffee; to go\" ;5814\n\
+                    \n\
+                    TX2;2024-01-16;;Credit;5;EUR;\"Say \"\"hi\"\"\";\n";
+        let transactions = parse_csv_borrowed(data, &ParseOptions::default()).unwrap();
+        assert_eq!(transactions[0].description, "Coffee; to go");
+        assert!(matches!(transactions[0].id, Cow::Borrowed("TX1")));
+        assert!(matches!(transactions[0].description, Cow::Borrowed(_)));
+        assert!(matches!(transactions[1].description, Cow::Owned(_)));
+
+        let owned: Vec<Transaction> = transactions
+            .into_iter()
+            .map(TransactionRef::into_owned)
+            .collect();
+        assert_eq!(owned, parse_csv(Cursor::new(data)).unwrap().transactions);
+        assert_eq!(TransactionRef::from(&owned[1]).into_owned(), owned[1]);
+
+        assert!(parse_csv_borrowed("", &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_parse_delimited_fields() {
+        assert_eq!(parse_csv_fields("a,,b"), ["a", "", "b"]);
+        assert_eq!(
+            parse_csv_fields(r#"TX1,"Coffee, to go"," Say ""hi"" ",""#),
+            ["TX1", "Coffee, to go", r#" Say "hi" "#, ""]
+        );
+        let fields = parse_delimited_fields("Miete;\"a;b\";€", ';');
+        assert_eq!(fields, ["Miete", "a;b", "€"]);
+        let fields = parse_delimited_fields("x¦\"\"\"\"¦y", '¦');
+        assert_eq!(fields, ["x", "\"", "y"]);
+    }
+
+    #[test]
+    fn test_parse_csv_report() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n\
+                    Total,,,,9.99,,\n\
+                    TX2,2024-01-16,,Debit,4.50,EUR,Croissant,note\n";
+        let options = ParseOptions::default();
+        let report = parse_csv_report(Cursor::new(data), &options).unwrap();
+        let ids: Vec<_> = report.transactions.iter().map(|tx| &tx.id).collect();
+        assert_eq!(ids, ["TX1", "TX2"]);
+        assert_eq!(report.errors.len(), 1);
+        let error = report.errors[0].to_string();
+        assert!(error.contains("line 3: invalid posted date"));
+        let warning = "line 4: ignored 1 fields beyond the header";
+        assert_eq!(report.warnings, [warning]);
+        assert!(!report.is_clean());
+
+        // A file that can't be read at all is still an error
+        assert!(parse_csv_report(Cursor::new("Date,Amount\n"), &options).is_err());
+    }
+
+    #[test]
+    fn test_error_source() {
+        use core::error::Error as _;
+
+        let header = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n";
+        let parse = |row: &str| parse_csv(Cursor::new(format!("{}{}", header, row))).unwrap_err();
+
+        let error = parse("TX1,2024-01-15,,Debit,abc,EUR,Coffee\n");
+        assert!(error.source().unwrap().is::<rust_decimal::Error>());
+        let error = parse("TX1,2024-13-01,,Debit,1.00,EUR,Coffee\n");
+        assert!(error.source().unwrap().is::<chrono::ParseError>());
+        let message = error.to_string();
+        assert!(message.contains("invalid posted date: input is out of range"));
+    }
+
+    #[test]
+    fn test_iso_currencies() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n\
+                    TX2,2024-01-16,,Debit,4.50,UDS,Croissant\n\
+                    TX3,2024-01-17,,Credit,100,PTS,Bonus\n";
+        let mut options = ParseOptions::default();
+        assert!(crate::parse_with(Cursor::new(data), Format::Csv, &options).is_ok());
+
+        options.iso_currencies = true;
+        options.custom_currencies = vec!["PTS".to_string()];
+        let err = crate::parse_with(Cursor::new(data), Format::Csv, &options).unwrap_err();
+        assert_eq!(err.to_string(), "unknown currency UDS in transaction TX2");
+        let mut output = Vec::new();
+        let converted = crate::convert_with(
+            Cursor::new(data),
+            Format::Csv,
+            &mut output,
+            Format::Json,
+            &options,
+            &WriteOptions::default(),
+        );
+        assert!(matches!(converted, Err(Error::UnknownCurrency { .. })));
+
+        let report = crate::parse_report(Cursor::new(data), Format::Csv, &options).unwrap();
+        let ids: Vec<_> = report.transactions.iter().map(|tx| &tx.id).collect();
+        assert_eq!(ids, ["TX1", "TX3"]);
+        assert_eq!(report.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_limits() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n\
+                    TX2,2024-01-16,,Debit,4.50,EUR,Croissant and coffee\n";
+        let parse = |limits: Limits| {
+            let options = ParseOptions {
+                limits,
+                ..ParseOptions::default()
+            };
+            parse_csv_with(Cursor::new(data), &options)
+        };
+        assert_eq!(parse(Limits::untrusted()).unwrap().transactions.len(), 2);
+        for limits in [
+            Limits {
+                max_transactions: Some(1),
+                ..Limits::default()
+            },
+            Limits {
+                max_string_len: Some(10),
+                ..Limits::default()
+            },
+        ] {
+            assert!(matches!(parse(limits), Err(Error::LimitExceeded(_))));
+        }
+
+        let options = ParseOptions {
+            limits: Limits {
+                max_input_bytes: Some(64),
+                ..Limits::default()
+            },
+            ..ParseOptions::default()
+        };
+        let mut output = Vec::new();
+        let converted = crate::convert_with(
+            Cursor::new(data),
+            Format::Csv,
+            &mut output,
+            Format::Json,
+            &options,
+            &WriteOptions::default(),
+        );
+        assert!(matches!(converted, Err(Error::LimitExceeded(_))));
+    }
 }
@@ -0,0 +1,530 @@
+use super::ParseOptions;
+use crate::{
+    Error, Money, Result, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+
+pub use super::Field;
+
+/// How the characters of a column are converted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ColumnType {
+    /// Free text, trimmed of padding
+    Text,
+    /// A date, or a date and time for [`Field::ExecutedDate`], in a chrono
+    /// `format` such as `%Y%m%d`
+    Date {
+        /// The chrono format string
+        format: String,
+    },
+    /// A decimal number with an optional leading or trailing sign. With
+    /// `implied_decimals`, the column holds digits only and the last
+    /// `implied_decimals` of them are the fraction (`0000012345` with 2 is
+    /// `123.45`), as COBOL `PIC 9(8)V99` fields do.
+    Decimal {
+        /// Number of implied fraction digits
+        #[serde(default)]
+        implied_decimals: Option<u32>,
+    },
+    /// A transaction kind written as one of two codes; kinds other than
+    /// debits and credits are written with the code of their direction
+    Kind {
+        /// Code for [`TransactionKind::Debit`]
+        debit: String,
+        /// Code for [`TransactionKind::Credit`]
+        credit: String,
+    },
+}
+
+/// One column of a fixed-width record.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Column {
+    /// The field the column holds
+    pub field: Field,
+    /// Position of the first character, counted from 0
+    pub offset: usize,
+    /// Number of characters
+    pub width: usize,
+    /// How the column is converted
+    #[serde(flatten)]
+    pub column_type: ColumnType,
+}
+
+/// The record layout of a fixed-width file.
+///
+/// Layouts can be built in code or deserialized from a configuration file;
+/// [`Layout::from_yaml`] also accepts JSON.
+///
+/// # Examples
+///
+/// ```
+/// use parser::formats::fixed_width::Layout;
+///
+/// let layout = Layout::from_yaml(
+///     r#"
+/// currency: RUB
+/// columns:
+///   - { field: id, offset: 0, width: 8, type: text }
+///   - { field: posted_date, offset: 8, width: 8, type: date, format: "%Y%m%d" }
+///   - { field: kind, offset: 16, width: 1, type: kind, debit: D, credit: C }
+///   - { field: amount, offset: 17, width: 10, type: decimal, implied_decimals: 2 }
+///   - { field: description, offset: 27, width: 30, type: text }
+/// "#,
+/// )
+/// .unwrap();
+/// assert_eq!(layout.record_width(), 57);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    /// The columns of a record, in any order
+    pub columns: Vec<Column>,
+    /// Currency for every transaction when the layout has no currency column
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Number of leading lines to skip, such as header records
+    #[serde(default)]
+    pub skip_lines: usize,
+}
+
+impl Layout {
+    /// Reads a layout from YAML (or JSON) and checks it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text doesn't describe a layout or the layout
+    /// is invalid (see [`Layout::validate`]).
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let layout: Layout = serde_yaml::from_str(yaml)
+            .map_err(|e| Error::parse("FixedWidth", format!("invalid layout: {}", e)))?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    /// Checks that the layout can produce transactions: the ID, posted date,
+    /// kind and amount have a column, there is a currency column or a
+    /// default currency, and every column's type suits its field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error::Parse`] describing the first problem found.
+    pub fn validate(&self) -> Result<()> {
+        for field in [Field::Id, Field::PostedDate, Field::Kind, Field::Amount] {
+            if self.column(field).is_none() {
+                return Err(layout_error(format!("no column for {:?}", field)));
+            }
+        }
+        if self.column(Field::Currency).is_none() && self.currency.is_none() {
+            return Err(layout_error(
+                "no currency column and no default currency".to_string(),
+            ));
+        }
+
+        for column in &self.columns {
+            let valid = match column.field {
+                Field::PostedDate | Field::ExecutedDate => {
+                    matches!(column.column_type, ColumnType::Date { .. })
+                }
+                Field::Kind => matches!(column.column_type, ColumnType::Kind { .. }),
+                Field::Amount | Field::TaxAmount | Field::TaxRate => {
+                    matches!(column.column_type, ColumnType::Decimal { .. })
+                }
+                _ => column.column_type == ColumnType::Text,
+            };
+            if !valid {
+                return Err(layout_error(format!(
+                    "column {:?} can't have type {:?}",
+                    column.field, column.column_type
+                )));
+            }
+            if column.width == 0 {
+                return Err(layout_error(format!(
+                    "column {:?} has no width",
+                    column.field
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the length of a record: the end of the rightmost column.
+    pub fn record_width(&self) -> usize {
+        self.columns
+            .iter()
+            .map(|column| column.offset + column.width)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn column(&self, field: Field) -> Option<&Column> {
+        self.columns.iter().find(|column| column.field == field)
+    }
+}
+
+/// Parses fixed-width records, one per line, according to `layout`.
+///
+/// Columns are cut out by character position and trimmed of padding; a
+/// line shorter than the layout leaves its trailing columns empty. Empty
+/// optional columns become `None`. Blank lines are skipped.
+///
+/// Lines after the [`ParseOptions::max_records`]th record are not read,
+/// and [`ParseOptions::limits`] cap the input and the batch.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing fixed-width records
+/// * `layout` - Where each field is in a record and how to convert it
+/// * `options` - Record and size caps, and currency checks
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the layout is invalid or a record cannot be converted.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The layout is invalid (see [`Layout::validate`])
+/// - A date, kind code or number doesn't match its column type
+/// - A required field is empty
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_fixed_width<R: Read>(
+    reader: R,
+    layout: &Layout,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    layout.validate()?;
+    options.parse_limited(reader, |reader| parse_records(reader, layout, options))
+}
+
+fn parse_records<R: Read>(
+    reader: R,
+    layout: &Layout,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let mut batch = TransactionBatch::default();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        if options.has_max_records(batch.transactions.len()) {
+            break;
+        }
+        let line = line?;
+        if index < layout.skip_lines || line.trim().is_empty() {
+            continue;
+        }
+        let transaction = parse_record(&line, layout).map_err(|message| {
+            Error::parse("FixedWidth", format!("line {}: {}", index + 1, message))
+        })?;
+        batch.transactions.push(transaction);
+    }
+    Ok(batch)
+}
+
+fn parse_record(line: &str, layout: &Layout) -> std::result::Result<Transaction, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let raw = |field: Field| -> Option<(&Column, String)> {
+        let column = layout.column(field)?;
+        let start = column.offset.min(chars.len());
+        let end = (column.offset + column.width).min(chars.len());
+        let value: String = chars[start..end]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        Some((column, value)).filter(|(_, value)| !value.is_empty())
+    };
+    let text = |field: Field| raw(field).map(|(_, value)| value);
+    let required = |field: Field| raw(field).ok_or_else(|| format!("missing {:?}", field));
+    let decimal = |field: Field| {
+        raw(field)
+            .map(|(column, value)| {
+                parse_decimal(&value, &column.column_type)
+                    .ok_or_else(|| format!("invalid {:?}: {}", field, value))
+            })
+            .transpose()
+    };
+
+    let (column, value) = required(Field::PostedDate)?;
+    let ColumnType::Date { format } = &column.column_type else {
+        unreachable!("validated layout")
+    };
+    let posted_at = NaiveDate::parse_from_str(&value, format)
+        .map_err(|e| format!("invalid PostedDate {}: {}", value, e))?;
+
+    let executed_at = raw(Field::ExecutedDate)
+        .map(|(column, value)| {
+            let ColumnType::Date { format } = &column.column_type else {
+                unreachable!("validated layout")
+            };
+            NaiveDateTime::parse_from_str(&value, format)
+                .map_err(|e| format!("invalid ExecutedDate {}: {}", value, e))
+        })
+        .transpose()?;
+
+    let (column, value) = required(Field::Kind)?;
+    let ColumnType::Kind { debit, credit } = &column.column_type else {
+        unreachable!("validated layout")
+    };
+    let kind = if value.eq_ignore_ascii_case(debit.trim()) {
+        TransactionKind::Debit
+    } else if value.eq_ignore_ascii_case(credit.trim()) {
+        TransactionKind::Credit
+    } else {
+        return Err(format!("invalid Kind: {}", value));
+    };
+
+    let status = text(Field::Status)
+        .map(|value| {
+            TransactionStatus::from_str(&value).map_err(|_| format!("invalid Status: {}", value))
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let amount = decimal(Field::Amount)?.ok_or_else(|| "missing Amount".to_string())?;
+    let currency = text(Field::Currency)
+        .or_else(|| layout.currency.clone())
+        .ok_or_else(|| "missing Currency".to_string())?;
+
+    Ok(Transaction {
+        id: required(Field::Id)?.1,
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money { amount, currency },
+        description: text(Field::Description).unwrap_or_default(),
+        account: text(Field::Account),
+        counterparty: text(Field::Counterparty),
+        category: text(Field::Category),
+        tax_amount: decimal(Field::TaxAmount)?,
+        tax_rate: decimal(Field::TaxRate)?,
+        extra: BTreeMap::new(),
+    })
+}
+
+fn parse_decimal(value: &str, column_type: &ColumnType) -> Option<Decimal> {
+    let (negative, digits) =
+        if let Some(rest) = value.strip_prefix('-').or_else(|| value.strip_suffix('-')) {
+            (true, rest.trim())
+        } else {
+            (
+                false,
+                value
+                    .strip_prefix('+')
+                    .or_else(|| value.strip_suffix('+'))
+                    .unwrap_or(value)
+                    .trim(),
+            )
+        };
+
+    let mut decimal = match column_type {
+        ColumnType::Decimal {
+            implied_decimals: Some(scale),
+        } => {
+            if !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let mut decimal = Decimal::from_str(digits).ok()?;
+            decimal.set_scale(*scale).ok()?;
+            decimal
+        }
+        _ => Decimal::from_str(digits).ok()?,
+    };
+    decimal.set_sign_negative(negative);
+    Some(decimal)
+}
+
+/// Writes transactions as fixed-width records according to `layout`.
+///
+/// Every record is [`Layout::record_width`] characters long, gaps between
+/// columns being filled with spaces. Text and dates are left-aligned and
+/// padded with spaces; numbers are right-aligned, and numbers with implied
+/// decimals are zero-filled digits with a leading `-` when negative.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the records to
+/// * `layout` - Where each field goes in a record and how to convert it
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The layout is invalid (see [`Layout::validate`])
+/// - A value is wider than its column, or has more fraction digits than an
+///   implied-decimals column holds
+/// - Any I/O operation fails
+pub fn write_fixed_width<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    layout: &Layout,
+) -> Result<()> {
+    layout.validate()?;
+
+    for tx in &batch.transactions {
+        let mut record = vec![' '; layout.record_width()];
+        for column in &layout.columns {
+            let value = format_field(tx, column).map_err(|message| {
+                Error::parse("FixedWidth", format!("transaction {}: {}", tx.id, message))
+            })?;
+            let Some(value) = value else {
+                continue;
+            };
+            let width = value.chars().count();
+            if width > column.width {
+                return Err(Error::parse(
+                    "FixedWidth",
+                    format!(
+                        "transaction {}: {:?} value {} is wider than {} characters",
+                        tx.id, column.field, value, column.width
+                    ),
+                ));
+            }
+            let start = match column.column_type {
+                ColumnType::Decimal { .. } => column.offset + column.width - width,
+                _ => column.offset,
+            };
+            for (slot, c) in record[start..].iter_mut().zip(value.chars()) {
+                *slot = c;
+            }
+        }
+        writeln!(
+            writer,
+            "{}",
+            record.into_iter().collect::<String>().trim_end()
+        )?;
+    }
+    Ok(())
+}
+
+fn format_field(tx: &Transaction, column: &Column) -> std::result::Result<Option<String>, String> {
+    let decimal =
+        |value: Option<Decimal>| value.map(|value| format_decimal(value, column)).transpose();
+    let value = match (column.field, &column.column_type) {
+        (Field::PostedDate, ColumnType::Date { format }) => {
+            Some(tx.posted_at.format(format).to_string())
+        }
+        (Field::ExecutedDate, ColumnType::Date { format }) => {
+            tx.executed_at.map(|dt| dt.format(format).to_string())
+        }
+        (Field::Kind, ColumnType::Kind { debit, credit }) => Some(if tx.is_credit() {
+            credit.clone()
+        } else {
+            debit.clone()
+        }),
+        (Field::Amount, _) => decimal(Some(tx.unsigned_amount()))?,
+        (Field::TaxAmount, _) => decimal(tx.tax_amount)?,
+        (Field::TaxRate, _) => decimal(tx.tax_rate)?,
+        (Field::Id, _) => Some(tx.id.clone()),
+        (Field::Status, _) => Some(tx.status.to_string()),
+        (Field::Currency, _) => Some(tx.amount.currency.clone()),
+        (Field::Description, _) => Some(tx.description.clone()),
+        (Field::Account, _) => tx.account.clone(),
+        (Field::Counterparty, _) => tx.counterparty.clone(),
+        (Field::Category, _) => tx.category.clone(),
+        _ => None,
+    };
+    Ok(value)
+}
+
+fn format_decimal(value: Decimal, column: &Column) -> std::result::Result<String, String> {
+    let ColumnType::Decimal {
+        implied_decimals: Some(scale),
+    } = column.column_type
+    else {
+        return Ok(value.to_string());
+    };
+
+    let mut scaled = value;
+    scaled.rescale(scale);
+    if scaled != value {
+        return Err(format!(
+            "{:?} value {} has more than {} decimal places",
+            column.field, value, scale
+        ));
+    }
+    let digits = scaled.mantissa().unsigned_abs().to_string();
+    Ok(if scaled.is_sign_negative() {
+        format!("-{:0>1$}", digits, column.width.saturating_sub(1))
+    } else {
+        format!("{:0>1$}", digits, column.width)
+    })
+}
+
+fn layout_error(message: String) -> Error {
+    Error::parse("FixedWidth", format!("invalid layout: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn layout() -> Layout {
+        Layout::from_yaml(
+            r#"
+columns:
+  - { field: id, offset: 0, width: 6, type: text }
+  - { field: posted_date, offset: 6, width: 8, type: date, format: "%Y%m%d" }
+  - { field: kind, offset: 14, width: 1, type: kind, debit: D, credit: C }
+  - { field: amount, offset: 15, width: 10, type: decimal, implied_decimals: 2 }
+  - { field: currency, offset: 25, width: 3, type: text }
+  - { field: description, offset: 28, width: 20, type: text }
+skip_lines: 1
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fixed_width_roundtrip() {
+        let data = "HEADER 20240131\n\
+                    TX000120240115D0000120050RUBPayment\n\
+                    TX000220240116C-000000005USD\n";
+        let batch =
+            parse_fixed_width(Cursor::new(data), &layout(), &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.transactions.len(), 2);
+        let tx = &batch.transactions[0];
+        assert_eq!(tx.id, "TX0001");
+        assert_eq!(tx.posted_at, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+        assert_eq!(tx.kind, TransactionKind::Debit);
+        assert_eq!(tx.amount.amount, Decimal::new(120050, 2));
+        assert_eq!(tx.description, "Payment");
+        assert_eq!(batch.transactions[1].amount.amount, Decimal::new(-5, 2));
+        assert_eq!(batch.transactions[1].description, "");
+
+        let mut output = Vec::new();
+        write_fixed_width(&batch, &mut output, &layout()).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "TX000120240115D0000120050RUBPayment\nTX000220240116C-000000005USD\n"
+        );
+    }
+
+    #[test]
+    fn test_fixed_width_errors() {
+        let err = Layout::from_yaml("columns: []").unwrap_err();
+        assert!(err.to_string().contains("no column for Id"));
+
+        let data = "HEADER\nTX000120240135D0000120050RUB\n";
+        let err =
+            parse_fixed_width(Cursor::new(data), &layout(), &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("line 2: invalid PostedDate"));
+
+        let data = "HEADER\nTX000120240115D0000120050RUB\n";
+        let mut batch =
+            parse_fixed_width(Cursor::new(data), &layout(), &ParseOptions::default()).unwrap();
+        batch.transactions[0].amount.amount = Decimal::new(1, 3);
+        let err = write_fixed_width(&batch, &mut Vec::new(), &layout()).unwrap_err();
+        assert!(err.to_string().contains("more than 2 decimal places"));
+    }
+}
@@ -0,0 +1,121 @@
+use super::csv::escape_csv_field;
+use crate::{Result, TransactionBatch};
+use std::io::Write;
+
+pub use super::posting::AccountMapping;
+
+/// Header row, named after GnuCash's import column types so each column can
+/// be assigned by its heading.
+const HEADER: &str = "Date,Num,Description,Notes,Account,Deposit,Withdrawal,Transfer Account";
+
+/// Writes transactions as a CSV file for GnuCash's "Import Transactions from
+/// CSV" assistant.
+///
+/// Each row holds one transaction: the `YYYY-MM-DD` posting date (choose the
+/// `y-m-d` date format when importing), the transaction ID as `Num`, the
+/// description, the counterparty as `Notes`, the asset account, the amount
+/// in either the `Deposit` or the `Withdrawal` column, and the category's
+/// account from `mapping` as `Transfer Account`. Account names use GnuCash's
+/// `:` separator, so `Expenses:Groceries` is found or created under
+/// `Expenses`.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the CSV data to
+/// * `mapping` - Which accounts to book the transactions to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`](crate::Error) if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_csv<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    mapping: &AccountMapping,
+) -> Result<()> {
+    writeln!(writer, "{}", HEADER)?;
+
+    for tx in &batch.transactions {
+        let amount = tx.unsigned_amount().to_string();
+        let (deposit, withdrawal) = if tx.is_credit() {
+            (amount.as_str(), "")
+        } else {
+            ("", amount.as_str())
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            tx.posted_at.format("%Y-%m-%d"),
+            escape_csv_field(&tx.id),
+            escape_csv_field(&tx.description),
+            escape_csv_field(tx.counterparty.as_deref().unwrap_or("")),
+            escape_csv_field(&mapping.asset_account),
+            deposit,
+            withdrawal,
+            escape_csv_field(mapping.account_for(tx)),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, Transaction, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_gnucash_csv() {
+        let tx = |id: &str, kind, category: Option<&str>| Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(120050, 2),
+                currency: "RUB".to_string(),
+            },
+            description: "Invoice 7, January".to_string(),
+            account: None,
+            counterparty: None,
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx("TX1", TransactionKind::Credit, None),
+                tx("TX2", TransactionKind::Debit, Some("Rent")),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let mut mapping = AccountMapping {
+            asset_account: "Assets:Current Assets:Checking".to_string(),
+            ..AccountMapping::default()
+        };
+        mapping
+            .by_category
+            .insert("Rent".to_string(), "Expenses:Rent".to_string());
+
+        let mut output = Vec::new();
+        write_csv(&batch, &mut output, &mapping).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Date,Num,Description,Notes,Account,Deposit,Withdrawal,Transfer Account\n\
+             2024-01-15,TX1,\"Invoice 7, January\",,Assets:Current Assets:Checking,1200.50,,Income:Uncategorized\n\
+             2024-01-15,TX2,\"Invoice 7, January\",,Assets:Current Assets:Checking,,1200.50,Expenses:Rent\n"
+        );
+    }
+}
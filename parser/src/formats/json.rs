@@ -0,0 +1,502 @@
+#[cfg(feature = "serde")]
+use crate::ParseReport;
+use crate::{
+    Balance, BatchMetadata, Error, Money, Period, Result, Transaction, TransactionBatch,
+    TransactionKind, TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// A batch as it appears in a JSON (or YAML) document.
+///
+/// Field names are camelCase, as in the WebAssembly bindings. Dates and
+/// amounts are strings so that no precision is lost in JSON numbers; optional
+/// fields are omitted when absent.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    balances: Vec<BalanceDocument>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<MetadataDocument>,
+    transactions: Vec<TransactionDocument>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MetadataDocument {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generated_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    period: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    schema_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BalanceDocument {
+    currency: String,
+    opening: String,
+    closing: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TransactionDocument {
+    id: String,
+    posted_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    executed_at: Option<String>,
+    kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    amount: String,
+    currency: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    counterparty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tax_amount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tax_rate: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extra: BTreeMap<String, String>,
+}
+
+/// A [`ParseReport`] as serialized: the batch document with the errors and
+/// warnings of the records that didn't parse cleanly.
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+pub(crate) struct ReportDocument<'a> {
+    #[serde(flatten)]
+    batch: BatchDocument,
+    errors: &'a [Error],
+    warnings: &'a [String],
+}
+
+#[cfg(feature = "serde")]
+impl<'a> ReportDocument<'a> {
+    /// Builds the document for a report.
+    pub(crate) fn new(report: &'a ParseReport) -> Self {
+        let batch = BatchDocument {
+            transactions: report
+                .transactions
+                .iter()
+                .map(TransactionDocument::from_transaction)
+                .collect(),
+            ..BatchDocument::header(
+                report.account_id.as_deref(),
+                &report.balances,
+                &report.metadata,
+            )
+        };
+        ReportDocument {
+            batch,
+            errors: &report.errors,
+            warnings: &report.warnings,
+        }
+    }
+}
+
+impl BatchDocument {
+    /// Builds the document for a batch.
+    pub(crate) fn from_batch(batch: &TransactionBatch) -> Self {
+        BatchDocument {
+            transactions: batch
+                .transactions
+                .iter()
+                .map(TransactionDocument::from_transaction)
+                .collect(),
+            ..Self::header(
+                batch.account_id.as_deref(),
+                &batch.balances,
+                &batch.metadata,
+            )
+        }
+    }
+
+    /// Builds the document of a batch without its transactions.
+    pub(crate) fn header(
+        account_id: Option<&str>,
+        balances: &[Balance],
+        metadata: &BatchMetadata,
+    ) -> Self {
+        BatchDocument {
+            account_id: account_id.map(str::to_string),
+            balances: balances
+                .iter()
+                .map(|balance| BalanceDocument {
+                    currency: balance.currency.clone(),
+                    opening: balance.opening.to_string(),
+                    closing: balance.closing.to_string(),
+                })
+                .collect(),
+            metadata: (!metadata.is_empty()).then(|| MetadataDocument {
+                generated_at: metadata
+                    .generated_at
+                    .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+                source_system: metadata.source_system.clone(),
+                period: metadata.period.map(|period| period.to_string()),
+                schema_version: metadata.schema_version.clone(),
+            }),
+            transactions: Vec::new(),
+        }
+    }
+
+    /// Converts the document into a batch, reporting errors as `format`.
+    pub(crate) fn into_batch(self, format: &'static str) -> Result<TransactionBatch> {
+        let balances = self
+            .balances
+            .into_iter()
+            .map(|balance| {
+                Ok(Balance {
+                    opening: parse_decimal(&balance.opening, "opening balance", format)?,
+                    closing: parse_decimal(&balance.closing, "closing balance", format)?,
+                    currency: balance.currency,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let metadata = match self.metadata {
+            Some(metadata) => metadata.into_metadata(format)?,
+            None => BatchMetadata::default(),
+        };
+
+        let transactions = self
+            .transactions
+            .into_iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                tx.into_transaction(format)
+                    .map_err(|e| e.prefixed(format, &format!("transaction {}: ", index + 1)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(TransactionBatch {
+            account_id: self.account_id,
+            transactions,
+            balances,
+            metadata,
+        })
+    }
+}
+
+impl MetadataDocument {
+    fn into_metadata(self, format: &'static str) -> Result<BatchMetadata> {
+        let generated_at = self
+            .generated_at
+            .map(|s| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+            .transpose()
+            .map_err(|e| {
+                Error::parse(format, format!("invalid generatedAt: {}", e)).with_source(e)
+            })?;
+        let period = self
+            .period
+            .map(|s| s.parse::<Period>())
+            .transpose()
+            .map_err(|e| e.prefixed(format, "metadata: "))?;
+
+        Ok(BatchMetadata {
+            generated_at,
+            source_system: self.source_system,
+            period,
+            schema_version: self.schema_version,
+        })
+    }
+}
+
+impl TransactionDocument {
+    fn into_transaction(self, format: &'static str) -> Result<Transaction> {
+        let posted_at = NaiveDate::parse_from_str(&self.posted_at, "%Y-%m-%d")
+            .map_err(|e| Error::parse(format, format!("invalid postedAt: {}", e)).with_source(e))?;
+
+        let executed_at = self
+            .executed_at
+            .map(|s| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+            .transpose()
+            .map_err(|e| {
+                Error::parse(format, format!("invalid executedAt: {}", e)).with_source(e)
+            })?;
+
+        let kind = TransactionKind::from_str(&self.kind)
+            .map_err(|e| Error::parse(format, format!("invalid kind: {}", e)))?;
+
+        let status = self
+            .status
+            .map(|s| TransactionStatus::from_str(&s))
+            .transpose()
+            .map_err(|e| Error::parse(format, format!("invalid status: {}", e)))?
+            .unwrap_or_default();
+
+        Ok(Transaction {
+            id: self.id,
+            posted_at,
+            executed_at,
+            kind,
+            status,
+            amount: Money {
+                amount: parse_decimal(&self.amount, "amount", format)?,
+                currency: self.currency,
+            },
+            description: self.description,
+            account: self.account,
+            counterparty: self.counterparty,
+            category: self.category,
+            tax_amount: self
+                .tax_amount
+                .map(|v| parse_decimal(&v, "taxAmount", format))
+                .transpose()?,
+            tax_rate: self
+                .tax_rate
+                .map(|v| parse_decimal(&v, "taxRate", format))
+                .transpose()?,
+            extra: self.extra,
+        })
+    }
+}
+
+impl TransactionDocument {
+    /// Builds the document for a transaction.
+    pub(crate) fn from_transaction(tx: &Transaction) -> Self {
+        TransactionDocument {
+            id: tx.id.clone(),
+            posted_at: tx.posted_at.format("%Y-%m-%d").to_string(),
+            executed_at: tx
+                .executed_at
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+            kind: tx.kind.to_string(),
+            status: (!tx.status.is_booked()).then(|| tx.status.to_string()),
+            amount: tx.amount.amount.to_string(),
+            currency: tx.amount.currency.clone(),
+            description: tx.description.clone(),
+            account: tx.account.clone(),
+            counterparty: tx.counterparty.clone(),
+            category: tx.category.clone(),
+            tax_amount: tx.tax_amount.map(|v| v.to_string()),
+            tax_rate: tx.tax_rate.map(|v| v.to_string()),
+            extra: tx.extra.clone(),
+        }
+    }
+}
+
+fn parse_decimal(value: &str, name: &str, format: &'static str) -> Result<Decimal> {
+    Decimal::from_str(value)
+        .map_err(|e| Error::parse(format, format!("invalid {}: {}", name, e)).with_source(e))
+}
+
+/// Parses transaction data from a JSON document.
+///
+/// The document is an object with an optional `accountId`, optional
+/// `balances` and a `transactions` array, using the same field names and
+/// string-encoded dates and amounts as [`write_json`] produces.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing a JSON document
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the document is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not valid JSON or lacks required fields
+/// - A date, kind, status or amount cannot be parsed
+pub fn parse_json<R: Read>(reader: R) -> Result<TransactionBatch> {
+    let document: BatchDocument =
+        serde_json::from_reader(reader).map_err(|e| Error::parse("JSON", e.to_string()))?;
+    document.into_batch("JSON")
+}
+
+/// Writes transaction data as a pretty-printed JSON document.
+///
+/// Every field of every transaction is written, with dates and amounts as
+/// strings; optional fields that are absent are omitted.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the JSON document to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_json<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, &BatchDocument::from_batch(batch))
+        .map_err(|e| Error::parse("JSON", e.to_string()))?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Writes the start of the document of [`write_json`], up to the opening
+/// bracket of the transactions.
+pub(crate) fn write_json_header<W: Write>(
+    writer: &mut W,
+    account_id: Option<&str>,
+    balances: &[Balance],
+    metadata: &BatchMetadata,
+) -> Result<()> {
+    let header = BatchDocument::header(account_id, balances, metadata);
+    let header =
+        serde_json::to_string_pretty(&header).map_err(|e| Error::parse("JSON", e.to_string()))?;
+    // The transactions come last, as an empty array
+    let header = header
+        .strip_suffix("]\n}")
+        .expect("the transactions end the document");
+    writer.write_all(header.as_bytes())?;
+    Ok(())
+}
+
+/// Writes one transaction of [`write_json`], after a comma unless it is the
+/// `first`.
+pub(crate) fn write_json_transaction<W: Write>(
+    writer: &mut W,
+    tx: &Transaction,
+    first: bool,
+) -> Result<()> {
+    let document = serde_json::to_string_pretty(&TransactionDocument::from_transaction(tx))
+        .map_err(|e| Error::parse("JSON", e.to_string()))?;
+    if !first {
+        write!(writer, ",")?;
+    }
+    // Strings can't span lines, so every line is indented to the depth of
+    // the array
+    for line in document.lines() {
+        write!(writer, "\n    {}", line)?;
+    }
+    Ok(())
+}
+
+/// Writes the end of the document of [`write_json`], after `count`
+/// transactions.
+pub(crate) fn write_json_footer<W: Write>(writer: &mut W, count: usize) -> Result<()> {
+    if count > 0 {
+        write!(writer, "\n  ")?;
+    }
+    writeln!(writer, "]\n}}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_json_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![
+                Transaction {
+                    id: "TX001".to_string(),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                    executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                        .unwrap()
+                        .and_hms_opt(10, 30, 0),
+                    kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
+                    amount: Money {
+                        amount: Decimal::new(120050, 2),
+                        currency: "RUB".to_string(),
+                    },
+                    description: "Оплата \"услуг\"".to_string(),
+                    account: Some("40817810".to_string()),
+                    counterparty: Some("ООО Ромашка".to_string()),
+                    category: Some("Services".to_string()),
+                    tax_amount: Some(Decimal::new(20008, 2)),
+                    tax_rate: Some(Decimal::new(20, 0)),
+                    extra: BTreeMap::from([("MCC".to_string(), "7399".to_string())]),
+                },
+                Transaction {
+                    id: "TX002".to_string(),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
+                    executed_at: None,
+                    kind: TransactionKind::Credit,
+                    status: TransactionStatus::Pending,
+                    amount: Money {
+                        amount: Decimal::new(5000, 2),
+                        currency: "USD".to_string(),
+                    },
+                    description: String::new(),
+                    account: None,
+                    counterparty: None,
+                    category: None,
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
+                },
+            ],
+            balances: vec![Balance {
+                currency: "USD".to_string(),
+                opening: Decimal::new(1000, 2),
+                closing: Decimal::new(6000, 2),
+            }],
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_json(&batch, &mut output).unwrap();
+        let json = String::from_utf8(output.clone()).unwrap();
+        assert!(json.contains("\"postedAt\": \"2024-01-15\""));
+        assert!(!json.contains("taxRate\": null"));
+        assert_eq!(json.matches("\"status\": \"Pending\"").count(), 1);
+        assert_eq!(json.matches("\"extra\"").count(), 1);
+
+        let parsed = parse_json(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+    }
+
+    #[test]
+    fn test_parse_json_errors() {
+        let err = parse_json(Cursor::new("{\"transactions\": [")).unwrap_err();
+        assert!(matches!(err, Error::Parse { format: "JSON", .. }));
+
+        let data = r#"{"transactions": [{"id": "TX1", "postedAt": "2024-13-01",
+            "kind": "Debit", "amount": "1", "currency": "USD"}]}"#;
+        let err = parse_json(Cursor::new(data)).unwrap_err();
+        assert!(err.to_string().contains("transaction 1: invalid postedAt"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_report() {
+        let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n\
+                    TX2,2024-13-01,,Debit,1.00,EUR,Tea\n";
+        let options = crate::ParseOptions::default();
+        let report = crate::formats::csv::parse_csv_report(Cursor::new(data), &options).unwrap();
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["transactions"][0]["postedAt"], "2024-01-15");
+        assert_eq!(json["errors"][0]["kind"], "parse");
+        assert_eq!(json["errors"][0]["format"], "CSV");
+        let message = json["errors"][0]["message"].as_str().unwrap();
+        assert!(message.starts_with("parse error in CSV: line 3"));
+        assert_eq!(json["warnings"], serde_json::json!([]));
+
+        let error = Error::LimitExceeded("input longer than 10 bytes".to_string());
+        let json = serde_json::to_string(&error).unwrap();
+        let expected =
+            r#"{"kind":"limit_exceeded","message":"limit exceeded: input longer than 10 bytes"}"#;
+        assert_eq!(json, expected);
+    }
+}
@@ -0,0 +1,161 @@
+use super::posting::{journal, Entry};
+use crate::{Result, TransactionBatch};
+use std::io::Write;
+
+pub use super::posting::AccountMapping;
+
+/// Writes transactions as a ledger-cli / hledger journal.
+///
+/// Each record becomes a cleared (`*`) entry dated `YYYY/MM/DD` with the
+/// counterparty as payee (or the description when there is none), the
+/// description and transaction ID as comments, and two postings booked with
+/// the same [`AccountMapping`] as the Beancount export. Non-zero opening
+/// balances are booked against [`AccountMapping::opening_balances`] first,
+/// and closing balances end the journal as balance assertions
+/// (`= 964.50 USD`) on the last posting date.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the journal to
+/// * `mapping` - Which accounts to book the transactions to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`](crate::Error) if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    mapping: &AccountMapping,
+) -> Result<()> {
+    let Some(journal) = journal(batch, mapping) else {
+        return Ok(());
+    };
+
+    if let Some(account_id) = &batch.account_id {
+        writeln!(writer, "; Account {}", account_id)?;
+        writeln!(writer)?;
+    }
+
+    for (index, entry) in journal.entries.iter().enumerate() {
+        if index > 0 {
+            writeln!(writer)?;
+        }
+        write_entry(writer, entry)?;
+    }
+
+    for balance in &batch.balances {
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "{} * Closing balance",
+            journal.last.format("%Y/%m/%d")
+        )?;
+        writeln!(
+            writer,
+            "    {:<40}  {:>14} {} = {} {}",
+            mapping.asset_account,
+            0,
+            balance.currency,
+            balance.closing,
+            balance.currency
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &Entry) -> Result<()> {
+    let date = entry.date.format("%Y/%m/%d");
+    match entry.payee {
+        Some(payee) => {
+            writeln!(writer, "{} * {}", date, single_line(payee))?;
+            if !entry.narration.is_empty() {
+                writeln!(writer, "    ; {}", single_line(entry.narration))?;
+            }
+        }
+        None => writeln!(writer, "{} * {}", date, single_line(entry.narration))?,
+    }
+    if let Some(id) = entry.id {
+        writeln!(writer, "    ; id: {}", single_line(id))?;
+    }
+    for posting in &entry.postings {
+        // Two spaces at least separate the account from the amount
+        writeln!(
+            writer,
+            "    {:<40}  {:>14} {}",
+            posting.account, posting.amount, entry.currency
+        )?;
+    }
+    Ok(())
+}
+
+/// Journal entries are line-based; newlines in free text would start a new
+/// directive.
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, Money, Transaction, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_write_ledger() {
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![Transaction {
+                id: "TX1".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 3, 5).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(4550, 2),
+                    currency: "EUR".to_string(),
+                },
+                description: "Weekly shop\nsecond line".to_string(),
+                account: None,
+                counterparty: Some("Grocer".to_string()),
+                category: Some("Groceries".to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            }],
+            balances: vec![Balance {
+                currency: "EUR".to_string(),
+                opening: Decimal::ZERO,
+                closing: Decimal::new(-4550, 2),
+            }],
+            metadata: Default::default(),
+        };
+        let mut mapping = AccountMapping::default();
+        mapping
+            .by_category
+            .insert("Groceries".to_string(), "Expenses:Food".to_string());
+
+        let mut output = Vec::new();
+        write(&batch, &mut output, &mapping).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(
+            output,
+            "2024/03/05 * Grocer\n    \
+             ; Weekly shop second line\n    \
+             ; id: TX1\n    \
+             Assets:Bank                                       -45.50 EUR\n    \
+             Expenses:Food                                      45.50 EUR\n\
+             \n\
+             2024/03/05 * Closing balance\n    \
+             Assets:Bank                                            0 EUR = -45.50 EUR\n"
+        );
+    }
+}
@@ -4,32 +4,911 @@
 //! in various formats. Each format has its own submodule with specialized
 //! parsing and writing functions.
 
+#[cfg(feature = "std")]
+use crate::{Error, Result, Transaction, TransactionBatch};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use alloc::borrow::Cow;
+#[cfg(feature = "std")]
+use chrono::NaiveDate;
+#[cfg(feature = "std")]
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use rust_decimal::{Decimal, RoundingStrategy};
+#[cfg(feature = "std")]
+use std::io::Read;
+
+/// Beancount ledger export.
+///
+/// This module converts transactions into Beancount directives for
+/// plain-text accounting.
+#[cfg(feature = "std")]
+pub mod beancount;
+
 /// Binary format parser and writer.
 ///
 /// This module provides functions to parse and write transaction data
-/// in a compact binary format.
+/// in a compact binary format. The [`binary::decode_binary`] and
+/// [`binary::encode_binary`] codec also works without the `std` feature.
 pub mod binary;
 
+/// CAMT.053 statement import.
+///
+/// This module reads ISO 20022 bank-to-customer statements, with their
+/// opening and closing balances.
+#[cfg(feature = "std")]
+pub mod camt;
+
 /// CSV format parser and writer.
 ///
 /// This module provides functions to parse and write transaction data
 /// in comma-separated values format.
+#[cfg(feature = "std")]
 pub mod csv;
 
+/// Fixed-width record parser and writer.
+///
+/// This module reads and writes fixed-width records, such as mainframe
+/// extracts, according to a configurable column layout.
+#[cfg(feature = "std")]
+pub mod fixed_width;
+
+/// GnuCash CSV export.
+///
+/// This module writes transactions in the layout GnuCash's CSV transaction
+/// import expects.
+#[cfg(feature = "std")]
+pub mod gnucash;
+
+/// JSON format parser and writer.
+///
+/// This module provides functions to parse and write transaction data
+/// as a JSON document, for exchange with web services.
+#[cfg(feature = "std")]
+pub mod json;
+
+/// Ledger journal export.
+///
+/// This module converts transactions into ledger-cli / hledger journal
+/// entries.
+#[cfg(feature = "std")]
+pub mod ledger;
+
+/// MT940 statement import.
+///
+/// This module reads SWIFT MT940 customer statements, with their opening
+/// and closing balances.
+#[cfg(feature = "std")]
+pub mod mt940;
+
+/// Open Banking transactions import.
+///
+/// This module reads the transactions responses of the UK Open Banking
+/// (PSD2) API, as served by banks and account aggregators.
+#[cfg(feature = "std")]
+pub mod open_banking;
+
+/// Parquet format parser and writer.
+///
+/// This module provides functions to read and write transaction data as
+/// Parquet files, one row per transaction, for analytics pipelines.
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+/// PDF statement writer.
+///
+/// This module renders transaction data as a printable PDF statement.
+#[cfg(feature = "pdf")]
+pub mod pdf;
+
+/// Double-entry bookings shared by the plain-text accounting exports.
+#[cfg(feature = "std")]
+mod posting;
+
+/// Bank CSV profiles.
+///
+/// This module describes the CSV dialects of bank exports, loadable from
+/// TOML files, for [`csv::parse_csv_with_profile`].
+#[cfg(feature = "std")]
+pub mod profile;
+
+/// Protobuf encoding with a published schema.
+///
+/// This module provides functions to parse and write transaction data as
+/// messages of the schema in `proto/transactions.proto`.
+#[cfg(feature = "proto")]
+pub mod protobuf;
+
+/// QIF (Quicken Interchange Format) parser and writer.
+///
+/// This module provides functions to exchange bank transactions with
+/// Quicken and other personal-finance tools.
+#[cfg(feature = "std")]
+pub mod qif;
+
+/// Relational database export and import.
+///
+/// This module provides async functions to bulk-insert a batch into
+/// PostgreSQL or MySQL and to read a batch back from a query.
+#[cfg(feature = "sql")]
+pub mod sql;
+
+/// SQLite file format.
+///
+/// This module provides functions to read, write and append to a
+/// `transactions` table in an SQLite database file.
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Excel workbook parser and writer.
+///
+/// This module provides functions to read and write transaction data as
+/// `.xlsx` workbooks with typed date and number cells.
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+/// XML format parser and writer.
+///
+/// This module provides functions to parse and write transaction data
+/// as a `<TransactionBatch>` document with nested `<Transaction>` elements.
+#[cfg(feature = "std")]
+pub mod xml;
+
 /// Plain text format parser and writer.
 ///
 /// This module provides functions to parse and write transaction data
 /// in a human-readable plain text format.
+#[cfg(feature = "std")]
 pub mod text;
 
+/// YNAB (You Need A Budget) CSV import.
+///
+/// This module parses register exports from YNAB, whose header differs from
+/// the CSV format's.
+#[cfg(feature = "std")]
+pub mod ynab;
+
+/// YAML format parser and writer.
+///
+/// This module provides functions to parse and write transaction data
+/// as a YAML document, convenient for hand-edited test fixtures.
+#[cfg(feature = "std")]
+pub mod yaml;
+
+/// A transaction field, as named in fixed-width layouts and
+/// [`WriteOptions::fields`]. It (de)serializes as the snake_case name used
+/// in layout and template files, so the impls come with `std` too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    any(feature = "std", feature = "serde"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+#[cfg_attr(
+    any(feature = "std", feature = "serde"),
+    serde(rename_all = "snake_case")
+)]
+pub enum Field {
+    /// [`Transaction::id`](crate::Transaction::id)
+    Id,
+    /// [`Transaction::posted_at`](crate::Transaction::posted_at)
+    PostedDate,
+    /// [`Transaction::executed_at`](crate::Transaction::executed_at)
+    ExecutedDate,
+    /// [`Transaction::kind`](crate::Transaction::kind)
+    Kind,
+    /// [`Transaction::status`](crate::Transaction::status)
+    Status,
+    /// The amount of [`Transaction::amount`](crate::Transaction::amount)
+    Amount,
+    /// The currency of [`Transaction::amount`](crate::Transaction::amount)
+    Currency,
+    /// [`Transaction::description`](crate::Transaction::description)
+    Description,
+    /// [`Transaction::account`](crate::Transaction::account)
+    Account,
+    /// [`Transaction::counterparty`](crate::Transaction::counterparty)
+    Counterparty,
+    /// [`Transaction::category`](crate::Transaction::category)
+    Category,
+    /// [`Transaction::tax_amount`](crate::Transaction::tax_amount)
+    TaxAmount,
+    /// [`Transaction::tax_rate`](crate::Transaction::tax_rate)
+    TaxRate,
+}
+
+/// Options controlling how input is parsed.
+///
+/// The defaults parse exactly like [`crate::parse`].
+///
+/// # Examples
+///
+/// ```
+/// use parser::{parse_with, Format, ParseOptions};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let options = ParseOptions {
+///     date_formats: vec!["%d/%m/%Y".to_string()],
+///     max_records: Some(10),
+///     ..ParseOptions::default()
+/// };
+/// let input = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+///              TX1,15/01/2024,,Debit,9.99,EUR,Coffee\n";
+/// let batch = parse_with(input.as_bytes(), Format::Csv, &options)?;
+/// assert_eq!(batch.transactions.len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Reject input that is otherwise skipped: CSV columns and fields beyond
+    /// the known ones, and unrecognized lines in text input
+    pub strict: bool,
+    /// chrono formats tried, in order, for posting dates in CSV and text
+    /// input that aren't `YYYY-MM-DD`
+    pub date_formats: Vec<String>,
+    /// CSV field delimiter; sniffed from the header (`,`, `;`, tab or `|`)
+    /// when `None`
+    pub delimiter: Option<char>,
+    /// Stop after this many transactions, e.g. to preview a large file; CSV
+    /// and text input isn't read any further, and SQLite files are queried
+    /// with a `LIMIT`. Documents (binary, JSON, XML, YAML, Parquet, Protobuf
+    /// and Excel) are still read and parsed whole before the batch is cut,
+    /// so bound their cost with [`ParseOptions::limits`] instead
+    pub max_records: Option<usize>,
+    /// Encoding of text-based input as a WHATWG label, such as
+    /// `windows-1251` or `utf-16le`; detected from the first bytes when
+    /// `None`: a byte order mark or UTF-16 zero bytes, else UTF-8 when valid
+    /// and Windows-1251 when not
+    pub encoding: Option<String>,
+    /// Decimal separator of amounts in CSV and text input, such as `,` for
+    /// `1234,56`; `.` when `None`
+    pub decimal_separator: Option<char>,
+    /// Digit grouping separator of amounts in CSV and text input, such as
+    /// `,` for `1,234.56` or ` ` for `1 234,56` (which also accepts
+    /// non-breaking spaces); no grouping when `None`
+    pub thousands_separator: Option<char>,
+    /// Labels of text input lines, such as [`text::TextLabels::russian`];
+    /// English labels are understood as well. English only when `None`
+    #[cfg(feature = "std")]
+    pub text_labels: Option<text::TextLabels>,
+    /// Accept text records without a date, type or amount, which then
+    /// default to 1970-01-01, `Debit` and zero, instead of rejecting them
+    pub allow_incomplete: bool,
+    /// Key to decrypt encrypted binary input with
+    #[cfg(feature = "encryption")]
+    pub encryption_key: Option<binary::EncryptionKey>,
+    /// Require binary input to be signed by the owner of this key (see
+    /// [`crate::signature`])
+    #[cfg(feature = "signing")]
+    pub verifying_key: Option<crate::signature::VerifyingKey>,
+    /// Caps on the size of the input, for data from untrusted sources
+    pub limits: Limits,
+    /// Reject currency codes that aren't in ISO 4217, such as the typo
+    /// `UDS`, unless listed in [`ParseOptions::custom_currencies`]
+    pub iso_currencies: bool,
+    /// Codes accepted besides ISO 4217 ones when
+    /// [`ParseOptions::iso_currencies`] is set, such as `PTS` for internal
+    /// loyalty points
+    pub custom_currencies: Vec<String>,
+}
+
+/// The outcome of [`crate::parse_report`]: the transactions of the records
+/// that parsed, and what went wrong with the others.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{parse_report, Format, ParseOptions};
+///
+/// # fn main() -> Result<(), parser::Error> {
+/// let input = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+///              TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n\
+///              TOTAL,,,,9.99,,\n";
+/// let report = parse_report(input.as_bytes(), Format::Csv, &ParseOptions::default())?;
+/// assert_eq!(report.transactions.len(), 1);
+/// assert_eq!(report.errors.len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    /// Optional identifier for the account these transactions belong to
+    pub account_id: Option<String>,
+    /// The transactions of the records that parsed, in input order
+    pub transactions: Vec<Transaction>,
+    /// Opening and closing balances declared by the source, one per currency
+    pub balances: Vec<crate::Balance>,
+    /// Information about the batch as a whole, from its header
+    pub metadata: crate::BatchMetadata,
+    /// Why each skipped record was rejected, in input order
+    pub errors: Vec<Error>,
+    /// Input that was accepted but partly ignored, such as CSV fields beyond
+    /// the header or unrecognized text lines
+    pub warnings: Vec<String>,
+}
+
+#[cfg(feature = "std")]
+impl ParseReport {
+    /// Whether every record parsed and nothing was ignored.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+
+    /// The batch of the transactions that parsed.
+    pub fn into_batch(self) -> TransactionBatch {
+        TransactionBatch {
+            account_id: self.account_id,
+            transactions: self.transactions,
+            balances: self.balances,
+            metadata: self.metadata,
+        }
+    }
+}
+
+/// Serializes like a JSON batch document (camelCase fields, dates and
+/// amounts as strings) with `errors` and `warnings` lists added.
+#[cfg(all(feature = "std", feature = "serde"))]
+impl serde::Serialize for ParseReport {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&json::ReportDocument::new(self), serializer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<TransactionBatch> for ParseReport {
+    fn from(batch: TransactionBatch) -> Self {
+        ParseReport {
+            account_id: batch.account_id,
+            transactions: batch.transactions,
+            balances: batch.balances,
+            metadata: batch.metadata,
+            ..ParseReport::default()
+        }
+    }
+}
+
+/// Caps on what parsing may read and keep, so that a hostile or corrupted
+/// file fails with [`crate::Error::LimitExceeded`] instead of exhausting
+/// memory.
+///
+/// The default sets no caps; [`Limits::untrusted`] has caps suitable for
+/// files uploaded by users.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{parse_with, Error, Format, Limits, ParseOptions};
+///
+/// let options = ParseOptions {
+///     limits: Limits {
+///         max_input_bytes: Some(64),
+///         ..Limits::untrusted()
+///     },
+///     ..ParseOptions::default()
+/// };
+/// let input = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+///              TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n";
+/// let result = parse_with(input.as_bytes(), Format::Csv, &options);
+/// assert!(matches!(result, Err(Error::LimitExceeded(_))));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Most transactions a batch may have
+    pub max_transactions: Option<usize>,
+    /// Longest text field, such as a description or an extra column, in
+    /// bytes
+    pub max_string_len: Option<usize>,
+    /// Most bytes read from the input, as stored (before decompression or
+    /// transcoding)
+    pub max_input_bytes: Option<u64>,
+}
+
+impl Limits {
+    /// Caps for files uploaded by users: a million transactions, 64 KiB
+    /// strings and 256 MiB of input.
+    pub fn untrusted() -> Self {
+        Limits {
+            max_transactions: Some(1_000_000),
+            max_string_len: Some(64 * 1024),
+            max_input_bytes: Some(256 * 1024 * 1024),
+        }
+    }
+
+    /// Fails when a batch would hold `count` transactions.
+    pub(crate) fn check_transactions(&self, count: usize) -> crate::Result<()> {
+        match self.max_transactions {
+            Some(max) if count > max => Err(crate::Error::LimitExceeded(format!(
+                "more than {} transactions",
+                max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails when a text field is `len` bytes long.
+    pub(crate) fn check_string(&self, len: usize) -> crate::Result<()> {
+        match self.max_string_len {
+            Some(max) if len > max => Err(crate::Error::LimitExceeded(format!(
+                "a string of {} bytes, more than {}",
+                len, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the text fields of a transaction with
+    /// [`Limits::check_string`].
+    #[cfg(feature = "std")]
+    pub(crate) fn check_transaction(&self, tx: &crate::TransactionRef<'_>) -> Result<()> {
+        if self.max_string_len.is_none() {
+            return Ok(());
+        }
+        let optional = [&tx.account, &tx.counterparty, &tx.category];
+        let mut fields = [&tx.id, &tx.currency, &tx.description]
+            .into_iter()
+            .chain(optional.into_iter().flatten())
+            .chain(tx.extra.iter().flat_map(|(name, value)| [name, value]));
+        fields.try_for_each(|field| self.check_string(field.len()))
+    }
+
+    /// Checks a parsed batch against the caps on transactions and strings.
+    #[cfg(feature = "std")]
+    pub(crate) fn check_batch(&self, batch: &TransactionBatch) -> Result<()> {
+        self.check_transactions(batch.transactions.len())?;
+        if let Some(account_id) = &batch.account_id {
+            self.check_string(account_id.len())?;
+        }
+        if self.max_string_len.is_some() {
+            for tx in &batch.transactions {
+                self.check_transaction(&tx.into())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads at most [`Limits::max_input_bytes`] from a reader, failing once the
+/// input goes on for longer.
+#[cfg(feature = "std")]
+pub(crate) struct LimitedReader<R> {
+    inner: R,
+    /// The cap
+    limit: Option<u64>,
+    /// Bytes read so far
+    read: u64,
+    /// Whether the input went past the cap
+    exceeded: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> LimitedReader<R> {
+    pub(crate) fn new(inner: R, limits: &Limits) -> Self {
+        LimitedReader {
+            inner,
+            limit: limits.max_input_bytes,
+            read: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Replaces the outcome of parsing with [`Error::LimitExceeded`] when the
+    /// input went past the cap; parsers report the failed read in their own
+    /// way, so it can't be told apart otherwise.
+    pub(crate) fn check<T>(&self, result: Result<T>) -> Result<T> {
+        match self.limit {
+            Some(limit) if self.exceeded => Err(Error::LimitExceeded(format!(
+                "input longer than {} bytes",
+                limit
+            ))),
+            _ => result,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(limit) = self.limit else {
+            return self.inner.read(buf);
+        };
+        // Read one byte past the cap to tell input that ends right at it
+        // from input that goes on
+        let remaining = (limit - self.read).saturating_add(1);
+        let remaining = usize::try_from(remaining).unwrap_or(usize::MAX);
+        let len = buf.len().min(remaining);
+        let read = self.inner.read(&mut buf[..len])?;
+        self.read += read as u64;
+        if self.read > limit {
+            self.exceeded = true;
+            return Err(std::io::Error::other("input size limit exceeded"));
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ParseOptions {
+    /// Runs `parse` on `reader` under these options' caps: at most
+    /// [`Limits::max_input_bytes`] are read, the batch is cut to
+    /// [`ParseOptions::max_records`] and then checked against the other
+    /// [`ParseOptions::limits`] and [`ParseOptions::iso_currencies`].
+    pub(crate) fn parse_limited<R: Read>(
+        &self,
+        reader: R,
+        parse: impl FnOnce(&mut LimitedReader<R>) -> Result<TransactionBatch>,
+    ) -> Result<TransactionBatch> {
+        let mut reader = LimitedReader::new(reader, &self.limits);
+        let batch = parse(&mut reader);
+        self.finish_batch(reader.check(batch)?)
+    }
+
+    /// Cuts a parsed batch to [`ParseOptions::max_records`] and checks it
+    /// against [`ParseOptions::limits`] and
+    /// [`ParseOptions::iso_currencies`], for readers that don't go through
+    /// [`ParseOptions::parse_limited`].
+    pub(crate) fn finish_batch(&self, mut batch: TransactionBatch) -> Result<TransactionBatch> {
+        if let Some(max) = self.max_records {
+            batch.transactions.truncate(max);
+        }
+        self.limits.check_batch(&batch)?;
+        self.check_currencies(&batch)?;
+        Ok(batch)
+    }
+
+    /// Whether a parser holding `count` transactions has all that
+    /// [`ParseOptions::max_records`] asks for, and can stop reading.
+    pub(crate) fn has_max_records(&self, count: usize) -> bool {
+        self.max_records.is_some_and(|max| count >= max)
+    }
+
+    /// Checks a currency code against [`ParseOptions::iso_currencies`];
+    /// `location` says where it was found, for the error.
+    pub(crate) fn check_currency(
+        &self,
+        code: &str,
+        location: impl FnOnce() -> String,
+    ) -> Result<()> {
+        if !self.iso_currencies
+            || crate::currency::is_iso_4217(code)
+            || self.custom_currencies.iter().any(|custom| custom == code)
+        {
+            return Ok(());
+        }
+        Err(Error::UnknownCurrency {
+            code: code.to_string(),
+            location: location(),
+        })
+    }
+
+    /// Checks the currency codes of a transaction against
+    /// [`ParseOptions::iso_currencies`].
+    pub(crate) fn check_transaction_currency(&self, tx: &Transaction) -> Result<()> {
+        self.check_currency(&tx.amount.currency, || format!("transaction {}", tx.id))
+    }
+
+    /// Checks every currency code of a batch against
+    /// [`ParseOptions::iso_currencies`].
+    pub(crate) fn check_currencies(&self, batch: &TransactionBatch) -> Result<()> {
+        for balance in &batch.balances {
+            self.check_currency(&balance.currency, || "balances".to_string())?;
+        }
+        batch
+            .transactions
+            .iter()
+            .try_for_each(|tx| self.check_transaction_currency(tx))
+    }
+
+    /// Parses a posting date as `YYYY-MM-DD` or one of
+    /// [`ParseOptions::date_formats`], returning the error of the default
+    /// format when none matches.
+    pub(crate) fn parse_date(&self, value: &str) -> core::result::Result<NaiveDate, chrono::ParseError> {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").or_else(|error| {
+            self.date_formats
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .ok_or(error)
+        })
+    }
+
+    /// Parses an amount written with [`ParseOptions::decimal_separator`] and
+    /// [`ParseOptions::thousands_separator`].
+    pub(crate) fn parse_amount(&self, value: &str) -> core::result::Result<Decimal, rust_decimal::Error> {
+        let decimal = self.decimal_separator.unwrap_or('.');
+        let is_grouping = |c: char| match self.thousands_separator {
+            Some(separator) if separator.is_whitespace() => c.is_whitespace(),
+            separator => Some(c) == separator,
+        };
+        if decimal == '.' && self.thousands_separator.is_none() {
+            return Decimal::from_str(value);
+        }
+        let normalized: String = value
+            .chars()
+            .filter(|c| !is_grouping(*c))
+            .map(|c| if c == decimal { '.' } else { c })
+            .collect();
+        Decimal::from_str(&normalized)
+    }
+
+    /// Whether `count` transactions reach [`ParseOptions::max_records`].
+    pub(crate) fn is_full(&self, count: usize) -> bool {
+        self.max_records.is_some_and(|max| count >= max)
+    }
+}
+
+/// The byte order mark spreadsheet programs such as Excel put at the start
+/// of UTF-8 files.
+#[cfg(feature = "std")]
+pub(crate) const BOM: char = '\u{feff}';
+
+/// Removes a leading [`BOM`] from the first line of a file.
+#[cfg(feature = "std")]
+pub(crate) fn strip_bom(line: String) -> String {
+    match line.strip_prefix(BOM) {
+        Some(rest) => rest.to_string(),
+        None => line,
+    }
+}
+
+/// Number of leading bytes [`decode`] looks at to detect an encoding.
+#[cfg(feature = "std")]
+pub(crate) const ENCODING_SAMPLE: usize = 8 * 1024;
+
+/// Decodes text-based input to UTF-8.
+///
+/// `encoding` is a WHATWG label; when `None`, the encoding is detected from
+/// the first bytes (see [`detect_encoding`]). UTF-8 input is passed through
+/// without being read ahead; other encodings are read whole and transcoded.
+/// A byte order mark of a UTF-16 encoding is dropped.
+#[cfg(feature = "std")]
+pub(crate) fn decode<'a, R: Read + 'a>(
+    mut reader: R,
+    encoding: Option<&str>,
+) -> Result<Box<dyn Read + 'a>> {
+    let mut bytes = Vec::new();
+    if encoding.is_none() {
+        (&mut reader)
+            .take(ENCODING_SAMPLE as u64)
+            .read_to_end(&mut bytes)?;
+    }
+    let encoding = resolve_encoding(encoding, &bytes)?;
+    if encoding == encoding_rs::UTF_8 {
+        return Ok(Box::new(std::io::Cursor::new(bytes).chain(reader)));
+    }
+
+    reader.read_to_end(&mut bytes)?;
+    let (bom, bom_length) = encoding_rs::Encoding::for_bom(&bytes).unwrap_or((encoding, 0));
+    let bytes = if bom == encoding && bom != encoding_rs::UTF_8 {
+        &bytes[bom_length..]
+    } else {
+        &bytes[..]
+    };
+    let text = encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .ok_or_else(|| {
+            Error::parse(
+                "Encoding",
+                format!("input is not valid {}", encoding.name()),
+            )
+        })?;
+    Ok(Box::new(std::io::Cursor::new(text.into_owned().into_bytes())))
+}
+
+/// The encoding [`decode`] decodes from: the one labelled `encoding`, or
+/// when `None` the one detected from `sample`, the first
+/// [`ENCODING_SAMPLE`] bytes of the input.
+#[cfg(feature = "std")]
+pub(crate) fn resolve_encoding(
+    encoding: Option<&str>,
+    sample: &[u8],
+) -> Result<&'static encoding_rs::Encoding> {
+    match encoding {
+        Some(label) => encoding_rs::Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| Error::UnsupportedFormat(format!("unknown encoding: {}", label))),
+        None => Ok(detect_encoding(sample)),
+    }
+}
+
+/// Guesses the encoding of the first bytes of some text:
+/// - a byte order mark says UTF-8, UTF-16LE or UTF-16BE
+/// - many zero bytes in odd (even) positions mean UTF-16LE (BE) without a
+///   byte order mark, as some Excel versions save "Unicode text"
+/// - otherwise it is UTF-8 when valid (a sequence cut off at the end of the
+///   sample is fine), and Windows-1251, the encoding of Russian bank exports,
+///   when not
+#[cfg(feature = "std")]
+fn detect_encoding(sample: &[u8]) -> &'static encoding_rs::Encoding {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(sample) {
+        return encoding;
+    }
+    let zeros_at = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    // Text is mostly ASCII, whose UTF-16 code units have a zero high byte
+    let threshold = sample.len() / 4;
+    if sample.len() >= 2 && zeros_at(1) > threshold {
+        return encoding_rs::UTF_16LE;
+    }
+    if sample.len() >= 2 && zeros_at(0) > threshold {
+        return encoding_rs::UTF_16BE;
+    }
+    match core::str::from_utf8(sample) {
+        Err(error) if error.error_len().is_some() => encoding_rs::WINDOWS_1251,
+        _ => encoding_rs::UTF_8,
+    }
+}
+
+/// Options controlling how a batch is written.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{write_with, Format, TransactionBatch, WriteOptions};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let options = WriteOptions {
+///     canonical: true,
+///     ..WriteOptions::default()
+/// };
+/// let mut output = Vec::new();
+/// write_with(&TransactionBatch::default(), &mut output, Format::Csv, &options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Write the batch in canonical form (see [`crate::canonical`]), so that
+    /// logically identical batches produce byte-identical output
+    pub canonical: bool,
+    /// Precede CSV output with a `# schema-version: N` comment, so that
+    /// future releases can map the file's layout without guessing; other
+    /// formats carry their version already
+    pub schema_version: bool,
+    /// chrono format of posting dates in CSV and text output, such as
+    /// `%d/%m/%Y`; `YYYY-MM-DD` when `None`. Other dates than ISO ones only
+    /// parse back with a matching [`ParseOptions::date_formats`]
+    pub date_format: Option<String>,
+    /// CSV field delimiter, such as `;` for spreadsheets with European
+    /// regional settings; `,` when `None`
+    pub delimiter: Option<char>,
+    /// Which CSV fields are quoted
+    pub quoting: Quoting,
+    /// What separates the records of text output
+    pub text_separator: TextSeparator,
+    /// Round amounts, tax amounts and balances to this many decimal places
+    /// (half away from zero) and pad them to it, in every format
+    pub decimal_places: Option<u32>,
+    /// The fields of CSV and text output, in order; all fields when `None`.
+    /// In text output the currency is part of the amount line. Output that
+    /// leaves out required fields doesn't parse back
+    pub fields: Option<Vec<Field>>,
+    /// Layout of text records, replacing the `Key: value` lines (and
+    /// [`WriteOptions::fields`] and [`WriteOptions::text_separator`])
+    #[cfg(feature = "std")]
+    pub text_template: Option<text::TextTemplate>,
+    /// Labels of text output lines, such as [`text::TextLabels::russian`];
+    /// English when `None`
+    #[cfg(feature = "std")]
+    pub text_labels: Option<text::TextLabels>,
+    /// Write CSV exactly as RFC 4180 prescribes, for ingestion systems that
+    /// enforce it: CRLF line endings, `,` as delimiter (overriding
+    /// [`WriteOptions::delimiter`]), and the header and every text field
+    /// quoted, empty ones included. Dates and numbers are quoted only when
+    /// [`WriteOptions::quoting`] is [`Quoting::Always`]
+    pub rfc4180: bool,
+    /// Start CSV and text output with a UTF-8 byte order mark, without
+    /// which Excel reads non-ASCII characters as its legacy code page
+    pub bom: bool,
+    /// Follow each transaction and the whole batch of binary output with a
+    /// CRC32, so that corruption is detected and located on reading
+    pub checksums: bool,
+    /// Follow binary output with an index of its transaction IDs, so that
+    /// [`binary::open_indexed`] can look transactions up without reading the
+    /// others. Indexed output can't be compressed, encrypted or signed
+    pub index: bool,
+    /// Compress binary output with zstd at this level (see
+    /// [`binary::write_binary_compressed`])
+    #[cfg(feature = "zstd")]
+    pub compression: Option<i32>,
+    /// Encrypt binary output with AES-256-GCM under this key, so that it can
+    /// only be read with the same key
+    #[cfg(feature = "encryption")]
+    pub encryption_key: Option<binary::EncryptionKey>,
+    /// Embed a signature of the batch by this key in binary output (see
+    /// [`crate::signature`])
+    #[cfg(feature = "signing")]
+    pub signing_key: Option<crate::signature::SigningKey>,
+}
+
+/// Which fields of CSV output are enclosed in quotes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quoting {
+    /// Only fields containing a delimiter, quote or line break
+    #[default]
+    Necessary,
+    /// Every field, including the header
+    Always,
+}
+
+/// What separates the records of text output. The parser accepts either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextSeparator {
+    /// A `---` line
+    #[default]
+    Dashes,
+    /// An empty line
+    BlankLine,
+}
+
+impl WriteOptions {
+    /// Returns the batch to write: the input itself, or a canonical and/or
+    /// rounded copy.
+    #[cfg(feature = "std")]
+    pub(crate) fn prepare<'a>(&self, batch: &'a TransactionBatch) -> Cow<'a, TransactionBatch> {
+        if !self.canonical && self.decimal_places.is_none() {
+            return Cow::Borrowed(batch);
+        }
+        let mut batch = batch.clone();
+        if self.canonical {
+            crate::canonical::canonicalize(&mut batch);
+        }
+        for tx in &mut batch.transactions {
+            self.round_transaction(tx);
+        }
+        for balance in &mut batch.balances {
+            self.round(&mut balance.opening);
+            self.round(&mut balance.closing);
+        }
+        Cow::Owned(batch)
+    }
+
+    /// Rounds the amounts of a transaction to
+    /// [`WriteOptions::decimal_places`], if set.
+    #[cfg(feature = "std")]
+    pub(crate) fn round_transaction(&self, tx: &mut Transaction) {
+        self.round(&mut tx.amount.amount);
+        if let Some(tax) = tx.tax_amount.as_mut() {
+            self.round(tax);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn round(&self, value: &mut Decimal) {
+        if let Some(places) = self.decimal_places {
+            *value = value.round_dp_with_strategy(places, RoundingStrategy::MidpointAwayFromZero);
+            value.rescale(places);
+        }
+    }
+
+    /// Formats a posting date with [`WriteOptions::date_format`].
+    #[cfg(feature = "std")]
+    pub(crate) fn format_date(&self, date: NaiveDate) -> String {
+        date.format(self.date_format.as_deref().unwrap_or("%Y-%m-%d"))
+            .to_string()
+    }
+}
+
 /// Supported transaction file formats.
 ///
 /// This enum represents all formats that can be used to parse
-/// and write transaction data.
+/// and write transaction data. Parquet, Protobuf, SQLite and Excel only
+/// exist with their features, so the set of variants depends on the build
+/// and matches outside this crate need a wildcard arm.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[derive(strum::EnumString, strum::Display, strum::AsRefStr)]
 #[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 #[strum(serialize_all = "lowercase")]
+#[non_exhaustive]
 pub enum Format {
     /// Comma-separated values format
     #[cfg_attr(feature = "cli", value(name = "csv"))]
@@ -46,6 +925,46 @@ pub enum Format {
     #[strum(serialize = "binary")]
     #[strum(serialize = "bin")]
     Binary,
+
+    /// JSON document
+    #[cfg_attr(feature = "cli", value(name = "json"))]
+    Json,
+
+    /// XML document
+    #[cfg_attr(feature = "cli", value(name = "xml"))]
+    Xml,
+
+    /// YAML document
+    #[cfg_attr(feature = "cli", value(name = "yaml", alias = "yml"))]
+    #[strum(serialize = "yaml")]
+    #[strum(serialize = "yml")]
+    Yaml,
+
+    /// Apache Parquet columnar file
+    #[cfg(feature = "parquet")]
+    #[cfg_attr(feature = "cli", value(name = "parquet"))]
+    Parquet,
+
+    /// Protobuf message (`proto/transactions.proto`)
+    #[cfg(feature = "proto")]
+    #[cfg_attr(feature = "cli", value(name = "protobuf", alias = "pb"))]
+    #[strum(serialize = "protobuf")]
+    #[strum(serialize = "pb")]
+    Protobuf,
+
+    /// SQLite database file with a `transactions` table
+    #[cfg(feature = "sqlite")]
+    #[cfg_attr(feature = "cli", value(name = "sqlite", alias = "db"))]
+    #[strum(serialize = "sqlite")]
+    #[strum(serialize = "db")]
+    Sqlite,
+
+    /// Excel workbook
+    #[cfg(feature = "xlsx")]
+    #[cfg_attr(feature = "cli", value(name = "xlsx", alias = "excel"))]
+    #[strum(serialize = "xlsx")]
+    #[strum(serialize = "excel")]
+    Xlsx,
 }
 
 impl Format {
@@ -62,4 +981,60 @@ impl Format {
     pub fn as_str(&self) -> &str {
         self.as_ref()
     }
+
+    /// Returns the conventional file extension for the format, without a dot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    ///
+    /// assert_eq!(Format::Text.extension(), "txt");
+    /// assert_eq!(Format::Binary.extension(), "bin");
+    /// ```
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Text => "txt",
+            Format::Binary => "bin",
+            Format::Json => "json",
+            Format::Xml => "xml",
+            Format::Yaml => "yaml",
+            #[cfg(feature = "parquet")]
+            Format::Parquet => "parquet",
+            #[cfg(feature = "proto")]
+            Format::Protobuf => "pb",
+            #[cfg(feature = "sqlite")]
+            Format::Sqlite => "sqlite",
+            #[cfg(feature = "xlsx")]
+            Format::Xlsx => "xlsx",
+        }
+    }
+
+    /// Guesses the format from a file extension (without the dot).
+    ///
+    /// Accepts the same names and aliases as parsing a `Format` from a string,
+    /// case-insensitively. Returns `None` for unknown extensions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    ///
+    /// assert_eq!(Format::from_extension("TXT"), Some(Format::Text));
+    /// assert_eq!(Format::from_extension("pdf"), None);
+    /// ```
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        extension.to_ascii_lowercase().parse().ok()
+    }
+
+    /// Whether the format is made of characters rather than bytes, so that
+    /// its input can be transcoded.
+    #[cfg(feature = "std")]
+    pub(crate) fn is_text(&self) -> bool {
+        matches!(
+            self,
+            Format::Csv | Format::Text | Format::Json | Format::Xml | Format::Yaml
+        )
+    }
 }
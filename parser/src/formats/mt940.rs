@@ -0,0 +1,387 @@
+use super::{strip_bom, ParseOptions};
+use crate::balance;
+use crate::{
+    Balance, Error, Money, Period, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// A `:tag:value` field of a statement, with the line it starts on.
+struct Field {
+    tag: String,
+    value: String,
+    line: usize,
+}
+
+/// Parses a SWIFT MT940 customer statement, as exported by most European
+/// banks.
+///
+/// Fields are read from the text block of the message; SWIFT block headers
+/// (`{1:...}{2:...}{4:`) and `-}` trailers are skipped, so both bare and
+/// wrapped messages are accepted, and a file may hold several statements.
+///
+/// - `:25:` is the batch's account ID
+/// - `:60F:` is the opening balance and `:62F:` the closing balance of the
+///   statement's currency; their dates are the batch's
+///   [`BatchMetadata::period`](crate::BatchMetadata::period)
+/// - `:13D:` is the [`BatchMetadata::generated_at`](crate::BatchMetadata::generated_at)
+///   time
+/// - each `:61:` statement line is a transaction, described by the `:86:`
+///   field that follows it. `C` and `D` marks are credits and debits;
+///   reversals (`RC`, `RD`) are [`TransactionKind::Reversal`] with the sign
+///   of their effect on the balance. The entry date is the posting date and
+///   the value date the execution date; without an entry date, the value
+///   date is the posting date. The bank's reference (after `//`) is the ID,
+///   or else the customer reference; lines with neither (`NONREF`) get
+///   sequential IDs (`MT000001`, ...).
+///
+/// The input is held to [`ParseOptions::limits`]. The whole statement is
+/// read so that it can be balanced, and only then cut to
+/// [`ParseOptions::max_records`] transactions.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing the MT940 statement
+/// * `options` - Record and size caps, and currency checks
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with the transactions and declared
+/// balances, or an [`Error`] if the statement is malformed or doesn't
+/// balance.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - A field, balance or statement line cannot be parsed
+/// - A statement line or closing balance comes before an opening balance
+/// - The opening balance plus the statement lines doesn't equal the closing
+///   balance
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_mt940<R: Read>(reader: R, options: &ParseOptions) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| parse_statement(reader))
+}
+
+fn parse_statement<R: Read>(reader: R) -> Result<TransactionBatch> {
+    let fields = read_fields(reader)?;
+
+    let mut batch = TransactionBatch::default();
+    let mut currency: Option<String> = None;
+    let (mut start, mut end) = (None, None);
+    let mut after_statement_line = false;
+    for field in fields {
+        let error =
+            |message: String| Error::parse("MT940", format!("line {}: {}", field.line, message));
+        let is_statement_line = field.tag == "61";
+        match field.tag.as_str() {
+            "25" if batch.account_id.is_none() => {
+                batch.account_id = Some(field.value.trim().to_string());
+            }
+            "13D" => {
+                let generated_at = NaiveDateTime::parse_from_str(
+                    field.value.trim().get(..10).unwrap_or_default(),
+                    "%y%m%d%H%M",
+                )
+                .map_err(|e| error(format!("invalid date and time: {}", e)).with_source(e))?;
+                batch.metadata.generated_at = Some(generated_at);
+            }
+            "60F" | "60M" => {
+                let (date, code, amount) = parse_balance(&field.value).map_err(error)?;
+                if !batch.balances.iter().any(|b| b.currency == code) {
+                    batch.balances.push(Balance {
+                        currency: code.clone(),
+                        opening: amount,
+                        closing: amount,
+                    });
+                    start = Some(start.map_or(date, |start: NaiveDate| start.min(date)));
+                }
+                currency = Some(code);
+            }
+            "62F" | "62M" => {
+                let (date, code, amount) = parse_balance(&field.value).map_err(error)?;
+                let balance = batch
+                    .balances
+                    .iter_mut()
+                    .find(|b| b.currency == code)
+                    .ok_or_else(|| {
+                        error(format!(
+                            "closing balance without opening balance in {}",
+                            code
+                        ))
+                    })?;
+                balance.closing = amount;
+                end = end.max(Some(date));
+            }
+            "61" => {
+                let code = currency.clone().ok_or_else(|| {
+                    error("statement line before the opening balance".to_string())
+                })?;
+                let index = batch.transactions.len();
+                let tx = parse_statement_line(&field.value, code, index).map_err(error)?;
+                batch.transactions.push(tx);
+            }
+            "86" if after_statement_line => {
+                if let Some(tx) = batch.transactions.last_mut() {
+                    tx.description = field
+                        .value
+                        .lines()
+                        .map(str::trim)
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                }
+            }
+            _ => {}
+        }
+        after_statement_line = is_statement_line;
+    }
+
+    if let (Some(start), Some(end)) = (start, end) {
+        if start <= end {
+            batch.metadata.period = Some(Period { start, end });
+        }
+    }
+    balance::ensure_balanced(&batch, "MT940")?;
+    Ok(batch)
+}
+
+/// Splits the text block of a message into its fields, joining continuation
+/// lines to the field they belong to.
+fn read_fields<R: Read>(reader: R) -> Result<Vec<Field>> {
+    let mut fields: Vec<Field> = Vec::new();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = strip_bom(line?);
+        let line = line.trim_end();
+        if line.trim().is_empty() || line.starts_with('{') || line.starts_with('-') {
+            continue;
+        }
+        let tag = line
+            .strip_prefix(':')
+            .and_then(|rest| rest.split_once(':'))
+            .filter(|(tag, _)| (2..=3).contains(&tag.len()));
+        match (tag, fields.last_mut()) {
+            (Some((tag, value)), _) => fields.push(Field {
+                tag: tag.to_string(),
+                value: value.to_string(),
+                line: index + 1,
+            }),
+            (None, Some(field)) => {
+                field.value.push('\n');
+                field.value.push_str(line);
+            }
+            (None, None) => {
+                return Err(Error::parse(
+                    "MT940",
+                    format!("line {}: expected a field such as :20:", index + 1),
+                ))
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// Parses a balance field (`C240131EUR1474,50`) into its date, currency and
+/// signed amount.
+fn parse_balance(value: &str) -> std::result::Result<(NaiveDate, String, Decimal), String> {
+    let value = value.trim();
+    let invalid = || format!("invalid balance: {}", value);
+    let (mark, rest) = value.split_at_checked(1).ok_or_else(invalid)?;
+    let (date, rest) = rest.split_at_checked(6).ok_or_else(invalid)?;
+    let (currency, amount) = rest.split_at_checked(3).ok_or_else(invalid)?;
+    let date = NaiveDate::parse_from_str(date, "%y%m%d").map_err(|_| invalid())?;
+    let amount = parse_amount(amount).ok_or_else(invalid)?;
+    let amount = match mark {
+        "C" => amount,
+        "D" => -amount,
+        _ => return Err(invalid()),
+    };
+    Ok((date, currency.to_string(), amount))
+}
+
+/// Parses a `:61:` statement line, such as
+/// `2401020102D25,50NTRFNONREF//B4A02`, into the `index`th transaction.
+fn parse_statement_line(
+    value: &str,
+    currency: String,
+    index: usize,
+) -> std::result::Result<Transaction, String> {
+    let first = value.lines().next().unwrap_or_default().trim();
+    let invalid = |what: &str| format!("invalid statement line, {}: {}", what, first);
+
+    let (value_date, rest) = first
+        .split_at_checked(6)
+        .ok_or_else(|| invalid("no value date"))?;
+    let value_date =
+        NaiveDate::parse_from_str(value_date, "%y%m%d").map_err(|_| invalid("bad value date"))?;
+    let (entry_date, rest) = match rest.get(..4) {
+        Some(entry) if entry.bytes().all(|b| b.is_ascii_digit()) => (
+            Some(entry_date(value_date, entry).ok_or_else(|| invalid("bad entry date"))?),
+            &rest[4..],
+        ),
+        _ => (None, rest),
+    };
+
+    let (kind, sign, rest) = if let Some(rest) = rest.strip_prefix("RC") {
+        (TransactionKind::Reversal, Decimal::NEGATIVE_ONE, rest)
+    } else if let Some(rest) = rest.strip_prefix("RD") {
+        (TransactionKind::Reversal, Decimal::ONE, rest)
+    } else if let Some(rest) = rest.strip_prefix('C') {
+        (TransactionKind::Credit, Decimal::ONE, rest)
+    } else if let Some(rest) = rest.strip_prefix('D') {
+        (TransactionKind::Debit, Decimal::ONE, rest)
+    } else {
+        return Err(invalid("no debit/credit mark"));
+    };
+    // The third letter of the currency code, for funds in another one
+    let rest = match rest.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => &rest[1..],
+        _ => rest,
+    };
+    let amount_len = rest
+        .find(|c: char| !c.is_ascii_digit() && c != ',')
+        .unwrap_or(rest.len());
+    let amount = parse_amount(&rest[..amount_len]).ok_or_else(|| invalid("bad amount"))?;
+    // The transaction type, such as NTRF, precedes the references
+    let references = rest[amount_len..].get(4..).unwrap_or_default();
+    let (customer, bank) = match references.split_once("//") {
+        Some((customer, bank)) => (customer, bank.trim()),
+        None => (references, ""),
+    };
+    let reference = [bank, customer.trim()]
+        .into_iter()
+        .find(|reference| !reference.is_empty() && *reference != "NONREF");
+
+    Ok(Transaction {
+        id: reference.map_or_else(|| format!("MT{:06}", index + 1), str::to_string),
+        posted_at: entry_date.unwrap_or(value_date),
+        executed_at: entry_date.and(value_date.and_hms_opt(0, 0, 0)),
+        kind,
+        status: TransactionStatus::Booked,
+        amount: Money {
+            amount: sign * amount,
+            currency,
+        },
+        description: String::new(),
+        account: None,
+        counterparty: None,
+        category: None,
+        tax_amount: None,
+        tax_rate: None,
+        extra: BTreeMap::new(),
+    })
+}
+
+/// The entry date `MMDD` of a statement line, in the year that puts it
+/// closest to the value date.
+fn entry_date(value_date: NaiveDate, entry: &str) -> Option<NaiveDate> {
+    let month = entry[..2].parse().ok()?;
+    let day = entry[2..].parse().ok()?;
+    let year = match (value_date.month(), month) {
+        (12, 1) => value_date.year() + 1,
+        (1, 12) => value_date.year() - 1,
+        _ => value_date.year(),
+    };
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Parses an amount with a decimal comma, which may end the amount
+/// (`1000,`).
+fn parse_amount(amount: &str) -> Option<Decimal> {
+    let amount = amount.trim().replace(',', ".");
+    let amount = amount.strip_suffix('.').unwrap_or(&amount);
+    Decimal::from_str(amount).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const STATEMENT: &str =
+        "{1:F01BANKNL2AXXXX0000000000}{2:O9401200240201BANKNL2AXXXX00000000002402011200N}{4:
+:20:STMT240131
+:25:NL91ABNA0417164300
+:28C:00001/001
+:13D:2402010600+0100
+:60F:C231231EUR1000,00
+:61:2401020102D25,50NTRFNONREF//B4A02
+:86:Coffee shop
+Amsterdam
+:61:240131C500,NTRFSALARY-JAN
+:86:Salary January
+:61:2401150115RC4,50NCHGNONREF
+:62F:C240131EUR1470,00
+-}
+";
+
+    #[test]
+    fn test_parse_mt940() {
+        let batch = parse_mt940(Cursor::new(STATEMENT), &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.account_id.as_deref(), Some("NL91ABNA0417164300"));
+        assert_eq!(
+            batch.balances,
+            vec![Balance {
+                currency: "EUR".to_string(),
+                opening: Decimal::new(1000, 0),
+                closing: Decimal::new(1470, 0),
+            }]
+        );
+        let period = batch.metadata.period.unwrap();
+        assert_eq!(period.to_string(), "2023-12-31..2024-01-31");
+        assert_eq!(
+            batch.metadata.generated_at,
+            NaiveDate::from_ymd_opt(2024, 2, 1)
+                .unwrap()
+                .and_hms_opt(6, 0, 0)
+        );
+
+        let [coffee, salary, reversal] = &batch.transactions[..] else {
+            panic!("expected three transactions");
+        };
+        assert_eq!(coffee.id, "B4A02");
+        assert_eq!(coffee.kind, TransactionKind::Debit);
+        assert_eq!(coffee.amount.amount, Decimal::new(2550, 2));
+        assert_eq!(coffee.description, "Coffee shop Amsterdam");
+        assert_eq!(salary.id, "SALARY-JAN");
+        assert_eq!(
+            salary.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 31).unwrap()
+        );
+        assert_eq!(salary.executed_at, None);
+        assert_eq!(reversal.id, "MT000003");
+        assert_eq!(reversal.signed_amount(), Decimal::new(-450, 2));
+    }
+
+    #[test]
+    fn test_parse_mt940_max_records() {
+        let options = ParseOptions {
+            max_records: Some(1),
+            ..ParseOptions::default()
+        };
+        let batch = parse_mt940(Cursor::new(STATEMENT), &options).unwrap();
+        assert_eq!(batch.transactions.len(), 1);
+        assert_eq!(batch.balances[0].closing, Decimal::new(1470, 0));
+    }
+
+    #[test]
+    fn test_unbalanced_mt940() {
+        let statement = STATEMENT.replace(":62F:C240131EUR1470,00", ":62F:C240131EUR1480,00");
+        let error = parse_mt940(Cursor::new(statement), &ParseOptions::default()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("= 1470.00, but the closing balance is 1480"));
+
+        let error = parse_mt940(
+            Cursor::new(":20:X\n:61:240102D1,00NTRF\n"),
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("line 2: statement line before the opening balance"));
+    }
+}
@@ -0,0 +1,305 @@
+use super::ParseOptions;
+use crate::{
+    Error, Money, Result, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::str::FromStr;
+
+/// A `GET /accounts/{AccountId}/transactions` (or `GET /transactions`)
+/// response of the UK Open Banking Account and Transaction API.
+///
+/// Field names follow the specification's PascalCase; the camelCase spelling
+/// some aggregators use is accepted as well.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Response {
+    #[serde(alias = "data")]
+    data: Data,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Data {
+    #[serde(default, alias = "transaction", alias = "transactions")]
+    transaction: Vec<ObTransaction>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ObTransaction {
+    #[serde(default, alias = "accountId")]
+    account_id: Option<String>,
+    #[serde(default, alias = "transactionId")]
+    transaction_id: Option<String>,
+    #[serde(default, alias = "transactionReference")]
+    transaction_reference: Option<String>,
+    #[serde(alias = "creditDebitIndicator")]
+    credit_debit_indicator: String,
+    #[serde(default, alias = "status")]
+    status: Option<String>,
+    #[serde(alias = "bookingDateTime", alias = "bookingDate")]
+    booking_date_time: String,
+    #[serde(default, alias = "valueDateTime", alias = "valueDate")]
+    value_date_time: Option<String>,
+    #[serde(default, alias = "transactionInformation")]
+    transaction_information: Option<String>,
+    #[serde(alias = "amount")]
+    amount: ObAmount,
+    #[serde(default, alias = "merchantDetails")]
+    merchant_details: Option<MerchantDetails>,
+    #[serde(default, alias = "creditorAccount")]
+    creditor_account: Option<PartyAccount>,
+    #[serde(default, alias = "debtorAccount")]
+    debtor_account: Option<PartyAccount>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ObAmount {
+    #[serde(alias = "amount")]
+    amount: String,
+    #[serde(alias = "currency")]
+    currency: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MerchantDetails {
+    #[serde(default, alias = "merchantName")]
+    merchant_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PartyAccount {
+    #[serde(default, alias = "name")]
+    name: Option<String>,
+}
+
+/// Parses a UK Open Banking (PSD2) transactions response, as returned by
+/// banks and account aggregators.
+///
+/// Each entry of `Data.Transaction` becomes a transaction:
+/// - `CreditDebitIndicator` gives the kind and `Amount` the amount
+/// - `Status` (`Booked` or `Pending`) gives the [`TransactionStatus`];
+///   entries without one are booked
+/// - the date of `BookingDateTime` is the posting date, and `ValueDateTime`
+///   the execution date (midnight when only a date is given)
+/// - `TransactionInformation` is the description, and `TransactionId` (or
+///   `TransactionReference`) the ID; entries with neither get sequential IDs
+///   (`OB000001`, ...)
+/// - the merchant, or else the creditor of a debit or the debtor of a
+///   credit, is the counterparty, and `AccountId` the account
+///
+/// When all entries belong to one account, it also becomes the batch's
+/// account ID. Dates may be RFC 3339 timestamps or plain `YYYY-MM-DD` dates;
+/// timestamps keep their local time.
+///
+/// The response is held to [`ParseOptions::limits`], and entries past
+/// [`ParseOptions::max_records`] are not converted.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing the JSON response
+/// * `options` - Record and size caps, and currency checks
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the response is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not valid JSON or lacks required fields
+/// - A date, credit/debit indicator, status or amount cannot be parsed
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_open_banking<R: Read>(reader: R, options: &ParseOptions) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| parse_response(reader, options))
+}
+
+fn parse_response<R: Read>(reader: R, options: &ParseOptions) -> Result<TransactionBatch> {
+    let response: Response =
+        serde_json::from_reader(reader).map_err(|e| Error::parse("OpenBanking", e.to_string()))?;
+
+    let mut batch = TransactionBatch::default();
+    let mut accounts = Vec::new();
+    for (index, tx) in response.data.transaction.into_iter().enumerate() {
+        if options.has_max_records(batch.transactions.len()) {
+            break;
+        }
+        if let Some(account) = &tx.account_id {
+            if !accounts.contains(account) {
+                accounts.push(account.clone());
+            }
+        }
+        let transaction = convert(tx, index).map_err(|message| {
+            Error::parse(
+                "OpenBanking",
+                format!("transaction {}: {}", index + 1, message),
+            )
+        })?;
+        batch.transactions.push(transaction);
+    }
+    if accounts.len() == 1 {
+        batch.account_id = accounts.pop();
+    }
+
+    Ok(batch)
+}
+
+fn convert(tx: ObTransaction, index: usize) -> std::result::Result<Transaction, String> {
+    let kind = match tx.credit_debit_indicator.to_ascii_lowercase().as_str() {
+        "credit" => TransactionKind::Credit,
+        "debit" => TransactionKind::Debit,
+        _ => {
+            return Err(format!(
+                "invalid CreditDebitIndicator: {}",
+                tx.credit_debit_indicator
+            ))
+        }
+    };
+    let status = match tx.status.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None | Some("booked") => TransactionStatus::Booked,
+        Some("pending") => TransactionStatus::Pending,
+        Some(_) => return Err(format!("invalid Status: {}", tx.status.unwrap_or_default())),
+    };
+    let posted_at = parse_date_time(&tx.booking_date_time)
+        .map(|dt| dt.date())
+        .ok_or_else(|| format!("invalid BookingDateTime: {}", tx.booking_date_time))?;
+    let executed_at = tx
+        .value_date_time
+        .map(|value| {
+            parse_date_time(&value).ok_or_else(|| format!("invalid ValueDateTime: {}", value))
+        })
+        .transpose()?;
+    let amount =
+        Decimal::from_str(tx.amount.amount.trim()).map_err(|e| format!("invalid Amount: {}", e))?;
+
+    let party = if kind == TransactionKind::Debit {
+        tx.creditor_account
+    } else {
+        tx.debtor_account
+    };
+    let counterparty = tx
+        .merchant_details
+        .and_then(|merchant| merchant.merchant_name)
+        .or_else(|| party.and_then(|party| party.name));
+
+    Ok(Transaction {
+        id: tx
+            .transaction_id
+            .or(tx.transaction_reference)
+            .unwrap_or_else(|| format!("OB{:06}", index + 1)),
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money {
+            amount: amount.abs(),
+            currency: tx.amount.currency,
+        },
+        description: tx.transaction_information.unwrap_or_default(),
+        account: tx.account_id,
+        counterparty,
+        category: None,
+        tax_amount: None,
+        tax_rate: None,
+        extra: BTreeMap::new(),
+    })
+}
+
+/// Parses an RFC 3339 timestamp (keeping its local time), a timestamp
+/// without offset, or a date.
+fn parse_date_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.naive_local())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_open_banking() {
+        let data = r#"{
+            "Data": {
+                "Transaction": [
+                    {
+                        "AccountId": "22289",
+                        "TransactionId": "123",
+                        "TransactionReference": "Ref 1",
+                        "Amount": { "Amount": "10.00", "Currency": "GBP" },
+                        "CreditDebitIndicator": "Credit",
+                        "Status": "Booked",
+                        "BookingDateTime": "2017-04-05T23:43:07+01:00",
+                        "ValueDateTime": "2017-04-06T00:00:00+01:00",
+                        "TransactionInformation": "Cash from Aubrey",
+                        "DebtorAccount": { "Name": "Aubrey" }
+                    },
+                    {
+                        "AccountId": "22289",
+                        "Amount": { "Amount": "-4.99", "Currency": "GBP" },
+                        "CreditDebitIndicator": "Debit",
+                        "Status": "Pending",
+                        "BookingDateTime": "2017-04-07",
+                        "TransactionInformation": "Card payment",
+                        "MerchantDetails": { "MerchantName": "Coffee Shop" }
+                    }
+                ]
+            },
+            "Links": { "Self": "https://api.example.com/accounts/22289/transactions" },
+            "Meta": { "TotalPages": 1 }
+        }"#;
+        let batch = parse_open_banking(Cursor::new(data), &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.account_id.as_deref(), Some("22289"));
+        let cash = &batch.transactions[0];
+        assert_eq!(cash.id, "123");
+        assert_eq!(cash.posted_at, NaiveDate::from_ymd_opt(2017, 4, 5).unwrap());
+        assert_eq!(
+            cash.executed_at,
+            NaiveDate::from_ymd_opt(2017, 4, 6)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+        );
+        assert_eq!(cash.kind, TransactionKind::Credit);
+        assert_eq!(cash.status, TransactionStatus::Booked);
+        assert_eq!(cash.amount.amount, Decimal::new(1000, 2));
+        assert_eq!(cash.description, "Cash from Aubrey");
+        assert_eq!(cash.counterparty.as_deref(), Some("Aubrey"));
+
+        let coffee = &batch.transactions[1];
+        assert_eq!(coffee.id, "OB000002");
+        assert_eq!(coffee.kind, TransactionKind::Debit);
+        assert_eq!(coffee.status, TransactionStatus::Pending);
+        assert_eq!(coffee.amount.amount, Decimal::new(499, 2));
+        assert_eq!(coffee.counterparty.as_deref(), Some("Coffee Shop"));
+    }
+
+    #[test]
+    fn test_parse_open_banking_camel_case() {
+        let data = r#"{"data": {"transaction": [{
+            "amount": {"amount": "1.00", "currency": "EUR"},
+            "creditDebitIndicator": "Sideways",
+            "bookingDate": "2024-01-15"
+        }]}}"#;
+        let err = parse_open_banking(Cursor::new(data), &ParseOptions::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("transaction 1: invalid CreditDebitIndicator: Sideways"));
+    }
+}
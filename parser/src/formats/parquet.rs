@@ -0,0 +1,155 @@
+use crate::arrow::{read_metadata, read_transactions};
+use crate::{Error, Result, TransactionBatch};
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::io::{Read, Write};
+
+/// Parses transaction data from a Parquet file.
+///
+/// Expects the columns of [`TransactionBatch::to_arrow`]; columns other than
+/// `id`, `posted_at`, `kind`, `amount` and `currency` may be missing. The
+/// account ID and balances are read from the file's schema metadata.
+///
+/// Parquet needs random access to the file footer, so the whole input is
+/// read into memory first.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing a Parquet file
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the file is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not a valid Parquet file
+/// - A required column is missing or has an unexpected type
+/// - A value cannot be converted (unknown kind or status, out-of-range decimal)
+pub fn parse_parquet<R: Read>(mut reader: R) -> Result<TransactionBatch> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(data))?;
+    let (account_id, balances) = read_metadata(builder.schema().metadata(), "Parquet")?;
+    let mut batch = TransactionBatch {
+        account_id,
+        transactions: Vec::new(),
+        balances,
+        metadata: Default::default(),
+    };
+
+    for record_batch in builder.build()? {
+        let record_batch = record_batch.map_err(|e| Error::parse("Parquet", e.to_string()))?;
+        read_transactions(&record_batch, &mut batch.transactions, "Parquet")?;
+    }
+
+    Ok(batch)
+}
+
+/// Writes transaction data as a Snappy-compressed Parquet file.
+///
+/// Writes the record batch of [`TransactionBatch::to_arrow`], so dates become
+/// `DATE`, execution times `TIMESTAMP(MICROS)` and amounts `DECIMAL(38, s)`,
+/// where `s` is the largest scale among the column's values. The account ID
+/// and balances are stored in the schema metadata.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the Parquet file to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if an amount doesn't fit the decimal
+/// column or any I/O operation fails.
+pub fn write_parquet<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    let record_batch = batch.to_arrow()?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    // ArrowWriter needs a `Send` sink, which an arbitrary `&mut W` isn't
+    let mut buffer = Vec::new();
+    let mut arrow_writer = ArrowWriter::try_new(&mut buffer, record_batch.schema(), Some(props))?;
+    arrow_writer.write(&record_batch)?;
+    arrow_writer.close()?;
+
+    writer.write_all(&buffer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, Money, Transaction, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parquet_roundtrip() {
+        let tx = |id: &str, amount: Decimal, tax: Option<Decimal>| Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                .unwrap()
+                .and_hms_micro_opt(10, 30, 0, 250),
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount,
+                currency: "RUB".to_string(),
+            },
+            description: "Payment".to_string(),
+            account: None,
+            counterparty: Some("ООО Ромашка".to_string()),
+            category: None,
+            tax_amount: tax,
+            tax_rate: tax.map(|_| Decimal::new(20, 0)),
+            extra: BTreeMap::new(),
+        };
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![
+                tx(
+                    "TX001",
+                    Decimal::new(120050, 2),
+                    Some(Decimal::new(20008, 2)),
+                ),
+                tx("TX002", Decimal::new(15, 1), None),
+                tx("TX003", Decimal::new(1_000_000_001, 4), None),
+            ],
+            balances: vec![Balance {
+                currency: "RUB".to_string(),
+                opening: Decimal::new(500000, 2),
+                closing: Decimal::new(100, 0),
+            }],
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_parquet(&batch, &mut output).unwrap();
+        assert_eq!(&output[..4], b"PAR1");
+
+        let parsed = parse_parquet(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+        assert_eq!(parsed.transactions[1].amount.amount.to_string(), "1.5000");
+    }
+
+    #[test]
+    fn test_parse_parquet_rejects_garbage() {
+        let err = parse_parquet(Cursor::new(b"not parquet".to_vec())).unwrap_err();
+        assert!(matches!(err, Error::Parquet(_)));
+    }
+}
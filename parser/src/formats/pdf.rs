@@ -0,0 +1,353 @@
+use crate::manifest::currency_totals;
+use crate::stats::signed_amount;
+use crate::{Result, TransactionBatch};
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point,
+};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::{BufWriter, Write};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 5.0;
+const FONT_SIZE: f32 = 8.0;
+
+/// Left edges of the transaction table columns, in millimetres.
+const COL_DATE: f32 = MARGIN;
+const COL_ID: f32 = 37.0;
+const COL_DESCRIPTION: f32 = 62.0;
+const COL_CATEGORY: f32 = 135.0;
+/// Right edge of the amount column.
+const COL_AMOUNT_RIGHT: f32 = PAGE_WIDTH - MARGIN;
+
+const DESCRIPTION_WIDTH: usize = 42;
+const CATEGORY_WIDTH: usize = 18;
+
+/// Renders a batch as a printable PDF statement.
+///
+/// The statement is laid out on A4 pages and contains:
+/// - a header with the account, the statement period (first to last posting
+///   date) and per-currency credit, debit and net totals;
+/// - the transaction table, continued across pages with a repeated header;
+/// - a per-category summary of net amounts;
+/// - a `Page N of M` footer on every page.
+///
+/// Debits are shown as negative amounts. The PDF uses the standard Helvetica
+/// font, which only covers Latin-1 text.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to render
+/// * `writer` - A writer to output the PDF document to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`](crate::Error) if rendering or
+/// writing fails.
+pub fn write_pdf<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    let (doc, page, layer) = PdfDocument::new(
+        "Account Statement",
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "Statement",
+    );
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+
+    let layer = doc.get_page(page).get_layer(layer);
+    let mut page = Page {
+        layers: vec![layer.clone()],
+        layer,
+        y: PAGE_HEIGHT - MARGIN,
+        regular,
+        bold,
+    };
+
+    write_header(&mut page, batch);
+    write_transactions(&mut page, &doc, batch);
+    write_category_summary(&mut page, &doc, batch);
+    write_footers(&page);
+
+    doc.save(&mut BufWriter::new(writer))?;
+    Ok(())
+}
+
+/// Current drawing position on the last page of the document.
+struct Page {
+    layer: PdfLayerReference,
+    /// Layers of all pages so far, in page order
+    layers: Vec<PdfLayerReference>,
+    y: f32,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+}
+
+impl Page {
+    fn text(&self, text: &str, x: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer
+            .use_text(text, FONT_SIZE, Mm(x), Mm(self.y), font);
+    }
+
+    fn text_right(&self, text: &str, right: f32, bold: bool) {
+        self.text(text, right - text_width(text, FONT_SIZE), bold);
+    }
+
+    fn rule(&self) {
+        let y = self.y + ROW_HEIGHT - 3.5;
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    fn next_line(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    /// Starts a new page if fewer than `rows` rows fit on the current one.
+    /// Returns `true` if a page break happened.
+    fn ensure_rows(&mut self, doc: &PdfDocumentReference, rows: usize) -> bool {
+        if self.y - rows as f32 * ROW_HEIGHT >= MARGIN {
+            return false;
+        }
+        let (page, layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Statement");
+        self.layer = doc.get_page(page).get_layer(layer);
+        self.layers.push(self.layer.clone());
+        self.y = PAGE_HEIGHT - MARGIN;
+        true
+    }
+}
+
+fn write_header(page: &mut Page, batch: &TransactionBatch) {
+    page.layer.use_text(
+        "Account Statement",
+        16.0,
+        Mm(MARGIN),
+        Mm(page.y),
+        &page.bold,
+    );
+    page.next_line(10.0);
+
+    page.text(
+        &format!("Account: {}", batch.account_id.as_deref().unwrap_or("-")),
+        MARGIN,
+        false,
+    );
+    page.next_line(ROW_HEIGHT);
+
+    let first = batch.transactions.iter().map(|tx| tx.posted_at).min();
+    let last = batch.transactions.iter().map(|tx| tx.posted_at).max();
+    let period = match (first, last) {
+        (Some(first), Some(last)) => format!("{} to {}", first, last),
+        _ => "-".to_string(),
+    };
+    page.text(&format!("Period: {}", period), MARGIN, false);
+    page.next_line(ROW_HEIGHT);
+
+    page.text(
+        &format!("Transactions: {}", batch.transactions.len()),
+        MARGIN,
+        false,
+    );
+    page.next_line(ROW_HEIGHT * 2.0);
+
+    page.text("Currency", MARGIN, true);
+    page.text_right("Credits", 90.0, true);
+    page.text_right("Debits", 130.0, true);
+    page.text_right("Net", COL_AMOUNT_RIGHT, true);
+    page.rule();
+    page.next_line(ROW_HEIGHT);
+
+    for total in currency_totals(batch) {
+        page.text(&total.currency, MARGIN, false);
+        page.text_right(&total.credits.to_string(), 90.0, false);
+        page.text_right(&total.debits.to_string(), 130.0, false);
+        page.text_right(
+            &(total.credits - total.debits).to_string(),
+            COL_AMOUNT_RIGHT,
+            false,
+        );
+        page.next_line(ROW_HEIGHT);
+    }
+
+    page.next_line(ROW_HEIGHT);
+}
+
+fn write_table_header(page: &mut Page) {
+    page.text("Date", COL_DATE, true);
+    page.text("ID", COL_ID, true);
+    page.text("Description", COL_DESCRIPTION, true);
+    page.text("Category", COL_CATEGORY, true);
+    page.text_right("Amount", COL_AMOUNT_RIGHT, true);
+    page.rule();
+    page.next_line(ROW_HEIGHT);
+}
+
+fn write_transactions(page: &mut Page, doc: &PdfDocumentReference, batch: &TransactionBatch) {
+    page.ensure_rows(doc, 2);
+    write_table_header(page);
+
+    for tx in &batch.transactions {
+        if page.ensure_rows(doc, 1) {
+            write_table_header(page);
+        }
+
+        page.text(
+            &tx.posted_at.format("%Y-%m-%d").to_string(),
+            COL_DATE,
+            false,
+        );
+        page.text(&truncate(&tx.id, 12), COL_ID, false);
+        page.text(
+            &truncate(&tx.description, DESCRIPTION_WIDTH),
+            COL_DESCRIPTION,
+            false,
+        );
+        page.text(
+            &truncate(tx.category.as_deref().unwrap_or(""), CATEGORY_WIDTH),
+            COL_CATEGORY,
+            false,
+        );
+        page.text_right(
+            &format!("{} {}", signed_amount(tx), tx.amount.currency),
+            COL_AMOUNT_RIGHT,
+            false,
+        );
+        page.next_line(ROW_HEIGHT);
+    }
+
+    page.next_line(ROW_HEIGHT);
+}
+
+fn write_category_summary(page: &mut Page, doc: &PdfDocumentReference, batch: &TransactionBatch) {
+    let mut categories: BTreeMap<(&str, &str), Decimal> = BTreeMap::new();
+    for tx in &batch.transactions {
+        let category = tx.category.as_deref().unwrap_or("Uncategorized");
+        *categories
+            .entry((category, tx.amount.currency.as_str()))
+            .or_default() += signed_amount(tx);
+    }
+
+    if categories.is_empty() {
+        return;
+    }
+
+    page.ensure_rows(doc, 3);
+    page.text("Category summary", MARGIN, true);
+    page.next_line(ROW_HEIGHT * 1.5);
+
+    for ((category, currency), total) in categories {
+        page.ensure_rows(doc, 1);
+        page.text(&truncate(category, 60), MARGIN, false);
+        page.text_right(&format!("{} {}", total, currency), COL_AMOUNT_RIGHT, false);
+        page.next_line(ROW_HEIGHT);
+    }
+}
+
+/// Numbers every page once the page count is known.
+fn write_footers(page: &Page) {
+    let count = page.layers.len();
+    let y = MARGIN / 2.0;
+    for (index, layer) in page.layers.iter().enumerate() {
+        let text = format!("Page {} of {}", index + 1, count);
+        let x = COL_AMOUNT_RIGHT - text_width(&text, FONT_SIZE);
+        layer.use_text(text, FONT_SIZE, Mm(x), Mm(y), &page.regular);
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(max_chars - 3).collect();
+        truncated.push_str("...");
+        truncated
+    }
+}
+
+/// Approximate rendered width in millimetres, used for right alignment.
+///
+/// Helvetica digits are 0.556 em wide and most other glyphs are close to
+/// that, which is accurate enough for the numeric columns we align.
+fn text_width(text: &str, font_size: f32) -> f32 {
+    const PT_TO_MM: f32 = 0.3528;
+    text.chars().count() as f32 * font_size * 0.556 * PT_TO_MM
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, Transaction, TransactionKind, TransactionStatus};
+    use chrono::NaiveDate;
+
+    fn batch_of(count: usize) -> TransactionBatch {
+        let transactions = (0..count)
+            .map(|i| Transaction {
+                id: format!("TX{:04}", i),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+                    + chrono::Duration::days(i as i64 % 28),
+                executed_at: None,
+                kind: if i % 3 == 0 {
+                    TransactionKind::Credit
+                } else {
+                    TransactionKind::Debit
+                },
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(1050 + i as i64, 2),
+                    currency: "USD".to_string(),
+                },
+                description: "A rather long description that will need truncating".to_string(),
+                account: None,
+                counterparty: None,
+                category: Some(if i % 2 == 0 { "Food" } else { "Rent" }.to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            })
+            .collect();
+
+        TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions,
+            balances: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_write_pdf() {
+        let mut buffer = Vec::new();
+        write_pdf(&batch_of(3), &mut buffer).unwrap();
+
+        assert!(buffer.starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn test_write_pdf_paginates() {
+        let mut short = Vec::new();
+        write_pdf(&batch_of(3), &mut short).unwrap();
+        let mut long = Vec::new();
+        write_pdf(&batch_of(200), &mut long).unwrap();
+
+        let pages = |pdf: &[u8]| {
+            pdf.windows(b"/Type/Pages".len())
+                .filter(|w| w.starts_with(b"/Type/Page") && !w.ends_with(b"s"))
+                .count()
+        };
+        assert!(pages(&long) > pages(&short));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a long description", 10), "a long ...");
+    }
+}
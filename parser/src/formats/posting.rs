@@ -0,0 +1,161 @@
+use crate::stats::signed_amount;
+use crate::{Transaction, TransactionBatch};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How transactions are booked to double-entry accounts.
+///
+/// Every transaction becomes a two-legged entry: one posting to
+/// [`asset_account`](Self::asset_account) and one to the account its
+/// category maps to.
+///
+/// # Examples
+///
+/// ```
+/// use parser::formats::beancount::AccountMapping;
+///
+/// let mut mapping = AccountMapping::default();
+/// mapping
+///     .by_category
+///     .insert("Groceries".to_string(), "Expenses:Food:Groceries".to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountMapping {
+    /// Account the statement belongs to, e.g. `Assets:Bank:Checking`
+    pub asset_account: String,
+    /// Accounts by [`Transaction::category`]
+    pub by_category: BTreeMap<String, String>,
+    /// Account for debits whose category is missing or unmapped
+    pub default_expense: String,
+    /// Account for credits whose category is missing or unmapped
+    pub default_income: String,
+    /// Account the opening balances are booked against
+    pub opening_balances: String,
+}
+
+impl Default for AccountMapping {
+    fn default() -> Self {
+        Self {
+            asset_account: "Assets:Bank".to_string(),
+            by_category: BTreeMap::new(),
+            default_expense: "Expenses:Uncategorized".to_string(),
+            default_income: "Income:Uncategorized".to_string(),
+            opening_balances: "Equity:Opening-Balances".to_string(),
+        }
+    }
+}
+
+impl AccountMapping {
+    /// Returns the account a transaction's counter-posting goes to.
+    pub fn account_for(&self, tx: &Transaction) -> &str {
+        tx.category
+            .as_ref()
+            .and_then(|category| self.by_category.get(category))
+            .map(String::as_str)
+            .unwrap_or(if tx.is_credit() {
+                &self.default_income
+            } else {
+                &self.default_expense
+            })
+    }
+}
+
+/// One leg of an entry.
+pub(crate) struct Posting<'a> {
+    pub account: &'a str,
+    pub amount: Decimal,
+}
+
+/// A balanced two-legged entry in a single currency.
+pub(crate) struct Entry<'a> {
+    pub date: NaiveDate,
+    pub payee: Option<&'a str>,
+    pub narration: &'a str,
+    /// Transaction ID, absent for generated entries
+    pub id: Option<&'a str>,
+    pub currency: &'a str,
+    pub postings: [Posting<'a>; 2],
+}
+
+/// A batch booked as double-entry postings, ready for a journal writer.
+pub(crate) struct Journal<'a> {
+    /// Every account used, sorted
+    pub accounts: BTreeSet<&'a str>,
+    /// First posting date
+    pub first: NaiveDate,
+    /// Last posting date
+    pub last: NaiveDate,
+    /// Opening balance entries, then the transactions by posting date
+    pub entries: Vec<Entry<'a>>,
+}
+
+/// Books a batch with `mapping`. Returns `None` for a batch without
+/// transactions, which has no dates to put entries on.
+pub(crate) fn journal<'a>(
+    batch: &'a TransactionBatch,
+    mapping: &'a AccountMapping,
+) -> Option<Journal<'a>> {
+    let dates = batch.transactions.iter().map(|tx| tx.posted_at);
+    let first = dates.clone().min()?;
+    let last = dates.max()?;
+
+    let mut entries: Vec<Entry> = batch
+        .balances
+        .iter()
+        .filter(|balance| !balance.opening.is_zero())
+        .map(|balance| Entry {
+            date: first,
+            payee: None,
+            narration: "Opening balance",
+            id: None,
+            currency: &balance.currency,
+            postings: [
+                Posting {
+                    account: &mapping.asset_account,
+                    amount: balance.opening,
+                },
+                Posting {
+                    account: &mapping.opening_balances,
+                    amount: -balance.opening,
+                },
+            ],
+        })
+        .collect();
+
+    let mut transactions: Vec<_> = batch.transactions.iter().collect();
+    transactions.sort_by_key(|tx| tx.posted_at);
+    entries.extend(transactions.into_iter().map(|tx| {
+        let amount = signed_amount(tx);
+        Entry {
+            date: tx.posted_at,
+            payee: tx.counterparty.as_deref(),
+            narration: &tx.description,
+            id: Some(&tx.id),
+            currency: &tx.amount.currency,
+            postings: [
+                Posting {
+                    account: &mapping.asset_account,
+                    amount,
+                },
+                Posting {
+                    account: mapping.account_for(tx),
+                    amount: -amount,
+                },
+            ],
+        }
+    }));
+
+    let accounts = entries
+        .iter()
+        .flat_map(|entry| entry.postings.iter().map(|posting| posting.account))
+        .chain([mapping.asset_account.as_str()])
+        .collect();
+
+    Some(Journal {
+        accounts,
+        first,
+        last,
+        entries,
+    })
+}
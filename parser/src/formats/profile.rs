@@ -0,0 +1,839 @@
+use super::csv::parse_delimited_fields;
+use super::ParseOptions;
+use crate::{
+    Error, Money, Result, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Where a field is found in a row: by header name, or by 0-based position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    /// The column whose header is this name (compared case-insensitively)
+    Name(String),
+    /// The column at this position, counted from 0
+    Index(usize),
+}
+
+/// The columns of a bank's CSV export. Fields the export lacks stay `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileColumns {
+    /// Transaction ID; rows get sequential IDs without it
+    pub id: Option<ColumnRef>,
+    /// Posting date (required); rows without one take the execution date
+    pub posted_date: Option<ColumnRef>,
+    /// Execution date and time
+    pub executed_date: Option<ColumnRef>,
+    /// Debit/credit code, for [`SignConvention::KindColumn`]
+    pub kind: Option<ColumnRef>,
+    /// Amount, for every convention but [`SignConvention::SplitColumns`]
+    pub amount: Option<ColumnRef>,
+    /// Outgoing amount, for [`SignConvention::SplitColumns`]
+    pub debit_amount: Option<ColumnRef>,
+    /// Incoming amount, for [`SignConvention::SplitColumns`]
+    pub credit_amount: Option<ColumnRef>,
+    /// Currency code; [`BankProfile::currency`] applies without it
+    pub currency: Option<ColumnRef>,
+    /// Description
+    pub description: Option<ColumnRef>,
+    /// Account
+    pub account: Option<ColumnRef>,
+    /// Counterparty
+    pub counterparty: Option<ColumnRef>,
+    /// Category
+    pub category: Option<ColumnRef>,
+    /// Fee charged on top of the amount; a non-zero fee is appended to the
+    /// description as `(fee 1.50 EUR)`
+    pub fee: Option<ColumnRef>,
+    /// Status, mapped to [`TransactionStatus`] by
+    /// [`BankProfile::pending_statuses`] and
+    /// [`BankProfile::cancelled_statuses`]; rows with one of
+    /// [`BankProfile::skip_statuses`] are skipped
+    pub status: Option<ColumnRef>,
+}
+
+/// How a bank tells debits from credits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignConvention {
+    /// Amounts are signed; negative amounts are debits
+    #[default]
+    NegativeIsDebit,
+    /// Amounts are signed; negative amounts are credits, as on card statements
+    NegativeIsCredit,
+    /// Amounts are unsigned and the `kind` column holds one of
+    /// [`BankProfile::debit_codes`] or [`BankProfile::credit_codes`]
+    KindColumn,
+    /// Debits and credits are in separate `debit_amount` and `credit_amount`
+    /// columns; the non-empty one is used
+    SplitColumns,
+}
+
+/// The CSV dialect of one bank's export: delimiter, header names or column
+/// order, date format, decimal separator and sign convention.
+///
+/// Profiles are usually kept in TOML files:
+///
+/// ```
+/// use parser::formats::profile::BankProfile;
+///
+/// let profile = BankProfile::from_toml(
+///     r#"
+/// name = "Example Bank"
+/// delimiter = ";"
+/// date_format = "%d.%m.%Y"
+/// decimal_separator = ","
+/// currency = "EUR"
+///
+/// [columns]
+/// posted_date = "Buchungstag"
+/// amount = "Betrag"
+/// description = "Verwendungszweck"
+/// counterparty = 3
+/// "#,
+/// )
+/// .unwrap();
+/// assert_eq!(profile.delimiter, ';');
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BankProfile {
+    /// Name of the profile, used in error messages
+    pub name: String,
+    /// Field delimiter
+    pub delimiter: char,
+    /// Number of lines before the header row (or the first record)
+    pub skip_lines: usize,
+    /// Whether the first row after the skipped lines is a header
+    pub has_header: bool,
+    /// Where each field is
+    pub columns: ProfileColumns,
+    /// chrono format of the posting date; a time part is ignored
+    pub date_format: String,
+    /// chrono format of the execution date and time; defaults to
+    /// `date_format`
+    pub datetime_format: Option<String>,
+    /// Decimal separator of amounts; the other of `.` and `,`, spaces and
+    /// apostrophes are taken as digit grouping and dropped
+    pub decimal_separator: char,
+    /// How debits are told from credits
+    pub sign: SignConvention,
+    /// Codes of the `kind` column meaning a debit (case-insensitive)
+    pub debit_codes: Vec<String>,
+    /// Codes of the `kind` column meaning a credit (case-insensitive)
+    pub credit_codes: Vec<String>,
+    /// Currency of rows without a currency column or value
+    pub currency: Option<String>,
+    /// Prefix of generated IDs (`CSV000001`, ...) when there is no ID column
+    pub id_prefix: String,
+    /// Values of the `status` column whose rows are skipped (case-insensitive)
+    pub skip_statuses: Vec<String>,
+    /// Values of the `status` column meaning [`TransactionStatus::Pending`]
+    /// (case-insensitive); other rows are booked
+    pub pending_statuses: Vec<String>,
+    /// Values of the `status` column meaning [`TransactionStatus::Cancelled`],
+    /// such as declined card payments (case-insensitive)
+    pub cancelled_statuses: Vec<String>,
+}
+
+impl Default for BankProfile {
+    fn default() -> Self {
+        Self {
+            name: "CSV".to_string(),
+            delimiter: ',',
+            skip_lines: 0,
+            has_header: true,
+            columns: ProfileColumns::default(),
+            date_format: "%Y-%m-%d".to_string(),
+            datetime_format: None,
+            decimal_separator: '.',
+            sign: SignConvention::default(),
+            debit_codes: vec!["Debit".to_string()],
+            credit_codes: vec!["Credit".to_string()],
+            currency: None,
+            id_prefix: "CSV".to_string(),
+            skip_statuses: Vec::new(),
+            pending_statuses: Vec::new(),
+            cancelled_statuses: Vec::new(),
+        }
+    }
+}
+
+impl BankProfile {
+    /// Reads a profile from TOML text. Keys left out keep their
+    /// [`Default`] values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text isn't a valid profile.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| Error::parse("CSV", format!("invalid profile: {}", e)))
+    }
+
+    /// Returns the built-in profile called `name` (case-insensitive), if any.
+    ///
+    /// Built-in profiles: `tinkoff`, `revolut` and `wise`.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "tinkoff" => Some(Self::tinkoff()),
+            "revolut" => Some(Self::revolut()),
+            "wise" => Some(Self::wise()),
+            _ => None,
+        }
+    }
+
+    /// Profile of Tinkoff bank's card and account statements ("Выписка" in
+    /// CSV): `;`-separated, quoted fields under Cyrillic headers, `,` as the
+    /// decimal separator and signed amounts in the account's currency,
+    /// usually RUB.
+    ///
+    /// The payment date becomes the posting date and the operation date and
+    /// time the execution date. The merchant (`Описание`) is the
+    /// description, the card number the account, and declined
+    /// (`FAILED`) operations are cancelled.
+    pub fn tinkoff() -> Self {
+        let column = |name: &str| Some(ColumnRef::Name(name.to_string()));
+        Self {
+            name: "Tinkoff".to_string(),
+            delimiter: ';',
+            columns: ProfileColumns {
+                posted_date: column("Дата платежа"),
+                executed_date: column("Дата операции"),
+                amount: column("Сумма платежа"),
+                currency: column("Валюта платежа"),
+                description: column("Описание"),
+                account: column("Номер карты"),
+                category: column("Категория"),
+                status: column("Статус"),
+                ..ProfileColumns::default()
+            },
+            date_format: "%d.%m.%Y".to_string(),
+            datetime_format: Some("%d.%m.%Y %H:%M:%S".to_string()),
+            decimal_separator: ',',
+            currency: Some("RUB".to_string()),
+            id_prefix: "TINKOFF".to_string(),
+            cancelled_statuses: vec!["FAILED".to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// Profile of Revolut account statements. Every row carries its own
+    /// currency, so statements of multi-currency accounts keep each pocket's
+    /// currency.
+    ///
+    /// The completion date becomes the posting date (pending operations take
+    /// their start date), the product (`Current`, `Savings`, ...) the account
+    /// and the fee is folded into the description. Pending operations are
+    /// pending, and declined, reverted and failed ones cancelled.
+    pub fn revolut() -> Self {
+        let column = |name: &str| Some(ColumnRef::Name(name.to_string()));
+        Self {
+            name: "Revolut".to_string(),
+            columns: ProfileColumns {
+                posted_date: column("Completed Date"),
+                executed_date: column("Started Date"),
+                amount: column("Amount"),
+                currency: column("Currency"),
+                description: column("Description"),
+                account: column("Product"),
+                fee: column("Fee"),
+                status: column("State"),
+                ..ProfileColumns::default()
+            },
+            date_format: "%Y-%m-%d %H:%M:%S".to_string(),
+            id_prefix: "REVOLUT".to_string(),
+            pending_statuses: vec!["PENDING".to_string()],
+            cancelled_statuses: vec![
+                "DECLINED".to_string(),
+                "REVERTED".to_string(),
+                "FAILED".to_string(),
+            ],
+            ..Self::default()
+        }
+    }
+
+    /// Profile of Wise (formerly TransferWise) statements, one per currency
+    /// balance or for all of them. Each row carries its own currency, and
+    /// the Wise transfer ID is kept as the transaction ID.
+    ///
+    /// Card payments name the merchant, which becomes the counterparty, and
+    /// the total fees are folded into the description.
+    pub fn wise() -> Self {
+        let column = |name: &str| Some(ColumnRef::Name(name.to_string()));
+        Self {
+            name: "Wise".to_string(),
+            columns: ProfileColumns {
+                id: column("TransferWise ID"),
+                posted_date: column("Date"),
+                amount: column("Amount"),
+                currency: column("Currency"),
+                description: column("Description"),
+                counterparty: column("Merchant"),
+                fee: column("Total fees"),
+                ..ProfileColumns::default()
+            },
+            date_format: "%d-%m-%Y".to_string(),
+            id_prefix: "WISE".to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// Reads a profile from a TOML file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or isn't a valid profile.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+}
+
+/// Column positions resolved against the header row.
+struct Positions {
+    id: Option<usize>,
+    posted_date: usize,
+    executed_date: Option<usize>,
+    kind: Option<usize>,
+    amount: Option<usize>,
+    debit_amount: Option<usize>,
+    credit_amount: Option<usize>,
+    currency: Option<usize>,
+    description: Option<usize>,
+    account: Option<usize>,
+    counterparty: Option<usize>,
+    category: Option<usize>,
+    fee: Option<usize>,
+    status: Option<usize>,
+}
+
+impl Positions {
+    fn resolve(profile: &BankProfile, header: Option<&[String]>) -> Result<Self> {
+        let find = |column: &Option<ColumnRef>, name: &str| -> Result<Option<usize>> {
+            match column {
+                None => Ok(None),
+                Some(ColumnRef::Index(index)) => Ok(Some(*index)),
+                Some(ColumnRef::Name(expected)) => header
+                    .and_then(|names| {
+                        names
+                            .iter()
+                            .position(|n| n.trim().to_lowercase() == expected.trim().to_lowercase())
+                    })
+                    .map(Some)
+                    .ok_or_else(|| {
+                        profile_error(profile, format!("missing {} column: {}", name, expected))
+                    }),
+            }
+        };
+        let required = |position: Option<usize>, name: &str| {
+            position.ok_or_else(|| profile_error(profile, format!("no {} column configured", name)))
+        };
+
+        let columns = &profile.columns;
+        let positions = Positions {
+            id: find(&columns.id, "id")?,
+            posted_date: required(find(&columns.posted_date, "posted_date")?, "posted_date")?,
+            executed_date: find(&columns.executed_date, "executed_date")?,
+            kind: find(&columns.kind, "kind")?,
+            amount: find(&columns.amount, "amount")?,
+            debit_amount: find(&columns.debit_amount, "debit_amount")?,
+            credit_amount: find(&columns.credit_amount, "credit_amount")?,
+            currency: find(&columns.currency, "currency")?,
+            description: find(&columns.description, "description")?,
+            account: find(&columns.account, "account")?,
+            counterparty: find(&columns.counterparty, "counterparty")?,
+            category: find(&columns.category, "category")?,
+            fee: find(&columns.fee, "fee")?,
+            status: find(&columns.status, "status")?,
+        };
+
+        match profile.sign {
+            SignConvention::SplitColumns => {
+                required(positions.debit_amount, "debit_amount")?;
+                required(positions.credit_amount, "credit_amount")?;
+            }
+            SignConvention::KindColumn => {
+                required(positions.kind, "kind")?;
+                required(positions.amount, "amount")?;
+            }
+            _ => {
+                required(positions.amount, "amount")?;
+            }
+        }
+        if positions.currency.is_none() && profile.currency.is_none() {
+            return Err(profile_error(
+                profile,
+                "no currency column and no default currency".to_string(),
+            ));
+        }
+        Ok(positions)
+    }
+}
+
+/// Parses a bank's CSV export described by `profile`, stopping after
+/// [`ParseOptions::max_records`] transactions.
+///
+/// Called by [`crate::formats::csv::parse_csv_with_profile`].
+pub(crate) fn parse<R: Read>(
+    reader: R,
+    profile: &BankProfile,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let mut lines = BufReader::new(reader)
+        .lines()
+        .enumerate()
+        .skip(profile.skip_lines);
+
+    let header = if profile.has_header {
+        match lines.next() {
+            Some((_, line)) => Some(
+                parse_delimited_fields(line?.trim_start_matches('\u{feff}'), profile.delimiter)
+                    .into_iter()
+                    .map(|name| name.trim().to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            None => return Ok(TransactionBatch::default()),
+        }
+    } else {
+        None
+    };
+    let positions = Positions::resolve(profile, header.as_deref())?;
+
+    let mut batch = TransactionBatch::default();
+    for (index, line) in lines {
+        if options.has_max_records(batch.transactions.len()) {
+            break;
+        }
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = parse_delimited_fields(&line, profile.delimiter);
+        let status = positions
+            .status
+            .and_then(|i| fields.get(i))
+            .map(|f| f.trim());
+        let listed = |statuses: &[String]| {
+            status.is_some_and(|status| statuses.iter().any(|s| s.eq_ignore_ascii_case(status)))
+        };
+        if listed(&profile.skip_statuses) {
+            continue;
+        }
+        let status = if listed(&profile.pending_statuses) {
+            TransactionStatus::Pending
+        } else if listed(&profile.cancelled_statuses) {
+            TransactionStatus::Cancelled
+        } else {
+            TransactionStatus::Booked
+        };
+        let id = format!("{}{:06}", profile.id_prefix, batch.transactions.len() + 1);
+        let transaction = parse_row(&fields, &positions, profile, id, status).map_err(|message| {
+            profile_error(profile, format!("line {}: {}", index + 1, message))
+        })?;
+        batch.transactions.push(transaction);
+    }
+    Ok(batch)
+}
+
+fn parse_row(
+    fields: &[String],
+    positions: &Positions,
+    profile: &BankProfile,
+    generated_id: String,
+    status: TransactionStatus,
+) -> std::result::Result<Transaction, String> {
+    let field = |position: Option<usize>| {
+        position
+            .and_then(|i| fields.get(i))
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+    };
+    let text = |position: Option<usize>| field(position).map(str::to_string);
+    let amount = |position: Option<usize>| {
+        field(position)
+            .map(|value| {
+                parse_amount(value, profile.decimal_separator)
+                    .ok_or_else(|| format!("invalid amount: {}", value))
+            })
+            .transpose()
+    };
+
+    let executed_at = field(positions.executed_date)
+        .map(|value| {
+            let format = profile
+                .datetime_format
+                .as_deref()
+                .unwrap_or(&profile.date_format);
+            NaiveDateTime::parse_from_str(value, format)
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(value, format).map(|d| d.and_time(Default::default()))
+                })
+                .map_err(|e| format!("invalid executed date {}: {}", value, e))
+        })
+        .transpose()?;
+    // Pending operations have no posting date yet
+    let posted_at = match field(Some(positions.posted_date)) {
+        Some(posted) => parse_date(posted, &profile.date_format)
+            .ok_or_else(|| format!("invalid posted date: {}", posted))?,
+        None => executed_at
+            .map(|executed| executed.date())
+            .ok_or("missing posted date")?,
+    };
+
+    let (kind, value) = match profile.sign {
+        SignConvention::NegativeIsDebit | SignConvention::NegativeIsCredit => {
+            let value = amount(positions.amount)?.ok_or("missing amount")?;
+            let negative_is_debit = profile.sign == SignConvention::NegativeIsDebit;
+            let kind = if value.is_sign_negative() == negative_is_debit {
+                TransactionKind::Debit
+            } else {
+                TransactionKind::Credit
+            };
+            (kind, value.abs())
+        }
+        SignConvention::KindColumn => {
+            let code = field(positions.kind).ok_or("missing transaction type")?;
+            let matches =
+                |codes: &[String]| codes.iter().any(|c| c.trim().eq_ignore_ascii_case(code));
+            let kind = if matches(&profile.debit_codes) {
+                TransactionKind::Debit
+            } else if matches(&profile.credit_codes) {
+                TransactionKind::Credit
+            } else {
+                return Err(format!("invalid transaction type: {}", code));
+            };
+            (
+                kind,
+                amount(positions.amount)?.ok_or("missing amount")?.abs(),
+            )
+        }
+        SignConvention::SplitColumns => {
+            match (
+                amount(positions.debit_amount)?,
+                amount(positions.credit_amount)?,
+            ) {
+                (Some(debit), None) => (TransactionKind::Debit, debit.abs()),
+                (None, Some(credit)) => (TransactionKind::Credit, credit.abs()),
+                (Some(debit), Some(credit)) if debit.is_zero() => {
+                    (TransactionKind::Credit, credit.abs())
+                }
+                (Some(debit), Some(credit)) if credit.is_zero() => {
+                    (TransactionKind::Debit, debit.abs())
+                }
+                (Some(_), Some(_)) => {
+                    return Err("both debit and credit amounts are set".to_string())
+                }
+                (None, None) => return Err("missing amount".to_string()),
+            }
+        }
+    };
+
+    let currency = text(positions.currency)
+        .or_else(|| profile.currency.clone())
+        .ok_or("missing currency")?;
+    let mut description = text(positions.description).unwrap_or_default();
+    if let Some(fee) = amount(positions.fee)?.filter(|fee| !fee.is_zero()) {
+        if !description.is_empty() {
+            description.push(' ');
+        }
+        description.push_str(&format!("(fee {} {})", fee.abs(), currency));
+    }
+
+    Ok(Transaction {
+        id: text(positions.id).unwrap_or(generated_id),
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money {
+            amount: value,
+            currency,
+        },
+        description,
+        account: text(positions.account),
+        counterparty: text(positions.counterparty),
+        category: text(positions.category),
+        tax_amount: None,
+        tax_rate: None,
+        extra: BTreeMap::new(),
+    })
+}
+
+/// Parses a date, ignoring a time part if the format has one.
+fn parse_date(value: &str, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, format).ok().or_else(|| {
+        NaiveDateTime::parse_from_str(value, format)
+            .ok()
+            .map(|dt| dt.date())
+    })
+}
+
+/// Parses an amount with the given decimal separator, dropping digit
+/// grouping and a leading `+`.
+pub(crate) fn parse_amount(value: &str, decimal_separator: char) -> Option<Decimal> {
+    let normalized: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'' && *c != '+')
+        .filter(|c| *c == decimal_separator || !matches!(c, '.' | ','))
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    Decimal::from_str(&normalized).ok()
+}
+
+fn profile_error(profile: &BankProfile, message: String) -> Error {
+    Error::parse("CSV", format!("{}: {}", profile.name, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_with_profile() {
+        let profile = BankProfile::from_toml(
+            r#"
+name = "Example Bank"
+delimiter = ";"
+skip_lines = 1
+date_format = "%d.%m.%Y"
+decimal_separator = ","
+currency = "EUR"
+
+[columns]
+posted_date = "Buchungstag"
+amount = "Betrag"
+description = "Verwendungszweck"
+counterparty = 1
+"#,
+        )
+        .unwrap();
+        let data = "Kontoauszug 01/2024\n\
+                    Buchungstag;Empfänger;Verwendungszweck;Betrag\n\
+                    15.01.2024;Vermieter;\"Miete; Januar\";-1.200,50\n\
+                    31.01.2024;Arbeitgeber;Gehalt;3.000,00\n";
+        let batch = parse(Cursor::new(data), &profile, &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.transactions.len(), 2);
+        let rent = &batch.transactions[0];
+        assert_eq!(rent.id, "CSV000001");
+        assert_eq!(
+            rent.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+        assert_eq!(rent.kind, TransactionKind::Debit);
+        assert_eq!(rent.amount.amount, Decimal::new(120050, 2));
+        assert_eq!(rent.amount.currency, "EUR");
+        assert_eq!(rent.description, "Miete; Januar");
+        assert_eq!(rent.counterparty.as_deref(), Some("Vermieter"));
+        assert_eq!(batch.transactions[1].kind, TransactionKind::Credit);
+
+        let err = parse(
+            Cursor::new("x\nDate;Amount\n"),
+            &profile,
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Example Bank: missing posted_date column: Buchungstag"));
+    }
+
+    #[test]
+    fn test_parse_split_columns_and_kind_codes() {
+        let profile = BankProfile {
+            has_header: false,
+            sign: SignConvention::SplitColumns,
+            currency: Some("USD".to_string()),
+            columns: ProfileColumns {
+                posted_date: Some(ColumnRef::Index(0)),
+                debit_amount: Some(ColumnRef::Index(1)),
+                credit_amount: Some(ColumnRef::Index(2)),
+                ..ProfileColumns::default()
+            },
+            ..BankProfile::default()
+        };
+        let batch = parse(
+            Cursor::new("2024-01-15,10.00,\n2024-01-16,,5\n"),
+            &profile,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(batch.transactions[0].kind, TransactionKind::Debit);
+        assert_eq!(batch.transactions[1].kind, TransactionKind::Credit);
+
+        let profile = BankProfile {
+            sign: SignConvention::KindColumn,
+            debit_codes: vec!["DR".to_string()],
+            credit_codes: vec!["CR".to_string()],
+            columns: ProfileColumns {
+                posted_date: Some(ColumnRef::Name("Date".to_string())),
+                kind: Some(ColumnRef::Name("D/C".to_string())),
+                amount: Some(ColumnRef::Name("Amount".to_string())),
+                currency: Some(ColumnRef::Name("Ccy".to_string())),
+                ..ProfileColumns::default()
+            },
+            ..BankProfile::default()
+        };
+        let data = "Date,D/C,Amount,Ccy\n2024-01-15,cr,\"1,000.00\",GBP\n2024-01-16,XX,1,GBP\n";
+        let err = parse(Cursor::new(data), &profile, &ParseOptions::default()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("line 3: invalid transaction type: XX"));
+    }
+
+    #[test]
+    fn test_parse_tinkoff() {
+        let data = "\"Дата операции\";\"Дата платежа\";\"Номер карты\";\"Статус\";\"Сумма операции\";\"Валюта операции\";\"Сумма платежа\";\"Валюта платежа\";\"Кэшбэк\";\"Категория\";\"MCC\";\"Описание\";\"Бонусы (включая кэшбэк)\"\n\
+                    \"16.01.2024 19:42:07\";\"17.01.2024\";\"*1234\";\"OK\";\"-1 250,90\";\"RUB\";\"-1 250,90\";\"RUB\";\"\";\"Супермаркеты\";\"5411\";\"Пятёрочка\";\"12,00\"\n\
+                    \"16.01.2024 20:01:00\";\"\";\"*1234\";\"FAILED\";\"-99,00\";\"RUB\";\"-99,00\";\"RUB\";\"\";\"Кино\";\"7832\";\"Кинотеатр\";\"0,00\"\n\
+                    \"18.01.2024 09:00:00\";\"\";\"\";\"OK\";\"50000,00\";\"RUB\";\"50000,00\";\"RUB\";\"\";\"Пополнения\";\"\";\"Зарплата\";\"0,00\"\n";
+        let profile = BankProfile::builtin("Tinkoff").unwrap();
+        let batch = parse(Cursor::new(data), &profile, &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.transactions.len(), 3);
+        let shop = &batch.transactions[0];
+        assert_eq!(shop.id, "TINKOFF000001");
+        assert_eq!(
+            shop.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 17).unwrap()
+        );
+        assert_eq!(
+            shop.executed_at,
+            NaiveDate::from_ymd_opt(2024, 1, 16)
+                .unwrap()
+                .and_hms_opt(19, 42, 7)
+        );
+        assert_eq!(shop.kind, TransactionKind::Debit);
+        assert_eq!(shop.amount.amount, Decimal::new(125090, 2));
+        assert_eq!(shop.amount.currency, "RUB");
+        assert_eq!(shop.description, "Пятёрочка");
+        assert_eq!(shop.account.as_deref(), Some("*1234"));
+        assert_eq!(shop.category.as_deref(), Some("Супермаркеты"));
+        assert_eq!(shop.status, TransactionStatus::Booked);
+
+        let declined = &batch.transactions[1];
+        assert_eq!(declined.status, TransactionStatus::Cancelled);
+        assert_eq!(
+            declined.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 16).unwrap()
+        );
+
+        let salary = &batch.transactions[2];
+        assert_eq!(salary.kind, TransactionKind::Credit);
+        assert_eq!(
+            salary.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 18).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_with_profile_limits() {
+        let profile = BankProfile {
+            currency: Some("EUR".to_string()),
+            columns: ProfileColumns {
+                posted_date: Some(ColumnRef::Name("Date".to_string())),
+                amount: Some(ColumnRef::Name("Amount".to_string())),
+                ..ProfileColumns::default()
+            },
+            ..BankProfile::default()
+        };
+        let data = "Date,Amount\n2024-01-15,-1\n2024-01-16,2\nnot a date,3\n";
+        let options = ParseOptions {
+            max_records: Some(2),
+            ..ParseOptions::default()
+        };
+        let batch =
+            crate::formats::csv::parse_csv_with_profile(data.as_bytes(), &profile, &options)
+                .unwrap();
+        assert_eq!(batch.transactions.len(), 2);
+
+        let options = ParseOptions {
+            limits: crate::Limits {
+                max_transactions: Some(1),
+                ..crate::Limits::default()
+            },
+            ..ParseOptions::default()
+        };
+        let err = crate::formats::csv::parse_csv_with_profile(
+            &data.as_bytes()[..39],
+            &profile,
+            &options,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_)));
+    }
+
+    #[test]
+    fn test_parse_tinkoff_windows_1251() {
+        let data = "\"Дата операции\";\"Дата платежа\";\"Номер карты\";\"Статус\";\"Сумма платежа\";\"Валюта платежа\";\"Категория\";\"Описание\"\n\
+                    \"16.01.2024 19:42:07\";\"17.01.2024\";\"*1234\";\"OK\";\"-1 250,90\";\"RUB\";\"Супермаркеты\";\"Пятёрочка\"\n";
+        let (encoded, _, _) = encoding_rs::WINDOWS_1251.encode(data);
+        let profile = BankProfile::tinkoff();
+        let batch = crate::formats::csv::parse_csv_with_profile(
+            &encoded[..],
+            &profile,
+            &crate::ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(batch.transactions[0].description, "Пятёрочка");
+        assert_eq!(batch.transactions[0].amount.amount, Decimal::new(125090, 2));
+    }
+
+    #[test]
+    fn test_parse_revolut_and_wise() {
+        let data = "Type,Product,Started Date,Completed Date,Description,Amount,Fee,Currency,State,Balance\n\
+                    CARD_PAYMENT,Current,2024-01-15 10:23:45,2024-01-16 08:00:01,Cafe,-4.50,0.00,EUR,COMPLETED,95.50\n\
+                    EXCHANGE,Current,2024-01-16 12:00:00,2024-01-16 12:00:00,Exchanged to USD,-100.00,0.50,EUR,COMPLETED,-5.00\n\
+                    EXCHANGE,Current,2024-01-16 12:00:00,2024-01-16 12:00:00,Exchanged from EUR,108.20,0.00,USD,COMPLETED,108.20\n\
+                    CARD_PAYMENT,Current,2024-01-17 09:00:00,,Shop,-20.00,0.00,EUR,DECLINED,\n\
+                    CARD_PAYMENT,Current,2024-01-18 18:30:00,,Taxi,-12.40,0.00,EUR,PENDING,\n";
+        let options = ParseOptions::default();
+        let batch = parse(Cursor::new(data), &BankProfile::revolut(), &options).unwrap();
+        assert_eq!(batch.transactions.len(), 5);
+        assert_eq!(batch.transactions[0].status, TransactionStatus::Booked);
+        assert_eq!(batch.transactions[3].status, TransactionStatus::Cancelled);
+        let taxi = &batch.transactions[4];
+        assert_eq!(taxi.status, TransactionStatus::Pending);
+        assert_eq!(taxi.posted_at, NaiveDate::from_ymd_opt(2024, 1, 18).unwrap());
+        assert_eq!(taxi.amount.amount, Decimal::new(1240, 2));
+        assert_eq!(
+            batch.transactions[0].posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 16).unwrap()
+        );
+        assert_eq!(batch.transactions[0].description, "Cafe");
+        assert_eq!(
+            batch.transactions[1].description,
+            "Exchanged to USD (fee 0.50 EUR)"
+        );
+        assert_eq!(batch.transactions[2].kind, TransactionKind::Credit);
+        assert_eq!(batch.transactions[2].amount.currency, "USD");
+
+        let data = "\"TransferWise ID\",Date,Amount,Currency,Description,\"Payment Reference\",\"Running Balance\",\"Exchange From\",\"Exchange To\",\"Exchange Rate\",\"Payer Name\",\"Payee Name\",\"Payee Account Number\",Merchant,\"Card Last Four Digits\",\"Card Holder Full Name\",Attachment,Note,\"Total fees\"\n\
+                    TRANSFER-123,15-01-2024,-250.00,GBP,\"Sent money to Jane Doe\",rent,750.00,,,,,\"Jane Doe\",12345678,,,,,,1.23\n\
+                    CARD-456,16-01-2024,-3.20,GBP,\"Card transaction of 3.20 GBP issued by Coffee Ltd\",,746.80,,,,,,,\"Coffee Ltd\",4321,\"J Smith\",,,0.00\n";
+        let wise = BankProfile::builtin("wise").unwrap();
+        let batch = parse(Cursor::new(data), &wise, &options).unwrap();
+        let transfer = &batch.transactions[0];
+        assert_eq!(transfer.id, "TRANSFER-123");
+        assert_eq!(
+            transfer.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+        assert_eq!(
+            transfer.description,
+            "Sent money to Jane Doe (fee 1.23 GBP)"
+        );
+        assert_eq!(
+            batch.transactions[1].counterparty.as_deref(),
+            Some("Coffee Ltd")
+        );
+    }
+}
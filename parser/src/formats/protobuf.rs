@@ -0,0 +1,297 @@
+use crate::{
+    Balance, Error, Money, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use prost::Message;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Messages generated from `proto/transactions.proto`.
+#[allow(missing_docs, clippy::all)]
+mod pb {
+    include!(concat!(env!("OUT_DIR"), "/ypbank.transactions.v1.rs"));
+}
+
+/// The published schema, for services that generate their own bindings.
+pub const SCHEMA: &str = include_str!("../../proto/transactions.proto");
+
+/// Parses transaction data encoded as a `ypbank.transactions.v1.TransactionBatch`
+/// protobuf message.
+///
+/// The schema is published in `proto/transactions.proto` (also available as
+/// [`SCHEMA`]); dates and amounts are strings in the same layout as the text
+/// format.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing one encoded message
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the message is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not a valid encoded message
+/// - A transaction has an unspecified kind
+/// - A date or amount cannot be parsed
+pub fn parse_protobuf<R: Read>(mut reader: R) -> Result<TransactionBatch> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let message = pb::TransactionBatch::decode(data.as_slice())
+        .map_err(|e| Error::parse("Protobuf", e.to_string()))?;
+
+    let balances = message
+        .balances
+        .into_iter()
+        .map(|balance| {
+            Ok(Balance {
+                opening: parse_decimal(&balance.opening, "opening balance")?,
+                closing: parse_decimal(&balance.closing, "closing balance")?,
+                currency: balance.currency,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let transactions = message
+        .transactions
+        .into_iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            from_message(tx)
+                .map_err(|e| e.prefixed("Protobuf", &format!("transaction {}: ", index + 1)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(TransactionBatch {
+        account_id: message.account_id,
+        transactions,
+        balances,
+        metadata: Default::default(),
+    })
+}
+
+fn from_message(tx: pb::Transaction) -> Result<Transaction> {
+    let mut amount = parse_decimal(&tx.amount, "amount")?;
+    let kind = match pb::TransactionKind::try_from(tx.kind) {
+        Ok(pb::TransactionKind::Debit) => TransactionKind::Debit,
+        Ok(pb::TransactionKind::Credit) => TransactionKind::Credit,
+        Ok(pb::TransactionKind::Transfer) => TransactionKind::Transfer,
+        Ok(pb::TransactionKind::Fee) => TransactionKind::Fee,
+        Ok(pb::TransactionKind::Interest) => TransactionKind::Interest,
+        Ok(pb::TransactionKind::Refund) => TransactionKind::Refund,
+        Ok(pb::TransactionKind::Reversal) => TransactionKind::Reversal,
+        Ok(pb::TransactionKind::Unspecified) => {
+            return Err(Error::parse(
+                "Protobuf",
+                format!("invalid kind: {}", tx.kind),
+            ))
+        }
+        // A kind added by a newer schema: keep the direction of the amount
+        Err(_) => {
+            let (kind, unsigned) = TransactionKind::from_sign(amount);
+            amount = unsigned;
+            kind
+        }
+    };
+
+    let status = match pb::TransactionStatus::try_from(tx.status) {
+        Ok(pb::TransactionStatus::Booked) => TransactionStatus::Booked,
+        Ok(pb::TransactionStatus::Pending) => TransactionStatus::Pending,
+        Ok(pb::TransactionStatus::Cancelled) => TransactionStatus::Cancelled,
+        Err(_) => {
+            return Err(Error::parse(
+                "Protobuf",
+                format!("invalid status: {}", tx.status),
+            ))
+        }
+    };
+
+    let posted_at = NaiveDate::parse_from_str(&tx.posted_at, "%Y-%m-%d").map_err(|e| {
+        Error::parse("Protobuf", format!("invalid posted_at: {}", e)).with_source(e)
+    })?;
+
+    let executed_at = tx
+        .executed_at
+        .map(|s| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+        .transpose()
+        .map_err(|e| {
+            Error::parse("Protobuf", format!("invalid executed_at: {}", e)).with_source(e)
+        })?;
+
+    Ok(Transaction {
+        id: tx.id,
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money {
+            amount,
+            currency: tx.currency,
+        },
+        description: tx.description,
+        account: tx.account,
+        counterparty: tx.counterparty,
+        category: tx.category,
+        tax_amount: tx
+            .tax_amount
+            .map(|v| parse_decimal(&v, "tax_amount"))
+            .transpose()?,
+        tax_rate: tx
+            .tax_rate
+            .map(|v| parse_decimal(&v, "tax_rate"))
+            .transpose()?,
+        extra: BTreeMap::new(),
+    })
+}
+
+fn parse_decimal(value: &str, name: &str) -> Result<Decimal> {
+    Decimal::from_str(value)
+        .map_err(|e| Error::parse("Protobuf", format!("invalid {}: {}", name, e)).with_source(e))
+}
+
+fn to_message(tx: &Transaction) -> pb::Transaction {
+    let kind = match tx.kind {
+        TransactionKind::Debit => pb::TransactionKind::Debit,
+        TransactionKind::Credit => pb::TransactionKind::Credit,
+        TransactionKind::Transfer => pb::TransactionKind::Transfer,
+        TransactionKind::Fee => pb::TransactionKind::Fee,
+        TransactionKind::Interest => pb::TransactionKind::Interest,
+        TransactionKind::Refund => pb::TransactionKind::Refund,
+        TransactionKind::Reversal => pb::TransactionKind::Reversal,
+    };
+    let status = match tx.status {
+        TransactionStatus::Booked => pb::TransactionStatus::Booked,
+        TransactionStatus::Pending => pb::TransactionStatus::Pending,
+        TransactionStatus::Cancelled => pb::TransactionStatus::Cancelled,
+    };
+
+    pb::Transaction {
+        id: tx.id.clone(),
+        posted_at: tx.posted_at.format("%Y-%m-%d").to_string(),
+        executed_at: tx
+            .executed_at
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string()),
+        kind: kind.into(),
+        amount: tx.amount.amount.to_string(),
+        currency: tx.amount.currency.clone(),
+        description: tx.description.clone(),
+        account: tx.account.clone(),
+        counterparty: tx.counterparty.clone(),
+        category: tx.category.clone(),
+        tax_amount: tx.tax_amount.map(|v| v.to_string()),
+        tax_rate: tx.tax_rate.map(|v| v.to_string()),
+        status: status.into(),
+    }
+}
+
+/// Writes transaction data as one encoded
+/// `ypbank.transactions.v1.TransactionBatch` protobuf message.
+///
+/// The message is written without a length prefix, so the output is exactly
+/// what other protobuf implementations expect to decode.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the encoded message to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_protobuf<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    let message = pb::TransactionBatch {
+        account_id: batch.account_id.clone(),
+        transactions: batch.transactions.iter().map(to_message).collect(),
+        balances: batch
+            .balances
+            .iter()
+            .map(|balance| pb::Balance {
+                currency: balance.currency.clone(),
+                opening: balance.opening.to_string(),
+                closing: balance.closing.to_string(),
+            })
+            .collect(),
+    };
+    writer.write_all(&message.encode_to_vec())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_protobuf_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![Transaction {
+                id: "TX001".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                    .unwrap()
+                    .and_hms_opt(10, 30, 0),
+                kind: TransactionKind::Credit,
+                status: TransactionStatus::Pending,
+                amount: Money {
+                    amount: Decimal::new(120050, 2),
+                    currency: "RUB".to_string(),
+                },
+                description: "Salary".to_string(),
+                account: None,
+                counterparty: Some("Employer".to_string()),
+                category: None,
+                tax_amount: None,
+                tax_rate: Some(Decimal::new(0, 0)),
+                extra: BTreeMap::new(),
+            }],
+            balances: vec![Balance {
+                currency: "RUB".to_string(),
+                opening: Decimal::new(0, 2),
+                closing: Decimal::new(120050, 2),
+            }],
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_protobuf(&batch, &mut output).unwrap();
+        let parsed = parse_protobuf(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+    }
+
+    #[test]
+    fn test_parse_protobuf_errors() {
+        let message = pb::TransactionBatch {
+            account_id: None,
+            transactions: vec![pb::Transaction {
+                id: "TX1".to_string(),
+                posted_at: "2024-01-15".to_string(),
+                amount: "1".to_string(),
+                currency: "USD".to_string(),
+                ..Default::default()
+            }],
+            balances: Vec::new(),
+        };
+        let err = parse_protobuf(Cursor::new(message.encode_to_vec())).unwrap_err();
+        assert!(err.to_string().contains("transaction 1: invalid kind: 0"));
+
+        let err = parse_protobuf(Cursor::new(vec![0xff, 0xff])).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse {
+                format: "Protobuf",
+                ..
+            }
+        ));
+    }
+}
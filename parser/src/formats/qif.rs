@@ -0,0 +1,471 @@
+use super::ParseOptions;
+use crate::{
+    Error, Money, Result, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+
+/// Account types whose records are plain bank-style transactions.
+const BANK_TYPES: [&str; 5] = ["Bank", "Cash", "CCard", "Oth A", "Oth L"];
+
+/// Which kind of records the current `!` header introduced.
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    None,
+    Account,
+    Transactions,
+}
+
+/// One split line group (`S`, `E`, `$`).
+#[derive(Default)]
+struct Split {
+    category: Option<String>,
+    memo: Option<String>,
+    amount: Option<Decimal>,
+}
+
+/// The fields of one `^`-terminated record.
+#[derive(Default)]
+struct Record {
+    start_line: usize,
+    date: Option<String>,
+    amount: Option<Decimal>,
+    number: Option<String>,
+    payee: Option<String>,
+    memo: Option<String>,
+    category: Option<String>,
+    splits: Vec<Split>,
+}
+
+impl Record {
+    fn is_empty(&self) -> bool {
+        self.start_line == 0
+    }
+
+    fn into_transaction(self, currency: &str, index: usize) -> Result<Transaction> {
+        let line_num = self.start_line;
+        let date = self.date.ok_or_else(|| {
+            Error::parse("QIF", format!("line {}: record has no date (D)", line_num))
+        })?;
+        let posted_at = parse_date(&date).ok_or_else(|| {
+            Error::parse("QIF", format!("line {}: invalid date: {}", line_num, date))
+        })?;
+
+        let amount = match self.amount {
+            Some(amount) => amount,
+            None if self.splits.iter().any(|s| s.amount.is_some()) => {
+                self.splits.iter().filter_map(|s| s.amount).sum()
+            }
+            None => {
+                return Err(Error::parse(
+                    "QIF",
+                    format!("line {}: record has no amount (T)", line_num),
+                ))
+            }
+        };
+
+        let mut split_categories: Vec<String> = Vec::new();
+        for category in self.splits.iter().filter_map(|s| s.category.clone()) {
+            if !split_categories.contains(&category) {
+                split_categories.push(category);
+            }
+        }
+        let category = self
+            .category
+            .or_else(|| (!split_categories.is_empty()).then(|| split_categories.join(", ")));
+
+        let description = self.memo.unwrap_or_else(|| {
+            self.splits
+                .iter()
+                .filter_map(|s| s.memo.as_deref())
+                .filter(|memo| !memo.is_empty())
+                .collect::<Vec<_>>()
+                .join("; ")
+        });
+
+        Ok(Transaction {
+            id: self.number.unwrap_or_else(|| format!("QIF{:06}", index)),
+            posted_at,
+            executed_at: None,
+            kind: if amount.is_sign_negative() {
+                TransactionKind::Debit
+            } else {
+                TransactionKind::Credit
+            },
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: amount.abs(),
+                currency: currency.to_string(),
+            },
+            description,
+            account: None,
+            counterparty: self.payee,
+            category,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        })
+    }
+}
+
+/// Parses a QIF date: `MM/DD/YYYY`, `MM/DD/YY`, or Quicken's `M/D'YY` for
+/// years from 2000, with optional padding spaces (` 1/ 5'24`).
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let millennium = value.contains('\'');
+    let mut parts = value.split(['/', '\'', '-', '.']);
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    let mut year: i32 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    if year < 100 {
+        year += if millennium { 2000 } else { 1900 };
+    }
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+fn parse_amount(value: &str, line_num: usize) -> Result<Decimal> {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| *c != ',' && !c.is_whitespace())
+        .collect();
+    Decimal::from_str(&cleaned).map_err(|e| {
+        Error::parse("QIF", format!("line {}: invalid amount: {}", line_num, e)).with_source(e)
+    })
+}
+
+/// Parses transaction data in Quicken Interchange Format.
+///
+/// Reads the bank-style sections (`!Type:Bank`, `!Type:Cash`, `!Type:CCard`,
+/// `!Type:Oth A`, `!Type:Oth L`); an `!Account` block sets the batch account
+/// from its `N` line. Within a record, `D` is the date, `T` (or `U`) the
+/// signed amount, `N` the check number, used as the transaction ID, `P` the
+/// payee, `M` the memo and `L` the category. Split lines (`S`, `E`, `$`) are
+/// collapsed into the single transaction: without a `T` line the amount is
+/// the sum of the splits, and the split categories and memos fill in a
+/// missing `L` and `M`. Other line codes are ignored.
+///
+/// QIF carries no currency, so every amount is given `currency`. Records
+/// without a check number get sequential IDs (`QIF000001`, ...).
+///
+/// Reading stops once [`ParseOptions::max_records`] transactions are in,
+/// and the input and batch are held to [`ParseOptions::limits`].
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing QIF data
+/// * `currency` - The currency code to assign to every transaction
+/// * `options` - Record and size caps, and currency checks
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the file is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - A record appears before any `!Type` header, or the header names an
+///   investment or list section
+/// - A record has no date or amount, or either cannot be parsed
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_qif<R: Read>(
+    reader: R,
+    currency: &str,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| parse_records(reader, currency, options))
+}
+
+fn parse_records<R: Read>(
+    reader: R,
+    currency: &str,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let mut batch = TransactionBatch::default();
+    let mut section = Section::None;
+    let mut record = Record::default();
+
+    for (index, line_result) in BufReader::new(reader).lines().enumerate() {
+        if options.has_max_records(batch.transactions.len()) {
+            break;
+        }
+        let line_num = index + 1;
+        let line = line_result?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('!') {
+            finish_record(std::mem::take(&mut record), section, currency, &mut batch)?;
+            let header = header.trim();
+            if header.eq_ignore_ascii_case("Account") {
+                section = Section::Account;
+            } else if let Some(kind) = header.strip_prefix("Type:") {
+                if !BANK_TYPES.contains(&kind.trim()) {
+                    return Err(Error::parse(
+                        "QIF",
+                        format!("line {}: unsupported section type: {}", line_num, kind),
+                    ));
+                }
+                section = Section::Transactions;
+            } else if !header.starts_with("Option:") && !header.starts_with("Clear:") {
+                return Err(Error::parse(
+                    "QIF",
+                    format!("line {}: unknown header: !{}", line_num, header),
+                ));
+            }
+            continue;
+        }
+
+        if section == Section::None {
+            return Err(Error::parse(
+                "QIF",
+                format!("line {}: expected a !Type header", line_num),
+            ));
+        }
+
+        let mut chars = line.chars();
+        let code = chars.next().unwrap_or_default();
+        let value = chars.as_str().trim();
+
+        if code == '^' {
+            finish_record(std::mem::take(&mut record), section, currency, &mut batch)?;
+            continue;
+        }
+        if record.is_empty() {
+            record.start_line = line_num;
+        }
+        // Account blocks reuse the letters with other meanings (`T` is the
+        // account type); only the name is of interest
+        if section == Section::Account {
+            if code == 'N' {
+                record.number = Some(value.to_string());
+            }
+            continue;
+        }
+
+        match code {
+            'D' => record.date = Some(value.to_string()),
+            'T' => record.amount = Some(parse_amount(value, line_num)?),
+            'U' if record.amount.is_none() => record.amount = Some(parse_amount(value, line_num)?),
+            'N' => record.number = Some(value.to_string()),
+            'P' => record.payee = Some(value.to_string()),
+            'M' => record.memo = Some(value.to_string()),
+            'L' => record.category = Some(value.to_string()),
+            'S' => record.splits.push(Split {
+                category: Some(value.to_string()),
+                ..Split::default()
+            }),
+            'E' | '$' => {
+                if record.splits.is_empty() {
+                    record.splits.push(Split::default());
+                }
+                let split = record.splits.last_mut().expect("split was just pushed");
+                if code == 'E' {
+                    split.memo = Some(value.to_string());
+                } else {
+                    split.amount = Some(parse_amount(value, line_num)?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Tolerate a final record without the closing `^`
+    finish_record(record, section, currency, &mut batch)?;
+    Ok(batch)
+}
+
+fn finish_record(
+    record: Record,
+    section: Section,
+    currency: &str,
+    batch: &mut TransactionBatch,
+) -> Result<()> {
+    if record.is_empty() {
+        return Ok(());
+    }
+    match section {
+        Section::Account => batch.account_id = record.number,
+        _ => {
+            let index = batch.transactions.len() + 1;
+            batch
+                .transactions
+                .push(record.into_transaction(currency, index)?);
+        }
+    }
+    Ok(())
+}
+
+/// Writes transaction data in Quicken Interchange Format.
+///
+/// Produces a `!Type:Bank` section, preceded by an `!Account` block when the
+/// batch has an account ID. Debits are written as negative amounts and the
+/// transaction ID as the check number (`N`). QIF has no place for currencies,
+/// execution times, accounts or tax, so those are not written.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the QIF data to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_qif<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    if let Some(account) = &batch.account_id {
+        writeln!(writer, "!Account")?;
+        writeln!(writer, "N{}", account)?;
+        writeln!(writer, "TBank")?;
+        writeln!(writer, "^")?;
+    }
+    writeln!(writer, "!Type:Bank")?;
+
+    for tx in &batch.transactions {
+        let amount = tx.signed_amount();
+        writeln!(writer, "D{}", tx.posted_at.format("%m/%d/%Y"))?;
+        writeln!(writer, "T{}", amount)?;
+        writeln!(writer, "N{}", tx.id)?;
+        if let Some(counterparty) = &tx.counterparty {
+            writeln!(writer, "P{}", counterparty)?;
+        }
+        if !tx.description.is_empty() {
+            writeln!(writer, "M{}", tx.description)?;
+        }
+        if let Some(category) = &tx.category {
+            writeln!(writer, "L{}", category)?;
+        }
+        writeln!(writer, "^")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_qif_with_splits() {
+        let data = "\
+!Account
+NEveryday Checking
+TBank
+^
+!Type:Bank
+D 1/15'24
+T-1,200.50
+N1042
+PGrocery Store
+MWeekly shopping
+LGroceries
+^
+D01/16/2024
+PSupermarket
+SGroceries
+EFood
+$-30.00
+SHousehold
+ECleaning
+$-12.50
+^
+D01/17/2024
+U2,500.00
+T2,500.00
+PEmployer
+";
+        let batch = parse_qif(Cursor::new(data), "USD", &ParseOptions::default()).unwrap();
+        assert_eq!(batch.account_id.as_deref(), Some("Everyday Checking"));
+        assert_eq!(batch.transactions.len(), 3);
+
+        let first = &batch.transactions[0];
+        assert_eq!(first.id, "1042");
+        assert_eq!(
+            first.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+        assert_eq!(first.kind, TransactionKind::Debit);
+        assert_eq!(first.amount.amount, Decimal::new(120050, 2));
+        assert_eq!(first.amount.currency, "USD");
+
+        let split = &batch.transactions[1];
+        assert_eq!(split.id, "QIF000002");
+        assert_eq!(split.amount.amount, Decimal::new(4250, 2));
+        assert_eq!(split.category.as_deref(), Some("Groceries, Household"));
+        assert_eq!(split.description, "Food; Cleaning");
+
+        assert_eq!(batch.transactions[2].kind, TransactionKind::Credit);
+
+        let err = parse_qif(
+            Cursor::new("!Type:Invst\nD01/01/2024\n^\n"),
+            "USD",
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unsupported section type"));
+    }
+
+    #[test]
+    fn test_parse_qif_limits() {
+        let data = "!Type:Bank\nD01/15/2024\nT-1.00\n^\nD01/16/2024\nT-2.00\n^\nD01/17/2024\nnot a record\n";
+        let options = ParseOptions {
+            max_records: Some(2),
+            ..ParseOptions::default()
+        };
+        let batch = parse_qif(Cursor::new(data), "USD", &options).unwrap();
+        assert_eq!(batch.transactions.len(), 2);
+
+        let options = ParseOptions {
+            limits: crate::Limits {
+                max_input_bytes: Some(16),
+                ..crate::Limits::default()
+            },
+            ..ParseOptions::default()
+        };
+        let err = parse_qif(Cursor::new(data), "USD", &options).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_)));
+    }
+
+    #[test]
+    fn test_qif_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![Transaction {
+                id: "TX001".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(120050, 2),
+                    currency: "RUB".to_string(),
+                },
+                description: "Payment".to_string(),
+                account: None,
+                counterparty: Some("ООО Ромашка".to_string()),
+                category: Some("Services".to_string()),
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            }],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_qif(&batch, &mut output).unwrap();
+        let parsed = parse_qif(Cursor::new(output), "RUB", &ParseOptions::default()).unwrap();
+        assert_eq!(parsed, batch);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    Error, Money, Result, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use sqlx::mysql::MySqlPool;
+use sqlx::postgres::PgPool;
+use sqlx::{QueryBuilder, Row};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Maximum number of rows sent in a single multi-row `INSERT` statement.
+///
+/// Keeps the bound parameter count well below the limits of both
+/// PostgreSQL (65535) and MySQL (65535) for the (at most thirteen) columns we
+/// write.
+const INSERT_CHUNK_SIZE: usize = 1000;
+
+/// A connection pool to one of the supported database backends.
+///
+/// The backend is selected from the DSN scheme: `postgres://` / `postgresql://`
+/// or `mysql://` / `mariadb://`.
+#[derive(Debug, Clone)]
+pub enum SqlPool {
+    /// PostgreSQL connection pool
+    Postgres(PgPool),
+    /// MySQL / MariaDB connection pool
+    MySql(MySqlPool),
+}
+
+impl SqlPool {
+    /// Connects to the database identified by `dsn`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedFormat`] for an unknown DSN scheme and
+    /// [`Error::Database`] if the connection cannot be established.
+    pub async fn connect(dsn: &str) -> Result<Self> {
+        let scheme = dsn.split("://").next().unwrap_or_default();
+        match scheme {
+            "postgres" | "postgresql" => Ok(Self::Postgres(PgPool::connect(dsn).await?)),
+            "mysql" | "mariadb" => Ok(Self::MySql(MySqlPool::connect(dsn).await?)),
+            _ => Err(Error::UnsupportedFormat(format!(
+                "database scheme '{}'",
+                scheme
+            ))),
+        }
+    }
+}
+
+/// Maps transaction fields onto the columns of an existing table.
+///
+/// Table and column names are interpolated into SQL verbatim, so they must
+/// come from trusted configuration, never from user input. Amounts are bound
+/// as `NUMERIC`/`DECIMAL`, dates as `DATE` and `TIMESTAMP`/`DATETIME`, and the
+/// transaction kind as text (`Debit` / `Credit`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableSchema {
+    /// Table name, optionally schema-qualified
+    pub table: String,
+    /// Column holding the transaction ID
+    pub id: String,
+    /// Column holding the posting date
+    pub posted_at: String,
+    /// Column holding the (nullable) execution timestamp
+    pub executed_at: String,
+    /// Column holding the transaction kind
+    pub kind: String,
+    /// Column holding the amount
+    pub amount: String,
+    /// Column holding the currency code
+    pub currency: String,
+    /// Column holding the description
+    pub description: String,
+    /// Column holding the (nullable) account
+    pub account: String,
+    /// Column holding the (nullable) counterparty
+    pub counterparty: String,
+    /// Column holding the (nullable) category
+    pub category: String,
+    /// Column holding the (nullable) tax amount; not stored when `None`
+    pub tax_amount: Option<String>,
+    /// Column holding the (nullable) tax rate; not stored when `None`
+    pub tax_rate: Option<String>,
+    /// Column holding the transaction status; not stored when `None`, in which
+    /// case every row is read back as booked
+    pub status: Option<String>,
+}
+
+impl TableSchema {
+    /// Creates a schema for `table` whose columns are named after the
+    /// [`Transaction`] fields.
+    ///
+    /// The tax and status columns are left unset, since most existing tables
+    /// don't have them.
+    pub fn new(table: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            id: "id".to_string(),
+            posted_at: "posted_at".to_string(),
+            executed_at: "executed_at".to_string(),
+            kind: "kind".to_string(),
+            amount: "amount".to_string(),
+            currency: "currency".to_string(),
+            description: "description".to_string(),
+            account: "account".to_string(),
+            counterparty: "counterparty".to_string(),
+            category: "category".to_string(),
+            tax_amount: None,
+            tax_rate: None,
+            status: None,
+        }
+    }
+
+    fn insert_prefix(&self) -> String {
+        let mut optional_columns = String::new();
+        for column in [&self.tax_amount, &self.tax_rate, &self.status]
+            .into_iter()
+            .flatten()
+        {
+            optional_columns.push_str(", ");
+            optional_columns.push_str(column);
+        }
+
+        format!(
+            "INSERT INTO {} ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}{}) ",
+            self.table,
+            self.id,
+            self.posted_at,
+            self.executed_at,
+            self.kind,
+            self.amount,
+            self.currency,
+            self.description,
+            self.account,
+            self.counterparty,
+            self.category,
+            optional_columns
+        )
+    }
+}
+
+impl Default for TableSchema {
+    fn default() -> Self {
+        Self::new("transactions")
+    }
+}
+
+/// Bulk-inserts all transactions of a batch into the table described by `schema`.
+///
+/// Rows are sent in multi-row `INSERT` statements inside a single database
+/// transaction, so either the whole batch is stored or nothing is.
+///
+/// # Returns
+///
+/// Returns the number of inserted rows.
+///
+/// # Errors
+///
+/// Returns [`Error::Database`] if any statement fails.
+pub async fn insert_batch(
+    pool: &SqlPool,
+    batch: &TransactionBatch,
+    schema: &TableSchema,
+) -> Result<u64> {
+    macro_rules! insert_chunks {
+        ($pool:expr, $db:ty) => {{
+            let mut db_tx = $pool.begin().await?;
+            let mut inserted = 0;
+            for chunk in batch.transactions.chunks(INSERT_CHUNK_SIZE) {
+                let mut builder = QueryBuilder::<$db>::new(schema.insert_prefix());
+                builder.push_values(chunk, |mut row, tx| {
+                    row.push_bind(tx.id.as_str())
+                        .push_bind(tx.posted_at)
+                        .push_bind(tx.executed_at)
+                        .push_bind(tx.kind.to_string())
+                        .push_bind(tx.amount.amount)
+                        .push_bind(tx.amount.currency.as_str())
+                        .push_bind(tx.description.as_str())
+                        .push_bind(tx.account.as_deref())
+                        .push_bind(tx.counterparty.as_deref())
+                        .push_bind(tx.category.as_deref());
+                    if schema.tax_amount.is_some() {
+                        row.push_bind(tx.tax_amount);
+                    }
+                    if schema.tax_rate.is_some() {
+                        row.push_bind(tx.tax_rate);
+                    }
+                    if schema.status.is_some() {
+                        row.push_bind(tx.status.to_string());
+                    }
+                });
+                inserted += builder.build().execute(&mut *db_tx).await?.rows_affected();
+            }
+            db_tx.commit().await?;
+            Ok(inserted)
+        }};
+    }
+
+    match pool {
+        SqlPool::Postgres(pool) => insert_chunks!(pool, sqlx::Postgres),
+        SqlPool::MySql(pool) => insert_chunks!(pool, sqlx::MySql),
+    }
+}
+
+/// Runs `query` and reads the resulting rows back into a batch.
+///
+/// The result set must expose columns named as in `schema`; a typical query is
+/// `SELECT * FROM transactions WHERE account = 'ACC123' ORDER BY posted_at`.
+/// The returned batch has no `account_id`.
+///
+/// # Errors
+///
+/// Returns [`Error::Database`] if the query fails or a column is missing or
+/// has an incompatible type, and [`Error::Parse`] if a `kind` or `status`
+/// value is not valid.
+pub async fn query_batch(
+    pool: &SqlPool,
+    query: &str,
+    schema: &TableSchema,
+) -> Result<TransactionBatch> {
+    macro_rules! read_rows {
+        ($pool:expr) => {{
+            let rows = sqlx::query(query).fetch_all($pool).await?;
+            let mut transactions = Vec::with_capacity(rows.len());
+            for row in &rows {
+                let kind: String = row.try_get(schema.kind.as_str())?;
+                let status = match &schema.status {
+                    Some(column) => {
+                        let status: String = row.try_get(column.as_str())?;
+                        TransactionStatus::from_str(&status).map_err(|e| {
+                            Error::parse("SQL", format!("invalid status '{}': {}", status, e))
+                        })?
+                    }
+                    None => TransactionStatus::Booked,
+                };
+                transactions.push(Transaction {
+                    id: row.try_get(schema.id.as_str())?,
+                    posted_at: row.try_get::<NaiveDate, _>(schema.posted_at.as_str())?,
+                    executed_at: row
+                        .try_get::<Option<NaiveDateTime>, _>(schema.executed_at.as_str())?,
+                    kind: TransactionKind::from_str(&kind).map_err(|e| {
+                        Error::parse("SQL", format!("invalid transaction type '{}': {}", kind, e))
+                    })?,
+                    status,
+                    amount: Money {
+                        amount: row.try_get::<Decimal, _>(schema.amount.as_str())?,
+                        currency: row.try_get(schema.currency.as_str())?,
+                    },
+                    description: row.try_get(schema.description.as_str())?,
+                    account: row.try_get(schema.account.as_str())?,
+                    counterparty: row.try_get(schema.counterparty.as_str())?,
+                    category: row.try_get(schema.category.as_str())?,
+                    tax_amount: match &schema.tax_amount {
+                        Some(column) => row.try_get::<Option<Decimal>, _>(column.as_str())?,
+                        None => None,
+                    },
+                    tax_rate: match &schema.tax_rate {
+                        Some(column) => row.try_get::<Option<Decimal>, _>(column.as_str())?,
+                        None => None,
+                    },
+                    extra: BTreeMap::new(),
+                });
+            }
+            transactions
+        }};
+    }
+
+    let transactions = match pool {
+        SqlPool::Postgres(pool) => read_rows!(pool),
+        SqlPool::MySql(pool) => read_rows!(pool),
+    };
+
+    Ok(TransactionBatch {
+        account_id: None,
+        transactions,
+        balances: Vec::new(),
+        metadata: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_prefix_uses_schema_names() {
+        let mut schema = TableSchema::new("ledger.entries");
+        schema.id = "tx_id".to_string();
+
+        let prefix = schema.insert_prefix();
+        assert!(prefix.starts_with("INSERT INTO ledger.entries (tx_id, posted_at,"));
+        assert!(prefix.ends_with("category) "));
+
+        schema.tax_rate = Some("vat_rate".to_string());
+        assert!(schema.insert_prefix().ends_with("category, vat_rate) "));
+
+        schema.status = Some("state".to_string());
+        assert!(schema
+            .insert_prefix()
+            .ends_with("category, vat_rate, state) "));
+    }
+
+    #[test]
+    fn test_connect_rejects_unknown_scheme() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(SqlPool::connect("sqlite://statements.db"));
+        assert!(matches!(result, Err(Error::UnsupportedFormat(_))));
+    }
+}
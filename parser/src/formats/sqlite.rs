@@ -0,0 +1,593 @@
+use super::ParseOptions;
+use crate::{
+    Balance, Error, Money, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::{ConnectOptions, Connection, Row};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use tokio::runtime::Runtime;
+
+/// Tables created in a new file.
+///
+/// Amounts are stored as `TEXT` so they keep their exact value and scale;
+/// SQLite still treats them as numbers in arithmetic such as `SUM(amount)`.
+/// `amount` is unsigned for every kind but transfers and reversals, so
+/// `signed_amount` repeats it with the sign of
+/// [`Transaction::signed_amount`], negative for money leaving the account,
+/// for sums over debits and credits together. It is derived, and ignored
+/// when reading.
+/// Dates use the `YYYY-MM-DD` / `YYYY-MM-DD HH:MM:SS` layout understood by
+/// SQLite's date functions.
+const CREATE_TABLES: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    id TEXT NOT NULL,
+    posted_at TEXT NOT NULL,
+    executed_at TEXT,
+    kind TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    signed_amount TEXT,
+    currency TEXT NOT NULL,
+    description TEXT NOT NULL,
+    account TEXT,
+    counterparty TEXT,
+    category TEXT,
+    tax_amount TEXT,
+    tax_rate TEXT,
+    status TEXT NOT NULL DEFAULT 'Booked'
+);
+CREATE TABLE IF NOT EXISTS batch_metadata (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS balances (
+    currency TEXT PRIMARY KEY,
+    opening TEXT NOT NULL,
+    closing TEXT NOT NULL
+);
+";
+
+/// Adds the `status` column to the `transactions` table of files written
+/// before it existed; their transactions are booked.
+const ADD_STATUS_COLUMN: &str =
+    "ALTER TABLE transactions ADD COLUMN status TEXT NOT NULL DEFAULT 'Booked'";
+
+/// Adds the `signed_amount` column to the `transactions` table of files
+/// written before it existed, filling it in from the kinds and amounts.
+const ADD_SIGNED_AMOUNT_COLUMN: &str = "
+ALTER TABLE transactions ADD COLUMN signed_amount TEXT;
+UPDATE transactions SET signed_amount = CASE
+    WHEN kind IN ('Debit', 'Fee') THEN '-' || amount
+    ELSE amount
+END;
+";
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// Reads a batch from the `transactions` table of an SQLite file.
+///
+/// Transactions are returned in insertion order. The account ID and balances
+/// come from the `batch_metadata` and `balances` tables when present.
+///
+/// # Arguments
+///
+/// * `path` - Path to the SQLite database file
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all stored transactions, or an [`Error`]
+/// if the file cannot be read.
+///
+/// # Errors
+///
+/// Returns [`Error::Database`] if the file cannot be opened or lacks the
+/// `transactions` table, and [`Error::Parse`] if a stored value is invalid.
+pub fn read_sqlite_path<P: AsRef<Path>>(path: P) -> Result<TransactionBatch> {
+    read_sqlite_path_with(path, &ParseOptions::default())
+}
+
+/// Reads a batch from an SQLite file with the given options, as
+/// [`crate::parse_path_with`] does for `Format::Sqlite`.
+///
+/// [`ParseOptions::limits`] cap the file size and the batch, no more than
+/// [`ParseOptions::max_records`] rows are queried, and currencies are checked
+/// as [`ParseOptions::iso_currencies`] asks. Like binary input, SQLite files
+/// aren't text, so the CSV and text options don't apply to them.
+///
+/// # Errors
+///
+/// Besides the errors of [`read_sqlite_path`], returns
+/// [`Error::LimitExceeded`] when a cap is exceeded and, with the `signing`
+/// feature, an error when `ParseOptions::verifying_key` is set, since SQLite
+/// files carry no signature to check.
+pub fn read_sqlite_path_with<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let path = path.as_ref();
+    #[cfg(feature = "signing")]
+    if options.verifying_key.is_some() {
+        return Err(Error::parse("SQLite", "the data is not signed"));
+    }
+    if let Some(limit) = options.limits.max_input_bytes {
+        if fs::metadata(path)?.len() > limit {
+            return Err(Error::LimitExceeded(format!(
+                "input longer than {} bytes",
+                limit
+            )));
+        }
+    }
+    let batch = runtime()?.block_on(async {
+        let mut conn = SqliteConnectOptions::new()
+            .filename(path)
+            .read_only(true)
+            .connect()
+            .await?;
+        read_batch(&mut conn, options.max_records).await
+    })?;
+    options.finish_batch(batch)
+}
+
+/// Writes a batch into a new SQLite file, replacing any existing file.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if an existing file cannot be removed and
+/// [`Error::Database`] if writing fails.
+pub fn write_sqlite_path<P: AsRef<Path>>(batch: &TransactionBatch, path: P) -> Result<()> {
+    match fs::remove_file(path.as_ref()) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    append_sqlite_path(batch, path)
+}
+
+/// Appends a batch to an SQLite file, creating the file and tables if needed.
+///
+/// The transactions are inserted in one database transaction. The account ID
+/// is recorded if the file has none yet, and for each currency of
+/// [`TransactionBatch::balances`] the stored closing balance is replaced,
+/// while an already stored opening balance is kept. Appending one statement
+/// period after another thus leaves the file balanced.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to append
+/// * `path` - Path to the SQLite database file
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// Returns [`Error::Database`] if the file cannot be opened or written.
+pub fn append_sqlite_path<P: AsRef<Path>>(batch: &TransactionBatch, path: P) -> Result<()> {
+    runtime()?.block_on(async {
+        let mut conn = SqliteConnectOptions::new()
+            .filename(path.as_ref())
+            .create_if_missing(true)
+            .connect()
+            .await?;
+        insert_batch(&mut conn, batch).await?;
+        conn.close().await?;
+        Ok(())
+    })
+}
+
+/// Creates the tables if needed and inserts `batch` into them, as
+/// [`append_sqlite_path`] does.
+pub(crate) async fn insert_batch(
+    conn: &mut SqliteConnection,
+    batch: &TransactionBatch,
+) -> Result<()> {
+    sqlx::raw_sql(CREATE_TABLES).execute(&mut *conn).await?;
+    if !column_exists(conn, "transactions", "status").await? {
+        sqlx::raw_sql(ADD_STATUS_COLUMN).execute(&mut *conn).await?;
+    }
+    if !column_exists(conn, "transactions", "signed_amount").await? {
+        sqlx::raw_sql(ADD_SIGNED_AMOUNT_COLUMN)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let mut db_tx = conn.begin().await?;
+    if let Some(account) = &batch.account_id {
+        sqlx::query("INSERT OR IGNORE INTO batch_metadata (key, value) VALUES ('account_id', ?)")
+            .bind(account)
+            .execute(&mut *db_tx)
+            .await?;
+    }
+    for balance in &batch.balances {
+        sqlx::query(
+            "INSERT INTO balances (currency, opening, closing) VALUES (?, ?, ?) \
+                 ON CONFLICT (currency) DO UPDATE SET closing = excluded.closing",
+        )
+        .bind(&balance.currency)
+        .bind(balance.opening.to_string())
+        .bind(balance.closing.to_string())
+        .execute(&mut *db_tx)
+        .await?;
+    }
+    for tx in &batch.transactions {
+        sqlx::query(
+            "INSERT INTO transactions (id, posted_at, executed_at, kind, amount, signed_amount, \
+                 currency, description, account, counterparty, category, tax_amount, tax_rate, \
+                 status) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&tx.id)
+        .bind(tx.posted_at.format("%Y-%m-%d").to_string())
+        .bind(
+            tx.executed_at
+                .map(|dt| dt.format(DATETIME_FORMAT).to_string()),
+        )
+        .bind(tx.kind.to_string())
+        .bind(tx.amount.amount.to_string())
+        .bind(tx.signed_amount().to_string())
+        .bind(&tx.amount.currency)
+        .bind(&tx.description)
+        .bind(tx.account.as_deref())
+        .bind(tx.counterparty.as_deref())
+        .bind(tx.category.as_deref())
+        .bind(tx.tax_amount.map(|v| v.to_string()))
+        .bind(tx.tax_rate.map(|v| v.to_string()))
+        .bind(tx.status.to_string())
+        .execute(&mut *db_tx)
+        .await?;
+    }
+    db_tx.commit().await?;
+    Ok(())
+}
+
+/// Parses a batch from an SQLite file read from `reader`.
+///
+/// SQLite needs a real file, so the data is first copied to a temporary
+/// file. Prefer [`read_sqlite_path`] (which [`crate::parse_path`] uses) when
+/// the data is already on disk.
+///
+/// # Errors
+///
+/// See [`read_sqlite_path`].
+pub fn parse_sqlite<R: Read>(reader: R) -> Result<TransactionBatch> {
+    parse_sqlite_with(reader, &ParseOptions::default())
+}
+
+/// Parses a batch from an SQLite file read from `reader` with the given
+/// options, as [`read_sqlite_path_with`] does.
+///
+/// # Errors
+///
+/// See [`read_sqlite_path_with`].
+pub fn parse_sqlite_with<R: Read>(
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    io::copy(&mut reader, &mut file)?;
+    file.flush()?;
+    read_sqlite_path_with(file.path(), options)
+}
+
+/// Writes a batch as an SQLite file to `writer`.
+///
+/// The database is built in a temporary file and then copied to `writer`.
+/// Prefer [`write_sqlite_path`] (which [`crate::write_path`] uses) when
+/// writing to disk.
+///
+/// # Errors
+///
+/// See [`write_sqlite_path`].
+pub fn write_sqlite<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("transactions.sqlite");
+    write_sqlite_path(batch, &path)?;
+    io::copy(&mut fs::File::open(&path)?, writer)?;
+    Ok(())
+}
+
+async fn read_batch(
+    conn: &mut SqliteConnection,
+    max_records: Option<usize>,
+) -> Result<TransactionBatch> {
+    let account_id = if table_exists(conn, "batch_metadata").await? {
+        sqlx::query("SELECT value FROM batch_metadata WHERE key = 'account_id'")
+            .fetch_optional(&mut *conn)
+            .await?
+            .map(|row| row.try_get::<String, _>("value"))
+            .transpose()?
+    } else {
+        None
+    };
+
+    let mut balances = Vec::new();
+    if table_exists(conn, "balances").await? {
+        let rows = sqlx::query("SELECT currency, opening, closing FROM balances ORDER BY currency")
+            .fetch_all(&mut *conn)
+            .await?;
+        for row in &rows {
+            balances.push(Balance {
+                currency: row.try_get("currency")?,
+                opening: decimal(row.try_get("opening")?, "opening balance")?,
+                closing: decimal(row.try_get("closing")?, "closing balance")?,
+            });
+        }
+    }
+
+    let status = match column_exists(conn, "transactions", "status").await? {
+        true => "status",
+        false => "'Booked' AS status",
+    };
+    let query = format!(
+        "SELECT id, posted_at, executed_at, kind, CAST(amount AS TEXT) AS amount, currency, \
+         description, account, counterparty, category, CAST(tax_amount AS TEXT) AS tax_amount, \
+         CAST(tax_rate AS TEXT) AS tax_rate, {} FROM transactions ORDER BY rowid LIMIT ?",
+        status
+    );
+    // A negative limit is no limit
+    let limit = max_records.map_or(-1, |max| i64::try_from(max).unwrap_or(i64::MAX));
+    let rows = sqlx::query(&query)
+        .bind(limit)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let mut transactions = Vec::with_capacity(rows.len());
+    for (index, row) in rows.iter().enumerate() {
+        let row_error =
+            |message: String| Error::parse("SQLite", format!("row {}: {}", index + 1, message));
+        let posted_at: String = row.try_get("posted_at")?;
+        let executed_at: Option<String> = row.try_get("executed_at")?;
+        let kind: String = row.try_get("kind")?;
+        let status: String = row.try_get("status")?;
+        let tax_amount: Option<String> = row.try_get("tax_amount")?;
+        let tax_rate: Option<String> = row.try_get("tax_rate")?;
+
+        transactions.push(Transaction {
+            id: row.try_get("id")?,
+            posted_at: NaiveDate::parse_from_str(&posted_at, "%Y-%m-%d")
+                .map_err(|e| row_error(format!("invalid posted_at: {}", e)).with_source(e))?,
+            executed_at: executed_at
+                .map(|s| NaiveDateTime::parse_from_str(&s, DATETIME_FORMAT))
+                .transpose()
+                .map_err(|e| row_error(format!("invalid executed_at: {}", e)).with_source(e))?,
+            kind: TransactionKind::from_str(&kind)
+                .map_err(|e| row_error(format!("invalid kind: {}", e)))?,
+            status: TransactionStatus::from_str(&status)
+                .map_err(|e| row_error(format!("invalid status: {}", e)))?,
+            amount: Money {
+                amount: decimal(row.try_get("amount")?, "amount")
+                    .map_err(|e| row_error(e.to_string()))?,
+                currency: row.try_get("currency")?,
+            },
+            description: row.try_get("description")?,
+            account: row.try_get("account")?,
+            counterparty: row.try_get("counterparty")?,
+            category: row.try_get("category")?,
+            tax_amount: tax_amount.map(|v| decimal(v, "tax_amount")).transpose()?,
+            tax_rate: tax_rate.map(|v| decimal(v, "tax_rate")).transpose()?,
+            extra: BTreeMap::new(),
+        });
+    }
+
+    Ok(TransactionBatch {
+        account_id,
+        transactions,
+        balances,
+        metadata: Default::default(),
+    })
+}
+
+async fn table_exists(conn: &mut SqliteConnection, name: &str) -> Result<bool> {
+    Ok(
+        sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(name)
+            .fetch_optional(conn)
+            .await?
+            .is_some(),
+    )
+}
+
+async fn column_exists(conn: &mut SqliteConnection, table: &str, name: &str) -> Result<bool> {
+    Ok(
+        sqlx::query("SELECT 1 FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(name)
+            .fetch_optional(conn)
+            .await?
+            .is_some(),
+    )
+}
+
+fn decimal(value: String, name: &str) -> Result<Decimal> {
+    // Values typed in by hand may have been stored as REAL, e.g. `1e2`
+    Decimal::from_str(&value)
+        .or_else(|_| Decimal::from_scientific(&value))
+        .map_err(|e| Error::parse("SQLite", format!("invalid {}: {}", name, e)).with_source(e))
+}
+
+pub(crate) fn runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn tx(id: &str, amount: Decimal) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                .unwrap()
+                .and_hms_opt(10, 30, 0),
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount,
+                currency: "RUB".to_string(),
+            },
+            description: "Payment".to_string(),
+            account: None,
+            counterparty: Some("ООО Ромашка".to_string()),
+            category: None,
+            tax_amount: None,
+            tax_rate: Some(Decimal::new(20, 0)),
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_sqlite_roundtrip_through_streams() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![
+                tx("TX001", Decimal::new(120050, 2)),
+                tx("TX002", Decimal::new(1, 0)),
+            ],
+            balances: vec![Balance {
+                currency: "RUB".to_string(),
+                opening: Decimal::new(500000, 2),
+                closing: Decimal::new(379850, 2),
+            }],
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_sqlite(&batch, &mut output).unwrap();
+        assert!(output.starts_with(b"SQLite format 3\0"));
+
+        let parsed = parse_sqlite(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+        assert_eq!(parsed.transactions[0].amount.amount.to_string(), "1200.50");
+    }
+
+    #[test]
+    fn test_append_sqlite_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        let balance = |opening, closing| Balance {
+            currency: "RUB".to_string(),
+            opening: Decimal::new(opening, 0),
+            closing: Decimal::new(closing, 0),
+        };
+
+        let january = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![tx("TX001", Decimal::new(10, 0))],
+            balances: vec![balance(100, 90)],
+            metadata: Default::default(),
+        };
+        let february = TransactionBatch {
+            account_id: None,
+            transactions: vec![tx("TX002", Decimal::new(20, 0))],
+            balances: vec![balance(90, 70)],
+            metadata: Default::default(),
+        };
+        append_sqlite_path(&january, &path).unwrap();
+        append_sqlite_path(&february, &path).unwrap();
+
+        let batch = read_sqlite_path(&path).unwrap();
+        assert_eq!(batch.account_id.as_deref(), Some("ACC123"));
+        assert_eq!(batch.transactions.len(), 2);
+        assert_eq!(batch.transactions[1].id, "TX002");
+        assert_eq!(batch.balances, vec![balance(100, 70)]);
+
+        write_sqlite_path(&february, &path).unwrap();
+        assert_eq!(read_sqlite_path(&path).unwrap().transactions.len(), 1);
+    }
+
+    #[test]
+    fn test_read_sqlite_path_with_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ledger.sqlite");
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx("TX001", Decimal::new(10, 0)),
+                tx("TX002", Decimal::new(20, 0)),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        write_sqlite_path(&batch, &path).unwrap();
+
+        let options = ParseOptions {
+            max_records: Some(1),
+            ..ParseOptions::default()
+        };
+        let parsed = read_sqlite_path_with(&path, &options).unwrap();
+        assert_eq!(parsed.transactions, batch.transactions[..1]);
+
+        let mut options = ParseOptions::default();
+        options.limits.max_input_bytes = Some(16);
+        let err = read_sqlite_path_with(&path, &options).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded(_)));
+    }
+
+    #[test]
+    fn test_files_without_status_or_signed_amount_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.sqlite");
+        runtime().unwrap().block_on(async {
+            let mut conn = SqliteConnectOptions::new()
+                .filename(&path)
+                .create_if_missing(true)
+                .connect()
+                .await
+                .unwrap();
+            let old_file = CREATE_TABLES
+                .replace(",\n    status TEXT NOT NULL DEFAULT 'Booked'", "")
+                .replace("\n    signed_amount TEXT,", "");
+            sqlx::raw_sql(&old_file).execute(&mut conn).await.unwrap();
+            sqlx::raw_sql(
+                "INSERT INTO transactions (id, posted_at, kind, amount, currency, description) \
+                 VALUES ('TX001', '2024-01-15', 'Debit', '10', 'RUB', 'Old')",
+            )
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        });
+        let batch = read_sqlite_path(&path).unwrap();
+        assert_eq!(batch.transactions[0].status, TransactionStatus::Booked);
+
+        let mut pending = tx("TX002", Decimal::new(20, 0));
+        pending.status = TransactionStatus::Pending;
+        let batch = TransactionBatch {
+            transactions: vec![pending],
+            ..TransactionBatch::default()
+        };
+        append_sqlite_path(&batch, &path).unwrap();
+        let statuses: Vec<_> = read_sqlite_path(&path)
+            .unwrap()
+            .transactions
+            .iter()
+            .map(|tx| tx.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            [TransactionStatus::Booked, TransactionStatus::Pending]
+        );
+
+        let signed: Vec<String> = runtime().unwrap().block_on(async {
+            let mut conn = SqliteConnectOptions::new()
+                .filename(&path)
+                .connect()
+                .await
+                .unwrap();
+            sqlx::query_scalar("SELECT signed_amount FROM transactions ORDER BY id")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap()
+        });
+        assert_eq!(signed, ["-10", "-20"]);
+    }
+}
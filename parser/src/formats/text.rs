@@ -1,11 +1,21 @@
-use crate::{Error, Money, Result, Transaction, TransactionBatch, TransactionKind};
+use super::{strip_bom, Field, ParseOptions, ParseReport, TextSeparator, WriteOptions};
+use crate::{
+    Balance, BatchMetadata, Error, Money, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
 use chrono::{NaiveDate, NaiveDateTime};
 use rust_decimal::Decimal;
+use serde::de::value::StrDeserializer;
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::str::FromStr;
 
 /// Default date used for transactions when posted_at is not yet parsed.
-/// This is a valid date (January 1, 1970) that will be replaced during parsing.
+/// This is a valid date (January 1, 1970) that will be replaced during parsing,
+/// or kept when [`ParseOptions::allow_incomplete`] accepts a record without one.
 /// Using a const ensures compile-time validation of the date.
 const DEFAULT_DATE: NaiveDate = match NaiveDate::from_ymd_opt(1970, 1, 1) {
     Some(date) => date,
@@ -16,7 +26,16 @@ const DEFAULT_DATE: NaiveDate = match NaiveDate::from_ymd_opt(1970, 1, 1) {
 ///
 /// The text format uses key-value pairs separated by colons, with transactions
 /// delimited by separator lines ("---"). This format is optimized for human
-/// readability and manual editing.
+/// readability and manual editing. A UTF-8 byte order mark at the start of
+/// the file is skipped.
+///
+/// Text values (IDs, descriptions, accounts, counterparties and categories)
+/// in double quotes are unescaped, as [`write_text`] writes values that
+/// span lines or would otherwise be altered; other values are taken
+/// literally. `Extra: key=value` lines fill [`Transaction::extra`], and a
+/// `Status` line the [`Transaction::status`] of records that aren't booked.
+/// `GeneratedAt`, `SourceSystem`, `Period` and `SchemaVersion` lines before
+/// the first record fill [`TransactionBatch::metadata`].
 ///
 /// # Arguments
 ///
@@ -32,50 +51,166 @@ const DEFAULT_DATE: NaiveDate = match NaiveDate::from_ymd_opt(1970, 1, 1) {
 /// This function will return an error if:
 /// - The file is empty
 /// - Any field contains invalid data
-/// - Required fields are missing
+/// - Required fields (date, type and amount) are missing; the error names
+///   them and the transaction
 pub fn parse_text<R: Read>(reader: R) -> Result<TransactionBatch> {
-    let buf_reader = BufReader::new(reader);
-    let mut lines = buf_reader.lines();
-    let mut transactions = Vec::new();
-    let mut account_id = None;
+    parse_text_with(reader, &ParseOptions::default())
+}
 
-    let header = lines
-        .next()
-        .ok_or_else(|| Error::parse("Text", "empty file"))??;
+/// Parses transaction data from plain text format with the given options.
+///
+/// Behaves like [`parse_text`], except that dates may use one of
+/// [`ParseOptions::date_formats`], amounts and balances may be written with
+/// [`ParseOptions::decimal_separator`] and
+/// [`ParseOptions::thousands_separator`], parsing stops after
+/// [`ParseOptions::max_records`] transactions, and in
+/// [`ParseOptions::strict`] mode unrecognized lines are errors instead of
+/// being ignored. [`ParseOptions::allow_incomplete`] accepts records that
+/// lack required fields.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing plain text transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the text is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error in the same cases as [`parse_text`],
+/// and in strict mode if a line is not recognized.
+pub fn parse_text_with<R: Read>(reader: R, options: &ParseOptions) -> Result<TransactionBatch> {
+    let english = TextLabels::default();
+    let mut parser = TextParser::new(options, &english, false);
+    parser.read(reader)?;
+    parser.finish().map(ParseReport::into_batch)
+}
 
-    if let Some(acc) = header.strip_prefix("Account: ") {
-        account_id = Some(acc.trim().to_string());
+/// Parses plain text data, skipping malformed records instead of failing.
+///
+/// Behaves like [`parse_text_with`], except that a record with an invalid
+/// value is left out and its error added to [`ParseReport::errors`], and
+/// lines that are ignored outside strict mode are reported in
+/// [`ParseReport::warnings`]. Balances that don't pair up are reported as an
+/// error too, and left out.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing plain text transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns the records that parsed along with what went wrong with the
+/// others, or an [`Error`] if the input can't be read at all, such as an
+/// empty file or an I/O error.
+pub fn parse_text_report<R: Read>(reader: R, options: &ParseOptions) -> Result<ParseReport> {
+    let english = TextLabels::default();
+    let mut parser = TextParser::new(options, &english, true);
+    parser.read(reader)?;
+    parser.finish()
+}
+
+/// The state of [`parse_text_with`] between lines.
+struct TextParser<'a> {
+    options: &'a ParseOptions,
+    /// The labels of [`ParseOptions::text_labels`], then the English ones
+    labels: [&'a TextLabels; 2],
+    report: ParseReport,
+    /// Whether malformed records are reported and skipped
+    lenient: bool,
+    /// The record being read
+    current: Option<Record>,
+    /// Whether the lines of a rejected record are being skipped
+    skipping: bool,
+    openings: Vec<(String, Decimal)>,
+    closings: Vec<(String, Decimal)>,
+}
+
+impl<'a> TextParser<'a> {
+    fn new(options: &'a ParseOptions, english: &'a TextLabels, lenient: bool) -> Self {
+        TextParser {
+            options,
+            labels: [options.text_labels.as_ref().unwrap_or(english), english],
+            report: ParseReport::default(),
+            lenient,
+            current: None,
+            skipping: false,
+            openings: Vec::new(),
+            closings: Vec::new(),
+        }
     }
 
-    let mut current_transaction: Option<Transaction> = None;
+    fn read<R: Read>(&mut self, reader: R) -> Result<()> {
+        let buf_reader = BufReader::new(reader);
+        let mut lines = buf_reader.lines();
 
-    for (line_num, line_result) in lines.enumerate() {
-        let line = line_result?;
-        let trimmed = line.trim();
+        let header = strip_bom(
+            lines
+                .next()
+                .ok_or_else(|| Error::parse("Text", "empty file"))??,
+        );
 
-        if trimmed.is_empty() {
-            if let Some(tx) = current_transaction.take() {
-                transactions.push(tx);
-            }
-            continue;
+        if let Some(acc) = labeled(header.trim(), &self.labels, |l| &l.account) {
+            self.report.account_id = Some(unquote_value(acc, 1)?);
         }
 
-        if trimmed == "---" {
-            if let Some(tx) = current_transaction.take() {
-                transactions.push(tx);
+        // The header is only consumed when it names the account; otherwise it
+        // is an ordinary line.
+        let header = (self.report.account_id.is_none()).then_some(Ok(header));
+        let first_line = if header.is_some() { 1 } else { 2 };
+
+        for (index, line_result) in header.into_iter().chain(lines).enumerate() {
+            let line_num = index + first_line;
+            let line = line_result?;
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed == "---" {
+                self.end_record()?;
+                self.skipping = false;
+                continue;
+            }
+
+            if self.options.is_full(self.report.transactions.len()) {
+                break;
+            }
+
+            if let Err(e) = self.line(line_num, trimmed) {
+                self.reject(e)?;
             }
-            continue;
         }
+        self.end_record()
+    }
 
-        if let Some(id) = trimmed.strip_prefix("ID: ") {
-            if let Some(tx) = current_transaction.take() {
-                transactions.push(tx);
+    /// Reads a line that is neither blank nor a separator.
+    fn line(&mut self, line_num: usize, trimmed: &str) -> Result<()> {
+        let (labels, options) = (self.labels, self.options);
+        if self.current.is_none() {
+            if let Some(value) = labeled(trimmed, &labels, |l| &l.opening_balance) {
+                self.openings.push(parse_balance(value, line_num, options)?);
+                return Ok(());
             }
-            current_transaction = Some(Transaction {
-                id: id.to_string(),
+            if let Some(value) = labeled(trimmed, &labels, |l| &l.closing_balance) {
+                self.closings.push(parse_balance(value, line_num, options)?);
+                return Ok(());
+            }
+            if self.metadata_line(line_num, trimmed)? {
+                return Ok(());
+            }
+        }
+
+        if let Some(id) = labeled(trimmed, &labels, |l| &l.id) {
+            self.end_record()?;
+            self.skipping = false;
+            let tx = Transaction {
+                id: unquote_value(id, line_num)?,
                 posted_at: DEFAULT_DATE,
                 executed_at: None,
                 kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
                 amount: Money {
                     amount: Decimal::ZERO,
                     currency: String::new(),
@@ -84,71 +219,418 @@ pub fn parse_text<R: Read>(reader: R) -> Result<TransactionBatch> {
                 account: None,
                 counterparty: None,
                 category: None,
+                tax_amount: None,
+                tax_rate: None,
+                extra: BTreeMap::new(),
+            };
+            self.current = Some(Record {
+                tx,
+                line: line_num,
+                has_date: false,
+                has_kind: false,
+                has_amount: false,
             });
-        } else if let Some(tx) = current_transaction.as_mut() {
-            if let Some(date_str) = trimmed.strip_prefix("Date: ") {
-                tx.posted_at = NaiveDate::parse_from_str(date_str, "%Y-%m-%d").map_err(|e| {
-                    Error::parse(
-                        "Text",
-                        format!("line {}: invalid date: {}", line_num + 2, e),
-                    )
+        } else if self.skipping {
+            // A line of a rejected record
+        } else if let Some(record) = self.current.as_mut() {
+            let tx = &mut record.tx;
+            if let Some(date_str) = labeled(trimmed, &labels, |l| &l.date) {
+                record.has_date = true;
+                tx.posted_at = options.parse_date(date_str).map_err(|e| {
+                    Error::parse("Text", format!("line {}: invalid date: {}", line_num, e))
+                        .with_source(e)
                 })?;
-            } else if let Some(executed_str) = trimmed.strip_prefix("ExecutedDate: ") {
+            } else if let Some(executed_str) = labeled(trimmed, &labels, |l| &l.executed_date) {
                 tx.executed_at = Some(
                     NaiveDateTime::parse_from_str(executed_str, "%Y-%m-%d %H:%M:%S").map_err(
                         |e| {
                             Error::parse(
                                 "Text",
-                                format!("line {}: invalid executed date: {}", line_num + 2, e),
+                                format!("line {}: invalid executed date: {}", line_num, e),
                             )
+                            .with_source(e)
                         },
                     )?,
                 );
-            } else if let Some(kind_str) = trimmed.strip_prefix("Type: ") {
-                tx.kind = TransactionKind::from_str(kind_str)
-                    .map_err(|e| Error::parse("Text", format!("line {}: invalid type: {}", line_num + 2, e)))?;
-            } else if let Some(amount_str) = trimmed.strip_prefix("Amount: ") {
-                let parts: Vec<&str> = amount_str.split_whitespace().collect();
-                if parts.len() != 2 {
-                    return Err(Error::parse(
+            } else if let Some(kind_str) = labeled(trimmed, &labels, |l| &l.kind) {
+                record.has_kind = true;
+                tx.kind = TransactionKind::from_str(kind_str).map_err(|e| {
+                    Error::parse("Text", format!("line {}: invalid type: {}", line_num, e))
+                })?;
+            } else if let Some(status) = labeled(trimmed, &labels, |l| &l.status) {
+                tx.status = TransactionStatus::from_str(status).map_err(|e| {
+                    Error::parse("Text", format!("line {}: invalid status: {}", line_num, e))
+                })?;
+            } else if let Some(amount_str) = labeled(trimmed, &labels, |l| &l.amount) {
+                record.has_amount = true;
+                let (amount, currency) = split_amount(amount_str).ok_or_else(|| {
+                    Error::parse("Text", format!("line {}: invalid amount format", line_num))
+                })?;
+                tx.amount.amount = options.parse_amount(amount).map_err(|e| {
+                    Error::parse("Text", format!("line {}: invalid amount: {}", line_num, e))
+                        .with_source(e)
+                })?;
+                tx.amount.currency = currency.to_string();
+            } else if let Some(desc) = labeled(trimmed, &labels, |l| &l.description) {
+                tx.description = unquote_value(desc, line_num)?;
+            } else if let Some(acc) = labeled(trimmed, &labels, |l| &l.account) {
+                tx.account = Some(unquote_value(acc, line_num)?);
+            } else if let Some(counter) = labeled(trimmed, &labels, |l| &l.counterparty) {
+                tx.counterparty = Some(unquote_value(counter, line_num)?);
+            } else if let Some(cat) = labeled(trimmed, &labels, |l| &l.category) {
+                tx.category = Some(unquote_value(cat, line_num)?);
+            } else if let Some(tax) = labeled(trimmed, &labels, |l| &l.tax_amount) {
+                tx.tax_amount = Some(options.parse_amount(tax).map_err(|e| {
+                    Error::parse(
                         "Text",
-                        format!("line {}: invalid amount format", line_num + 2),
-                    ));
-                }
-                tx.amount.amount = Decimal::from_str(parts[0]).map_err(|e| {
+                        format!("line {}: invalid tax amount: {}", line_num, e),
+                    )
+                    .with_source(e)
+                })?);
+            } else if let Some(rate) = labeled(trimmed, &labels, |l| &l.tax_rate) {
+                let rate = rate.strip_suffix('%').unwrap_or(rate).trim_end();
+                tx.tax_rate = Some(options.parse_amount(rate).map_err(|e| {
                     Error::parse(
                         "Text",
-                        format!("line {}: invalid amount: {}", line_num + 2, e),
+                        format!("line {}: invalid tax rate: {}", line_num, e),
                     )
+                    .with_source(e)
+                })?);
+            } else if let Some(entry) = labeled(trimmed, &labels, |l| &l.extra) {
+                let (key, value) = parse_extra(entry, line_num)?;
+                tx.extra.insert(key, value);
+            } else {
+                return self.unrecognized(line_num, trimmed);
+            }
+        } else {
+            return self.unrecognized(line_num, trimmed);
+        }
+        Ok(())
+    }
+
+    /// Reads a line of the batch header that sets a [`BatchMetadata`] field;
+    /// returns whether it was one.
+    fn metadata_line(&mut self, line_num: usize, trimmed: &str) -> Result<bool> {
+        let (labels, metadata) = (self.labels, &mut self.report.metadata);
+        if let Some(value) = labeled(trimmed, &labels, |l| &l.generated_at) {
+            let generated_at =
+                NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map_err(|e| {
+                    Error::parse(
+                        "Text",
+                        format!("line {}: invalid generation time: {}", line_num, e),
+                    )
+                    .with_source(e)
                 })?;
-                tx.amount.currency = parts[1].to_string();
-            } else if let Some(desc) = trimmed.strip_prefix("Description: ") {
-                tx.description = desc.to_string();
-            } else if let Some(acc) = trimmed.strip_prefix("Account: ") {
-                tx.account = Some(acc.to_string());
-            } else if let Some(counter) = trimmed.strip_prefix("Counterparty: ") {
-                tx.counterparty = Some(counter.to_string());
-            } else if let Some(cat) = trimmed.strip_prefix("Category: ") {
-                tx.category = Some(cat.to_string());
+            metadata.generated_at = Some(generated_at);
+        } else if let Some(value) = labeled(trimmed, &labels, |l| &l.source_system) {
+            metadata.source_system = Some(unquote_value(value, line_num)?);
+        } else if let Some(value) = labeled(trimmed, &labels, |l| &l.period) {
+            let period = value
+                .parse()
+                .map_err(|e: Error| e.prefixed("Text", &format!("line {}: ", line_num)))?;
+            metadata.period = Some(period);
+        } else if let Some(value) = labeled(trimmed, &labels, |l| &l.schema_version) {
+            metadata.schema_version = Some(unquote_value(value, line_num)?);
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Rejects a line outside strict mode, and reports it in lenient mode.
+    fn unrecognized(&mut self, line_num: usize, line: &str) -> Result<()> {
+        if self.options.strict {
+            return Err(unrecognized_line(line_num, line));
+        }
+        if self.lenient {
+            let warning = format!("line {}: ignored unrecognized line: {}", line_num, line);
+            self.report.warnings.push(warning);
+        }
+        Ok(())
+    }
+
+    /// Adds the record being read to the transactions.
+    fn end_record(&mut self) -> Result<()> {
+        match self.current.take() {
+            Some(record) => {
+                let transactions = &mut self.report.transactions;
+                let result = record.finish(transactions, self.labels[0], self.options);
+                self.report_error(result)
             }
+            None => Ok(()),
         }
     }
 
-    if let Some(tx) = current_transaction {
-        transactions.push(tx);
+    /// Skips the record that `error` occurred in, when lenient.
+    fn reject(&mut self, error: Error) -> Result<()> {
+        self.current = None;
+        self.skipping = true;
+        self.report_error(Err(error))
     }
 
-    Ok(TransactionBatch {
-        account_id,
-        transactions,
-    })
+    /// Adds the error of `result` to the report when lenient and the error
+    /// is about the data; returns it otherwise.
+    fn report_error(&mut self, result: Result<()>) -> Result<()> {
+        match result {
+            Err(e @ Error::Parse { .. }) if self.lenient => {
+                self.report.errors.push(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn finish(mut self) -> Result<ParseReport> {
+        let openings = std::mem::take(&mut self.openings);
+        let closings = std::mem::take(&mut self.closings);
+        match pair_balances(openings, closings) {
+            Ok(balances) => self.report.balances = balances,
+            Err(e) => self.report_error(Err(e))?,
+        }
+        Ok(self.report)
+    }
+}
+
+/// Whether a text value must be quoted to survive a round trip: it is
+/// empty, has surrounding whitespace, spans lines (or holds other control
+/// characters), or starts with a quote itself.
+fn needs_quoting(value: &str) -> bool {
+    value.is_empty()
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.starts_with('"')
+        || value.contains(char::is_control)
+}
+
+/// Writes a text value as is, or in double quotes with `\\`, `\"`, `\n`,
+/// `\r` and `\t` escapes when it [needs quoting](needs_quoting).
+fn quote_value(value: &str) -> Cow<'_, str> {
+    if !needs_quoting(value) {
+        return Cow::Borrowed(value);
+    }
+    Cow::Owned(quoted(value))
+}
+
+/// Writes a text value in double quotes, with escapes as [`quote_value`].
+fn quoted(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        match ch {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes an entry of [`Transaction::extra`] as `key=value`, quoting the
+/// key if it contains `=` and the value as [`quote_value`] does.
+fn format_extra(key: &str, value: &str) -> String {
+    let key = if key.contains('=') {
+        Cow::Owned(quoted(key))
+    } else {
+        quote_value(key)
+    };
+    format!("{}={}", key, quote_value(value))
+}
+
+/// Reads an entry written by [`format_extra`].
+fn parse_extra(entry: &str, line_num: usize) -> Result<(String, String)> {
+    let split = if entry.starts_with('"') {
+        // The key ends at the first quote that isn't escaped
+        let mut escaped = false;
+        entry
+            .char_indices()
+            .skip(1)
+            .find(|&(_, ch)| {
+                let end = ch == '"' && !escaped;
+                escaped = ch == '\\' && !escaped;
+                end
+            })
+            .and_then(|(end, _)| Some((&entry[..=end], entry[end + 1..].strip_prefix('=')?)))
+    } else {
+        entry.split_once('=')
+    };
+    let (key, value) = split.ok_or_else(|| {
+        Error::parse(
+            "Text",
+            format!("line {}: invalid extra field, expected key=value", line_num),
+        )
+    })?;
+    let key = unquote_value(key, line_num)?;
+    Ok((key, unquote_value(value, line_num)?))
+}
+
+/// Reads a text value written by [`quote_value`]: values in double quotes
+/// are unescaped, others taken literally.
+fn unquote_value(value: &str, line_num: usize) -> Result<String> {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return Ok(value.to_string());
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unquoted.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unquoted.push('\\'),
+            Some('"') => unquoted.push('"'),
+            Some('n') => unquoted.push('\n'),
+            Some('r') => unquoted.push('\r'),
+            Some('t') => unquoted.push('\t'),
+            other => {
+                return Err(Error::parse(
+                    "Text",
+                    format!(
+                        "line {}: invalid escape: \\{}",
+                        line_num,
+                        other.map(String::from).unwrap_or_default()
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(unquoted)
+}
+
+/// A transaction being read, with the required fields seen so far.
+struct Record {
+    tx: Transaction,
+    /// Line of the record's ID
+    line: usize,
+    has_date: bool,
+    has_kind: bool,
+    has_amount: bool,
+}
+
+impl Record {
+    /// Adds the transaction to `transactions`, unless it lacks a required
+    /// field and [`ParseOptions::allow_incomplete`] is off.
+    fn finish(
+        self,
+        transactions: &mut Vec<Transaction>,
+        labels: &TextLabels,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        let missing: Vec<&str> = [
+            (self.has_date, &labels.date),
+            (self.has_kind, &labels.kind),
+            (self.has_amount, &labels.amount),
+        ]
+        .iter()
+        .filter(|(seen, _)| !seen)
+        .map(|(_, label)| label.as_str())
+        .collect();
+        if !missing.is_empty() && !options.allow_incomplete {
+            return Err(Error::parse(
+                "Text",
+                format!(
+                    "transaction {} (line {}): missing {}",
+                    transactions.len() + 1,
+                    self.line,
+                    missing.join(", ")
+                ),
+            ));
+        }
+        transactions.push(self.tx);
+        Ok(())
+    }
+}
+
+fn unrecognized_line(line_num: usize, line: &str) -> Error {
+    Error::parse(
+        "Text",
+        format!("line {}: unrecognized line: {}", line_num, line),
+    )
+}
+
+/// Splits an `<amount> <currency>` value at its last space; the amount may
+/// contain spaces that group its digits.
+fn split_amount(value: &str) -> Option<(&str, &str)> {
+    let (amount, currency) = value.trim().rsplit_once(char::is_whitespace)?;
+    Some((amount.trim_end(), currency))
+}
+
+/// Parses the `<amount> <currency>` value of a balance line.
+fn parse_balance(
+    value: &str,
+    line_num: usize,
+    options: &ParseOptions,
+) -> Result<(String, Decimal)> {
+    let (amount, currency) = split_amount(value).ok_or_else(|| {
+        Error::parse(
+            "Text",
+            format!("line {}: invalid balance format", line_num),
+        )
+    })?;
+    let amount = options.parse_amount(amount).map_err(|e| {
+        Error::parse(
+            "Text",
+            format!("line {}: invalid balance: {}", line_num, e),
+        )
+        .with_source(e)
+    })?;
+    Ok((currency.to_string(), amount))
+}
+
+/// Matches opening and closing balance lines by currency.
+fn pair_balances(
+    openings: Vec<(String, Decimal)>,
+    closings: Vec<(String, Decimal)>,
+) -> Result<Vec<Balance>> {
+    let mut balances = Vec::with_capacity(openings.len());
+    for (currency, opening) in openings {
+        let closing = closings
+            .iter()
+            .find(|(c, _)| *c == currency)
+            .map(|(_, amount)| *amount)
+            .ok_or_else(|| {
+                Error::parse(
+                    "Text",
+                    format!("opening balance in {} has no closing balance", currency),
+                )
+            })?;
+        balances.push(Balance {
+            currency,
+            opening,
+            closing,
+        });
+    }
+
+    if let Some((currency, _)) = closings
+        .iter()
+        .find(|(c, _)| !balances.iter().any(|b| b.currency == *c))
+    {
+        return Err(Error::parse(
+            "Text",
+            format!("closing balance in {} has no opening balance", currency),
+        ));
+    }
+
+    Ok(balances)
 }
 
 /// Writes transaction data in a human-readable plain text format.
 ///
 /// This function outputs transactions using key-value pairs with colons,
 /// separated by "---" delimiters. The format is designed to be easy to
-/// read and edit manually.
+/// read and edit manually. Text values that are empty, span lines, have
+/// surrounding whitespace or start with a quote are written in double quotes
+/// with backslash escapes, so that they parse back unchanged. The
+/// [`Transaction::status`] of records that aren't booked follows the type as
+/// a `Status` line, and each entry of [`Transaction::extra`] follows the
+/// fields as an `Extra: key=value` line.
 ///
 /// # Arguments
 ///
@@ -163,48 +645,443 @@ pub fn parse_text<R: Read>(reader: R) -> Result<TransactionBatch> {
 ///
 /// This function will return an error if any I/O operation fails.
 pub fn write_text<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
-    if let Some(account) = &batch.account_id {
-        writeln!(writer, "Account: {}", account)?;
-        writeln!(writer)?;
+    write_text_with(batch, writer, &WriteOptions::default())
+}
+
+/// The keys of text format lines, such as `Date` in `Date: 2024-01-15`.
+///
+/// The default labels are English; [`TextLabels::russian`] and
+/// [`TextLabels::builtin`] provide translations, and custom ones can be
+/// deserialized (all fields are optional and default to English). Only the
+/// keys are translated: values such as `Debit` stay as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextLabels {
+    /// Label of the account line, in the batch header and in transactions
+    pub account: String,
+    /// Label of the opening balance lines of the batch header
+    pub opening_balance: String,
+    /// Label of the closing balance lines of the batch header
+    pub closing_balance: String,
+    /// Label of [`BatchMetadata::generated_at`] in the batch header
+    pub generated_at: String,
+    /// Label of [`BatchMetadata::source_system`] in the batch header
+    pub source_system: String,
+    /// Label of [`BatchMetadata::period`] in the batch header
+    pub period: String,
+    /// Label of [`BatchMetadata::schema_version`] in the batch header
+    pub schema_version: String,
+    /// Label of the line starting a transaction, with its ID
+    pub id: String,
+    /// Label of the posting date
+    pub date: String,
+    /// Label of the execution date and time
+    pub executed_date: String,
+    /// Label of the transaction kind
+    pub kind: String,
+    /// Label of the status of transactions that aren't booked
+    pub status: String,
+    /// Label of the amount and currency
+    pub amount: String,
+    /// Label of the description
+    pub description: String,
+    /// Label of the counterparty
+    pub counterparty: String,
+    /// Label of the category
+    pub category: String,
+    /// Label of the tax amount
+    pub tax_amount: String,
+    /// Label of the tax rate
+    pub tax_rate: String,
+    /// Label of the lines holding [`Transaction::extra`] entries, as
+    /// `key=value`
+    pub extra: String,
+}
+
+impl Default for TextLabels {
+    fn default() -> Self {
+        TextLabels {
+            account: "Account".to_string(),
+            opening_balance: "OpeningBalance".to_string(),
+            closing_balance: "ClosingBalance".to_string(),
+            generated_at: "GeneratedAt".to_string(),
+            source_system: "SourceSystem".to_string(),
+            period: "Period".to_string(),
+            schema_version: "SchemaVersion".to_string(),
+            id: "ID".to_string(),
+            date: "Date".to_string(),
+            executed_date: "ExecutedDate".to_string(),
+            kind: "Type".to_string(),
+            status: "Status".to_string(),
+            amount: "Amount".to_string(),
+            description: "Description".to_string(),
+            counterparty: "Counterparty".to_string(),
+            category: "Category".to_string(),
+            tax_amount: "TaxAmount".to_string(),
+            tax_rate: "TaxRate".to_string(),
+            extra: "Extra".to_string(),
+        }
+    }
+}
+
+impl TextLabels {
+    /// Russian labels (`Дата: 2024-01-15`, `Сумма: 1200.50 RUB`, ...).
+    pub fn russian() -> Self {
+        TextLabels {
+            account: "Счёт".to_string(),
+            opening_balance: "Входящий остаток".to_string(),
+            closing_balance: "Исходящий остаток".to_string(),
+            generated_at: "Сформирован".to_string(),
+            source_system: "Источник".to_string(),
+            period: "Период".to_string(),
+            schema_version: "Версия схемы".to_string(),
+            id: "Номер".to_string(),
+            date: "Дата".to_string(),
+            executed_date: "Дата исполнения".to_string(),
+            kind: "Тип".to_string(),
+            status: "Статус".to_string(),
+            amount: "Сумма".to_string(),
+            description: "Описание".to_string(),
+            counterparty: "Контрагент".to_string(),
+            category: "Категория".to_string(),
+            tax_amount: "Сумма налога".to_string(),
+            tax_rate: "Ставка налога".to_string(),
+            extra: "Доп. поле".to_string(),
+        }
     }
 
-    for (i, tx) in batch.transactions.iter().enumerate() {
-        if i > 0 {
-            writeln!(writer, "---")?;
+    /// Returns the labels of a language by its code: `en` or `ru`.
+    pub fn builtin(language: &str) -> Option<Self> {
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Some(TextLabels::default()),
+            "ru" => Some(TextLabels::russian()),
+            _ => None,
         }
+    }
+}
+
+/// The value of a `Label: value` line, for the first set of labels whose
+/// label the line starts with.
+fn labeled<'a>(
+    line: &'a str,
+    labels: &[&TextLabels],
+    label: fn(&TextLabels) -> &String,
+) -> Option<&'a str> {
+    labels
+        .iter()
+        .find_map(|set| line.strip_prefix(label(set).as_str())?.strip_prefix(": "))
+}
 
-        writeln!(writer, "ID: {}", tx.id)?;
-        writeln!(writer, "Date: {}", tx.posted_at.format("%Y-%m-%d"))?;
+/// A user-defined layout of text records.
+///
+/// The template is written once per transaction, with each `{field}`
+/// placeholder replaced by the value of that field; `{{` and `}}` stand for
+/// literal braces. Fields are named as in [`Field`]'s serialized form:
+/// `id`, `posted_date`, `executed_date`, `kind`, `status`, `amount`,
+/// `currency`, `description`, `account`, `counterparty`, `category`,
+/// `tax_amount` and `tax_rate`. Missing optional values are empty. Labels, line breaks and
+/// the separator between records are part of the template, so the output
+/// only parses back with [`parse_text`] when it keeps the standard layout.
+///
+/// # Examples
+///
+/// ```
+/// use parser::formats::text::TextTemplate;
+/// use parser::{write_with, Format, TransactionBatch, WriteOptions};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let template = TextTemplate::new("{posted_date}  {amount} {currency}  {description}\n")?;
+/// let options = WriteOptions {
+///     text_template: Some(template),
+///     ..WriteOptions::default()
+/// };
+/// let mut output = Vec::new();
+/// write_with(&TransactionBatch::default(), &mut output, Format::Text, &options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Field(Field),
+}
+
+impl TextTemplate {
+    /// Compiles a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a brace is unbalanced or a placeholder names no
+    /// field.
+    pub fn new(source: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars.as_str().split_once('}').ok_or_else(|| {
+                        Error::parse("Template", "unclosed placeholder")
+                    })?;
+                    let deserializer: StrDeserializer<serde::de::value::Error> =
+                        name.trim().into_deserializer();
+                    let field = Field::deserialize(deserializer).map_err(|_| {
+                        Error::parse("Template", format!("unknown placeholder: {{{}}}", name))
+                    })?;
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TemplatePart::Field(field));
+                    chars = rest.chars();
+                }
+                '}' => return Err(Error::parse("Template", "unmatched '}'")),
+                ch => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        Ok(TextTemplate { parts })
+    }
 
-        if let Some(executed) = tx.executed_at {
-            writeln!(
-                writer,
-                "ExecutedDate: {}",
-                executed.format("%Y-%m-%d %H:%M:%S")
-            )?;
+    fn render<W: Write>(
+        &self,
+        writer: &mut W,
+        tx: &Transaction,
+        options: &WriteOptions,
+    ) -> Result<()> {
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => writer.write_all(text.as_bytes())?,
+                TemplatePart::Field(field) => {
+                    write!(writer, "{}", super::csv::field_value(tx, *field, options))?
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+impl FromStr for TextTemplate {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        TextTemplate::new(source)
+    }
+}
 
-        writeln!(writer, "Type: {}", tx.kind)?;
+/// Every field with a line of its own, in the order of [`write_text`].
+const LINE_FIELDS: [Field; 12] = [
+    Field::Id,
+    Field::PostedDate,
+    Field::ExecutedDate,
+    Field::Kind,
+    Field::Status,
+    Field::Amount,
+    Field::Description,
+    Field::Account,
+    Field::Counterparty,
+    Field::Category,
+    Field::TaxAmount,
+    Field::TaxRate,
+];
+
+/// Writes transaction data in plain text format with the given options.
+///
+/// Behaves like [`write_text`], except that [`WriteOptions::fields`] selects
+/// and orders the lines of each record, posting dates use
+/// [`WriteOptions::date_format`] and records are separated by
+/// [`WriteOptions::text_separator`]. The currency is always part of the
+/// `Amount` line. With a [`WriteOptions::text_template`], each record is
+/// rendered from the template instead. The batch is written as given; [`crate::write_with`]
+/// applies the options that change the batch itself.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the text data to
+/// * `options` - How to write the batch
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_text_with<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    options: &WriteOptions,
+) -> Result<()> {
+    let (account_id, metadata) = (batch.account_id.as_deref(), &batch.metadata);
+    write_text_header(writer, account_id, &batch.balances, metadata, options)?;
+    for (i, tx) in batch.transactions.iter().enumerate() {
+        write_text_record(writer, tx, i == 0, options)?;
+    }
+    Ok(())
+}
+
+/// Writes the account, balance and metadata lines that precede the records
+/// of [`write_text_with`].
+pub(crate) fn write_text_header<W: Write>(
+    writer: &mut W,
+    account_id: Option<&str>,
+    balances: &[Balance],
+    metadata: &BatchMetadata,
+    options: &WriteOptions,
+) -> Result<()> {
+    let english = TextLabels::default();
+    let labels = options.text_labels.as_ref().unwrap_or(&english);
+    if let Some(account) = account_id {
+        writeln!(writer, "{}: {}", labels.account, quote_value(account))?;
+    }
+    for balance in balances {
         writeln!(
             writer,
-            "Amount: {} {}",
-            tx.amount.amount, tx.amount.currency
+            "{}: {} {}",
+            labels.opening_balance, balance.opening, balance.currency
         )?;
-        writeln!(writer, "Description: {}", tx.description)?;
+        writeln!(
+            writer,
+            "{}: {} {}",
+            labels.closing_balance, balance.closing, balance.currency
+        )?;
+    }
+    if let Some(generated_at) = metadata.generated_at {
+        let generated_at = generated_at.format("%Y-%m-%d %H:%M:%S");
+        writeln!(writer, "{}: {}", labels.generated_at, generated_at)?;
+    }
+    if let Some(source_system) = &metadata.source_system {
+        let source_system = quote_value(source_system);
+        writeln!(writer, "{}: {}", labels.source_system, source_system)?;
+    }
+    if let Some(period) = metadata.period {
+        writeln!(writer, "{}: {}", labels.period, period)?;
+    }
+    if let Some(schema_version) = &metadata.schema_version {
+        let schema_version = quote_value(schema_version);
+        writeln!(writer, "{}: {}", labels.schema_version, schema_version)?;
+    }
+    if account_id.is_some() || !balances.is_empty() || !metadata.is_empty() {
+        writeln!(writer)?;
+    }
+    Ok(())
+}
 
-        if let Some(acc) = &tx.account {
-            writeln!(writer, "Account: {}", acc)?;
-        }
+/// Writes one record of [`write_text_with`], preceded by the separator
+/// unless it is the `first`.
+pub(crate) fn write_text_record<W: Write>(
+    writer: &mut W,
+    tx: &Transaction,
+    first: bool,
+    options: &WriteOptions,
+) -> Result<()> {
+    if let Some(template) = &options.text_template {
+        return template.render(writer, tx, options);
+    }
 
-        if let Some(counter) = &tx.counterparty {
-            writeln!(writer, "Counterparty: {}", counter)?;
+    if !first {
+        match options.text_separator {
+            TextSeparator::Dashes => writeln!(writer, "---")?,
+            TextSeparator::BlankLine => writeln!(writer)?,
         }
+    }
 
-        if let Some(cat) = &tx.category {
-            writeln!(writer, "Category: {}", cat)?;
+    let english = TextLabels::default();
+    let labels = options.text_labels.as_ref().unwrap_or(&english);
+    let fields = options.fields.as_deref().unwrap_or(&LINE_FIELDS);
+    for field in fields {
+        write_field(writer, tx, *field, labels, options)?;
+    }
+    if options.fields.is_none() {
+        for (key, value) in &tx.extra {
+            writeln!(writer, "{}: {}", labels.extra, format_extra(key, value))?;
         }
     }
+    Ok(())
+}
 
+/// Writes the line of one field, if the transaction has a value for it.
+fn write_field<W: Write>(
+    writer: &mut W,
+    tx: &Transaction,
+    field: Field,
+    labels: &TextLabels,
+    options: &WriteOptions,
+) -> Result<()> {
+    match field {
+        Field::Id => writeln!(writer, "{}: {}", labels.id, quote_value(&tx.id))?,
+        Field::PostedDate => writeln!(
+            writer,
+            "{}: {}",
+            labels.date,
+            options.format_date(tx.posted_at)
+        )?,
+        Field::ExecutedDate => {
+            if let Some(executed) = tx.executed_at {
+                writeln!(
+                    writer,
+                    "{}: {}",
+                    labels.executed_date,
+                    executed.format("%Y-%m-%d %H:%M:%S")
+                )?;
+            }
+        }
+        Field::Kind => writeln!(writer, "{}: {}", labels.kind, tx.kind)?,
+        Field::Status => {
+            if !tx.status.is_booked() {
+                writeln!(writer, "{}: {}", labels.status, tx.status)?;
+            }
+        }
+        Field::Amount => writeln!(writer, "{}: {:#}", labels.amount, tx.amount)?,
+        // Written as part of the amount
+        Field::Currency => {}
+        Field::Description => writeln!(
+            writer,
+            "{}: {}",
+            labels.description,
+            quote_value(&tx.description)
+        )?,
+        Field::Account => {
+            if let Some(acc) = &tx.account {
+                writeln!(writer, "{}: {}", labels.account, quote_value(acc))?;
+            }
+        }
+        Field::Counterparty => {
+            if let Some(counter) = &tx.counterparty {
+                writeln!(writer, "{}: {}", labels.counterparty, quote_value(counter))?;
+            }
+        }
+        Field::Category => {
+            if let Some(cat) = &tx.category {
+                writeln!(writer, "{}: {}", labels.category, quote_value(cat))?;
+            }
+        }
+        Field::TaxAmount => {
+            if let Some(tax) = tx.tax_amount {
+                writeln!(writer, "{}: {}", labels.tax_amount, tax)?;
+            }
+        }
+        Field::TaxRate => {
+            if let Some(rate) = tx.tax_rate {
+                writeln!(writer, "{}: {}%", labels.tax_rate, rate)?;
+            }
+        }
+    }
     Ok(())
 }
 
@@ -242,6 +1119,8 @@ mod tests {
         let mut batch = TransactionBatch {
             account_id: Some("ACC123".to_string()),
             transactions: vec![],
+            balances: Vec::new(),
+            metadata: Default::default(),
         };
 
         batch.transactions.push(Transaction {
@@ -249,6 +1128,7 @@ mod tests {
             posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
             executed_at: None,
             kind: TransactionKind::Credit,
+            status: TransactionStatus::Booked,
             amount: Money {
                 amount: Decimal::from_str("1000.50").unwrap(),
                 currency: "USD".to_string(),
@@ -257,6 +1137,9 @@ mod tests {
             account: None,
             counterparty: None,
             category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
         });
 
         let mut buffer = Vec::new();
@@ -265,6 +1148,19 @@ mod tests {
         let output = String::from_utf8(buffer).unwrap();
         assert!(output.contains("ID: TX001"));
         assert!(output.contains("Account: ACC123"));
+
+        batch.transactions.push(batch.transactions[0].clone());
+        let options = WriteOptions {
+            text_separator: TextSeparator::BlankLine,
+            fields: Some(vec![Field::Id, Field::Amount]),
+            ..WriteOptions::default()
+        };
+        let mut buffer = Vec::new();
+        write_text_with(&batch, &mut buffer, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "Account: ACC123\n\nID: TX001\nAmount: 1000.50 USD\n\nID: TX001\nAmount: 1000.50 USD\n"
+        );
     }
 
     #[test]
@@ -281,6 +1177,7 @@ mod tests {
                             .unwrap(),
                     ),
                     kind: TransactionKind::Credit,
+                    status: TransactionStatus::Booked,
                     amount: Money {
                         amount: Decimal::from_str("1000.50").unwrap(),
                         currency: "USD".to_string(),
@@ -289,12 +1186,16 @@ mod tests {
                     account: Some("ACC123".to_string()),
                     counterparty: Some("Employer Inc".to_string()),
                     category: Some("Salary".to_string()),
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
                 },
                 Transaction {
                     id: "TX002".to_string(),
                     posted_at: NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
                     executed_at: None,
                     kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
                     amount: Money {
                         amount: Decimal::from_str("150.50").unwrap(),
                         currency: "USD".to_string(),
@@ -303,8 +1204,17 @@ mod tests {
                     account: Some("ACC123".to_string()),
                     counterparty: Some("Store".to_string()),
                     category: Some("Food".to_string()),
+                    tax_amount: Some(Decimal::from_str("25.08").unwrap()),
+                    tax_rate: Some(Decimal::from(20)),
+                    extra: BTreeMap::new(),
                 },
             ],
+            balances: vec![Balance {
+                currency: "USD".to_string(),
+                opening: Decimal::from_str("500.00").unwrap(),
+                closing: Decimal::from_str("1350.00").unwrap(),
+            }],
+            metadata: Default::default(),
         };
 
         // Write to text format
@@ -317,6 +1227,7 @@ mod tests {
 
         // Compare
         assert_eq!(parsed_batch.account_id, original_batch.account_id);
+        assert_eq!(parsed_batch.balances, original_batch.balances);
         assert_eq!(parsed_batch.transactions.len(), original_batch.transactions.len());
 
         for (original, parsed) in original_batch.transactions.iter().zip(parsed_batch.transactions.iter()) {
@@ -329,6 +1240,243 @@ mod tests {
             assert_eq!(parsed.account, original.account);
             assert_eq!(parsed.counterparty, original.counterparty);
             assert_eq!(parsed.category, original.category);
+            assert_eq!(parsed.tax_amount, original.tax_amount);
+            assert_eq!(parsed.tax_rate, original.tax_rate);
         }
     }
+
+    #[test]
+    fn test_parse_text_with_options() {
+        let data = "ID: TX001\nDate: 15.01.2024\nТип: перевод\nType: Debit\nAmount: 10 RUB\nDescription: Аренда\n";
+        let (encoded, _, _) = encoding_rs::WINDOWS_1251.encode(data);
+        let mut options = ParseOptions {
+            date_formats: vec!["%d.%m.%Y".to_string()],
+            encoding: Some("windows-1251".to_string()),
+            ..ParseOptions::default()
+        };
+        let batch = crate::parse_with(&encoded[..], crate::Format::Text, &options).unwrap();
+        assert_eq!(batch.transactions[0].description, "Аренда");
+        assert_eq!(
+            batch.transactions[0].posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+
+        options.strict = true;
+        let err = crate::parse_with(&encoded[..], crate::Format::Text, &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("line 3: unrecognized line: Тип: перевод"));
+    }
+
+    #[test]
+    fn test_locale_amounts() {
+        let data = "OpeningBalance: 10\u{a0}000,00 EUR\nClosingBalance: 8\u{a0}765,44 EUR\n\
+                    ID: TX001\nDate: 2024-01-15\nType: Debit\nAmount: 1 234,56 EUR\nDescription: Rent\n";
+        let options = ParseOptions {
+            decimal_separator: Some(','),
+            thousands_separator: Some(' '),
+            ..ParseOptions::default()
+        };
+        let batch = parse_text_with(Cursor::new(data), &options).unwrap();
+        assert_eq!(batch.transactions[0].amount.amount, Decimal::new(123456, 2));
+        assert_eq!(batch.transactions[0].amount.currency, "EUR");
+        assert_eq!(batch.balances[0].opening, Decimal::new(1000000, 2));
+
+        let err = parse_text(Cursor::new(data)).unwrap_err();
+        assert!(err.to_string().contains("line 1: invalid balance"));
+    }
+
+    #[test]
+    fn test_bom() {
+        let data = "\u{feff}Account: ACC1\n\nID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 5 EUR\n";
+        let batch = parse_text(Cursor::new(data)).unwrap();
+        assert_eq!(batch.account_id.as_deref(), Some("ACC1"));
+        assert_eq!(batch.transactions[0].id, "TX1");
+    }
+
+    #[test]
+    fn test_text_template() {
+        let data = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 9.99 EUR\nDescription: Coffee\n";
+        let batch = parse_text(Cursor::new(data)).unwrap();
+        let options = WriteOptions {
+            text_template: Some(
+                "{posted_date} | {{{id}}} {description} {amount} {currency}\n"
+                    .parse()
+                    .unwrap(),
+            ),
+            date_format: Some("%d.%m.%Y".to_string()),
+            ..WriteOptions::default()
+        };
+        let mut buffer = Vec::new();
+        write_text_with(&batch, &mut buffer, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "15.01.2024 | {TX1} Coffee 9.99 EUR\n"
+        );
+
+        let err = TextTemplate::new("{date}").unwrap_err();
+        assert!(err.to_string().contains("unknown placeholder: {date}"));
+        assert!(TextTemplate::new("{id").is_err());
+        assert!(TextTemplate::new("id}").is_err());
+    }
+
+    #[test]
+    fn test_values_are_escaped() {
+        let descriptions = [
+            "Line one\nAmount: 999 USD",
+            "  indented",
+            "\"quoted\"",
+            "C:\\path\\file",
+            "",
+            "Plain: with colon",
+        ];
+        let batch = TransactionBatch {
+            account_id: Some(" ACC1 ".to_string()),
+            transactions: descriptions
+                .iter()
+                .enumerate()
+                .map(|(i, description)| Transaction {
+                    id: format!("TX{}", i),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                    executed_at: None,
+                    kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
+                    amount: Money {
+                        amount: Decimal::ONE,
+                        currency: "EUR".to_string(),
+                    },
+                    description: description.to_string(),
+                    account: None,
+                    counterparty: Some("Tab\tSeparated".to_string()),
+                    category: None,
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
+                })
+                .collect(),
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        let mut buffer = Vec::new();
+        write_text(&batch, &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("Description: \"Line one\\nAmount: 999 USD\"\n"));
+        assert!(output.contains("Description: Plain: with colon\n"));
+
+        let parsed = parse_text(Cursor::new(output)).unwrap();
+        assert_eq!(parsed.account_id, batch.account_id);
+        assert_eq!(parsed.transactions.len(), descriptions.len());
+        for (parsed, original) in parsed.transactions.iter().zip(&batch.transactions) {
+            assert_eq!(parsed.description, original.description);
+            assert_eq!(parsed.counterparty, original.counterparty);
+            assert_eq!(parsed.amount.amount, Decimal::ONE);
+        }
+
+        let err = parse_text(Cursor::new("ID: TX1\nDescription: \"bad \\q\"\n")).unwrap_err();
+        assert!(err.to_string().contains("line 2: invalid escape: \\q"));
+    }
+
+    #[test]
+    fn test_extra_fields() {
+        let data = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 5 USD\n\
+                    Extra: MCC=5411\nExtra: \"a=b\"=\"\"\nExtra: Terminal= T-01\n";
+        let batch = parse_text(Cursor::new(data)).unwrap();
+        let extra = &batch.transactions[0].extra;
+        assert_eq!(extra["MCC"], "5411");
+        assert_eq!(extra["a=b"], "");
+        assert_eq!(extra["Terminal"], " T-01");
+
+        let mut buffer = Vec::new();
+        write_text(&batch, &mut buffer).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        let expected = "Extra: MCC=5411\nExtra: Terminal=\" T-01\"\nExtra: \"a=b\"=\"\"\n";
+        assert!(output.ends_with(expected));
+        assert_eq!(parse_text(Cursor::new(output)).unwrap(), batch);
+
+        let err = parse_text(Cursor::new("ID: TX1\nExtra: MCC\n")).unwrap_err();
+        assert!(err.to_string().contains("line 2: invalid extra field"));
+    }
+
+    #[test]
+    fn test_localized_labels() {
+        let data = "Счёт: 40817810\n\
+                    Входящий остаток: 1000 RUB\n\
+                    Исходящий остаток: 800 RUB\n\n\
+                    Номер: TX1\n\
+                    Дата: 2024-01-15\n\
+                    Тип: Debit\n\
+                    Сумма: 200 RUB\n\
+                    Description: Аренда\n";
+        let options = ParseOptions {
+            text_labels: TextLabels::builtin("ru"),
+            ..ParseOptions::default()
+        };
+        let batch = parse_text_with(Cursor::new(data), &options).unwrap();
+        assert_eq!(batch.account_id.as_deref(), Some("40817810"));
+        assert_eq!(batch.balances[0].closing, Decimal::from(800));
+        let tx = &batch.transactions[0];
+        assert_eq!(tx.id, "TX1");
+        assert_eq!(tx.amount.amount, Decimal::from(200));
+        assert_eq!(tx.description, "Аренда");
+
+        let options = WriteOptions {
+            text_labels: Some(TextLabels::russian()),
+            ..WriteOptions::default()
+        };
+        let mut buffer = Vec::new();
+        write_text_with(&batch, &mut buffer, &options).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.starts_with("Счёт: 40817810\nВходящий остаток: 1000 RUB\n"));
+        assert!(output.contains("Номер: TX1\nДата: 2024-01-15\nТип: Debit\nСумма: 200 RUB\n"));
+        assert!(parse_text(Cursor::new(output)).unwrap().transactions.is_empty());
+    }
+
+    #[test]
+    fn test_incomplete_transactions() {
+        let data = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 5 EUR\n---\n\
+                    ID: TX2\nType: Credit\nDescription: No date or amount\n";
+        let err = parse_text(Cursor::new(data)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("transaction 2 (line 6): missing Date, Amount"));
+
+        let options = ParseOptions {
+            allow_incomplete: true,
+            ..ParseOptions::default()
+        };
+        let batch = parse_text_with(Cursor::new(data), &options).unwrap();
+        assert_eq!(batch.transactions[1].posted_at, DEFAULT_DATE);
+        assert_eq!(batch.transactions[1].amount.amount, Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_parse_text_report() {
+        let data = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 9.99 EUR\n\
+                    ---\n\
+                    ID: TX2\nDate: someday\nType: Debit\nAmount: 5 EUR\nNote: skipped\n\
+                    ---\n\
+                    ID: TX3\nType: Credit\n\
+                    ID: TX4\nDate: 2024-01-16\nType: Credit\nAmount: 100 EUR\nNote: kept\n";
+        let report = parse_text_report(Cursor::new(data), &ParseOptions::default()).unwrap();
+        let ids: Vec<_> = report.transactions.iter().map(|tx| &tx.id).collect();
+        assert_eq!(ids, ["TX1", "TX4"]);
+        let errors: Vec<_> = report.errors.iter().map(|e| e.to_string()).collect();
+        assert!(errors[0].contains("line 7: invalid date"), "{:?}", errors);
+        assert!(errors[1].contains("(line 12): missing"), "{:?}", errors);
+        assert!(errors[1].ends_with("Date, Amount"), "{:?}", errors);
+        assert_eq!(errors.len(), 2);
+        let warning = "line 18: ignored unrecognized line: Note: kept";
+        assert_eq!(report.warnings, [warning]);
+
+        // Strict mode rejects the record with the unknown line instead
+        let options = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+        let report = parse_text_report(Cursor::new(data), &options).unwrap();
+        assert_eq!(report.transactions.len(), 1);
+        assert_eq!(report.errors.len(), 3);
+        assert!(report.warnings.is_empty());
+    }
 }
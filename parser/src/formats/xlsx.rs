@@ -0,0 +1,454 @@
+use crate::{
+    Balance, Error, Money, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use calamine::{open_workbook_from_rs, Data, DataType, Range, Reader, Xlsx};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use rust_xlsxwriter::{Format as CellFormat, Workbook, Worksheet, XlsxError};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Write};
+use std::str::FromStr;
+
+/// Name of the worksheet holding one row per transaction.
+pub const TRANSACTIONS_SHEET: &str = "Transactions";
+
+/// Name of the worksheet holding the account ID and balances.
+pub const STATEMENT_SHEET: &str = "Statement";
+
+/// Header row of the transactions sheet; the same names as the CSV columns.
+const HEADER: [&str; 13] = [
+    "TransactionId",
+    "PostedDate",
+    "ExecutedDate",
+    "Type",
+    "Amount",
+    "Currency",
+    "Description",
+    "Account",
+    "Counterparty",
+    "Category",
+    "TaxAmount",
+    "TaxRate",
+    "Status",
+];
+
+/// Columns that must be present in the header row.
+const REQUIRED: [&str; 5] = ["TransactionId", "PostedDate", "Type", "Amount", "Currency"];
+
+/// Parses transaction data from an Excel workbook (`.xlsx`).
+///
+/// Transactions are read from the [`TRANSACTIONS_SHEET`] worksheet, or the
+/// first worksheet if there is none by that name. Its first row is a header
+/// naming the columns as in the CSV format, in any order and case; other
+/// columns are ignored. Dates may be date cells or `YYYY-MM-DD` text, and
+/// amounts number cells or text. An optional [`STATEMENT_SHEET`] worksheet
+/// supplies the account ID and balances.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing an XLSX workbook
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the workbook is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not a valid XLSX workbook
+/// - A required column is missing from the header row
+/// - A date, transaction type, status or amount cannot be parsed
+pub fn parse_xlsx<R: Read>(mut reader: R) -> Result<TransactionBatch> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data)).map_err(xlsx_error)?;
+
+    let names = workbook.sheet_names();
+    let sheet = names
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(TRANSACTIONS_SHEET))
+        .or_else(|| names.first())
+        .ok_or_else(|| Error::parse("XLSX", "workbook has no worksheets"))?;
+    let range = workbook.worksheet_range(sheet).map_err(xlsx_error)?;
+
+    let mut batch = TransactionBatch {
+        transactions: parse_transactions(&range)?,
+        ..TransactionBatch::default()
+    };
+
+    if let Some(sheet) = names
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(STATEMENT_SHEET))
+    {
+        let range = workbook.worksheet_range(sheet).map_err(xlsx_error)?;
+        parse_statement(&range, &mut batch)?;
+    }
+
+    Ok(batch)
+}
+
+fn parse_transactions(range: &Range<Data>) -> Result<Vec<Transaction>> {
+    let mut rows = range.rows();
+    let Some(header) = rows.next() else {
+        return Ok(Vec::new());
+    };
+
+    let positions: Vec<Option<usize>> = HEADER
+        .iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|cell| text(cell).is_some_and(|t| t.trim().eq_ignore_ascii_case(name)))
+        })
+        .collect();
+    if let Some(missing) = REQUIRED
+        .iter()
+        .find(|name| positions[column(name)].is_none())
+    {
+        return Err(Error::parse("XLSX", format!("missing column: {}", missing)));
+    }
+
+    let mut transactions = Vec::new();
+    // Rows are numbered as Excel shows them, the header being row 1
+    for (index, row) in rows.enumerate() {
+        let cell = |name: &str| {
+            positions[column(name)]
+                .and_then(|i| row.get(i))
+                .filter(|cell| {
+                    !cell.is_empty() && cell.get_string().is_none_or(|s| !s.trim().is_empty())
+                })
+        };
+        if REQUIRED.iter().all(|name| cell(name).is_none()) {
+            continue;
+        }
+        let row_num = index + 2;
+        let row_error =
+            |message: String| Error::parse("XLSX", format!("row {}: {}", row_num, message));
+
+        let string = |name: &str| cell(name).and_then(text).map(|t| t.trim().to_string());
+        let optional = |name: &str| string(name).filter(|t| !t.is_empty());
+        let decimal = |name: &str| {
+            cell(name)
+                .map(|c| to_decimal(c).ok_or_else(|| row_error(format!("invalid {}: {}", name, c))))
+                .transpose()
+        };
+
+        let posted_at = cell("PostedDate")
+            .and_then(to_date)
+            .ok_or_else(|| row_error("invalid PostedDate".to_string()))?;
+        let executed_at = cell("ExecutedDate")
+            .map(|c| {
+                to_datetime(c).ok_or_else(|| row_error(format!("invalid ExecutedDate: {}", c)))
+            })
+            .transpose()?;
+        let kind = TransactionKind::from_str(&string("Type").unwrap_or_default())
+            .map_err(|e| row_error(format!("invalid transaction type: {}", e)))?;
+        let amount = decimal("Amount")?.ok_or_else(|| row_error("missing Amount".to_string()))?;
+        let status = optional("Status")
+            .map(|s| {
+                TransactionStatus::from_str(&s)
+                    .map_err(|e| row_error(format!("invalid Status: {}", e)))
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        transactions.push(Transaction {
+            id: string("TransactionId").unwrap_or_default(),
+            posted_at,
+            executed_at,
+            kind,
+            status,
+            amount: Money {
+                amount,
+                currency: string("Currency").unwrap_or_default(),
+            },
+            description: string("Description").unwrap_or_default(),
+            account: optional("Account"),
+            counterparty: optional("Counterparty"),
+            category: optional("Category"),
+            tax_amount: decimal("TaxAmount")?,
+            tax_rate: decimal("TaxRate")?,
+            extra: BTreeMap::new(),
+        });
+    }
+
+    Ok(transactions)
+}
+
+/// Reads the statement sheet: an `Account` row, then a `Currency`, `Opening`,
+/// `Closing` header followed by one row per balance.
+fn parse_statement(range: &Range<Data>, batch: &mut TransactionBatch) -> Result<()> {
+    let mut in_balances = false;
+    for row in range.rows() {
+        let label = row.first().and_then(text).map(|t| t.trim().to_string());
+        match label.as_deref() {
+            None | Some("") => continue,
+            Some(label) if label.eq_ignore_ascii_case("Account") => {
+                batch.account_id = row.get(1).and_then(text).filter(|t| !t.is_empty());
+            }
+            Some(label) if label.eq_ignore_ascii_case("Currency") => in_balances = true,
+            Some(currency) if in_balances => {
+                let amount = |i: usize, name: &str| {
+                    row.get(i).and_then(to_decimal).ok_or_else(|| {
+                        Error::parse(
+                            "XLSX",
+                            format!("{}: invalid {} balance", STATEMENT_SHEET, name),
+                        )
+                    })
+                };
+                batch.balances.push(Balance {
+                    currency: currency.to_string(),
+                    opening: amount(1, "opening")?,
+                    closing: amount(2, "closing")?,
+                });
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn column(name: &str) -> usize {
+    HEADER
+        .iter()
+        .position(|h| *h == name)
+        .expect("known column")
+}
+
+fn text(cell: &Data) -> Option<String> {
+    match cell {
+        Data::String(s) | Data::DateTimeIso(s) => Some(s.clone()),
+        Data::Int(_) | Data::Float(_) => cell.as_string(),
+        Data::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn to_decimal(cell: &Data) -> Option<Decimal> {
+    match cell {
+        Data::Int(v) => Some(Decimal::from(*v)),
+        // `f64`'s `Display` gives the shortest text that reads back as the
+        // same value, so 1200.5 doesn't turn into 1200.4999999999999545...
+        Data::Float(v) => Decimal::from_str(&v.to_string()).ok(),
+        Data::String(s) => Decimal::from_str(s.trim()).ok(),
+        _ => None,
+    }
+}
+
+fn to_date(cell: &Data) -> Option<NaiveDate> {
+    match cell {
+        Data::String(s) => NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").ok(),
+        _ => cell.as_date(),
+    }
+}
+
+fn to_datetime(cell: &Data) -> Option<NaiveDateTime> {
+    match cell {
+        Data::String(s) => NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M:%S").ok(),
+        _ => cell.as_datetime(),
+    }
+}
+
+/// Writes transaction data as an Excel workbook (`.xlsx`).
+///
+/// The [`TRANSACTIONS_SHEET`] worksheet gets a header row and one row per
+/// transaction, with dates as date cells and amounts, tax amounts and tax
+/// rates as number cells formatted with the value's number of decimal
+/// places. Excel stores numbers as 64-bit floats, so amounts with more than
+/// 15 significant digits lose precision. If the batch has an account ID or
+/// balances, they are written to a [`STATEMENT_SHEET`] worksheet.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the workbook to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if the workbook cannot be built or
+/// any I/O operation fails.
+pub fn write_xlsx<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    let mut workbook = Workbook::new();
+    build_workbook(batch, &mut workbook).map_err(xlsx_error)?;
+    let buffer = workbook.save_to_buffer().map_err(xlsx_error)?;
+    writer.write_all(&buffer)?;
+    Ok(())
+}
+
+fn build_workbook(
+    batch: &TransactionBatch,
+    workbook: &mut Workbook,
+) -> std::result::Result<(), XlsxError> {
+    let bold = CellFormat::new().set_bold();
+    let date = CellFormat::new().set_num_format("yyyy-mm-dd");
+    let datetime = CellFormat::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(TRANSACTIONS_SHEET)?;
+    for (col, name) in HEADER.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *name, &bold)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+
+    for (index, tx) in batch.transactions.iter().enumerate() {
+        let row = index as u32 + 1;
+        sheet.write_string(row, 0, &tx.id)?;
+        sheet.write_datetime_with_format(row, 1, tx.posted_at, &date)?;
+        if let Some(executed_at) = tx.executed_at {
+            sheet.write_datetime_with_format(row, 2, executed_at, &datetime)?;
+        }
+        sheet.write_string(row, 3, tx.kind.to_string())?;
+        write_decimal(sheet, row, 4, tx.amount.amount)?;
+        sheet.write_string(row, 5, &tx.amount.currency)?;
+        sheet.write_string(row, 6, &tx.description)?;
+        let optional = [&tx.account, &tx.counterparty, &tx.category];
+        for (col, value) in (7..).zip(optional) {
+            if let Some(value) = value {
+                sheet.write_string(row, col, value)?;
+            }
+        }
+        if let Some(tax_amount) = tx.tax_amount {
+            write_decimal(sheet, row, 10, tax_amount)?;
+        }
+        if let Some(tax_rate) = tx.tax_rate {
+            write_decimal(sheet, row, 11, tax_rate)?;
+        }
+        sheet.write_string(row, 12, tx.status.to_string())?;
+    }
+    sheet.autofit();
+
+    if batch.account_id.is_none() && batch.balances.is_empty() {
+        return Ok(());
+    }
+
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(STATEMENT_SHEET)?;
+    sheet.write_string_with_format(0, 0, "Account", &bold)?;
+    if let Some(account_id) = &batch.account_id {
+        sheet.write_string(0, 1, account_id)?;
+    }
+    for (col, name) in ["Currency", "Opening", "Closing"].iter().enumerate() {
+        sheet.write_string_with_format(2, col as u16, *name, &bold)?;
+    }
+    for (index, balance) in batch.balances.iter().enumerate() {
+        let row = index as u32 + 3;
+        sheet.write_string(row, 0, &balance.currency)?;
+        write_decimal(sheet, row, 1, balance.opening)?;
+        write_decimal(sheet, row, 2, balance.closing)?;
+    }
+    sheet.autofit();
+
+    Ok(())
+}
+
+/// Writes a number cell displayed with the decimal's own scale.
+fn write_decimal(
+    sheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Decimal,
+) -> std::result::Result<(), XlsxError> {
+    let num_format = match value.scale() {
+        0 => "0".to_string(),
+        scale => format!("0.{}", "0".repeat(scale as usize)),
+    };
+    let number = f64::try_from(value).unwrap_or(f64::NAN);
+    sheet.write_number_with_format(
+        row,
+        col,
+        number,
+        &CellFormat::new().set_num_format(num_format),
+    )?;
+    Ok(())
+}
+
+fn xlsx_error(error: impl std::fmt::Display) -> Error {
+    Error::parse("XLSX", error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xlsx_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![Transaction {
+                id: "TX001".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                    .unwrap()
+                    .and_hms_opt(10, 30, 15),
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Pending,
+                amount: Money {
+                    amount: Decimal::new(120055, 2),
+                    currency: "RUB".to_string(),
+                },
+                description: "Payment, \"urgent\"".to_string(),
+                account: None,
+                counterparty: Some("ООО Ромашка".to_string()),
+                category: None,
+                tax_amount: Some(Decimal::new(20009, 2)),
+                tax_rate: Some(Decimal::new(20, 0)),
+                extra: BTreeMap::new(),
+            }],
+            balances: vec![Balance {
+                currency: "RUB".to_string(),
+                opening: Decimal::new(5000, 0),
+                closing: Decimal::new(379945, 2),
+            }],
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_xlsx(&batch, &mut output).unwrap();
+        assert_eq!(&output[..2], b"PK");
+
+        let parsed = parse_xlsx(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+    }
+
+    #[test]
+    fn test_parse_xlsx_with_text_cells_and_extra_columns() {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        let rows = [
+            [
+                "Branch",
+                "type",
+                "PostedDate",
+                "Amount",
+                "Currency",
+                "TransactionId",
+            ],
+            ["Moscow", "Credit", "2024-02-01", "99.90", "USD", "42"],
+            ["", "", "", "", "", ""],
+        ];
+        for (row, values) in rows.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                sheet.write_string(row as u32, col as u16, *value).unwrap();
+            }
+        }
+
+        let parsed = parse_xlsx(Cursor::new(workbook.save_to_buffer().unwrap())).unwrap();
+        assert_eq!(parsed.transactions.len(), 1);
+        assert_eq!(parsed.transactions[0].id, "42");
+        assert_eq!(parsed.transactions[0].amount.amount.to_string(), "99.90");
+
+        let mut workbook = Workbook::new();
+        workbook
+            .add_worksheet()
+            .write_string(0, 0, "TransactionId")
+            .unwrap();
+        let err = parse_xlsx(Cursor::new(workbook.save_to_buffer().unwrap())).unwrap_err();
+        assert!(err.to_string().contains("missing column: PostedDate"));
+    }
+}
@@ -0,0 +1,561 @@
+use crate::{
+    Balance, BatchMetadata, Error, Money, Period, Result, Transaction, TransactionBatch,
+    TransactionKind, TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use quick_xml::escape::{escape, resolve_predefined_entity};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufReader, Read, Write};
+use std::str::FromStr;
+
+/// Child elements of a `<Transaction>` or `<Balance>`, with the attributes
+/// that matter (the currency and the names of `<Extra>` elements) kept aside.
+#[derive(Default)]
+struct Record {
+    fields: HashMap<String, String>,
+    currency: Option<String>,
+    extra: BTreeMap<String, String>,
+    /// The name of the `<Extra>` element being read
+    extra_name: Option<String>,
+}
+
+impl Record {
+    fn take(&mut self, name: &str) -> Option<String> {
+        self.fields.remove(name)
+    }
+
+    fn require(&mut self, name: &str, element: &str) -> Result<String> {
+        self.take(name)
+            .ok_or_else(|| Error::parse("XML", format!("{}: missing <{}>", element, name)))
+    }
+}
+
+/// Parses transaction data from an XML document.
+///
+/// The document has a `<TransactionBatch>` root with an optional
+/// `<AccountId>`, optional `<Balance currency="...">` elements holding
+/// `<Opening>` and `<Closing>`, an optional `<Metadata>` element with
+/// `<GeneratedAt>`, `<SourceSystem>`, `<Period>` and `<SchemaVersion>`
+/// children, and one `<Transaction>` element per
+/// transaction. Transaction fields are child elements named like the text
+/// format labels (`ID`, `Date`, `ExecutedDate`, `Type`, `Status`, `Amount`,
+/// `Description`, `Account`, `Counterparty`, `Category`, `TaxAmount`,
+/// `TaxRate`); `<Amount>` carries the currency in a `currency` attribute,
+/// and `<Status>` is left out for booked transactions. Each entry of
+/// [`Transaction::extra`] is an `<Extra name="...">` element.
+/// Unknown elements are ignored.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing an XML document
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the document is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not well-formed XML or the root is not `<TransactionBatch>`
+/// - A transaction lacks `ID`, `Date`, `Type` or `Amount`
+/// - A date, kind, status or amount cannot be parsed
+pub fn parse_xml<R: Read>(reader: R) -> Result<TransactionBatch> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    let mut buf = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut record = Record::default();
+    let mut batch = TransactionBatch::default();
+    let mut seen_root = false;
+
+    loop {
+        let event = reader.read_event_into(&mut buf).map_err(|e| {
+            Error::parse("XML", format!("at byte {}: {}", reader.error_position(), e))
+        })?;
+
+        match event {
+            Event::Start(element) => {
+                seen_root = true;
+                open_element(&element, &path, &mut record)?;
+                path.push(element_name(&element)?);
+                text.clear();
+            }
+            Event::Empty(element) => {
+                seen_root = true;
+                open_element(&element, &path, &mut record)?;
+                let name = element_name(&element)?;
+                close_element(&name, &path, String::new(), &mut record, &mut batch)?;
+            }
+            Event::End(_) => {
+                // quick-xml has already checked that the tags match
+                if let Some(name) = path.pop() {
+                    close_element(
+                        &name,
+                        &path,
+                        std::mem::take(&mut text),
+                        &mut record,
+                        &mut batch,
+                    )?;
+                }
+            }
+            Event::Text(content) => text.push_str(&content.xml_content().map_err(xml_error)?),
+            Event::CData(content) => text.push_str(&content.decode().map_err(xml_error)?),
+            Event::GeneralRef(reference) => {
+                if let Some(ch) = reference.resolve_char_ref().map_err(xml_error)? {
+                    text.push(ch);
+                } else {
+                    let name = reference.decode().map_err(xml_error)?;
+                    let value = resolve_predefined_entity(&name)
+                        .ok_or_else(|| Error::parse("XML", format!("unknown entity &{};", name)))?;
+                    text.push_str(value);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if !seen_root {
+        return Err(Error::parse(
+            "XML",
+            "missing <TransactionBatch> root element",
+        ));
+    }
+
+    Ok(batch)
+}
+
+fn element_name(element: &BytesStart) -> Result<String> {
+    Ok(std::str::from_utf8(element.local_name().as_ref())
+        .map_err(|e| Error::parse("XML", format!("invalid element name: {}", e)))?
+        .to_string())
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>> {
+    element
+        .try_get_attribute(name)
+        .map_err(xml_error)?
+        .map(|attr| attr.decode_and_unescape_value(element.decoder()).map(|v| v.into_owned()))
+        .transpose()
+        .map_err(xml_error)
+}
+
+fn xml_error(error: impl std::fmt::Display) -> Error {
+    Error::parse("XML", error.to_string())
+}
+
+fn open_element(element: &BytesStart, path: &[String], record: &mut Record) -> Result<()> {
+    let name = element_name(element)?;
+    match path {
+        [] if name != "TransactionBatch" => Err(Error::parse(
+            "XML",
+            format!("expected <TransactionBatch> root element, found <{}>", name),
+        )),
+        [_] if name == "Transaction" || name == "Balance" => {
+            *record = Record {
+                currency: attribute(element, "currency")?,
+                ..Record::default()
+            };
+            Ok(())
+        }
+        [_, parent] if parent == "Transaction" && name == "Amount" => {
+            record.currency = attribute(element, "currency")?;
+            Ok(())
+        }
+        [_, parent] if parent == "Transaction" && name == "Extra" => {
+            record.extra_name = Some(
+                attribute(element, "name")?
+                    .ok_or_else(|| Error::parse("XML", "<Extra> has no name attribute"))?,
+            );
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn close_element(
+    name: &str,
+    path: &[String],
+    text: String,
+    record: &mut Record,
+    batch: &mut TransactionBatch,
+) -> Result<()> {
+    match path {
+        [_] => match name {
+            "AccountId" => batch.account_id = Some(text),
+            "Transaction" => {
+                let index = batch.transactions.len() + 1;
+                let tx = build_transaction(std::mem::take(record))
+                    .map_err(|e| e.prefixed("XML", &format!("transaction {}: ", index)))?;
+                batch.transactions.push(tx);
+            }
+            "Balance" => batch.balances.push(build_balance(std::mem::take(record))?),
+            _ => {}
+        },
+        [_, parent] if parent == "Transaction" && name == "Extra" => {
+            if let Some(extra_name) = record.extra_name.take() {
+                record.extra.insert(extra_name, text);
+            }
+        }
+        [_, parent] if parent == "Transaction" || parent == "Balance" => {
+            record.fields.insert(name.to_string(), text);
+        }
+        [_, parent] if parent == "Metadata" => set_metadata(&mut batch.metadata, name, text)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn set_metadata(metadata: &mut BatchMetadata, name: &str, text: String) -> Result<()> {
+    match name {
+        "GeneratedAt" => {
+            let generated_at = NaiveDateTime::parse_from_str(text.trim(), "%Y-%m-%d %H:%M:%S")
+                .map_err(|e| {
+                    Error::parse("XML", format!("invalid GeneratedAt: {}", e)).with_source(e)
+                })?;
+            metadata.generated_at = Some(generated_at);
+        }
+        "SourceSystem" => metadata.source_system = Some(text),
+        "Period" => {
+            let period = Period::from_str(text.trim()).map_err(|e| e.prefixed("XML", ""))?;
+            metadata.period = Some(period);
+        }
+        "SchemaVersion" => metadata.schema_version = Some(text),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn build_transaction(mut record: Record) -> Result<Transaction> {
+    let id = record.require("ID", "Transaction")?;
+
+    let posted_at = NaiveDate::parse_from_str(&record.require("Date", "Transaction")?, "%Y-%m-%d")
+        .map_err(|e| Error::parse("XML", format!("invalid Date: {}", e)).with_source(e))?;
+
+    let executed_at = record
+        .take("ExecutedDate")
+        .map(|s| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+        .transpose()
+        .map_err(|e| Error::parse("XML", format!("invalid ExecutedDate: {}", e)).with_source(e))?;
+
+    let kind = TransactionKind::from_str(&record.require("Type", "Transaction")?)
+        .map_err(|e| Error::parse("XML", format!("invalid Type: {}", e)))?;
+
+    let status = record
+        .take("Status")
+        .map(|s| TransactionStatus::from_str(&s))
+        .transpose()
+        .map_err(|e| Error::parse("XML", format!("invalid Status: {}", e)))?
+        .unwrap_or_default();
+
+    let amount = parse_decimal(&record.require("Amount", "Transaction")?, "Amount")?;
+    let currency = record
+        .currency
+        .take()
+        .ok_or_else(|| Error::parse("XML", "<Amount> has no currency attribute"))?;
+
+    Ok(Transaction {
+        id,
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money { amount, currency },
+        description: record.take("Description").unwrap_or_default(),
+        account: record.take("Account"),
+        counterparty: record.take("Counterparty"),
+        category: record.take("Category"),
+        tax_amount: record
+            .take("TaxAmount")
+            .map(|v| parse_decimal(&v, "TaxAmount"))
+            .transpose()?,
+        tax_rate: record
+            .take("TaxRate")
+            .map(|v| parse_decimal(&v, "TaxRate"))
+            .transpose()?,
+        extra: record.extra,
+    })
+}
+
+fn build_balance(mut record: Record) -> Result<Balance> {
+    Ok(Balance {
+        opening: parse_decimal(&record.require("Opening", "Balance")?, "Opening")?,
+        closing: parse_decimal(&record.require("Closing", "Balance")?, "Closing")?,
+        currency: record
+            .currency
+            .ok_or_else(|| Error::parse("XML", "<Balance> has no currency attribute"))?,
+    })
+}
+
+fn parse_decimal(value: &str, name: &str) -> Result<Decimal> {
+    Decimal::from_str(value.trim())
+        .map_err(|e| Error::parse("XML", format!("invalid {}: {}", name, e)).with_source(e))
+}
+
+/// Writes transaction data as an XML document.
+///
+/// Produces the layout described in [`parse_xml`], indented by two spaces.
+/// Optional fields that are absent are omitted.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the XML document to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_xml<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    let account_id = batch.account_id.as_deref();
+    write_xml_header(writer, account_id, &batch.balances, &batch.metadata)?;
+    for tx in &batch.transactions {
+        write_xml_transaction(writer, tx)?;
+    }
+    write_xml_footer(writer)
+}
+
+/// Writes the start of the document of [`write_xml`], up to the first
+/// transaction.
+pub(crate) fn write_xml_header<W: Write>(
+    writer: &mut W,
+    account_id: Option<&str>,
+    balances: &[Balance],
+    metadata: &BatchMetadata,
+) -> Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, "<TransactionBatch>")?;
+
+    if let Some(account) = account_id {
+        write_element(writer, 1, "AccountId", account)?;
+    }
+
+    for balance in balances {
+        writeln!(
+            writer,
+            r#"  <Balance currency="{}">"#,
+            escape_attribute(&balance.currency)
+        )?;
+        write_element(writer, 2, "Opening", &balance.opening.to_string())?;
+        write_element(writer, 2, "Closing", &balance.closing.to_string())?;
+        writeln!(writer, "  </Balance>")?;
+    }
+
+    if !metadata.is_empty() {
+        writeln!(writer, "  <Metadata>")?;
+        if let Some(generated_at) = metadata.generated_at {
+            let generated_at = generated_at.format("%Y-%m-%d %H:%M:%S").to_string();
+            write_element(writer, 2, "GeneratedAt", &generated_at)?;
+        }
+        if let Some(source_system) = &metadata.source_system {
+            write_element(writer, 2, "SourceSystem", source_system)?;
+        }
+        if let Some(period) = metadata.period {
+            write_element(writer, 2, "Period", &period.to_string())?;
+        }
+        if let Some(schema_version) = &metadata.schema_version {
+            write_element(writer, 2, "SchemaVersion", schema_version)?;
+        }
+        writeln!(writer, "  </Metadata>")?;
+    }
+    Ok(())
+}
+
+/// Writes the `Transaction` element of one transaction.
+pub(crate) fn write_xml_transaction<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
+    writeln!(writer, "  <Transaction>")?;
+    write_element(writer, 2, "ID", &tx.id)?;
+    write_element(
+        writer,
+        2,
+        "Date",
+        &tx.posted_at.format("%Y-%m-%d").to_string(),
+    )?;
+    if let Some(executed) = tx.executed_at {
+        write_element(
+            writer,
+            2,
+            "ExecutedDate",
+            &executed.format("%Y-%m-%d %H:%M:%S").to_string(),
+        )?;
+    }
+    write_element(writer, 2, "Type", &tx.kind.to_string())?;
+    if !tx.status.is_booked() {
+        write_element(writer, 2, "Status", &tx.status.to_string())?;
+    }
+    writeln!(
+        writer,
+        r#"    <Amount currency="{}">{}</Amount>"#,
+        escape_attribute(&tx.amount.currency),
+        tx.amount.amount
+    )?;
+    write_element(writer, 2, "Description", &tx.description)?;
+    if let Some(account) = &tx.account {
+        write_element(writer, 2, "Account", account)?;
+    }
+    if let Some(counterparty) = &tx.counterparty {
+        write_element(writer, 2, "Counterparty", counterparty)?;
+    }
+    if let Some(category) = &tx.category {
+        write_element(writer, 2, "Category", category)?;
+    }
+    if let Some(tax) = tx.tax_amount {
+        write_element(writer, 2, "TaxAmount", &tax.to_string())?;
+    }
+    if let Some(rate) = tx.tax_rate {
+        write_element(writer, 2, "TaxRate", &rate.to_string())?;
+    }
+    for (name, value) in &tx.extra {
+        writeln!(
+            writer,
+            r#"    <Extra name="{}">{}</Extra>"#,
+            escape_attribute(name),
+            escape_text(value)
+        )?;
+    }
+    writeln!(writer, "  </Transaction>")?;
+    Ok(())
+}
+
+/// Writes the end of the document of [`write_xml`].
+pub(crate) fn write_xml_footer<W: Write>(writer: &mut W) -> Result<()> {
+    writeln!(writer, "</TransactionBatch>")?;
+    Ok(())
+}
+
+fn write_element<W: Write>(writer: &mut W, depth: usize, name: &str, value: &str) -> Result<()> {
+    writeln!(
+        writer,
+        "{:indent$}<{name}>{}</{name}>",
+        "",
+        escape_text(value),
+        indent = depth * 2,
+        name = name
+    )?;
+    Ok(())
+}
+
+/// Escapes element text. Carriage returns are written as `&#13;`, since
+/// XML parsers turn `\r\n` and `\r` into `\n`.
+fn escape_text(value: &str) -> String {
+    escape(value).replace('\r', "&#13;")
+}
+
+/// Escapes an attribute value, also writing newlines and tabs as character
+/// references, which XML parsers turn into spaces in attributes.
+fn escape_attribute(value: &str) -> String {
+    escape_text(value)
+        .replace('\n', "&#10;")
+        .replace('\t', "&#9;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_xml_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![
+                Transaction {
+                    id: "TX001".to_string(),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                    executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                        .unwrap()
+                        .and_hms_opt(10, 30, 0),
+                    kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
+                    amount: Money {
+                        amount: Decimal::new(120050, 2),
+                        currency: "RUB".to_string(),
+                    },
+                    description: "Tom & Jerry <Ltd>".to_string(),
+                    account: Some("40817810".to_string()),
+                    counterparty: Some("ООО \"Ромашка\"".to_string()),
+                    category: Some("Services".to_string()),
+                    tax_amount: Some(Decimal::new(20008, 2)),
+                    tax_rate: Some(Decimal::new(20, 0)),
+                    extra: BTreeMap::from([
+                        ("MCC".to_string(), "7399".to_string()),
+                        ("Note \"1\"".to_string(), "a\r\nb".to_string()),
+                    ]),
+                },
+                Transaction {
+                    id: "TX002".to_string(),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
+                    executed_at: None,
+                    kind: TransactionKind::Credit,
+                    status: TransactionStatus::Booked,
+                    amount: Money {
+                        amount: Decimal::new(5000, 2),
+                        currency: "USD".to_string(),
+                    },
+                    description: "line 1\r\nline 2\rline 3\n".to_string(),
+                    account: None,
+                    counterparty: None,
+                    category: None,
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
+                },
+            ],
+            balances: vec![Balance {
+                currency: "USD".to_string(),
+                opening: Decimal::new(1000, 2),
+                closing: Decimal::new(6000, 2),
+            }],
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_xml(&batch, &mut output).unwrap();
+        let xml = String::from_utf8(output.clone()).unwrap();
+        assert!(xml.contains("<Description>Tom &amp; Jerry &lt;Ltd&gt;</Description>"));
+        assert!(xml.contains(r#"<Amount currency="RUB">1200.50</Amount>"#));
+        assert!(xml.contains(r#"<Extra name="MCC">7399</Extra>"#));
+        assert!(xml.contains("<Description>line 1&#13;\nline 2&#13;line 3\n</Description>"));
+
+        let parsed = parse_xml(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+    }
+
+    #[test]
+    fn test_parse_xml_core_banking_export() {
+        let data = r#"<?xml version="1.0"?>
+<!-- exported by the core-banking system -->
+<TransactionBatch>
+  <Transaction>
+    <ID>TX1</ID><Date>2024-03-01</Date><Type>Credit</Type>
+    <Amount currency="EUR"> 15.5 </Amount>
+    <Description><![CDATA[Refund <partial>]]></Description>
+    <Channel>SWIFT</Channel>
+    <Description2/>
+  </Transaction>
+</TransactionBatch>"#;
+        let batch = parse_xml(Cursor::new(data)).unwrap();
+        let tx = &batch.transactions[0];
+        assert_eq!(tx.amount.amount, Decimal::new(155, 1));
+        assert_eq!(tx.description, "Refund <partial>");
+
+        let err = parse_xml(Cursor::new("")).unwrap_err();
+        assert!(err.to_string().contains("missing <TransactionBatch>"));
+        let err = parse_xml(Cursor::new("<Batch/>")).unwrap_err();
+        assert!(err.to_string().contains("expected <TransactionBatch>"));
+
+        let data = "<TransactionBatch><Transaction><ID>TX1</ID></Transaction></TransactionBatch>";
+        let err = parse_xml(Cursor::new(data)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("transaction 1: Transaction: missing <Date>"));
+    }
+}
@@ -0,0 +1,158 @@
+use super::json::{BatchDocument, TransactionDocument};
+use crate::{Balance, BatchMetadata, Error, Result, Transaction, TransactionBatch};
+use std::io::{Read, Write};
+
+/// Parses transaction data from a YAML document.
+///
+/// The document has the same structure and field names as the JSON format
+/// (`accountId`, `balances`, `transactions` with `postedAt`, `kind`,
+/// `amount`, `currency`, ...). Amounts may be written unquoted; they are read
+/// as written, without going through floating point. Comments are allowed
+/// anywhere, which makes YAML convenient for hand-written test fixtures.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing a YAML document
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the document is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The input is not valid YAML or lacks required fields
+/// - A date, kind, status or amount cannot be parsed
+pub fn parse_yaml<R: Read>(reader: R) -> Result<TransactionBatch> {
+    let document: BatchDocument =
+        serde_yaml::from_reader(reader).map_err(|e| Error::parse("YAML", e.to_string()))?;
+    document.into_batch("YAML")
+}
+
+/// Writes transaction data as a YAML document.
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the YAML document to
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+///
+/// # Errors
+///
+/// This function will return an error if any I/O operation fails.
+pub fn write_yaml<W: Write>(batch: &TransactionBatch, writer: &mut W) -> Result<()> {
+    serde_yaml::to_writer(writer, &BatchDocument::from_batch(batch))
+        .map_err(|e| Error::parse("YAML", e.to_string()))
+}
+
+/// Writes the start of the document of [`write_yaml`], up to the
+/// `transactions` key.
+pub(crate) fn write_yaml_header<W: Write>(
+    writer: &mut W,
+    account_id: Option<&str>,
+    balances: &[Balance],
+    metadata: &BatchMetadata,
+) -> Result<()> {
+    let header = BatchDocument::header(account_id, balances, metadata);
+    let header = serde_yaml::to_string(&header).map_err(|e| Error::parse("YAML", e.to_string()))?;
+    // The transactions come last, as an empty sequence
+    let header = header
+        .strip_suffix(" []\n")
+        .expect("the transactions end the document");
+    writer.write_all(header.as_bytes())?;
+    Ok(())
+}
+
+/// Writes one transaction of [`write_yaml`], as an item of the
+/// `transactions` sequence.
+pub(crate) fn write_yaml_transaction<W: Write>(writer: &mut W, tx: &Transaction) -> Result<()> {
+    let item = serde_yaml::to_string(&[TransactionDocument::from_transaction(tx)])
+        .map_err(|e| Error::parse("YAML", e.to_string()))?;
+    write!(writer, "\n{}", item.trim_end_matches('\n'))?;
+    Ok(())
+}
+
+/// Writes the end of the document of [`write_yaml`], after `count`
+/// transactions.
+pub(crate) fn write_yaml_footer<W: Write>(writer: &mut W, count: usize) -> Result<()> {
+    if count == 0 {
+        write!(writer, " []")?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, Transaction, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_yaml_fixture() {
+        let data = r#"
+# Fixture for the refund flow
+accountId: ACC123
+transactions:
+  - id: TX001
+    postedAt: 2024-01-15
+    kind: Debit
+    amount: 1200.50   # unquoted, keeps its scale
+    currency: RUB
+    description: Payment
+    category: Services
+    taxRate: 20
+  - id: TX002
+    postedAt: 2024-01-16
+    executedAt: 2024-01-16 09:00:00
+    kind: Credit
+    amount: "50"
+    currency: USD
+"#;
+        let batch = parse_yaml(Cursor::new(data)).unwrap();
+        assert_eq!(batch.account_id.as_deref(), Some("ACC123"));
+        assert_eq!(batch.transactions[0].amount.amount, Decimal::new(120050, 2));
+        assert_eq!(batch.transactions[0].tax_rate, Some(Decimal::new(20, 0)));
+        assert_eq!(batch.transactions[1].description, "");
+        assert!(batch.transactions[1].executed_at.is_some());
+    }
+
+    #[test]
+    fn test_yaml_roundtrip() {
+        let batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![Transaction {
+                id: "TX001".to_string(),
+                posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                executed_at: None,
+                kind: TransactionKind::Debit,
+                status: TransactionStatus::Booked,
+                amount: Money {
+                    amount: Decimal::new(1000, 2),
+                    currency: "EUR".to_string(),
+                },
+                description: "Coffee: large # with milk\r\nto go".to_string(),
+                account: None,
+                counterparty: Some("Café".to_string()),
+                category: None,
+                tax_amount: Some(Decimal::new(167, 2)),
+                tax_rate: None,
+                extra: BTreeMap::from([("Terminal ID".to_string(), "T-01: #2".to_string())]),
+            }],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+
+        let mut output = Vec::new();
+        write_yaml(&batch, &mut output).unwrap();
+        let parsed = parse_yaml(Cursor::new(output)).unwrap();
+        assert_eq!(parsed, batch);
+    }
+}
@@ -0,0 +1,219 @@
+use super::csv::parse_csv_fields;
+use super::ParseOptions;
+use crate::{
+    Error, Money, Result, Transaction, TransactionBatch, TransactionKind, TransactionStatus,
+};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// Columns that every YNAB register export has.
+const REQUIRED: [&str; 5] = ["Date", "Payee", "Memo", "Outflow", "Inflow"];
+
+/// Date layouts YNAB offers, tried in order. Slash dates are read month
+/// first, YNAB's default, unless that cannot be a valid date.
+const DATE_FORMATS: [&str; 5] = ["%m/%d/%Y", "%Y-%m-%d", "%d/%m/%Y", "%d.%m.%Y", "%Y/%m/%d"];
+
+/// Parses a register export from YNAB (You Need A Budget).
+///
+/// Columns are located by the header row, which must contain `Date`,
+/// `Payee`, `Memo`, `Outflow` and `Inflow`; `Account` and
+/// `Category Group/Category` (or `Category`) are used when present. A row
+/// with an outflow becomes a [`TransactionKind::Debit`] and one with an
+/// inflow a [`TransactionKind::Credit`]; should a row have both, they are
+/// netted. Amounts may carry currency symbols and either `.` or `,` as the
+/// decimal separator (`$1,234.56`, `1.234,56€`).
+///
+/// The payee becomes the counterparty and the memo the description. YNAB
+/// exports carry neither IDs nor currencies, so every amount is given
+/// `currency` and rows get sequential IDs (`YNAB000001`, ...).
+///
+/// No more rows are read once there are [`ParseOptions::max_records`]
+/// transactions, and the caps of [`ParseOptions::limits`] apply.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing the exported CSV data
+/// * `currency` - The currency code to assign to every transaction
+/// * `options` - Record and size caps, and currency checks
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] with all parsed transactions, or an [`Error`]
+/// if the file is malformed or contains invalid data.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - A required column is missing from the header
+/// - A date or amount cannot be parsed
+/// - A cap of [`ParseOptions::limits`] is exceeded
+pub fn parse_ynab<R: Read>(
+    reader: R,
+    currency: &str,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| parse_rows(reader, currency, options))
+}
+
+fn parse_rows<R: Read>(
+    reader: R,
+    currency: &str,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let mut lines = BufReader::new(reader).lines();
+    let header = match lines.next() {
+        Some(line) => line?,
+        None => return Ok(TransactionBatch::default()),
+    };
+    let header: Vec<String> = parse_csv_fields(header.trim_start_matches('\u{feff}'))
+        .into_iter()
+        .map(|name| name.trim().to_string())
+        .collect();
+    let position = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+
+    let mut required = [0; REQUIRED.len()];
+    for (slot, name) in required.iter_mut().zip(REQUIRED) {
+        *slot = position(name)
+            .ok_or_else(|| Error::parse("YNAB", format!("missing column: {}", name)))?;
+    }
+    let [date, payee, memo, outflow, inflow] = required;
+    let account = position("Account");
+    let category = position("Category Group/Category").or_else(|| position("Category"));
+
+    let mut batch = TransactionBatch::default();
+    for (index, line) in lines.enumerate() {
+        if options.has_max_records(batch.transactions.len()) {
+            break;
+        }
+        let line_num = index + 2;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = parse_csv_fields(&line);
+        let field = |i: usize| fields.get(i).map(|f| f.trim()).unwrap_or("");
+        let optional =
+            |i: Option<usize>| i.map(field).filter(|f| !f.is_empty()).map(str::to_string);
+        let line_error =
+            |message: String| Error::parse("YNAB", format!("line {}: {}", line_num, message));
+
+        let posted_at = parse_date(field(date))
+            .ok_or_else(|| line_error(format!("invalid date: {}", field(date))))?;
+        let amount = |i: usize| {
+            parse_amount(field(i))
+                .ok_or_else(|| line_error(format!("invalid amount: {}", field(i))))
+        };
+        let net = amount(inflow)? - amount(outflow)?;
+
+        batch.transactions.push(Transaction {
+            id: format!("YNAB{:06}", batch.transactions.len() + 1),
+            posted_at,
+            executed_at: None,
+            kind: if net.is_sign_negative() {
+                TransactionKind::Debit
+            } else {
+                TransactionKind::Credit
+            },
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: net.abs(),
+                currency: currency.to_string(),
+            },
+            description: field(memo).to_string(),
+            account: optional(account),
+            counterparty: optional(Some(payee)),
+            category: optional(category),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        });
+    }
+
+    Ok(batch)
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+/// Parses an amount as YNAB formats it for the budget's locale. An empty
+/// field is zero.
+fn parse_amount(value: &str) -> Option<Decimal> {
+    let negative = value.contains('-') || (value.starts_with('(') && value.ends_with(')'));
+    let digits: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+    if digits.is_empty() {
+        return value.trim().is_empty().then_some(Decimal::ZERO);
+    }
+
+    // The last separator is the decimal one, unless a lone comma groups
+    // thousands (`1,234`)
+    let normalized = match digits.rfind(['.', ',']) {
+        Some(pos) if digits.contains('.') || digits.len() - pos - 1 != 3 => {
+            let (whole, fraction) = digits.split_at(pos);
+            format!("{}.{}", whole.replace(['.', ','], ""), &fraction[1..])
+        }
+        _ => digits.replace(['.', ','], ""),
+    };
+
+    let amount = Decimal::from_str(&normalized).ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_ynab() {
+        let data = "\u{feff}\"Account\",\"Flag\",\"Date\",\"Payee\",\"Category Group/Category\",\"Category Group\",\"Category\",\"Memo\",\"Outflow\",\"Inflow\",\"Cleared\"\n\
+            \"Checking\",\"\",\"01/15/2024\",\"Grocer\",\"Everyday: Food\",\"Everyday\",\"Food\",\"Weekly shop\",\"$1,234.50\",\"$0.00\",\"Cleared\"\n\
+            \"Checking\",\"\",\"01/31/2024\",\"Employer\",\"Inflow: Ready to Assign\",\"Inflow\",\"Ready to Assign\",\"\",\"$0.00\",\"$3,000.00\",\"Cleared\"\n";
+        let batch = parse_ynab(Cursor::new(data), "USD", &ParseOptions::default()).unwrap();
+
+        assert_eq!(batch.transactions.len(), 2);
+        let shop = &batch.transactions[0];
+        assert_eq!(shop.id, "YNAB000001");
+        assert_eq!(
+            shop.posted_at,
+            NaiveDate::from_ymd_opt(2024, 1, 15).unwrap()
+        );
+        assert_eq!(shop.kind, TransactionKind::Debit);
+        assert_eq!(shop.amount.amount, Decimal::new(123450, 2));
+        assert_eq!(shop.counterparty.as_deref(), Some("Grocer"));
+        assert_eq!(shop.category.as_deref(), Some("Everyday: Food"));
+        assert_eq!(shop.account.as_deref(), Some("Checking"));
+        assert_eq!(shop.description, "Weekly shop");
+
+        let salary = &batch.transactions[1];
+        assert_eq!(salary.kind, TransactionKind::Credit);
+        assert_eq!(salary.amount.amount, Decimal::new(3000, 0));
+
+        let err = parse_ynab(
+            Cursor::new("Date,Payee,Memo,Amount\n"),
+            "USD",
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("missing column: Outflow"));
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount(""), Some(Decimal::ZERO));
+        assert_eq!(parse_amount("1.234,56€"), Some(Decimal::new(123456, 2)));
+        assert_eq!(parse_amount("12,5 kr"), Some(Decimal::new(125, 1)));
+        assert_eq!(parse_amount("$1,234"), Some(Decimal::new(1234, 0)));
+        assert_eq!(parse_amount("-$5.00"), Some(Decimal::new(-500, 2)));
+        assert_eq!(parse_amount("n/a"), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Detecting gaps in statements.
+//!
+//! A daily feed that silently skips a day looks like any other statement
+//! until month-end reconciliation fails. [`TransactionBatch::gaps`] scans the
+//! period a batch covers for runs of days without a single transaction and
+//! reports those longer than a number of business days, which on an active
+//! account usually means that data went missing.
+
+use crate::{Period, TransactionBatch};
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// A run of days without transactions, as returned by
+/// [`TransactionBatch::gaps`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    /// The days without transactions
+    pub period: Period,
+    /// How many of them are Monday to Friday
+    pub business_days: u32,
+}
+
+impl TransactionBatch {
+    /// Returns the runs of days without transactions that have more than
+    /// `max_business_days` business days, in date order.
+    ///
+    /// The batch's [`period`](Self::period) is scanned, so a declared
+    /// period also finds days missing before the first or after the last
+    /// transaction. Business days are Monday to Friday; public holidays
+    /// aren't known, so a gap over a holiday counts it as a business day. A
+    /// batch without transactions has no gaps, since there is no activity to
+    /// miss.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    ///
+    /// // Friday 2024-01-05, then nothing until Thursday 2024-01-11
+    /// let text = "ID: TX1\nDate: 2024-01-05\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-01-11\nType: Credit\nAmount: 9 USD\n";
+    /// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+    ///
+    /// let gaps = batch.gaps(2);
+    /// assert_eq!(gaps[0].period.to_string(), "2024-01-06..2024-01-10");
+    /// assert_eq!(gaps[0].business_days, 3);
+    /// assert!(batch.gaps(3).is_empty());
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn gaps(&self, max_business_days: u32) -> Vec<Gap> {
+        let active: BTreeSet<NaiveDate> = self.transactions.iter().map(|tx| tx.posted_at).collect();
+        let Some(period) = self.period().filter(|_| !active.is_empty()) else {
+            return Vec::new();
+        };
+
+        let mut gaps = Vec::new();
+        let mut current: Option<Gap> = None;
+        for day in period
+            .start
+            .iter_days()
+            .take_while(|day| *day <= period.end)
+        {
+            if active.contains(&day) {
+                gaps.extend(current.take());
+                continue;
+            }
+            let gap = current.get_or_insert(Gap {
+                period: Period {
+                    start: day,
+                    end: day,
+                },
+                business_days: 0,
+            });
+            gap.period.end = day;
+            if !matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+                gap.business_days += 1;
+            }
+        }
+        gaps.extend(current);
+        gaps.retain(|gap| gap.business_days > max_business_days);
+        gaps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BatchMetadata, Money, Transaction, TransactionKind, TransactionStatus};
+    use alloc::collections::BTreeMap;
+    use alloc::string::{String, ToString};
+    use rust_decimal::Decimal;
+
+    fn tx(date: &str) -> Transaction {
+        Transaction {
+            id: date.to_string(),
+            posted_at: date.parse().unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::ONE,
+                currency: "EUR".to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_gaps() {
+        // 2024-03-01 is a Friday
+        let mut batch = TransactionBatch {
+            transactions: ["2024-03-01", "2024-03-04", "2024-03-08", "2024-03-07"]
+                .into_iter()
+                .map(tx)
+                .collect(),
+            ..TransactionBatch::default()
+        };
+
+        // The weekend before Monday the 4th has no business days
+        let gaps = batch.gaps(0);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].period, "2024-03-05..2024-03-06".parse().unwrap());
+        assert_eq!(gaps[0].business_days, 2);
+        assert!(batch.gaps(2).is_empty());
+
+        batch.metadata = BatchMetadata {
+            period: Some("2024-03-01..2024-03-15".parse().unwrap()),
+            ..BatchMetadata::default()
+        };
+        let gaps = batch.gaps(2);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].period, "2024-03-09..2024-03-15".parse().unwrap());
+        assert_eq!(gaps[0].business_days, 5);
+
+        batch.transactions.clear();
+        assert!(batch.gaps(0).is_empty());
+    }
+}
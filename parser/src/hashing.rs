@@ -0,0 +1,56 @@
+//! Hashing helpers shared by the fingerprints and signatures.
+
+use alloc::format;
+use alloc::string::String;
+use sha2::{Digest, Sha256};
+
+/// Hashes a field length-prefixed, so that no two distinct sequences of
+/// fields share an encoding. A missing field hashes differently from an
+/// empty one.
+pub(crate) fn hash_field(hasher: &mut Sha256, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            hasher.update([1]);
+            hasher.update((value.len() as u64).to_le_bytes());
+            hasher.update(value.as_bytes());
+        }
+        None => hasher.update([0]),
+    }
+}
+
+/// Formats bytes as lowercase hexadecimal digits.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Formats a SHA-256 digest as `sha256:<hex digest>`.
+pub(crate) fn digest_string(digest: &[u8]) -> String {
+    format!("sha256:{}", to_hex(digest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_field() {
+        let digest = |fields: &[Option<&str>]| -> [u8; 32] {
+            let mut hasher = Sha256::new();
+            for field in fields {
+                hash_field(&mut hasher, *field);
+            }
+            hasher.finalize().into()
+        };
+        assert_ne!(
+            digest(&[Some("ab"), Some("c")]),
+            digest(&[Some("a"), Some("bc")])
+        );
+        assert_ne!(digest(&[Some("")]), digest(&[None]));
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0x00, 0x0f, 0xab]), "000fab");
+        assert_eq!(digest_string(&[0xff]), "sha256:ff");
+    }
+}
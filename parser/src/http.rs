@@ -0,0 +1,84 @@
+//! Streaming download of statement files over HTTP(S).
+
+use crate::Result;
+use std::io::Read;
+use std::time::Duration;
+
+/// How long to wait for the connection to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns `true` if `uri` should be fetched over HTTP rather than opened as a file.
+pub(crate) fn is_url(uri: &str) -> bool {
+    uri.starts_with("https://") || uri.starts_with("http://")
+}
+
+/// Starts a GET request and returns the response body as a reader.
+///
+/// The body is consumed from the socket as the parser reads it, so large
+/// files are never buffered in full. Non-2xx responses are reported as
+/// errors before any data is handed to the parser.
+///
+/// Only connecting is timed out: a large file on a slow link may take any
+/// time to download, and the body is read at the parser's pace.
+pub(crate) fn open(url: &str) -> Result<impl Read> {
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(None)
+        .build()?;
+    let response = client.get(url).send()?.error_for_status()?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves one request with `status`, sending `body` in two halves.
+    fn serve(status: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/statement.csv", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let head = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            );
+            let (first, second) = body.split_at(body.len() / 2);
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(first.as_bytes()).unwrap();
+            stream.flush().unwrap();
+            thread::sleep(Duration::from_millis(200));
+            stream.write_all(second.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn test_open() {
+        let body = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+                    TX1,2024-01-15,,Debit,9.99,EUR,Coffee\n";
+        let mut data = String::new();
+        let mut reader = open(&serve("200 OK", body)).unwrap();
+        reader.read_to_string(&mut data).unwrap();
+        assert_eq!(data, body);
+
+        assert!(open(&serve("404 Not Found", "")).is_err());
+    }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://partner.example.com/statements/acc123.csv"));
+        assert!(is_url("http://localhost:8080/export"));
+        assert!(!is_url("examples/transactions.csv"));
+        assert!(!is_url("s3://statements/acc123.bin"));
+    }
+}
@@ -0,0 +1,303 @@
+//! Batches that share repeated strings.
+//!
+//! A statement repeats a handful of currencies, accounts, counterparties and
+//! categories on every row, and a [`TransactionBatch`] holds a separate
+//! `String` for each occurrence. [`InternedBatch`] keeps one shared copy of
+//! each distinct value instead, which for a large batch is most of its
+//! memory. [`parse_csv_interned`] parses CSV straight into it, without
+//! allocating the repeated strings in the first place.
+//!
+//! # Examples
+//!
+//! ```
+//! use parser::intern::parse_csv_interned;
+//! use parser::ParseOptions;
+//! use std::sync::Arc;
+//!
+//! # fn main() -> Result<(), parser::Error> {
+//! let csv = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,\
+//!            Description,Account,Counterparty,Category\n\
+//!            TX1,2024-01-15,,Debit,4.50,USD,Coffee,ACC123,,Food\n\
+//!            TX2,2024-01-16,,Debit,9.80,USD,Lunch,ACC123,,Food\n";
+//! let batch = parse_csv_interned(csv.as_bytes(), &ParseOptions::default())?;
+//! let [first, second] = &batch.transactions[..] else { unreachable!() };
+//! assert!(Arc::ptr_eq(&first.currency, &second.currency));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::formats::csv::CsvDecoder;
+use crate::formats::{self, ParseOptions};
+use crate::{
+    Balance, BatchMetadata, Error, Money, Result, Transaction, TransactionBatch, TransactionKind,
+    TransactionRef, TransactionStatus,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+use std::sync::Arc;
+
+/// A pool of shared strings: interning a string returns the pool's copy of
+/// it, allocating one only the first time.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// Creates an empty pool.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the pool's copy of `s`, adding it if it's new.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// Number of distinct strings in the pool.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+/// A [`Transaction`] whose repeated fields share their strings with the
+/// other transactions of an [`InternedBatch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternedTransaction {
+    /// Unique identifier for the transaction
+    pub id: String,
+    /// Date when the transaction was posted to the account
+    pub posted_at: NaiveDate,
+    /// Optional timestamp when the transaction was actually executed
+    pub executed_at: Option<NaiveDateTime>,
+    /// Whether this is a debit or credit transaction
+    pub kind: TransactionKind,
+    /// Whether the transaction is booked, pending or cancelled
+    pub status: TransactionStatus,
+    /// The amount of the transaction
+    pub amount: Decimal,
+    /// The currency code of the amount
+    pub currency: Arc<str>,
+    /// Human-readable description of the transaction
+    pub description: String,
+    /// Optional account identifier
+    pub account: Option<Arc<str>>,
+    /// Optional counterparty (the other party in the transaction)
+    pub counterparty: Option<Arc<str>>,
+    /// Optional category for transaction classification
+    pub category: Option<Arc<str>>,
+    /// Optional tax (e.g. VAT) included in the amount, in the same currency
+    pub tax_amount: Option<Decimal>,
+    /// Optional tax rate in percent (`20` for 20% VAT)
+    pub tax_rate: Option<Decimal>,
+    /// Source columns without a field of their own, keyed by column name
+    pub extra: BTreeMap<Arc<str>, String>,
+}
+
+impl InternedTransaction {
+    /// Converts a transaction, interning its repeated fields in `interner`.
+    pub fn new(tx: TransactionRef<'_>, interner: &mut Interner) -> Self {
+        let mut intern = |field: Option<&str>| field.map(|s| interner.intern(s));
+        let account = intern(tx.account.as_deref());
+        let counterparty = intern(tx.counterparty.as_deref());
+        let category = intern(tx.category.as_deref());
+        InternedTransaction {
+            id: tx.id.into_owned(),
+            posted_at: tx.posted_at,
+            executed_at: tx.executed_at,
+            kind: tx.kind,
+            status: tx.status,
+            amount: tx.amount,
+            currency: interner.intern(&tx.currency),
+            description: tx.description.into_owned(),
+            account,
+            counterparty,
+            category,
+            tax_amount: tx.tax_amount,
+            tax_rate: tx.tax_rate,
+            extra: tx
+                .extra
+                .into_iter()
+                .map(|(name, value)| (interner.intern(&name), value.into_owned()))
+                .collect(),
+        }
+    }
+
+    /// Copies the transaction into a [`Transaction`].
+    pub fn to_transaction(&self) -> Transaction {
+        let owned = |field: &Option<Arc<str>>| field.as_deref().map(str::to_string);
+        Transaction {
+            id: self.id.clone(),
+            posted_at: self.posted_at,
+            executed_at: self.executed_at,
+            kind: self.kind,
+            status: self.status,
+            amount: Money {
+                amount: self.amount,
+                currency: self.currency.to_string(),
+            },
+            description: self.description.clone(),
+            account: owned(&self.account),
+            counterparty: owned(&self.counterparty),
+            category: owned(&self.category),
+            tax_amount: self.tax_amount,
+            tax_rate: self.tax_rate,
+            extra: self
+                .extra
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// A [`TransactionBatch`] whose transactions share their repeated strings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InternedBatch {
+    /// Optional identifier for the account these transactions belong to
+    pub account_id: Option<String>,
+    /// The list of transactions in this batch
+    pub transactions: Vec<InternedTransaction>,
+    /// Opening and closing balances declared by the source, one per currency
+    pub balances: Vec<Balance>,
+    /// Information about the batch as a whole
+    pub metadata: BatchMetadata,
+}
+
+impl InternedBatch {
+    /// Converts a batch, sharing the strings its transactions repeat.
+    pub fn from_batch(batch: &TransactionBatch) -> Self {
+        let mut interner = Interner::new();
+        InternedBatch {
+            account_id: batch.account_id.clone(),
+            transactions: batch
+                .transactions
+                .iter()
+                .map(|tx| InternedTransaction::new(tx.into(), &mut interner))
+                .collect(),
+            balances: batch.balances.clone(),
+            metadata: batch.metadata.clone(),
+        }
+    }
+
+    /// Copies the batch into a [`TransactionBatch`].
+    pub fn to_batch(&self) -> TransactionBatch {
+        TransactionBatch {
+            account_id: self.account_id.clone(),
+            transactions: self
+                .transactions
+                .iter()
+                .map(InternedTransaction::to_transaction)
+                .collect(),
+            balances: self.balances.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// Parses CSV data into an [`InternedBatch`].
+///
+/// Behaves like [`crate::parse_with`] for [`crate::Format::Csv`], but
+/// allocates each distinct currency, account, counterparty, category and
+/// extra column name once for the whole file.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing CSV transaction data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns the parsed batch, or an [`Error`] in the same cases as
+/// [`crate::parse_with`].
+pub fn parse_csv_interned<R: Read>(reader: R, options: &ParseOptions) -> Result<InternedBatch> {
+    let mut reader = BufReader::new(formats::decode(reader, options.encoding.as_deref())?);
+    let mut decoder = CsvDecoder::new(options);
+    let mut interner = Interner::new();
+    let mut batch = InternedBatch::default();
+    let mut line = String::new();
+    for index in 0.. {
+        if decoder.has_header() && decoder.is_full() {
+            break;
+        }
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let text = line.strip_suffix('\n').unwrap_or(&line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        if let Some(tx) = decoder.decode_borrowed(index, text)? {
+            batch
+                .transactions
+                .push(InternedTransaction::new(tx, &mut interner));
+        }
+    }
+    if !decoder.has_header() {
+        return Err(Error::parse("CSV", "empty file"));
+    }
+    batch.metadata = decoder.metadata().clone();
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    #[test]
+    fn test_interned_batch() {
+        let csv = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,\
+                   Description,Account,Counterparty,Category,MCC\r\n\
+                   TX1,2024-01-15,,Credit,5000.00,USD,Salary,ACC1,Employer,Salary,\r\n\
+                   TX2,2024-01-16,,Debit,150.50,USD,Groceries,ACC1,,Food,5411\r\n\
+                   TX3,2024-01-17,,Debit,20.00,EUR,Coffee,ACC1,,Food,5814\r\n";
+        let expected = crate::parse(csv.as_bytes(), Format::Csv).unwrap();
+        let batch = parse_csv_interned(csv.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(batch.to_batch(), expected);
+        assert_eq!(InternedBatch::from_batch(&expected), batch);
+
+        let [first, second, third] = &batch.transactions[..] else {
+            panic!("expected 3 transactions");
+        };
+        assert!(Arc::ptr_eq(&first.currency, &second.currency));
+        assert!(Arc::ptr_eq(
+            second.category.as_ref().unwrap(),
+            third.category.as_ref().unwrap()
+        ));
+        assert!(!Arc::ptr_eq(&second.currency, &third.currency));
+
+        let mut interner = Interner::new();
+        let usd = interner.intern("USD");
+        assert!(Arc::ptr_eq(&usd, &interner.intern("USD")));
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn test_interned_batch_keeps_status() {
+        let text = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 4.50 USD\n\
+                    Status: Pending\n---\n\
+                    ID: TX2\nDate: 2024-01-16\nType: Debit\nAmount: 9.80 USD\n\
+                    Status: Cancelled\n";
+        let batch = crate::parse(text.as_bytes(), Format::Text).unwrap();
+        assert_eq!(batch.transactions[0].status, TransactionStatus::Pending);
+        assert_eq!(
+            TransactionRef::from(&batch.transactions[1]).status,
+            TransactionStatus::Cancelled
+        );
+        let interned = InternedBatch::from_batch(&batch);
+        assert_eq!(interned.transactions[0].status, TransactionStatus::Pending);
+        assert_eq!(interned.to_batch(), batch);
+    }
+}
@@ -1,7 +1,7 @@
 //! Financial transaction parser library.
 //!
 //! This library provides functionality to parse and write financial transactions
-//! in multiple formats: CSV, plain text, and a custom binary format with magic
+//! in multiple formats: CSV, plain text, JSON, XML, YAML, and a custom binary format with magic
 //! number and version header. All I/O is built on top of [`std::io::Read`] and
 //! [`std::io::Write`], so the same code works against files, in-memory buffers,
 //! or stdin/stdout.
@@ -25,19 +25,124 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! # `no_std` support
+//!
+//! Everything built on `std::io` sits behind the default `std` feature.
+//! Without it the crate is `#![no_std]` and only needs `alloc`: the domain
+//! types and the binary codec ([`formats::binary::decode_binary`] and
+//! [`formats::binary::encode_binary`]) remain available, working over
+//! `&[u8]` and `Vec<u8>`.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
 pub mod domain;
-pub use domain::{Money, Transaction, TransactionBatch, TransactionKind};
+pub use domain::{
+    sum_by_currency, Balance, BatchMetadata, Money, Period, Transaction, TransactionBatch,
+    TransactionKind, TransactionRef, TransactionStatus,
+};
 
 pub mod error;
 pub use error::{Error, Result};
 
 pub mod formats;
-pub use formats::Format;
+#[cfg(feature = "std")]
+pub use formats::ParseReport;
+pub use formats::{Field, Format, Limits, ParseOptions, Quoting, TextSeparator, WriteOptions};
+
+pub mod balance;
+
+pub mod canonical;
+
+pub mod fingerprint;
+mod hashing;
+
+pub mod sort;
+pub use sort::{SortField, SortKey};
+
+pub mod merge;
+pub use merge::MergePolicy;
+
+pub mod dedup;
+pub use dedup::DedupKey;
+
+pub mod split;
+pub use split::SplitKey;
+
+pub mod gaps;
+
+pub mod reconcile;
+
+pub mod diff;
+
+pub mod patch;
+
+pub mod validate;
+
+#[cfg(feature = "std")]
+pub mod filter;
+
+#[cfg(feature = "std")]
+pub mod pii;
+
+#[cfg(feature = "query")]
+pub mod query;
+
+pub mod currency;
+
+pub mod tax;
+
+#[cfg(feature = "std")]
+pub mod delta;
+
+#[cfg(feature = "std")]
+pub mod fees;
+
+#[cfg(feature = "std")]
+pub mod intern;
+#[cfg(feature = "std")]
+pub mod log;
 
-use std::io::{Read, Write};
+#[cfg(feature = "std")]
+pub mod manifest;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(feature = "std")]
+pub mod writer;
+#[cfg(feature = "std")]
+pub use writer::TransactionWriter;
+
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "tokio")]
+pub use async_io::{parse_async, parse_stream, write_async};
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+#[cfg(feature = "charts")]
+pub mod charts;
+
+#[cfg(feature = "signing")]
+pub mod signature;
+
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "s3")]
+mod s3;
+
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{BufReader, BufWriter, Read, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
 
 /// Parses a batch of transactions from a reader in the specified format.
 ///
@@ -47,7 +152,7 @@ use std::io::{Read, Write};
 /// # Arguments
 ///
 /// * `reader` - A reader containing transaction data
-/// * `format` - The format of the input data (CSV, Text, or Binary)
+/// * `format` - The format of the input data (CSV, Text, JSON, XML, YAML, or Binary)
 ///
 /// # Returns
 ///
@@ -69,11 +174,206 @@ use std::io::{Read, Write};
 /// # Ok(())
 /// # }
 /// ```
+#[cfg(feature = "std")]
 pub fn parse<R: Read>(reader: R, format: Format) -> Result<TransactionBatch> {
     match format {
         Format::Csv => formats::csv::parse_csv(reader),
         Format::Text => formats::text::parse_text(reader),
         Format::Binary => formats::binary::parse_binary(reader),
+        Format::Json => formats::json::parse_json(reader),
+        Format::Xml => formats::xml::parse_xml(reader),
+        Format::Yaml => formats::yaml::parse_yaml(reader),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => formats::parquet::parse_parquet(reader),
+        #[cfg(feature = "proto")]
+        Format::Protobuf => formats::protobuf::parse_protobuf(reader),
+        #[cfg(feature = "sqlite")]
+        Format::Sqlite => formats::sqlite::parse_sqlite(reader),
+        #[cfg(feature = "xlsx")]
+        Format::Xlsx => formats::xlsx::parse_xlsx(reader),
+    }
+}
+
+/// Parses a batch of transactions from a reader with the given options.
+///
+/// Behaves like [`parse`], except that:
+/// - text-based input (CSV, text, JSON, XML, YAML) is first decoded from
+///   [`ParseOptions::encoding`], or from the encoding detected from its
+///   first bytes: UTF-8, UTF-16 (with or without byte order mark) or
+///   Windows-1251
+/// - at most [`ParseOptions::max_records`] transactions are returned; CSV
+///   and text input stops being read there, but the other formats are
+///   parsed whole and then truncated, so only [`ParseOptions::limits`]
+///   bounds the work done on them
+/// - CSV and text input honour the delimiter, date formats and strictness of
+///   the options (see [`formats::csv::parse_csv_with`] and
+///   [`formats::text::parse_text_with`])
+/// - with the `encryption` feature, encrypted binary input is decrypted with
+///   `ParseOptions::encryption_key`
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `format` - The format of the input data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns a [`TransactionBatch`] containing all parsed transactions, or an [`Error`]
+/// if parsing fails.
+///
+/// # Errors
+///
+/// Besides the errors of [`parse`], this function returns an error if the
+/// encoding is unknown or the input isn't valid in it, and
+/// [`Error::UnknownCurrency`] for a currency outside ISO 4217 when
+/// [`ParseOptions::iso_currencies`] is set.
+#[cfg(feature = "std")]
+pub fn parse_with<R: Read>(
+    reader: R,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    options.parse_limited(reader, |reader| {
+        if format.is_text() {
+            formats::decode(reader, options.encoding.as_deref())
+                .and_then(|decoded| parse_decoded(decoded, format, options))
+        } else {
+            parse_decoded(reader, format, options)
+        }
+    })
+}
+
+/// Parses transactions from a reader, skipping malformed records instead of
+/// failing at the first one.
+///
+/// Real exports often contain a few junk rows, such as totals or notes
+/// between the transactions. CSV lines and text records with an invalid
+/// value are left out of the report and their errors collected in
+/// [`ParseReport::errors`]; input that is ignored, such as CSV fields beyond
+/// the header, is listed in [`ParseReport::warnings`]. See
+/// [`formats::csv::parse_csv_report`] and
+/// [`formats::text::parse_text_report`]. Other formats have no records to
+/// skip and are parsed as [`parse_with`] does.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `format` - The format of the input data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns a [`ParseReport`] with the transactions that parsed and what went
+/// wrong with the rest.
+///
+/// # Errors
+///
+/// Returns an error when the input as a whole can't be parsed: it can't be
+/// read or decoded, a CSV header is missing or unknown, a cap of
+/// [`ParseOptions::limits`] is exceeded, or, in formats other than CSV and
+/// text, anything is invalid.
+///
+/// With [`ParseOptions::iso_currencies`], transactions in an unknown
+/// currency are skipped as well; their errors come after those of the
+/// records that didn't parse. An unknown currency in the balances is an
+/// error of the whole input.
+#[cfg(feature = "std")]
+pub fn parse_report<R: Read>(
+    reader: R,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<ParseReport> {
+    if !matches!(format, Format::Csv | Format::Text) {
+        return parse_with(reader, format, options).map(ParseReport::from);
+    }
+    let mut reader = formats::LimitedReader::new(reader, &options.limits);
+    let decoded = formats::decode(&mut reader, options.encoding.as_deref());
+    let report = decoded.and_then(|decoded| match format {
+        Format::Csv => formats::csv::parse_csv_report(decoded, options),
+        _ => formats::text::parse_text_report(decoded, options),
+    });
+    let mut report = reader.check(report)?;
+    if let Some(max) = options.max_records {
+        report.transactions.truncate(max);
+    }
+    let limits = &options.limits;
+    limits.check_transactions(report.transactions.len())?;
+    for tx in &report.transactions {
+        limits.check_transaction(&tx.into())?;
+    }
+    for balance in &report.balances {
+        options.check_currency(&balance.currency, || "balances".to_string())?;
+    }
+    let mut errors = Vec::new();
+    report.transactions.retain(|tx| {
+        let checked = options.check_transaction_currency(tx);
+        checked.map_err(|e| errors.push(e)).is_ok()
+    });
+    report.errors.append(&mut errors);
+    Ok(report)
+}
+
+/// Checks transactions from a reader and returns every error found, not just
+/// the first one.
+///
+/// Parses as [`parse_report`] does, so a file with several bad CSV lines or
+/// text records can be fixed in one go. `options.strict` is honored: its
+/// errors, such as surplus CSV fields, are listed with the others rather
+/// than stopping the scan. An error that stops the whole input from
+/// parsing, such as a missing CSV header, is returned on its own. The
+/// [`validate`] module checks the contents of a parsed batch instead.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `format` - The format of the input data
+/// * `options` - How to parse the data
+///
+/// # Returns
+///
+/// Returns the errors in input order; an empty list means the input is
+/// valid.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{validate_input, Format, ParseOptions};
+///
+/// let data = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description\n\
+///             TX1,2024-01-15,,Debit,abc,EUR,Coffee\n\
+///             TX2,2024-01-16,,Debit,4.50,EUR,Croissant,note\n\
+///             TX3,2024-13-01,,Debit,1.00,EUR,Tea\n";
+/// let options = ParseOptions { strict: true, ..ParseOptions::default() };
+/// let errors = validate_input(data.as_bytes(), Format::Csv, &options);
+/// assert_eq!(errors.len(), 3);
+/// assert_eq!(validate_input(data.as_bytes(), Format::Json, &options).len(), 1);
+/// ```
+#[cfg(feature = "std")]
+pub fn validate_input<R: Read>(
+    reader: R,
+    format: Format,
+    options: &ParseOptions,
+) -> Vec<Error> {
+    match parse_report(reader, format, options) {
+        Ok(report) => report.errors,
+        Err(error) => alloc::vec![error],
+    }
+}
+
+#[cfg(feature = "std")]
+fn parse_decoded<R: Read>(
+    reader: R,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    match format {
+        Format::Csv => formats::csv::parse_csv_with(reader, options),
+        Format::Text => formats::text::parse_text_with(reader, options),
+        Format::Binary => formats::binary::parse_binary_with(reader, options),
+        #[cfg(feature = "sqlite")]
+        Format::Sqlite => formats::sqlite::parse_sqlite_with(reader, options),
+        _ => parse(reader, format),
     }
 }
 
@@ -86,7 +386,7 @@ pub fn parse<R: Read>(reader: R, format: Format) -> Result<TransactionBatch> {
 ///
 /// * `batch` - The transaction batch to write
 /// * `writer` - A writer to output the transaction data to
-/// * `format` - The desired output format (CSV, Text, or Binary)
+/// * `format` - The desired output format (CSV, Text, JSON, XML, YAML, or Binary)
 ///
 /// # Returns
 ///
@@ -105,10 +405,321 @@ pub fn parse<R: Read>(reader: R, format: Format) -> Result<TransactionBatch> {
 /// # Ok(())
 /// # }
 /// ```
+#[cfg(feature = "std")]
 pub fn write<W: Write>(batch: &TransactionBatch, writer: &mut W, format: Format) -> Result<()> {
     match format {
         Format::Csv => formats::csv::write_csv(batch, writer),
         Format::Text => formats::text::write_text(batch, writer),
         Format::Binary => formats::binary::write_binary(batch, writer),
+        Format::Json => formats::json::write_json(batch, writer),
+        Format::Xml => formats::xml::write_xml(batch, writer),
+        Format::Yaml => formats::yaml::write_yaml(batch, writer),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => formats::parquet::write_parquet(batch, writer),
+        #[cfg(feature = "proto")]
+        Format::Protobuf => formats::protobuf::write_protobuf(batch, writer),
+        #[cfg(feature = "sqlite")]
+        Format::Sqlite => formats::sqlite::write_sqlite(batch, writer),
+        #[cfg(feature = "xlsx")]
+        Format::Xlsx => formats::xlsx::write_xlsx(batch, writer),
+    }
+}
+
+/// Writes a batch of transactions to a writer with the given options.
+///
+/// Behaves like [`write()`], except that [`WriteOptions::canonical`] makes the
+/// output byte-identical for logically identical batches and
+/// [`WriteOptions::decimal_places`] rounds amounts in every format. CSV and
+/// text output also honour the date format, field selection, quoting and
+/// record separator of the options (see [`formats::csv::write_csv_with`]
+/// and [`formats::text::write_text_with`]), and binary output its checksums
+/// and compression (see [`formats::binary::write_binary_with`]).
+///
+/// # Arguments
+///
+/// * `batch` - The transaction batch to write
+/// * `writer` - A writer to output the transaction data to
+/// * `format` - The desired output format
+/// * `options` - How to write the batch
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+#[cfg(feature = "std")]
+pub fn write_with<W: Write>(
+    batch: &TransactionBatch,
+    writer: &mut W,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<()> {
+    write_preamble(writer, format, options)?;
+    let batch = options.prepare(batch);
+    match format {
+        Format::Csv => formats::csv::write_csv_with(&batch, writer, options),
+        Format::Text => formats::text::write_text_with(&batch, writer, options),
+        Format::Binary => formats::binary::write_binary_with(&batch, writer, options),
+        _ => write(&batch, writer, format),
+    }
+}
+
+/// Writes what precedes the data in `format`: a byte order mark and the
+/// CSV schema comment, if the options ask for them.
+#[cfg(feature = "std")]
+pub(crate) fn write_preamble<W: Write>(
+    writer: &mut W,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<()> {
+    if options.bom && matches!(format, Format::Csv | Format::Text) {
+        write!(writer, "{}", formats::BOM)?;
+    }
+    if options.schema_version && format == Format::Csv {
+        formats::csv::write_schema_comment(writer)?;
+    }
+    Ok(())
+}
+
+/// Converts transactions from one format to another.
+///
+/// Equivalent to [`convert_with`] with default options.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{convert, Format};
+///
+/// # fn main() -> Result<(), parser::Error> {
+/// let csv = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,\
+///            Description,Account,Counterparty,Category\n\
+///            TX1,2024-01-15,,Debit,150.50,USD,Groceries,ACC123,,Food\n";
+/// let mut json = Vec::new();
+/// let count = convert(csv.as_bytes(), Format::Csv, &mut json, Format::Json)?;
+/// assert_eq!(count, 1);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn convert<R: Read, W: Write>(
+    reader: R,
+    from: Format,
+    writer: &mut W,
+    to: Format,
+) -> Result<usize> {
+    let (parse_options, write_options) = (ParseOptions::default(), WriteOptions::default());
+    convert_with(reader, from, writer, to, &parse_options, &write_options)
+}
+
+/// Converts transactions from one format to another with the given options.
+///
+/// The output is the same as [`parse_with`] followed by [`write_with`]
+/// would produce, but CSV input is piped through a [`TransactionWriter`] one
+/// record at a time, so converting it runs in constant memory however large
+/// the file is. Other input, and canonical output, which has to be sorted,
+/// is converted through a [`TransactionBatch`].
+///
+/// CSV output of CSV input has the input's extra columns, including those
+/// that are empty in every record.
+///
+/// # Arguments
+///
+/// * `reader` - A reader containing transaction data
+/// * `from` - The format of the input data
+/// * `writer` - A writer to output the converted data to
+/// * `to` - The desired output format
+/// * `parse_options` - How to parse the input
+/// * `write_options` - How to write the output
+///
+/// # Returns
+///
+/// Returns the number of transactions converted, or an [`Error`] if parsing
+/// or writing fails. Output written before a parse error is left as it is.
+#[cfg(feature = "std")]
+pub fn convert_with<R: Read, W: Write>(
+    reader: R,
+    from: Format,
+    writer: &mut W,
+    to: Format,
+    parse_options: &ParseOptions,
+    write_options: &WriteOptions,
+) -> Result<usize> {
+    if from != Format::Csv || write_options.canonical {
+        let batch = parse_with(reader, from, parse_options)?;
+        write_with(&batch, writer, to, write_options)?;
+        return Ok(batch.transactions.len());
     }
+
+    let mut reader = formats::LimitedReader::new(reader, &parse_options.limits);
+    let count = convert_csv(&mut reader, writer, to, parse_options, write_options);
+    reader.check(count)
+}
+
+/// Pipes CSV input through a [`TransactionWriter`] for [`convert_with`].
+#[cfg(feature = "std")]
+fn convert_csv<R: Read, W: Write>(
+    reader: R,
+    writer: &mut W,
+    to: Format,
+    parse_options: &ParseOptions,
+    write_options: &WriteOptions,
+) -> Result<usize> {
+    let reader = formats::decode(reader, parse_options.encoding.as_deref())?;
+    let records = formats::csv::CsvRecords::new(reader, parse_options)?;
+    let extra: Vec<String> = records.extra_columns().map(str::to_string).collect();
+    let header = TransactionBatch {
+        metadata: records.metadata().clone(),
+        ..TransactionBatch::default()
+    };
+    let mut output =
+        TransactionWriter::begin_from(writer, to, &header, write_options)?.extra_columns(extra);
+    let mut count = 0;
+    for tx in records {
+        let tx = tx?;
+        parse_options.check_transaction_currency(&tx)?;
+        output.write(&tx)?;
+        count += 1;
+    }
+    output.finish()?;
+    Ok(count)
+}
+
+/// Parses a batch of transactions from a file in the specified format.
+///
+/// With the `s3` feature enabled, `path` may also be an `s3://bucket/key` URI,
+/// and with the `http` feature an `http://` or `https://` URL; the data is then
+/// streamed from the network straight into the parser.
+///
+/// Text-based files in another encoding than UTF-8 are decoded as
+/// [`parse_with`] detects it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use parser::{parse_path, Format};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let batch = parse_path("transactions.csv", Format::Csv)?;
+/// println!("Parsed {} transactions", batch.transactions.len());
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn parse_path<P: AsRef<Path>>(path: P, format: Format) -> Result<TransactionBatch> {
+    parse_path_with(path, format, &ParseOptions::default())
+}
+
+/// Parses a batch of transactions from a file with the given options.
+///
+/// Accepts the same locations as [`parse_path`]; see [`parse_with`] for the
+/// effect of the options.
+#[cfg(feature = "std")]
+pub fn parse_path_with<P: AsRef<Path>>(
+    path: P,
+    format: Format,
+    options: &ParseOptions,
+) -> Result<TransactionBatch> {
+    let path = path.as_ref();
+
+    #[cfg(feature = "s3")]
+    if let Some(location) = path.to_str().and_then(s3::S3Location::parse) {
+        let reader = BufReader::new(s3::S3Reader::open(&location)?);
+        return parse_with(reader, format, options);
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(url) = path.to_str().filter(|p| http::is_url(p)) {
+        return parse_with(BufReader::new(http::open(url)?), format, options);
+    }
+
+    #[cfg(feature = "sqlite")]
+    if format == Format::Sqlite {
+        return formats::sqlite::read_sqlite_path_with(path, options);
+    }
+
+    let file = File::open(path)?;
+    parse_with(BufReader::new(file), format, options)
+}
+
+/// Writes a batch of transactions to a file in the specified format.
+///
+/// The file is created or truncated. With the `s3` feature enabled, `path`
+/// may also be an `s3://bucket/key` URI, in which case the output is uploaded
+/// as it is produced.
+///
+/// # Examples
+///
+/// ```no_run
+/// use parser::{write_path, Format, TransactionBatch};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let batch = TransactionBatch::default();
+/// write_path(&batch, "transactions.bin", Format::Binary)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn write_path<P: AsRef<Path>>(batch: &TransactionBatch, path: P, format: Format) -> Result<()> {
+    write_path_with(batch, path, format, &WriteOptions::default())
+}
+
+/// Writes a batch of transactions to a file with the given options.
+///
+/// Accepts the same locations as [`write_path`]; see [`write_with`] for the
+/// effect of the options.
+#[cfg(feature = "std")]
+pub fn write_path_with<P: AsRef<Path>>(
+    batch: &TransactionBatch,
+    path: P,
+    format: Format,
+    options: &WriteOptions,
+) -> Result<()> {
+    let path = path.as_ref();
+
+    #[cfg(feature = "s3")]
+    if let Some(location) = path.to_str().and_then(s3::S3Location::parse) {
+        let mut writer = s3::S3Writer::create(&location)?;
+        write_with(batch, &mut writer, format, options)?;
+        return writer.finish();
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(url) = path.to_str().filter(|p| http::is_url(p)) {
+        return Err(Error::UnsupportedFormat(format!(
+            "writing to HTTP URLs is not supported: {}",
+            url
+        )));
+    }
+
+    #[cfg(feature = "sqlite")]
+    if format == Format::Sqlite {
+        return formats::sqlite::write_sqlite_path(&options.prepare(batch), path);
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_with(batch, &mut writer, format, options)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Returns the JSON Schema of a serialized [`TransactionBatch`].
+///
+/// The schema describes the `serde` representation of the domain types (Rust
+/// field names, amounts as decimal strings, dates as `YYYY-MM-DD`), generated
+/// from the types themselves, so it can't drift from the code. It is not the
+/// schema of [`Format::Json`] documents. Amounts may also be numbers, as
+/// deserialization accepts them.
+///
+/// # Examples
+///
+/// ```
+/// let schema = serde_json::to_value(parser::schema())?;
+/// assert_eq!(schema["title"], "TransactionBatch");
+/// assert!(schema["$defs"]["Transaction"]["required"]
+///     .as_array()
+///     .unwrap()
+///     .contains(&"posted_at".into()));
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[cfg(feature = "schemars")]
+pub fn schema() -> schemars::Schema {
+    schemars::schema_for!(TransactionBatch)
 }
@@ -0,0 +1,374 @@
+//! Append-only transaction log.
+//!
+//! A log stores a feed as a sequence of events instead of a finished batch:
+//! new transactions are appended, and later corrections or reversals are
+//! appended too, never rewriting earlier bytes. [`replay`] folds the events
+//! into the current [`TransactionBatch`], and [`compact`] rewrites a log as
+//! the minimal event list with the same state.
+//!
+//! # Layout
+//!
+//! A log starts with the magic number `YPBL` (u32) and a version byte,
+//! followed by any number of frames:
+//!
+//! ```text
+//! event tag (u8) | payload length (u32) | payload
+//! ```
+//!
+//! Payloads use the primitives of the binary format. Because every frame is
+//! length-prefixed, a frame cut short by a crash is reported instead of being
+//! misread.
+
+use crate::formats::binary::{
+    read_optional_string, read_string, read_transaction, read_u32, read_u8, write_optional_string,
+    write_string, write_transaction, write_u32, write_u8, IoSink, IoSource,
+};
+use crate::{Error, Result, Transaction, TransactionBatch};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+const MAGIC_NUMBER: u32 = 0x5950424C;
+/// Version 2 stores transactions with the binary format's extension block.
+/// Log versions match the transaction encoding they use.
+const VERSION: u8 = 2;
+
+const TAG_APPEND: u8 = 1;
+const TAG_CORRECT: u8 = 2;
+const TAG_REVERSE: u8 = 3;
+const TAG_SET_ACCOUNT: u8 = 4;
+
+/// One entry of a transaction log.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogEvent {
+    /// A new transaction; its ID must not be in use
+    Append(Transaction),
+    /// Replaces the transaction with the same ID, keeping its position
+    Correct(Transaction),
+    /// Reverses (removes) the transaction with this ID
+    Reverse(String),
+    /// Sets the account identifier of the batch
+    SetAccount(Option<String>),
+}
+
+/// Writes events to a log.
+pub struct LogWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> LogWriter<W> {
+    /// Starts a new log, writing its header.
+    pub fn create(mut writer: W) -> Result<Self> {
+        let sink = &mut IoSink(&mut writer);
+        write_u32(sink, MAGIC_NUMBER)?;
+        write_u8(sink, VERSION)?;
+        Ok(Self { writer })
+    }
+
+    /// Continues an existing log; `writer` must be positioned at its end.
+    pub fn resume(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Appends one event.
+    pub fn append(&mut self, event: &LogEvent) -> Result<()> {
+        let mut payload = Vec::new();
+        let tag = match event {
+            LogEvent::Append(tx) => {
+                write_transaction(&mut payload, tx, VERSION)?;
+                TAG_APPEND
+            }
+            LogEvent::Correct(tx) => {
+                write_transaction(&mut payload, tx, VERSION)?;
+                TAG_CORRECT
+            }
+            LogEvent::Reverse(id) => {
+                write_string(&mut payload, id)?;
+                TAG_REVERSE
+            }
+            LogEvent::SetAccount(account) => {
+                write_optional_string(&mut payload, account.as_deref())?;
+                TAG_SET_ACCOUNT
+            }
+        };
+
+        let sink = &mut IoSink(&mut self.writer);
+        write_u8(sink, tag)?;
+        write_u32(sink, payload.len() as u32)?;
+        self.writer.write_all(&payload)?;
+        Ok(())
+    }
+
+    /// Flushes buffered events to the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl LogWriter<BufWriter<File>> {
+    /// Opens the log at `path` for appending, creating it if it doesn't exist.
+    ///
+    /// Logs written by an older version can't be appended to; run
+    /// [`compact_path`] on them first, which rewrites them in the current
+    /// version.
+    ///
+    /// Call [`LogWriter::flush`] once a group of events is written; events
+    /// still buffered when the writer is dropped are flushed on a best-effort
+    /// basis.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            let version = read_header(&mut File::open(path)?)?;
+            if version != VERSION {
+                return Err(Error::parse(
+                    "Log",
+                    format!("cannot append to a version {} log; compact it first", version),
+                ));
+            }
+            let file = OpenOptions::new().append(true).open(path)?;
+            Ok(Self::resume(BufWriter::new(file)))
+        } else {
+            Self::create(BufWriter::new(File::create(path)?))
+        }
+    }
+}
+
+/// Reads every event of a log.
+///
+/// # Errors
+///
+/// Returns an error if the header is invalid, an event has an unknown tag,
+/// or the last frame is truncated.
+pub fn read_log<R: Read>(reader: R) -> Result<Vec<LogEvent>> {
+    let mut reader = IoSource(reader);
+    let version = read_header(&mut reader.0)?;
+
+    let mut events = Vec::new();
+    loop {
+        let mut tag = [0u8; 1];
+        match reader.0.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+
+        let truncated = |_| Error::parse("Log", format!("truncated event #{}", events.len() + 1));
+        let length = read_u32(&mut reader).map_err(truncated)? as usize;
+        let mut payload = vec![0u8; length];
+        reader
+            .0
+            .read_exact(&mut payload)
+            .map_err(|e| truncated(e.into()))?;
+
+        let payload = &mut payload.as_slice();
+        let event = match tag[0] {
+            TAG_APPEND => LogEvent::Append(read_transaction(payload, version)?),
+            TAG_CORRECT => LogEvent::Correct(read_transaction(payload, version)?),
+            TAG_REVERSE => LogEvent::Reverse(read_string(payload)?),
+            TAG_SET_ACCOUNT => LogEvent::SetAccount(read_optional_string(payload)?),
+            tag => {
+                return Err(Error::parse(
+                    "Log",
+                    format!("unknown event tag {} in event #{}", tag, events.len() + 1),
+                ))
+            }
+        };
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+/// Writes a complete log containing `events`.
+pub fn write_log<W: Write>(writer: &mut W, events: &[LogEvent]) -> Result<()> {
+    let mut log = LogWriter::create(writer)?;
+    for event in events {
+        log.append(event)?;
+    }
+    log.flush()
+}
+
+/// Folds events into the batch they describe.
+///
+/// # Errors
+///
+/// Returns an error if a transaction ID is appended twice, or a correction or
+/// reversal refers to an ID that isn't present.
+pub fn replay(events: &[LogEvent]) -> Result<TransactionBatch> {
+    let mut account_id = None;
+    let mut slots: Vec<Option<Transaction>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for (i, event) in events.iter().enumerate() {
+        let unknown = |id: &str| {
+            Error::parse(
+                "Log",
+                format!("event #{}: unknown transaction ID {}", i + 1, id),
+            )
+        };
+
+        match event {
+            LogEvent::Append(tx) => {
+                if index.contains_key(&tx.id) {
+                    return Err(Error::parse(
+                        "Log",
+                        format!("event #{}: duplicate transaction ID {}", i + 1, tx.id),
+                    ));
+                }
+                index.insert(tx.id.clone(), slots.len());
+                slots.push(Some(tx.clone()));
+            }
+            LogEvent::Correct(tx) => {
+                let slot = index.get(&tx.id).ok_or_else(|| unknown(&tx.id))?;
+                slots[*slot] = Some(tx.clone());
+            }
+            LogEvent::Reverse(id) => {
+                let slot = index.remove(id).ok_or_else(|| unknown(id))?;
+                slots[slot] = None;
+            }
+            LogEvent::SetAccount(account) => account_id = account.clone(),
+        }
+    }
+
+    Ok(TransactionBatch {
+        account_id,
+        transactions: slots.into_iter().flatten().collect(),
+        balances: Vec::new(),
+        metadata: Default::default(),
+    })
+}
+
+/// Returns the shortest event list that replays to the same batch.
+pub fn compact(events: &[LogEvent]) -> Result<Vec<LogEvent>> {
+    let batch = replay(events)?;
+
+    let mut compacted = Vec::with_capacity(batch.transactions.len() + 1);
+    if batch.account_id.is_some() {
+        compacted.push(LogEvent::SetAccount(batch.account_id));
+    }
+    compacted.extend(batch.transactions.into_iter().map(LogEvent::Append));
+    Ok(compacted)
+}
+
+/// Compacts the log file at `path` in place.
+///
+/// The compacted log is written next to the original and renamed over it,
+/// so readers see either the old or the new file, never a partial one.
+pub fn compact_path<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    let events = read_log(BufReader::new(File::open(path)?))?;
+    let compacted = compact(&events)?;
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".compact");
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    write_log(&mut writer, &compacted)?;
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Validates the log header and returns its version.
+fn read_header<R: Read>(reader: &mut R) -> Result<u8> {
+    let reader = &mut IoSource(reader);
+    if read_u32(reader)? != MAGIC_NUMBER {
+        return Err(Error::parse("Log", "invalid magic number"));
+    }
+    let version = read_u8(reader)?;
+    if version == 0 || version > VERSION {
+        return Err(Error::parse(
+            "Log",
+            format!("unsupported version: {}", version),
+        ));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use std::collections::BTreeMap;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::io::Cursor;
+
+    fn tx(id: &str, amount: i64) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Credit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(amount, 2),
+                currency: "RUB".to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn events() -> Vec<LogEvent> {
+        vec![
+            LogEvent::SetAccount(Some("ACC1".to_string())),
+            LogEvent::Append(tx("A", 100)),
+            LogEvent::Append(tx("B", 200)),
+            LogEvent::Append(tx("C", 300)),
+            LogEvent::Correct(tx("A", 110)),
+            LogEvent::Reverse("B".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_replay_and_compact() {
+        let batch = replay(&events()).unwrap();
+
+        assert_eq!(batch.account_id.as_deref(), Some("ACC1"));
+        assert_eq!(batch.transactions, vec![tx("A", 110), tx("C", 300)]);
+
+        let compacted = compact(&events()).unwrap();
+        assert_eq!(compacted.len(), 3);
+        assert_eq!(replay(&compacted).unwrap(), batch);
+
+        assert!(replay(&[LogEvent::Reverse("X".to_string())]).is_err());
+    }
+
+    #[test]
+    fn test_log_roundtrip_and_truncation() {
+        let mut buffer = Vec::new();
+        write_log(&mut buffer, &events()).unwrap();
+
+        assert_eq!(read_log(Cursor::new(&buffer)).unwrap(), events());
+
+        let error = read_log(Cursor::new(&buffer[..buffer.len() - 2])).unwrap_err();
+        assert!(error.to_string().contains("truncated event #6"));
+    }
+
+    #[test]
+    fn test_open_appends_across_sessions() {
+        let path = std::env::temp_dir().join(format!("ypbank-log-{}.ypbl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        for event in events() {
+            let mut log = LogWriter::open(&path).unwrap();
+            log.append(&event).unwrap();
+            log.flush().unwrap();
+        }
+        compact_path(&path).unwrap();
+
+        let events = read_log(File::open(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(events.len(), 3);
+    }
+}
@@ -0,0 +1,394 @@
+//! Integrity manifests for statement files.
+//!
+//! A manifest records, for each file, a content fingerprint of the parsed
+//! batch together with the record count and per-currency totals. Because the
+//! fingerprint is computed from parsed transactions rather than raw bytes, a
+//! file keeps its fingerprint across re-encodings that don't change its data,
+//! while a change to any field of a record, [`crate::Transaction::extra`]
+//! included, is detected. Execution times count to the second.
+//!
+//! Manifests are stored as plain text, one block per file:
+//!
+//! ```text
+//! File: examples/transactions.csv
+//! Format: csv
+//! Fingerprint: sha256:4c1d...
+//! Records: 5
+//! Total: USD 5000.00 1395.50
+//! ```
+//!
+//! Each `Total` line lists the currency, the sum of credits and the sum of
+//! debits.
+
+use crate::hashing::{digest_string, hash_field};
+use crate::{Error, Format, Result, TransactionBatch};
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+
+/// Credit and debit sums for one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrencyTotal {
+    /// The currency code
+    pub currency: String,
+    /// Sum of all credit amounts in this currency
+    pub credits: Decimal,
+    /// Sum of all debit amounts in this currency
+    pub debits: Decimal,
+}
+
+/// Manifest record describing one statement file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path of the file, as given when the manifest was created
+    pub path: String,
+    /// Format used to parse the file
+    pub format: Format,
+    /// Content fingerprint, see [`ordered_fingerprint`]
+    pub fingerprint: String,
+    /// Number of transactions in the file
+    pub records: usize,
+    /// Per-currency totals, ordered by currency code
+    pub totals: Vec<CurrencyTotal>,
+}
+
+/// A list of manifest entries.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    /// One entry per file
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Computes the content fingerprint of a batch, in the order of its
+/// transactions.
+///
+/// The fingerprint is a SHA-256 digest over every transaction, in order, with
+/// each field length-prefixed so that no two distinct batches share an
+/// encoding. Amounts are normalized (`10.50` and `10.5` hash the same).
+/// The batch `account_id` is not included, since not every format carries it.
+///
+/// Unlike [`TransactionBatch::fingerprint`], which ignores the order of the
+/// transactions, this tells a reordered file from the original, so it binds
+/// manifests and [deltas](crate::delta) to the exact batch. The encodings
+/// differ, so the two fingerprints of a batch never match.
+///
+/// The result is formatted as `sha256:<hex digest>`.
+pub fn ordered_fingerprint(batch: &TransactionBatch) -> String {
+    let mut hasher = Sha256::new();
+
+    for tx in &batch.transactions {
+        let executed_at = tx
+            .executed_at
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
+
+        hash_field(&mut hasher, Some(&tx.id));
+        hash_field(&mut hasher, Some(&tx.posted_at.format("%Y-%m-%d").to_string()));
+        hash_field(&mut hasher, executed_at.as_deref());
+        hash_field(&mut hasher, Some(&tx.kind.to_string()));
+        hash_field(&mut hasher, Some(&tx.amount.amount.normalize().to_string()));
+        hash_field(&mut hasher, Some(&tx.amount.currency));
+        hash_field(&mut hasher, Some(&tx.description));
+        hash_field(&mut hasher, tx.account.as_deref());
+        hash_field(&mut hasher, tx.counterparty.as_deref());
+        hash_field(&mut hasher, tx.category.as_deref());
+
+        // Tax fields are hashed only when present, behind a marker byte no
+        // field starts with, so fingerprints of batches without tax are
+        // unchanged.
+        if tx.tax_amount.is_some() || tx.tax_rate.is_some() {
+            hasher.update([2]);
+            let tax_amount = tx.tax_amount.map(|v| v.normalize().to_string());
+            let tax_rate = tx.tax_rate.map(|v| v.normalize().to_string());
+            hash_field(&mut hasher, tax_amount.as_deref());
+            hash_field(&mut hasher, tax_rate.as_deref());
+        }
+        // Likewise, the status only when the transaction isn't booked.
+        if !tx.status.is_booked() {
+            hasher.update([3]);
+            hash_field(&mut hasher, Some(&tx.status.to_string()));
+        }
+        // And the extra fields only when there are any.
+        if !tx.extra.is_empty() {
+            hasher.update([4]);
+            hasher.update((tx.extra.len() as u64).to_le_bytes());
+            for (key, value) in &tx.extra {
+                hash_field(&mut hasher, Some(key));
+                hash_field(&mut hasher, Some(value));
+            }
+        }
+    }
+
+    digest_string(&hasher.finalize())
+}
+
+/// Sums credits and debits per currency, ordered by currency code.
+pub fn currency_totals(batch: &TransactionBatch) -> Vec<CurrencyTotal> {
+    let mut totals: BTreeMap<&str, (Decimal, Decimal)> = BTreeMap::new();
+
+    for tx in &batch.transactions {
+        let entry = totals.entry(tx.amount.currency.as_str()).or_default();
+        if tx.is_credit() {
+            entry.0 += tx.unsigned_amount();
+        } else {
+            entry.1 += tx.unsigned_amount();
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(currency, (credits, debits))| CurrencyTotal {
+            currency: currency.to_string(),
+            credits,
+            debits,
+        })
+        .collect()
+}
+
+impl ManifestEntry {
+    /// Builds the manifest entry for a parsed file.
+    pub fn from_batch(path: impl Into<String>, format: Format, batch: &TransactionBatch) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            fingerprint: ordered_fingerprint(batch),
+            records: batch.transactions.len(),
+            totals: currency_totals(batch),
+        }
+    }
+
+    /// Checks a freshly parsed batch against this entry.
+    ///
+    /// Returns a human-readable description of every mismatch; an empty list
+    /// means the file is intact.
+    pub fn verify(&self, batch: &TransactionBatch) -> Vec<String> {
+        let actual = Self::from_batch(self.path.clone(), self.format, batch);
+        let mut mismatches = Vec::new();
+
+        if actual.records != self.records {
+            mismatches.push(format!(
+                "record count {} (expected {})",
+                actual.records, self.records
+            ));
+        }
+        if actual.totals != self.totals {
+            mismatches.push("currency totals differ".to_string());
+        }
+        if actual.fingerprint != self.fingerprint {
+            mismatches.push("fingerprint differs".to_string());
+        }
+
+        mismatches
+    }
+}
+
+impl Manifest {
+    /// Parses a manifest from its text representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a block is missing a required key or contains an
+    /// invalid value.
+    pub fn read<R: Read>(reader: R) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut block: Vec<(usize, String)> = Vec::new();
+
+        for (line_num, line_result) in BufReader::new(reader).lines().enumerate() {
+            let line = line_result?;
+            if line.trim().is_empty() {
+                if !block.is_empty() {
+                    entries.push(parse_entry(&block)?);
+                    block.clear();
+                }
+                continue;
+            }
+            block.push((line_num + 1, line));
+        }
+
+        if !block.is_empty() {
+            entries.push(parse_entry(&block)?);
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Writes the manifest in its text representation.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            writeln!(writer, "File: {}", entry.path)?;
+            writeln!(writer, "Format: {}", entry.format)?;
+            writeln!(writer, "Fingerprint: {}", entry.fingerprint)?;
+            writeln!(writer, "Records: {}", entry.records)?;
+            for total in &entry.totals {
+                writeln!(
+                    writer,
+                    "Total: {} {} {}",
+                    total.currency, total.credits, total.debits
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_entry(block: &[(usize, String)]) -> Result<ManifestEntry> {
+    let mut path = None;
+    let mut format = None;
+    let mut fingerprint = None;
+    let mut records = None;
+    let mut totals = Vec::new();
+
+    for (line_num, line) in block {
+        let (key, value) = line.split_once(": ").ok_or_else(|| {
+            Error::parse("Manifest", format!("line {}: expected 'Key: value'", line_num))
+        })?;
+        let invalid = |what: &str| {
+            Error::parse("Manifest", format!("line {}: invalid {}", line_num, what))
+        };
+
+        match key {
+            "File" => path = Some(value.to_string()),
+            "Format" => format = Some(Format::from_str(value).map_err(|_| invalid("format"))?),
+            "Fingerprint" => fingerprint = Some(value.to_string()),
+            "Records" => records = Some(value.parse().map_err(|_| invalid("record count"))?),
+            "Total" => {
+                let parts: Vec<&str> = value.split_whitespace().collect();
+                if parts.len() != 3 {
+                    return Err(invalid("total"));
+                }
+                totals.push(CurrencyTotal {
+                    currency: parts[0].to_string(),
+                    credits: Decimal::from_str(parts[1]).map_err(|_| invalid("credit total"))?,
+                    debits: Decimal::from_str(parts[2]).map_err(|_| invalid("debit total"))?,
+                });
+            }
+            _ => return Err(invalid("key")),
+        }
+    }
+
+    let first_line = block[0].0;
+    let missing = |key: &str| {
+        Error::parse(
+            "Manifest",
+            format!("entry at line {}: missing {}", first_line, key),
+        )
+    };
+
+    Ok(ManifestEntry {
+        path: path.ok_or_else(|| missing("File"))?,
+        format: format.ok_or_else(|| missing("Format"))?,
+        fingerprint: fingerprint.ok_or_else(|| missing("Fingerprint"))?,
+        records: records.ok_or_else(|| missing("Records"))?,
+        totals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, Transaction, TransactionKind, TransactionStatus};
+    use chrono::NaiveDate;
+    use std::io::Cursor;
+
+    fn sample_batch() -> TransactionBatch {
+        TransactionBatch {
+            account_id: Some("ACC123".to_string()),
+            transactions: vec![
+                Transaction {
+                    id: "TX001".to_string(),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+                    executed_at: None,
+                    kind: TransactionKind::Credit,
+                    status: TransactionStatus::Booked,
+                    amount: Money {
+                        amount: Decimal::from_str("1000.50").unwrap(),
+                        currency: "USD".to_string(),
+                    },
+                    description: "Salary".to_string(),
+                    account: None,
+                    counterparty: None,
+                    category: None,
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
+                },
+                Transaction {
+                    id: "TX002".to_string(),
+                    posted_at: NaiveDate::from_ymd_opt(2024, 1, 16).unwrap(),
+                    executed_at: None,
+                    kind: TransactionKind::Debit,
+                    status: TransactionStatus::Booked,
+                    amount: Money {
+                        amount: Decimal::from_str("20").unwrap(),
+                        currency: "EUR".to_string(),
+                    },
+                    description: "Coffee".to_string(),
+                    account: None,
+                    counterparty: Some("Cafe".to_string()),
+                    category: None,
+                    tax_amount: None,
+                    tax_rate: None,
+                    extra: BTreeMap::new(),
+                },
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable_across_formats() {
+        let batch = sample_batch();
+
+        for format in [Format::Csv, Format::Text, Format::Binary] {
+            let mut buffer = Vec::new();
+            crate::write(&batch, &mut buffer, format).unwrap();
+            let parsed = crate::parse(Cursor::new(buffer), format).unwrap();
+            assert_eq!(
+                ordered_fingerprint(&parsed),
+                ordered_fingerprint(&batch),
+                "{}",
+                format
+            );
+        }
+
+        let mut changed = batch.clone();
+        changed.transactions[1].description = "Tea".to_string();
+        assert_ne!(ordered_fingerprint(&changed), ordered_fingerprint(&batch));
+
+        let mut extra = batch.clone();
+        extra.transactions[1]
+            .extra
+            .insert("MCC".to_string(), "5814".to_string());
+        assert_ne!(ordered_fingerprint(&extra), ordered_fingerprint(&batch));
+
+        let mut reordered = batch.clone();
+        reordered.transactions.reverse();
+        assert_ne!(ordered_fingerprint(&reordered), ordered_fingerprint(&batch));
+        assert_ne!(ordered_fingerprint(&batch), batch.fingerprint());
+    }
+
+    #[test]
+    fn test_manifest_roundtrip_and_verify() {
+        let batch = sample_batch();
+        let manifest = Manifest {
+            entries: vec![ManifestEntry::from_batch("jan.csv", Format::Csv, &batch)],
+        };
+        assert_eq!(manifest.entries[0].totals[0].currency, "EUR");
+
+        let mut buffer = Vec::new();
+        manifest.write(&mut buffer).unwrap();
+        let parsed = Manifest::read(Cursor::new(buffer)).unwrap();
+        assert_eq!(parsed, manifest);
+
+        assert!(parsed.entries[0].verify(&batch).is_empty());
+
+        let mut truncated = batch.clone();
+        truncated.transactions.pop();
+        assert_eq!(parsed.entries[0].verify(&truncated).len(), 3);
+    }
+}
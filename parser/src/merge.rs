@@ -0,0 +1,342 @@
+//! Merging batches, such as monthly statements into a yearly archive.
+//!
+//! Statements of consecutive periods often overlap by a day or two, and a
+//! re-exported month can contain corrected versions of records already
+//! archived. [`TransactionBatch::merge`] matches transactions by ID and
+//! resolves records that appear in both batches by a [`MergePolicy`].
+
+use crate::{Balance, Error, Period, Result, Transaction, TransactionBatch};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// What [`TransactionBatch::merge`] does with a transaction whose ID is
+/// already in the batch.
+///
+/// Records with the same [fingerprint](Transaction::fingerprint) aren't a
+/// conflict: the repeated one is dropped under every policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    /// Fail with [`Error::DuplicateId`]
+    #[default]
+    Error,
+    /// Keep the transaction already in the batch
+    KeepFirst,
+    /// Keep the transaction with the later [`Transaction::executed_at`];
+    /// a transaction without one is older than any with one, and on a tie
+    /// the transaction already in the batch is kept
+    KeepLatest,
+}
+
+/// Where a transaction of the merged batch currently is.
+#[derive(Clone, Copy)]
+enum Slot {
+    /// At this index of the batch merged into
+    Existing(usize),
+    /// At this index of the transactions being appended
+    Appended(usize),
+}
+
+impl TransactionBatch {
+    /// Merges `other` into the batch.
+    ///
+    /// Transactions of `other` are appended in their order, except those
+    /// whose ID is already in the batch, which `policy` decides about; a
+    /// transaction kept from `other` takes the place of the one it
+    /// replaces. The batch is left unchanged when the merge fails.
+    ///
+    /// The rest of the batches is combined as follows:
+    ///
+    /// - a batch without an account ID takes the one of `other`;
+    /// - the balance of a currency both batches declare opens with the
+    ///   balance of the batch with the earlier [period](Self::period) and
+    ///   closes with that of the later one; a balance only one batch
+    ///   declares is kept unless the other batch has transactions in its
+    ///   currency, which it wouldn't account for;
+    /// - if either batch declares a period, the merged batch covers the
+    ///   periods of both; it keeps the later generation time, and the
+    ///   source system and schema version if the batches don't disagree on
+    ///   them.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The batch to merge into this one
+    /// * `policy` - How to resolve transactions that are in both batches
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::AccountMismatch`] if both batches have an account ID
+    /// and they differ, and [`Error::DuplicateId`] if a transaction of
+    /// `other` conflicts with one in the batch under [`MergePolicy::Error`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{Format, MergePolicy};
+    ///
+    /// let january = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 5 USD\n";
+    /// let february = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 5.00 USD\n---\n\
+    ///                 ID: TX2\nDate: 2024-02-01\nType: Credit\nAmount: 9 USD\n";
+    /// let mut archive = parser::parse(january.as_bytes(), Format::Text)?;
+    /// archive.merge(parser::parse(february.as_bytes(), Format::Text)?, MergePolicy::Error)?;
+    ///
+    /// let ids: Vec<_> = archive.transactions.iter().map(|tx| tx.id.as_str()).collect();
+    /// assert_eq!(ids, ["TX1", "TX2"]);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn merge(&mut self, other: TransactionBatch, policy: MergePolicy) -> Result<()> {
+        let account_id = match (&self.account_id, &other.account_id) {
+            (Some(left), Some(right)) if left != right => {
+                return Err(Error::AccountMismatch {
+                    left: left.clone(),
+                    right: right.clone(),
+                })
+            }
+            (left, right) => left.clone().or_else(|| right.clone()),
+        };
+
+        let (self_period, other_period) = (self.period(), other.period());
+        let other_first = match (self_period, other_period) {
+            (Some(own), Some(theirs)) => theirs.start < own.start,
+            _ => false,
+        };
+        let balances = merge_balances(
+            &self.balances,
+            &self.transactions,
+            other.balances,
+            &other.transactions,
+            other_first,
+        );
+
+        let mut slots: BTreeMap<String, Slot> = BTreeMap::new();
+        for (index, tx) in self.transactions.iter().enumerate() {
+            slots.entry(tx.id.clone()).or_insert(Slot::Existing(index));
+        }
+        let mut replaced: BTreeMap<usize, Transaction> = BTreeMap::new();
+        let mut appended: Vec<Transaction> = Vec::new();
+        for tx in other.transactions {
+            let Some(&slot) = slots.get(&tx.id) else {
+                slots.insert(tx.id.clone(), Slot::Appended(appended.len()));
+                appended.push(tx);
+                continue;
+            };
+            let current = match slot {
+                Slot::Existing(index) => replaced.get(&index).unwrap_or(&self.transactions[index]),
+                Slot::Appended(index) => &appended[index],
+            };
+            if current.fingerprint() == tx.fingerprint() {
+                continue;
+            }
+            let replace = match policy {
+                MergePolicy::Error => return Err(Error::DuplicateId(tx.id)),
+                MergePolicy::KeepFirst => false,
+                MergePolicy::KeepLatest => tx.executed_at > current.executed_at,
+            };
+            if replace {
+                match slot {
+                    Slot::Existing(index) => {
+                        replaced.insert(index, tx);
+                    }
+                    Slot::Appended(index) => appended[index] = tx,
+                }
+            }
+        }
+
+        if self.metadata.period.is_some() || other.metadata.period.is_some() {
+            self.metadata.period = match (self_period, other_period) {
+                (Some(own), Some(theirs)) => Some(Period {
+                    start: own.start.min(theirs.start),
+                    end: own.end.max(theirs.end),
+                }),
+                (own, theirs) => own.or(theirs),
+            };
+        }
+        self.metadata.generated_at = self.metadata.generated_at.max(other.metadata.generated_at);
+        self.metadata.source_system = agree(
+            self.metadata.source_system.take(),
+            other.metadata.source_system,
+        );
+        self.metadata.schema_version = agree(
+            self.metadata.schema_version.take(),
+            other.metadata.schema_version,
+        );
+
+        for (index, tx) in replaced {
+            self.transactions[index] = tx;
+        }
+        self.transactions.extend(appended);
+        self.account_id = account_id;
+        self.balances = balances;
+        Ok(())
+    }
+}
+
+/// Combines the balances of two batches, `own` belonging to the batch with
+/// `own_transactions` and `theirs` to the one with `their_transactions`.
+fn merge_balances(
+    own: &[Balance],
+    own_transactions: &[Transaction],
+    theirs: Vec<Balance>,
+    their_transactions: &[Transaction],
+    theirs_first: bool,
+) -> Vec<Balance> {
+    let has_currency = |transactions: &[Transaction], currency: &str| {
+        transactions.iter().any(|tx| tx.amount.currency == currency)
+    };
+
+    let mut balances = Vec::new();
+    for balance in own {
+        match theirs.iter().find(|b| b.currency == balance.currency) {
+            Some(other) => {
+                let (first, last) = if theirs_first {
+                    (other, balance)
+                } else {
+                    (balance, other)
+                };
+                balances.push(Balance {
+                    currency: balance.currency.clone(),
+                    opening: first.opening,
+                    closing: last.closing,
+                });
+            }
+            None if !has_currency(their_transactions, &balance.currency) => {
+                balances.push(balance.clone())
+            }
+            None => {}
+        }
+    }
+    for balance in theirs {
+        if own.iter().all(|b| b.currency != balance.currency)
+            && !has_currency(own_transactions, &balance.currency)
+        {
+            balances.push(balance);
+        }
+    }
+    balances
+}
+
+/// The value both sides have, `None` if they disagree.
+fn agree(own: Option<String>, theirs: Option<String>) -> Option<String> {
+    match (own, theirs) {
+        (Some(own), Some(theirs)) => (own == theirs).then_some(own),
+        (own, theirs) => own.or(theirs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::string::ToString;
+    use alloc::vec;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn tx(id: &str, day: u32, amount: i64, executed_hour: Option<u32>) -> Transaction {
+        let posted_at = NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        Transaction {
+            id: id.to_string(),
+            posted_at,
+            executed_at: executed_hour.and_then(|hour| posted_at.and_hms_opt(hour, 0, 0)),
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from(amount),
+                currency: "EUR".to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn batch(transactions: Vec<Transaction>) -> TransactionBatch {
+        TransactionBatch {
+            transactions,
+            ..TransactionBatch::default()
+        }
+    }
+
+    fn amounts(batch: &TransactionBatch) -> Vec<(String, i64)> {
+        batch
+            .transactions
+            .iter()
+            .map(|tx| (tx.id.clone(), tx.amount.amount.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_policies() {
+        let base = batch(vec![tx("A", 1, 10, Some(9)), tx("B", 2, 20, None)]);
+        let other = || {
+            batch(vec![
+                tx("B", 2, 21, Some(8)),
+                tx("C", 3, 30, Some(9)),
+                tx("A", 1, 11, Some(8)),
+                tx("C", 3, 31, Some(10)),
+            ])
+        };
+
+        let mut first = base.clone();
+        first.merge(other(), MergePolicy::KeepFirst).unwrap();
+        let pairs = |list: &[(&str, i64)]| {
+            list.iter()
+                .map(|&(id, amount)| (id.to_string(), amount))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(amounts(&first), pairs(&[("A", 10), ("B", 20), ("C", 30)]));
+
+        let mut latest = base.clone();
+        latest.merge(other(), MergePolicy::KeepLatest).unwrap();
+        assert_eq!(amounts(&latest), pairs(&[("A", 10), ("B", 21), ("C", 31)]));
+
+        let mut strict = base.clone();
+        let error = strict.merge(other(), MergePolicy::Error).unwrap_err();
+        assert!(matches!(error, Error::DuplicateId(ref id) if id == "B"));
+        assert_eq!(strict, base);
+
+        // The same record in both batches isn't a conflict
+        let mut same = base.clone();
+        same.merge(batch(vec![tx("A", 1, 10, Some(9))]), MergePolicy::Error)
+            .unwrap();
+        assert_eq!(same, base);
+    }
+
+    #[test]
+    fn test_merge_accounts_and_balances() {
+        let balance = |currency: &str, opening, closing| Balance {
+            currency: currency.to_string(),
+            opening: Decimal::from(opening),
+            closing: Decimal::from(closing),
+        };
+        let mut february = batch(vec![tx("B", 20, 5, None)]);
+        february.account_id = Some("ACC1".to_string());
+        february.balances = vec![balance("EUR", 90, 85), balance("USD", 7, 7)];
+        let mut january = batch(vec![tx("A", 10, 10, None)]);
+        january.balances = vec![balance("EUR", 100, 90), balance("GBP", 3, 3)];
+
+        let mut merged = february.clone();
+        merged.merge(january, MergePolicy::Error).unwrap();
+        assert_eq!(merged.account_id.as_deref(), Some("ACC1"));
+        assert_eq!(
+            merged.balances,
+            vec![
+                balance("EUR", 100, 85),
+                balance("USD", 7, 7),
+                balance("GBP", 3, 3)
+            ]
+        );
+        assert_eq!(merged.metadata, Default::default());
+
+        let mut other = batch(Vec::new());
+        other.account_id = Some("ACC2".to_string());
+        assert!(matches!(
+            merged.merge(other, MergePolicy::KeepFirst),
+            Err(Error::AccountMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,354 @@
+//! Field-level patches between two versions of a batch.
+//!
+//! A [`Patch`] is the distributable form of a [`DiffReport`]: the fields
+//! that changed, with their old and new values, and the transactions removed
+//! and added. A correction to a million-row archive then ships as a file of
+//! a few lines instead of a full re-export.
+//!
+//! Unlike a [`Delta`](crate::delta::Delta), which is bound to one exact base
+//! batch by its fingerprint, a patch finds what it changes by transaction ID
+//! and old values. It applies to any copy of the records, in any order and
+//! after unrelated changes, and fails only where it conflicts with the
+//! batch.
+//!
+//! A patch carries no changes to [`Transaction::extra`]: the diff it is
+//! made from ignores that field, so extra columns corrected in the new
+//! version are not in the patch. Added transactions keep theirs only
+//! because they are copied whole.
+//!
+//! With the `serde` feature a patch serializes, for example to JSON:
+//!
+//! ```json
+//! {"ops": [
+//!   {"op": "update", "id": "TX2", "changes": [
+//!     {"field": "category", "left": null, "right": "Food"}
+//!   ]},
+//!   {"op": "add", "transaction": {"id": "TX9", "posted_at": "2024-01-20", ...}}
+//! ]}
+//! ```
+
+use crate::diff::{diff_transactions, DiffReport, FieldChange};
+use crate::{Error, Result, Transaction, TransactionBatch};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Changes to the transactions of a batch, as made by
+/// [`DiffReport::to_patch`] and applied by [`apply_patch`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+    /// The changes, applied in order
+    pub ops: Vec<PatchOp>,
+}
+
+impl Patch {
+    /// Returns `true` if applying the patch leaves the batch unchanged.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// One change of a [`Patch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "op", rename_all = "snake_case"))]
+pub enum PatchOp {
+    /// Sets fields of the transaction with the ID and the changes' old
+    /// values to their new values
+    Update {
+        /// The ID of the transaction before the update
+        id: String,
+        /// The fields to change
+        changes: Vec<FieldChange>,
+    },
+    /// Removes a transaction equal to this one
+    Remove {
+        /// The transaction to remove
+        transaction: Transaction,
+    },
+    /// Appends a transaction
+    Add {
+        /// The transaction to append
+        transaction: Transaction,
+    },
+}
+
+impl DiffReport {
+    /// Returns the patch that turns the first compared batch into the
+    /// second: an update per differing transaction, then the removed and
+    /// the added transactions.
+    ///
+    /// Compare with [`DiffOptions::by_id`](crate::diff::DiffOptions::by_id)
+    /// for a patch of one operation per changed ID. Paired by position, a
+    /// single inserted transaction makes an update of every later one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::diff::{diff_batches, DiffOptions};
+    /// use parser::patch::apply_patch;
+    /// use parser::Format;
+    ///
+    /// let old = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10 EUR\n---\n\
+    ///            ID: TX2\nDate: 2024-01-16\nType: Debit\nAmount: 3 EUR\n";
+    /// let new = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10 EUR\n---\n\
+    ///            ID: TX2\nDate: 2024-01-16\nType: Debit\nAmount: 3 EUR\nCategory: Food\n";
+    /// let mut old = parser::parse(old.as_bytes(), Format::Text)?;
+    /// let new = parser::parse(new.as_bytes(), Format::Text)?;
+    ///
+    /// let options = DiffOptions {
+    ///     by_id: true,
+    ///     ..DiffOptions::default()
+    /// };
+    /// let patch = diff_batches(&old, &new, &options).to_patch();
+    /// assert_eq!(patch.ops.len(), 1);
+    ///
+    /// apply_patch(&mut old, &patch)?;
+    /// assert_eq!(old.transactions, new.transactions);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn to_patch(&self) -> Patch {
+        let updates = self.changed.iter().map(|diff| PatchOp::Update {
+            id: diff.id.clone(),
+            changes: diff.changes.clone(),
+        });
+        let removals = self.removed.iter().map(|tx| PatchOp::Remove {
+            transaction: tx.clone(),
+        });
+        let additions = self.added.iter().map(|tx| PatchOp::Add {
+            transaction: tx.clone(),
+        });
+        Patch {
+            ops: updates.chain(removals).chain(additions).collect(),
+        }
+    }
+}
+
+/// Applies a patch to a batch.
+///
+/// Each update changes the first transaction with its ID whose fields have
+/// the update's old values, each removal removes the first transaction
+/// equal to its one, ignoring [`Transaction::extra`] as
+/// [`diff_transactions`] does, and additions are appended. Balances and
+/// metadata are left as they are.
+///
+/// # Errors
+///
+/// Returns [`Error::PatchConflict`] if an update or removal finds no such
+/// transaction. The batch is left unchanged then.
+pub fn apply_patch(batch: &mut TransactionBatch, patch: &Patch) -> Result<()> {
+    let mut transactions = batch.transactions.clone();
+    for op in &patch.ops {
+        match op {
+            PatchOp::Update { id, changes } => {
+                let mut with_id = transactions.iter_mut().filter(|tx| tx.id == *id).peekable();
+                if with_id.peek().is_none() {
+                    return Err(Error::PatchConflict(format!(
+                        "updated transaction {} is not in the batch",
+                        id
+                    )));
+                }
+                let tx = with_id
+                    .find(|tx| changes.iter().all(|change| has_old_value(tx, change)))
+                    .ok_or_else(|| {
+                        Error::PatchConflict(format!(
+                            "transaction {} doesn't have the values the patch changes",
+                            id
+                        ))
+                    })?;
+                for change in changes {
+                    set_new_value(tx, change);
+                }
+            }
+            PatchOp::Remove { transaction } => {
+                let index = transactions
+                    .iter()
+                    .position(|tx| diff_transactions(tx, transaction).is_empty())
+                    .ok_or_else(|| {
+                        Error::PatchConflict(format!(
+                            "removed transaction {} is not in the batch",
+                            transaction.id
+                        ))
+                    })?;
+                transactions.remove(index);
+            }
+            PatchOp::Add { transaction } => transactions.push(transaction.clone()),
+        }
+    }
+    batch.transactions = transactions;
+    Ok(())
+}
+
+/// Whether the field of `change` has its old value in `tx`.
+fn has_old_value(tx: &Transaction, change: &FieldChange) -> bool {
+    match change {
+        FieldChange::Id(c) => tx.id == c.left,
+        FieldChange::PostedAt(c) => tx.posted_at == c.left,
+        FieldChange::ExecutedAt(c) => tx.executed_at == c.left,
+        FieldChange::Kind(c) => tx.kind == c.left,
+        FieldChange::Status(c) => tx.status == c.left,
+        FieldChange::Amount(c) => tx.amount == c.left,
+        FieldChange::Description(c) => tx.description == c.left,
+        FieldChange::Account(c) => tx.account == c.left,
+        FieldChange::Counterparty(c) => tx.counterparty == c.left,
+        FieldChange::Category(c) => tx.category == c.left,
+        FieldChange::TaxAmount(c) => tx.tax_amount == c.left,
+        FieldChange::TaxRate(c) => tx.tax_rate == c.left,
+    }
+}
+
+/// Sets the field of `change` in `tx` to its new value.
+fn set_new_value(tx: &mut Transaction, change: &FieldChange) {
+    match change {
+        FieldChange::Id(c) => tx.id = c.right.clone(),
+        FieldChange::PostedAt(c) => tx.posted_at = c.right,
+        FieldChange::ExecutedAt(c) => tx.executed_at = c.right,
+        FieldChange::Kind(c) => tx.kind = c.right,
+        FieldChange::Status(c) => tx.status = c.right,
+        FieldChange::Amount(c) => tx.amount = c.right.clone(),
+        FieldChange::Description(c) => tx.description = c.right.clone(),
+        FieldChange::Account(c) => tx.account = c.right.clone(),
+        FieldChange::Counterparty(c) => tx.counterparty = c.right.clone(),
+        FieldChange::Category(c) => tx.category = c.right.clone(),
+        FieldChange::TaxAmount(c) => tx.tax_amount = c.right,
+        FieldChange::TaxRate(c) => tx.tax_rate = c.right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{diff_batches, Change, DiffOptions};
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::collections::BTreeMap;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn tx(id: &str, amount: i64) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from(amount),
+                currency: "EUR".to_string(),
+            },
+            description: "Rent".to_string(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn batch(transactions: Vec<Transaction>) -> TransactionBatch {
+        TransactionBatch {
+            transactions,
+            ..TransactionBatch::default()
+        }
+    }
+
+    #[test]
+    fn test_patch_roundtrip() {
+        let old = batch(vec![tx("A", 1), tx("B", 2), tx("C", 3)]);
+        let mut corrected = tx("B", 2);
+        corrected.category = Some("Housing".to_string());
+        let new = batch(vec![tx("A", 1), corrected]);
+        let options = DiffOptions {
+            by_id: true,
+            ..DiffOptions::default()
+        };
+
+        let patch = diff_batches(&old, &new, &options).to_patch();
+        assert_eq!(patch.ops.len(), 2);
+        assert!(matches!(&patch.ops[1], PatchOp::Remove { transaction } if transaction.id == "C"));
+
+        // Matching by ID and old values doesn't depend on the order
+        let mut reordered = batch(vec![tx("C", 3), tx("B", 2), tx("A", 1)]);
+        apply_patch(&mut reordered, &patch).unwrap();
+        assert!(diff_batches(&reordered, &new, &options).is_identical());
+        assert!(diff_batches(&old, &old, &options).to_patch().is_empty());
+    }
+
+    #[test]
+    fn test_patch_of_an_insertion() {
+        let old = batch((1..=6).map(|n| tx(&format!("T{}", n), n)).collect());
+        let mut new = old.clone();
+        new.transactions.insert(2, tx("NEW", 7));
+        let options = DiffOptions {
+            by_id: true,
+            ..DiffOptions::default()
+        };
+
+        let patch = diff_batches(&old, &new, &options).to_patch();
+        assert_eq!(
+            patch.ops,
+            vec![PatchOp::Add {
+                transaction: tx("NEW", 7)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_apply_patch_conflict() {
+        let mut target = batch(vec![tx("A", 1), tx("B", 5)]);
+        let patch = Patch {
+            ops: vec![
+                PatchOp::Add {
+                    transaction: tx("D", 4),
+                },
+                PatchOp::Update {
+                    id: "B".to_string(),
+                    changes: vec![FieldChange::Amount(Change {
+                        left: tx("B", 2).amount,
+                        right: tx("B", 3).amount,
+                    })],
+                },
+            ],
+        };
+
+        let error = apply_patch(&mut target, &patch).unwrap_err();
+        assert!(matches!(error, Error::PatchConflict(_)));
+        assert!(error
+            .to_string()
+            .contains("transaction B doesn't have the values the patch changes"));
+        // Nothing was applied, not even the addition before the conflict
+        assert_eq!(target.transactions.len(), 2);
+
+        let patch = Patch {
+            ops: vec![PatchOp::Remove {
+                transaction: tx("Z", 1),
+            }],
+        };
+        assert!(apply_patch(&mut target, &patch).is_err());
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn test_patch_serde() {
+        let old = batch(vec![tx("A", 1)]);
+        let mut new = batch(vec![tx("A", 1), tx("B", 2)]);
+        new.transactions[0].executed_at = NaiveDate::from_ymd_opt(2024, 5, 1)
+            .unwrap()
+            .and_hms_opt(9, 30, 0);
+        let options = DiffOptions {
+            by_id: true,
+            ..DiffOptions::default()
+        };
+        let patch = diff_batches(&old, &new, &options).to_patch();
+
+        let json = serde_json::to_value(&patch).unwrap();
+        assert_eq!(json["ops"][0]["op"], "update");
+        assert_eq!(json["ops"][0]["changes"][0]["field"], "executed_at");
+        assert_eq!(json["ops"][1]["transaction"]["id"], "B");
+        let parsed: Patch = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, patch);
+    }
+}
@@ -0,0 +1,395 @@
+//! Detecting personal data in free-text fields.
+//!
+//! Descriptions and counterparties often carry more than they should: a
+//! card number a merchant echoed, a customer's IBAN or phone number in a
+//! transfer reference. [`TransactionBatch::scan_pii`] flags such values as a
+//! pre-flight check before a batch leaves the bank, and [`find_pii`] scans
+//! any text.
+//!
+//! Detection is pattern-based and errs towards reporting:
+//!
+//! - card numbers are runs of 13 to 19 digits, optionally grouped by spaces
+//!   or dashes, that start like a card network's numbers (2 to 6) and pass
+//!   the Luhn check, where a longer run counts only up to the end of a
+//!   group, so a card number followed by another number is still found;
+//! - IBANs are a country code, check digits and up to 30 letters or digits,
+//!   optionally grouped by fours, that pass the ISO 7064 mod-97 check;
+//! - e-mail addresses are `local@domain.tld`;
+//! - phone numbers are international numbers starting with `+` or `00`
+//!   with 8 to 15 digits, and national numbers starting with `0` with 9 to
+//!   12 digits, not counting ones that contain a date.
+//!
+//! A value that is part of an earlier kind in this list isn't reported
+//! again, so an IBAN's digits aren't also a phone number. Where a candidate
+//! turns out not to be a value, such as a date followed by a phone number,
+//! the search resumes right after its start.
+
+use crate::{Transaction, TransactionBatch};
+use regex::Regex;
+use std::fmt;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+static CARD_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b[0-9](?:[ -]?[0-9]){12,18}\b").unwrap());
+static IBAN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[A-Z]{2}[0-9]{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,4})?\b").unwrap()
+});
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[A-Z0-9._%+-]+@[A-Z0-9-]+(?:\.[A-Z0-9-]+)*\.[A-Z]{2,}\b").unwrap()
+});
+// Bounded, since a rejected candidate is searched again from its start
+static PHONE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:\+|\b0|\(0)[0-9 ()/.-]{6,30}[0-9]").unwrap());
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[0-9]{1,2}[./-][0-9]{1,2}[./-][0-9]{2,4}|[0-9]{4}-[0-9]{2}-[0-9]{2}").unwrap()
+});
+
+/// A kind, the pattern of its candidates and the check that finds the value
+/// in one, as a range of the candidate.
+type Detector = (PiiKind, &'static Regex, fn(&str) -> Option<Range<usize>>);
+
+/// What kind of personal data a value looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum PiiKind {
+    /// A payment card number (PAN)
+    CardNumber,
+    /// An international bank account number
+    Iban,
+    /// A phone number
+    Phone,
+    /// An e-mail address
+    Email,
+}
+
+/// A free-text field of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PiiField {
+    /// [`Transaction::description`]
+    Description,
+    /// [`Transaction::counterparty`]
+    Counterparty,
+    /// [`Transaction::category`]
+    Category,
+    /// The column of [`Transaction::extra`] with this name
+    Extra(String),
+}
+
+impl fmt::Display for PiiField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PiiField::Description => f.write_str("description"),
+            PiiField::Counterparty => f.write_str("counterparty"),
+            PiiField::Category => f.write_str("category"),
+            PiiField::Extra(column) => write!(f, "column '{}'", column),
+        }
+    }
+}
+
+/// A value in a transaction that looks like personal data, as returned by
+/// [`TransactionBatch::scan_pii`].
+///
+/// The value itself isn't copied, so that a report of findings doesn't
+/// spread it further; `span` locates it in the field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiFinding {
+    /// Position of the transaction in the batch, from 0
+    pub index: usize,
+    /// ID of the transaction
+    pub id: String,
+    /// The field the value is in
+    pub field: PiiField,
+    /// What the value looks like
+    pub kind: PiiKind,
+    /// Byte range of the value in the field's text
+    pub span: Range<usize>,
+}
+
+impl TransactionBatch {
+    /// Scans the description, counterparty, category and extra columns of
+    /// every transaction for values that look like personal data.
+    ///
+    /// See the [module documentation](crate::pii) for what is detected.
+    ///
+    /// # Returns
+    ///
+    /// The findings in transaction order, then field order, then position
+    /// in the field; empty if the batch looks clean.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::pii::{PiiField, PiiKind};
+    /// use parser::Format;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 12 EUR\n\
+    ///             Description: Refund to card 4111 1111 1111 1111, call +49 30 1234567\n---\n\
+    ///             ID: TX2\nDate: 2024-01-16\nType: Debit\nAmount: 3 EUR\nDescription: Coffee\n";
+    /// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+    ///
+    /// let findings = batch.scan_pii();
+    /// assert_eq!(findings.len(), 2);
+    /// assert_eq!((findings[0].id.as_str(), findings[0].kind), ("TX1", PiiKind::CardNumber));
+    /// assert_eq!(findings[1].field, PiiField::Description);
+    /// assert_eq!(&batch.transactions[0].description[findings[1].span.clone()], "+49 30 1234567");
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn scan_pii(&self) -> Vec<PiiFinding> {
+        let mut findings = Vec::new();
+        for (index, tx) in self.transactions.iter().enumerate() {
+            for (field, text) in free_text(tx) {
+                for (kind, span) in find_pii(text) {
+                    findings.push(PiiFinding {
+                        index,
+                        id: tx.id.clone(),
+                        field: field.clone(),
+                        kind,
+                        span,
+                    });
+                }
+            }
+        }
+        findings
+    }
+}
+
+/// Finds the values in `text` that look like personal data.
+///
+/// # Returns
+///
+/// The kind and byte range of every value, ordered by position.
+///
+/// # Examples
+///
+/// ```
+/// use parser::pii::{find_pii, PiiKind};
+///
+/// let text = "SEPA transfer from DE89 3704 0044 0532 0130 00, jane.doe@example.com";
+/// let found: Vec<_> = find_pii(text).into_iter().map(|(kind, span)| (kind, &text[span])).collect();
+/// assert_eq!(
+///     found,
+///     [
+///         (PiiKind::Iban, "DE89 3704 0044 0532 0130 00"),
+///         (PiiKind::Email, "jane.doe@example.com")
+///     ]
+/// );
+/// assert!(find_pii("Invoice 2024-03-15, order 0123").is_empty());
+/// ```
+pub fn find_pii(text: &str) -> Vec<(PiiKind, Range<usize>)> {
+    let mut found: Vec<(PiiKind, Range<usize>)> = Vec::new();
+    let detectors: [Detector; 4] = [
+        (PiiKind::CardNumber, &CARD_NUMBER, card_number),
+        (PiiKind::Iban, &IBAN, |text| {
+            is_iban(text).then_some(0..text.len())
+        }),
+        (PiiKind::Email, &EMAIL, |text| Some(0..text.len())),
+        (PiiKind::Phone, &PHONE, |text| {
+            is_phone(text).then_some(0..text.len())
+        }),
+    ];
+    for (kind, regex, value) in detectors {
+        let mut start = 0;
+        while let Some(candidate) = regex.find_at(text, start) {
+            let span = value(candidate.as_str())
+                .map(|value| candidate.start() + value.start..candidate.start() + value.end)
+                .filter(|span| {
+                    !found
+                        .iter()
+                        .any(|(_, other)| span.start < other.end && other.start < span.end)
+                });
+            start = match span {
+                Some(span) => {
+                    let end = span.end;
+                    found.push((kind, span));
+                    end
+                }
+                None => next_char(text, candidate.start()),
+            };
+        }
+    }
+    found.sort_by_key(|(_, span)| span.start);
+    found
+}
+
+/// The free-text fields of `tx` that have a value.
+fn free_text(tx: &Transaction) -> impl Iterator<Item = (PiiField, &str)> {
+    [
+        (PiiField::Description, Some(tx.description.as_str())),
+        (PiiField::Counterparty, tx.counterparty.as_deref()),
+        (PiiField::Category, tx.category.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(field, text)| text.map(|text| (field, text)))
+    .chain(
+        tx.extra
+            .iter()
+            .map(|(column, value)| (PiiField::Extra(column.clone()), value.as_str())),
+    )
+}
+
+/// The position of the character after the one at `index`.
+fn next_char(text: &str, index: usize) -> usize {
+    index + text[index..].chars().next().map_or(1, char::len_utf8)
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// The longest start of `text` that ends with a group of digits and is a
+/// card number.
+fn card_number(text: &str) -> Option<Range<usize>> {
+    let group_ends = text
+        .match_indices([' ', '-'])
+        .map(|(index, _)| index)
+        .chain([text.len()]);
+    let longest = group_ends.rev().find(|&end| is_card_number(&text[..end]))?;
+    Some(0..longest)
+}
+
+/// Whether `text` has the length and prefix of a card number and passes the
+/// Luhn check.
+fn is_card_number(text: &str) -> bool {
+    let digits = digits(text);
+    if !(13..=19).contains(&digits.len()) || !(2..=6).contains(&digits[0]) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Whether `text` has the length of an IBAN and passes the mod-97 check.
+fn is_iban(text: &str) -> bool {
+    let iban: Vec<char> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if !(15..=34).contains(&iban.len()) {
+        return false;
+    }
+    // The country code and check digits move to the end, and letters count
+    // as 10 to 35
+    let remainder = iban[4..]
+        .iter()
+        .chain(&iban[..4])
+        .try_fold(0u32, |remainder, c| {
+            let value = c.to_digit(36)?;
+            let shift = if value < 10 { 10 } else { 100 };
+            Some((remainder * shift + value) % 97)
+        });
+    remainder == Some(1)
+}
+
+/// Whether `text` has the number of digits of a phone number and isn't a
+/// date.
+fn is_phone(text: &str) -> bool {
+    let count = digits(text).len();
+    let international = if let Some(rest) = text.strip_prefix('+') {
+        Some(rest)
+    } else {
+        text.strip_prefix("00")
+    };
+    let plausible = match international {
+        Some(rest) => (8..=15).contains(&digits(rest).len()),
+        None => (9..=12).contains(&count),
+    };
+    plausible && !DATE.is_match(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+
+    fn kinds(text: &str) -> Vec<(PiiKind, &str)> {
+        find_pii(text)
+            .into_iter()
+            .map(|(kind, span)| (kind, &text[span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_pii() {
+        assert_eq!(
+            kinds("PAN 5500-0000-0000-0004 / 4111 1111 1111 1112"),
+            [(PiiKind::CardNumber, "5500-0000-0000-0004")]
+        );
+        assert_eq!(
+            kinds("to gb82west12345698765432 ref 123"),
+            [(PiiKind::Iban, "gb82west12345698765432")]
+        );
+        // Wrong check digits
+        assert!(kinds("GB83WEST12345698765432").is_empty());
+        assert_eq!(
+            kinds("call (030) 123 4567 or 0044 20 7946 0958, mail a.b+c@bank.co.uk"),
+            [
+                (PiiKind::Phone, "(030) 123 4567"),
+                (PiiKind::Phone, "0044 20 7946 0958"),
+                (PiiKind::Email, "a.b+c@bank.co.uk"),
+            ]
+        );
+        assert!(kinds("Paid 01.02.2024 12:30, amount 0.50, terminal 0042").is_empty());
+        assert!(kinds("Reference 9780306406157 order 1234567890123").is_empty());
+        // A card number followed by another number, and a phone number
+        // after a date
+        assert_eq!(
+            kinds("Refund card 4111 1111 1111 1111 100 EUR"),
+            [(PiiKind::CardNumber, "4111 1111 1111 1111")]
+        );
+        assert_eq!(
+            kinds("4111111111111111 12"),
+            [(PiiKind::CardNumber, "4111111111111111")]
+        );
+        assert_eq!(
+            kinds("Ref 2024-01-15 0123456789"),
+            [(PiiKind::Phone, "0123456789")]
+        );
+    }
+
+    #[test]
+    fn test_scan_pii() {
+        let tx = Transaction {
+            id: "TX1".to_string(),
+            posted_at: "2024-05-01".parse().unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Credit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::ONE,
+                currency: "EUR".to_string(),
+            },
+            description: "Salary May".to_string(),
+            account: Some("DE89370400440532013000".to_string()),
+            counterparty: Some("ACME payroll@acme.example".to_string()),
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::from([("Ref".to_string(), "+44 7700 900123".to_string())]),
+        };
+        let batch = TransactionBatch {
+            transactions: vec![tx],
+            ..TransactionBatch::default()
+        };
+
+        // The account is expected to hold an account number
+        let findings = batch.scan_pii();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].field, PiiField::Counterparty);
+        assert_eq!(findings[0].span, 5..25);
+        assert_eq!(findings[1].field.to_string(), "column 'Ref'");
+        assert_eq!(findings[1].kind, PiiKind::Phone);
+    }
+}
@@ -0,0 +1,195 @@
+//! SQL queries over batches.
+//!
+//! With the `query` feature, [`query_sql`] runs an SQL query against a batch
+//! without a database server or a file: the batch is loaded into an
+//! in-memory SQLite database, in the tables the [`Format::Sqlite`] format
+//! writes, and the query's result is returned.
+//!
+//! The `transactions` table has the columns `id`, `posted_at`,
+//! `executed_at`, `kind`, `amount`, `signed_amount`, `currency`,
+//! `description`, `account`, `counterparty`, `category`, `tax_amount`,
+//! `tax_rate` and `status`, the `balances` table `currency`, `opening` and
+//! `closing`, and the `batch_metadata` table the account ID under the key
+//! `account_id`.
+//!
+//! `amount` is unsigned for debits and credits alike, so `SUM(amount)` adds
+//! them together; sum `signed_amount`, which is negative for money leaving
+//! the account, for net totals. Amounts are stored as exact decimal text,
+//! but SQLite computes with floating-point numbers, so sums can come out as
+//! `70.50000000000001`; `ROUND(SUM(signed_amount), 2)` rounds them back to
+//! cents.
+//!
+//! [`Format::Sqlite`]: crate::Format::Sqlite
+
+use crate::formats::sqlite::{insert_batch, runtime};
+use crate::{Result, TransactionBatch};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{Column, ConnectOptions, Connection, Executor, Row, Statement, TypeInfo, ValueRef};
+use std::fmt;
+
+/// A value of a query result, as SQLite typed it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// SQL `NULL`
+    Null,
+    /// An integer, such as the result of `COUNT(*)`
+    Integer(i64),
+    /// A floating-point number, such as the result of `SUM(signed_amount)`
+    Real(f64),
+    /// Text, such as a category or a stored amount
+    Text(String),
+    /// Binary data
+    Blob(Vec<u8>),
+}
+
+impl fmt::Display for Value {
+    /// Writes the value as the `sqlite3` shell does: `NULL` as nothing and
+    /// blobs as hex.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{}", value),
+            Value::Text(value) => f.write_str(value),
+            Value::Blob(bytes) => bytes.iter().try_for_each(|b| write!(f, "{:02X}", b)),
+        }
+    }
+}
+
+/// The result of [`query_sql`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryResult {
+    /// Names of the result columns
+    pub columns: Vec<String>,
+    /// The rows, each with one value per column
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Runs an SQL query against a batch.
+///
+/// See the [module documentation](crate::query) for the tables. The
+/// database only exists for the duration of the call, so statements that
+/// change it have no effect beyond their own result.
+///
+/// # Arguments
+///
+/// * `batch` - The batch to query
+/// * `sql` - One SQL statement in SQLite's dialect
+///
+/// # Errors
+///
+/// Returns [`Error::Database`](crate::Error::Database) if the statement
+/// is invalid or fails.
+///
+/// # Examples
+///
+/// ```
+/// use parser::query::{query_sql, Value};
+/// use parser::Format;
+///
+/// let text = "ID: TX1\nDate: 2024-01-03\nType: Debit\nAmount: 5 USD\nCategory: Food\n---\n\
+///             ID: TX2\nDate: 2024-01-04\nType: Debit\nAmount: 7.25 USD\nCategory: Food\n---\n\
+///             ID: TX3\nDate: 2024-01-05\nType: Credit\nAmount: 100 USD\nCategory: Salary\n";
+/// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+///
+/// let result = query_sql(
+///     &batch,
+///     "SELECT category, COUNT(*) AS n, ROUND(SUM(signed_amount), 2) AS net \
+///      FROM transactions GROUP BY category ORDER BY category",
+/// )?;
+/// assert_eq!(result.columns, ["category", "n", "net"]);
+/// assert_eq!(
+///     result.rows[0],
+///     [Value::Text("Food".into()), Value::Integer(2), Value::Real(-12.25)]
+/// );
+/// # Ok::<(), parser::Error>(())
+/// ```
+pub fn query_sql(batch: &TransactionBatch, sql: &str) -> Result<QueryResult> {
+    runtime()?.block_on(async {
+        let mut conn = SqliteConnectOptions::new()
+            .in_memory(true)
+            .connect()
+            .await?;
+        insert_batch(&mut conn, batch).await?;
+
+        let rows = sqlx::query(sql).fetch_all(&mut conn).await?;
+        let columns = match rows.first() {
+            Some(row) => row.columns().iter().map(|c| c.name().to_string()).collect(),
+            // Without rows the names come from the prepared statement
+            None => (&mut conn)
+                .prepare(sql)
+                .await?
+                .columns()
+                .iter()
+                .map(|c| c.name().to_string())
+                .collect(),
+        };
+        let rows = rows.iter().map(values).collect::<Result<_>>()?;
+        conn.close().await?;
+        Ok(QueryResult { columns, rows })
+    })
+}
+
+fn values(row: &SqliteRow) -> Result<Vec<Value>> {
+    (0..row.len())
+        .map(|index| {
+            let raw = row.try_get_raw(index)?;
+            if raw.is_null() {
+                return Ok(Value::Null);
+            }
+            Ok(match raw.type_info().name() {
+                "INTEGER" => Value::Integer(row.try_get(index)?),
+                "REAL" => Value::Real(row.try_get(index)?),
+                "BLOB" => Value::Blob(row.try_get(index)?),
+                _ => Value::Text(row.try_get(index)?),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Format;
+
+    fn batch() -> TransactionBatch {
+        let text = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 50 EUR\n---\n\
+                    ID: TX2\nDate: 2024-02-01\nType: Credit\nAmount: 20.50 EUR\n";
+        crate::parse(text.as_bytes(), Format::Text).unwrap()
+    }
+
+    #[test]
+    fn test_query_sql() {
+        let result = query_sql(
+            &batch(),
+            "SELECT strftime('%m', posted_at) AS month, amount, category, executed_at IS NULL \
+             FROM transactions ORDER BY posted_at DESC",
+        )
+        .unwrap();
+        assert_eq!(result.columns[0], "month");
+        assert_eq!(
+            result.rows[0],
+            vec![
+                Value::Text("02".into()),
+                Value::Text("20.50".into()),
+                Value::Null,
+                Value::Integer(1)
+            ]
+        );
+        assert_eq!(result.rows.len(), 2);
+
+        let net = query_sql(&batch(), "SELECT SUM(signed_amount) FROM transactions").unwrap();
+        assert_eq!(net.rows, vec![vec![Value::Real(-29.5)]]);
+
+        let empty = query_sql(&batch(), "SELECT id FROM transactions WHERE 0").unwrap();
+        assert_eq!(empty.columns, vec!["id"]);
+        assert!(empty.rows.is_empty());
+    }
+
+    #[test]
+    fn test_query_sql_error() {
+        let error = query_sql(&batch(), "SELECT payee FROM transactions").unwrap_err();
+        assert_eq!(error.kind(), "database");
+        assert!(error.to_string().contains("no such column: payee"));
+    }
+}
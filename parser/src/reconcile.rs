@@ -0,0 +1,362 @@
+//! Reconciling two batches, such as a bank statement against a ledger.
+//!
+//! [`reconcile`] pairs up the transactions of two batches that record the
+//! same money movement, even when the two sides don't share IDs, and lists
+//! what is left over on either side. Transactions are matched in two passes:
+//!
+//! 1. by ID, for sources that carry the other side's reference, when the
+//!    signed amount and currency are equal as well: the same ID with a
+//!    different amount is a discrepancy to look into, not a match;
+//! 2. among the rest, fuzzily: the signed amount and currency must be equal,
+//!    the posting dates at most [`ReconcileOptions::date_tolerance`] days
+//!    apart and the descriptions at least
+//!    [`ReconcileOptions::min_similarity`] similar. When a transaction has
+//!    several candidates, the most similar descriptions and then the closest
+//!    dates are paired first.
+//!
+//! Descriptions are compared after normalization, ignoring case,
+//! punctuation and spacing, by the share of letter pairs they have in common
+//! (the Sørensen–Dice coefficient over bigrams), from `0.0` for nothing in
+//! common to `1.0` for the same text.
+
+use crate::{Transaction, TransactionBatch};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// How [`reconcile`] matches transactions without a common ID.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconcileOptions {
+    /// How many days apart the posting dates of a pair may be
+    pub date_tolerance: u32,
+    /// How similar the descriptions of a pair must at least be, from `0.0`
+    /// (any descriptions) to `1.0` (the same after normalization)
+    pub min_similarity: f64,
+}
+
+impl Default for ReconcileOptions {
+    /// Three days apart, a third of the descriptions' letter pairs shared.
+    fn default() -> Self {
+        Self {
+            date_tolerance: 3,
+            min_similarity: 0.3,
+        }
+    }
+}
+
+/// How a pair of transactions was matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchKind {
+    /// The transactions have the same ID and amount
+    Id,
+    /// The transactions have the same amount, close dates and similar
+    /// descriptions
+    Fuzzy {
+        /// How many days apart the posting dates are
+        days_apart: u32,
+        /// How similar the descriptions are, from `0.0` to `1.0`
+        similarity: f64,
+    },
+}
+
+/// Two transactions that record the same money movement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match<'a> {
+    /// The transaction of the left batch
+    pub left: &'a Transaction,
+    /// The transaction of the right batch
+    pub right: &'a Transaction,
+    /// How the transactions were matched
+    pub kind: MatchKind,
+}
+
+/// The result of [`reconcile`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Reconciliation<'a> {
+    /// The matched pairs, in the order of the left batch
+    pub matched: Vec<Match<'a>>,
+    /// Transactions of the left batch without a match, in their order
+    pub unmatched_left: Vec<&'a Transaction>,
+    /// Transactions of the right batch without a match, in their order
+    pub unmatched_right: Vec<&'a Transaction>,
+}
+
+impl Reconciliation<'_> {
+    /// Whether every transaction on both sides was matched.
+    pub fn is_complete(&self) -> bool {
+        self.unmatched_left.is_empty() && self.unmatched_right.is_empty()
+    }
+}
+
+/// Matches the transactions of `left` with those of `right`.
+///
+/// See the [module documentation](crate::reconcile) for how transactions
+/// are matched. Each transaction is matched at most once.
+///
+/// # Examples
+///
+/// ```
+/// use parser::reconcile::{reconcile, MatchKind, ReconcileOptions};
+/// use parser::Format;
+///
+/// let bank = "ID: B1\nDate: 2024-03-01\nType: Debit\nAmount: 42.00 EUR\n\
+///             Description: CARD PAYMENT ACME STORE 0231\n---\n\
+///             ID: B2\nDate: 2024-03-02\nType: Fee\nAmount: 1.50 EUR\nDescription: Account fee\n";
+/// let ledger = "ID: L7\nDate: 2024-02-29\nType: Debit\nAmount: 42 EUR\nDescription: Acme store\n";
+/// let bank = parser::parse(bank.as_bytes(), Format::Text)?;
+/// let ledger = parser::parse(ledger.as_bytes(), Format::Text)?;
+///
+/// let result = reconcile(&bank, &ledger, &ReconcileOptions::default());
+/// assert_eq!(result.matched.len(), 1);
+/// assert_eq!(result.matched[0].right.id, "L7");
+/// assert!(matches!(result.matched[0].kind, MatchKind::Fuzzy { days_apart: 1, .. }));
+/// assert_eq!(result.unmatched_left[0].id, "B2");
+/// # Ok::<(), parser::Error>(())
+/// ```
+pub fn reconcile<'a>(
+    left: &'a TransactionBatch,
+    right: &'a TransactionBatch,
+    options: &ReconcileOptions,
+) -> Reconciliation<'a> {
+    let mut left_match: Vec<Option<(usize, MatchKind)>> =
+        alloc::vec![None; left.transactions.len()];
+    let mut right_used = alloc::vec![false; right.transactions.len()];
+
+    let mut by_id: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, tx) in right.transactions.iter().enumerate() {
+        by_id.entry(tx.id.as_str()).or_default().push(index);
+    }
+    for (index, tx) in left.transactions.iter().enumerate() {
+        let Some(candidates) = by_id.get_mut(tx.id.as_str()) else {
+            continue;
+        };
+        let same_amount = candidates.iter().position(|&r| {
+            let other = &right.transactions[r];
+            other.amount.currency == tx.amount.currency
+                && other.signed_amount() == tx.signed_amount()
+        });
+        if let Some(position) = same_amount {
+            let candidate = candidates.remove(position);
+            left_match[index] = Some((candidate, MatchKind::Id));
+            right_used[candidate] = true;
+        }
+    }
+
+    // Every admissible fuzzy pair, paired best first
+    let lefts = unmatched(left, |index| left_match[index].is_some());
+    let rights = unmatched(right, |index| right_used[index]);
+    let mut candidates = Vec::new();
+    for (l, left_tx, left_text) in &lefts {
+        for (r, right_tx, right_text) in &rights {
+            if left_tx.amount.currency != right_tx.amount.currency
+                || left_tx.signed_amount() != right_tx.signed_amount()
+            {
+                continue;
+            }
+            let days_apart = (left_tx.posted_at - right_tx.posted_at)
+                .num_days()
+                .unsigned_abs();
+            if days_apart > u64::from(options.date_tolerance) {
+                continue;
+            }
+            let similarity = similarity(left_text, right_text);
+            if similarity < options.min_similarity {
+                continue;
+            }
+            candidates.push((*l, *r, days_apart as u32, similarity));
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.3.total_cmp(&a.3)
+            .then(a.2.cmp(&b.2))
+            .then(a.0.cmp(&b.0))
+            .then(a.1.cmp(&b.1))
+    });
+    for (l, r, days_apart, similarity) in candidates {
+        if left_match[l].is_none() && !right_used[r] {
+            left_match[l] = Some((
+                r,
+                MatchKind::Fuzzy {
+                    days_apart,
+                    similarity,
+                },
+            ));
+            right_used[r] = true;
+        }
+    }
+
+    let mut result = Reconciliation::default();
+    for (tx, matched) in left.transactions.iter().zip(left_match) {
+        match matched {
+            Some((r, kind)) => result.matched.push(Match {
+                left: tx,
+                right: &right.transactions[r],
+                kind,
+            }),
+            None => result.unmatched_left.push(tx),
+        }
+    }
+    result.unmatched_right = right
+        .transactions
+        .iter()
+        .zip(right_used)
+        .filter(|(_, used)| !used)
+        .map(|(tx, _)| tx)
+        .collect();
+    result
+}
+
+/// The transactions of `batch` that aren't `used` yet, with their index and
+/// normalized description.
+fn unmatched(
+    batch: &TransactionBatch,
+    used: impl Fn(usize) -> bool,
+) -> Vec<(usize, &Transaction, String)> {
+    batch
+        .transactions
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !used(*index))
+        .map(|(index, tx)| (index, tx, normalize(&tx.description)))
+        .collect()
+}
+
+/// Lowercases `text` and keeps only letters and digits, with a single space
+/// between words.
+fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        normalized.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    normalized
+}
+
+/// The Sørensen–Dice coefficient of the character bigrams of two normalized
+/// texts.
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let bigrams = |text: &str| {
+        let chars: Vec<char> = text.chars().collect();
+        let mut counts: BTreeMap<(char, char), usize> = BTreeMap::new();
+        for pair in chars.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += 1;
+        }
+        counts
+    };
+    let (a, b) = (bigrams(a), bigrams(b));
+    let total: usize = a.values().chain(b.values()).sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let shared: usize = a
+        .iter()
+        .map(|(pair, count)| (*count).min(b.get(pair).copied().unwrap_or(0)))
+        .sum();
+    (2 * shared) as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::string::ToString;
+    use alloc::vec;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn tx(id: &str, day: u32, amount: i64, description: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 5, day).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from(amount),
+                currency: "EUR".to_string(),
+            },
+            description: description.to_string(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn batch(transactions: Vec<Transaction>) -> TransactionBatch {
+        TransactionBatch {
+            transactions,
+            ..TransactionBatch::default()
+        }
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let bank = batch(vec![
+            tx("REF-1", 10, 100, "Transfer"),
+            tx("B2", 10, 25, "SEPA DD Electricity Co. 05/24"),
+            tx("B3", 11, 25, "CARD Bakery Sonne"),
+            tx("B4", 20, 60, "Gym"),
+            tx("REF-2", 15, 40, "Refund"),
+        ]);
+        let ledger = batch(vec![
+            tx("L1", 12, 25, "Bakery Sonne"),
+            tx("REF-1", 1, 100, "Something else entirely"),
+            tx("L3", 8, 25, "Electricity co"),
+            tx("L4", 10, 60, "Gym"),
+            tx("REF-2", 15, 999, "Refund"),
+        ]);
+
+        let result = reconcile(&bank, &ledger, &ReconcileOptions::default());
+        let pairs: Vec<(&str, &str)> = result
+            .matched
+            .iter()
+            .map(|m| (m.left.id.as_str(), m.right.id.as_str()))
+            .collect();
+        assert_eq!(pairs, vec![("REF-1", "REF-1"), ("B2", "L3"), ("B3", "L1")]);
+        assert_eq!(result.matched[0].kind, MatchKind::Id);
+        assert!(matches!(
+            result.matched[2].kind,
+            MatchKind::Fuzzy { days_apart: 1, .. }
+        ));
+        // The gym payments are ten days apart, and the amounts of REF-2
+        // differ
+        let ids = |txs: &[&Transaction]| txs.iter().map(|tx| tx.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&result.unmatched_left), ["B4", "REF-2"]);
+        assert_eq!(ids(&result.unmatched_right), ["L4", "REF-2"]);
+        assert!(!result.is_complete());
+
+        let options = ReconcileOptions {
+            date_tolerance: 10,
+            ..ReconcileOptions::default()
+        };
+        let result = reconcile(&bank, &ledger, &options);
+        assert_eq!(ids(&result.unmatched_left), ["REF-2"]);
+        assert_eq!(ids(&result.unmatched_right), ["REF-2"]);
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(
+            normalize("  CARD-Payment: ACME,  Inc. "),
+            "card payment acme inc"
+        );
+        assert_eq!(similarity("acme", "acme"), 1.0);
+        assert_eq!(similarity("ab", "cd"), 0.0);
+        assert_eq!(
+            similarity("acme store", "card payment acme store 0231"),
+            0.5
+        );
+        assert!(similarity("acme store", "electricity") < 0.3);
+    }
+}
@@ -0,0 +1,149 @@
+//! Streaming access to S3-compatible object storage.
+//!
+//! Objects are addressed as `s3://bucket/key`. Credentials, region and
+//! endpoint are taken from the standard environment variables
+//! (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`, and
+//! `AWS_ENDPOINT` for S3-compatible services such as MinIO).
+//!
+//! The adapters below expose object bodies as blocking [`Read`] / [`Write`]
+//! implementations by driving the async `object_store` client on a private
+//! single-threaded runtime, so they must not be used from inside an async
+//! context.
+
+use crate::{Error, Result};
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use object_store::aws::AmazonS3Builder;
+use object_store::buffered::BufWriter;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::runtime::Runtime;
+
+/// A parsed `s3://bucket/key` URI.
+pub(crate) struct S3Location {
+    bucket: String,
+    key: String,
+}
+
+impl S3Location {
+    /// Parses an `s3://` URI, returning `None` for anything else.
+    pub(crate) fn parse(uri: &str) -> Option<Self> {
+        let rest = uri.strip_prefix("s3://")?;
+        let (bucket, key) = rest.split_once('/')?;
+        if bucket.is_empty() || key.is_empty() {
+            return None;
+        }
+        Some(Self {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        })
+    }
+
+    fn store(&self) -> Result<Arc<dyn ObjectStore>> {
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(&self.bucket)
+            .build()?;
+        Ok(Arc::new(store))
+    }
+}
+
+/// Reads an object body chunk by chunk as it arrives from the network.
+pub(crate) struct S3Reader {
+    runtime: Runtime,
+    stream: BoxStream<'static, object_store::Result<Bytes>>,
+    chunk: Bytes,
+}
+
+impl S3Reader {
+    pub(crate) fn open(location: &S3Location) -> Result<Self> {
+        let runtime = runtime()?;
+        let store = location.store()?;
+        let path = ObjectPath::from(location.key.as_str());
+        let stream = runtime.block_on(store.get(&path))?.into_stream();
+
+        Ok(Self {
+            runtime,
+            stream,
+            chunk: Bytes::new(),
+        })
+    }
+}
+
+impl Read for S3Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.runtime.block_on(self.stream.next()) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Err(io::Error::other(e)),
+                None => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len());
+        buf[..n].copy_from_slice(&self.chunk[..n]);
+        self.chunk = self.chunk.slice(n..);
+        Ok(n)
+    }
+}
+
+/// Uploads an object, switching to a multipart upload once the written data
+/// outgrows a single request.
+pub(crate) struct S3Writer {
+    runtime: Runtime,
+    inner: BufWriter,
+}
+
+impl S3Writer {
+    pub(crate) fn create(location: &S3Location) -> Result<Self> {
+        let store = location.store()?;
+        let path = ObjectPath::from(location.key.as_str());
+
+        Ok(Self {
+            runtime: runtime()?,
+            inner: BufWriter::new(store, path),
+        })
+    }
+
+    /// Completes the upload. The object is not visible until this returns.
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.shutdown())?;
+        Ok(())
+    }
+}
+
+impl Write for S3Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.runtime.block_on(self.inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.runtime.block_on(self.inner.flush())
+    }
+}
+
+fn runtime() -> Result<Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_uri() {
+        let location = S3Location::parse("s3://statements/2024/01/acc123.bin").unwrap();
+        assert_eq!(location.bucket, "statements");
+        assert_eq!(location.key, "2024/01/acc123.bin");
+
+        assert!(S3Location::parse("s3://bucket-only").is_none());
+        assert!(S3Location::parse("s3:///key").is_none());
+        assert!(S3Location::parse("statements/acc123.bin").is_none());
+    }
+}
@@ -0,0 +1,299 @@
+//! Ed25519 signatures over transaction batches.
+//!
+//! A signature covers the canonical form of a batch (see [`crate::canonical`])
+//! serialized in binary format, so it survives conversions that keep the
+//! data, such as reordering records or re-exporting the batch as CSV, while
+//! a changed transaction, balance or account makes it fail to verify.
+//!
+//! [`sign_batch`] produces a detached [`Signature`] to store next to an
+//! exported statement, and [`verify_batch`] checks it against the signer's
+//! [`VerifyingKey`]. Binary output can carry the signature itself instead:
+//! set `WriteOptions::signing_key` when writing, and
+//! `ParseOptions::verifying_key` to check it when parsing.
+//!
+//! # Examples
+//!
+//! ```
+//! use parser::signature::{sign_batch, verify_batch, SigningKey};
+//! use parser::TransactionBatch;
+//!
+//! # fn main() -> Result<(), parser::Error> {
+//! let key = SigningKey::generate()?;
+//! let mut batch = TransactionBatch::default();
+//! let signature = sign_batch(&batch, &key);
+//! verify_batch(&batch, &signature, &key.verifying_key())?;
+//!
+//! batch.account_id = Some("ACC123".to_string());
+//! assert!(verify_batch(&batch, &signature, &key.verifying_key()).is_err());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::canonical::canonicalize;
+use crate::formats::binary::encode_binary;
+use crate::hashing::to_hex;
+use crate::{Error, Result, TransactionBatch};
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use std::fmt;
+use std::str::FromStr;
+
+/// Prefixed to the signed bytes, so that a batch signature can't be taken
+/// for a signature over anything else.
+const CONTEXT: &[u8] = b"YPBank batch signature v1\0";
+
+/// A private Ed25519 key that signs batches.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SigningKey([u8; 32]);
+
+impl SigningKey {
+    /// Creates a key from its 32-byte seed.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self(seed)
+    }
+
+    /// Generates a random key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system's random number generator fails.
+    pub fn generate() -> Result<Self> {
+        let mut seed = [0u8; 32];
+        ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut seed)
+            .map_err(|_| Error::parse("Signature", "cannot generate a key"))?;
+        Ok(Self(seed))
+    }
+
+    /// The key's seed.
+    pub fn seed(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The public key that verifies this key's signatures.
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let mut key = [0u8; 32];
+        key.copy_from_slice(self.key_pair().public_key().as_ref());
+        VerifyingKey(key)
+    }
+
+    fn key_pair(&self) -> Ed25519KeyPair {
+        Ed25519KeyPair::from_seed_unchecked(&self.0).expect("Ed25519 seeds are 32 bytes")
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SigningKey(..)")
+    }
+}
+
+/// Parses a key from its seed as 64 hexadecimal digits.
+impl FromStr for SigningKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        decode_hex(s, "key").map(Self)
+    }
+}
+
+/// A public Ed25519 key that verifies signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VerifyingKey([u8; 32]);
+
+impl VerifyingKey {
+    /// Creates a key from its bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The key's bytes.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Formats the key as 64 hexadecimal digits.
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(&self.0))
+    }
+}
+
+/// Parses a key from 64 hexadecimal digits.
+impl FromStr for VerifyingKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        decode_hex(s, "key").map(Self)
+    }
+}
+
+/// An Ed25519 signature over a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Signature([u8; 64]);
+
+impl Signature {
+    /// Creates a signature from its bytes.
+    pub fn from_bytes(bytes: [u8; 64]) -> Self {
+        Self(bytes)
+    }
+
+    /// The signature's bytes.
+    pub fn as_bytes(&self) -> &[u8; 64] {
+        &self.0
+    }
+}
+
+/// Formats the signature as 128 hexadecimal digits.
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(&self.0))
+    }
+}
+
+/// Parses a signature from 128 hexadecimal digits.
+impl FromStr for Signature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        decode_hex(s, "signature").map(Self)
+    }
+}
+
+/// Signs a batch.
+///
+/// # Arguments
+///
+/// * `batch` - The batch to sign
+/// * `key` - The signer's private key
+///
+/// # Returns
+///
+/// Returns a signature over the canonical form of `batch`.
+pub fn sign_batch(batch: &TransactionBatch, key: &SigningKey) -> Signature {
+    let signature = key.key_pair().sign(&signed_bytes(batch));
+    let mut bytes = [0u8; 64];
+    bytes.copy_from_slice(signature.as_ref());
+    Signature(bytes)
+}
+
+/// Verifies a signature made with [`sign_batch`].
+///
+/// # Arguments
+///
+/// * `batch` - The batch that was signed
+/// * `signature` - The signature to check
+/// * `key` - The signer's public key
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidSignature`] if `signature` isn't a signature over
+/// `batch` by the owner of `key`.
+pub fn verify_batch(
+    batch: &TransactionBatch,
+    signature: &Signature,
+    key: &VerifyingKey,
+) -> Result<()> {
+    UnparsedPublicKey::new(&ED25519, &key.0)
+        .verify(&signed_bytes(batch), &signature.0)
+        .map_err(|_| Error::InvalidSignature)
+}
+
+/// The bytes a batch signature covers: the canonical batch in binary format.
+fn signed_bytes(batch: &TransactionBatch) -> Vec<u8> {
+    let mut batch = batch.clone();
+    canonicalize(&mut batch);
+    let mut bytes = CONTEXT.to_vec();
+    encode_binary(&batch, &mut bytes).expect("writing to a Vec doesn't fail");
+    bytes
+}
+
+fn decode_hex<const N: usize>(s: &str, what: &str) -> Result<[u8; N]> {
+    let invalid = || {
+        Error::parse(
+            "Signature",
+            format!("invalid {}: expected {} hexadecimal digits", what, N * 2),
+        )
+    };
+    let s = s.trim();
+    if s.len() != N * 2 || !s.is_ascii() {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; N];
+    for (byte, i) in bytes.iter_mut().zip((0..s.len()).step_by(2)) {
+        *byte = u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, Transaction, TransactionKind, TransactionStatus};
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+
+    fn transaction(id: &str, amount: Decimal) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount,
+                currency: "USD".to_string(),
+            },
+            description: "Grocery shopping".to_string(),
+            account: Some("ACC123456".to_string()),
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key: SigningKey = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            .parse()
+            .unwrap();
+        // The first test vector of RFC 8032
+        assert_eq!(
+            key.verifying_key().to_string(),
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"
+        );
+
+        let mut batch = TransactionBatch {
+            account_id: Some("ACC123456".to_string()),
+            transactions: vec![
+                transaction("TX1", Decimal::new(15050, 2)),
+                transaction("TX2", Decimal::new(10, 0)),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let signature = sign_batch(&batch, &key);
+        let public = key.verifying_key();
+        assert!(verify_batch(&batch, &signature, &public).is_ok());
+        assert_eq!(
+            signature.to_string().parse::<Signature>().unwrap(),
+            signature
+        );
+
+        // Changes that keep the data keep the signature valid
+        batch.transactions.reverse();
+        batch.transactions[0].amount.amount = Decimal::new(1000, 2);
+        assert!(verify_batch(&batch, &signature, &public).is_ok());
+
+        batch.transactions[1].amount.amount = Decimal::new(15051, 2);
+        let err = verify_batch(&batch, &signature, &public).unwrap_err();
+        assert!(matches!(err, Error::InvalidSignature));
+
+        let other = SigningKey::generate().unwrap().verifying_key();
+        batch.transactions[1].amount.amount = Decimal::new(15050, 2);
+        assert!(verify_batch(&batch, &signature, &other).is_err());
+        assert!("abc".parse::<VerifyingKey>().is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! Sorting batches by a list of keys.
+//!
+//! Banks order their exports differently: by posting date, by execution
+//! time, newest first, or not at all. [`TransactionBatch::sort_by_keys`]
+//! puts a batch into a chosen order, given as [`SortKey`]s such as
+//! `date,amount:desc`; each key only decides between transactions the
+//! previous keys consider equal.
+
+use crate::{Error, Result, Transaction, TransactionBatch};
+use alloc::format;
+use core::cmp::Ordering;
+use core::fmt;
+use core::str::FromStr;
+
+/// A field transactions can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum SortField {
+    /// [`Transaction::posted_at`]
+    Date,
+    /// [`Transaction::signed_amount`], so debits come before credits
+    Amount,
+    /// [`Transaction::id`]
+    Id,
+    /// [`Transaction::category`]; transactions without one come first
+    Category,
+}
+
+/// One key of a sort order: a field and a direction.
+///
+/// Written as the field name, optionally followed by `:asc` or `:desc`, as
+/// in `amount:desc`; keys without a direction sort ascending.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{SortField, SortKey};
+///
+/// let key: SortKey = "Amount:desc".parse()?;
+/// assert_eq!(key, SortKey::desc(SortField::Amount));
+/// assert_eq!(key.to_string(), "amount:desc");
+/// # Ok::<(), parser::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    /// The field to compare
+    pub field: SortField,
+    /// Whether larger values come first
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Sorts by `field`, smallest value first.
+    pub fn asc(field: SortField) -> Self {
+        Self {
+            field,
+            descending: false,
+        }
+    }
+
+    /// Sorts by `field`, largest value first.
+    pub fn desc(field: SortField) -> Self {
+        Self {
+            field,
+            descending: true,
+        }
+    }
+
+    /// Compares two transactions by this key.
+    pub fn compare(&self, a: &Transaction, b: &Transaction) -> Ordering {
+        let ordering = match self.field {
+            SortField::Date => a.posted_at.cmp(&b.posted_at),
+            SortField::Amount => a.signed_amount().cmp(&b.signed_amount()),
+            SortField::Id => a.id.cmp(&b.id),
+            SortField::Category => a.category.cmp(&b.category),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = if self.descending { "desc" } else { "asc" };
+        write!(f, "{}:{}", self.field, direction)
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, direction) = match s.trim().split_once(':') {
+            Some((field, direction)) => (field, Some(direction)),
+            None => (s.trim(), None),
+        };
+        let field = SortField::from_str(field.trim()).map_err(|_| {
+            Error::parse(
+                "Sort",
+                format!(
+                    "unknown sort field '{}', expected date, amount, id or category",
+                    field.trim()
+                ),
+            )
+        })?;
+        match direction.map(str::trim) {
+            None => Ok(Self::asc(field)),
+            Some(d) if d.eq_ignore_ascii_case("asc") => Ok(Self::asc(field)),
+            Some(d) if d.eq_ignore_ascii_case("desc") => Ok(Self::desc(field)),
+            Some(d) => Err(Error::parse(
+                "Sort",
+                format!("unknown sort direction '{}', expected asc or desc", d),
+            )),
+        }
+    }
+}
+
+impl TransactionBatch {
+    /// Sorts the transactions by `keys`, the first key deciding first.
+    ///
+    /// The sort is stable: transactions that are equal by every key keep
+    /// their order, so an empty list of keys leaves the batch as it is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{Format, SortField, SortKey};
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-03\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-01-01\nType: Credit\nAmount: 9 USD\n---\n\
+    ///             ID: TX3\nDate: 2024-01-03\nType: Credit\nAmount: 1 USD\n";
+    /// let mut batch = parser::parse(text.as_bytes(), Format::Text)?;
+    /// batch.sort_by_keys(&[SortKey::desc(SortField::Date), SortKey::asc(SortField::Amount)]);
+    ///
+    /// let ids: Vec<_> = batch.transactions.iter().map(|tx| tx.id.as_str()).collect();
+    /// assert_eq!(ids, ["TX1", "TX3", "TX2"]);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn sort_by_keys(&mut self, keys: &[SortKey]) {
+        self.transactions.sort_by(|a, b| {
+            keys.iter()
+                .map(|key| key.compare(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::collections::BTreeMap;
+    use alloc::string::{String, ToString};
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    fn tx(
+        id: &str,
+        day: u32,
+        kind: TransactionKind,
+        amount: i64,
+        category: Option<&str>,
+    ) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 5, day).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from(amount),
+                currency: "EUR".to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_sort_by_keys() {
+        let mut batch = TransactionBatch {
+            transactions: vec![
+                tx("A", 2, TransactionKind::Credit, 10, Some("Salary")),
+                tx("B", 1, TransactionKind::Debit, 30, Some("Food")),
+                tx("C", 2, TransactionKind::Debit, 5, None),
+                tx("D", 1, TransactionKind::Credit, 30, Some("Food")),
+            ],
+            ..TransactionBatch::default()
+        };
+        let ids = |batch: &TransactionBatch| {
+            batch
+                .transactions
+                .iter()
+                .map(|tx| tx.id.clone())
+                .collect::<Vec<_>>()
+                .join("")
+        };
+
+        batch.sort_by_keys(&[SortKey::asc(SortField::Amount)]);
+        assert_eq!(ids(&batch), "BCAD");
+
+        batch.sort_by_keys(&[
+            SortKey::asc(SortField::Category),
+            SortKey::desc(SortField::Id),
+        ]);
+        assert_eq!(ids(&batch), "CDBA");
+
+        // Stable: B and D are equal by date and keep their order
+        batch.sort_by_keys(&[SortKey::desc(SortField::Date)]);
+        assert_eq!(ids(&batch), "CADB");
+    }
+
+    #[test]
+    fn test_parse_sort_key() {
+        assert_eq!(
+            "date".parse::<SortKey>().unwrap(),
+            SortKey::asc(SortField::Date)
+        );
+        assert_eq!(
+            " ID : DESC ".parse::<SortKey>().unwrap(),
+            SortKey::desc(SortField::Id)
+        );
+        assert!("payee"
+            .parse::<SortKey>()
+            .unwrap_err()
+            .to_string()
+            .contains("unknown sort field 'payee'"));
+        assert!("date:up".parse::<SortKey>().is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Splitting a batch into parts, such as one per month.
+//!
+//! [`TransactionBatch::split_by`] fans a large export out into smaller
+//! batches by a [`SplitKey`], for archiving statements per month or handing
+//! each account its own file.
+
+use crate::{Balance, BatchMetadata, Period, Transaction, TransactionBatch};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chrono::{Datelike, NaiveDate};
+
+/// What [`TransactionBatch::split_by`] groups transactions by.
+///
+/// Each variant names the key its parts are stored under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum SplitKey {
+    /// The month of [`Transaction::posted_at`], as `2024-01`
+    Month,
+    /// The year of [`Transaction::posted_at`], as `2024`
+    Year,
+    /// [`Transaction::account`], or the batch's account ID for
+    /// transactions without one
+    Account,
+    /// [`Transaction::category`]
+    Category,
+    /// The currency of [`Transaction::amount`]
+    Currency,
+}
+
+impl SplitKey {
+    /// Returns the key of the part `tx` belongs to, empty for a transaction
+    /// without the field.
+    fn key(&self, tx: &Transaction, account_id: Option<&str>) -> String {
+        match self {
+            SplitKey::Month => format!("{:04}-{:02}", tx.posted_at.year(), tx.posted_at.month()),
+            SplitKey::Year => format!("{:04}", tx.posted_at.year()),
+            SplitKey::Account => tx
+                .account
+                .as_deref()
+                .filter(|account| !account.is_empty())
+                .or(account_id)
+                .unwrap_or_default()
+                .to_string(),
+            SplitKey::Category => tx.category.clone().unwrap_or_default(),
+            SplitKey::Currency => tx.amount.currency.clone(),
+        }
+    }
+
+    /// Returns the days of the month or year `date` falls in.
+    fn calendar_period(&self, date: NaiveDate) -> Option<Period> {
+        let (start, end) = match self {
+            SplitKey::Month => {
+                let start = date.with_day(1)?;
+                let next = match date.month() {
+                    12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)?,
+                    month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1)?,
+                };
+                (start, next.pred_opt()?)
+            }
+            SplitKey::Year => (
+                NaiveDate::from_ymd_opt(date.year(), 1, 1)?,
+                NaiveDate::from_ymd_opt(date.year(), 12, 31)?,
+            ),
+            _ => return None,
+        };
+        Some(Period { start, end })
+    }
+}
+
+impl TransactionBatch {
+    /// Splits the batch into parts by `key`.
+    ///
+    /// Transactions keep their order within each part. A transaction
+    /// without the field `key` groups by, such as an uncategorized one, goes
+    /// to the part with the empty key.
+    ///
+    /// Every part keeps the batch's metadata, except that a part of a month
+    /// or year covers only the days of the declared period within it. Parts
+    /// by account carry their account as account ID, and parts by currency
+    /// keep the declared balance of their currency; other parts have no
+    /// balances, since the batch's balances don't describe them.
+    ///
+    /// # Returns
+    ///
+    /// The parts, ordered by key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::{Format, SplitKey};
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-02-01\nType: Credit\nAmount: 9 USD\n---\n\
+    ///             ID: TX3\nDate: 2024-01-02\nType: Credit\nAmount: 1 USD\n";
+    /// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+    /// let parts = batch.split_by(SplitKey::Month);
+    ///
+    /// let keys: Vec<_> = parts.keys().map(String::as_str).collect();
+    /// assert_eq!(keys, ["2024-01", "2024-02"]);
+    /// assert_eq!(parts["2024-01"].transactions.len(), 2);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn split_by(&self, key: SplitKey) -> BTreeMap<String, TransactionBatch> {
+        let mut groups: BTreeMap<String, Vec<Transaction>> = BTreeMap::new();
+        for tx in &self.transactions {
+            groups
+                .entry(key.key(tx, self.account_id.as_deref()))
+                .or_default()
+                .push(tx.clone());
+        }
+
+        groups
+            .into_iter()
+            .map(|(name, transactions)| {
+                let period = match (self.metadata.period, transactions.first()) {
+                    (Some(declared), Some(tx)) => match key.calendar_period(tx.posted_at) {
+                        Some(calendar) => Some(Period {
+                            start: declared.start.max(calendar.start),
+                            end: declared.end.min(calendar.end),
+                        })
+                        .filter(|period| period.start <= period.end),
+                        None => Some(declared),
+                    },
+                    (declared, _) => declared,
+                };
+                let balances: Vec<Balance> = match key {
+                    SplitKey::Currency => self
+                        .balances
+                        .iter()
+                        .filter(|balance| balance.currency == name)
+                        .cloned()
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                let account_id = match key {
+                    SplitKey::Account if !name.is_empty() => Some(name.clone()),
+                    _ => self.account_id.clone(),
+                };
+                let part = TransactionBatch {
+                    account_id,
+                    transactions,
+                    balances,
+                    metadata: BatchMetadata {
+                        period,
+                        ..self.metadata.clone()
+                    },
+                };
+                (name, part)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::vec;
+    use rust_decimal::Decimal;
+
+    fn tx(id: &str, date: (i32, u32, u32), currency: &str, account: Option<&str>) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::ONE,
+                currency: currency.to_string(),
+            },
+            description: String::new(),
+            account: account.map(str::to_string),
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn ids(batch: &TransactionBatch) -> String {
+        batch.transactions.iter().map(|tx| tx.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_split_by_month_and_year() {
+        let batch = TransactionBatch {
+            transactions: vec![
+                tx("A", (2023, 12, 31), "EUR", None),
+                tx("B", (2024, 2, 10), "EUR", None),
+                tx("C", (2024, 2, 1), "EUR", None),
+            ],
+            balances: vec![Balance {
+                currency: "EUR".to_string(),
+                opening: Decimal::ZERO,
+                closing: Decimal::ZERO,
+            }],
+            metadata: BatchMetadata {
+                period: Some("2023-12-15..2024-02-20".parse().unwrap()),
+                ..BatchMetadata::default()
+            },
+            ..TransactionBatch::default()
+        };
+
+        let months = batch.split_by(SplitKey::Month);
+        assert_eq!(months.len(), 2);
+        assert_eq!(ids(&months["2023-12"]), "A");
+        assert_eq!(ids(&months["2024-02"]), "BC");
+        assert_eq!(
+            months["2024-02"].metadata.period,
+            Some("2024-02-01..2024-02-20".parse().unwrap())
+        );
+        assert!(months["2024-02"].balances.is_empty());
+
+        let years = batch.split_by(SplitKey::Year);
+        assert_eq!(ids(&years["2024"]), "BC");
+        assert_eq!(
+            years["2023"].metadata.period,
+            Some("2023-12-15..2023-12-31".parse().unwrap())
+        );
+        assert_eq!(batch.split_by(SplitKey::Currency)["EUR"].balances.len(), 1);
+    }
+
+    #[test]
+    fn test_split_by_account() {
+        let batch = TransactionBatch {
+            account_id: Some("MAIN".to_string()),
+            transactions: vec![
+                tx("A", (2024, 1, 1), "EUR", Some("SAVINGS")),
+                tx("B", (2024, 1, 2), "EUR", None),
+                tx("C", (2024, 1, 3), "USD", Some("")),
+            ],
+            ..TransactionBatch::default()
+        };
+
+        let parts = batch.split_by(SplitKey::Account);
+        assert_eq!(ids(&parts["MAIN"]), "BC");
+        assert_eq!(parts["SAVINGS"].account_id.as_deref(), Some("SAVINGS"));
+
+        let parts = batch.split_by(SplitKey::Category);
+        assert_eq!(ids(&parts[""]), "ABC");
+    }
+}
@@ -0,0 +1,629 @@
+//! Statistics over transaction batches.
+//!
+//! These helpers aggregate a batch into the series used by reports and
+//! charts: an overall [`Summary`], totals per month, per category or another
+//! [`GroupKey`], and the running balance. Amounts in different currencies
+//! are never mixed; every result row carries its currency.
+//!
+//! Only booked transactions are counted: pending and cancelled ones haven't
+//! moved any money, so every function here leaves them out, as
+//! [`crate::balance::check_balances`] does.
+
+use crate::{Period, Transaction, TransactionBatch};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Credits and debits for one calendar month in one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonthlyTotal {
+    /// Calendar year
+    pub year: i32,
+    /// Calendar month, 1-12
+    pub month: u32,
+    /// The currency code
+    pub currency: String,
+    /// Sum of credits posted in the month
+    pub credits: Decimal,
+    /// Sum of debits posted in the month
+    pub debits: Decimal,
+}
+
+/// Credits and debits for one category in one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryTotal {
+    /// Category name; transactions without one are grouped as `Uncategorized`
+    pub category: String,
+    /// The currency code
+    pub currency: String,
+    /// Sum of credits in the category
+    pub credits: Decimal,
+    /// Sum of debits in the category
+    pub debits: Decimal,
+}
+
+/// Running balance at the end of a posting date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalancePoint {
+    /// Posting date
+    pub date: NaiveDate,
+    /// The currency code
+    pub currency: String,
+    /// Cumulative credits minus debits up to and including `date`
+    pub balance: Decimal,
+}
+
+/// Overview of a batch, as returned by [`TransactionBatch::summary`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Summary {
+    /// Number of booked transactions
+    pub count: usize,
+    /// Earliest posting date, `None` without booked transactions
+    pub first_date: Option<NaiveDate>,
+    /// Latest posting date, `None` without booked transactions
+    pub last_date: Option<NaiveDate>,
+    /// Totals per currency, ordered by currency code
+    pub currencies: Vec<CurrencySummary>,
+}
+
+/// The transactions of a batch in one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CurrencySummary {
+    /// The currency code
+    pub currency: String,
+    /// Number of transactions in the currency
+    pub count: usize,
+    /// Sum of credits
+    pub credits: Decimal,
+    /// Sum of debits
+    pub debits: Decimal,
+    /// Credits minus debits
+    pub net: Decimal,
+    /// The credit with the largest amount, the first of them on a tie
+    pub largest_credit: Option<Transaction>,
+    /// The debit with the largest amount, the first of them on a tie
+    pub largest_debit: Option<Transaction>,
+}
+
+/// What [`TransactionBatch::group_by`] groups transactions by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum GroupKey {
+    /// [`Transaction::category`]; transactions without one are grouped as
+    /// `Uncategorized`
+    Category,
+    /// [`Transaction::counterparty`]; transactions without one are grouped
+    /// as `Unknown`
+    Counterparty,
+    /// The month of [`Transaction::posted_at`], as `2024-01`
+    Month,
+}
+
+/// Totals of one group in one currency, as returned by
+/// [`TransactionBatch::group_by`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupTotal {
+    /// The group, such as a category name or `2024-01`
+    pub key: String,
+    /// The currency code
+    pub currency: String,
+    /// Number of transactions in the group and currency
+    pub count: usize,
+    /// Sum of credits
+    pub credits: Decimal,
+    /// Sum of debits
+    pub debits: Decimal,
+    /// Credits minus debits
+    pub net: Decimal,
+}
+
+/// The length of the buckets of [`TransactionBatch::bucket_by`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumString, strum::Display)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Interval {
+    /// One calendar day
+    Day,
+    /// Monday to Sunday, as ISO 8601 weeks
+    Week,
+    /// One calendar month
+    Month,
+}
+
+impl Interval {
+    /// Returns the bucket `date` falls in.
+    pub fn bucket(&self, date: NaiveDate) -> Period {
+        let start = match self {
+            Interval::Day => date,
+            Interval::Week => date.week(Weekday::Mon).first_day(),
+            Interval::Month => date.with_day(1).expect("every month has a first day"),
+        };
+        let next = match self {
+            Interval::Day => start.succ_opt(),
+            Interval::Week => start.checked_add_days(Days::new(7)),
+            Interval::Month => start.checked_add_months(Months::new(1)),
+        };
+        Period {
+            start,
+            end: next
+                .and_then(|next| next.pred_opt())
+                .unwrap_or(NaiveDate::MAX),
+        }
+    }
+}
+
+/// Totals of one bucket in one currency, as returned by
+/// [`TransactionBatch::bucket_by`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bucket {
+    /// The days of the bucket
+    pub period: Period,
+    /// The currency code
+    pub currency: String,
+    /// Number of transactions posted in the bucket
+    pub count: usize,
+    /// Sum of credits
+    pub credits: Decimal,
+    /// Sum of debits
+    pub debits: Decimal,
+    /// Credits minus debits
+    pub net: Decimal,
+}
+
+impl TransactionBatch {
+    /// Summarizes the batch: how many transactions it has, the dates they
+    /// span, and per currency the credit and debit totals and the largest
+    /// credit and debit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::Format;
+    /// use rust_decimal::Decimal;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-03\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-01-09\nType: Credit\nAmount: 9 USD\n---\n\
+    ///             ID: TX3\nDate: 2024-01-05\nType: Debit\nAmount: 7 USD\n";
+    /// let summary = parser::parse(text.as_bytes(), Format::Text)?.summary();
+    ///
+    /// assert_eq!(summary.count, 3);
+    /// assert_eq!(summary.last_date.unwrap().to_string(), "2024-01-09");
+    /// let usd = &summary.currencies[0];
+    /// assert_eq!(usd.net, Decimal::from(-3));
+    /// assert_eq!(usd.largest_debit.as_ref().unwrap().id, "TX3");
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn summary(&self) -> Summary {
+        let mut currencies: BTreeMap<&str, CurrencySummary> = BTreeMap::new();
+        let mut count = 0;
+        for tx in booked(&self.transactions) {
+            count += 1;
+            let summary = currencies
+                .entry(tx.amount.currency.as_str())
+                .or_insert_with(|| CurrencySummary {
+                    currency: tx.amount.currency.clone(),
+                    count: 0,
+                    credits: Decimal::ZERO,
+                    debits: Decimal::ZERO,
+                    net: Decimal::ZERO,
+                    largest_credit: None,
+                    largest_debit: None,
+                });
+            summary.count += 1;
+            summary.net += tx.signed_amount();
+            let (total, largest) = if tx.is_credit() {
+                (&mut summary.credits, &mut summary.largest_credit)
+            } else {
+                (&mut summary.debits, &mut summary.largest_debit)
+            };
+            *total += tx.unsigned_amount();
+            if largest
+                .as_ref()
+                .is_none_or(|largest| tx.unsigned_amount() > largest.unsigned_amount())
+            {
+                *largest = Some(tx.clone());
+            }
+        }
+
+        let dates = booked(&self.transactions).map(|tx| tx.posted_at);
+        Summary {
+            count,
+            first_date: dates.clone().min(),
+            last_date: dates.max(),
+            currencies: currencies.into_values().collect(),
+        }
+    }
+
+    /// Sums credits and debits per group and currency, ordered by group then
+    /// currency.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::stats::GroupKey;
+    /// use parser::Format;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-02-01\nType: Debit\nAmount: 9 USD\n---\n\
+    ///             ID: TX3\nDate: 2024-02-05\nType: Credit\nAmount: 1 USD\n";
+    /// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+    ///
+    /// let months = batch.group_by(GroupKey::Month);
+    /// assert_eq!(months[1].key, "2024-02");
+    /// assert_eq!((months[1].count, months[1].net.to_string()), (2, "-8".to_string()));
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn group_by(&self, key: GroupKey) -> Vec<GroupTotal> {
+        let mut totals: BTreeMap<(String, &str), GroupTotal> = BTreeMap::new();
+        for tx in booked(&self.transactions) {
+            let group = match key {
+                GroupKey::Category => tx
+                    .category
+                    .as_deref()
+                    .unwrap_or("Uncategorized")
+                    .to_string(),
+                GroupKey::Counterparty => {
+                    tx.counterparty.as_deref().unwrap_or("Unknown").to_string()
+                }
+                GroupKey::Month => {
+                    format!("{:04}-{:02}", tx.posted_at.year(), tx.posted_at.month())
+                }
+            };
+            let total = totals
+                .entry((group.clone(), tx.amount.currency.as_str()))
+                .or_insert_with(|| GroupTotal {
+                    key: group,
+                    currency: tx.amount.currency.clone(),
+                    count: 0,
+                    credits: Decimal::ZERO,
+                    debits: Decimal::ZERO,
+                    net: Decimal::ZERO,
+                });
+            total.count += 1;
+            total.net += tx.signed_amount();
+            if tx.is_credit() {
+                total.credits += tx.unsigned_amount();
+            } else {
+                total.debits += tx.unsigned_amount();
+            }
+        }
+
+        totals.into_values().collect()
+    }
+
+    /// Sums credits and debits per day, week or month of posting, ordered
+    /// by bucket then currency.
+    ///
+    /// Every currency gets a bucket for each interval from the first to the
+    /// last posting date of the batch, with zero totals where it has no
+    /// transactions, so that the buckets can be charted as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use parser::stats::Interval;
+    /// use parser::Format;
+    ///
+    /// let text = "ID: TX1\nDate: 2024-01-31\nType: Debit\nAmount: 5 USD\n---\n\
+    ///             ID: TX2\nDate: 2024-03-01\nType: Credit\nAmount: 9 USD\n";
+    /// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+    ///
+    /// let buckets = batch.bucket_by(Interval::Month);
+    /// let months: Vec<_> = buckets.iter().map(|b| b.period.to_string()).collect();
+    /// assert_eq!(
+    ///     months,
+    ///     ["2024-01-01..2024-01-31", "2024-02-01..2024-02-29", "2024-03-01..2024-03-31"]
+    /// );
+    /// assert_eq!(buckets[1].count, 0);
+    /// # Ok::<(), parser::Error>(())
+    /// ```
+    pub fn bucket_by(&self, interval: Interval) -> Vec<Bucket> {
+        let dates = booked(&self.transactions).map(|tx| tx.posted_at);
+        let (Some(first), Some(last)) = (dates.clone().min(), dates.max()) else {
+            return Vec::new();
+        };
+
+        let mut buckets: BTreeMap<(NaiveDate, &str), Bucket> = BTreeMap::new();
+        let currencies: BTreeSet<&str> = booked(&self.transactions)
+            .map(|tx| tx.amount.currency.as_str())
+            .collect();
+        let mut period = interval.bucket(first);
+        loop {
+            for &currency in &currencies {
+                buckets.insert(
+                    (period.start, currency),
+                    Bucket {
+                        period,
+                        currency: currency.to_string(),
+                        count: 0,
+                        credits: Decimal::ZERO,
+                        debits: Decimal::ZERO,
+                        net: Decimal::ZERO,
+                    },
+                );
+            }
+            match period.end.succ_opt() {
+                Some(next) if period.end < last => period = interval.bucket(next),
+                _ => break,
+            }
+        }
+
+        for tx in booked(&self.transactions) {
+            let start = interval.bucket(tx.posted_at).start;
+            if let Some(bucket) = buckets.get_mut(&(start, tx.amount.currency.as_str())) {
+                bucket.count += 1;
+                bucket.net += tx.signed_amount();
+                if tx.is_credit() {
+                    bucket.credits += tx.unsigned_amount();
+                } else {
+                    bucket.debits += tx.unsigned_amount();
+                }
+            }
+        }
+
+        buckets.into_values().collect()
+    }
+}
+
+/// Returns credits minus debits for a single transaction.
+pub fn signed_amount(tx: &Transaction) -> Decimal {
+    tx.signed_amount()
+}
+
+/// Sums credits and debits per posting month, ordered by month then currency.
+pub fn monthly_totals(batch: &TransactionBatch) -> Vec<MonthlyTotal> {
+    let mut totals: BTreeMap<(i32, u32, &str), (Decimal, Decimal)> = BTreeMap::new();
+
+    for tx in booked(&batch.transactions) {
+        let key = (
+            tx.posted_at.year(),
+            tx.posted_at.month(),
+            tx.amount.currency.as_str(),
+        );
+        add(totals.entry(key).or_default(), tx);
+    }
+
+    totals
+        .into_iter()
+        .map(
+            |((year, month, currency), (credits, debits))| MonthlyTotal {
+                year,
+                month,
+                currency: currency.to_string(),
+                credits,
+                debits,
+            },
+        )
+        .collect()
+}
+
+/// Sums credits and debits per category, ordered by category then currency.
+pub fn category_totals(batch: &TransactionBatch) -> Vec<CategoryTotal> {
+    let mut totals: BTreeMap<(&str, &str), (Decimal, Decimal)> = BTreeMap::new();
+
+    for tx in booked(&batch.transactions) {
+        let key = (
+            tx.category.as_deref().unwrap_or("Uncategorized"),
+            tx.amount.currency.as_str(),
+        );
+        add(totals.entry(key).or_default(), tx);
+    }
+
+    totals
+        .into_iter()
+        .map(|((category, currency), (credits, debits))| CategoryTotal {
+            category: category.to_string(),
+            currency: currency.to_string(),
+            credits,
+            debits,
+        })
+        .collect()
+}
+
+/// Computes the running balance per currency, starting from zero.
+///
+/// Returns one point per currency and posting date that has transactions,
+/// ordered by date then currency.
+pub fn balance_over_time(batch: &TransactionBatch) -> Vec<BalancePoint> {
+    let mut daily: BTreeMap<(NaiveDate, &str), Decimal> = BTreeMap::new();
+    for tx in booked(&batch.transactions) {
+        *daily
+            .entry((tx.posted_at, tx.amount.currency.as_str()))
+            .or_default() += signed_amount(tx);
+    }
+
+    let mut running: BTreeMap<&str, Decimal> = BTreeMap::new();
+    daily
+        .into_iter()
+        .map(|((date, currency), net)| {
+            let balance = running.entry(currency).or_default();
+            *balance += net;
+            BalancePoint {
+                date,
+                currency: currency.to_string(),
+                balance: *balance,
+            }
+        })
+        .collect()
+}
+
+/// The transactions that count towards the statistics.
+fn booked(transactions: &[Transaction]) -> impl Iterator<Item = &Transaction> + Clone {
+    transactions.iter().filter(|tx| tx.status.is_booked())
+}
+
+fn add(totals: &mut (Decimal, Decimal), tx: &Transaction) {
+    if tx.is_credit() {
+        totals.0 += tx.unsigned_amount();
+    } else {
+        totals.1 += tx.unsigned_amount();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use std::str::FromStr;
+
+    fn tx(date: &str, kind: TransactionKind, amount: &str, category: Option<&str>) -> Transaction {
+        Transaction {
+            id: format!("TX-{}", date),
+            posted_at: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from_str(amount).unwrap(),
+                currency: "USD".to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn sample_batch() -> TransactionBatch {
+        TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx(
+                    "2024-01-31",
+                    TransactionKind::Credit,
+                    "1000",
+                    Some("Salary"),
+                ),
+                tx("2024-01-31", TransactionKind::Debit, "50", Some("Food")),
+                tx("2024-02-01", TransactionKind::Debit, "20.50", Some("Food")),
+                tx("2024-02-03", TransactionKind::Debit, "5", None),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_monthly_totals() {
+        let totals = monthly_totals(&sample_batch());
+
+        assert_eq!(totals.len(), 2);
+        assert_eq!((totals[0].year, totals[0].month), (2024, 1));
+        assert_eq!(totals[0].credits, Decimal::from(1000));
+        assert_eq!(totals[0].debits, Decimal::from(50));
+        assert_eq!(totals[1].month, 2);
+        assert_eq!(totals[1].debits, Decimal::from_str("25.50").unwrap());
+    }
+
+    #[test]
+    fn test_category_totals() {
+        let totals = category_totals(&sample_batch());
+
+        let names: Vec<&str> = totals.iter().map(|t| t.category.as_str()).collect();
+        assert_eq!(names, vec!["Food", "Salary", "Uncategorized"]);
+        assert_eq!(totals[0].debits, Decimal::from_str("70.50").unwrap());
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut batch = sample_batch();
+        batch.transactions[3].amount.currency = "EUR".to_string();
+        let summary = batch.summary();
+
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.first_date, NaiveDate::from_ymd_opt(2024, 1, 31));
+        assert_eq!(summary.last_date, NaiveDate::from_ymd_opt(2024, 2, 3));
+        let codes: Vec<&str> = summary
+            .currencies
+            .iter()
+            .map(|c| c.currency.as_str())
+            .collect();
+        assert_eq!(codes, vec!["EUR", "USD"]);
+
+        let usd = &summary.currencies[1];
+        assert_eq!(usd.count, 3);
+        assert_eq!(usd.debits, Decimal::from_str("70.50").unwrap());
+        assert_eq!(usd.net, Decimal::from_str("929.50").unwrap());
+        assert_eq!(
+            usd.largest_debit.as_ref().unwrap().amount.amount,
+            Decimal::from(50)
+        );
+        assert!(summary.currencies[0].largest_credit.is_none());
+
+        assert_eq!(TransactionBatch::default().summary(), Summary::default());
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut batch = sample_batch();
+        batch.transactions[0].counterparty = Some("Employer".to_string());
+
+        let groups = batch.group_by(GroupKey::Counterparty);
+        let keys: Vec<(&str, usize)> = groups.iter().map(|g| (g.key.as_str(), g.count)).collect();
+        assert_eq!(keys, vec![("Employer", 1), ("Unknown", 3)]);
+
+        let categories = batch.group_by(GroupKey::Category);
+        assert_eq!(categories.len(), category_totals(&batch).len());
+        assert_eq!(categories[0].net, Decimal::from_str("-70.50").unwrap());
+    }
+
+    #[test]
+    fn test_bucket_by() {
+        let batch = sample_batch();
+
+        let weeks = batch.bucket_by(Interval::Week);
+        let periods: Vec<String> = weeks.iter().map(|b| b.period.to_string()).collect();
+        assert_eq!(
+            periods,
+            vec!["2024-01-29..2024-02-04"],
+            "Jan 31 to Feb 3 2024 is one ISO week"
+        );
+        assert_eq!(weeks[0].count, 4);
+        assert_eq!(weeks[0].net, Decimal::from_str("924.50").unwrap());
+
+        let days = batch.bucket_by(Interval::Day);
+        let counts: Vec<usize> = days.iter().map(|b| b.count).collect();
+        assert_eq!(counts, vec![2, 1, 0, 1]);
+
+        let months = batch.bucket_by(Interval::Month);
+        assert_eq!(months[1].period, "2024-02-01..2024-02-29".parse().unwrap());
+        assert_eq!(months[1].debits, Decimal::from_str("25.50").unwrap());
+        assert!(TransactionBatch::default()
+            .bucket_by(Interval::Day)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_non_booked_transactions_are_left_out() {
+        let booked = sample_batch();
+        let mut batch = booked.clone();
+        let mut cancelled = tx("2024-03-15", TransactionKind::Debit, "500", Some("Food"));
+        cancelled.status = TransactionStatus::Cancelled;
+        let mut pending = tx("2024-02-01", TransactionKind::Credit, "75", None);
+        pending.status = TransactionStatus::Pending;
+        batch.transactions.extend([cancelled, pending]);
+
+        assert_eq!(batch.summary(), booked.summary());
+        assert_eq!(
+            batch.group_by(GroupKey::Category),
+            booked.group_by(GroupKey::Category)
+        );
+        assert_eq!(
+            batch.bucket_by(Interval::Month),
+            booked.bucket_by(Interval::Month)
+        );
+        assert_eq!(monthly_totals(&batch), monthly_totals(&booked));
+        assert_eq!(category_totals(&batch), category_totals(&booked));
+        assert_eq!(balance_over_time(&batch), balance_over_time(&booked));
+    }
+
+    #[test]
+    fn test_balance_over_time() {
+        let points = balance_over_time(&sample_batch());
+
+        let balances: Vec<String> = points.iter().map(|p| p.balance.to_string()).collect();
+        assert_eq!(balances, vec!["950", "929.50", "924.50"]);
+    }
+}
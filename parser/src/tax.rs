@@ -0,0 +1,296 @@
+//! VAT and other sales-tax extraction.
+//!
+//! Bank statements rarely carry the tax split as data; it is usually buried
+//! in the payment purpose, e.g. `Оплата по счету 15, в т.ч. НДС 20% - 166.67`
+//! or `Invoice 42, incl. VAT 20%: 16.67`. [`parse_tax`] recovers the rate and
+//! amount from such descriptions, and [`apply_tax`] fills
+//! [`Transaction::tax_amount`] and [`Transaction::tax_rate`] for a whole
+//! batch, falling back to configured [`TaxRates`] when a description says
+//! nothing.
+
+use crate::{Transaction, TransactionBatch};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Words that introduce a tax mention, lowercase.
+const TAX_KEYWORDS: [&str; 3] = ["ндс", "vat", "gst"];
+
+/// Phrases marking a payment as exempt from tax, lowercase.
+const EXEMPT_PHRASES: [&str; 5] = [
+    "без ндс",
+    "ндс не облагается",
+    "не облагается ндс",
+    "vat exempt",
+    "no vat",
+];
+
+/// Tax details recovered from a description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TaxInfo {
+    /// Tax rate in percent, if stated
+    pub rate: Option<Decimal>,
+    /// Tax amount, if stated
+    pub amount: Option<Decimal>,
+}
+
+/// Rates used for transactions whose description doesn't mention tax.
+///
+/// A category rate wins over the default. With neither, the transaction is
+/// left without tax.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TaxRates {
+    /// Rate in percent applied to every transaction without a category rate
+    pub default: Option<Decimal>,
+    /// Rates in percent by [`Transaction::category`]
+    pub by_category: BTreeMap<String, Decimal>,
+}
+
+impl TaxRates {
+    /// Returns the configured rate for a transaction.
+    pub fn rate_for(&self, tx: &Transaction) -> Option<Decimal> {
+        tx.category
+            .as_ref()
+            .and_then(|category| self.by_category.get(category))
+            .copied()
+            .or(self.default)
+    }
+}
+
+/// Extracts tax information from a payment description.
+///
+/// Recognizes a tax keyword (`НДС`, `VAT`, `GST`, any case) followed by an
+/// optional rate with a `%` sign and an optional amount, in either order;
+/// both `.` and `,` are accepted as decimal separators. Exemption phrases
+/// such as `Без НДС` or `НДС не облагается` yield a zero rate and amount.
+///
+/// # Returns
+///
+/// Returns `None` if the description doesn't mention tax.
+///
+/// # Examples
+///
+/// ```
+/// use parser::tax::parse_tax;
+/// use rust_decimal::Decimal;
+///
+/// let info = parse_tax("Оплата по счету 15, в т.ч. НДС 20% - 166.67").unwrap();
+/// assert_eq!(info.rate, Some(Decimal::from(20)));
+/// assert_eq!(info.amount, Some(Decimal::new(16667, 2)));
+/// ```
+pub fn parse_tax(description: &str) -> Option<TaxInfo> {
+    let lower = description.to_lowercase();
+
+    if EXEMPT_PHRASES.iter().any(|phrase| lower.contains(phrase)) {
+        return Some(TaxInfo {
+            rate: Some(Decimal::ZERO),
+            amount: Some(Decimal::ZERO),
+        });
+    }
+
+    let start = TAX_KEYWORDS
+        .iter()
+        .filter_map(|keyword| lower.find(keyword).map(|i| i + keyword.len()))
+        .min()?;
+
+    let mut info = TaxInfo::default();
+    let mut rest = &lower[start..];
+    while info.rate.is_none() || info.amount.is_none() {
+        let Some((value, is_rate, tail)) = next_number(rest) else {
+            break;
+        };
+        if is_rate {
+            info.rate = info.rate.or(Some(value));
+        } else {
+            info.amount = info.amount.or(Some(value));
+        }
+        rest = tail;
+    }
+
+    Some(info)
+}
+
+/// Computes the tax included in a gross amount at `rate` percent, rounded to
+/// two decimal places.
+///
+/// # Examples
+///
+/// ```
+/// use parser::tax::included_tax;
+/// use rust_decimal::Decimal;
+///
+/// assert_eq!(included_tax(Decimal::new(100000, 2), Decimal::from(20)), Decimal::new(16667, 2));
+/// ```
+pub fn included_tax(gross: Decimal, rate: Decimal) -> Decimal {
+    let hundred = Decimal::ONE_HUNDRED;
+    (gross * rate / (hundred + rate))
+        .round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Fills the tax fields of every transaction in a batch.
+///
+/// Fields that are already set are kept. Otherwise the description is
+/// searched with [`parse_tax`]; a stated rate without an amount is turned
+/// into an amount with [`included_tax`]. Transactions whose description says
+/// nothing use the rate from `rates`, if any.
+///
+/// # Returns
+///
+/// Returns the number of transactions that were changed.
+pub fn apply_tax(batch: &mut TransactionBatch, rates: &TaxRates) -> usize {
+    let mut changed = 0;
+
+    for tx in &mut batch.transactions {
+        if tx.tax_amount.is_some() {
+            continue;
+        }
+
+        let info = parse_tax(&tx.description).unwrap_or(TaxInfo {
+            rate: rates.rate_for(tx),
+            amount: None,
+        });
+        let rate = tx.tax_rate.or(info.rate);
+        let amount = info
+            .amount
+            .or_else(|| rate.map(|rate| included_tax(tx.amount.amount, rate)));
+
+        if amount.is_some() || rate != tx.tax_rate {
+            tx.tax_amount = amount;
+            tx.tax_rate = rate;
+            changed += 1;
+        }
+    }
+
+    changed
+}
+
+/// Finds the next number in `text`.
+///
+/// Digit groups separated by a space (`1 000,50`) are joined. Returns the
+/// value, whether it is a percentage, and the text after it.
+fn next_number(text: &str) -> Option<(Decimal, bool, &str)> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits = &text[start..];
+
+    let mut number = String::new();
+    let mut end = 0;
+    for (i, c) in digits.char_indices() {
+        if c.is_ascii_digit() {
+            number.push(c);
+        } else if c == '.' || c == ',' {
+            number.push('.');
+        } else if (c == ' ' || c == '\u{a0}') && is_digit_group(&digits[i + c.len_utf8()..]) {
+            // Thousands separator
+        } else {
+            break;
+        }
+        end = i + c.len_utf8();
+    }
+
+    // A trailing separator ends the sentence rather than the number.
+    if number.ends_with('.') {
+        number.pop();
+        end -= 1;
+    }
+
+    let value = number.parse::<Decimal>().ok()?;
+    let tail = &digits[end..];
+    let is_rate = tail.trim_start().starts_with('%');
+    Some((value, is_rate, tail))
+}
+
+/// Returns `true` if `text` starts with exactly three digits.
+fn is_digit_group(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 3
+        && bytes[..3].iter().all(u8::is_ascii_digit)
+        && !bytes.get(3).is_some_and(u8::is_ascii_digit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, TransactionKind, TransactionStatus};
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use chrono::NaiveDate;
+
+    fn tx(description: &str, amount: i64, category: Option<&str>) -> Transaction {
+        Transaction {
+            id: "TX1".to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            executed_at: None,
+            kind: TransactionKind::Debit,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(amount, 2),
+                currency: "RUB".to_string(),
+            },
+            description: description.to_string(),
+            account: None,
+            counterparty: None,
+            category: category.map(str::to_string),
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_tax_descriptions() {
+        let info = parse_tax("Оплата по договору 7. В том числе НДС (20 %) 1 000,50 руб.").unwrap();
+        assert_eq!(info.rate, Some(Decimal::from(20)));
+        assert_eq!(info.amount, Some(Decimal::new(100050, 2)));
+
+        let info = parse_tax("Invoice 42, VAT 16.67 at 20%").unwrap();
+        assert_eq!(info.rate, Some(Decimal::from(20)));
+        assert_eq!(info.amount, Some(Decimal::new(1667, 2)));
+
+        let info = parse_tax("Услуги связи, НДС 10%.").unwrap();
+        assert_eq!(info.rate, Some(Decimal::from(10)));
+        assert_eq!(info.amount, None);
+
+        let info = parse_tax("Перевод средств. Без НДС").unwrap();
+        assert_eq!(info.amount, Some(Decimal::ZERO));
+
+        assert_eq!(parse_tax("Salary for March"), None);
+    }
+
+    #[test]
+    fn test_apply_tax() {
+        let mut batch = TransactionBatch {
+            account_id: None,
+            transactions: vec![
+                tx("Счет 15, в т.ч. НДС 20% - 166.67", 100000, None),
+                tx("Услуги связи, НДС 10%", 11000, None),
+                tx("Groceries", 12000, Some("Food")),
+                tx("Transfer", 5000, None),
+            ],
+            balances: Vec::new(),
+            metadata: Default::default(),
+        };
+        let mut rates = TaxRates::default();
+        rates
+            .by_category
+            .insert("Food".to_string(), Decimal::from(10));
+
+        assert_eq!(apply_tax(&mut batch, &rates), 3);
+
+        let tax: vec::Vec<_> = batch
+            .transactions
+            .iter()
+            .map(|tx| (tx.tax_rate, tx.tax_amount))
+            .collect();
+        assert_eq!(
+            tax,
+            vec![
+                (Some(Decimal::from(20)), Some(Decimal::new(16667, 2))),
+                (Some(Decimal::from(10)), Some(Decimal::new(1000, 2))),
+                (Some(Decimal::from(10)), Some(Decimal::new(1091, 2))),
+                (None, None),
+            ]
+        );
+        assert_eq!(apply_tax(&mut batch, &rates), 0);
+    }
+}
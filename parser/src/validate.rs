@@ -0,0 +1,489 @@
+//! Checking that parsed batches make sense.
+//!
+//! Parsing only checks that the input is well-formed: a transaction with an
+//! empty ID, a negative debit or the currency `UDS` parses fine. A
+//! [`Validator`] runs [`ValidationRule`]s over a parsed batch and collects
+//! what they find in a [`ValidationReport`]; [`validate`] runs the built-in
+//! rules:
+//!
+//! | Rule | Name | Severity |
+//! |------|------|----------|
+//! | [`NonEmptyId`] | `non_empty_id` | error |
+//! | [`KnownCurrency`] | `known_currency` | error |
+//! | [`NonNegativeAmount`] | `non_negative_amount` | error |
+//! | [`ExecutedBeforePosted`] | `executed_before_posted` | warning |
+//! | [`UniqueIds`] | `unique_ids` | error |
+//!
+//! Rules of your own implement [`ValidationRule`] and are added with
+//! [`Validator::rule`]; [`Validator::severity`] changes how seriously a
+//! rule's issues are taken.
+
+use crate::currency::is_iso_4217;
+use crate::{Transaction, TransactionBatch};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use rust_decimal::Decimal;
+
+/// How serious a [`ValidationIssue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Severity {
+    /// Suspicious, but possibly correct
+    Warning,
+    /// The batch is wrong
+    Error,
+}
+
+/// A problem a [`ValidationRule`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// The [name](ValidationRule::name) of the rule
+    pub rule: &'static str,
+    /// How serious the issue is
+    pub severity: Severity,
+    /// Position of the transaction in the batch, from 0, or `None` for an
+    /// issue with the batch as a whole
+    pub index: Option<usize>,
+    /// What is wrong
+    pub message: String,
+}
+
+impl ValidationIssue {
+    /// Creates an issue with the transaction at `index`.
+    pub fn at(rule: &'static str, severity: Severity, index: usize, message: String) -> Self {
+        Self {
+            rule,
+            severity,
+            index: Some(index),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    /// Writes the issue as `error [unique_ids]: record 3: ...`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]: ", self.severity, self.rule)?;
+        if let Some(index) = self.index {
+            write!(f, "record {}: ", index + 1)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// The issues found by [`Validator::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationReport {
+    /// The issues, ordered by record, with issues of the whole batch first
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Returns `true` if no issue is an error; warnings are allowed.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// The issues with [`Severity::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    /// The issues with [`Severity::Warning`].
+    pub fn warnings(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+}
+
+/// A check of a batch's contents.
+///
+/// # Examples
+///
+/// ```
+/// use parser::validate::{Severity, ValidationIssue, ValidationRule, Validator};
+/// use parser::{Format, TransactionBatch};
+///
+/// /// Flags transactions without a category.
+/// struct Categorized;
+///
+/// impl ValidationRule for Categorized {
+///     fn name(&self) -> &'static str {
+///         "categorized"
+///     }
+///
+///     fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>) {
+///         for (index, tx) in batch.transactions.iter().enumerate() {
+///             if tx.category.is_none() {
+///                 let message = format!("transaction {} has no category", tx.id);
+///                 issues.push(ValidationIssue::at(self.name(), Severity::Warning, index, message));
+///             }
+///         }
+///     }
+/// }
+///
+/// let text = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: 10 EUR\n";
+/// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+///
+/// let report = Validator::default().rule(Categorized).validate(&batch);
+/// assert!(report.is_valid());
+/// assert_eq!(
+///     report.issues[0].to_string(),
+///     "warning [categorized]: record 1: transaction TX1 has no category"
+/// );
+/// # Ok::<(), parser::Error>(())
+/// ```
+pub trait ValidationRule {
+    /// A short, stable name for the rule, such as `"unique_ids"`.
+    fn name(&self) -> &'static str;
+
+    /// Adds an issue to `issues` for every problem with `batch`.
+    fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>);
+}
+
+/// Runs a set of [`ValidationRule`]s.
+///
+/// [`Validator::default`] has the built-in rules listed in the
+/// [module documentation](mod@crate::validate), and [`Validator::empty`] none.
+pub struct Validator {
+    rules: Vec<Box<dyn ValidationRule>>,
+    severities: BTreeMap<&'static str, Severity>,
+}
+
+impl Default for Validator {
+    fn default() -> Self {
+        Self::empty()
+            .rule(NonEmptyId)
+            .rule(KnownCurrency::default())
+            .rule(NonNegativeAmount)
+            .rule(ExecutedBeforePosted)
+            .rule(UniqueIds)
+    }
+}
+
+impl Validator {
+    /// Creates a validator without rules.
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            severities: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a rule, run after the rules added before it, or replaces the
+    /// rule with the same name, such as a built-in rule configured
+    /// differently.
+    pub fn rule(mut self, rule: impl ValidationRule + 'static) -> Self {
+        match self.rules.iter().position(|r| r.name() == rule.name()) {
+            Some(index) => self.rules[index] = Box::new(rule),
+            None => self.rules.push(Box::new(rule)),
+        }
+        self
+    }
+
+    /// Reports every issue of the rule named `rule` with `severity`, such as
+    /// [`Severity::Warning`] for duplicate IDs a source is known to have.
+    pub fn severity(mut self, rule: &'static str, severity: Severity) -> Self {
+        self.severities.insert(rule, severity);
+        self
+    }
+
+    /// Runs the rules over `batch`.
+    pub fn validate(&self, batch: &TransactionBatch) -> ValidationReport {
+        let mut issues = Vec::new();
+        for rule in &self.rules {
+            rule.check(batch, &mut issues);
+        }
+        for issue in &mut issues {
+            if let Some(&severity) = self.severities.get(issue.rule) {
+                issue.severity = severity;
+            }
+        }
+        issues.sort_by_key(|issue| issue.index);
+        ValidationReport { issues }
+    }
+}
+
+/// Checks `batch` with the built-in rules.
+///
+/// This checks the contents of a parsed batch; [`crate::validate_input`] checks
+/// that input parses.
+///
+/// # Examples
+///
+/// ```
+/// use parser::Format;
+///
+/// let text = "ID: TX1\nDate: 2024-01-15\nType: Debit\nAmount: -10 UDS\n";
+/// let batch = parser::parse(text.as_bytes(), Format::Text)?;
+///
+/// let report = parser::validate::validate(&batch);
+/// assert!(!report.is_valid());
+/// let rules: Vec<_> = report.errors().map(|issue| issue.rule).collect();
+/// assert_eq!(rules, ["known_currency", "non_negative_amount"]);
+/// # Ok::<(), parser::Error>(())
+/// ```
+pub fn validate(batch: &TransactionBatch) -> ValidationReport {
+    Validator::default().validate(batch)
+}
+
+/// Runs `check` on every transaction and reports the messages it returns
+/// with `severity`.
+fn check_each(
+    rule: &'static str,
+    severity: Severity,
+    batch: &TransactionBatch,
+    issues: &mut Vec<ValidationIssue>,
+    check: impl Fn(&Transaction) -> Option<String>,
+) {
+    for (index, tx) in batch.transactions.iter().enumerate() {
+        if let Some(message) = check(tx) {
+            issues.push(ValidationIssue::at(rule, severity, index, message));
+        }
+    }
+}
+
+/// Transactions need an ID that isn't blank.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonEmptyId;
+
+impl ValidationRule for NonEmptyId {
+    fn name(&self) -> &'static str {
+        "non_empty_id"
+    }
+
+    fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>) {
+        check_each(self.name(), Severity::Error, batch, issues, |tx| {
+            tx.id
+                .trim()
+                .is_empty()
+                .then(|| String::from("transaction has no ID"))
+        });
+    }
+}
+
+/// Currencies of amounts and balances need to be ISO 4217 codes, or one of
+/// `custom`.
+#[derive(Debug, Clone, Default)]
+pub struct KnownCurrency {
+    /// Codes accepted besides ISO 4217 ones, such as internal
+    /// pseudo-currencies
+    pub custom: Vec<String>,
+}
+
+impl KnownCurrency {
+    fn is_known(&self, code: &str) -> bool {
+        is_iso_4217(code) || self.custom.iter().any(|custom| custom == code)
+    }
+}
+
+impl ValidationRule for KnownCurrency {
+    fn name(&self) -> &'static str {
+        "known_currency"
+    }
+
+    fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>) {
+        for balance in &batch.balances {
+            if !self.is_known(&balance.currency) {
+                issues.push(ValidationIssue {
+                    rule: self.name(),
+                    severity: Severity::Error,
+                    index: None,
+                    message: format!("balance has unknown currency '{}'", balance.currency),
+                });
+            }
+        }
+        check_each(self.name(), Severity::Error, batch, issues, |tx| {
+            (!self.is_known(&tx.amount.currency)).then(|| {
+                format!(
+                    "transaction {} has unknown currency '{}'",
+                    tx.id, tx.amount.currency
+                )
+            })
+        });
+    }
+}
+
+/// Amounts need to be zero or positive, since the kind gives the direction.
+/// Transfers and reversals, whose amounts carry the direction, are exempt.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonNegativeAmount;
+
+impl ValidationRule for NonNegativeAmount {
+    fn name(&self) -> &'static str {
+        "non_negative_amount"
+    }
+
+    fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>) {
+        check_each(self.name(), Severity::Error, batch, issues, |tx| {
+            (tx.kind.sign().is_some() && tx.amount.amount < Decimal::ZERO).then(|| {
+                format!(
+                    "transaction {} has a negative amount {} for a {}",
+                    tx.id,
+                    tx.amount,
+                    tx.kind.to_string().to_lowercase()
+                )
+            })
+        });
+    }
+}
+
+/// A transaction is executed before or on the day it is posted, so an
+/// execution time after the posting date is suspicious.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExecutedBeforePosted;
+
+impl ValidationRule for ExecutedBeforePosted {
+    fn name(&self) -> &'static str {
+        "executed_before_posted"
+    }
+
+    fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>) {
+        check_each(self.name(), Severity::Warning, batch, issues, |tx| {
+            let executed = tx.executed_at?.date();
+            (executed > tx.posted_at).then(|| {
+                format!(
+                    "transaction {} was executed on {}, after it was posted on {}",
+                    tx.id, executed, tx.posted_at
+                )
+            })
+        });
+    }
+}
+
+/// Transaction IDs need to be unique within the batch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UniqueIds;
+
+impl ValidationRule for UniqueIds {
+    fn name(&self) -> &'static str {
+        "unique_ids"
+    }
+
+    fn check(&self, batch: &TransactionBatch, issues: &mut Vec<ValidationIssue>) {
+        let mut first: BTreeMap<&str, usize> = BTreeMap::new();
+        for (index, tx) in batch.transactions.iter().enumerate() {
+            match first.get(tx.id.as_str()) {
+                Some(&earlier) => issues.push(ValidationIssue::at(
+                    self.name(),
+                    Severity::Error,
+                    index,
+                    format!("ID {} repeats record {}", tx.id, earlier + 1),
+                )),
+                None => {
+                    first.insert(&tx.id, index);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Balance, Money, TransactionKind, TransactionStatus};
+    use alloc::vec;
+    use chrono::NaiveDate;
+
+    fn tx(id: &str, kind: TransactionKind, amount: i64, currency: &str) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            executed_at: None,
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::from(amount),
+                currency: currency.to_string(),
+            },
+            description: String::new(),
+            account: None,
+            counterparty: None,
+            category: None,
+            tax_amount: None,
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    fn rules(report: &ValidationReport) -> Vec<(Option<usize>, &'static str, Severity)> {
+        report
+            .issues
+            .iter()
+            .map(|issue| (issue.index, issue.rule, issue.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        let mut late = tx("TX3", TransactionKind::Credit, 5, "EUR");
+        late.executed_at = NaiveDate::from_ymd_opt(2024, 5, 3)
+            .unwrap()
+            .and_hms_opt(8, 0, 0);
+        let batch = TransactionBatch {
+            transactions: vec![
+                tx(" ", TransactionKind::Debit, 10, "EUR"),
+                tx("TX2", TransactionKind::Fee, -1, "PTS"),
+                late,
+                tx("TX2", TransactionKind::Reversal, -10, "USD"),
+            ],
+            balances: vec![Balance {
+                currency: "XYZ".to_string(),
+                opening: Decimal::ZERO,
+                closing: Decimal::ZERO,
+            }],
+            ..TransactionBatch::default()
+        };
+
+        let report = validate(&batch);
+        assert_eq!(
+            rules(&report),
+            vec![
+                (None, "known_currency", Severity::Error),
+                (Some(0), "non_empty_id", Severity::Error),
+                (Some(1), "known_currency", Severity::Error),
+                (Some(1), "non_negative_amount", Severity::Error),
+                (Some(2), "executed_before_posted", Severity::Warning),
+                (Some(3), "unique_ids", Severity::Error),
+            ]
+        );
+        assert_eq!(
+            report.issues[3].to_string(),
+            "error [non_negative_amount]: record 2: transaction TX2 has a negative amount -1 PTS for a fee"
+        );
+        assert_eq!(report.warnings().count(), 1);
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_validator_configuration() {
+        let batch = TransactionBatch {
+            transactions: vec![
+                tx("TX1", TransactionKind::Debit, 10, "PTS"),
+                tx("TX1", TransactionKind::Debit, 10, "EUR"),
+            ],
+            ..TransactionBatch::default()
+        };
+
+        // The configured currency rule replaces the built-in one
+        let report = Validator::default()
+            .rule(KnownCurrency {
+                custom: vec!["PTS".to_string()],
+            })
+            .severity("unique_ids", Severity::Warning)
+            .validate(&batch);
+        assert_eq!(
+            rules(&report),
+            vec![(Some(1), "unique_ids", Severity::Warning)]
+        );
+        assert!(report.is_valid());
+        assert!(Validator::empty().validate(&batch).issues.is_empty());
+    }
+}
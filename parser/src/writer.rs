@@ -0,0 +1,442 @@
+//! Writing transactions one at a time.
+//!
+//! [`crate::write`] needs the whole [`TransactionBatch`] in memory.
+//! [`TransactionWriter`] instead takes transactions as they come and writes
+//! each one straight away, so that conversion pipelines run in constant
+//! memory however large the input is.
+
+use crate::formats::binary::BinaryWriter;
+use crate::formats::{csv, json, text, xml, yaml};
+use crate::{
+    Balance, BatchMetadata, Error, Format, Result, Transaction, TransactionBatch, WriteOptions,
+};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Writes a batch in any format one transaction at a time.
+///
+/// [`TransactionWriter::begin`] writes what precedes the transactions,
+/// [`TransactionWriter::write`] writes each transaction as it comes, and
+/// [`TransactionWriter::finish`] ends the output, which is incomplete without
+/// it. The output is the same as [`crate::write_with`] would produce for the
+/// whole batch, except that CSV output only has columns for the
+/// [`Transaction::extra`] fields declared with
+/// [`TransactionWriter::extra_columns`], since the batch's fields aren't known
+/// until the end.
+///
+/// CSV, text, JSON, XML, YAML and binary output is written as it comes.
+/// Parquet, Protobuf, SQLite and Excel files can only be written as a whole,
+/// so for them the transactions are collected and written by `finish`.
+///
+/// # Examples
+///
+/// ```
+/// use parser::{Format, TransactionWriter};
+///
+/// # fn main() -> Result<(), parser::Error> {
+/// let mut writer = TransactionWriter::begin(Vec::new(), Format::Xml, Some("ACC123"))?;
+/// // for tx in source { writer.write(&tx)?; }
+/// let output = writer.finish()?;
+/// assert!(String::from_utf8_lossy(&output).contains("<AccountId>ACC123</AccountId>"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct TransactionWriter<W: Write> {
+    output: Output<W>,
+    format: Format,
+    options: WriteOptions,
+    /// Names of the CSV columns for [`Transaction::extra`] fields
+    extra: BTreeSet<String>,
+    /// The batch metadata, written before the CSV header
+    metadata: BatchMetadata,
+    /// Number of transactions written so far
+    count: usize,
+}
+
+enum Output<W: Write> {
+    /// Output of a format written as it comes
+    Direct(W),
+    Binary(BinaryWriter<W>),
+    /// Output of a format written by `finish`, with the batch to write
+    Buffered(W, TransactionBatch),
+}
+
+impl<W: Write> TransactionWriter<W> {
+    /// Starts writing a batch with default options.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A writer to output the batch to
+    /// * `format` - The output format
+    /// * `account_id` - The batch's account, if any
+    ///
+    /// # Returns
+    ///
+    /// Returns the writer, or an [`Error`] if writing fails.
+    pub fn begin(writer: W, format: Format, account_id: Option<&str>) -> Result<Self> {
+        Self::begin_with(writer, format, account_id, &[], &WriteOptions::default())
+    }
+
+    /// Starts writing a batch with declared balances and the given options.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A writer to output the batch to
+    /// * `format` - The output format
+    /// * `account_id` - The batch's account, if any
+    /// * `balances` - The batch's declared balances
+    /// * `options` - How to write the batch
+    ///
+    /// # Returns
+    ///
+    /// Returns the writer, or an [`Error`] if writing fails or the options
+    /// need the whole batch: [`WriteOptions::canonical`], which sorts it, and
+    /// the binary options that [`BinaryWriter::create_with`] rejects.
+    pub fn begin_with(
+        writer: W,
+        format: Format,
+        account_id: Option<&str>,
+        balances: &[Balance],
+        options: &WriteOptions,
+    ) -> Result<Self> {
+        let header = TransactionBatch {
+            account_id: account_id.map(str::to_string),
+            transactions: Vec::new(),
+            balances: balances.to_vec(),
+            metadata: BatchMetadata::default(),
+        };
+        Self::begin_from(writer, format, &header, options)
+    }
+
+    /// Starts writing a batch with the account, balances and metadata of
+    /// `header`, and the given options. The transactions of `header` are
+    /// not written.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - A writer to output the batch to
+    /// * `format` - The output format
+    /// * `header` - The batch whose header to write
+    /// * `options` - How to write the batch
+    ///
+    /// # Returns
+    ///
+    /// Returns the writer, or an [`Error`] in the same cases as
+    /// [`TransactionWriter::begin_with`].
+    pub fn begin_from(
+        mut writer: W,
+        format: Format,
+        header: &TransactionBatch,
+        options: &WriteOptions,
+    ) -> Result<Self> {
+        let header = TransactionBatch {
+            account_id: header.account_id.clone(),
+            transactions: Vec::new(),
+            balances: header.balances.clone(),
+            metadata: header.metadata.clone(),
+        };
+        let streamed = matches!(
+            format,
+            Format::Csv | Format::Text | Format::Json | Format::Xml | Format::Yaml | Format::Binary
+        );
+        if !streamed {
+            return Ok(Self::new(Output::Buffered(writer, header), format, options));
+        }
+        if options.canonical {
+            return Err(Error::UnsupportedFormat(
+                "canonical output can't be written one transaction at a time".to_string(),
+            ));
+        }
+
+        let header = options.prepare(&header);
+        let (account_id, balances) = (header.account_id.as_deref(), &header.balances[..]);
+        let metadata = &header.metadata;
+        if format == Format::Binary {
+            let binary = BinaryWriter::create_from(writer, &header, options)?;
+            return Ok(Self::new(Output::Binary(binary), format, options));
+        }
+
+        // The CSV header waits for the extra columns
+        if format != Format::Csv {
+            crate::write_preamble(&mut writer, format, options)?;
+        }
+        match format {
+            Format::Csv => {}
+            Format::Text => {
+                text::write_text_header(&mut writer, account_id, balances, metadata, options)?
+            }
+            Format::Json => json::write_json_header(&mut writer, account_id, balances, metadata)?,
+            Format::Xml => xml::write_xml_header(&mut writer, account_id, balances, metadata)?,
+            Format::Yaml => yaml::write_yaml_header(&mut writer, account_id, balances, metadata)?,
+            _ => unreachable!("{} is written as a whole", format),
+        }
+        let mut streamed = Self::new(Output::Direct(writer), format, options);
+        streamed.metadata = header.metadata.clone();
+        Ok(streamed)
+    }
+
+    fn new(output: Output<W>, format: Format, options: &WriteOptions) -> Self {
+        Self {
+            output,
+            format,
+            options: options.clone(),
+            extra: BTreeSet::new(),
+            metadata: BatchMetadata::default(),
+            count: 0,
+        }
+    }
+
+    /// Declares the [`Transaction::extra`] fields that CSV output has
+    /// columns for. Other formats write every field and ignore this, as does
+    /// CSV output with [`WriteOptions::fields`] set.
+    ///
+    /// Must be called before the first transaction is written.
+    ///
+    /// # Arguments
+    ///
+    /// * `names` - The names of the fields
+    pub fn extra_columns<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        if self.options.fields.is_none() {
+            self.extra = names.into_iter().map(Into::into).collect();
+        }
+        self
+    }
+
+    /// Writes the CSV preamble and header before the first transaction.
+    fn start_csv(&mut self) -> Result<()> {
+        if let Output::Direct(writer) = &mut self.output {
+            if self.format == Format::Csv && self.count == 0 {
+                crate::write_preamble(writer, Format::Csv, &self.options)?;
+                csv::write_metadata_comments(writer, &self.metadata)?;
+                csv::write_csv_header(writer, &self.extra, &self.options)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the next transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - The transaction to write
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an [`Error`] if writing fails.
+    pub fn write(&mut self, tx: &Transaction) -> Result<()> {
+        let mut tx = Cow::Borrowed(tx);
+        if self.options.decimal_places.is_some() {
+            self.options.round_transaction(tx.to_mut());
+        }
+        self.start_csv()?;
+        let options = &self.options;
+        let first = self.count == 0;
+        match &mut self.output {
+            Output::Direct(writer) => match self.format {
+                Format::Csv => csv::write_csv_record(writer, &tx, &self.extra, options)?,
+                Format::Text => text::write_text_record(writer, &tx, first, options)?,
+                Format::Json => json::write_json_transaction(writer, &tx, first)?,
+                Format::Xml => xml::write_xml_transaction(writer, &tx)?,
+                Format::Yaml => yaml::write_yaml_transaction(writer, &tx)?,
+                _ => unreachable!("{} is written as a whole", self.format),
+            },
+            Output::Binary(writer) => writer.write_tx(&tx)?,
+            // The options apply when the batch is written
+            Output::Buffered(_, batch) => batch.transactions.push(tx.into_owned()),
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Ends the output.
+    ///
+    /// # Returns
+    ///
+    /// Returns the underlying writer, or an [`Error`] if writing fails.
+    pub fn finish(mut self) -> Result<W> {
+        self.start_csv()?;
+        match self.output {
+            Output::Direct(mut writer) => {
+                match self.format {
+                    Format::Json => json::write_json_footer(&mut writer, self.count)?,
+                    Format::Xml => xml::write_xml_footer(&mut writer)?,
+                    Format::Yaml => yaml::write_yaml_footer(&mut writer, self.count)?,
+                    _ => {}
+                }
+                writer.flush()?;
+                Ok(writer)
+            }
+            Output::Binary(writer) => writer.finish(),
+            Output::Buffered(mut writer, batch) => {
+                crate::write_with(&batch, &mut writer, self.format, &self.options)?;
+                writer.flush()?;
+                Ok(writer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Money, ParseOptions, TransactionKind, TransactionStatus};
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+
+    fn sample_batch() -> TransactionBatch {
+        let transaction = |id: &str, kind, cents, counterparty: Option<&str>| Transaction {
+            id: id.to_string(),
+            posted_at: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+            executed_at: NaiveDate::from_ymd_opt(2024, 1, 15)
+                .unwrap()
+                .and_hms_opt(10, 30, 0),
+            kind,
+            status: TransactionStatus::Booked,
+            amount: Money {
+                amount: Decimal::new(cents, 2),
+                currency: "USD".to_string(),
+            },
+            description: "Payment: \"monthly\"".to_string(),
+            account: Some("ACC123456".to_string()),
+            counterparty: counterparty.map(str::to_string),
+            category: None,
+            tax_amount: Some(Decimal::new(2005, 3)),
+            tax_rate: None,
+            extra: BTreeMap::new(),
+        };
+        TransactionBatch {
+            account_id: Some("ACC123456".to_string()),
+            transactions: vec![
+                transaction("TX1", TransactionKind::Credit, 500000, Some("Employer")),
+                transaction("TX2", TransactionKind::Debit, 15050, None),
+            ],
+            balances: vec![Balance {
+                currency: "USD".to_string(),
+                opening: Decimal::new(100000, 2),
+                closing: Decimal::new(584950, 2),
+            }],
+            metadata: BatchMetadata {
+                generated_at: NaiveDate::from_ymd_opt(2024, 2, 1)
+                    .unwrap()
+                    .and_hms_opt(6, 0, 0),
+                source_system: Some("Core \"Banking\"".to_string()),
+                period: Some("2024-01-01..2024-01-31".parse().unwrap()),
+                schema_version: Some("2.1".to_string()),
+            },
+        }
+    }
+
+    fn stream(batch: &TransactionBatch, format: Format, options: &WriteOptions) -> Vec<u8> {
+        let mut writer = TransactionWriter::begin_from(Vec::new(), format, batch, options).unwrap();
+        for tx in &batch.transactions {
+            writer.write(tx).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_matches_batch_output() {
+        let options = WriteOptions {
+            decimal_places: Some(2),
+            bom: true,
+            ..WriteOptions::default()
+        };
+        let full = sample_batch();
+        let mut empty = full.clone();
+        empty.transactions.clear();
+        let mut bare = empty.clone();
+        bare.account_id = None;
+        bare.balances.clear();
+        bare.metadata = BatchMetadata::default();
+
+        for format in [
+            Format::Csv,
+            Format::Text,
+            Format::Json,
+            Format::Xml,
+            Format::Yaml,
+            Format::Binary,
+        ] {
+            for batch in [&full, &empty, &bare] {
+                for options in [&WriteOptions::default(), &options] {
+                    let mut expected = Vec::new();
+                    crate::write_with(batch, &mut expected, format, options).unwrap();
+                    assert_eq!(
+                        String::from_utf8_lossy(&stream(batch, format, options)),
+                        String::from_utf8_lossy(&expected),
+                        "{} output differs",
+                        format
+                    );
+                }
+            }
+
+            let mut output = Vec::new();
+            crate::write(&full, &mut output, format).unwrap();
+            let parsed = crate::parse(&output[..], format).unwrap();
+            assert_eq!(
+                parsed.metadata, full.metadata,
+                "{} metadata differs",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let input = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,\
+                     Description,Account,Counterparty,Category,Branch\n\
+                     TX1,2024-01-15,,Credit,5000.00,USD,Salary,ACC1,Employer,,Main\n\
+                     \n\
+                     TX2,2024-01-16,,Debit,150.50,USD,Groceries,ACC1,,Food,North\n";
+        let batch = crate::parse(input.as_bytes(), Format::Csv).unwrap();
+        for format in [Format::Csv, Format::Json, Format::Yaml, Format::Binary] {
+            let mut expected = Vec::new();
+            crate::write(&batch, &mut expected, format).unwrap();
+            let mut output = Vec::new();
+            let count = crate::convert(input.as_bytes(), Format::Csv, &mut output, format);
+            assert_eq!(count.unwrap(), 2);
+            assert_eq!(output, expected, "{} output differs", format);
+        }
+
+        let options = ParseOptions {
+            max_records: Some(1),
+            ..ParseOptions::default()
+        };
+        let mut output = Vec::new();
+        let count = crate::convert_with(
+            input.as_bytes(),
+            Format::Csv,
+            &mut output,
+            Format::Csv,
+            &options,
+            &WriteOptions::default(),
+        );
+        assert_eq!(count.unwrap(), 1);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().next().unwrap().ends_with(",Branch"));
+        assert!(!output.contains("TX2"));
+    }
+
+    #[test]
+    fn test_unsupported_options() {
+        let options = WriteOptions {
+            canonical: true,
+            ..WriteOptions::default()
+        };
+        let result = TransactionWriter::begin_with(Vec::new(), Format::Csv, None, &[], &options);
+        assert!(matches!(result, Err(Error::UnsupportedFormat(_))));
+
+        let options = WriteOptions {
+            index: true,
+            ..WriteOptions::default()
+        };
+        let result = TransactionWriter::begin_with(Vec::new(), Format::Binary, None, &[], &options);
+        assert!(matches!(result, Err(Error::UnsupportedFormat(_))));
+    }
+}
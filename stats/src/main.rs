@@ -0,0 +1,193 @@
+#[cfg(any(feature = "charts", feature = "query"))]
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser as ClapParser;
+use cli_common::Outcome;
+#[cfg(feature = "charts")]
+use parser::charts::{render_chart, ChartKind};
+use parser::fees::{charge_totals, ChargeRules};
+use parser::filter::Filter;
+use parser::manifest::currency_totals;
+use parser::stats::{category_totals, monthly_totals};
+use parser::{Format, TransactionBatch};
+#[cfg(feature = "charts")]
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(ClapParser)]
+#[command(name = "ypbank_stats")]
+#[command(about = "Summarize YPBank transaction files")]
+struct Args {
+    #[arg(short, long, help = "Input file path (use '-' for stdin)")]
+    input: String,
+
+    #[arg(long, help = "Input format")]
+    input_format: Format,
+
+    #[arg(
+        long,
+        help = "Only report on this currency (default: all; charts use the first)"
+    )]
+    currency: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "EXPR",
+        help = "Only report on transactions matching EXPR, e.g. 'kind == \"debit\"'"
+    )]
+    filter: Option<Filter>,
+
+    #[arg(long, default_value_t = 5, help = "Number of categories to list")]
+    top: usize,
+
+    #[arg(
+        long = "max-gap",
+        value_name = "DAYS",
+        help = "List runs of more than DAYS business days without transactions"
+    )]
+    max_gap: Option<u32>,
+
+    #[cfg(feature = "query")]
+    #[arg(
+        long,
+        value_name = "QUERY",
+        help = "Print the result of an SQL query over the 'transactions' table instead of the summary"
+    )]
+    sql: Option<String>,
+
+    #[cfg(feature = "charts")]
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Render a chart to PATH (.svg or .png)"
+    )]
+    chart: Option<PathBuf>,
+
+    #[cfg(feature = "charts")]
+    #[arg(
+        long,
+        value_enum,
+        default_value = "monthly-spend",
+        help = "Kind of chart to render"
+    )]
+    chart_type: ChartKind,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    cli_common::run("ypbank_stats", || {
+        report(&args)?;
+        Ok(Outcome::Success)
+    })
+}
+
+fn report(args: &Args) -> Result<()> {
+    let mut batch = cli_common::read_batch(&args.input, args.input_format)?;
+    if let Some(filter) = &args.filter {
+        batch = batch.filter(filter);
+    }
+
+    #[cfg(feature = "query")]
+    if let Some(sql) = &args.sql {
+        let result = parser::query::query_sql(&batch, sql).context("Query failed")?;
+        println!("{}", result.columns.join("\t"));
+        for row in &result.rows {
+            let values: Vec<String> = row.iter().map(ToString::to_string).collect();
+            println!("{}", values.join("\t"));
+        }
+        return Ok(());
+    }
+
+    let currencies: Vec<String> = match &args.currency {
+        Some(currency) => vec![currency.clone()],
+        None => currency_totals(&batch)
+            .into_iter()
+            .map(|total| total.currency)
+            .collect(),
+    };
+
+    print_summary(&batch, &currencies, args.top);
+
+    if let Some(max_gap) = args.max_gap {
+        let gaps = batch.gaps(max_gap);
+        if !gaps.is_empty() {
+            println!("\nGaps:");
+            for gap in gaps {
+                println!("  {}  business days: {}", gap.period, gap.business_days);
+            }
+        }
+    }
+
+    #[cfg(feature = "charts")]
+    if let Some(path) = &args.chart {
+        let currency = currencies.first().context("No transactions to chart")?;
+        render_chart(&batch, args.chart_type, currency, path)
+            .with_context(|| format!("Failed to render chart: {}", path.display()))?;
+        eprintln!("Wrote {} chart to {}", args.chart_type, path.display());
+    }
+
+    Ok(())
+}
+
+fn print_summary(batch: &TransactionBatch, currencies: &[String], top: usize) {
+    let summary = batch.summary();
+
+    println!("Transactions: {}", summary.count);
+    if let (Some(first), Some(last)) = (summary.first_date, summary.last_date) {
+        println!("Period: {} to {}", first, last);
+    }
+
+    for currency in currencies {
+        let Some(total) = summary
+            .currencies
+            .iter()
+            .find(|total| &total.currency == currency)
+        else {
+            println!("\n{}: no transactions", currency);
+            continue;
+        };
+
+        println!("\n{}", currency);
+        println!("  Credits: {}", total.credits);
+        println!("  Debits:  {}", total.debits);
+        println!("  Net:     {}", total.net);
+        if let Some(tx) = &total.largest_debit {
+            println!("  Largest debit: {} ({})", tx.amount.amount, tx.id);
+        }
+
+        // Gross excludes bank charges: what the net would be without fees
+        // and interest.
+        if let Some(charges) = charge_totals(batch, &ChargeRules::default())
+            .into_iter()
+            .find(|charges| &charges.currency == currency)
+        {
+            println!("  Fees:    {}", charges.fees);
+            println!("  Interest: {}", charges.interest);
+            println!(
+                "  Gross:   {}",
+                total.credits - total.debits + charges.fees - charges.interest
+            );
+        }
+
+        println!("  Monthly spend:");
+        for month in monthly_totals(batch)
+            .iter()
+            .filter(|month| &month.currency == currency)
+        {
+            println!("    {}-{:02}  {}", month.year, month.month, month.debits);
+        }
+
+        let mut categories: Vec<_> = category_totals(batch)
+            .into_iter()
+            .filter(|category| &category.currency == currency && !category.debits.is_zero())
+            .collect();
+        categories.sort_by_key(|category| std::cmp::Reverse(category.debits));
+
+        if !categories.is_empty() {
+            println!("  Top categories:");
+            for category in categories.iter().take(top) {
+                println!("    {:<20}  {}", category.category, category.debits);
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+use clap::{Parser as ClapParser, Subcommand};
+use cli_common::Outcome;
+use parser::manifest::{Manifest, ManifestEntry};
+use parser::Format;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::process::ExitCode;
+
+#[derive(ClapParser)]
+#[command(name = "ypbank_sum")]
+#[command(about = "Create and verify integrity manifests for YPBank transaction files")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print a manifest for the given files
+    Create {
+        #[arg(
+            long,
+            help = "Format of all files (default: guessed from the extension)"
+        )]
+        format: Option<Format>,
+
+        #[arg(required = true, help = "Files to include in the manifest")]
+        files: Vec<String>,
+    },
+    /// Verify files against a manifest
+    Check {
+        #[arg(help = "Manifest file to verify")]
+        manifest: String,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    cli_common::run("ypbank_sum", || match &args.command {
+        Command::Create { format, files } => create(*format, files),
+        Command::Check { manifest } => check(manifest),
+    })
+}
+
+fn create(format: Option<Format>, files: &[String]) -> Result<Outcome> {
+    let mut manifest = Manifest::default();
+
+    for path in files {
+        let format = cli_common::resolve_format(path, format)?;
+        let batch = cli_common::read_batch(path, format)?;
+        manifest
+            .entries
+            .push(ManifestEntry::from_batch(path.as_str(), format, &batch));
+    }
+
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    manifest
+        .write(&mut writer)
+        .context("Failed to write manifest")?;
+
+    Ok(Outcome::Success)
+}
+
+fn check(manifest_path: &str) -> Result<Outcome> {
+    let file = File::open(manifest_path)
+        .with_context(|| format!("Failed to open manifest: {}", manifest_path))?;
+    let manifest = Manifest::read(file)
+        .with_context(|| format!("Failed to parse manifest: {}", manifest_path))?;
+
+    let mut failures = 0;
+
+    for entry in &manifest.entries {
+        let problems = match parser::parse_path(&entry.path, entry.format) {
+            Ok(batch) => entry.verify(&batch),
+            Err(e) => vec![e.to_string()],
+        };
+
+        if problems.is_empty() {
+            println!("{}: OK", entry.path);
+        } else {
+            failures += 1;
+            println!("{}: FAILED ({})", entry.path, problems.join("; "));
+        }
+    }
+
+    if failures > 0 {
+        eprintln!(
+            "ypbank_sum: {} of {} files did not match",
+            failures,
+            manifest.entries.len()
+        );
+        return Ok(Outcome::Failure);
+    }
+
+    Ok(Outcome::Success)
+}
@@ -0,0 +1,390 @@
+//! WebAssembly bindings for the parser library.
+//!
+//! Build with `wasm-pack build wasm --target web` (or `--target bundler`) to
+//! get an npm-ready package. Batches cross the boundary as plain JS objects:
+//!
+//! ```text
+//! {
+//!   accountId: string | undefined,
+//!   metadata: { generatedAt?, sourceSystem?, period?, schemaVersion? },
+//!   balances: [{ currency, opening, closing }],
+//!   transactions: [{
+//!     id, postedAt, executedAt?, kind, status?, amount, currency,
+//!     description, account?, counterparty?, category?, taxAmount?, taxRate?,
+//!     extra?: { [column]: string }
+//!   }]
+//! }
+//! ```
+//!
+//! Dates use the same `YYYY-MM-DD` / `YYYY-MM-DD HH:MM:SS` layout as the text
+//! and CSV formats, and amounts are decimal strings so no precision is lost in
+//! JavaScript numbers. `status` is `Booked`, `Pending` or `Cancelled`, and
+//! `Booked` when left out. A period is written `YYYY-MM-DD..YYYY-MM-DD`;
+//! `metadata` and `balances` may be left out when there are none. Formats are given by name (`csv`, `text`/`txt`,
+//! `binary`/`bin`, `json`, `xml`, `yaml`/`yml`).
+
+use chrono::{NaiveDate, NaiveDateTime};
+use js_sys::{Array, Object, Reflect};
+use parser::{
+    Balance, BatchMetadata, Format, Money, Period, Transaction, TransactionBatch, TransactionKind,
+    TransactionStatus,
+};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// Parses a statement file and returns the batch as a JS object.
+#[wasm_bindgen(js_name = parseTransactions)]
+pub fn parse_transactions(data: &[u8], format: &str) -> Result<JsValue, JsError> {
+    let format = Format::from_str(format)?;
+    let batch = parser::parse(Cursor::new(data), format)?;
+    batch_to_js(&batch)
+}
+
+/// Serializes a JS batch object into the requested format.
+#[wasm_bindgen(js_name = writeTransactions)]
+pub fn write_transactions(batch: JsValue, format: &str) -> Result<Vec<u8>, JsError> {
+    let format = Format::from_str(format)?;
+    let batch = batch_from_js(&batch)?;
+
+    let mut output = Vec::new();
+    parser::write(&batch, &mut output, format)?;
+    Ok(output)
+}
+
+/// Checks whether a statement file parses, returning the error message if not.
+#[wasm_bindgen(js_name = validateTransactions)]
+pub fn validate_transactions(data: &[u8], format: &str) -> Option<String> {
+    let format = match Format::from_str(format) {
+        Ok(format) => format,
+        Err(e) => return Some(parser::Error::from(e).to_string()),
+    };
+
+    parser::parse(Cursor::new(data), format)
+        .err()
+        .map(|e| e.to_string())
+}
+
+fn batch_to_js(batch: &TransactionBatch) -> Result<JsValue, JsError> {
+    let obj = Object::new();
+    set(&obj, "accountId", optional(batch.account_id.as_deref()))?;
+    set(&obj, "metadata", metadata_to_js(&batch.metadata)?)?;
+
+    let balances = Array::new();
+    for balance in &batch.balances {
+        balances.push(&balance_to_js(balance)?);
+    }
+    set(&obj, "balances", balances.into())?;
+
+    let transactions = Array::new();
+    for tx in &batch.transactions {
+        transactions.push(&transaction_to_js(tx)?);
+    }
+    set(&obj, "transactions", transactions.into())?;
+
+    Ok(obj.into())
+}
+
+fn metadata_to_js(metadata: &BatchMetadata) -> Result<JsValue, JsError> {
+    let obj = Object::new();
+    let generated_at = metadata
+        .generated_at
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
+    set(&obj, "generatedAt", optional(generated_at.as_deref()))?;
+    set(
+        &obj,
+        "sourceSystem",
+        optional(metadata.source_system.as_deref()),
+    )?;
+    let period = metadata.period.map(|period| period.to_string());
+    set(&obj, "period", optional(period.as_deref()))?;
+    set(
+        &obj,
+        "schemaVersion",
+        optional(metadata.schema_version.as_deref()),
+    )?;
+    Ok(obj.into())
+}
+
+fn balance_to_js(balance: &Balance) -> Result<JsValue, JsError> {
+    let obj = Object::new();
+    set(&obj, "currency", JsValue::from_str(&balance.currency))?;
+    set(
+        &obj,
+        "opening",
+        JsValue::from_str(&balance.opening.to_string()),
+    )?;
+    set(
+        &obj,
+        "closing",
+        JsValue::from_str(&balance.closing.to_string()),
+    )?;
+    Ok(obj.into())
+}
+
+fn transaction_to_js(tx: &Transaction) -> Result<JsValue, JsError> {
+    let obj = Object::new();
+    set(&obj, "id", JsValue::from_str(&tx.id))?;
+    set(
+        &obj,
+        "postedAt",
+        JsValue::from_str(&tx.posted_at.format("%Y-%m-%d").to_string()),
+    )?;
+    let executed_at = tx
+        .executed_at
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
+    set(&obj, "executedAt", optional(executed_at.as_deref()))?;
+    set(&obj, "kind", JsValue::from_str(&tx.kind.to_string()))?;
+    set(&obj, "status", JsValue::from_str(&tx.status.to_string()))?;
+    set(
+        &obj,
+        "amount",
+        JsValue::from_str(&tx.amount.amount.to_string()),
+    )?;
+    set(&obj, "currency", JsValue::from_str(&tx.amount.currency))?;
+    set(&obj, "description", JsValue::from_str(&tx.description))?;
+    set(&obj, "account", optional(tx.account.as_deref()))?;
+    set(&obj, "counterparty", optional(tx.counterparty.as_deref()))?;
+    set(&obj, "category", optional(tx.category.as_deref()))?;
+    let tax_amount = tx.tax_amount.map(|v| v.to_string());
+    set(&obj, "taxAmount", optional(tax_amount.as_deref()))?;
+    let tax_rate = tx.tax_rate.map(|v| v.to_string());
+    set(&obj, "taxRate", optional(tax_rate.as_deref()))?;
+
+    let extra = Object::new();
+    for (key, value) in &tx.extra {
+        set(&extra, key, JsValue::from_str(value))?;
+    }
+    set(&obj, "extra", extra.into())?;
+    Ok(obj.into())
+}
+
+fn batch_from_js(value: &JsValue) -> Result<TransactionBatch, JsError> {
+    let account_id = get_optional_string(value, "accountId")?;
+
+    let metadata = Reflect::get(value, &"metadata".into()).map_err(js_error)?;
+    let metadata = if metadata.is_undefined() || metadata.is_null() {
+        BatchMetadata::default()
+    } else {
+        metadata_from_js(&metadata)?
+    };
+
+    let balances = Reflect::get(value, &"balances".into()).map_err(js_error)?;
+    let balances = if balances.is_undefined() || balances.is_null() {
+        Vec::new()
+    } else if Array::is_array(&balances) {
+        Array::from(&balances)
+            .iter()
+            .map(|balance| balance_from_js(&balance))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        return Err(JsError::new("`balances` must be an array"));
+    };
+
+    let transactions = Reflect::get(value, &"transactions".into()).map_err(js_error)?;
+    if !Array::is_array(&transactions) {
+        return Err(JsError::new("`transactions` must be an array"));
+    }
+
+    let transactions = Array::from(&transactions)
+        .iter()
+        .map(|tx| transaction_from_js(&tx))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(TransactionBatch {
+        account_id,
+        transactions,
+        balances,
+        metadata,
+    })
+}
+
+fn metadata_from_js(value: &JsValue) -> Result<BatchMetadata, JsError> {
+    let generated_at = get_optional_string(value, "generatedAt")?
+        .map(|s| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+        .transpose()
+        .map_err(|e| JsError::new(&format!("invalid generatedAt: {}", e)))?;
+
+    let period = get_optional_string(value, "period")?
+        .map(|s| Period::from_str(&s))
+        .transpose()?;
+
+    Ok(BatchMetadata {
+        generated_at,
+        source_system: get_optional_string(value, "sourceSystem")?,
+        period,
+        schema_version: get_optional_string(value, "schemaVersion")?,
+    })
+}
+
+fn balance_from_js(value: &JsValue) -> Result<Balance, JsError> {
+    let decimal = |key: &str| {
+        Decimal::from_str(&get_string(value, key)?)
+            .map_err(|e| JsError::new(&format!("invalid {}: {}", key, e)))
+    };
+
+    Ok(Balance {
+        currency: get_string(value, "currency")?,
+        opening: decimal("opening")?,
+        closing: decimal("closing")?,
+    })
+}
+
+fn transaction_from_js(value: &JsValue) -> Result<Transaction, JsError> {
+    let posted_at = NaiveDate::parse_from_str(&get_string(value, "postedAt")?, "%Y-%m-%d")
+        .map_err(|e| JsError::new(&format!("invalid postedAt: {}", e)))?;
+
+    let executed_at = get_optional_string(value, "executedAt")?
+        .map(|s| NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S"))
+        .transpose()
+        .map_err(|e| JsError::new(&format!("invalid executedAt: {}", e)))?;
+
+    let kind = TransactionKind::from_str(&get_string(value, "kind")?)
+        .map_err(|e| JsError::new(&format!("invalid kind: {}", e)))?;
+
+    let status = get_optional_string(value, "status")?
+        .map(|s| TransactionStatus::from_str(&s))
+        .transpose()
+        .map_err(|e| JsError::new(&format!("invalid status: {}", e)))?
+        .unwrap_or_default();
+
+    let amount = Decimal::from_str(&get_string(value, "amount")?)
+        .map_err(|e| JsError::new(&format!("invalid amount: {}", e)))?;
+
+    Ok(Transaction {
+        id: get_string(value, "id")?,
+        posted_at,
+        executed_at,
+        kind,
+        status,
+        amount: Money {
+            amount,
+            currency: get_string(value, "currency")?,
+        },
+        description: get_optional_string(value, "description")?.unwrap_or_default(),
+        account: get_optional_string(value, "account")?,
+        counterparty: get_optional_string(value, "counterparty")?,
+        category: get_optional_string(value, "category")?,
+        tax_amount: get_optional_decimal(value, "taxAmount")?,
+        tax_rate: get_optional_decimal(value, "taxRate")?,
+        extra: extra_from_js(value)?,
+    })
+}
+
+fn extra_from_js(value: &JsValue) -> Result<BTreeMap<String, String>, JsError> {
+    let extra = Reflect::get(value, &"extra".into()).map_err(js_error)?;
+    if extra.is_undefined() || extra.is_null() {
+        return Ok(BTreeMap::new());
+    }
+    if !extra.is_object() {
+        return Err(JsError::new("`extra` must be an object"));
+    }
+
+    Object::keys(&Object::from(extra.clone()))
+        .iter()
+        .map(|key| {
+            let key = key.as_string().unwrap_or_default();
+            let value = get_string(&extra, &key)
+                .map_err(|_| JsError::new(&format!("extra field `{}` must be a string", key)))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+fn set(obj: &Object, key: &str, value: JsValue) -> Result<(), JsError> {
+    Reflect::set(obj, &key.into(), &value).map_err(js_error)?;
+    Ok(())
+}
+
+fn optional(value: Option<&str>) -> JsValue {
+    value.map(JsValue::from_str).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn get_string(obj: &JsValue, key: &str) -> Result<String, JsError> {
+    get_optional_string(obj, key)?.ok_or_else(|| JsError::new(&format!("missing field `{}`", key)))
+}
+
+fn get_optional_decimal(obj: &JsValue, key: &str) -> Result<Option<Decimal>, JsError> {
+    get_optional_string(obj, key)?
+        .map(|s| Decimal::from_str(&s))
+        .transpose()
+        .map_err(|e| JsError::new(&format!("invalid {}: {}", key, e)))
+}
+
+fn get_optional_string(obj: &JsValue, key: &str) -> Result<Option<String>, JsError> {
+    let value = Reflect::get(obj, &key.into()).map_err(js_error)?;
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    value
+        .as_string()
+        .map(Some)
+        .ok_or_else(|| JsError::new(&format!("field `{}` must be a string", key)))
+}
+
+fn js_error(value: JsValue) -> JsError {
+    JsError::new(&value.as_string().unwrap_or_else(|| format!("{:?}", value)))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    const CSV: &str = "TransactionId,PostedDate,ExecutedDate,Type,Amount,Currency,Description,Account,Counterparty,Category\n\
+                       TX001,2024-01-15,2024-01-15 10:30:00,Credit,1000.50,USD,Salary payment,ACC123,Employer Inc,Salary\n\
+                       TX002,2024-01-16,,Debit,150.50,USD,Groceries,ACC123,Store,Food\n";
+
+    fn batch() -> TransactionBatch {
+        let mut batch = parser::parse(Cursor::new(CSV), Format::Csv).unwrap();
+        batch.account_id = Some("ACC123".to_string());
+        batch.transactions[1].status = TransactionStatus::Pending;
+        batch.transactions[1].tax_amount = Some(Decimal::new(2508, 2));
+        batch.transactions[1].tax_rate = Some(Decimal::new(20, 0));
+        batch.transactions[1]
+            .extra
+            .insert("MCC".to_string(), "5411".to_string());
+        batch.balances = vec![Balance {
+            currency: "USD".to_string(),
+            opening: Decimal::new(10000, 2),
+            closing: Decimal::new(95000, 2),
+        }];
+        batch.metadata = BatchMetadata {
+            generated_at: Some(
+                NaiveDateTime::parse_from_str("2024-02-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            ),
+            source_system: Some("core".to_string()),
+            period: Some(Period::from_str("2024-01-01..2024-01-31").unwrap()),
+            schema_version: Some("2".to_string()),
+        };
+        batch
+    }
+
+    #[wasm_bindgen_test]
+    fn test_batch_roundtrip() {
+        let batch = batch();
+        let value = batch_to_js(&batch).unwrap();
+        assert_eq!(batch_from_js(&value).unwrap(), batch);
+
+        let mut json = Vec::new();
+        parser::write(&batch, &mut json, Format::Json).unwrap();
+        let value = parse_transactions(&json, "json").unwrap();
+        assert_eq!(write_transactions(value, "json").unwrap(), json);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_optional_fields() {
+        let value = parse_transactions(CSV.as_bytes(), "csv").unwrap();
+        for key in ["metadata", "balances"] {
+            Reflect::delete_property(&Object::from(value.clone()), &key.into()).unwrap();
+        }
+        let batch = batch_from_js(&value).unwrap();
+        assert_eq!(batch, parser::parse(Cursor::new(CSV), Format::Csv).unwrap());
+
+        assert!(validate_transactions(CSV.as_bytes(), "csv").is_none());
+        let error = validate_transactions(b"not a csv", "csv").unwrap();
+        assert!(error.contains("invalid header"));
+    }
+}